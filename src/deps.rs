@@ -0,0 +1,1449 @@
+//! Чтение файлов установки (с диска, встроенных и по адресу) и порядок установки по зависимостям
+
+use colored::*;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use crate::{output::{deps_depth_value, JsonReader, JsonValue, Lang, Msg}, parser::*, sim::*};
+
+// ============== Менеджер зависимостей ==============
+
+/// Примеры из examples/, встроенные в программу: `--file builtin:python` работает и без
+/// каталога examples рядом. Встроенный файл получает путь builtin:/имя.instnoth, поэтому
+/// его `depends: "python.instnoth"` находит соседний встроенный файл, а не файл на диске
+pub mod builtin {
+    use std::path::{Path, PathBuf};
+
+    const FILES: &[(&str, &str)] = &[
+        ("python", include_str!("../examples/python.instnoth")),
+        ("nodejs", include_str!("../examples/nodejs.instnoth")),
+        ("docker", include_str!("../examples/docker.instnoth")),
+        ("linux", include_str!("../examples/linux.instnoth")),
+        ("all", include_str!("../examples/all.instnoth")),
+        ("devstack", include_str!("../examples/devstack.instnoth")),
+        ("virus", include_str!("../examples/virus.instnoth")),
+        ("virus_deleter", include_str!("../examples/virus_deleter.instnoth")),
+    ];
+
+    const PREFIX: &str = "builtin:";
+
+    /// Имена и содержимое встроенных файлов в порядке --list-builtin
+    pub fn all() -> &'static [(&'static str, &'static str)] {
+        FILES
+    }
+
+    pub fn get(name: &str) -> Option<&'static str> {
+        FILES.iter().find(|(known, _)| *known == name).map(|(_, source)| *source)
+    }
+
+    /// Путь встроенного файла по имени
+    pub fn path(name: &str) -> PathBuf {
+        PathBuf::from(format!("{}/{}.instnoth", PREFIX, name))
+    }
+
+    /// Путь из командной строки: builtin:python (или builtin:python.instnoth) становится
+    /// путём встроенного файла, остальные остаются как есть
+    pub fn resolve(file: &Path) -> PathBuf {
+        match file.to_str().and_then(|f| f.strip_prefix(PREFIX)) {
+            Some(name) => {
+                let name = name.trim_start_matches('/');
+                path(name.strip_suffix(".instnoth").unwrap_or(name))
+            }
+            None => file.to_path_buf(),
+        }
+    }
+
+    pub fn is_builtin(path: &Path) -> bool {
+        path.to_str().is_some_and(|p| p.starts_with(PREFIX))
+    }
+
+    /// Содержимое по пути встроенного файла; None — путь не встроенный или такого файла нет
+    pub fn source(path: &Path) -> Option<&'static str> {
+        let name = path.to_str()?.strip_prefix(PREFIX)?.strip_prefix('/')?.strip_suffix(".instnoth")?;
+        get(name)
+    }
+}
+
+/// Заготовки для `instnoth new --template`: файлы templates/*.instnoth, встроенные в
+/// программу, как и примеры. {{name}}, {{version}} и {{author}} подставляются при создании
+pub mod templates {
+    const TEMPLATES: &[(&str, &str, &str)] = &[
+        ("minimal", "заголовок и две фазы — с чего начать свой файл", include_str!("../templates/minimal.instnoth")),
+        ("webserver", "nginx, сертификат TLS, firewall и запуск сервиса", include_str!("../templates/webserver.instnoth")),
+        ("os-install", "разметка диска, базовая система, настройка и загрузчик", include_str!("../templates/os-install.instnoth")),
+        ("devtools", "системные пакеты, пакеты Python, npm и cargo, ключ SSH", include_str!("../templates/devtools.instnoth")),
+    ];
+
+    /// Имена, описания и содержимое заготовок в порядке --list-templates
+    pub fn all() -> &'static [(&'static str, &'static str, &'static str)] {
+        TEMPLATES
+    }
+
+    pub fn names() -> impl Iterator<Item = &'static str> {
+        TEMPLATES.iter().map(|(name, _, _)| *name)
+    }
+
+    pub fn get(name: &str) -> Option<&'static str> {
+        TEMPLATES.iter().find(|(known, _, _)| *known == name).map(|(_, _, source)| *source)
+    }
+
+    pub fn render(source: &str, name: &str, version: &str, author: &str) -> String {
+        source.replace("{{name}}", name).replace("{{version}}", version).replace("{{author}}", author)
+    }
+}
+
+/// Файлы установки по http(s)-адресам. Загружает их curl, поэтому своей реализации TLS не
+/// нужно. Адрес хранится как путь: родитель https://host/a/demo.instnoth — https://host/a,
+/// и относительные `depends:` удалённого файла становятся адресами соседних файлов
+pub mod remote {
+    use colored::Colorize;
+    use indicatif::{HumanBytes, ProgressBar, ProgressDrawTarget, ProgressStyle};
+    use std::io::{IsTerminal, Read};
+    use std::path::Path;
+    use std::process::{Command, Stdio};
+    use std::sync::{Mutex, OnceLock};
+    use std::time::Duration;
+
+    pub struct Options {
+        /// --insecure: не проверять сертификат
+        pub insecure: bool,
+        pub timeout: Duration,
+    }
+
+    static OPTIONS: OnceLock<Options> = OnceLock::new();
+    /// Загруженные за запуск файлы: зависимость читается несколько раз — для порядка
+    /// установки, --parallel и --show-deps, — а загружается один
+    static FETCHED: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
+
+    /// Настройки из командной строки; до вызова действуют значения по умолчанию
+    pub fn configure(options: Options) {
+        let _ = OPTIONS.set(options);
+    }
+
+    pub fn url(path: &Path) -> Option<&str> {
+        path.to_str().filter(|p| p.starts_with("http://") || p.starts_with("https://"))
+    }
+
+    /// Тело ответа по адресу. Пока идёт загрузка, в терминале stderr — счётчик настоящих
+    /// байт, непохожий на имитируемые полосы установки
+    pub fn fetch(url: &str) -> Result<String, String> {
+        if let Some((_, body)) = FETCHED.lock().unwrap().iter().find(|(known, _)| known == url) {
+            return Ok(body.clone());
+        }
+        let options = OPTIONS.get_or_init(|| Options { insecure: false, timeout: Duration::from_secs(30) });
+        let mut curl = Command::new("curl");
+        curl.args(["--silent", "--show-error", "--fail", "--location", "--max-time"])
+            .arg(options.timeout.as_secs().to_string());
+        if options.insecure {
+            curl.arg("--insecure");
+        }
+        let mut child = curl.arg("--").arg(url)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("не удалось запустить curl (нужен для загрузки по адресу): {}", e))?;
+
+        let bar = if std::io::stderr().is_terminal() {
+            ProgressBar::new_spinner()
+        } else {
+            ProgressBar::with_draw_target(None, ProgressDrawTarget::hidden())
+        };
+        bar.set_style(ProgressStyle::default_spinner()
+            .template("  {spinner:.dim} {msg:.dim} {bytes:.dim} {binary_bytes_per_sec:.dim}").unwrap());
+        bar.set_message(url.to_string());
+        bar.enable_steady_tick(Duration::from_millis(100));
+        let mut body = Vec::new();
+        let mut chunk = [0u8; 8192];
+        let mut stdout = child.stdout.take().unwrap();
+        while let Ok(n @ 1..) = stdout.read(&mut chunk) {
+            body.extend_from_slice(&chunk[..n]);
+            bar.set_position(body.len() as u64);
+        }
+        bar.finish_and_clear();
+
+        let output = child.wait_with_output().map_err(|e| e.to_string())?;
+        if !output.status.success() {
+            let message = String::from_utf8_lossy(&output.stderr).trim().to_string();
+            return Err(if message.is_empty() { format!("curl завершился с кодом {}", output.status) } else { message });
+        }
+        let body = String::from_utf8(body).map_err(|_| "ответ не в UTF-8".to_string())?;
+        if std::io::stderr().is_terminal() {
+            eprintln!("  {} {} ({})", "↓".dimmed(), url, HumanBytes(body.len() as u64));
+        }
+        FETCHED.lock().unwrap().push((url.to_string(), body.clone()));
+        Ok(body)
+    }
+}
+
+/// Содержимое файла установки: встроенного по пути builtin:/имя.instnoth, из stdin для "-",
+/// по http(s)-адресу или с диска
+pub fn read_package_file(path: &Path) -> io::Result<String> {
+    if path == Path::new("-") {
+        return io::read_to_string(io::stdin());
+    }
+    if let Some(url) = remote::url(path) {
+        return remote::fetch(url).map_err(io::Error::other);
+    }
+    if builtin::is_builtin(path) {
+        return builtin::source(path)
+            .map(str::to_string)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "нет такого встроенного файла (см. --list-builtin)"));
+    }
+    fs::read_to_string(path)
+}
+
+/// Есть ли файл установки: на диске, среди встроенных или по http(s)-адресу (его наличие
+/// выяснится только при загрузке)
+pub fn package_file_exists(path: &Path) -> bool {
+    path.exists() || builtin::source(path).is_some() || remote::url(path).is_some()
+}
+
+/// Путь, по которому искали файл, — для сообщений: у файла на диске абсолютный, потому что
+/// относительные пути зависимостей отсчитываются не от текущего каталога
+pub fn attempted_path(path: &Path) -> PathBuf {
+    if builtin::is_builtin(path) || remote::url(path).is_some() {
+        return path.to_path_buf();
+    }
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+// Один и тот же пакет — один и тот же файл на диске (без ./ и ссылок); у встроенного пакета
+// и пакета из stdin — имя и версия
+fn package_key(pkg: &Package) -> String {
+    match &pkg.file_path {
+        Some(path) if !builtin::is_builtin(path) => fs::canonicalize(path).unwrap_or_else(|_| path.clone()).display().to_string(),
+        _ => format!("{} {}", pkg.name, pkg.version),
+    }
+}
+
+fn package_label(pkg: &Package) -> String {
+    pkg.file_path.as_ref().map_or_else(|| pkg.name.clone(), |path| path.display().to_string())
+}
+
+// «a.instnoth → b.instnoth → a.instnoth»: путь от первого вхождения пакета и снова он
+fn cycle_chain(path: &[PathEntry], pkg: &Package) -> String {
+    let mut chain: Vec<String> = path.iter().map(|entry| package_label(&entry.pkg)).collect();
+    chain.push(package_label(pkg));
+    chain.join(" → ")
+}
+
+/// Пакет на пути обхода зависимостей и номер его следующей зависимости
+struct PathEntry {
+    key: String,
+    pkg: Package,
+    next: usize,
+}
+
+impl PathEntry {
+    fn new(pkg: Package) -> Self {
+        Self { key: package_key(&pkg), pkg, next: 0 }
+    }
+}
+
+/// Пакет, указанный ещё раз, хотя он уже попал в порядок установки
+#[derive(Debug, Clone, PartialEq)]
+pub struct Duplicate {
+    /// Файл (или имя) повторно указанного пакета
+    pub package: String,
+    /// Указанный пакет, вместе с которым он попал в порядок установки первым
+    pub via: String,
+}
+
+pub struct DependencyManager {
+    /// Каталог, от которого отсчитываются зависимости пакета без файла (из stdin)
+    pub base_path: PathBuf,
+    /// Зависимость, которую не удалось загрузить, пропускается с предупреждением,
+    /// а не прерывает разрешение (--ignore-missing-deps)
+    pub ignore_missing: bool,
+    /// Насколько глубоко следовать зависимостям (--deps-depth): 0 — только указанные пакеты,
+    /// 1 — и их прямые зависимости; None — без ограничения
+    pub depth_limit: Option<usize>,
+    /// Повторно указанные пакеты последнего get_install_order: в порядок они не входят
+    pub duplicates: Vec<Duplicate>,
+    /// Пакеты, загруженные последним scan: среди них load_dependency ищет зависимость по provides
+    loaded: Vec<Package>,
+}
+
+impl DependencyManager {
+    pub fn new(base_path: PathBuf) -> Self {
+        Self {
+            base_path,
+            ignore_missing: false,
+            depth_limit: None,
+            duplicates: Vec::new(),
+            loaded: Vec::new(),
+        }
+    }
+
+    /// Файл зависимости dep_path пакета pkg: относительный путь отсчитывается от каталога
+    /// файла, в котором объявлена зависимость, — у вложенных зависимостей тоже
+    pub fn resolve_path(&self, pkg: &Package, dep_path: &str) -> PathBuf {
+        let path = Path::new(dep_path);
+        if path.is_absolute() {
+            return path.to_path_buf();
+        }
+        match pkg.file_path.as_deref().and_then(Path::parent) {
+            Some(dir) => dir.join(path),
+            None => self.base_path.join(path),
+        }
+    }
+
+    pub fn load_package(&self, path: &Path) -> Result<Package, String> {
+        let content = read_package_file(path).map_err(|e| match remote::url(path) {
+            Some(url) => format!("Не удалось загрузить {}: {}", url, e),
+            None => format!("Не удалось прочитать файл {:?}: {}", path, e),
+        })?;
+        
+        let mut parser = InstnothParser::with_path(content, path.to_path_buf());
+        parser.parse()
+    }
+
+    /// Пакет зависимости dep пакета pkg: файл по её пути, а если такого файла нет — уже
+    /// загруженный пакет (см. scan), у которого это имя в provides
+    pub fn load_dependency(&self, pkg: &Package, dep: &Dependency) -> Result<Package, String> {
+        let full_path = self.resolve_path(pkg, &dep.path);
+        if !package_file_exists(&full_path) {
+            let key = package_key(pkg);
+            if let Some(provider) = self.loaded.iter().find(|p| p.provides.contains(&dep.path) && package_key(p) != key) {
+                return Ok(provider.clone());
+            }
+        }
+        self.load_package(&full_path)
+    }
+
+    /// Пакеты вместе с зависимостями из `depends:`: каждая зависимость раньше пакета,
+    /// которому нужна, и только один раз — в том числе указанный ещё раз сам по себе
+    /// (он попадает в duplicates). Цикл зависимостей — Err, как и зависимости,
+    /// которые не загрузились: в ошибке они перечислены все, а не только первая
+    ///
+    /// ```
+    /// use instnoth::deps::DependencyManager;
+    /// use std::path::{Path, PathBuf};
+    ///
+    /// let mut manager = DependencyManager::new(PathBuf::from("examples"));
+    /// let all = manager.load_package(Path::new("examples/all.instnoth")).unwrap();
+    /// let order = manager.get_install_order(&[all]).unwrap();
+    /// assert_eq!(order.last().unwrap().name, "All-in-One Dev Bundle");
+    /// assert!(order.len() > 1);
+    /// ```
+    pub fn get_install_order(&mut self, packages: &[Package]) -> Result<Vec<Package>, String> {
+        let mut order = Vec::new();
+        // Пакет в порядке установки → указанный пакет, вместе с которым он туда попал
+        let mut visited: HashMap<String, String> = HashMap::new();
+        let mut missing = Vec::new();
+        let expandable = self.scan(packages);
+
+        self.duplicates.clear();
+        for pkg in packages {
+            let label = package_label(pkg);
+            if let Some(via) = visited.get(&package_key(pkg)) {
+                self.duplicates.push(Duplicate { package: label, via: via.clone() });
+                continue;
+            }
+            self.visit_package(pkg, &label, &expandable, &mut order, &mut visited, &mut missing)?;
+        }
+
+        if missing.is_empty() || self.ignore_missing {
+            for problem in &missing {
+                eprintln!("{} Не удалось загрузить зависимость: {}", "⚠".yellow(), problem);
+            }
+            return Ok(order);
+        }
+        Err(format!(
+            "не удалось загрузить зависимости ({}; --ignore-missing-deps пропускает их):\n  {}",
+            missing.len(), missing.join("\n  ")
+        ))
+    }
+
+    // Обход в глубину со своим стеком вместо рекурсии: цепочка из сотен файлов, каждый из
+    // которых зависит от следующего, не переполняет стек программы. stack — путь обхода от
+    // корня до текущего пакета: цикл — его хвост от первого вхождения пакета. Пакеты
+    // сравниваются по файлам, потому что имена могут совпадать. Зависимости берутся только
+    // у пакетов из expandable
+    fn visit_package(
+        &self,
+        pkg: &Package,
+        root: &str,
+        expandable: &HashSet<String>,
+        order: &mut Vec<Package>,
+        visited: &mut HashMap<String, String>,
+        missing: &mut Vec<String>,
+    ) -> Result<(), String> {
+        let mut stack = vec![PathEntry::new(pkg.clone())];
+        while let Some(top) = stack.last_mut() {
+            // Все зависимости пакета уже в order — теперь и он сам
+            let expand = expandable.contains(&top.key);
+            let Some(dep) = top.pkg.depends.get(top.next).filter(|_| expand).cloned() else {
+                let done = stack.pop().expect("стек не пуст");
+                visited.insert(done.key, root.to_string());
+                order.push(done.pkg);
+                continue;
+            };
+            top.next += 1;
+            let pkg = &top.pkg;
+            let full_path = self.resolve_path(pkg, &dep.path);
+            let dep_pkg = match self.load_dependency(pkg, &dep) {
+                Ok(dep_pkg) => dep_pkg,
+                Err(e) => {
+                    // Остальные зависимости всё равно проверяются, чтобы перечислить все
+                    missing.push(if package_file_exists(&full_path) {
+                        format!("{} требует {}: {}", pkg.name, dep.path, e)
+                    } else if full_path.extension().is_none() {
+                        // Без расширения это скорее имя из provides, чем файл
+                        format!("{} требует {}: нет ни файла {}, ни пакета с provides: \"{}\"", pkg.name, dep.path, attempted_path(&full_path).display(), dep.path)
+                    } else {
+                        format!("{} требует {}: нет файла {}", pkg.name, dep.path, attempted_path(&full_path).display())
+                    });
+                    continue;
+                }
+            };
+            if let Some(constraint) = &dep.constraint {
+                if !constraint.matches(&dep_pkg.version) {
+                    return Err(format!(
+                        "пакет {} требует {} {}, но {} имеет версию {}",
+                        pkg.name, dep.path, constraint, dep_pkg.name, dep_pkg.version
+                    ));
+                }
+            }
+            let entry = PathEntry::new(dep_pkg);
+            if let Some(start) = stack.iter().position(|e| e.key == entry.key) {
+                return Err(format!("Обнаружена циклическая зависимость: {}", cycle_chain(&stack[start..], &entry.pkg)));
+            }
+            if !visited.contains_key(&entry.key) {
+                stack.push(entry);
+            }
+        }
+        Ok(())
+    }
+
+    /// Загрузить пакеты packages и их зависимости в пределах depth_limit: среди них
+    /// load_dependency ищет зависимости по provides. get_install_order вызывает его сам,
+    /// дереву и графу зависимостей он нужен заранее. Возвращает ключи пакетов, зависимости
+    /// которых ещё в пределах depth_limit, то есть ближе него к указанным пакетам по
+    /// кратчайшему пути. Обход по уровням, а не в глубину: пакет, найденный сначала
+    /// глубоко, а потом и ближе, должен раскрыться по ближнему пути
+    pub fn scan(&mut self, packages: &[Package]) -> HashSet<String> {
+        let mut expandable = HashSet::new();
+        let mut seen: HashSet<String> = packages.iter().map(package_key).collect();
+        self.loaded = packages.to_vec();
+        let mut level = packages.to_vec();
+        let mut depth = 0;
+        while !level.is_empty() && self.depth_limit.is_none_or(|limit| depth < limit) {
+            let mut next = Vec::new();
+            for pkg in &level {
+                expandable.insert(package_key(pkg));
+                for dep in &pkg.depends {
+                    if let Ok(dep_pkg) = self.load_dependency(pkg, dep) {
+                        if seen.insert(package_key(&dep_pkg)) {
+                            self.loaded.push(dep_pkg.clone());
+                            next.push(dep_pkg);
+                        }
+                    }
+                }
+            }
+            level = next;
+            depth += 1;
+        }
+        expandable
+    }
+
+    /// Порядок установки волнами: в уровне 0 пакеты без зависимостей, в уровне N — те, чьи
+    /// зависимости все в уровнях до N. Пакеты одного уровня друг от друга не зависят и могут
+    /// ставиться одновременно; внутри уровня они в том же порядке, что в get_install_order.
+    /// Ошибки (циклы, незагрузившиеся зависимости) — те же, что у get_install_order
+    pub fn get_install_levels(&mut self, packages: &[Package]) -> Result<Vec<Vec<Package>>, String> {
+        let order = self.get_install_order(packages)?;
+        // В order зависимость всегда раньше пакета, поэтому её уровень уже известен
+        let mut level_of: Vec<usize> = Vec::with_capacity(order.len());
+        for deps in self.dependencies_in(&order) {
+            level_of.push(deps.iter().map(|&dep| level_of[dep] + 1).max().unwrap_or(0));
+        }
+        let mut levels: Vec<Vec<Package>> = vec![Vec::new(); level_of.iter().max().map_or(0, |max| max + 1)];
+        for (pkg, level) in order.into_iter().zip(level_of) {
+            levels[level].push(pkg);
+        }
+        Ok(levels)
+    }
+
+    // Пакет и все его зависимости в порядке установки — строки lockfile в каталоге dir
+    fn lock_entries(&mut self, pkg: &Package, dir: &Path) -> Result<Vec<LockedPackage>, String> {
+        let dir = std::path::absolute(dir).unwrap_or_else(|_| dir.to_path_buf());
+        self.get_install_order(std::slice::from_ref(pkg))?
+            .into_iter()
+            .map(|p| {
+                let file = p.file_path.clone().ok_or_else(|| format!("{}: у пакета нет файла", p.name))?;
+                let content = read_package_file(&file).map_err(|e| format!("{}: {}", file.display(), e))?;
+                let path = if builtin::is_builtin(&file) || remote::url(&file).is_some() {
+                    file.display().to_string()
+                } else {
+                    let absolute = attempted_path(&file);
+                    absolute.strip_prefix(&dir).unwrap_or(&absolute).display().to_string()
+                };
+                Ok(LockedPackage { path, name: p.name, version: p.version, sha256: sha256_hex(content.as_bytes()) })
+            })
+            .collect()
+    }
+
+    /// instnoth lock: записать в lock_path пакет pkg и его зависимости — путь, имя, версию
+    /// и SHA-256 содержимого каждого файла. Ok — сколько файлов записано
+    pub fn write_lockfile(&mut self, pkg: &Package, lock_path: &Path) -> Result<usize, String> {
+        let entries = self.lock_entries(pkg, lock_path.parent().unwrap_or(Path::new("")))?;
+        fs::write(lock_path, lock_to_json(&entries)).map_err(|e| format!("не удалось записать {}: {}", lock_path.display(), e))?;
+        Ok(entries.len())
+    }
+
+    /// --locked: совпадают ли файлы pkg и его зависимостей с записанными в lock_path. Err —
+    /// что изменилось: по строке на каждый файл, новый, пропавший или с другим содержимым
+    pub fn verify_lockfile(&mut self, pkg: &Package, lock_path: &Path) -> Result<(), String> {
+        let text = fs::read_to_string(lock_path).map_err(|e| format!("{}: {}", lock_path.display(), e))?;
+        let locked = lock_from_json(&text).ok_or_else(|| format!("{}: не читается как lockfile", lock_path.display()))?;
+        let current = self.lock_entries(pkg, lock_path.parent().unwrap_or(Path::new("")))?;
+        let short = |hash: &str| hash.chars().take(12).collect::<String>();
+        let mut drift = Vec::new();
+        for now in &current {
+            match locked.iter().find(|l| l.path == now.path) {
+                None => drift.push(format!("+ {}: {} {} — нет в lockfile", now.path, now.name, now.version)),
+                Some(was) if was == now => {}
+                Some(was) if was.name != now.name || was.version != now.version => drift.push(format!(
+                    "~ {}: {} {} → {} {} (sha256 {} → {})",
+                    now.path, was.name, was.version, now.name, now.version, short(&was.sha256), short(&now.sha256)
+                )),
+                Some(was) => drift.push(format!(
+                    "~ {}: {} {}, содержимое изменилось (sha256 {} → {})",
+                    now.path, now.name, now.version, short(&was.sha256), short(&now.sha256)
+                )),
+            }
+        }
+        for was in locked.iter().filter(|l| !current.iter().any(|now| now.path == l.path)) {
+            drift.push(format!("- {}: {} {} — больше не нужен", was.path, was.name, was.version));
+        }
+        if drift.is_empty() {
+            return Ok(());
+        }
+        Err(format!("{} не совпадает с файлами ({}):\n  {}", lock_path.display(), plural(drift.len() as u32, "изменение", "изменения", "изменений"), drift.join("\n  ")))
+    }
+
+    /// Для каждого пакета order — номера его зависимостей в том же order (для --parallel).
+    /// Только более ранние: у пакета на границе --deps-depth зависимости не учитывались,
+    /// и попавшая в order другим путём может стоять после него
+    pub fn dependencies_in(&self, order: &[Package]) -> Vec<Vec<usize>> {
+        order.iter()
+            .enumerate()
+            .map(|(i, pkg)| {
+                pkg.depends.iter()
+                    .filter_map(|dep| self.load_dependency(pkg, dep).ok())
+                    .filter_map(|dep| order[..i].iter().position(|p| package_key(p) == package_key(&dep)))
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+// Строка conflicts, которая называет пакет name с provides, без учёта регистра, и через что:
+// «(conflicts: "nginx")» или «(conflicts: "webserver", provides у nginx)»
+fn conflict_reason(conflicts: &[String], name: &str, provides: &[String]) -> Option<String> {
+    conflicts.iter().find_map(|conflict| {
+        if conflict.to_lowercase() == name.to_lowercase() {
+            Some(format!("(conflicts: \"{}\")", conflict))
+        } else if provides.iter().any(|p| p.to_lowercase() == conflict.to_lowercase()) {
+            Some(format!("(conflicts: \"{}\", provides у {})", conflict, name))
+        } else {
+            None
+        }
+    })
+}
+
+/// Пары пакетов order, которые нельзя ставить вместе: conflicts одного называет имя или
+/// provides другого. По строке на пару, с файлами обоих пакетов
+pub fn find_conflicts(order: &[Package]) -> Vec<String> {
+    let mut found = Vec::new();
+    for (i, a) in order.iter().enumerate() {
+        for b in &order[i + 1..] {
+            let pair = conflict_reason(&a.conflicts, &b.name, &b.provides).map(|reason| (a, b, reason))
+                .or_else(|| conflict_reason(&b.conflicts, &a.name, &a.provides).map(|reason| (b, a, reason)));
+            if let Some((from, to, reason)) = pair {
+                found.push(format!("{} конфликтует с {} {}", package_label(from), package_label(to), reason));
+            }
+        }
+    }
+    found
+}
+
+/// Сравнение имени с шаблоном без учёта регистра: * — любая последовательность, ? — один символ
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    wildcard_match(&pattern.to_lowercase(), &text.to_lowercase())
+}
+
+/// То же с учётом регистра — для имён файлов, как в shell
+pub fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Позиция последней * в шаблоне и место в тексте, с которого она сейчас совпадает
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// --list-phases: фазы каждого файла с числом команд
+pub fn list_phases(packages: &[Package]) {
+    for pkg in packages {
+        println!();
+        let file = pkg.file_path.as_deref().map(|p| p.display().to_string()).unwrap_or_default();
+        println!("{} {} {}", pkg.name.green().bold(), pkg.version, format!("({})", file).dimmed());
+        let width = pkg.phases.iter().map(|p| p.name.chars().count()).max().unwrap_or(0);
+        for (i, phase) in pkg.phases.iter().enumerate() {
+            let optional = if phase.optional { " optional".yellow().to_string() } else { String::new() };
+            println!("  {:>2}. {:<width$}  {}{}", i + 1, phase.name.cyan(), plural(phase.commands.len() as u32, "команда", "команды", "команд").dimmed(), optional, width = width);
+        }
+    }
+    println!();
+}
+
+/// Шаблоны --only-phase, которым не соответствует ни одна фаза пакетов
+pub fn unmatched_phase_patterns<'a>(patterns: &'a [String], packages: &[Package]) -> Vec<&'a str> {
+    patterns.iter()
+        .filter(|pattern| !packages.iter().flat_map(|p| &p.phases).any(|phase| glob_match(pattern, &phase.name)))
+        .map(String::as_str)
+        .collect()
+}
+
+/// --plan: уровни get_install_levels, пакеты одного уровня — через запятую; depth —
+/// действующий --deps-depth, он выводится всегда, чтобы было видно, полный ли это план
+pub fn print_install_levels(levels: &[Vec<Package>], depth: Option<usize>, lang: Lang) {
+    println!();
+    println!("{}", Msg::LevelsTitle.text(lang).green().bold());
+    println!("{}: {}", Msg::DepsDepth.text(lang).green().bold(), deps_depth_value(lang, depth));
+    println!("{}", "─".repeat(40).dimmed());
+    for (i, level) in levels.iter().enumerate() {
+        let names: Vec<&str> = level.iter().map(|p| p.name.as_str()).collect();
+        let number = format!("{}.", i + 1).yellow();
+        if names.len() > 1 {
+            println!("  {} {}", number, Msg::LevelParallel.fill(lang, &[&names.join(", ").cyan()]));
+        } else {
+            println!("  {} {}", number, names.join(", ").cyan());
+        }
+    }
+    let total: usize = levels.iter().map(Vec::len).sum();
+    println!();
+    println!("{}", Msg::LevelsTotal.fill(lang, &[&Msg::Levels.count(lang, levels.len() as u32), &Msg::Packages.count(lang, total as u32)]).dimmed());
+    println!();
+}
+
+/// --show-deps: дерево зависимостей pkg. Пакет, уже показанный в другой ветви, не
+/// раскрывается снова; с max_depth (--max-depth) глубже этого числа уровней вместо
+/// зависимостей выводится, сколько уровней скрыто, а глубже depth_limit менеджера
+/// (--deps-depth) — только пути зависимостей, без чтения их файлов
+pub fn show_dependency_tree(pkg: &Package, dep_manager: &DependencyManager, max_depth: Option<usize>, visited: &mut HashSet<String>) {
+    // Свой стек вместо рекурсии, как в get_install_order: path — пакеты от корня дерева до
+    // текущего, зависимость из них — цикл, а не уже показанный пакет
+    let mut path: Vec<PathEntry> = Vec::new();
+    if show_package_line(pkg, 0, visited) {
+        path.push(PathEntry::new(pkg.clone()));
+    }
+    while !path.is_empty() {
+        let depth = path.len() - 1;
+        let prefix = "  ".repeat(depth * 2);
+        // Глубже --deps-depth файлы зависимостей не читаются вовсе: видны только их пути
+        if let Some(limit) = dep_manager.depth_limit.filter(|&limit| depth >= limit && path[depth].next == 0 && !path[depth].pkg.depends.is_empty()) {
+            let files: Vec<&str> = path[depth].pkg.depends.iter().map(|dep| dep.path.as_str()).collect();
+            println!("{}  └─ {}", prefix, format!("… не загружены: {} (глубина зависимостей {})", files.join(", "), limit).dimmed());
+            path[depth].next = path[depth].pkg.depends.len();
+        }
+        if path[depth].next == 0 && !path[depth].pkg.depends.is_empty() && max_depth.is_some_and(|max| depth >= max) {
+            let hidden = depth_below(&path, dep_manager);
+            let word = if hidden % 10 == 1 && hidden % 100 != 11 { "скрыт" } else { "скрыто" };
+            println!("{}  └─ {}", prefix, format!("… {} {}", plural(hidden as u32, "уровень", "уровня", "уровней"), word).dimmed());
+            path[depth].next = path[depth].pkg.depends.len();
+        }
+        let top = &mut path[depth];
+        let Some(dep) = top.pkg.depends.get(top.next).cloned() else {
+            path.pop();
+            continue;
+        };
+        top.next += 1;
+        let is_last = top.next == top.pkg.depends.len();
+        let branch = if is_last { "└─" } else { "├─" };
+
+        let constraint = dep.constraint.as_ref()
+            .map(|c| format!(" ({})", c))
+            .unwrap_or_default();
+
+        let Ok(dep_pkg) = dep_manager.load_dependency(&top.pkg, &dep) else {
+            println!("{}  {} {}{} {}", prefix, branch, dep.path.yellow(), constraint.dimmed(), "(не найден)".red());
+            continue;
+        };
+        let mismatch = match &dep.constraint {
+            Some(c) if !c.matches(&dep_pkg.version) => {
+                format!(" (несовместимо: v{})", dep_pkg.version).red().to_string()
+            }
+            _ => String::new(),
+        };
+        let entry = PathEntry::new(dep_pkg);
+        if let Some(start) = path.iter().position(|e| e.key == entry.key) {
+            let cycle = format!("(цикл: {})", cycle_chain(&path[start..], &entry.pkg));
+            println!("{}  {} {}{}{} {}", prefix, branch, dep.path.yellow(), constraint.dimmed(), mismatch, cycle.red());
+            continue;
+        }
+        println!("{}  {} {}{}{}", prefix, branch, dep.path.yellow(), constraint.dimmed(), mismatch);
+        if show_package_line(&entry.pkg, (depth + 1) * 2, visited) {
+            path.push(entry);
+        }
+    }
+}
+
+/// Граф зависимостей для `instnoth deps --format dot|mermaid`. Обходит то же, что
+/// show_dependency_tree, но узел — файл пакета, как в порядке установки: общая зависимость
+/// остаётся одним узлом со всеми входящими рёбрами
+#[derive(Debug, Default)]
+pub struct DependencyGraph {
+    nodes: Vec<GraphNode>,
+    edges: Vec<GraphEdge>,
+}
+
+// «имя\nvверсия», без версии — только имя
+fn graph_label(pkg: &Package) -> String {
+    if pkg.version.is_empty() {
+        pkg.name.clone()
+    } else {
+        format!("{}\nv{}", pkg.name, pkg.version)
+    }
+}
+
+#[derive(Debug)]
+struct GraphNode {
+    key: String,
+    /// «имя\nvверсия»; у ненайденной зависимости — путь из depends
+    label: String,
+    missing: bool,
+}
+
+#[derive(Debug)]
+struct GraphEdge {
+    from: usize,
+    to: usize,
+    constraint: Option<String>,
+    /// Ребро замыкает цикл: ведёт в пакет на пути к from
+    cycle: bool,
+}
+
+impl DependencyGraph {
+    pub fn build(packages: &[Package], dep_manager: &DependencyManager) -> Self {
+        let mut graph = DependencyGraph::default();
+        for pkg in packages {
+            let (root, new) = graph.node(package_key(pkg), graph_label(pkg), false);
+            if !new {
+                continue;
+            }
+            // Путь от корня и номер узла каждого пакета на нём
+            let mut path = vec![(PathEntry::new(pkg.clone()), root)];
+            while let Some((top, from)) = path.last_mut() {
+                let from = *from;
+                let Some(dep) = top.pkg.depends.get(top.next).cloned() else {
+                    path.pop();
+                    continue;
+                };
+                top.next += 1;
+                let full_path = dep_manager.resolve_path(&top.pkg, &dep.path);
+                let constraint = dep.constraint.as_ref().map(|c| c.to_string());
+                let Ok(dep_pkg) = dep_manager.load_dependency(&top.pkg, &dep) else {
+                    let (to, _) = graph.node(format!("missing:{}", attempted_path(&full_path).display()), dep.path.clone(), true);
+                    graph.edges.push(GraphEdge { from, to, constraint, cycle: false });
+                    continue;
+                };
+                let entry = PathEntry::new(dep_pkg);
+                let cycle = path.iter().any(|(e, _)| e.key == entry.key);
+                let (to, new) = graph.node(entry.key.clone(), graph_label(&entry.pkg), false);
+                graph.edges.push(GraphEdge { from, to, constraint, cycle });
+                if new {
+                    path.push((entry, to));
+                }
+            }
+        }
+        graph
+    }
+
+    // Номер узла по ключу и true, если узел только что добавлен
+    fn node(&mut self, key: String, label: String, missing: bool) -> (usize, bool) {
+        match self.nodes.iter().position(|n| n.key == key) {
+            Some(index) => (index, false),
+            None => {
+                self.nodes.push(GraphNode { key, label, missing });
+                (self.nodes.len() - 1, true)
+            }
+        }
+    }
+
+    /// Graphviz: `dot -Tsvg graph.dot -o graph.svg`
+    pub fn to_dot(&self) -> String {
+        let quote = |text: &str| format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n"));
+        let mut out = String::from("digraph deps {\n    node [shape=box];\n");
+        for (i, node) in self.nodes.iter().enumerate() {
+            let style = if node.missing { ", color=red, fontcolor=red, style=dashed" } else { "" };
+            let label = if node.missing { format!("{}\n(не найден)", node.label) } else { node.label.clone() };
+            out.push_str(&format!("    n{} [label={}{}];\n", i, quote(&label), style));
+        }
+        for edge in &self.edges {
+            let mut attrs = Vec::new();
+            if let Some(constraint) = &edge.constraint {
+                attrs.push(format!("label={}", quote(constraint)));
+            }
+            if edge.cycle {
+                attrs.push("color=red, penwidth=2".to_string());
+            }
+            if self.nodes[edge.to].missing {
+                attrs.push("color=red, style=dashed".to_string());
+            }
+            let attrs = if attrs.is_empty() { String::new() } else { format!(" [{}]", attrs.join(", ")) };
+            out.push_str(&format!("    n{} -> n{}{};\n", edge.from, edge.to, attrs));
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// Mermaid: блок ```mermaid в Markdown
+    pub fn to_mermaid(&self) -> String {
+        let quote = |text: &str| format!("\"{}\"", text.replace('"', "#quot;").replace('\n', "<br/>"));
+        let mut out = String::from("graph TD\n");
+        for (i, node) in self.nodes.iter().enumerate() {
+            let label = if node.missing { format!("{}\n(не найден)", node.label) } else { node.label.clone() };
+            out.push_str(&format!("    n{}[{}]\n", i, quote(&label)));
+        }
+        for edge in &self.edges {
+            let arrow = if self.nodes[edge.to].missing { "-.->" } else { "-->" };
+            match &edge.constraint {
+                Some(constraint) => out.push_str(&format!("    n{} {}|{}| n{}\n", edge.from, arrow, quote(constraint), edge.to)),
+                None => out.push_str(&format!("    n{} {} n{}\n", edge.from, arrow, edge.to)),
+            }
+        }
+        let missing: Vec<String> = self.nodes.iter().enumerate().filter(|(_, n)| n.missing).map(|(i, _)| format!("n{}", i)).collect();
+        if !missing.is_empty() {
+            out.push_str("    classDef missing stroke:#d00,color:#d00,stroke-dasharray:5 5\n");
+            out.push_str(&format!("    class {} missing\n", missing.join(",")));
+        }
+        let cycles: Vec<String> = self.edges.iter().enumerate().filter(|(_, e)| e.cycle).map(|(i, _)| i.to_string()).collect();
+        if !cycles.is_empty() {
+            out.push_str(&format!("    linkStyle {} stroke:#d00,stroke-width:2px\n", cycles.join(",")));
+        }
+        out
+    }
+}
+
+// Строка пакета в дереве; false — он уже показан и раскрывать его не нужно
+fn show_package_line(pkg: &Package, indent: usize, visited: &mut HashSet<String>) -> bool {
+    let prefix = "  ".repeat(indent);
+    let marker = if indent == 0 { "📦" } else { "├─" };
+    
+    let license = if pkg.license.is_empty() {
+        String::new()
+    } else {
+        format!(" [{}]", pkg.license)
+    };
+    println!("{}{} {} (v{}){}", prefix, marker, pkg.name.cyan().bold(), pkg.version, license.dimmed());
+    
+    if !visited.insert(package_key(pkg)) {
+        println!("{}  └─ {}", prefix, "(уже показан)".dimmed());
+        return false;
+    }
+    true
+}
+
+// Уровней зависимостей под последним пакетом shown (пути в дереве) — длина самой длинной
+// цепочки. Ненайденная зависимость и замыкающая цикл — тоже уровень: в дереве они были бы строкой
+fn depth_below(shown: &[PathEntry], dep_manager: &DependencyManager) -> usize {
+    let (last, above) = shown.split_last().expect("путь не пуст");
+    let mut known: HashMap<String, usize> = HashMap::new();
+    // Пакеты на пути от last и глубина уже пройденных зависимостей каждого
+    let mut path = vec![(PathEntry::new(last.pkg.clone()), 0)];
+    loop {
+        let (top, depth) = path.last_mut().expect("стек не пуст");
+        let Some(dep) = top.pkg.depends.get(top.next).cloned() else {
+            let (done, depth) = path.pop().expect("стек не пуст");
+            known.insert(done.key, depth);
+            match path.last_mut() {
+                Some((_, parent)) => *parent = (*parent).max(depth + 1),
+                None => return depth,
+            }
+            continue;
+        };
+        top.next += 1;
+        *depth = (*depth).max(1);
+        let Ok(dep_pkg) = dep_manager.load_dependency(&top.pkg, &dep) else { continue };
+        let entry = PathEntry::new(dep_pkg);
+        if let Some(&below) = known.get(&entry.key) {
+            *depth = (*depth).max(below + 1);
+        } else if !path.iter().any(|(e, _)| e.key == entry.key) && !above.iter().any(|e| e.key == entry.key) {
+            path.push((entry, 0));
+        }
+    }
+}
+
+// ============== Lockfile ==============
+
+/// SHA-256 байтов в шестнадцатеричном виде — для содержимого файлов в lockfile
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    const K: [u32; 64] = [
+        0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+        0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+        0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+        0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+        0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+        0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+        0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+        0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+    ];
+    let mut h: [u32; 8] = [0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19];
+    // Дополнение: 0x80, нули до 56 байт по модулю 64 и длина в битах
+    let mut message = bytes.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((bytes.len() as u64) * 8).to_be_bytes());
+    for chunk in message.chunks(64) {
+        let mut w = [0u32; 64];
+        for i in 0..16 {
+            w[i] = u32::from_be_bytes([chunk[4 * i], chunk[4 * i + 1], chunk[4 * i + 2], chunk[4 * i + 3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut hh] = h;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = hh.wrapping_add(s1).wrapping_add(ch).wrapping_add(K[i]).wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+            hh = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+        for (state, value) in h.iter_mut().zip([a, b, c, d, e, f, g, hh]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+    h.iter().map(|word| format!("{:08x}", word)).collect()
+}
+
+/// Файл пакета, как он записан в lockfile
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockedPackage {
+    /// Путь от каталога lockfile; вне его — абсолютный, у встроенных и удалённых — как есть
+    pub path: String,
+    pub name: String,
+    pub version: String,
+    pub sha256: String,
+}
+
+/// Lockfile файла установки: all.instnoth → all.instnoth.lock рядом с ним. Встроенным,
+/// удалённым и пакетам из stdin его положить некуда
+pub fn lockfile_path(pkg: &Package) -> Result<PathBuf, String> {
+    let path = pkg.file_path.as_deref().ok_or_else(|| "пакет из stdin: lockfile кладётся рядом с файлом установки".to_string())?;
+    if builtin::is_builtin(path) || remote::url(path).is_some() {
+        return Err(format!("{}: lockfile кладётся рядом с файлом установки на диске", path.display()));
+    }
+    let name = path.file_name().ok_or_else(|| format!("{}: не файл", path.display()))?;
+    Ok(path.with_file_name(format!("{}.lock", name.to_string_lossy())))
+}
+
+fn lock_to_json(packages: &[LockedPackage]) -> String {
+    let lines: Vec<String> = packages.iter()
+        .map(|p| format!("  {}", JsonValue::Object(vec![
+            ("path".to_string(), p.path.as_str().into()),
+            ("name".to_string(), p.name.as_str().into()),
+            ("version".to_string(), p.version.as_str().into()),
+            ("sha256".to_string(), p.sha256.as_str().into()),
+        ])))
+        .collect();
+    format!("{{\"version\":1,\"packages\":[\n{}\n]}}\n", lines.join(",\n"))
+}
+
+fn lock_from_json(text: &str) -> Option<Vec<LockedPackage>> {
+    let fields = JsonReader::new(text).object()?;
+    let (_, JsonValue::List(items)) = fields.into_iter().find(|(name, _)| name == "packages")? else { return None };
+    items.into_iter()
+        .map(|item| {
+            let JsonValue::Object(fields) = item else { return None };
+            let text = |key: &str| fields.iter().find_map(|(field, value)| match value {
+                JsonValue::Str(s) if field == key => Some(s.clone()),
+                _ => None,
+            });
+            Some(LockedPackage { path: text("path")?, name: text("name")?, version: text("version")?, sha256: text("sha256")? })
+        })
+        .collect()
+}
+
+// ============== Установленные пакеты ==============
+
+/// Имя файла списка установленных пакетов в каталоге состояния
+pub const INSTALLED_FILE: &str = "installed.json";
+
+/// Каталог состояния по умолчанию: $XDG_DATA_HOME/instnoth, иначе ~/.local/share/instnoth
+pub fn default_state_dir() -> Option<PathBuf> {
+    let data = std::env::var_os("XDG_DATA_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))?;
+    Some(data.join("instnoth"))
+}
+
+/// Пакет, который «установлен»: откуда и когда
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstalledPackage {
+    pub name: String,
+    pub version: String,
+    /// Файл установки: абсолютный путь, builtin:/…, адрес или "-" для stdin
+    pub source: String,
+    /// Секунды Unix
+    pub installed_at: u64,
+    /// provides и conflicts пакета — чтобы новые пакеты проверялись на конфликт с ним
+    pub provides: Vec<String>,
+    pub conflicts: Vec<String>,
+}
+
+/// Установленные пакеты между запусками: installed.json в каталоге --state-dir. Ничего не
+/// ставится и здесь — список помнит только то, что было показано
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Inventory {
+    pub packages: Vec<InstalledPackage>,
+}
+
+impl Inventory {
+    fn to_json(&self) -> String {
+        let packages = self.packages.iter()
+            .map(|p| JsonValue::Object(vec![
+                ("name".to_string(), p.name.as_str().into()),
+                ("version".to_string(), p.version.as_str().into()),
+                ("source".to_string(), p.source.as_str().into()),
+                // Строкой, как seed в SavedProgress: u64 не помещается в целые JsonValue
+                ("installed_at".to_string(), p.installed_at.to_string().into()),
+                ("provides".to_string(), p.provides.as_slice().into()),
+                ("conflicts".to_string(), p.conflicts.as_slice().into()),
+            ]))
+            .collect();
+        JsonValue::Object(vec![("version".to_string(), 1.into()), ("packages".to_string(), JsonValue::List(packages))]).to_string()
+    }
+
+    fn from_json(text: &str) -> Option<Self> {
+        let mut inventory = Inventory::default();
+        for (name, value) in JsonReader::new(text).object()? {
+            let (name, JsonValue::List(items)) = (name.as_str(), value) else { continue };
+            if name != "packages" {
+                continue;
+            }
+            for item in items {
+                let JsonValue::Object(fields) = item else { return None };
+                let text = |key: &str| fields.iter().find_map(|(field, value)| match value {
+                    JsonValue::Str(s) if field == key => Some(s.clone()),
+                    _ => None,
+                });
+                // Списков нет в файлах, записанных до provides и conflicts
+                let names = |key: &str| fields.iter().find_map(|(field, value)| match value {
+                    JsonValue::List(items) if field == key => Some(items.iter().filter_map(|item| match item {
+                        JsonValue::Str(s) => Some(s.clone()),
+                        _ => None,
+                    }).collect()),
+                    _ => None,
+                }).unwrap_or_default();
+                inventory.packages.push(InstalledPackage {
+                    name: text("name")?,
+                    version: text("version")?,
+                    source: text("source").unwrap_or_default(),
+                    installed_at: text("installed_at").and_then(|s| s.parse().ok()).unwrap_or(0),
+                    provides: names("provides"),
+                    conflicts: names("conflicts"),
+                });
+            }
+        }
+        Some(inventory)
+    }
+
+    /// Список из файла; файла ещё нет — пустой список
+    pub fn load(path: &Path) -> Result<Self, String> {
+        match fs::read_to_string(path) {
+            Ok(text) => Self::from_json(&text).ok_or_else(|| format!("{}: не читается как список установленных пакетов", path.display())),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(format!("{}: {}", path.display(), e)),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.to_json() + "\n")
+    }
+
+    /// Установленный пакет по имени без учёта регистра
+    pub fn get(&self, name: &str) -> Option<&InstalledPackage> {
+        self.packages.iter().find(|p| p.name.to_lowercase() == name.to_lowercase())
+    }
+
+    /// Запись, из-за которой пакет ставить не нужно: установлена та же версия или новее
+    pub fn current(&self, pkg: &Package) -> Option<&InstalledPackage> {
+        self.get(&pkg.name).filter(|installed| {
+            compare_versions(&parse_version(&installed.version), &parse_version(&pkg.version)).is_ge()
+        })
+    }
+
+    /// Запомнить установку пакета; прежняя запись того же имени заменяется
+    pub fn record(&mut self, pkg: &Package, now: u64) {
+        let source = pkg.file_path.as_deref().map_or("-".to_string(), |path| attempted_path(path).display().to_string());
+        let entry = InstalledPackage {
+            name: pkg.name.clone(),
+            version: pkg.version.clone(),
+            source,
+            installed_at: now,
+            provides: pkg.provides.clone(),
+            conflicts: pkg.conflicts.clone(),
+        };
+        match self.packages.iter_mut().find(|p| p.name.to_lowercase() == pkg.name.to_lowercase()) {
+            Some(existing) => *existing = entry,
+            None => self.packages.push(entry),
+        }
+    }
+
+    /// Как find_conflicts, но между пакетами order и установленными. Установленный пакет
+    /// того же имени не конфликт: order его заменяет
+    pub fn conflicts(&self, order: &[Package]) -> Vec<String> {
+        let mut found = Vec::new();
+        for pkg in order {
+            for installed in self.packages.iter().filter(|p| p.name.to_lowercase() != pkg.name.to_lowercase()) {
+                let label = format!("{} v{} ({})", installed.name, installed.version, installed.source);
+                if let Some(reason) = conflict_reason(&pkg.conflicts, &installed.name, &installed.provides) {
+                    found.push(format!("{} конфликтует с установленным {} {}", package_label(pkg), label, reason));
+                } else if let Some(reason) = conflict_reason(&installed.conflicts, &pkg.name, &pkg.provides) {
+                    found.push(format!("установленный {} конфликтует с {} {}", label, package_label(pkg), reason));
+                }
+            }
+        }
+        found
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<InstalledPackage> {
+        let index = self.packages.iter().position(|p| p.name.to_lowercase() == name.to_lowercase())?;
+        Some(self.packages.remove(index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_match_ignores_case() {
+        assert!(glob_match("тестирование*", "Тестирование оборудования"));
+        assert!(glob_match("*GRUB", "Установка загрузчика grub"));
+        assert!(glob_match("Фаза ?", "фаза 2"));
+        assert!(glob_match("a*b*c", "aXbYbZc"));
+        assert!(!glob_match("Настройка*", "Предварительная настройка"));
+        assert!(!glob_match("Фаза ?", "Фаза 10"));
+    }
+
+    #[test]
+    fn wildcard_match_respects_case() {
+        assert!(wildcard_match("Demo*.instnoth", "Demo_x.instnoth"));
+        assert!(!wildcard_match("Demo*.instnoth", "demo_x.instnoth"));
+    }
+
+    #[test]
+    fn builtin_files_parse_and_depend_on_each_other() {
+        for (name, source) in builtin::all() {
+            let mut parser = InstnothParser::with_path(source.to_string(), builtin::path(name));
+            assert!(parser.parse().is_ok(), "{}", name);
+        }
+        assert_eq!(builtin::resolve(Path::new("builtin:all")), PathBuf::from("builtin:/all.instnoth"));
+        assert_eq!(builtin::resolve(Path::new("examples/all.instnoth")), PathBuf::from("examples/all.instnoth"));
+
+        let path = builtin::resolve(Path::new("builtin:devstack.instnoth"));
+        let mut manager = DependencyManager::new(path.parent().unwrap().to_path_buf());
+        let package = manager.load_package(&path).unwrap();
+        let order = manager.get_install_order(&[package]).unwrap();
+        let names: Vec<&str> = order.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["Arch Linux", "Python", "Node.js", "Docker Engine", "Developer Stack"]);
+        assert!(read_package_file(Path::new("builtin:/nope.instnoth")).is_err());
+    }
+
+    #[test]
+    fn cycle_error_shows_whole_chain_of_files() {
+        let dir = std::env::temp_dir().join(format!("instnoth-cycle-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        // Имена у всех пакетов одинаковые: цикл различает их по файлам
+        let write = |name: &str, depends: &str| {
+            fs::write(dir.join(name), format!("package: \"same\"\ndepends: {}\n\nphase \"A\" {{\n  message \"x\"\n}}\n", depends)).unwrap();
+        };
+        let cycle = |root: &str| {
+            let mut manager = DependencyManager::new(dir.clone());
+            let package = manager.load_package(&dir.join(root)).unwrap();
+            manager.get_install_order(&[package]).unwrap_err()
+        };
+        let file = |name: &str| dir.join(name).display().to_string();
+
+        write("a.instnoth", "\"b.instnoth\"");
+        write("b.instnoth", "\"a.instnoth\"");
+        assert_eq!(cycle("a.instnoth"), format!("Обнаружена циклическая зависимость: {} → {} → {}", file("a.instnoth"), file("b.instnoth"), file("a.instnoth")));
+
+        // Цикл начинается не с корня: корень в цепочку не входит
+        write("root.instnoth", "\"c.instnoth\"");
+        write("c.instnoth", "\"d.instnoth\"");
+        write("d.instnoth", "\"e.instnoth\"");
+        write("e.instnoth", "\"c.instnoth\"");
+        assert_eq!(cycle("root.instnoth"), format!(
+            "Обнаружена циклическая зависимость: {} → {} → {} → {}",
+            file("c.instnoth"), file("d.instnoth"), file("e.instnoth"), file("c.instnoth")
+        ));
+
+        write("self.instnoth", "\"./self.instnoth\"");
+        assert_eq!(cycle("self.instnoth"), format!("Обнаружена циклическая зависимость: {} → {}", file("self.instnoth"), file("./self.instnoth")));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn packages_are_planned_once_per_file() {
+        let dir = std::env::temp_dir().join(format!("instnoth-once-{}", std::process::id()));
+        fs::create_dir_all(dir.join("other")).unwrap();
+        let write = |name: &str, package: &str, depends: &str| {
+            fs::write(dir.join(name), format!("package: \"{}\"\n{}\nphase \"A\" {{\n  message \"x\"\n}}\n", package, depends)).unwrap();
+        };
+        write("app.instnoth", "app", "depends: \"lib.instnoth\"");
+        write("lib.instnoth", "lib", "");
+        // То же имя, другой файл — другой пакет
+        write("other/lib.instnoth", "lib", "");
+
+        let mut manager = DependencyManager::new(dir.clone());
+        let packages: Vec<Package> = ["app.instnoth", "./lib.instnoth", "other/lib.instnoth"].iter()
+            .map(|name| manager.load_package(&dir.join(name)).unwrap())
+            .collect();
+        let order = manager.get_install_order(&packages).unwrap();
+        let files: Vec<PathBuf> = order.iter().map(|p| p.file_path.clone().unwrap()).collect();
+        assert_eq!(files, [dir.join("lib.instnoth"), dir.join("app.instnoth"), dir.join("other/lib.instnoth")]);
+        assert_eq!(manager.duplicates, [Duplicate {
+            package: dir.join("./lib.instnoth").display().to_string(),
+            via: dir.join("app.instnoth").display().to_string(),
+        }]);
+        assert_eq!(manager.dependencies_in(&order), [vec![], vec![0], vec![]]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn install_levels_group_independent_packages() {
+        let dir = std::env::temp_dir().join(format!("instnoth-levels-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let write = |name: &str, depends: &[&str]| {
+            let depends: String = depends.iter().map(|d| format!(" \"{}.instnoth\"", d)).collect();
+            let depends = if depends.is_empty() { String::new() } else { format!("depends:{}\n", depends) };
+            fs::write(dir.join(format!("{}.instnoth", name)), format!("package: \"{}\"\n{}\nphase \"A\" {{\n  message \"x\"\n}}\n", name, depends)).unwrap();
+        };
+        write("app", &["web", "worker"]);
+        write("web", &["lib", "log"]);
+        write("worker", &["lib"]);
+        write("lib", &["core"]);
+        write("log", &[]);
+        write("core", &[]);
+        write("tool", &["core"]);
+
+        let mut manager = DependencyManager::new(dir.clone());
+        let packages: Vec<Package> = ["app", "tool"].iter()
+            .map(|name| manager.load_package(&dir.join(format!("{}.instnoth", name))).unwrap())
+            .collect();
+        let levels = manager.get_install_levels(&packages).unwrap();
+        let names: Vec<Vec<&str>> = levels.iter().map(|level| level.iter().map(|p| p.name.as_str()).collect()).collect();
+        assert_eq!(names, [vec!["core", "log"], vec!["lib", "tool"], vec!["web", "worker"], vec!["app"]]);
+
+        // Плоский порядок — те же пакеты, и каждая зависимость в нём раньше пакета и уровнем ниже
+        let order = manager.get_install_order(&packages).unwrap();
+        assert_eq!(order.len(), levels.iter().map(Vec::len).sum::<usize>());
+        let level_of = |name: &str| levels.iter().position(|level| level.iter().any(|p| p.name == name)).unwrap();
+        for (i, deps) in manager.dependencies_in(&order).iter().enumerate() {
+            for &dep in deps {
+                assert!(dep < i, "{} раньше {}", order[i].name, order[dep].name);
+                assert!(level_of(&order[dep].name) < level_of(&order[i].name), "{}", order[i].name);
+            }
+        }
+
+        write("core", &["app"]);
+        let cycle = manager.get_install_order(&packages).unwrap_err();
+        assert_eq!(manager.get_install_levels(&packages).unwrap_err(), cycle);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn depth_limit_follows_shortest_path_to_each_package() {
+        let dir = std::env::temp_dir().join(format!("instnoth-depth-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let write = |name: &str, depends: &str| {
+            fs::write(dir.join(format!("{}.instnoth", name)), format!("package: \"{}\"\ndepends: {}\n", name, depends)).unwrap();
+        };
+        // c находится и через b (глубина 2), и напрямую (глубина 1) — раскрывается по ближнему пути
+        write("a", "\"b.instnoth\" \"c.instnoth\"");
+        write("b", "\"c.instnoth\"");
+        write("c", "\"d.instnoth\"");
+        fs::write(dir.join("d.instnoth"), "package: \"d\"\ndepends: \"missing.instnoth\"\n").unwrap();
+
+        let mut manager = DependencyManager::new(dir.clone());
+        let a = manager.load_package(&dir.join("a.instnoth")).unwrap();
+        let mut names = |limit: Option<usize>| {
+            manager.depth_limit = limit;
+            manager.get_install_order(std::slice::from_ref(&a)).map(|order| order.iter().map(|p| p.name.clone()).collect::<Vec<_>>())
+        };
+        assert_eq!(names(Some(0)).unwrap(), ["a"]);
+        assert_eq!(names(Some(1)).unwrap(), ["b", "c", "a"]);
+        // Файл зависимости d глубже предела и не читается, поэтому его отсутствие не ошибка
+        assert_eq!(names(Some(2)).unwrap(), ["d", "c", "b", "a"]);
+        assert!(names(None).unwrap_err().contains("d требует missing.instnoth"));
+
+        manager.depth_limit = Some(1);
+        let levels = manager.get_install_levels(&[a]).unwrap();
+        assert_eq!(levels.iter().map(Vec::len).collect::<Vec<_>>(), [2, 1]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn long_dependency_chain_does_not_overflow_the_stack() {
+        let dir = std::env::temp_dir().join(format!("instnoth-chain-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        const LENGTH: usize = 1000;
+        for i in 0..LENGTH {
+            let depends = if i + 1 < LENGTH { format!("depends: \"c{}.instnoth\"\n", i + 1) } else { String::new() };
+            fs::write(dir.join(format!("c{}.instnoth", i)), format!("package: \"c{}\"\n{}\nphase \"A\" {{\n  message \"x\"\n}}\n", i, depends)).unwrap();
+        }
+        // Рекурсия на такой цепочке переполнила бы стек потока в 256 КБ
+        let root = dir.clone();
+        let (order, levels) = std::thread::Builder::new().stack_size(256 * 1024).spawn(move || {
+            let mut manager = DependencyManager::new(root.clone());
+            let first = manager.load_package(&root.join("c0.instnoth")).unwrap();
+            show_dependency_tree(&first, &manager, None, &mut HashSet::new());
+            show_dependency_tree(&first, &manager, Some(3), &mut HashSet::new());
+            (manager.get_install_order(std::slice::from_ref(&first)).unwrap(), manager.get_install_levels(&[first]).unwrap())
+        }).unwrap().join().unwrap();
+        let names: Vec<String> = order.iter().map(|p| p.name.clone()).collect();
+        assert_eq!(names, (0..LENGTH).rev().map(|i| format!("c{}", i)).collect::<Vec<_>>());
+        assert_eq!(levels.len(), LENGTH);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn graph_keeps_shared_dependency_as_one_node() {
+        let dir = std::env::temp_dir().join(format!("instnoth-graph-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let write = |name: &str, depends: &str| {
+            fs::write(dir.join(format!("{}.instnoth", name)), format!("package: \"{}\"\nversion: \"1.0\"\ndepends: {}\n", name, depends)).unwrap();
+        };
+        write("app", "\"left.instnoth\" \"right.instnoth\" \"nope.instnoth\"");
+        write("left", "\"base.instnoth >= 1.0\"");
+        write("right", "\"base.instnoth\"");
+        write("base", "\"app.instnoth\"");
+
+        let manager = DependencyManager::new(dir.clone());
+        let app = manager.load_package(&dir.join("app.instnoth")).unwrap();
+        let graph = DependencyGraph::build(&[app], &manager);
+        let dot = graph.to_dot();
+        // base — один узел с рёбрами из left и right; ребро base → app замыкает цикл
+        assert_eq!(dot.matches("label=\"base\\nv1.0\"").count(), 1, "{dot}");
+        assert!(dot.contains("n1 -> n2 [label=\">= 1.0\"];") && dot.contains("n3 -> n2;"), "{dot}");
+        assert!(dot.contains("n2 -> n0 [color=red, penwidth=2];"), "{dot}");
+        assert!(dot.contains("n4 [label=\"nope.instnoth\\n(не найден)\", color=red, fontcolor=red, style=dashed];"), "{dot}");
+
+        let mermaid = graph.to_mermaid();
+        assert!(mermaid.contains("n1 -->|\">= 1.0\"| n2") && mermaid.contains("n0 -.-> n4"), "{mermaid}");
+        assert!(mermaid.contains("class n4 missing") && mermaid.contains("linkStyle 2 stroke"), "{mermaid}");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn sha256_matches_reference_vectors() {
+        assert_eq!(sha256_hex(b""), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+        assert_eq!(sha256_hex(b"abc"), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        // Дополнение не помещается в первый блок
+        assert_eq!(sha256_hex(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1");
+        let locked = vec![LockedPackage { path: "lib/a.instnoth".into(), name: "a".into(), version: "1.0".into(), sha256: sha256_hex(b"a") }];
+        assert_eq!(lock_from_json(&lock_to_json(&locked)), Some(locked));
+    }
+
+    #[test]
+    fn inventory_skips_only_same_or_older_versions() {
+        let package = |version: &str| InstnothParser::with_path(format!("package: \"Python\"\nversion: \"{}\"\n", version), builtin::path("python")).parse().unwrap();
+        let mut inventory = Inventory::default();
+        inventory.record(&package("3.12.1"), 1_800_000_000);
+        assert_eq!(inventory.get("python").map(|p| p.source.as_str()), Some("builtin:/python.instnoth"));
+        assert!(inventory.current(&package("3.12.1")).is_some());
+        assert!(inventory.current(&package("3.9")).is_some());
+        assert!(inventory.current(&package("3.12.2")).is_none());
+        // Новая установка заменяет запись, а не добавляет вторую
+        inventory.record(&package("3.13"), 1_800_000_100);
+        assert_eq!(inventory.packages.len(), 1);
+
+        assert_eq!(Inventory::from_json(&inventory.to_json()), Some(inventory.clone()));
+        assert_eq!(Inventory::from_json("{\"packages\":[1]}"), None);
+        assert_eq!(inventory.remove("PYTHON").map(|p| p.version), Some("3.13".to_string()));
+        assert!(inventory.remove("python").is_none());
+    }
+
+    #[test]
+    fn dependency_by_provides_name_resolves_among_loaded_packages() {
+        let dir = std::env::temp_dir().join(format!("instnoth-provides-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("all.instnoth"), "package: \"all\"\ndepends: \"nginx.instnoth\" \"site.instnoth\"\n").unwrap();
+        fs::write(dir.join("nginx.instnoth"), "package: \"nginx\"\nprovides: \"webserver, httpd\"\n").unwrap();
+        fs::write(dir.join("site.instnoth"), "package: \"site\"\ndepends: \"webserver\"\n").unwrap();
+
+        let mut manager = DependencyManager::new(dir.clone());
+        let load = |name: &str| manager.load_package(&dir.join(name)).unwrap();
+        let (all, site) = (load("all.instnoth"), load("site.instnoth"));
+        // nginx загружен через all — его provides закрывает зависимость site
+        let order = manager.get_install_order(std::slice::from_ref(&all)).unwrap();
+        assert_eq!(order.iter().map(|p| p.name.as_str()).collect::<Vec<_>>(), ["nginx", "site", "all"]);
+        assert_eq!(manager.dependencies_in(&order), [vec![], vec![0], vec![0, 1]]);
+
+        // Сам по себе site не знает, кто provides webserver
+        let error = manager.get_install_order(&[site]).unwrap_err();
+        assert!(error.contains("нет ни файла") && error.contains("provides: \"webserver\""), "{error}");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn conflicts_match_names_and_provides_in_run_and_installed() {
+        let package = |name: &str, header: &str| {
+            InstnothParser::with_path(format!("package: \"{}\"\nversion: \"1.0\"\n{}", name, header), PathBuf::from(format!("{}.instnoth", name))).parse().unwrap()
+        };
+        let nginx = package("nginx", "provides: \"webserver\"\n");
+        let apache = package("apache", "conflicts: \"WebServer\"\n");
+        let site = package("site", "conflicts: \"apache-legacy\"\n");
+        assert_eq!(find_conflicts(&[nginx.clone(), site.clone(), apache.clone()]),
+            ["apache.instnoth конфликтует с nginx.instnoth (conflicts: \"WebServer\", provides у nginx)"]);
+        assert!(find_conflicts(&[nginx.clone(), site.clone()]).is_empty());
+
+        let mut inventory = Inventory::default();
+        inventory.record(&apache, 1_800_000_000);
+        let inventory = Inventory::from_json(&inventory.to_json()).unwrap();
+        assert_eq!(inventory.conflicts(&[nginx, site]), ["установленный apache v1.0 (".to_string()
+            + &attempted_path(Path::new("apache.instnoth")).display().to_string() + ") конфликтует с nginx.instnoth (conflicts: \"WebServer\", provides у nginx)"]);
+        // Переустановка того же пакета — замена, а не конфликт с самим собой
+        assert!(inventory.conflicts(&[apache]).is_empty());
+    }
+}
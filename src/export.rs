@@ -0,0 +1,373 @@
+//! Экспорт установки в самостоятельный скрипт
+
+use indicatif::{DecimalBytes, HumanBytes};
+use crate::parser::*;
+
+// ============== Экспорт в скрипт ==============
+
+/// Оформление строки экспортированного скрипта
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScriptStyle {
+    Plain,
+    Dim,
+    Accent,
+    Heading,
+    Success,
+    Warning,
+    Error,
+}
+
+/// Язык экспорта: простые действия, в которые export_command раскладывает каждую команду.
+/// Раскладка общая, поэтому новому языку (PowerShell) нужна только своя реализация
+pub trait ScriptBackend {
+    /// Начало скрипта: заголовок, вспомогательные функции, разбор --quick
+    fn begin(&mut self, title: &str);
+    fn line(&mut self, style: ScriptStyle, text: &str);
+    fn sleep(&mut self, ms: u64);
+    /// Спиннер на ms миллисекунд, затем отметка ✓ у label
+    fn spinner(&mut self, label: &str, ms: u64);
+    /// Полоса прогресса label, заполняющаяся за ms
+    fn bar(&mut self, label: &str, ms: u64);
+    /// Общий прогресс установки
+    fn progress(&mut self, percent: u8);
+    /// Текст «набирается» со скоростью cps символов в секунду
+    fn typed(&mut self, text: &str, cps: u64);
+    /// С вероятностью chance процентов — сообщение об ошибке и выход с кодом code
+    fn fail(&mut self, message: &str, code: i32, chance: u8);
+    /// Готовый скрипт
+    fn finish(&mut self) -> String;
+}
+
+/// bash-скрипт, которому нужны только echo/printf, sleep и (для цветов) tput
+#[derive(Default)]
+pub struct ShellScript {
+    pub(crate) script: String,
+}
+
+impl ShellScript {
+    /// Строка в одинарных кавычках: внутри них оболочка ничего не раскрывает
+    fn quote(text: &str) -> String {
+        format!("'{}'", text.replace('\'', "'\\''"))
+    }
+
+    fn seconds(ms: u64) -> String {
+        format!("{}.{:03}", ms / 1000, ms % 1000)
+    }
+
+    pub(crate) fn push(&mut self, line: &str) {
+        self.script.push_str(line);
+        self.script.push('\n');
+    }
+}
+
+impl ScriptBackend for ShellScript {
+    fn begin(&mut self, title: &str) {
+        self.push(&format!(r#"#!/usr/bin/env bash
+# {title}
+# Скрипт создан командой instnoth export: ни instnoth, ни файлы .instnoth ему не нужны
+# Запуск: bash script.sh    без пауз: bash script.sh --quick
+set -u
+QUICK=0
+[ "${{1:-}}" = "--quick" ] && QUICK=1
+
+if [ -t 1 ] && command -v tput >/dev/null 2>&1 && [ "$(tput colors 2>/dev/null || echo 0)" -ge 8 ]; then
+    RESET=$(tput sgr0) BOLD=$(tput bold) DIM=$(tput dim 2>/dev/null || true)
+    RED=$(tput setaf 1) GREEN=$(tput setaf 2) YELLOW=$(tput setaf 3) BLUE=$(tput setaf 4) CYAN=$(tput setaf 6)
+else
+    RESET= BOLD= DIM= RED= GREEN= YELLOW= BLUE= CYAN=
+fi
+
+# say ЦВЕТ ТЕКСТ — строка цветом
+say() {{ printf '%s%s%s\n' "$1" "$2" "$RESET"; }}
+
+# pause СЕКУНДЫ — пауза, кроме --quick
+pause() {{ [ "$QUICK" = 1 ] || sleep "$1"; }}
+
+# spin ТЕКСТ КАДРЫ — спиннер по 0.1 с на кадр, затем ✓
+spin() {{
+    local frames='|/-\' i
+    if [ "$QUICK" = 0 ]; then
+        for ((i = 0; i < $2; i++)); do
+            printf '\r  %s%s%s %s' "$CYAN" "${{frames:i%4:1}}" "$RESET" "$1"
+            sleep 0.1
+        done
+    fi
+    printf '\r  %s✓%s %s\n' "$GREEN" "$RESET" "$1"
+}}
+
+# bar ТЕКСТ СЕКУНДЫ_НА_ШАГ — полоса из 30 шагов
+bar() {{
+    local i fill
+    say "$BLUE" "  $1"
+    for ((i = 0; i <= 30; i++)); do
+        printf -v fill '%*s' "$i" ''
+        printf '\r    [%s%-30s%s] %3d%%' "$CYAN" "${{fill// /#}}" "$RESET" $((i * 100 / 30))
+        [ "$i" = 30 ] || pause "$2"
+    done
+    printf '\n'
+}}
+
+# progress ПРОЦЕНТ — общий прогресс установки
+progress() {{
+    local done rest
+    printf -v done '%*s' $(($1 / 5)) ''
+    printf -v rest '%*s' $((20 - $1 / 5)) ''
+    say "$DIM" "  Общий прогресс: [${{done// /=}}$rest] $1%"
+}}
+
+# typed ТЕКСТ СЕКУНДЫ_НА_СИМВОЛ — текст набирается по символу
+typed() {{
+    local i
+    for ((i = 0; i < ${{#1}}; i++)); do
+        printf '%s' "${{1:i:1}}"
+        pause "$2"
+    done
+    printf '\n'
+}}
+
+# fail КОД ТЕКСТ — сбой: установка прерывается с кодом
+fail() {{
+    say "$RED" "  ✗ $2"
+    say "$RED$BOLD" "Установка прервана"
+    exit "$1"
+}}
+"#, title = title.replace('\n', " ")));
+    }
+
+    fn line(&mut self, style: ScriptStyle, text: &str) {
+        let color = match style {
+            ScriptStyle::Plain => "\"\"",
+            ScriptStyle::Dim => "\"$DIM\"",
+            ScriptStyle::Accent => "\"$CYAN\"",
+            ScriptStyle::Heading => "\"$BOLD$BLUE\"",
+            ScriptStyle::Success => "\"$GREEN\"",
+            ScriptStyle::Warning => "\"$YELLOW\"",
+            ScriptStyle::Error => "\"$RED\"",
+        };
+        if text.is_empty() {
+            self.push("echo");
+        } else {
+            self.push(&format!("say {} {}", color, Self::quote(text)));
+        }
+    }
+
+    fn sleep(&mut self, ms: u64) {
+        if ms > 0 {
+            self.push(&format!("pause {}", Self::seconds(ms)));
+        }
+    }
+
+    fn spinner(&mut self, label: &str, ms: u64) {
+        self.push(&format!("spin {} {}", Self::quote(label), (ms / 100).max(1)));
+    }
+
+    fn bar(&mut self, label: &str, ms: u64) {
+        self.push(&format!("bar {} {}", Self::quote(label), Self::seconds(ms / 30)));
+    }
+
+    fn progress(&mut self, percent: u8) {
+        self.push(&format!("progress {}", percent.min(100)));
+    }
+
+    fn typed(&mut self, text: &str, cps: u64) {
+        self.push(&format!("typed {} {}", Self::quote(text), Self::seconds(1000 / cps.max(1))));
+    }
+
+    fn fail(&mut self, message: &str, code: i32, chance: u8) {
+        let fail = format!("fail {} {}", code, Self::quote(message));
+        if chance >= 100 {
+            self.push(&fail);
+        } else if chance > 0 {
+            self.push(&format!("if ((RANDOM % 100 < {})); then {}; fi", chance, fail));
+        }
+    }
+
+    fn finish(&mut self) -> String {
+        std::mem::take(&mut self.script)
+    }
+}
+
+/// Команда в действиях экспорта. Длительности — те же, что у оценки оставшегося времени;
+/// то, что в терминале рисуется особо (таблицы разделов, randomart, отчёты), в скрипте
+/// сводится к строке со спиннером
+pub(crate) fn export_command(cmd: &Command, out: &mut dyn ScriptBackend) {
+    let ms = estimated_ms(cmd);
+    match cmd {
+        Command::Message(text) => out.line(ScriptStyle::Plain, &format!("  {}", text)),
+        Command::Delay(delay) => out.sleep(*delay),
+        Command::Progress(percent) => out.progress(*percent),
+        Command::Success(text) => out.line(ScriptStyle::Success, &format!("  ✓ {}", text)),
+        Command::Warning(text) => out.line(ScriptStyle::Warning, &format!("  ⚠ {}", text)),
+        Command::Error { message, fatal, exit } => {
+            if *fatal == Some(true) {
+                out.fail(message, *exit, 100);
+            } else {
+                out.line(ScriptStyle::Error, &format!("  ✗ {}", message));
+            }
+        }
+        Command::Fail { message, code } => out.fail(message, *code, 100),
+        Command::FailRandom { message, chance, code } => out.fail(message, *code, *chance),
+        Command::TypeText { text, cps, .. } => out.typed(text, *cps),
+        Command::CreateDir(path) => out.spinner(&format!("Создание директории: {}", path), ms),
+        Command::Download { url, size } => {
+            out.line(ScriptStyle::Accent, &format!("  ⬇ Загрузка: {}", url));
+            out.bar(&format!("{} ({})", url.rsplit('/').next().unwrap_or(url), DecimalBytes(*size)), ms);
+        }
+        Command::Extract { from, to } => out.bar(&format!("Распаковка {} → {}", from, to), ms),
+        Command::InstallDep { name, version } => out.spinner(&format!("Установка зависимости {} (v{})", name, version), ms),
+        Command::Configure { key, value } => out.line(ScriptStyle::Dim, &format!("  ⚙ {} = {}", key, value)),
+        Command::Cleanup => out.spinner("Очистка временных файлов", ms),
+        Command::CopyFile { from, to } => out.spinner(&format!("Копирование {} → {}", from, to), ms),
+        Command::Symlink { from, to } => out.line(ScriptStyle::Dim, &format!("  ln -s {} {}", from, to)),
+        Command::SetPermission { path, mode } => out.line(ScriptStyle::Dim, &format!("  chmod {} {}", mode, path)),
+        Command::RunScript(script) => out.spinner(&format!("Выполнение скрипта {}", script), ms),
+        Command::CheckDep(dep) => out.spinner(&format!("Проверка зависимости: {}", dep), ms),
+        Command::WriteConfig { path, .. } => out.spinner(&format!("Запись конфигурации: {}", path), ms),
+        Command::DetectCpu => out.spinner("Определение процессора", ms),
+        Command::DetectMemory => out.spinner("Определение памяти", ms),
+        Command::DetectDisk => out.spinner("Определение дисков", ms),
+        Command::DetectGpu => out.spinner("Определение видеокарты", ms),
+        Command::DetectNetwork => out.spinner("Проверка сети", ms),
+        Command::DetectOs => out.spinner("Определение операционной системы", ms),
+        Command::DetectKernel => out.spinner("Определение версии ядра", ms),
+        Command::DetectBios => out.spinner("Определение режима загрузки", ms),
+        Command::ScanHardware => out.bar("Сканирование оборудования", ms),
+        Command::DetectDrivers => out.spinner("Определение драйверов", ms),
+        Command::Sensors => out.spinner("Чтение датчиков", ms),
+        Command::RunTest { name, .. } => out.bar(&format!("Тест: {}", name), ms),
+        Command::TestHardware(name) => out.spinner(&format!("Тест оборудования: {}", name), ms),
+        Command::BenchmarkCpu => out.bar("Бенчмарк процессора", ms),
+        Command::BenchmarkMemory => out.bar("Бенчмарк памяти", ms),
+        Command::BenchmarkDisk => out.bar("Бенчмарк диска", ms),
+        Command::LoadKernelModule(module) => out.spinner(&format!("modprobe {}", module), ms),
+        Command::UnloadKernelModule(module) => out.spinner(&format!("modprobe -r {}", module), ms),
+        Command::UpdateInitramfs => out.spinner("Генерация initramfs", ms),
+        Command::UpdateGrub => out.spinner("Обновление конфигурации GRUB", ms),
+        Command::CompileKernel { version } => out.bar(&format!("Компиляция ядра {}", version), ms),
+        Command::SetKernelParam { key, value } => out.line(ScriptStyle::Dim, &format!("  sysctl {}={}", key, value)),
+        Command::SetCmdline(cmdline) => out.line(ScriptStyle::Dim, &format!("  Параметры ядра: {}", cmdline)),
+        Command::MountPartition { device, mount_point } => out.spinner(&format!("Монтирование {} → {}", device, mount_point), ms),
+        Command::UnmountPartition(path) => out.spinner(&format!("Размонтирование {}", path), ms),
+        Command::FormatPartition { device, fs_type } => out.bar(&format!("Форматирование {} в {}", device, fs_type), ms),
+        Command::CreatePartition { device, size } => out.spinner(&format!("Создание раздела {} ({})", device, size), ms),
+        Command::ShowPartitions(disk) => out.spinner(&format!("Чтение таблицы разделов {}", disk), ms),
+        Command::SmartReport { device, .. } => out.spinner(&format!("SMART: {}", device), ms),
+        Command::CreateRaid { level, devices } => out.bar(&format!("Создание RAID{} из {}", level, devices.join(" ")), ms),
+        Command::CreatePool { name, devices, kind } => {
+            out.bar(&format!("Создание пула {} ({}) из {}", name, format!("{:?}", kind).to_lowercase(), devices.join(" ")), ms)
+        }
+        Command::CreateSubvolume(path) => out.spinner(&format!("Создание подтома {}", path), ms),
+        Command::SetupSwap { size, path, zram } => {
+            let label = if *zram { format!("Настройка zram ({})", HumanBytes(*size)) } else { format!("Создание swap-файла {} ({})", path, HumanBytes(*size)) };
+            out.spinner(&label, ms)
+        }
+        Command::CheckSpace { path, need, force_fail } => {
+            out.spinner(&format!("Проверка свободного места в {}: нужно {}", path, HumanBytes(*need)), ms);
+            if *force_fail {
+                out.fail(&format!("Недостаточно места в {}", path), 1, 100);
+            }
+        }
+        Command::EncryptPartition { device, cipher, .. } => out.bar(&format!("Шифрование {} ({})", device, cipher), ms),
+        Command::GenerateFstab => out.spinner("Генерация /etc/fstab", ms),
+        Command::SetHostname(name) => out.line(ScriptStyle::Dim, &format!("  Имя хоста: {}", name)),
+        Command::SetTimezone(zone) => out.line(ScriptStyle::Dim, &format!("  Часовой пояс: {}", zone)),
+        Command::SetLocale(locale) => out.line(ScriptStyle::Dim, &format!("  Локаль: {}", locale)),
+        Command::GenerateLocales(locales) => out.spinner(&format!("Генерация локалей: {}", locales.join(" ")), ms),
+        Command::SetKeymap(keymap) => out.line(ScriptStyle::Dim, &format!("  Раскладка: {}", keymap)),
+        Command::SyncTime => out.spinner("Синхронизация времени", ms),
+        Command::CreateSnapshot(name) => out.spinner(&format!("Создание снимка {}", name), ms),
+        Command::RollbackTo(name) => out.spinner(&format!("Откат к снимку {}", name), ms),
+        Command::CreateUser { username, groups } => out.spinner(&format!("Создание пользователя {} ({})", username, groups), ms),
+        Command::SetPassword(user) => out.line(ScriptStyle::Dim, &format!("  Пароль для {} установлен", user)),
+        Command::EnableService(service) => out.spinner(&format!("systemctl enable {}", service), ms),
+        Command::DisableService(service) => out.spinner(&format!("systemctl disable {}", service), ms),
+        Command::StartService(service) => out.spinner(&format!("systemctl start {}", service), ms),
+        Command::StopService(service) => out.spinner(&format!("systemctl stop {}", service), ms),
+        Command::WaitFor { what, expect_timeout, .. } => {
+            out.spinner(&format!("Ожидание {}", what), ms);
+            if *expect_timeout {
+                out.line(ScriptStyle::Warning, &format!("  ⚠ Истекло время ожидания {}", what));
+            }
+        }
+        Command::SystemdBoot { units, .. } => out.bar(&format!("Запуск юнитов systemd: {}", units), ms),
+        Command::InstallBootloader(target) => out.bar(&format!("Установка загрузчика на {}", target), ms),
+        Command::CheckIntegrity(file) => out.spinner(&format!("Проверка целостности {}", file), ms),
+        Command::VerifySignature(file) => out.spinner(&format!("Проверка подписи {}", file), ms),
+        Command::Checksum { file, algo } => out.spinner(&format!("{}sum {}", algo.name().to_lowercase(), file), ms),
+        Command::InstallPackages(packages) => out.bar(&format!("Установка пакетов: {}", packages), ms),
+        Command::UpdateSystem => out.bar("Обновление системы", ms),
+        Command::InstallDriver(driver) => out.bar(&format!("Установка драйвера {}", driver), ms),
+        Command::InstallLangDeps { manager, packages } => {
+            out.bar(&format!("{} install {}", format!("{:?}", manager).to_lowercase(), packages), ms)
+        }
+        Command::AddRepo { name, .. } => out.spinner(&format!("Добавление репозитория {}", name), ms),
+        Command::ImportKey(id) => out.spinner(&format!("Импорт ключа {}", id), ms),
+        Command::SelectMirror { country, count } => out.spinner(&format!("Выбор самого быстрого из {} зеркал ({})", count, country), ms),
+        Command::NetworkConfig { interface, config } => out.spinner(&format!("Настройка сети: {} ({})", interface, config), ms),
+        Command::FirewallRule(rule) => out.line(ScriptStyle::Dim, &format!("  firewall: {}", rule)),
+        Command::GenerateCert { domain, days, key_type } => {
+            out.spinner(&format!("Сертификат {} ({}, {} дней)", domain, key_type.name(), days), ms)
+        }
+        Command::HardenSsh => out.spinner("Усиление настроек SSH", ms),
+        Command::GenerateSshKey { key_type, bits, comment } => {
+            out.spinner(&format!("Ключ SSH {} {} бит ({})", key_type.name(), bits, comment), ms)
+        }
+        Command::SpeedTest => out.spinner("Тест скорости сети", ms),
+        Command::Reboot { delay_secs } => {
+            for left in (1..=*delay_secs).rev() {
+                out.line(ScriptStyle::Warning, &format!("  Перезагрузка через {}...", left));
+                out.sleep(1000);
+            }
+            out.spinner("Перезагрузка системы", 3000);
+        }
+        Command::LogTail { lines, .. } => out.spinner(&format!("Журнал: {} строк", lines), ms),
+        Command::Compile { name, files, jobs } => out.bar(&format!("Компиляция {} ({} файлов, {} потоков)", name, files, jobs), ms),
+        Command::DockerPull { image, layers } => out.bar(&format!("docker pull {} ({} слоёв)", image, layers), ms),
+        Command::GitClone { url, objects } => out.bar(&format!("git clone {} ({} объектов)", url, objects), ms),
+        Command::GitSubmoduleUpdate => out.spinner("git submodule update --init --recursive", ms),
+        Command::CreateVm { name, cpus, memory, disk } => {
+            out.bar(&format!("Создание ВМ {} ({} CPU, {}, диск {})", name, cpus, HumanBytes(*memory), HumanBytes(*disk)), ms)
+        }
+        Command::StartContainer(image) => out.spinner(&format!("Запуск контейнера {}", image), ms),
+        Command::ProvisionCloud { provider, resources } => out.bar(&format!("{}: создание ресурсов ({})", provider.name(), resources), ms),
+        Command::RunPlaybook { playbook, hosts } => out.bar(&format!("ansible-playbook {} ({} хостов)", playbook, hosts), ms),
+        Command::InitDatabase { engine, version } => out.spinner(&format!("Инициализация {} {}", engine, version), ms),
+        Command::RunMigrations { count } => out.bar(&format!("Миграции базы данных: {}", count), ms),
+        Command::UpdateFirmware { component, version } => out.bar(&format!("Обновление прошивки {} до {}", component, version), ms),
+        Command::ShowLicense { id_or_path } => {
+            let license = match id_or_path {
+                LicenseSource::Spdx(id) => id,
+                LicenseSource::File(path) => path,
+            };
+            out.line(ScriptStyle::Dim, &format!("  Лицензия: {}", license));
+        }
+        Command::Table { title, rows } => {
+            if !title.is_empty() {
+                out.line(ScriptStyle::Heading, &format!("  {}", title));
+            }
+            let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+            let widths: Vec<usize> = (0..columns)
+                .map(|c| rows.iter().filter_map(|r| r.get(c)).map(|cell| cell.chars().count()).max().unwrap_or(0))
+                .collect();
+            for (i, row) in rows.iter().enumerate() {
+                let cells: Vec<String> = row.iter().zip(&widths).map(|(cell, w)| format!("{:<w$}", cell, w = *w)).collect();
+                out.line(if i == 0 { ScriptStyle::Accent } else { ScriptStyle::Plain }, &format!("  {}", cells.join("  ").trim_end()));
+            }
+        }
+        // Вопросы отвечаются значением по умолчанию, как с --non-interactive
+        Command::Ask { prompt, default, .. } => {
+            let answer = if *default { "[Y/n] y" } else { "[y/N] n" };
+            out.line(ScriptStyle::Accent, &format!("  ? {} {}", prompt, answer));
+        }
+        Command::Choose { prompt, options, default, .. } => {
+            out.line(ScriptStyle::Accent, &format!("  ? {}", prompt));
+            for (i, option) in options.iter().enumerate() {
+                let marker = if i == *default { "›" } else { " " };
+                out.line(ScriptStyle::Plain, &format!("    {} {}. {}", marker, i + 1, option));
+            }
+        }
+        Command::Retry { body, .. } => body.iter().for_each(|c| export_command(c, out)),
+        Command::Parallel(children) => children.iter().for_each(|c| export_command(c, out)),
+        Command::Revert { action, target } => out.spinner(&format!("{}: {}", action, target), ms),
+    }
+}
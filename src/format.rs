@@ -0,0 +1,800 @@
+//! Форматирование файлов установки и запись пакета обратно в текст .instnoth
+
+use std::fmt;
+use crate::parser::*;
+
+// ============== Форматирование ==============
+
+/// Строка файла в дереве `instnoth fmt`. В отличие от Package, дерево хранит всё, что
+/// нужно для обратной записи: порядок строк, пустые строки и комментарии. Код строк
+/// хранится уже в каноническом виде
+#[derive(Debug, Clone, PartialEq)]
+enum SyntaxNode {
+    Blank,
+    /// Комментарий на отдельной строке, вместе с '#'
+    Comment(String),
+    /// Поле заголовка, команда или строка row; comment — комментарий в конце строки
+    Line { code: String, comment: Option<String> },
+    /// phase, define, retry или parallel с телом до закрывающей '}'
+    Block { header: String, comment: Option<String>, body: Vec<SyntaxNode>, close: String, close_comment: Option<String> },
+}
+
+/// Код строки без комментария и сам комментарий, если он есть
+fn split_comment(line: &str) -> (&str, Option<String>) {
+    let code = InstnothParser::strip_comment(line);
+    let comment = line[code.len()..].trim();
+    (code.trim(), (!comment.is_empty()).then(|| comment.to_string()))
+}
+
+/// Строковый литерал в двойных кавычках; read_literal вернёт из него ровно value
+pub(crate) fn quote_literal(value: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Числа и true/false остаются без кавычек: числовые параметры в кавычках не читаются
+fn is_plain_value(value: &str) -> bool {
+    let digits = value.strip_prefix('-').unwrap_or(value);
+    value == "true" || value == "false"
+        || (digits.starts_with(|c: char| c.is_ascii_digit())
+            && digits.chars().all(|c| c.is_ascii_digit() || c == '.')
+            && digits.matches('.').count() <= 1)
+}
+
+/// Слова строки через один пробел; литералы переписываются в двойных кавычках.
+/// С braces фигурные скобки вне литералов становятся отдельными словами
+fn canonical_words(code: &str, braces: bool) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut pos = 0;
+    while pos < code.len() {
+        if InstnothParser::is_literal_start(code, pos) {
+            if let Ok((value, end)) = InstnothParser::read_literal(code, pos) {
+                word.push_str(&quote_literal(&value));
+                pos = end;
+                continue;
+            }
+        }
+        let c = code[pos..].chars().next().unwrap_or(' ');
+        pos += c.len_utf8();
+        if c.is_whitespace() || (braces && (c == '{' || c == '}')) {
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            if !c.is_whitespace() {
+                words.push(c.to_string());
+            }
+        } else {
+            word.push(c);
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+/// Команда: параметры name=значение с текстом без кавычек берутся в кавычки
+fn format_command(code: &str) -> String {
+    let words: Vec<String> = canonical_words(code, false).into_iter().map(|word| {
+        let Some((name, value)) = word.split_once('=') else { return word };
+        let is_name = !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !is_name || value.is_empty() || value.contains('"') || is_plain_value(value) {
+            return word;
+        }
+        format!("{}={}", name, quote_literal(value))
+    }).collect();
+    words.join(" ")
+}
+
+/// Заголовок блока заканчивается " {", атрибуты фазы и число попыток retry не меняются
+fn format_block_header(code: &str) -> String {
+    canonical_words(code, true).join(" ")
+}
+
+/// Поле верхнего уровня как `ключ: "значение"`; строки, которые парсер не считает полем,
+/// только выравниваются по пробелам
+fn format_header(code: &str) -> String {
+    let Some(key) = InstnothParser::header_like_key(code) else {
+        return canonical_words(code, false).join(" ");
+    };
+    let rest = &code[key.len() + 1..];
+    let values = match key {
+        "depends" | "provides" | "conflicts" => InstnothParser::literals(rest).ok().filter(|deps| !deps.is_empty()),
+        // Логическое значение можно было написать без кавычек
+        "require_root" => Some(vec![InstnothParser::extract_quoted_value(rest).unwrap_or_else(|_| rest.trim().to_string())]),
+        _ => InstnothParser::extract_quoted_value(rest).ok().map(|value| vec![value]),
+    };
+    match values {
+        Some(values) => {
+            let values: Vec<String> = values.iter().map(|v| quote_literal(v)).collect();
+            format!("{}: {}", key, values.join(" "))
+        }
+        None => canonical_words(code, false).join(" "),
+    }
+}
+
+/// Дерево файла без потерь: каждая строка исходника попадает ровно в один узел
+fn parse_syntax(source: &str) -> Vec<SyntaxNode> {
+    let lines: Vec<&str> = source.lines().collect();
+    let mut i = 0;
+    syntax_nodes(&lines, &mut i, true)
+}
+
+// Узлы до закрывающей '}' (по завершении *i указывает на неё) или до конца файла
+fn syntax_nodes(lines: &[&str], i: &mut usize, top_level: bool) -> Vec<SyntaxNode> {
+    let mut nodes = Vec::new();
+    while *i < lines.len() {
+        let (code, comment) = split_comment(lines[*i]);
+        if !top_level && code.starts_with('}') {
+            break;
+        }
+        *i += 1;
+        if code.is_empty() {
+            nodes.push(comment.map_or(SyntaxNode::Blank, SyntaxNode::Comment));
+            continue;
+        }
+
+        let mut header = code.to_string();
+        let mut comment = comment;
+        let mut body = Vec::new();
+        if InstnothParser::brace_delta(code) <= 0 {
+            // '{' фазы или define может стоять на следующей строке: заголовок собирается в одну
+            let next = (*i..lines.len()).find(|&j| !split_comment(lines[j]).0.is_empty());
+            let opens_below = top_level && (code.starts_with("phase") || code.starts_with("define "))
+                && next.is_some_and(|j| split_comment(lines[j]).0.starts_with('{'));
+            if !opens_below {
+                let code = if top_level { format_header(code) } else { format_command(code) };
+                nodes.push(SyntaxNode::Line { code, comment });
+                continue;
+            }
+            let next = next.expect("opens_below проверяет, что строка есть");
+            for line in &lines[*i..next] {
+                body.extend(split_comment(line).1.map(SyntaxNode::Comment));
+            }
+            let (brace, brace_comment) = split_comment(lines[next]);
+            header = format!("{} {}", header, brace);
+            comment = comment.or(brace_comment);
+            *i = next + 1;
+        }
+
+        body.extend(syntax_nodes(lines, i, false));
+        let (close, close_comment) = match lines.get(*i) {
+            Some(line) => {
+                *i += 1;
+                let (close, close_comment) = split_comment(line);
+                (canonical_words(close, true).join(" "), close_comment)
+            }
+            None => ("}".to_string(), None),
+        };
+        nodes.push(SyntaxNode::Block { header: format_block_header(&header), comment, body, close, close_comment });
+    }
+    nodes
+}
+
+/// Не больше одной пустой строки подряд и ни одной в начале и в конце. На верхнем уровне
+/// блок со своими комментариями над ним отделяется от соседей пустой строкой
+fn normalize_blanks(nodes: &[SyntaxNode], top_level: bool) -> Vec<SyntaxNode> {
+    let mut result: Vec<SyntaxNode> = Vec::new();
+    for (n, node) in nodes.iter().enumerate() {
+        let last = result.last();
+        if *node == SyntaxNode::Blank {
+            if last.is_some_and(|last| *last != SyntaxNode::Blank) {
+                result.push(SyntaxNode::Blank);
+            }
+            continue;
+        }
+        if top_level {
+            let starts_block = nodes[n..].iter()
+                .find(|node| !matches!(node, SyntaxNode::Comment(_)))
+                .is_some_and(|node| matches!(node, SyntaxNode::Block { .. }));
+            let separate = match last {
+                Some(SyntaxNode::Block { .. }) => true,
+                Some(SyntaxNode::Blank | SyntaxNode::Comment(_)) | None => false,
+                Some(SyntaxNode::Line { .. }) => starts_block,
+            };
+            if separate {
+                result.push(SyntaxNode::Blank);
+            }
+        }
+        result.push(node.clone());
+    }
+    if result.last() == Some(&SyntaxNode::Blank) {
+        result.pop();
+    }
+    result
+}
+
+fn push_line(out: &mut String, depth: usize, code: &str, comment: Option<&str>) {
+    out.push_str(&"  ".repeat(depth));
+    out.push_str(code);
+    if let Some(comment) = comment {
+        out.push(' ');
+        out.push_str(comment);
+    }
+    out.push('\n');
+}
+
+fn write_syntax(out: &mut String, nodes: &[SyntaxNode], depth: usize) {
+    for node in normalize_blanks(nodes, depth == 0) {
+        match node {
+            SyntaxNode::Blank => out.push('\n'),
+            SyntaxNode::Comment(text) => push_line(out, depth, &text, None),
+            SyntaxNode::Line { code, comment } => push_line(out, depth, &code, comment.as_deref()),
+            SyntaxNode::Block { header, comment, body, close, close_comment } => {
+                push_line(out, depth, &header, comment.as_deref());
+                write_syntax(out, &body, depth + 1);
+                push_line(out, depth, &close, close_comment.as_deref());
+            }
+        }
+    }
+}
+
+/// Канонический вид файла: два пробела на уровень вложенности, одна пустая строка между
+/// фазами, поля `ключ: "значение"`, текстовые параметры в кавычках. Комментарии остаются
+/// на своих местах
+///
+/// ```
+/// use instnoth::format::format_source;
+///
+/// let source = "package:   \"demo\"\nphase \"Установка\"\n{\n      configure key=port value=8080   # порт\n}\n";
+/// assert_eq!(format_source(source), "package: \"demo\"\n\nphase \"Установка\" {\n  configure key=\"port\" value=8080 # порт\n}\n");
+/// ```
+pub fn format_source(source: &str) -> String {
+    let mut out = String::new();
+    write_syntax(&mut out, &parse_syntax(source), 0);
+    out
+}
+
+/// Размер для параметров вида memory="2G": крупнейшая единица, в которой он целый,
+/// чтобы parse_human_size вернул ровно те же байты
+fn exact_size(bytes: u64) -> String {
+    [("T", 1u64 << 40), ("G", 1 << 30), ("M", 1 << 20), ("K", 1 << 10)].iter()
+        .find(|(_, unit)| bytes > 0 && bytes.is_multiple_of(*unit))
+        .map_or_else(|| bytes.to_string(), |(suffix, unit)| format!("{}{}", bytes / unit, suffix))
+}
+
+impl Command {
+    /// Команда в синтаксисе .instnoth, в том виде, в каком её выводит fmt. Блоки retry
+    /// и parallel, а также table со строками row занимают несколько строк; тело блока
+    /// сдвинуто на два пробела. Revert парсер не создаёт, он записывается комментарием
+    pub fn to_instnoth(&self) -> String {
+        let q = |s: &str| quote_literal(s);
+        let lower = |v: &dyn fmt::Debug| quote_literal(&format!("{:?}", v).to_lowercase());
+        let block = |header: String, body: &[Command]| {
+            let mut lines = vec![header];
+            for cmd in body {
+                lines.extend(cmd.to_instnoth().lines().map(|line| format!("  {}", line)));
+            }
+            lines.push("}".to_string());
+            lines.join("\n")
+        };
+        match self {
+            Command::Message(text) => format!("message {}", q(text)),
+            Command::Delay(ms) => format!("delay {}", ms),
+            Command::Progress(pct) => format!("progress {}", pct),
+            Command::CreateDir(path) => format!("create_dir {}", q(path)),
+            Command::Download { url, size } => format!("download {} size={}", q(url), size),
+            Command::Extract { from, to } => format!("extract {} to={}", q(from), q(to)),
+            Command::InstallDep { name, version } => format!("install_dep {} version={}", q(name), q(version)),
+            Command::Configure { key, value } => format!("configure key={} value={}", q(key), q(value)),
+            Command::Cleanup => "cleanup".to_string(),
+            Command::Success(text) => format!("success {}", q(text)),
+            Command::Error { message, fatal, exit } => {
+                let mut line = format!("error {}", q(message));
+                if let Some(fatal) = fatal {
+                    line.push_str(&format!(" fatal={}", fatal));
+                }
+                if *exit != 1 {
+                    line.push_str(&format!(" exit={}", exit));
+                }
+                line
+            }
+            Command::Warning(text) => format!("warning {}", q(text)),
+            Command::CopyFile { from, to } => format!("copy_file {} to={}", q(from), q(to)),
+            Command::Symlink { from, to } => format!("symlink {} to={}", q(from), q(to)),
+            Command::SetPermission { path, mode } => format!("set_permission {} mode={}", q(path), q(mode)),
+            Command::RunScript(script) => format!("run_script {}", q(script)),
+            Command::CheckDep(dep) => format!("check_dep {}", q(dep)),
+            Command::WriteConfig { path, content } => format!("write_config {} content={}", q(path), q(content)),
+            Command::DetectCpu => "detect_cpu".to_string(),
+            Command::DetectMemory => "detect_memory".to_string(),
+            Command::DetectDisk => "detect_disk".to_string(),
+            Command::DetectGpu => "detect_gpu".to_string(),
+            Command::DetectNetwork => "detect_network".to_string(),
+            Command::DetectOs => "detect_os".to_string(),
+            Command::DetectKernel => "detect_kernel".to_string(),
+            Command::DetectBios => "detect_bios".to_string(),
+            Command::RunTest { name, duration } => format!("run_test {} duration={}", q(name), duration),
+            Command::LoadKernelModule(module) => format!("load_module {}", q(module)),
+            Command::UnloadKernelModule(module) => format!("unload_module {}", q(module)),
+            Command::UpdateInitramfs => "update_initramfs".to_string(),
+            Command::UpdateGrub => "update_grub".to_string(),
+            Command::MountPartition { device, mount_point } => format!("mount {} to={}", q(device), q(mount_point)),
+            Command::UnmountPartition(mount_point) => format!("unmount {}", q(mount_point)),
+            Command::FormatPartition { device, fs_type } => format!("format {} fs={}", q(device), q(fs_type)),
+            Command::CreatePartition { device, size } => format!("create_partition {} size={}", q(device), q(size)),
+            Command::SetHostname(hostname) => format!("set_hostname {}", q(hostname)),
+            Command::SetTimezone(tz) => format!("set_timezone {}", q(tz)),
+            Command::SetLocale(locale) => format!("set_locale {}", q(locale)),
+            Command::GenerateLocales(locales) => format!("generate_locales {}", q(&locales.join(" "))),
+            Command::CreateSnapshot(name) => format!("create_snapshot {}", q(name)),
+            Command::RollbackTo(name) => format!("rollback_to {}", q(name)),
+            Command::SetKeymap(keymap) => format!("set_keymap {}", q(keymap)),
+            Command::CreateUser { username, groups } => format!("create_user {} groups={}", q(username), q(groups)),
+            Command::SetPassword(user) => format!("set_password {}", q(user)),
+            Command::EnableService(service) => format!("enable_service {}", q(service)),
+            Command::DisableService(service) => format!("disable_service {}", q(service)),
+            Command::StartService(service) => format!("start_service {}", q(service)),
+            Command::StopService(service) => format!("stop_service {}", q(service)),
+            Command::InstallBootloader(target) => format!("install_bootloader {}", q(target)),
+            Command::GenerateFstab => "generate_fstab".to_string(),
+            Command::CheckIntegrity(target) => format!("check_integrity {}", q(target)),
+            Command::VerifySignature(file) => format!("verify_signature {}", q(file)),
+            Command::CompileKernel { version } => format!("compile_kernel {}", q(version)),
+            Command::InstallPackages(packages) => format!("install_packages {}", q(packages)),
+            Command::UpdateSystem => "update_system".to_string(),
+            Command::SyncTime => "sync_time".to_string(),
+            Command::TestHardware(component) => format!("test_hardware {}", q(component)),
+            Command::BenchmarkCpu => "benchmark_cpu".to_string(),
+            Command::BenchmarkMemory => "benchmark_memory".to_string(),
+            Command::BenchmarkDisk => "benchmark_disk".to_string(),
+            Command::NetworkConfig { interface, config } => format!("network_config {} config={}", q(interface), q(config)),
+            Command::FirewallRule(rule) => format!("firewall_rule {}", q(rule)),
+            Command::ScanHardware => "scan_hardware".to_string(),
+            Command::DetectDrivers => "detect_drivers".to_string(),
+            Command::InstallDriver(driver) => format!("install_driver {}", q(driver)),
+            Command::Reboot { delay_secs } => format!("reboot delay={}", delay_secs),
+            Command::TypeText { text, cps, style } => format!("type {} speed={} style={}", q(text), cps, lower(style)),
+            Command::LogTail { lines, style } => format!("log_tail lines={} style={}", lines, lower(style)),
+            Command::Compile { name, files, jobs } => format!("compile {} files={} jobs={}", q(name), files, jobs),
+            Command::Checksum { file, algo } => format!("checksum {} algo={}", q(file), q(algo.name())),
+            Command::Fail { message, code } => format!("fail {} code={}", q(message), code),
+            Command::DockerPull { image, layers } => format!("docker_pull {} layers={}", q(image), layers),
+            Command::GitClone { url, objects } => format!("git_clone {} objects={}", q(url), objects),
+            Command::GitSubmoduleUpdate => "git_submodule_update".to_string(),
+            Command::SystemdBoot { units, failures } => format!("systemd_boot units={} failures={}", units, failures),
+            Command::ShowPartitions(device) => format!("show_partitions {}", q(device)),
+            Command::SmartReport { device, fail_chance } => format!("smart_report {} fail_chance={}", q(device), fail_chance),
+            Command::Sensors => "sensors".to_string(),
+            Command::CreateRaid { level, devices } => format!("create_raid level={} devices={}", level, q(&devices.join(" "))),
+            Command::CreatePool { name, devices, kind } => {
+                format!("create_pool {} devices={} type={}", q(name), q(&devices.join(" ")), lower(kind))
+            }
+            Command::CreateSubvolume(path) => format!("create_subvolume {}", q(path)),
+            Command::SetupSwap { size, path, zram } => {
+                format!("setup_swap size={} path={} zram={}", q(&exact_size(*size)), q(path), zram)
+            }
+            Command::SetKernelParam { key, value } => format!("set_kernel_param {} {}", q(key), q(value)),
+            Command::SetCmdline(cmdline) => format!("set_cmdline {}", q(cmdline)),
+            Command::InstallLangDeps { manager, packages } => {
+                let keyword = match manager {
+                    LangManager::Pip => "install_pydeps",
+                    LangManager::Npm => "install_npmdeps",
+                    LangManager::Cargo => "install_crates",
+                };
+                format!("{} {}", keyword, q(packages))
+            }
+            Command::CreateVm { name, cpus, memory, disk } => format!(
+                "create_vm {} cpus={} memory={} disk={}", q(name), cpus, q(&exact_size(*memory)), q(&exact_size(*disk))
+            ),
+            Command::StartContainer(image) => format!("start_container {}", q(image)),
+            Command::GenerateCert { domain, days, key_type } => {
+                format!("generate_cert {} days={} type={}", q(domain), days, q(key_type.name()))
+            }
+            Command::HardenSsh => "harden_ssh".to_string(),
+            // У ed25519 размер ключа фиксирован, и bits= вызвал бы предупреждение
+            Command::GenerateSshKey { key_type: KeyType::Ed25519, comment, .. } => {
+                format!("generate_ssh_key type=\"ed25519\" comment={}", q(comment))
+            }
+            Command::GenerateSshKey { key_type, bits, comment } => {
+                format!("generate_ssh_key type={} bits={} comment={}", q(key_type.name()), bits, q(comment))
+            }
+            Command::ProvisionCloud { provider, resources } => {
+                format!("provision_cloud provider={} resources={}", q(provider.name()), resources)
+            }
+            Command::RunPlaybook { playbook, hosts } => format!("run_playbook {} hosts={}", q(playbook), hosts),
+            Command::InitDatabase { engine, version } => format!("init_database {} version={}", q(engine), q(version)),
+            Command::RunMigrations { count } => format!("run_migrations count={}", count),
+            Command::ShowLicense { id_or_path: LicenseSource::Spdx(id) } => format!("show_license {}", q(id)),
+            Command::ShowLicense { id_or_path: LicenseSource::File(path) } => format!("show_license file={}", q(path)),
+            Command::SpeedTest => "speed_test".to_string(),
+            Command::UpdateFirmware { component, version } => format!("update_firmware {} version={}", q(component), q(version)),
+            Command::WaitFor { what, timeout_secs, expect_timeout } => format!(
+                "wait_for {} timeout={} expect={}", q(what), timeout_secs, q(if *expect_timeout { "timeout" } else { "success" })
+            ),
+            Command::CheckSpace { path, need, force_fail } => {
+                format!("check_space {} need={} force_fail={}", q(path), q(&exact_size(*need)), force_fail)
+            }
+            Command::SelectMirror { country, count } => format!("select_mirror country={} count={}", q(country), count),
+            Command::AddRepo { name, flavor: None } => format!("add_repo {}", q(name)),
+            Command::AddRepo { name, flavor: Some(distro) } => format!("add_repo {} flavor={}", q(name), q(distro.name())),
+            Command::ImportKey(key) => format!("import_key {}", q(key)),
+            Command::EncryptPartition { device, cipher, name } => {
+                format!("encrypt_partition {} cipher={} name={}", q(device), q(cipher), q(name))
+            }
+            Command::Retry { attempts, body, .. } => block(format!("retry {} {{", attempts), body),
+            Command::Parallel(children) => block("parallel {".to_string(), children),
+            Command::FailRandom { message, chance, code } => format!("fail_random {} chance={} code={}", q(message), chance, code),
+            Command::Table { title, rows } => {
+                let mut lines = vec![format!("table {}", q(title))];
+                for row in rows {
+                    let cells: Vec<String> = row.iter().map(|cell| q(cell)).collect();
+                    lines.push(format!("row {}", cells.join(" ")).trim_end().to_string());
+                }
+                lines.join("\n")
+            }
+            Command::Ask { prompt, default, required } => {
+                let mut line = format!("ask {} default={}", q(prompt), q(if *default { "yes" } else { "no" }));
+                if *required {
+                    line.push_str(" required");
+                }
+                line
+            }
+            Command::Choose { prompt, options, default, var } => format!(
+                "choose {} options={} default={} var={}",
+                q(prompt), q(&options.join(", ")), q(options.get(*default).map_or("", String::as_str)), q(var)
+            ),
+            Command::Revert { action, target } => format!("# {}: {}", action, target),
+        }
+    }
+}
+
+impl Package {
+    /// Пакет в синтаксисе .instnoth: разбор результата даёт тот же пакет (с точностью до
+    /// номеров строк), а fmt его не меняет. Пустые поля заголовка не выводятся
+    ///
+    /// ```
+    /// use instnoth::parser::InstnothParser;
+    ///
+    /// let source = "package: \"demo\"\nversion: \"1.0\"\n\nphase \"Установка\" {\n  message \"Привет\"\n}\n";
+    /// let package = InstnothParser::new(source.to_string()).parse().unwrap();
+    /// assert_eq!(package.to_instnoth(), source);
+    /// ```
+    pub fn to_instnoth(&self) -> String {
+        let mut out = String::new();
+        let fields = [
+            ("package", self.name.as_str()), ("version", &self.version), ("description", &self.description),
+            ("author", &self.author), ("license", &self.license), ("homepage", &self.homepage),
+            ("size", &self.size), ("arch", &self.arch), ("distro", self.distro.map_or("", |d| d.name())),
+            ("on_error", if self.abort_on_error { "abort" } else { "" }),
+            ("require_root", if self.require_root { "true" } else { "" }),
+        ];
+        for (key, value) in fields.iter().filter(|(_, value)| !value.is_empty()) {
+            out.push_str(&format!("{}: {}\n", key, quote_literal(value)));
+        }
+        if !self.depends.is_empty() {
+            let deps: Vec<String> = self.depends.iter().map(|d| quote_literal(&d.to_string())).collect();
+            out.push_str(&format!("depends: {}\n", deps.join(" ")));
+        }
+        for (key, names) in [("provides", &self.provides), ("conflicts", &self.conflicts)] {
+            if !names.is_empty() {
+                let names: Vec<String> = names.iter().map(|name| quote_literal(name)).collect();
+                out.push_str(&format!("{}: {}\n", key, names.join(" ")));
+            }
+        }
+
+        for phase in &self.phases {
+            let mut header = format!("phase {}", quote_literal(&phase.name));
+            if phase.optional {
+                header.push_str(" optional");
+            }
+            if phase.weight != 1 {
+                header.push_str(&format!(" weight={}", phase.weight));
+            }
+            out.push_str(&format!("\n{} {{\n", header));
+            for cmd in &phase.commands {
+                for line in cmd.to_instnoth().lines() {
+                    out.push_str(&format!("  {}\n", line));
+                }
+            }
+            out.push_str("}\n");
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deps::{builtin, templates};
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use std::collections::HashSet;
+
+    #[test]
+    fn fmt_output_is_canonical_and_keeps_comments() {
+        let source = "# top\npackage:\"demo\"   # name\nversion: '1.0'\nphase \"One\"   optional weight=3\n# before brace\n{\n\tmessage   \"hi  there\"  # greet\n\n\n   download \"x\" size=10 \n      retry 2 {\n   run_test name=unit duration=100\n } # end retry\n}\n\n\n\nphase \"Two\" {\n  configure key=a.b value=r\"C:\\x\"\n}\n";
+        let expected = "# top\npackage: \"demo\" # name\nversion: \"1.0\"\n\nphase \"One\" optional weight=3 {\n  # before brace\n  message \"hi  there\" # greet\n\n  download \"x\" size=10\n  retry 2 {\n    run_test name=\"unit\" duration=100\n  } # end retry\n}\n\nphase \"Two\" {\n  configure key=\"a.b\" value=\"C:\\\\x\"\n}\n";
+        assert_eq!(format_source(source), expected);
+        assert_eq!(format_source(expected), expected);
+    }
+
+    fn without_lines(commands: &[Command]) -> Vec<Command> {
+        commands.iter().cloned().map(|cmd| match cmd {
+            Command::Retry { attempts, body, .. } => Command::Retry { attempts, body: without_lines(&body), body_lines: Vec::new() },
+            cmd => cmd,
+        }).collect()
+    }
+
+    /// Смысл файла: заголовок, зависимости и фазы без номеров строк, которые при
+    /// переписывании файла меняются
+    fn meaning(package: &Package) -> String {
+        let phases: Vec<_> = package.phases.iter()
+            .map(|p| (p.name.clone(), p.optional, p.weight, without_lines(&p.commands)))
+            .collect();
+        let depends: Vec<String> = package.depends.iter().map(|d| d.to_string()).collect();
+        let header = [&package.name, &package.version, &package.description, &package.author, &package.license,
+            &package.homepage, &package.size, &package.arch];
+        format!("{:?} {:?} {} {} {:?} {:?} {:?} {:?}", header, package.distro, package.abort_on_error, package.require_root, depends,
+            package.provides, package.conflicts, phases)
+    }
+
+    #[test]
+    fn fmt_keeps_meaning_of_builtin_files_and_templates() {
+        let parse = |source: &str| InstnothParser::new(source.to_string()).parse_package();
+        let sources = builtin::all().iter().map(|(_, source)| *source)
+            .chain(templates::all().iter().map(|(_, _, source)| *source));
+        for source in sources {
+            let formatted = format_source(source);
+            assert_eq!(meaning(&parse(&formatted)), meaning(&parse(source)));
+            assert_eq!(format_source(&formatted), formatted);
+        }
+    }
+
+    /// Текст, который сериализатор обязан экранировать: кавычки, обратная косая, '#',
+    /// фигурные скобки, '=', перевод строки, табуляция и кириллица
+    fn random_text(rng: &mut StdRng) -> String {
+        const PIECES: &[&str] = &["a", "Z", "9", " ", "\"", "'", "\\", "#", "{", "}", "=", "\n", "\t", "ё", "r\"", "-", "/"];
+        (0..rng.gen_range(0..8)).map(|_| PIECES[rng.gen_range(0..PIECES.len())]).collect()
+    }
+
+    /// Непустое слово без пробелов — для значений, которые парсер делит по пробелам или запятым
+    fn random_word(rng: &mut StdRng) -> String {
+        (0..rng.gen_range(1..8)).map(|_| b"abcxyz019-"[rng.gen_range(0..10)] as char).collect()
+    }
+
+    fn random_words(rng: &mut StdRng, max: usize) -> Vec<String> {
+        let mut words: Vec<String> = Vec::new();
+        for _ in 0..rng.gen_range(1..=max) {
+            let word = random_word(rng);
+            if !words.contains(&word) {
+                words.push(word);
+            }
+        }
+        words
+    }
+
+    fn random_size(rng: &mut StdRng) -> u64 {
+        rng.gen_range(1..4096) << [0, 10, 20, 30][rng.gen_range(0..4)]
+    }
+
+    fn random_command(rng: &mut StdRng, depth: usize) -> Command {
+        let kind = rng.gen_range(0..127);
+        command_of_kind(rng, kind, depth)
+    }
+
+    /// Команда вида kind со случайными, но допустимыми для парсера значениями;
+    /// depth ограничивает вложенность retry
+    fn command_of_kind(rng: &mut StdRng, kind: u32, depth: usize) -> Command {
+        let t = |rng: &mut StdRng| random_text(rng);
+        match kind {
+            0 => Command::Message(t(rng)),
+            1 => Command::Delay(rng.gen_range(0..5000)),
+            2 => Command::Progress(rng.gen_range(0..=100)),
+            3 => Command::CreateDir(t(rng)),
+            4 => Command::Download { url: t(rng), size: rng.gen_range(0..100_000) },
+            5 => Command::Extract { from: t(rng), to: t(rng) },
+            6 => Command::InstallDep { name: t(rng), version: t(rng) },
+            7 => Command::Configure { key: t(rng), value: t(rng) },
+            8 => Command::Cleanup,
+            9 => Command::Success(t(rng)),
+            10 => Command::Error { message: t(rng), fatal: [None, Some(true), Some(false)][rng.gen_range(0..3)], exit: rng.gen_range(1..256) },
+            11 => Command::Warning(t(rng)),
+            12 => Command::CopyFile { from: t(rng), to: t(rng) },
+            13 => Command::Symlink { from: t(rng), to: t(rng) },
+            14 => Command::SetPermission { path: t(rng), mode: t(rng) },
+            15 => Command::RunScript(t(rng)),
+            16 => Command::CheckDep(t(rng)),
+            17 => Command::WriteConfig { path: t(rng), content: t(rng) },
+            18 => Command::DetectCpu,
+            19 => Command::DetectMemory,
+            20 => Command::DetectDisk,
+            21 => Command::DetectGpu,
+            22 => Command::DetectNetwork,
+            23 => Command::DetectOs,
+            24 => Command::DetectKernel,
+            25 => Command::DetectBios,
+            26 => Command::RunTest { name: t(rng), duration: rng.gen_range(0..5000) },
+            27 => Command::LoadKernelModule(t(rng)),
+            28 => Command::UnloadKernelModule(t(rng)),
+            29 => Command::UpdateInitramfs,
+            30 => Command::UpdateGrub,
+            31 => Command::MountPartition { device: t(rng), mount_point: t(rng) },
+            32 => Command::UnmountPartition(t(rng)),
+            33 => Command::FormatPartition { device: t(rng), fs_type: t(rng) },
+            34 => Command::CreatePartition { device: t(rng), size: t(rng) },
+            35 => Command::SetHostname(t(rng)),
+            36 => Command::SetTimezone(t(rng)),
+            37 => Command::SetLocale(t(rng)),
+            38 => Command::GenerateLocales(random_words(rng, 3)),
+            39 => Command::CreateSnapshot(t(rng)),
+            40 => Command::RollbackTo(t(rng)),
+            41 => Command::SetKeymap(t(rng)),
+            42 => Command::CreateUser { username: t(rng), groups: t(rng) },
+            43 => Command::SetPassword(t(rng)),
+            44 => Command::EnableService(t(rng)),
+            45 => Command::DisableService(t(rng)),
+            46 => Command::StartService(t(rng)),
+            47 => Command::StopService(t(rng)),
+            48 => Command::InstallBootloader(t(rng)),
+            49 => Command::GenerateFstab,
+            50 => Command::CheckIntegrity(t(rng)),
+            51 => Command::VerifySignature(t(rng)),
+            52 => Command::CompileKernel { version: t(rng) },
+            53 => Command::InstallPackages(t(rng)),
+            54 => Command::UpdateSystem,
+            55 => Command::SyncTime,
+            56 => Command::TestHardware(t(rng)),
+            57 => Command::BenchmarkCpu,
+            58 => Command::BenchmarkMemory,
+            59 => Command::BenchmarkDisk,
+            60 => Command::NetworkConfig { interface: t(rng), config: t(rng) },
+            61 => Command::FirewallRule(t(rng)),
+            62 => Command::ScanHardware,
+            63 => Command::DetectDrivers,
+            64 => Command::InstallDriver(t(rng)),
+            65 => Command::Reboot { delay_secs: rng.gen_range(0..60) },
+            66 => Command::TypeText {
+                text: t(rng),
+                cps: rng.gen_range(1..200),
+                style: [TextStyle::Message, TextStyle::Success, TextStyle::Warning, TextStyle::Error][rng.gen_range(0..4)],
+            },
+            67 => Command::LogTail { lines: rng.gen_range(0..100), style: [LogStyle::Gcc, LogStyle::Make, LogStyle::Dpkg, LogStyle::Journal][rng.gen_range(0..4)] },
+            68 => Command::Compile { name: t(rng), files: rng.gen_range(0..1000), jobs: rng.gen_range(1..16) },
+            69 => Command::Checksum { file: t(rng), algo: [ChecksumAlgo::Md5, ChecksumAlgo::Sha1, ChecksumAlgo::Sha256, ChecksumAlgo::Sha512][rng.gen_range(0..4)] },
+            70 => Command::Fail { message: t(rng), code: rng.gen_range(1..256) },
+            71 => Command::DockerPull { image: t(rng), layers: rng.gen_range(0..20) },
+            72 => Command::GitClone { url: t(rng), objects: rng.gen_range(0..10_000) },
+            73 => Command::GitSubmoduleUpdate,
+            74 => {
+                let units = rng.gen_range(0..50);
+                Command::SystemdBoot { units, failures: rng.gen_range(0..=units) }
+            }
+            75 => Command::ShowPartitions(t(rng)),
+            76 => Command::SmartReport { device: t(rng), fail_chance: rng.gen_range(0..=100) },
+            77 => Command::Sensors,
+            78 => Command::CreateRaid { level: [0, 1, 5, 6, 10][rng.gen_range(0..5)], devices: random_words(rng, 5) },
+            79 => Command::CreatePool { name: random_word(rng), devices: random_words(rng, 4), kind: [PoolKind::Zfs, PoolKind::Btrfs][rng.gen_range(0..2)] },
+            80 => Command::CreateSubvolume(format!("{}/{}", random_word(rng), random_word(rng))),
+            81 => Command::SetupSwap { size: random_size(rng), path: t(rng), zram: rng.gen() },
+            82 => Command::SetKernelParam { key: t(rng), value: t(rng) },
+            83 => Command::SetCmdline(t(rng)),
+            84 => Command::InstallLangDeps { manager: [LangManager::Pip, LangManager::Npm, LangManager::Cargo][rng.gen_range(0..3)], packages: t(rng) },
+            85 => Command::CreateVm { name: random_word(rng), cpus: rng.gen_range(0..64), memory: random_size(rng), disk: random_size(rng) },
+            86 => Command::StartContainer(t(rng)),
+            87 => {
+                let domain = random_word(rng);
+                Command::GenerateCert {
+                    domain: if rng.gen() { format!("*.{}", domain) } else { domain },
+                    days: rng.gen_range(1..1000),
+                    key_type: [KeyType::Rsa, KeyType::Ecdsa, KeyType::Ed25519][rng.gen_range(0..3)],
+                }
+            }
+            88 => Command::HardenSsh,
+            89 => {
+                let (key_type, bits) = match rng.gen_range(0..3) {
+                    0 => (KeyType::Rsa, rng.gen_range(1024..=16384)),
+                    1 => (KeyType::Ecdsa, [256, 384, 521][rng.gen_range(0..3)]),
+                    _ => (KeyType::Ed25519, 256),
+                };
+                Command::GenerateSshKey { key_type, bits, comment: t(rng) }
+            }
+            90 => Command::ProvisionCloud { provider: [CloudProvider::Aws, CloudProvider::Gcp, CloudProvider::Azure][rng.gen_range(0..3)], resources: rng.gen_range(1..20) },
+            91 => Command::RunPlaybook { playbook: t(rng), hosts: rng.gen_range(1..10) },
+            // СУБД приводится к нижнему регистру
+            92 => Command::InitDatabase { engine: random_word(rng), version: t(rng) },
+            93 => Command::RunMigrations { count: rng.gen_range(0..50) },
+            94 => Command::ShowLicense { id_or_path: if rng.gen() { LicenseSource::Spdx(t(rng)) } else { LicenseSource::File(t(rng)) } },
+            95 => Command::SpeedTest,
+            96 => Command::UpdateFirmware { component: t(rng), version: t(rng) },
+            97 => Command::WaitFor { what: t(rng), timeout_secs: rng.gen_range(1..60), expect_timeout: rng.gen() },
+            98 => Command::CheckSpace { path: format!("/{}", t(rng)), need: random_size(rng), force_fail: rng.gen() },
+            99 => Command::SelectMirror { country: ["DE", "US", "RU", "JP"][rng.gen_range(0..4)].to_string(), count: rng.gen_range(1..10) },
+            100 => {
+                let distros = [Distro::Apt, Distro::Dnf, Distro::Pacman, Distro::Zypper, Distro::Apk, Distro::Emerge];
+                Command::AddRepo { name: t(rng), flavor: rng.gen::<bool>().then(|| distros[rng.gen_range(0..distros.len())]) }
+            }
+            101 => Command::ImportKey((0..rng.gen_range(8..=40)).map(|_| b"0123456789ABCDEFabcdef"[rng.gen_range(0..22)] as char).collect()),
+            102 => Command::EncryptPartition { device: t(rng), cipher: t(rng), name: t(rng) },
+            103..=106 if depth < 2 => {
+                let body = (0..rng.gen_range(0..4)).map(|_| random_command(rng, depth + 1)).collect();
+                Command::Retry { attempts: rng.gen_range(1..5), body, body_lines: Vec::new() }
+            }
+            107..=110 => {
+                // parallel допускает только download, delay, run_test и install_dep
+                let children = (0..rng.gen_range(0..4))
+                    .map(|_| {
+                        let kind = [1, 4, 6, 26][rng.gen_range(0..4)];
+                        command_of_kind(rng, kind, depth)
+                    })
+                    .collect();
+                Command::Parallel(children)
+            }
+            111 => Command::FailRandom { message: t(rng), chance: rng.gen_range(0..=100), code: rng.gen_range(1..256) },
+            // Ячейки без '|': единственная ячейка с '|' делится на несколько
+            112..=114 => Command::Table {
+                title: t(rng),
+                rows: (0..rng.gen_range(0..4)).map(|_| (0..rng.gen_range(0..4)).map(|_| t(rng)).collect()).collect(),
+            },
+            115 | 116 => Command::Ask { prompt: t(rng), default: rng.gen(), required: rng.gen() },
+            117 | 118 => {
+                let options = random_words(rng, 4);
+                Command::Choose { prompt: t(rng), default: rng.gen_range(0..options.len()), options, var: t(rng) }
+            }
+            _ => Command::Message(t(rng)),
+        }
+    }
+
+    fn random_package(rng: &mut StdRng) -> Package {
+        let mut package = InstnothParser::new(String::new()).parse_package();
+        package.name = format!("{}{}", random_word(rng), random_text(rng));
+        package.version = random_text(rng);
+        package.description = random_text(rng);
+        package.author = random_text(rng);
+        package.license = random_text(rng);
+        package.homepage = random_text(rng);
+        package.arch = random_text(rng);
+        package.size = format!("{} MB", rng.gen_range(1..1000));
+        package.distro = rng.gen::<bool>().then_some(Distro::Pacman);
+        package.abort_on_error = rng.gen();
+        package.require_root = rng.gen();
+        for _ in 0..rng.gen_range(0..3) {
+            let constraint = ["", ">= 1.2", "<= 2", "= 3.0.1", "~ 1.4", "3.12"][rng.gen_range(0..6)];
+            package.depends.push(InstnothParser::parse_dependency(&format!("{}.instnoth {}", random_word(rng), constraint), 0).unwrap());
+        }
+        for _ in 0..rng.gen_range(0..3) {
+            package.provides.push(random_word(rng));
+            package.conflicts.push(random_word(rng));
+        }
+        for n in 0..rng.gen_range(0..4) {
+            let commands = (0..rng.gen_range(0..12)).map(|_| random_command(rng, 0)).collect::<Vec<_>>();
+            package.phases.push(Phase {
+                name: format!("{} {}", n, random_text(rng)),
+                command_lines: vec![0; commands.len()],
+                commands,
+                line: 0,
+                optional: rng.gen(),
+                weight: rng.gen_range(1..4),
+            });
+        }
+        package
+    }
+
+    #[test]
+    fn to_instnoth_round_trips_random_packages() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut keywords = HashSet::new();
+        for _ in 0..300 {
+            let package = random_package(&mut rng);
+            let source = package.to_instnoth();
+            let parsed = InstnothParser::new(source.clone()).parse().unwrap_or_else(|e| panic!("{}\n{}", e, source));
+            assert_eq!(meaning(&parsed), meaning(&package), "{}", source);
+            // Сериализатор пишет тот же канонический вид, что и fmt
+            assert_eq!(format_source(&source), source);
+            keywords.extend(source.lines().filter_map(|l| l.split_whitespace().next()).map(str::to_string));
+        }
+        let missing: Vec<_> = COMMANDS.iter().map(|spec| spec.keyword).filter(|k| !keywords.contains(*k)).collect();
+        assert!(missing.is_empty(), "команды без проверки: {:?}", missing);
+    }
+}
@@ -0,0 +1,30 @@
+//! InstNoth — симулятор установки, который ничего не устанавливает.
+//!
+//! Библиотека — всё, кроме разбора командной строки: программа `instnoth` только собирает
+//! из флагов [`sim::SimulatorOptions`] и вызывает то же, что доступно здесь. Файл установки
+//! разбирает [`parser::InstnothParser`], порядок пакетов по `depends:` даёт
+//! [`deps::DependencyManager`], показывает установку [`sim::Simulator`], а случайные, но
+//! правдоподобные сведения о системе берутся из [`sysinfo::RandomSystemInfo`]. Файлы в
+//! формате TOML разбирает тот же парсер, переводя их через [`toml_format`].
+//!
+//! ```
+//! use instnoth::parser::InstnothParser;
+//! use instnoth::sim::{Simulator, SimulatorOptions};
+//!
+//! let source = "package: \"demo\"\nversion: \"1.0\"\n\nphase \"Установка\" {\n    message \"Готово\"\n}\n";
+//! let package = InstnothParser::new(source.to_string()).parse().unwrap();
+//! assert_eq!(package.phases[0].name, "Установка");
+//!
+//! let mut simulator = Simulator::with_options(SimulatorOptions { quick: true, seed: Some(7), non_interactive: true, ..Default::default() });
+//! simulator.run(&package).unwrap();
+//! ```
+
+#[macro_use]
+pub mod output;
+pub mod deps;
+pub mod export;
+pub mod format;
+pub mod parser;
+pub mod sim;
+pub mod sysinfo;
+pub mod toml_format;
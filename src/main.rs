@@ -1,1564 +1,1322 @@
 use clap::Parser as ClapParser;
 use colored::*;
-use indicatif::{ProgressBar, ProgressStyle};
-use rand::Rng;
-use std::collections::{HashMap, HashSet};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::collections::HashSet;
+use std::ffi::OsString;
 use std::fs;
-use std::io::{self, Write};
+use std::io::{self, IsTerminal};
 use std::path::{Path, PathBuf};
-use std::thread;
+use std::sync::atomic::Ordering;
 use std::time::Duration;
+use instnoth::{deps::*, export::*, format::*, output::*, parser::*, say, sim::*, toml_format::*};
 
 /// InstNoth - Симулятор установки, который ничего не устанавливает
 #[derive(ClapParser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, long_about = None,
+    after_help = "Без подкоманды файлы устанавливаются, как с run: instnoth файл.instnoth --quick\nФлаги общие для всех подкоманд и пишутся до или после неё")]
 struct Args {
-    /// Путь к файлам установки (.instnoth) - можно указать несколько
-    #[arg(short, long, num_args = 1..)]
+    /// Путь к файлам установки (.instnoth) - можно указать несколько; "-" — прочитать из stdin,
+    /// каталог — все .instnoth в нём, шаблон вида "demos/**/*.instnoth" раскрывается сам
+    #[arg(global = true, short, long, num_args = 1..)]
     file: Option<Vec<PathBuf>>,
 
+    /// Искать .instnoth в каталогах из --file и во всех вложенных
+    #[arg(global = true, long, default_value_t = false)]
+    recursive: bool,
+
     /// Режим быстрой установки (без задержек)
-    #[arg(short, long, default_value_t = false)]
+    #[arg(global = true, short, long, default_value_t = false)]
     quick: bool,
 
     /// Подробный вывод
-    #[arg(short, long, default_value_t = false)]
+    #[arg(global = true, short, long, default_value_t = false)]
     verbose: bool,
 
+    /// Краткий вывод: только фазы с итогом и результат установки
+    #[arg(global = true, long, default_value_t = false, conflicts_with = "verbose")]
+    quiet: bool,
+
     /// Показать список встроенных файлов установки
-    #[arg(long, default_value_t = false)]
+    #[arg(global = true, long, default_value_t = false)]
     list_builtin: bool,
 
-    /// Пропустить установку зависимостей
-    #[arg(long, default_value_t = false)]
+    /// Удалить пакеты: фазы в обратном порядке с обратными операциями, зависимости — последними
+    #[arg(global = true, long, default_value_t = false)]
+    uninstall: bool,
+
+    /// Пошаговый режим: перед каждой командой показать её и ждать Enter (s — пропустить, p — пропустить фазу, q — прервать)
+    #[arg(global = true, long, default_value_t = false)]
+    step: bool,
+
+    /// Каждая команда сбоит с вероятностью PERCENT процентов (с --seed — воспроизводимо)
+    #[arg(global = true, long, value_name = "PERCENT", value_parser = clap::value_parser!(u8).range(0..=100))]
+    chaos: Option<u8>,
+
+    /// Сбой --chaos прерывает установку, а не повторяется
+    #[arg(global = true, long, default_value_t = false, requires = "chaos")]
+    chaos_fatal: bool,
+
+    /// Пропустить установку зависимостей (то же, что --deps-depth 0)
+    #[arg(global = true, long, default_value_t = false, conflicts_with = "deps_depth")]
     skip_deps: bool,
 
+    /// Брать зависимости не глубже N уровней: 0 — только указанные пакеты, 1 — и их прямые зависимости
+    #[arg(global = true, long, value_name = "N")]
+    deps_depth: Option<usize>,
+
+    /// Не прерывать установку, если файл зависимости не найден или не разбирается: пропустить его с предупреждением
+    #[arg(global = true, long, default_value_t = false)]
+    ignore_missing_deps: bool,
+
+    /// Ставить пакеты, даже если они конфликтуют (conflicts:) друг с другом или с установленными
+    #[arg(global = true, long, default_value_t = false)]
+    force_conflicts: bool,
+
+    /// Устанавливать пакеты, даже если та же или более новая версия уже установлена
+    #[arg(global = true, long, default_value_t = false, conflicts_with = "uninstall")]
+    reinstall: bool,
+
+    /// Сверить файлы и их зависимости с FILE.lock (instnoth lock) и не запускать установку, если что-то изменилось
+    #[arg(global = true, long, default_value_t = false)]
+    locked: bool,
+
+    /// Устанавливать до N пакетов одновременно; пакет начинается, когда установлены его зависимости
+    #[arg(global = true, long, value_name = "N", value_parser = clap::value_parser!(u8).range(1..), conflicts_with_all = ["uninstall", "step", "state_file"])]
+    parallel: Option<u8>,
+
+    /// Проверить файлы и их зависимости без запуска (вывод в формате file:line: severity: message)
+    #[arg(global = true, long, num_args = 1.., value_name = "FILE")]
+    lint: Option<Vec<PathBuf>>,
+
     /// Показать дерево зависимостей без установки
-    #[arg(long, default_value_t = false)]
+    #[arg(global = true, long, default_value_t = false)]
     show_deps: bool,
-}
 
-// ============== Структуры данных ==============
+    /// В --show-deps раскрывать зависимости не глубже N уровней
+    #[arg(global = true, long, value_name = "N")]
+    max_depth: Option<usize>,
 
-#[derive(Debug, Clone)]
-struct Package {
-    name: String,
-    version: String,
-    description: String,
-    author: String,
-    depends: Vec<String>,
-    phases: Vec<Phase>,
-    file_path: Option<PathBuf>,
-}
+    /// Показать уровни зависимостей без установки: пакеты одного уровня можно ставить параллельно
+    #[arg(global = true, long, default_value_t = false)]
+    plan: bool,
 
-#[derive(Debug, Clone)]
-struct Phase {
-    name: String,
-    commands: Vec<Command>,
-}
+    /// Показать план: пакеты, фазы и команды с разобранными параметрами — без выполнения
+    #[arg(global = true, long, default_value_t = false)]
+    dry_run: bool,
 
-#[derive(Debug, Clone)]
-enum Command {
-    Message(String),
-    Delay(u64),
-    Progress(u8),
-    CreateDir(String),
-    Download { url: String, size: u64 },
-    Extract { from: String, to: String },
-    InstallDep { name: String, version: String },
-    Configure { key: String, value: String },
-    Cleanup,
-    Success(String),
-    Error(String),
-    Warning(String),
-    CopyFile { from: String, to: String },
-    Symlink { from: String, to: String },
-    SetPermission { path: String, mode: String },
-    RunScript(String),
-    CheckDep(String),
-    WriteConfig { path: String, content: String },
-    DetectCpu,
-    DetectMemory,
-    DetectDisk,
-    DetectGpu,
-    DetectNetwork,
-    DetectOs,
-    DetectKernel,
-    DetectBios,
-    RunTest { name: String, duration: u64 },
-    LoadKernelModule(String),
-    UnloadKernelModule(String),
-    UpdateInitramfs,
-    UpdateGrub,
-    MountPartition { device: String, mount_point: String },
-    UnmountPartition(String),
-    FormatPartition { device: String, fs_type: String },
-    CreatePartition { device: String, size: String },
-    SetHostname(String),
-    SetTimezone(String),
-    SetLocale(String),
-    CreateUser { username: String, groups: String },
-    SetPassword(String),
-    EnableService(String),
-    DisableService(String),
-    StartService(String),
-    StopService(String),
-    InstallBootloader(String),
-    GenerateFstab,
-    CheckIntegrity(String),
-    VerifySignature(String),
-    CompileKernel { version: String },
-    InstallPackages(String),
-    UpdateSystem,
-    SyncTime,
-    TestHardware(String),
-    BenchmarkCpu,
-    BenchmarkMemory,
-    BenchmarkDisk,
-    NetworkConfig { interface: String, config: String },
-    FirewallRule(String),
-    ScanHardware,
-    DetectDrivers,
-    InstallDriver(String),
-}
+    /// Показать фазы файлов и число команд в них без установки
+    #[arg(global = true, long, default_value_t = false)]
+    list_phases: bool,
 
-// ============== Парсер ==============
+    /// Выполнить только фазы с этим именем (без учёта регистра, можно * и ?); можно указать несколько раз
+    #[arg(global = true, long, value_name = "NAME")]
+    only_phase: Vec<String>,
 
-struct InstnothParser {
-    content: String,
-    file_path: Option<PathBuf>,
-}
+    /// Пропустить фазы с этим именем (без учёта регистра, можно * и ?); можно указать несколько раз
+    #[arg(global = true, long, value_name = "NAME")]
+    skip_phase: Vec<String>,
 
-impl InstnothParser {
-    fn new(content: String) -> Self {
-        Self { content, file_path: None }
-    }
+    /// Минимальная установка (пропустить фазы с атрибутом optional)
+    #[arg(global = true, long, default_value_t = false)]
+    minimal: bool,
 
-    fn with_path(content: String, path: PathBuf) -> Self {
-        Self { content, file_path: Some(path) }
-    }
+    /// Не выполнять команды reboot
+    #[arg(global = true, long, default_value_t = false)]
+    no_reboot: bool,
 
-    fn parse(&mut self) -> Result<Package, String> {
-        let mut package = Package {
-            name: String::new(),
-            version: String::new(),
-            description: String::new(),
-            author: String::new(),
-            depends: Vec::new(),
-            phases: Vec::new(),
-            file_path: self.file_path.clone(),
-        };
+    /// Выбирать железо заново при каждой команде detect_*, бенчмарке и датчиках, а не одно на весь запуск
+    #[arg(global = true, long, default_value_t = false)]
+    fresh_hardware: bool,
 
-        let lines: Vec<&str> = self.content.lines().collect();
-        let mut i = 0;
+    /// Отвечать на ask/choose значениями по умолчанию, не спрашивая
+    #[arg(global = true, long, default_value_t = false)]
+    non_interactive: bool,
 
-        while i < lines.len() {
-            let line = lines[i].trim();
+    /// Спросить пароль sudo перед первой фазой, как будто установке нужны права root
+    #[arg(global = true, long, default_value_t = false)]
+    sudo: bool,
 
-            if line.is_empty() || line.starts_with('#') {
-                i += 1;
-                continue;
-            }
+    /// Считать сбои fail/fail_random и фатальные error предупреждениями и продолжать установку
+    #[arg(global = true, long, default_value_t = false)]
+    ignore_failures: bool,
 
-            if line.starts_with("package:") {
-                package.name = Self::extract_quoted_value(line)?;
-            } else if line.starts_with("version:") {
-                package.version = Self::extract_quoted_value(line)?;
-            } else if line.starts_with("description:") {
-                package.description = Self::extract_quoted_value(line)?;
-            } else if line.starts_with("author:") {
-                package.author = Self::extract_quoted_value(line)?;
-            } else if line.starts_with("depends:") {
-                // Парсим зависимости: depends: "file1.instnoth" "file2.instnoth"
-                // или depends: "file1.instnoth, file2.instnoth"
-                let deps_str = &line["depends:".len()..];
-                package.depends = Self::parse_depends(deps_str);
-            } else if line.starts_with("phase") {
-                let phase_name = Self::extract_phase_name(line)?;
-                let mut phase = Phase {
-                    name: phase_name,
-                    commands: Vec::new(),
-                };
-
-                if !line.contains('{') {
-                    i += 1;
-                    while i < lines.len() && !lines[i].contains('{') {
-                        i += 1;
-                    }
-                }
-                i += 1;
+    /// Строгий режим: дубликаты полей и фаз считаются ошибками
+    #[arg(global = true, long, default_value_t = false)]
+    strict: bool,
 
-                while i < lines.len() {
-                    let cmd_line = lines[i].trim();
-                    if cmd_line == "}" || cmd_line.starts_with('}') {
-                        break;
-                    }
-                    if !cmd_line.is_empty() && !cmd_line.starts_with('#') {
-                        if let Ok(cmd) = self.parse_command(cmd_line) {
-                            phase.commands.push(cmd);
-                        }
-                    }
-                    i += 1;
-                }
+    /// Только проверить файлы (значения команд и параметры) без запуска установки
+    #[arg(global = true, long, default_value_t = false)]
+    validate: bool,
 
-                package.phases.push(phase);
-            }
+    /// Максимально допустимая задержка delay/duration в миллисекундах
+    #[arg(global = true, long, default_value_t = 60_000)]
+    max_delay: u64,
 
-            i += 1;
-        }
+    /// Не выводить сводку времени по фазам после установки
+    #[arg(global = true, long, default_value_t = false)]
+    no_summary: bool,
 
-        if package.name.is_empty() {
-            return Err("Не указано имя пакета".to_string());
-        }
+    /// Не выводить шуточную карточку итогов с достижением после установки
+    #[arg(global = true, long, default_value_t = false)]
+    no_fun: bool,
 
-        Ok(package)
-    }
+    /// Начальное значение генератора случайных чисел: одинаковый seed и файл дают одинаковый вывод
+    #[arg(global = true, long)]
+    seed: Option<u64>,
 
-    fn parse_depends(deps_str: &str) -> Vec<String> {
-        let mut deps = Vec::new();
-        let mut current = String::new();
-        let mut in_quotes = false;
+    /// Записывать прогресс в FILE после каждой фазы; если файл остался от прерванного запуска — предложить продолжить
+    #[arg(global = true, long, value_name = "FILE", conflicts_with = "uninstall")]
+    state_file: Option<PathBuf>,
 
-        for c in deps_str.chars() {
-            match c {
-                '"' => {
-                    if in_quotes {
-                        if !current.trim().is_empty() {
-                            deps.push(current.trim().to_string());
-                        }
-                        current = String::new();
-                    }
-                    in_quotes = !in_quotes;
-                }
-                ',' if !in_quotes => {
-                    if !current.trim().is_empty() {
-                        deps.push(current.trim().to_string());
-                    }
-                    current = String::new();
-                }
-                _ if in_quotes => {
-                    current.push(c);
-                }
-                _ => {}
-            }
-        }
+    /// Начать заново, не глядя на сохранённое в --state-file состояние
+    #[arg(global = true, long, default_value_t = false, requires = "state_file")]
+    restart: bool,
 
-        if !current.trim().is_empty() {
-            deps.push(current.trim().to_string());
-        }
+    /// Каталог со списком установленных пакетов (installed.json); по умолчанию
+    /// $XDG_DATA_HOME/instnoth или ~/.local/share/instnoth
+    #[arg(global = true, long, value_name = "DIR")]
+    state_dir: Option<PathBuf>,
 
-        deps
-    }
+    /// Формат вывода: text — для терминала, json — по событию в строке для других программ
+    #[arg(global = true, long, value_enum, default_value_t = OutputFormat::Text)]
+    output: OutputFormat,
 
-    fn extract_quoted_value(line: &str) -> Result<String, String> {
-        if let Some(start) = line.find('"') {
-            if let Some(end) = line[start + 1..].find('"') {
-                return Ok(line[start + 1..start + 1 + end].to_string());
-            }
-        }
-        Err(format!("Не удалось извлечь значение из: {}", line))
-    }
+    /// Множитель скорости пауз и анимаций: 2 — вдвое быстрее, 0.5 — вдвое медленнее (--quick — без пауз)
+    #[arg(global = true, long, default_value_t = 1.0, value_parser = parse_speed)]
+    speed: f32,
 
-    fn extract_phase_name(line: &str) -> Result<String, String> {
-        if let Some(start) = line.find('"') {
-            if let Some(end) = line[start + 1..].find('"') {
-                return Ok(line[start + 1..start + 1 + end].to_string());
-            }
-        }
-        Err("Не удалось извлечь имя фазы".to_string())
-    }
+    /// Без цветов (так же действует переменная окружения NO_COLOR)
+    #[arg(global = true, long, default_value_t = false)]
+    no_color: bool,
 
-    fn parse_command(&self, line: &str) -> Result<Command, String> {
-        let parts: Vec<&str> = line.splitn(2, ' ').collect();
-        let cmd = parts[0];
-        let args = if parts.len() > 1 { parts[1] } else { "" };
+    /// Заменять эмодзи и псевдографику символами ASCII
+    #[arg(global = true, long, default_value_t = false)]
+    ascii: bool,
 
-        match cmd {
-            "message" => Ok(Command::Message(Self::extract_quoted_value(line)?)),
-            "delay" => {
-                let ms: u64 = args.trim().parse().unwrap_or(100);
-                Ok(Command::Delay(ms))
-            }
-            "progress" => {
-                let pct: u8 = args.trim().parse().unwrap_or(0);
-                Ok(Command::Progress(pct))
-            }
-            "create_dir" => Ok(Command::CreateDir(Self::extract_quoted_value(line)?)),
-            "download" => {
-                let url = Self::extract_quoted_value(line)?;
-                let size = self.extract_param(args, "size").unwrap_or(1024);
-                Ok(Command::Download { url, size })
-            }
-            "extract" => {
-                let from = Self::extract_quoted_value(line)?;
-                let to = self.extract_string_param(args, "to").unwrap_or_default();
-                Ok(Command::Extract { from, to })
-            }
-            "install_dep" => {
-                let name = Self::extract_quoted_value(line)?;
-                let version = self.extract_string_param(args, "version").unwrap_or("latest".to_string());
-                Ok(Command::InstallDep { name, version })
-            }
-            "configure" => {
-                let key = self.extract_string_param(args, "key").unwrap_or_default();
-                let value = self.extract_string_param(args, "value").unwrap_or_default();
-                Ok(Command::Configure { key, value })
-            }
-            "cleanup" => Ok(Command::Cleanup),
-            "success" => Ok(Command::Success(Self::extract_quoted_value(line)?)),
-            "error" => Ok(Command::Error(Self::extract_quoted_value(line)?)),
-            "warning" => Ok(Command::Warning(Self::extract_quoted_value(line)?)),
-            "copy_file" => {
-                let from = Self::extract_quoted_value(line)?;
-                let to = self.extract_string_param(args, "to").unwrap_or_default();
-                Ok(Command::CopyFile { from, to })
-            }
-            "symlink" => {
-                let from = Self::extract_quoted_value(line)?;
-                let to = self.extract_string_param(args, "to").unwrap_or_default();
-                Ok(Command::Symlink { from, to })
-            }
-            "set_permission" => {
-                let path = Self::extract_quoted_value(line)?;
-                let mode = self.extract_string_param(args, "mode").unwrap_or("755".to_string());
-                Ok(Command::SetPermission { path, mode })
-            }
-            "run_script" => Ok(Command::RunScript(Self::extract_quoted_value(line)?)),
-            "check_dep" => Ok(Command::CheckDep(Self::extract_quoted_value(line)?)),
-            "write_config" => {
-                let path = Self::extract_quoted_value(line)?;
-                let content = self.extract_string_param(args, "content").unwrap_or_default();
-                Ok(Command::WriteConfig { path, content })
-            }
-            "detect_cpu" => Ok(Command::DetectCpu),
-            "detect_memory" => Ok(Command::DetectMemory),
-            "detect_disk" => Ok(Command::DetectDisk),
-            "detect_gpu" => Ok(Command::DetectGpu),
-            "detect_network" => Ok(Command::DetectNetwork),
-            "detect_os" => Ok(Command::DetectOs),
-            "detect_kernel" => Ok(Command::DetectKernel),
-            "detect_bios" => Ok(Command::DetectBios),
-            "run_test" => {
-                let name = Self::extract_quoted_value(line)?;
-                let duration = self.extract_param(args, "duration").unwrap_or(1000);
-                Ok(Command::RunTest { name, duration })
-            }
-            "load_module" => Ok(Command::LoadKernelModule(Self::extract_quoted_value(line)?)),
-            "unload_module" => Ok(Command::UnloadKernelModule(Self::extract_quoted_value(line)?)),
-            "update_initramfs" => Ok(Command::UpdateInitramfs),
-            "update_grub" => Ok(Command::UpdateGrub),
-            "mount" => {
-                let device = Self::extract_quoted_value(line)?;
-                let mount_point = self.extract_string_param(args, "to").unwrap_or_default();
-                Ok(Command::MountPartition { device, mount_point })
-            }
-            "unmount" => Ok(Command::UnmountPartition(Self::extract_quoted_value(line)?)),
-            "format" => {
-                let device = Self::extract_quoted_value(line)?;
-                let fs_type = self.extract_string_param(args, "fs").unwrap_or("ext4".to_string());
-                Ok(Command::FormatPartition { device, fs_type })
-            }
-            "create_partition" => {
-                let device = Self::extract_quoted_value(line)?;
-                let size = self.extract_string_param(args, "size").unwrap_or("100%".to_string());
-                Ok(Command::CreatePartition { device, size })
-            }
-            "set_hostname" => Ok(Command::SetHostname(Self::extract_quoted_value(line)?)),
-            "set_timezone" => Ok(Command::SetTimezone(Self::extract_quoted_value(line)?)),
-            "set_locale" => Ok(Command::SetLocale(Self::extract_quoted_value(line)?)),
-            "create_user" => {
-                let username = Self::extract_quoted_value(line)?;
-                let groups = self.extract_string_param(args, "groups").unwrap_or("users".to_string());
-                Ok(Command::CreateUser { username, groups })
-            }
-            "set_password" => Ok(Command::SetPassword(Self::extract_quoted_value(line)?)),
-            "enable_service" => Ok(Command::EnableService(Self::extract_quoted_value(line)?)),
-            "disable_service" => Ok(Command::DisableService(Self::extract_quoted_value(line)?)),
-            "start_service" => Ok(Command::StartService(Self::extract_quoted_value(line)?)),
-            "stop_service" => Ok(Command::StopService(Self::extract_quoted_value(line)?)),
-            "install_bootloader" => Ok(Command::InstallBootloader(Self::extract_quoted_value(line)?)),
-            "generate_fstab" => Ok(Command::GenerateFstab),
-            "check_integrity" => Ok(Command::CheckIntegrity(Self::extract_quoted_value(line)?)),
-            "verify_signature" => Ok(Command::VerifySignature(Self::extract_quoted_value(line)?)),
-            "compile_kernel" => {
-                let version = Self::extract_quoted_value(line)?;
-                Ok(Command::CompileKernel { version })
-            }
-            "install_packages" => Ok(Command::InstallPackages(Self::extract_quoted_value(line)?)),
-            "update_system" => Ok(Command::UpdateSystem),
-            "sync_time" => Ok(Command::SyncTime),
-            "test_hardware" => Ok(Command::TestHardware(Self::extract_quoted_value(line)?)),
-            "benchmark_cpu" => Ok(Command::BenchmarkCpu),
-            "benchmark_memory" => Ok(Command::BenchmarkMemory),
-            "benchmark_disk" => Ok(Command::BenchmarkDisk),
-            "network_config" => {
-                let interface = Self::extract_quoted_value(line)?;
-                let config = self.extract_string_param(args, "config").unwrap_or("dhcp".to_string());
-                Ok(Command::NetworkConfig { interface, config })
-            }
-            "firewall_rule" => Ok(Command::FirewallRule(Self::extract_quoted_value(line)?)),
-            "scan_hardware" => Ok(Command::ScanHardware),
-            "detect_drivers" => Ok(Command::DetectDrivers),
-            "install_driver" => Ok(Command::InstallDriver(Self::extract_quoted_value(line)?)),
-            _ => Err(format!("Неизвестная команда: {}", cmd)),
-        }
-    }
+    /// Тема оформления: встроенная (default, plain) или файл .toml с цветами и значками
+    #[arg(global = true, long, value_name = "NAME|FILE")]
+    theme: Option<String>,
 
-    fn extract_param(&self, args: &str, name: &str) -> Option<u64> {
-        let pattern = format!("{}=", name);
-        if let Some(pos) = args.find(&pattern) {
-            let start = pos + pattern.len();
-            let rest = &args[start..];
-            let end = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
-            rest[..end].parse().ok()
-        } else {
-            None
-        }
+    /// Язык вывода: ru или en; по умолчанию — по переменной LANG
+    #[arg(global = true, long, value_enum)]
+    lang: Option<Lang>,
+
+    /// Ширина рамок и разделителей в символах; по умолчанию — ширина терминала
+    #[arg(global = true, long, value_name = "COLS", value_parser = clap::value_parser!(u16).range(40..=400))]
+    width: Option<u16>,
+
+    /// Записать весь вывод установки в файл: без цветов, с отметкой времени у каждой строки
+    #[arg(global = true, long, value_name = "FILE")]
+    log_file: Option<PathBuf>,
+
+    /// Загружать файлы по https-адресам без проверки сертификата
+    #[arg(global = true, long, default_value_t = false)]
+    insecure: bool,
+
+    /// Сколько секунд ждать загрузки файла по адресу
+    #[arg(global = true, long, value_name = "SECS", default_value_t = 30, value_parser = clap::value_parser!(u64).range(1..))]
+    fetch_timeout: u64,
+
+    /// Записать вывод в FILE как запись asciinema (asciicast v2) — с цветами и настоящими паузами
+    #[arg(global = true, long, value_name = "FILE", conflicts_with = "output")]
+    record: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<CliCommand>,
+}
+
+/// Подкоманды; без подкоманды instnoth устанавливает пакеты из --file
+#[derive(clap::Subcommand, Debug)]
+enum CliCommand {
+    /// Установить пакеты из файлов (то же, что --file)
+    Run {
+        /// Файлы установки — всё, что принимает --file
+        #[arg(value_name = "FILE")]
+        files: Vec<PathBuf>,
+    },
+    /// Разобрать и проверить файлы без установки (то же, что --validate)
+    Check {
+        /// Файлы установки — всё, что принимает --file
+        #[arg(value_name = "FILE")]
+        files: Vec<PathBuf>,
+    },
+    /// Показать дерево зависимостей (то же, что --show-deps)
+    Deps {
+        /// Файлы установки — всё, что принимает --file
+        #[arg(value_name = "FILE")]
+        files: Vec<PathBuf>,
+        /// Вывести граф текстом для Graphviz или Mermaid вместо дерева
+        #[arg(long, value_enum)]
+        format: Option<GraphFormat>,
+        /// Записать граф в FILE (по умолчанию — в stdout)
+        #[arg(short = 'o', long = "out", value_name = "FILE", requires = "format")]
+        out: Option<PathBuf>,
+    },
+    /// Показать встроенные файлы установки (то же, что --list-builtin)
+    List {
+        /// Показать установленные пакеты: версию, исходный файл и время установки
+        #[arg(long, default_value_t = false)]
+        installed: bool,
+    },
+    /// Убрать пакеты из списка установленных; с --uninstall — сначала показать их удаление
+    Remove {
+        /// Имена пакетов (без учёта регистра)
+        #[arg(required = true, value_name = "NAME")]
+        names: Vec<String>,
+    },
+    /// Привести файлы к единому виду: отступы, кавычки и пустые строки; комментарии сохраняются
+    Fmt {
+        /// Файлы установки — всё, что принимает --file
+        #[arg(value_name = "FILE")]
+        files: Vec<PathBuf>,
+        /// Ничего не менять, только проверить: код выхода 1, если какой-то файл изменился бы
+        #[arg(long, default_value_t = false)]
+        check: bool,
+    },
+    /// Сохранить установку как самостоятельный скрипт: запускается там, где instnoth нет
+    Export {
+        /// Файлы установки — всё, что принимает --file
+        #[arg(value_name = "FILE")]
+        files: Vec<PathBuf>,
+        /// Язык скрипта
+        #[arg(long, value_enum, default_value_t = ScriptFormat::Sh)]
+        format: ScriptFormat,
+        /// Записать скрипт в FILE (по умолчанию — в stdout)
+        #[arg(short = 'o', long = "out", value_name = "FILE")]
+        out: Option<PathBuf>,
+    },
+    /// Создать NAME.instnoth из заготовки; версия — сегодняшняя дата, автор — $USER
+    New {
+        /// Имя пакета и файла
+        #[arg(required_unless_present = "list_templates")]
+        name: Option<String>,
+        /// Заготовка (список — --list-templates)
+        #[arg(long, default_value = "minimal", value_parser = clap::builder::PossibleValuesParser::new(templates::names()))]
+        template: String,
+        /// Перезаписать существующий файл
+        #[arg(long, default_value_t = false)]
+        force: bool,
+        /// Показать заготовки с описаниями
+        #[arg(long, default_value_t = false)]
+        list_templates: bool,
+    },
+    /// Записать рядом с каждым файлом FILE.lock: его зависимости с версиями и SHA-256 содержимого (для run --locked)
+    Lock {
+        /// Файлы установки на диске
+        #[arg(value_name = "FILE")]
+        files: Vec<PathBuf>,
+    },
+    /// Описание всех команд языка в JSON: параметры, их типы и значения по умолчанию
+    Schema,
+    /// Напечатать скрипт автодополнения для оболочки (имена фаз для --only-phase и
+    /// --skip-phase берутся из файла после --file)
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+    /// Имена фаз файла по одному в строке — для скриптов автодополнения
+    #[command(name = "__complete-phases", hide = true)]
+    CompletePhases { file: PathBuf },
+    /// Первое слово не подкоманда — значит, файл: `instnoth файл.instnoth ...`
+    #[command(external_subcommand)]
+    External(Vec<OsString>),
+}
+
+impl Args {
+    /// Разбор командной строки; без подкоманды она подставляется как `run` перед
+    /// первым файлом, и остальные аргументы разбираются как обычно
+    fn parse_cli() -> Args {
+        let args = Args::parse();
+        let Some(CliCommand::External(rest)) = &args.command else {
+            return args;
+        };
+        let mut argv: Vec<OsString> = std::env::args_os().collect();
+        argv.insert(argv.len() - rest.len(), "run".into());
+        Args::parse_from(argv)
     }
 
-    fn extract_string_param(&self, args: &str, name: &str) -> Option<String> {
-        let pattern = format!("{}=\"", name);
-        if let Some(pos) = args.find(&pattern) {
-            let start = pos + pattern.len();
-            let rest = &args[start..];
-            if let Some(end) = rest.find('"') {
-                return Some(rest[..end].to_string());
-            }
+    /// Файлы подкоманды — перед указанными через --file
+    fn add_files(&mut self, files: Vec<PathBuf>) {
+        let rest = self.file.take().unwrap_or_default();
+        if !files.is_empty() || !rest.is_empty() {
+            self.file = Some(files.into_iter().chain(rest).collect());
         }
-        None
     }
 }
 
-// ============== Генераторы случайных данных ==============
-
-struct RandomSystemInfo;
-
-impl RandomSystemInfo {
-    fn cpu() -> (&'static str, &'static str, u32, u32) {
-        let mut rng = rand::thread_rng();
-        let cpus = [
-            ("Intel", "Core i9-13900K", 24, 5800),
-            ("Intel", "Core i7-12700K", 12, 5000),
-            ("Intel", "Core i5-13600K", 14, 5100),
-            ("Intel", "Xeon E5-2699 v4", 22, 3600),
-            ("AMD", "Ryzen 9 7950X", 16, 5700),
-            ("AMD", "Ryzen 7 7800X3D", 8, 5000),
-            ("AMD", "Ryzen 5 7600X", 6, 5300),
-            ("AMD", "EPYC 7742", 64, 3400),
-            ("AMD", "Threadripper 3990X", 64, 4300),
-            ("Apple", "M2 Ultra", 24, 3500),
-        ];
-        let idx = rng.gen_range(0..cpus.len());
-        cpus[idx]
-    }
-
-    fn memory() -> (u64, &'static str, u32) {
-        let mut rng = rand::thread_rng();
-        let configs = [
-            (8, "DDR4", 2666),
-            (16, "DDR4", 3200),
-            (32, "DDR4", 3600),
-            (32, "DDR5", 4800),
-            (64, "DDR5", 5600),
-            (128, "DDR5", 6000),
-            (16, "DDR5", 5200),
-            (64, "DDR4", 3200),
-        ];
-        let idx = rng.gen_range(0..configs.len());
-        configs[idx]
-    }
-
-    fn disk() -> (&'static str, &'static str, u64, &'static str) {
-        let mut rng = rand::thread_rng();
-        let disks = [
-            ("Samsung", "990 PRO", 2000, "NVMe"),
-            ("Samsung", "870 EVO", 1000, "SATA"),
-            ("WD", "Black SN850X", 2000, "NVMe"),
-            ("WD", "Blue SN570", 500, "NVMe"),
-            ("Seagate", "Barracuda", 2000, "HDD"),
-            ("Crucial", "MX500", 1000, "SATA"),
-            ("Kingston", "NV2", 1000, "NVMe"),
-            ("Toshiba", "X300", 4000, "HDD"),
-            ("Intel", "Optane 905P", 960, "NVMe"),
-        ];
-        let idx = rng.gen_range(0..disks.len());
-        disks[idx]
-    }
-
-    fn gpu() -> (&'static str, &'static str, u32) {
-        let mut rng = rand::thread_rng();
-        let gpus = [
-            ("NVIDIA", "GeForce RTX 4090", 24),
-            ("NVIDIA", "GeForce RTX 4080", 16),
-            ("NVIDIA", "GeForce RTX 4070 Ti", 12),
-            ("NVIDIA", "GeForce RTX 3080", 10),
-            ("AMD", "Radeon RX 7900 XTX", 24),
-            ("AMD", "Radeon RX 7800 XT", 16),
-            ("AMD", "Radeon RX 6800", 16),
-            ("Intel", "Arc A770", 16),
-            ("Intel", "Arc A380", 6),
-            ("NVIDIA", "Quadro RTX 8000", 48),
-        ];
-        let idx = rng.gen_range(0..gpus.len());
-        gpus[idx]
-    }
-
-    fn network() -> (&'static str, &'static str, &'static str) {
-        let mut rng = rand::thread_rng();
-        let nics = [
-            ("Intel", "I225-V 2.5GbE", "2.5 Gbps"),
-            ("Intel", "X710 10GbE", "10 Gbps"),
-            ("Realtek", "RTL8125", "2.5 Gbps"),
-            ("Realtek", "RTL8111", "1 Gbps"),
-            ("Broadcom", "BCM57416", "10 Gbps"),
-            ("Mellanox", "ConnectX-6", "100 Gbps"),
-            ("Intel", "Wi-Fi 6E AX211", "2.4 Gbps"),
-            ("Qualcomm", "Atheros AR9485", "300 Mbps"),
-        ];
-        let idx = rng.gen_range(0..nics.len());
-        nics[idx]
-    }
-
-    fn bios() -> (&'static str, &'static str, &'static str) {
-        let mut rng = rand::thread_rng();
-        let bioses = [
-            ("American Megatrends", "UEFI", "3.5.2"),
-            ("Phoenix", "UEFI", "2.1.0"),
-            ("Insyde", "UEFI", "5.0"),
-            ("Award", "Legacy BIOS", "6.0"),
-            ("AMI", "Aptio V", "1.24"),
-            ("Dell", "UEFI", "2.8.1"),
-            ("HP", "UEFI", "F.47"),
-            ("Lenovo", "UEFI", "N24ET82W"),
-        ];
-        let idx = rng.gen_range(0..bioses.len());
-        bioses[idx]
-    }
-
-    fn kernel() -> &'static str {
-        let mut rng = rand::thread_rng();
-        let kernels = [
-            "6.6.8-arch1-1",
-            "6.5.0-14-generic",
-            "6.1.52-gentoo",
-            "5.15.0-91-generic",
-            "6.6.6-200.fc39.x86_64",
-            "6.4.12-1-MANJARO",
-            "5.10.0-27-amd64",
-            "6.2.16-300.fc38.x86_64",
-        ];
-        let idx = rng.gen_range(0..kernels.len());
-        kernels[idx]
-    }
-
-    fn os() -> (&'static str, &'static str) {
-        let mut rng = rand::thread_rng();
-        let systems = [
-            ("Ubuntu", "22.04.3 LTS (Jammy Jellyfish)"),
-            ("Fedora", "39 (Workstation Edition)"),
-            ("Debian", "12 (Bookworm)"),
-            ("Arch Linux", "Rolling Release"),
-            ("openSUSE", "Tumbleweed"),
-            ("Linux Mint", "21.2 (Victoria)"),
-            ("Pop!_OS", "22.04 LTS"),
-            ("Manjaro", "23.1 (Vulcan)"),
-            ("CentOS Stream", "9"),
-            ("Rocky Linux", "9.3"),
-        ];
-        let idx = rng.gen_range(0..systems.len());
-        systems[idx]
-    }
-
-    fn mac_address() -> String {
-        let mut rng = rand::thread_rng();
-        format!(
-            "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
-            rng.gen::<u8>(), rng.gen::<u8>(), rng.gen::<u8>(),
-            rng.gen::<u8>(), rng.gen::<u8>(), rng.gen::<u8>()
-        )
-    }
-
-    fn ip_address() -> String {
-        let mut rng = rand::thread_rng();
-        format!(
-            "192.168.{}.{}",
-            rng.gen_range(0..255),
-            rng.gen_range(1..254)
-        )
-    }
+/// Язык скрипта для `instnoth export`
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+enum ScriptFormat {
+    /// bash: echo, sleep и tput для цветов
+    Sh,
 }
 
-// ============== Менеджер зависимостей ==============
+/// Формат графа для `instnoth deps --format`
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+enum GraphFormat {
+    /// Graphviz DOT
+    Dot,
+    /// Mermaid flowchart
+    Mermaid,
+}
 
-struct DependencyManager {
-    base_path: PathBuf,
-    installed: HashSet<String>,
+/// Оболочка для `instnoth completions`
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+enum Shell {
+    Bash,
+    Zsh,
+    Fish,
 }
 
-impl DependencyManager {
-    fn new(base_path: PathBuf) -> Self {
-        Self {
-            base_path,
-            installed: HashSet::new(),
-        }
-    }
+// ============== Автодополнение ==============
 
-    fn resolve_path(&self, dep_path: &str) -> PathBuf {
-        let path = Path::new(dep_path);
-        if path.is_absolute() {
-            path.to_path_buf()
-        } else {
-            self.base_path.join(path)
-        }
-    }
+/// Флаги, значения которых — имена фаз файла из --file
+const PHASE_FLAGS: [&str; 2] = ["only-phase", "skip-phase"];
 
-    fn load_package(&self, path: &Path) -> Result<Package, String> {
-        let content = fs::read_to_string(path)
-            .map_err(|e| format!("Не удалось прочитать файл {:?}: {}", path, e))?;
-        
-        let mut parser = InstnothParser::with_path(content, path.to_path_buf());
-        parser.parse()
+/// Что предлагать в качестве значения флага или аргумента подкоманды
+enum CompletionValue {
+    /// Флаг без значения
+    Flag,
+    Phases,
+    Choices(Vec<String>),
+    Path,
+    Any,
+}
+
+fn completion_value(arg: &clap::Arg) -> CompletionValue {
+    if !arg.get_action().takes_values() {
+        return CompletionValue::Flag;
+    }
+    if arg.get_long().is_some_and(|long| PHASE_FLAGS.contains(&long)) {
+        return CompletionValue::Phases;
+    }
+    let choices: Vec<String> = arg.get_possible_values().iter()
+        .filter(|v| !v.is_hide_set())
+        .map(|v| v.get_name().to_string())
+        .collect();
+    if !choices.is_empty() {
+        return CompletionValue::Choices(choices);
     }
+    match arg.get_value_hint() {
+        clap::ValueHint::AnyPath | clap::ValueHint::FilePath | clap::ValueHint::DirPath => CompletionValue::Path,
+        _ => CompletionValue::Any,
+    }
+}
 
-    fn get_install_order(&mut self, packages: &[Package]) -> Result<Vec<Package>, String> {
-        let mut order = Vec::new();
-        let mut visited = HashSet::new();
-        let mut in_stack = HashSet::new();
+/// Видимые флаги команды вместе с -h/-V, которые clap добавляет при сборке
+fn completion_args(cmd: &clap::Command) -> Vec<&clap::Arg> {
+    cmd.get_arguments().filter(|a| !a.is_hide_set() && !a.is_positional()).collect()
+}
+
+/// Видимая подкоманда: имя, описание, что дополнять в её аргументе и можно ли их несколько
+struct CompletionSubcommand {
+    name: String,
+    about: String,
+    value: CompletionValue,
+    many: bool,
+}
 
-        for pkg in packages {
-            self.visit_package(pkg, &mut order, &mut visited, &mut in_stack)?;
+fn completion_subcommands(cmd: &clap::Command) -> Vec<CompletionSubcommand> {
+    cmd.get_subcommands().filter(|c| !c.is_hide_set()).map(|c| {
+        let positional = c.get_positionals().next();
+        CompletionSubcommand {
+            name: c.get_name().to_string(),
+            about: help_line(c.get_about().map(|h| h.to_string())),
+            value: positional.map_or(CompletionValue::Any, completion_value),
+            many: positional.is_some_and(|a| matches!(a.get_action(), clap::ArgAction::Append)),
         }
+    }).collect()
+}
 
-        Ok(order)
-    }
+/// Справка одной строкой: переносы из doc-комментариев не нужны в описаниях оболочек
+fn help_line(help: Option<String>) -> String {
+    help.unwrap_or_default().split_whitespace().collect::<Vec<_>>().join(" ")
+}
 
-    fn visit_package(
-        &self,
-        pkg: &Package,
-        order: &mut Vec<Package>,
-        visited: &mut HashSet<String>,
-        in_stack: &mut HashSet<String>,
-    ) -> Result<(), String> {
-        let pkg_id = pkg.name.clone();
+fn option_words(arg: &clap::Arg) -> Vec<String> {
+    arg.get_short().map(|s| format!("-{}", s)).into_iter()
+        .chain(arg.get_long().map(|l| format!("--{}", l)))
+        .collect()
+}
 
-        if in_stack.contains(&pkg_id) {
-            return Err(format!("Обнаружена циклическая зависимость: {}", pkg_id));
-        }
+/// Скрипт автодополнения: флаги и их значения — из описания Args, имена фаз — от
+/// `instnoth __complete-phases <файл>` во время дополнения
+fn completion_script(shell: Shell) -> String {
+    let mut cmd = <Args as clap::CommandFactory>::command();
+    cmd.build();
+    match shell {
+        Shell::Bash => bash_completion(&cmd),
+        Shell::Zsh => zsh_completion(&cmd),
+        Shell::Fish => fish_completion(&cmd),
+    }
+}
 
-        if visited.contains(&pkg_id) {
-            return Ok(());
+fn bash_completion(cmd: &clap::Command) -> String {
+    let name = cmd.get_name();
+    let reply = |value: &CompletionValue| match value {
+        CompletionValue::Phases => format!("_{}_phases", name),
+        CompletionValue::Choices(choices) => format!("COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))", choices.join(" ")),
+        CompletionValue::Path => "COMPREPLY=($(compgen -f -- \"$cur\"))".to_string(),
+        CompletionValue::Flag | CompletionValue::Any => "COMPREPLY=()".to_string(),
+    };
+    let mut words = Vec::new();
+    let mut cases = String::new();
+    for arg in completion_args(cmd) {
+        let names = option_words(arg);
+        let value = completion_value(arg);
+        if !matches!(value, CompletionValue::Flag) {
+            cases += &format!("        {})\n            {}\n            return ;;\n", names.join("|"), reply(&value));
         }
+        words.extend(names);
+    }
+    let subcommands = completion_subcommands(cmd);
+    for sub in &subcommands {
+        if !matches!(sub.value, CompletionValue::Any) {
+            cases += &format!("        {})\n            {}\n            return ;;\n", sub.name, reply(&sub.value));
+        }
+    }
+    let subcommands: Vec<&str> = subcommands.iter().map(|sub| sub.name.as_str()).collect();
+    format!(r#"# Автодополнение {name} для bash: source <({name} completions bash)
+
+_{name}_phases() {{
+    local i file=
+    for ((i = 1; i < COMP_CWORD; i++)); do
+        case "${{COMP_WORDS[i]}}" in
+            -f|--file) [ -n "$file" ] || file="${{COMP_WORDS[i+1]}}" ;;
+            *.instnoth|builtin:*) [ -n "$file" ] || file="${{COMP_WORDS[i]}}" ;;
+        esac
+    done
+    [ -n "$file" ] || return
+    # Имена фаз бывают с пробелами: по слову на строку, экранирует readline
+    local IFS=$'\n'
+    compopt -o filenames 2>/dev/null
+    COMPREPLY=($(compgen -W "$({name} __complete-phases "$file" 2>/dev/null)" -- "$cur"))
+}}
+
+_{name}() {{
+    local cur="${{COMP_WORDS[COMP_CWORD]}}" prev="${{COMP_WORDS[COMP_CWORD-1]}}"
+    case "$prev" in
+{cases}    esac
+    if [[ "$cur" == -* ]]; then
+        COMPREPLY=($(compgen -W "{words}" -- "$cur"))
+    elif [ "$COMP_CWORD" -eq 1 ]; then
+        COMPREPLY=($(compgen -W "{subcommands}" -- "$cur") $(compgen -f -- "$cur"))
+    else
+        COMPREPLY=($(compgen -f -- "$cur"))
+    fi
+}}
+
+complete -F _{name} {name}
+"#, words = words.join(" "), subcommands = subcommands.join(" "))
+}
 
-        in_stack.insert(pkg_id.clone());
-
-        // Обрабатываем зависимости
-        for dep_path in &pkg.depends {
-            let full_path = self.resolve_path(dep_path);
-            if let Ok(dep_pkg) = self.load_package(&full_path) {
-                self.visit_package(&dep_pkg, order, visited, in_stack)?;
-            } else {
-                eprintln!("{} Не удалось загрузить зависимость: {}", "⚠".yellow(), dep_path);
+fn zsh_completion(cmd: &clap::Command) -> String {
+    let name = cmd.get_name();
+    let quote = |text: &str| text.replace('\'', "'\\''").replace('[', "\\[").replace(']', "\\]").replace(':', "\\:");
+    let action = |value: &CompletionValue| match value {
+        CompletionValue::Phases => format!("_{}_phases", name),
+        CompletionValue::Choices(choices) => format!("({})", choices.join(" ")),
+        CompletionValue::Path => "_files".to_string(),
+        CompletionValue::Flag | CompletionValue::Any => " ".to_string(),
+    };
+    let mut specs = String::new();
+    for arg in completion_args(cmd) {
+        let help = quote(&help_line(arg.get_help().map(|h| h.to_string())));
+        let repeat = if matches!(arg.get_action(), clap::ArgAction::Append | clap::ArgAction::Count) { "*" } else { "" };
+        let value = match completion_value(arg) {
+            CompletionValue::Flag => String::new(),
+            value => {
+                let value_name = arg.get_value_names()
+                    .and_then(|names| names.first())
+                    .map_or_else(|| arg.get_id().to_string(), |n| n.to_lowercase());
+                format!(":{}:{}", value_name, action(&value))
             }
+        };
+        for word in option_words(arg) {
+            specs += &format!("        '{}{}[{}]{}'\n", repeat, word, help, value);
         }
-
-        in_stack.remove(&pkg_id);
-        visited.insert(pkg_id);
-        order.push(pkg.clone());
-
-        Ok(())
     }
-
-    fn mark_installed(&mut self, name: &str) {
-        self.installed.insert(name.to_string());
+    let subcommands = completion_subcommands(cmd);
+    let described: Vec<String> = subcommands.iter().map(|sub| format!("'{}:{}'", sub.name, quote(&sub.about))).collect();
+    let mut arg_cases = String::new();
+    for sub in &subcommands {
+        if !matches!(sub.value, CompletionValue::Any) {
+            let position = if sub.many { "*" } else { "1" };
+            arg_cases += &format!("                {}) _arguments -s $opts '{}: :{}' ;;\n", sub.name, position, action(&sub.value));
+        }
     }
+    format!(r#"#compdef {name}
+# Автодополнение {name} для zsh: {name} completions zsh > "${{fpath[1]}}/_{name}"
+
+_{name}_phases() {{
+    local i file
+    for ((i = 2; i < CURRENT; i++)); do
+        [[ -z $file && $words[i] == (-f|--file) ]] && file=$words[i+1]
+        [[ -z $file && $words[i] == (*.instnoth|builtin:*) ]] && file=$words[i]
+    done
+    [[ -n $file ]] || return 1
+    local -a phases
+    phases=(${{(f)"$({name} __complete-phases $file 2>/dev/null)"}})
+    compadd -a phases
+}}
+
+_{name}() {{
+    # Флаги общие для всех подкоманд: они же дополняются и после run, check и deps
+    local -a opts=(
+{specs}    )
+    local context state state_descr line
+    typeset -A opt_args
+    _arguments -s -C $opts '1: :->command' '*:: :->args'
+    case $state in
+        command)
+            local -a commands=({described})
+            _describe command commands
+            ;;
+        args)
+            case $words[1] in
+{arg_cases}            esac
+            ;;
+    esac
+}}
+
+if [ "$funcstack[1]" = "_{name}" ]; then
+    _{name} "$@"
+else
+    compdef _{name} {name}
+fi
+"#, described = described.join(" "))
+}
 
-    fn is_installed(&self, name: &str) -> bool {
-        self.installed.contains(name)
+fn fish_completion(cmd: &clap::Command) -> String {
+    let name = cmd.get_name();
+    let quote = |text: &str| format!("'{}'", text.replace('\\', "\\\\").replace('\'', "\\'"));
+    let mut lines = String::new();
+    for sub in completion_subcommands(cmd) {
+        lines += &format!("complete -c {} -n __fish_use_subcommand -f -a {} -d {}\n", name, sub.name, quote(&sub.about));
+        if let CompletionValue::Choices(choices) = sub.value {
+            lines += &format!("complete -c {} -n '__fish_seen_subcommand_from {}' -f -a {}\n", name, sub.name, quote(&choices.join(" ")));
+        }
     }
+    for arg in completion_args(cmd) {
+        let mut line = format!("complete -c {}", name);
+        if let Some(short) = arg.get_short() {
+            line += &format!(" -s {}", short);
+        }
+        if let Some(long) = arg.get_long() {
+            line += &format!(" -l {}", long);
+        }
+        match completion_value(arg) {
+            CompletionValue::Flag => {}
+            CompletionValue::Phases => line += &format!(" -x -a '(__{}_phases)'", name),
+            CompletionValue::Choices(choices) => line += &format!(" -x -a {}", quote(&choices.join(" "))),
+            CompletionValue::Path => line += " -r -F",
+            CompletionValue::Any => line += " -x",
+        }
+        line += &format!(" -d {}\n", quote(&help_line(arg.get_help().map(|h| h.to_string()))));
+        lines += &line;
+    }
+    format!(r#"# Автодополнение {name} для fish: {name} completions fish > ~/.config/fish/completions/{name}.fish
+
+function __{name}_phases
+    set -l tokens (commandline -opc)
+    set -l file
+    for i in (seq (count $tokens))
+        test -n "$file"; and break
+        if contains -- $tokens[$i] -f --file; and test $i -lt (count $tokens)
+            set file $tokens[(math $i + 1)]
+        else if string match -q -- '*.instnoth' $tokens[$i]; or string match -q -- 'builtin:*' $tokens[$i]
+            set file $tokens[$i]
+        end
+    end
+    test -n "$file"; and {name} __complete-phases $file 2>/dev/null
+end
+
+{lines}"#)
 }
 
-fn show_dependency_tree(pkg: &Package, dep_manager: &DependencyManager, indent: usize, visited: &mut HashSet<String>) {
-    let prefix = "  ".repeat(indent);
-    let marker = if indent == 0 { "📦" } else { "├─" };
-    
-    println!("{}{} {} (v{})", prefix, marker, pkg.name.cyan().bold(), pkg.version);
-    
-    if visited.contains(&pkg.name) {
-        println!("{}  └─ {}", prefix, "(уже показан)".dimmed());
-        return;
+/// __complete-phases: имена фаз файла по одному в строке. Ошибки разбора не мешают —
+/// автодополнению хватит фаз, которые удалось прочитать
+fn print_phase_names(file: &Path) -> bool {
+    let file = builtin::resolve(file);
+    let Ok(content) = read_package_file(&file) else {
+        return false;
+    };
+    let package = InstnothParser::with_path(content, file).parse_package();
+    for phase in &package.phases {
+        println!("{}", phase.name);
     }
-    visited.insert(pkg.name.clone());
-    
-    for (i, dep_path) in pkg.depends.iter().enumerate() {
-        let full_path = dep_manager.resolve_path(dep_path);
-        let is_last = i == pkg.depends.len() - 1;
-        let branch = if is_last { "└─" } else { "├─" };
-        
-        if let Ok(dep_pkg) = dep_manager.load_package(&full_path) {
-            println!("{}  {} {}", prefix, branch, dep_path.yellow());
-            show_dependency_tree(&dep_pkg, dep_manager, indent + 2, visited);
-        } else {
-            println!("{}  {} {} {}", prefix, branch, dep_path.yellow(), "(не найден)".red());
-        }
+    true
+}
+
+// ============== Main ==============
+
+fn list_builtin() {
+    println!();
+    println!("{}", "Встроенные файлы установки:".green().bold());
+    println!("{}", "─".repeat(40).dimmed());
+    let width = builtin::all().iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    for (name, source) in builtin::all() {
+        // Имя и версия — из заголовка самого файла
+        let package = InstnothParser::with_path(source.to_string(), builtin::path(name)).parse_package();
+        println!("  {} - {} {}", format!("{:<w$}", name, w = width).cyan(), package.name, package.version);
     }
+    println!();
+    println!("Использование:");
+    println!("  {} --file builtin:<имя>", "instnoth".yellow());
+    println!("  {} --file builtin:python builtin:nodejs", "instnoth".yellow());
+    println!("  {} --file builtin:all --show-deps", "instnoth".yellow());
+    println!();
 }
 
-// ============== Симулятор ==============
+/// installed.json в --state-dir или в каталоге по умолчанию; None — нет ни того, ни HOME
+fn installed_path(args: &Args) -> Option<PathBuf> {
+    args.state_dir.clone().or_else(default_state_dir).map(|dir| dir.join(INSTALLED_FILE))
+}
 
-struct Simulator {
-    quick_mode: bool,
-    verbose: bool,
-    progress: u8,
+/// Список установленных пакетов; нечитаемый файл — ошибка, чтобы не затереть его пустым
+fn load_inventory(path: &Path) -> Inventory {
+    Inventory::load(path).unwrap_or_else(|e| {
+        eprintln!("{} {}", "✗".red(), e);
+        std::process::exit(1);
+    })
 }
 
-impl Simulator {
-    fn new(quick_mode: bool, verbose: bool) -> Self {
-        Self {
-            quick_mode,
-            verbose,
-            progress: 0,
-        }
+fn save_inventory(inventory: &Inventory, path: &Path) {
+    if let Err(e) = inventory.save(path) {
+        eprintln!("{} Не удалось записать {}: {}", "⚠".yellow(), path.display(), e);
     }
+}
 
-    fn run(&mut self, package: &Package) -> Result<(), String> {
-        self.print_header(package);
+/// instnoth list --installed
+fn list_installed(inventory: &Inventory) {
+    println!();
+    println!("{}", "Установленные пакеты:".green().bold());
+    println!("{}", "─".repeat(40).dimmed());
+    if inventory.packages.is_empty() {
+        println!("  {}", "(пока ничего)".dimmed());
+    }
+    let width = inventory.packages.iter().map(|p| p.name.chars().count()).max().unwrap_or(0);
+    let version_width = inventory.packages.iter().map(|p| p.version.chars().count() + 1).max().unwrap_or(0);
+    for p in &inventory.packages {
+        let source = if p.source == "-" { "stdin" } else { p.source.as_str() };
+        let version = format!("v{}", p.version);
+        println!("  {} {:<vw$}  {}  {}", format!("{:<w$}", p.name, w = width).cyan(), version, iso_date(p.installed_at).replace('T', " ").dimmed(), source.dimmed(), vw = version_width);
+    }
+    println!();
+}
 
-        for phase in &package.phases {
-            self.run_phase(phase)?;
+/// Конфликтующие пакеты: без --force-conflicts установка не начинается, с ним — громкое
+/// предупреждение, и установка идёт дальше
+fn check_conflicts(conflicts: &[String], force: bool) {
+    if conflicts.is_empty() {
+        return;
+    }
+    if !force {
+        eprintln!("{} Конфликт пакетов ({}; --force-conflicts ставит их всё равно):", "✗".red(), plural(conflicts.len() as u32, "конфликт", "конфликта", "конфликтов"));
+        for conflict in conflicts {
+            eprintln!("  {}", conflict);
         }
-
-        self.print_footer(package);
-        Ok(())
+        std::process::exit(1);
+    }
+    eprintln!();
+    eprintln!("{}", "☠ ☠ ☠  --force-conflicts: СТАВЯТСЯ КОНФЛИКТУЮЩИЕ ПАКЕТЫ  ☠ ☠ ☠".red().bold());
+    for conflict in conflicts {
+        eprintln!("  {} {}", "☠".red().bold(), conflict.red());
     }
+    eprintln!("{}", "  Пакетный менеджер умывает руки: что бы ни случилось дальше, вы этого хотели.".red().bold());
+    eprintln!();
+}
 
-    fn print_header(&self, package: &Package) {
-        println!();
-        println!("{}", "╔═══════════════════════════════════════════════════════════════════╗".cyan());
-        println!("{}", "║  InstNoth Installer v1.0                                          ║".cyan());
-        println!("{}", "╚═══════════════════════════════════════════════════════════════════╝".cyan());
-        println!();
-        println!("{}:    {}", "Package".green().bold(), package.name.white().bold());
-        println!("{}:    {}", "Version".green().bold(), package.version.white());
-        if !package.description.is_empty() {
-            println!("{}:", "Description".green().bold());
-            println!("  {}", package.description.white().dimmed());
-        }
-        if !package.author.is_empty() {
-            println!("{}:     {}", "Author".green().bold(), package.author.white());
-        }
-        if !package.depends.is_empty() {
-            println!("{}:   {}", "Depends".green().bold(), package.depends.join(", ").yellow());
-        }
-        println!();
-        println!("{}", "───────────────────────────────────────────────────────────────────".dimmed());
-        println!();
+/// Скрипт экспорта на диск; на unix — сразу исполняемый
+fn write_script(path: &Path, script: &str) -> io::Result<()> {
+    fs::write(path, script)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755))?;
     }
+    Ok(())
+}
 
-    fn print_footer(&self, package: &Package) {
-        println!();
-        println!("{}", "═══════════════════════════════════════════════════════════════════".green());
-        println!("{}", format!("  {} {} установлен успешно!", "✓".green().bold(), package.name).green());
-        println!("{}", "═══════════════════════════════════════════════════════════════════".green());
-        println!();
+fn list_templates() {
+    println!();
+    println!("{}", "Заготовки для instnoth new:".green().bold());
+    println!("{}", "─".repeat(40).dimmed());
+    let width = templates::all().iter().map(|(name, _, _)| name.len()).max().unwrap_or(0);
+    for (name, description, _) in templates::all() {
+        println!("  {} - {}", format!("{:<w$}", name, w = width).cyan(), description);
     }
+    println!();
+    println!("Использование:");
+    println!("  {} new <имя> --template <заготовка>", "instnoth".yellow());
+    println!();
+}
 
-    fn run_phase(&mut self, phase: &Phase) -> Result<(), String> {
-        println!();
-        println!("{} {}", "▶".blue().bold(), phase.name.blue().bold());
-        println!("{}", "─".repeat(50).dimmed());
+/// instnoth new: NAME.instnoth в текущем каталоге (или NAME, если он уже с расширением)
+/// из заготовки; версия — сегодняшняя дата, автор — текущий пользователь. Существующий
+/// файл перезаписывается только с force
+fn new_package(name: &str, template: &str, force: bool) -> Result<PathBuf, String> {
+    let path = if name.ends_with(".instnoth") { PathBuf::from(name) } else { PathBuf::from(format!("{}.instnoth", name)) };
+    let package = path.file_stem().map(|s| s.to_string_lossy().to_string()).filter(|s| !s.is_empty())
+        .ok_or_else(|| format!("Некорректное имя пакета: {:?}", name))?;
+    let source = templates::get(template)
+        .ok_or_else(|| format!("Нет заготовки {:?} (см. instnoth new --list-templates)", template))?;
+    if path.exists() && !force {
+        return Err(format!("Файл {} уже существует (перезаписать — --force)", path.display()));
+    }
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let content = templates::render(source, &package, &date_version(now), &user_name());
+    fs::write(&path, content).map_err(|e| format!("Не удалось создать файл {}: {}", path.display(), e))?;
+    Ok(path)
+}
 
-        for cmd in &phase.commands {
-            self.execute_command(cmd)?;
+/// Файлы .instnoth и .instnoth.toml каталога, по порядку путей; с recursive — и во вложенных каталогах,
+/// кроме скрытых
+fn instnoth_files(dir: &Path, recursive: bool) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir).into_iter().flatten().flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            if recursive && !entry.file_name().to_string_lossy().starts_with('.') {
+                files.extend(instnoth_files(&path, true));
+            }
+        } else if path.extension().is_some_and(|ext| ext == "instnoth") || path.to_string_lossy().ends_with(".instnoth.toml") {
+            files.push(path);
         }
-
-        Ok(())
     }
+    files.sort();
+    files
+}
 
-    fn execute_command(&mut self, cmd: &Command) -> Result<(), String> {
-        match cmd {
-            Command::Message(msg) => {
-                println!("  {} {}", "→".dimmed(), msg);
-            }
-            Command::Delay(ms) => {
-                if !self.quick_mode {
-                    thread::sleep(Duration::from_millis(*ms));
-                }
-            }
-            Command::Progress(pct) => {
-                self.progress = *pct;
-                self.show_progress_bar(*pct);
-            }
-            Command::CreateDir(path) => {
-                self.simulate_operation(&format!("Создание директории: {}", path), 200)?;
-                if self.verbose {
-                    println!("    {} mkdir -p {}", "$".dimmed(), path.yellow());
-                }
-            }
-            Command::Download { url, size } => {
-                self.simulate_download(url, *size)?;
-            }
-            Command::Extract { from, to } => {
-                self.simulate_extraction(from, to)?;
-            }
-            Command::InstallDep { name, version } => {
-                self.simulate_dep_install(name, version)?;
-            }
-            Command::Configure { key, value } => {
-                println!("  {} Конфигурация: {}={}", "⚙".cyan(), key.yellow(), value.green());
-                if !self.quick_mode {
-                    thread::sleep(Duration::from_millis(100));
+/// Пути по шаблону: * и ? — в пределах имени, с учётом регистра (wildcard_match), ** — любое число вложенных
+/// каталогов. Скрытые файлы и каталоги совпадают, только если шаблон тоже начинается с точки
+fn expand_glob(pattern: &Path) -> Vec<PathBuf> {
+    let mut current = vec![PathBuf::new()];
+    for component in pattern.components() {
+        let part = component.as_os_str().to_string_lossy();
+        let mut next = Vec::new();
+        for dir in &current {
+            let listed = if dir.as_os_str().is_empty() { Path::new(".") } else { dir.as_path() };
+            if part == "**" {
+                let mut dirs = vec![dir.clone()];
+                let mut i = 0;
+                while i < dirs.len() {
+                    let listed = if dirs[i].as_os_str().is_empty() { PathBuf::from(".") } else { dirs[i].clone() };
+                    let mut nested: Vec<PathBuf> = fs::read_dir(listed).into_iter().flatten().flatten()
+                        .filter(|e| e.path().is_dir() && !e.file_name().to_string_lossy().starts_with('.'))
+                        .map(|e| dirs[i].join(e.file_name()))
+                        .collect();
+                    nested.sort();
+                    dirs.extend(nested);
+                    i += 1;
                 }
-            }
-            Command::Cleanup => {
-                self.simulate_operation("Очистка временных файлов...", 300)?;
-                if self.verbose {
-                    println!("    {} rm -rf /tmp/instnoth_*", "$".dimmed());
+                next.extend(dirs);
+            } else if part.contains(['*', '?']) {
+                for entry in fs::read_dir(listed).into_iter().flatten().flatten() {
+                    let name = entry.file_name().to_string_lossy().to_string();
+                    if wildcard_match(&part, &name) && (!name.starts_with('.') || part.starts_with('.')) {
+                        next.push(dir.join(name));
+                    }
                 }
+            } else {
+                next.push(dir.join(&*part));
             }
-            Command::Success(msg) => {
-                println!("  {} {}", "✓".green().bold(), msg.green());
-            }
-            Command::Error(msg) => {
-                println!("  {} {}", "✗".red().bold(), msg.red());
-            }
-            Command::Warning(msg) => {
-                println!("  {} {}", "⚠".yellow().bold(), msg.yellow());
-            }
-            Command::CopyFile { from, to } => {
-                println!("  {} Копирование: {} → {}", "📄".normal(), from.dimmed(), to.cyan());
-                if self.verbose {
-                    println!("    {} cp {} {}", "$".dimmed(), from, to);
-                }
-                if !self.quick_mode {
-                    thread::sleep(Duration::from_millis(150));
-                }
+        }
+        current = next;
+    }
+    current.retain(|p| p.is_file());
+    current.sort();
+    current
+}
+
+/// Пути --file: каталоги и шаблоны раскрываются в файлы, одинаковые файлы остаются по одному.
+/// Каталог или шаблон без совпадений — ошибка с тем, где искали
+fn collect_files(specs: &[PathBuf], recursive: bool) -> Result<Vec<PathBuf>, String> {
+    let mut files: Vec<PathBuf> = Vec::new();
+    let mut seen = HashSet::new();
+    for spec in specs {
+        let spec = builtin::resolve(spec);
+        let text = spec.to_string_lossy();
+        let found = if spec == Path::new("-") || builtin::is_builtin(&spec) || remote::url(&spec).is_some() {
+            vec![spec.clone()]
+        } else if spec.is_dir() {
+            let found = instnoth_files(&spec, recursive);
+            if found.is_empty() {
+                let hint = if recursive { "" } else { " (вложенные каталоги просматриваются с --recursive)" };
+                return Err(format!("В каталоге {} нет файлов .instnoth{}", spec.display(), hint));
+            }
+            found
+        } else if text.contains(['*', '?']) {
+            let found = expand_glob(&spec);
+            if found.is_empty() {
+                // Где искали: часть шаблона до первого подстановочного знака
+                let base: PathBuf = spec.components()
+                    .take_while(|c| !c.as_os_str().to_string_lossy().contains(['*', '?']))
+                    .collect();
+                let base = if base.as_os_str().is_empty() { ".".to_string() } else { base.display().to_string() };
+                return Err(format!("Шаблон \"{}\" не совпал ни с одним файлом (искали в {})", text, base));
+            }
+            found
+        } else {
+            vec![spec.clone()]
+        };
+        for file in found {
+            // Один файл, указанный по-разному (./a и a, каталог и шаблон), — один пакет
+            let key = fs::canonicalize(&file).unwrap_or_else(|_| file.clone());
+            if seen.insert(key) {
+                files.push(file);
             }
-            Command::Symlink { from, to } => {
-                println!("  {} Создание ссылки: {} → {}", "🔗".normal(), from.dimmed(), to.cyan());
-                if self.verbose {
-                    println!("    {} ln -s {} {}", "$".dimmed(), from, to);
-                }
-                if !self.quick_mode {
-                    thread::sleep(Duration::from_millis(100));
-                }
+        }
+    }
+    Ok(files)
+}
+
+// Режим --validate: разбор и проверка файлов без запуска симуляции
+fn validate_files(files: &[PathBuf], args: &Args) -> bool {
+    let mut total_errors = 0;
+    let mut total_warnings = 0;
+
+    for file_path in files {
+        let content = match read_package_file(file_path) {
+            Ok(c) => c,
+            Err(e) => {
+                let what = if remote::url(file_path).is_some() { "не удалось загрузить" } else { "не удалось прочитать файл" };
+                eprintln!("{} {}: {}: {}", "✗".red(), file_path.display(), what, e);
+                total_errors += 1;
+                continue;
             }
-            Command::SetPermission { path, mode } => {
-                println!("  {} Установка прав {} для {}", "🔐".normal(), mode.yellow(), path.cyan());
-                if self.verbose {
-                    println!("    {} chmod {} {}", "$".dimmed(), mode, path);
+        };
+
+        let mut parser = InstnothParser::with_path(content, file_path.clone());
+        parser.strict = args.strict;
+        parser.validate = true;
+        parser.max_delay = args.max_delay;
+        let result = parser.parse();
+
+        for diag in &parser.diagnostics {
+            match diag.severity {
+                Severity::Error => {
+                    total_errors += 1;
+                    eprintln!("{} {}:{}: {}", "✗".red(), file_path.display(), diag.line, diag.message);
                 }
-                if !self.quick_mode {
-                    thread::sleep(Duration::from_millis(50));
+                Severity::Warning => {
+                    total_warnings += 1;
+                    eprintln!("{} {}:{}: {}", "⚠".yellow(), file_path.display(), diag.line, diag.message);
                 }
             }
-            Command::RunScript(script) => {
-                println!("  {} Выполнение скрипта: {}", "▷".cyan(), script.yellow());
-                self.simulate_script_execution()?;
-            }
-            Command::CheckDep(dep) => {
-                print!("  {} Проверка зависимости: {} ... ", "?".blue(), dep.cyan());
-                io::stdout().flush().unwrap();
-                if !self.quick_mode {
-                    thread::sleep(Duration::from_millis(200));
-                }
-                println!("{}", "OK".green().bold());
+        }
+        let has_diag_errors = parser.diagnostics.iter().any(|d| d.severity == Severity::Error);
+        if let Err(e) = result {
+            if !has_diag_errors {
+                total_errors += 1;
+                eprintln!("{} {}: {}", "✗".red(), file_path.display(), e);
             }
-            Command::WriteConfig { path, content } => {
-                println!("  {} Запись конфигурации: {}", "📝".normal(), path.cyan());
-                if self.verbose && !content.is_empty() {
-                    for line in content.lines().take(3) {
-                        println!("    {}", line.dimmed());
-                    }
-                    if content.lines().count() > 3 {
-                        println!("    {}", "...".dimmed());
-                    }
-                }
-                if !self.quick_mode {
-                    thread::sleep(Duration::from_millis(100));
-                }
+        }
+    }
+
+    println!(
+        "{} Проверено файлов: {}, ошибок: {}, предупреждений: {}",
+        if total_errors == 0 { "✓".green() } else { "✗".red() },
+        files.len(), total_errors, total_warnings
+    );
+    total_errors == 0
+}
+
+/// instnoth fmt: файлы переписываются в каноническом виде, stdin — в stdout. С check
+/// ничего не меняется, а файлы, которые изменились бы, перечисляются. Файл с ошибками
+/// разбора не трогается: без дерева разбора нельзя поручиться, что смысл не изменится
+fn fmt_files(files: &[PathBuf], check: bool) -> bool {
+    let mut ok = true;
+    for file_path in files {
+        let content = match read_package_file(file_path) {
+            Ok(c) => c,
+            Err(e) => {
+                eprintln!("{} Не удалось прочитать файл {:?}: {}", "✗".red(), file_path, e);
+                ok = false;
+                continue;
             }
-            Command::DetectCpu => { self.detect_cpu()?; }
-            Command::DetectMemory => { self.detect_memory()?; }
-            Command::DetectDisk => { self.detect_disk()?; }
-            Command::DetectGpu => { self.detect_gpu()?; }
-            Command::DetectNetwork => { self.detect_network()?; }
-            Command::DetectOs => { self.detect_os()?; }
-            Command::DetectKernel => { self.detect_kernel()?; }
-            Command::DetectBios => { self.detect_bios()?; }
-            Command::RunTest { name, duration } => { self.run_test(name, *duration)?; }
-            Command::LoadKernelModule(module) => { self.load_kernel_module(module)?; }
-            Command::UnloadKernelModule(module) => { self.unload_kernel_module(module)?; }
-            Command::UpdateInitramfs => { self.update_initramfs()?; }
-            Command::UpdateGrub => { self.update_grub()?; }
-            Command::MountPartition { device, mount_point } => { self.mount_partition(device, mount_point)?; }
-            Command::UnmountPartition(mount_point) => { self.unmount_partition(mount_point)?; }
-            Command::FormatPartition { device, fs_type } => { self.format_partition(device, fs_type)?; }
-            Command::CreatePartition { device, size } => { self.create_partition(device, size)?; }
-            Command::SetHostname(hostname) => {
-                println!("  {} Установка имени хоста: {}", "🖥".normal(), hostname.cyan());
-                if self.verbose {
-                    println!("    {} hostnamectl set-hostname {}", "$".dimmed(), hostname);
-                }
-                if !self.quick_mode { thread::sleep(Duration::from_millis(100)); }
+        };
+        // У TOML свой синтаксис, и format_source его только испортит
+        if is_toml(&content, Some(file_path)) {
+            eprintln!("{} {}: файлы в формате TOML не форматируются, пропущен", "⚠".yellow(), file_path.display());
+            if file_path == Path::new("-") && !check {
+                print!("{}", content);
             }
-            Command::SetTimezone(tz) => {
-                println!("  {} Установка часового пояса: {}", "🌍".normal(), tz.cyan());
-                if self.verbose {
-                    println!("    {} timedatectl set-timezone {}", "$".dimmed(), tz);
-                }
-                if !self.quick_mode { thread::sleep(Duration::from_millis(100)); }
+            continue;
+        }
+        let mut parser = InstnothParser::new(content.clone());
+        parser.parse_package();
+        if let Some(error) = parser.diagnostics.iter().find(|d| d.severity == Severity::Error) {
+            eprintln!("{} {}:{}: {} — файл не отформатирован", "✗".red(), file_path.display(), error.line, error.message);
+            ok = false;
+            continue;
+        }
+
+        let formatted = format_source(&content);
+        let from_stdin = file_path == Path::new("-");
+        if check {
+            if formatted != content {
+                println!("{} {}: требуется форматирование", "⚠".yellow(), file_path.display());
+                ok = false;
+            }
+        } else if from_stdin {
+            print!("{}", formatted);
+        } else if formatted != content {
+            if builtin::is_builtin(file_path) || remote::url(file_path).is_some() {
+                eprintln!("{} {}: переписать можно только файл на диске (проверить — fmt --check)", "✗".red(), file_path.display());
+                ok = false;
+                continue;
             }
-            Command::SetLocale(locale) => {
-                println!("  {} Установка локали: {}", "🌐".normal(), locale.cyan());
-                if self.verbose {
-                    println!("    {} localectl set-locale LANG={}", "$".dimmed(), locale);
+            match fs::write(file_path, &formatted) {
+                Ok(()) => println!("{} Отформатирован {}", "✓".green(), file_path.display()),
+                Err(e) => {
+                    eprintln!("{} Не удалось записать файл {:?}: {}", "✗".red(), file_path, e);
+                    ok = false;
                 }
-                if !self.quick_mode { thread::sleep(Duration::from_millis(100)); }
-            }
-            Command::CreateUser { username, groups } => { self.create_user(username, groups)?; }
-            Command::SetPassword(user) => {
-                print!("  {} Установка пароля для {} ... ", "🔑".normal(), user.cyan());
-                io::stdout().flush().unwrap();
-                if !self.quick_mode { thread::sleep(Duration::from_millis(300)); }
-                println!("{}", "OK".green());
             }
-            Command::EnableService(service) => { self.manage_service(service, "enable")?; }
-            Command::DisableService(service) => { self.manage_service(service, "disable")?; }
-            Command::StartService(service) => { self.manage_service(service, "start")?; }
-            Command::StopService(service) => { self.manage_service(service, "stop")?; }
-            Command::InstallBootloader(target) => { self.install_bootloader(target)?; }
-            Command::GenerateFstab => { self.generate_fstab()?; }
-            Command::CheckIntegrity(target) => { self.check_integrity(target)?; }
-            Command::VerifySignature(file) => { self.verify_signature(file)?; }
-            Command::CompileKernel { version } => { self.compile_kernel(version)?; }
-            Command::InstallPackages(packages) => { self.install_packages(packages)?; }
-            Command::UpdateSystem => { self.update_system()?; }
-            Command::SyncTime => { self.sync_time()?; }
-            Command::TestHardware(component) => { self.test_hardware(component)?; }
-            Command::BenchmarkCpu => { self.benchmark_cpu()?; }
-            Command::BenchmarkMemory => { self.benchmark_memory()?; }
-            Command::BenchmarkDisk => { self.benchmark_disk()?; }
-            Command::NetworkConfig { interface, config } => { self.network_config(interface, config)?; }
-            Command::FirewallRule(rule) => {
-                println!("  {} Добавление правила firewall: {}", "🛡".normal(), rule.yellow());
-                if !self.quick_mode { thread::sleep(Duration::from_millis(100)); }
-            }
-            Command::ScanHardware => { self.scan_hardware()?; }
-            Command::DetectDrivers => { self.detect_drivers()?; }
-            Command::InstallDriver(driver) => { self.install_driver(driver)?; }
         }
-        Ok(())
     }
+    ok
+}
 
-    // ===== Методы детекции =====
+// Режим --lint: разбор, проверка значений и зависимостей каждого файла
+fn lint_files(files: &[PathBuf], args: &Args) -> bool {
+    let mut has_errors = false;
 
-    fn detect_cpu(&mut self) -> Result<(), String> {
-        print!("  {} Определение процессора ... ", "🔍".normal());
-        io::stdout().flush().unwrap();
-        if !self.quick_mode { thread::sleep(Duration::from_millis(500)); }
-        let (vendor, model, cores, freq) = RandomSystemInfo::cpu();
-        println!();
-        println!("    {} {} {}", "├".dimmed(), "Производитель:".dimmed(), vendor.cyan());
-        println!("    {} {} {}", "├".dimmed(), "Модель:".dimmed(), model.white().bold());
-        println!("    {} {} {} ядер", "├".dimmed(), "Ядра:".dimmed(), cores.to_string().yellow());
-        println!("    {} {} {} MHz", "└".dimmed(), "Частота:".dimmed(), freq.to_string().green());
-        Ok(())
-    }
-
-    fn detect_memory(&mut self) -> Result<(), String> {
-        print!("  {} Определение памяти ... ", "🔍".normal());
-        io::stdout().flush().unwrap();
-        if !self.quick_mode { thread::sleep(Duration::from_millis(400)); }
-        let (size, mem_type, speed) = RandomSystemInfo::memory();
-        println!();
-        println!("    {} {} {} GB", "├".dimmed(), "Объём:".dimmed(), size.to_string().white().bold());
-        println!("    {} {} {}", "├".dimmed(), "Тип:".dimmed(), mem_type.cyan());
-        println!("    {} {} {} MHz", "└".dimmed(), "Скорость:".dimmed(), speed.to_string().green());
-        Ok(())
-    }
-
-    fn detect_disk(&mut self) -> Result<(), String> {
-        print!("  {} Определение накопителей ... ", "🔍".normal());
-        io::stdout().flush().unwrap();
-        if !self.quick_mode { thread::sleep(Duration::from_millis(600)); }
-        let (vendor, model, size, disk_type) = RandomSystemInfo::disk();
-        println!();
-        println!("    {} {} {}", "├".dimmed(), "Производитель:".dimmed(), vendor.cyan());
-        println!("    {} {} {}", "├".dimmed(), "Модель:".dimmed(), model.white().bold());
-        println!("    {} {} {} GB", "├".dimmed(), "Объём:".dimmed(), size.to_string().yellow());
-        println!("    {} {} {}", "└".dimmed(), "Тип:".dimmed(), disk_type.green());
-        Ok(())
-    }
-
-    fn detect_gpu(&mut self) -> Result<(), String> {
-        print!("  {} Определение видеокарты ... ", "🔍".normal());
-        io::stdout().flush().unwrap();
-        if !self.quick_mode { thread::sleep(Duration::from_millis(500)); }
-        let (vendor, model, vram) = RandomSystemInfo::gpu();
-        println!();
-        println!("    {} {} {}", "├".dimmed(), "Производитель:".dimmed(), vendor.cyan());
-        println!("    {} {} {}", "├".dimmed(), "Модель:".dimmed(), model.white().bold());
-        println!("    {} {} {} GB VRAM", "└".dimmed(), "Память:".dimmed(), vram.to_string().green());
-        Ok(())
-    }
-
-    fn detect_network(&mut self) -> Result<(), String> {
-        print!("  {} Определение сетевых адаптеров ... ", "🔍".normal());
-        io::stdout().flush().unwrap();
-        if !self.quick_mode { thread::sleep(Duration::from_millis(500)); }
-        let (vendor, model, speed) = RandomSystemInfo::network();
-        let mac = RandomSystemInfo::mac_address();
-        let ip = RandomSystemInfo::ip_address();
-        println!();
-        println!("    {} {} {}", "├".dimmed(), "Адаптер:".dimmed(), format!("{} {}", vendor, model).white().bold());
-        println!("    {} {} {}", "├".dimmed(), "Скорость:".dimmed(), speed.green());
-        println!("    {} {} {}", "├".dimmed(), "MAC:".dimmed(), mac.yellow());
-        println!("    {} {} {}", "└".dimmed(), "IP:".dimmed(), ip.cyan());
-        Ok(())
-    }
-
-    fn detect_os(&mut self) -> Result<(), String> {
-        print!("  {} Определение операционной системы ... ", "🔍".normal());
-        io::stdout().flush().unwrap();
-        if !self.quick_mode { thread::sleep(Duration::from_millis(300)); }
-        let (name, version) = RandomSystemInfo::os();
-        println!();
-        println!("    {} {} {}", "├".dimmed(), "Система:".dimmed(), name.white().bold());
-        println!("    {} {} {}", "└".dimmed(), "Версия:".dimmed(), version.cyan());
-        Ok(())
-    }
+    for file_path in files {
+        let mut findings: Vec<Diagnostic> = Vec::new();
 
-    fn detect_kernel(&mut self) -> Result<(), String> {
-        print!("  {} Определение версии ядра ... ", "🔍".normal());
-        io::stdout().flush().unwrap();
-        if !self.quick_mode { thread::sleep(Duration::from_millis(200)); }
-        let kernel = RandomSystemInfo::kernel();
-        println!("{}", kernel.green());
-        Ok(())
-    }
+        let content = match read_package_file(file_path) {
+            Ok(c) => c,
+            Err(e) => {
+                let what = if remote::url(file_path).is_some() { "не удалось загрузить" } else { "не удалось прочитать файл" };
+                println!("{}: error: {}: {}", file_path.display(), what, e);
+                has_errors = true;
+                continue;
+            }
+        };
 
-    fn detect_bios(&mut self) -> Result<(), String> {
-        print!("  {} Определение BIOS/UEFI ... ", "🔍".normal());
-        io::stdout().flush().unwrap();
-        if !self.quick_mode { thread::sleep(Duration::from_millis(400)); }
-        let (vendor, bios_type, version) = RandomSystemInfo::bios();
-        println!();
-        println!("    {} {} {}", "├".dimmed(), "Производитель:".dimmed(), vendor.cyan());
-        println!("    {} {} {}", "├".dimmed(), "Тип:".dimmed(), bios_type.white().bold());
-        println!("    {} {} {}", "└".dimmed(), "Версия:".dimmed(), version.green());
-        Ok(())
-    }
-
-    fn run_test(&mut self, name: &str, duration: u64) -> Result<(), String> {
-        print!("  {} Тест: {} ", "🧪".normal(), name.cyan());
-        io::stdout().flush().unwrap();
-        if !self.quick_mode {
-            let pb = ProgressBar::new(100);
-            pb.set_style(ProgressStyle::default_bar()
-                .template("[{bar:20.green/white}] {percent}%").unwrap()
-                .progress_chars("█▓░"));
-            let steps = 20;
-            let step_duration = duration / steps;
-            for i in 0..=steps { pb.set_position((i * 5) as u64); thread::sleep(Duration::from_millis(step_duration)); }
-            pb.finish_and_clear();
+        let mut parser = InstnothParser::with_path(content, file_path.clone());
+        parser.strict = args.strict;
+        parser.validate = true;
+        parser.max_delay = args.max_delay;
+        let pkg = parser.parse_package();
+        findings.append(&mut parser.diagnostics);
+
+        if pkg.name.is_empty() {
+            findings.push(Diagnostic {
+                line: 1,
+                severity: Severity::Error,
+                message: "не указано имя пакета (package:)".to_string(),
+            });
         }
-        println!("{}", "PASSED".green().bold());
-        Ok(())
-    }
-
-    fn test_hardware(&mut self, component: &str) -> Result<(), String> {
-        println!("  {} Тестирование {}", "🔬".normal(), component.cyan());
-        let tests = match component {
-            "memory" | "ram" => vec!["Проверка ячеек памяти", "Тест чтения/записи", "Стресс-тест"],
-            "cpu" => vec!["Арифметические операции", "SIMD инструкции", "Температурный мониторинг"],
-            "disk" | "storage" => vec!["Проверка секторов", "Тест SMART", "Скорость чтения/записи"],
-            "gpu" => vec!["Рендеринг", "Вычисления CUDA/OpenCL", "Температура"],
-            _ => vec!["Базовый тест", "Функциональный тест"],
-        };
-        for test in tests { self.run_test(test, 500)?; }
-        Ok(())
-    }
-
-    fn benchmark_cpu(&mut self) -> Result<(), String> {
-        println!("  {} CPU Benchmark", "📊".normal());
-        if !self.quick_mode {
-            let tests = [("Single-thread", "12,847"), ("Multi-thread", "98,432"), ("Floating point", "45,621"), ("Integer ops", "67,891")];
-            for (name, score) in tests {
-                print!("    {} {} ... ", "→".dimmed(), name);
-                io::stdout().flush().unwrap();
-                thread::sleep(Duration::from_millis(400));
-                println!("{} points", score.green().bold());
+
+        let base = file_path.parent().map(Path::to_path_buf).unwrap_or_default();
+        let dep_manager = DependencyManager::new(base);
+
+        for dep in &pkg.depends {
+            let full_path = dep_manager.resolve_path(&pkg, &dep.path);
+            // Имя без расширения может быть provides другого пакета: его найдёт только установка, где он есть
+            if !package_file_exists(&full_path) && full_path.extension().is_none() {
+                findings.push(Diagnostic {
+                    line: dep.line,
+                    severity: Severity::Warning,
+                    message: format!("зависимость {} не найдена как файл ({}); как имя из provides она найдётся, только если пакет с ним ставится вместе", dep.path, attempted_path(&full_path).display()),
+                });
+                continue;
             }
-        } else {
-            println!("    {} Score: {} points", "→".dimmed(), "98,432".green().bold());
-        }
-        Ok(())
-    }
-
-    fn benchmark_memory(&mut self) -> Result<(), String> {
-        println!("  {} Memory Benchmark", "📊".normal());
-        if !self.quick_mode {
-            let tests = [("Read", "52,341 MB/s"), ("Write", "48,762 MB/s"), ("Copy", "45,123 MB/s"), ("Latency", "68.4 ns")];
-            for (name, result) in tests {
-                print!("    {} {} ... ", "→".dimmed(), name);
-                io::stdout().flush().unwrap();
-                thread::sleep(Duration::from_millis(300));
-                println!("{}", result.green().bold());
+            if !package_file_exists(&full_path) {
+                findings.push(Diagnostic {
+                    line: dep.line,
+                    severity: Severity::Error,
+                    message: format!("зависимость не найдена: {} ({})", dep.path, attempted_path(&full_path).display()),
+                });
+                continue;
             }
-        }
-        Ok(())
-    }
-
-    fn benchmark_disk(&mut self) -> Result<(), String> {
-        println!("  {} Disk Benchmark", "📊".normal());
-        if !self.quick_mode {
-            let tests = [("Sequential Read", "3,521 MB/s"), ("Sequential Write", "3,012 MB/s"), ("Random Read 4K", "89,456 IOPS"), ("Random Write 4K", "76,234 IOPS")];
-            for (name, result) in tests {
-                print!("    {} {} ... ", "→".dimmed(), name);
-                io::stdout().flush().unwrap();
-                thread::sleep(Duration::from_millis(400));
-                println!("{}", result.green().bold());
+            match dep_manager.load_package(&full_path) {
+                Err(e) => findings.push(Diagnostic {
+                    line: dep.line,
+                    severity: Severity::Error,
+                    message: format!("зависимость {} не загружается: {}", dep.path, e),
+                }),
+                Ok(dep_pkg) => {
+                    if let Some(c) = dep.constraint.as_ref().filter(|c| !c.matches(&dep_pkg.version)) {
+                        findings.push(Diagnostic {
+                            line: dep.line,
+                            severity: Severity::Error,
+                            message: format!(
+                                "зависимость {} требует {}, найдена версия {}",
+                                dep.path, c, dep_pkg.version
+                            ),
+                        });
+                    }
+                }
             }
         }
-        Ok(())
-    }
-
-    fn load_kernel_module(&mut self, module: &str) -> Result<(), String> {
-        print!("  {} Загрузка модуля ядра: {} ... ", "📦".normal(), module.cyan());
-        io::stdout().flush().unwrap();
-        if !self.quick_mode { thread::sleep(Duration::from_millis(300)); }
-        if self.verbose { println!(); println!("    {} modprobe {}", "$".dimmed(), module); }
-        println!("{}", "OK".green());
-        Ok(())
-    }
-
-    fn unload_kernel_module(&mut self, module: &str) -> Result<(), String> {
-        print!("  {} Выгрузка модуля ядра: {} ... ", "📤".normal(), module.cyan());
-        io::stdout().flush().unwrap();
-        if !self.quick_mode { thread::sleep(Duration::from_millis(200)); }
-        println!("{}", "OK".green());
-        Ok(())
-    }
-
-    fn update_initramfs(&mut self) -> Result<(), String> {
-        println!("  {} Обновление initramfs...", "🔄".normal());
-        if !self.quick_mode {
-            let steps = ["Сборка модулей...", "Генерация образа...", "Сжатие (gzip)...", "Запись /boot/initramfs.img..."];
-            for step in steps {
-                print!("    {} {}", "→".dimmed(), step);
-                io::stdout().flush().unwrap();
-                thread::sleep(Duration::from_millis(400));
-                println!(" {}", "✓".green());
-            }
+
+        findings.extend(lint_package(&pkg));
+
+        findings.sort_by_key(|d| d.line);
+        for d in &findings {
+            let severity = match d.severity {
+                Severity::Error => {
+                    has_errors = true;
+                    "error"
+                }
+                Severity::Warning => "warning",
+            };
+            println!("{}:{}: {}: {}", file_path.display(), d.line, severity, d.message);
         }
-        println!("    {} initramfs обновлён", "✓".green());
-        Ok(())
-    }
-
-    fn update_grub(&mut self) -> Result<(), String> {
-        println!("  {} Обновление GRUB...", "🔄".normal());
-        if !self.quick_mode {
-            let entries = ["Linux 6.6.8-arch1-1", "Linux 6.6.8-arch1-1 (fallback)", "Windows Boot Manager", "UEFI Firmware Settings"];
-            println!("    {} Генерация grub.cfg...", "→".dimmed());
-            thread::sleep(Duration::from_millis(300));
-            println!("    {} Обнаруженные записи:", "→".dimmed());
-            for entry in entries { thread::sleep(Duration::from_millis(150)); println!("      {} {}", "•".dimmed(), entry); }
+    }
+
+    !has_errors
+}
+
+// Проверки уровня пакета: пустые фазы и недостижимые значения прогресса
+fn lint_package(pkg: &Package) -> Vec<Diagnostic> {
+    let mut findings = Vec::new();
+    let mut max_progress: Option<(u8, usize)> = None;
+
+    for phase in &pkg.phases {
+        if phase.commands.is_empty() {
+            findings.push(Diagnostic {
+                line: phase.line,
+                severity: Severity::Warning,
+                message: format!("фаза \"{}\" не содержит команд", phase.name),
+            });
         }
-        println!("    {} GRUB обновлён", "✓".green());
-        Ok(())
-    }
-
-    fn compile_kernel(&mut self, version: &str) -> Result<(), String> {
-        println!("  {} Компиляция ядра {}", "🔨".normal(), version.cyan());
-        if !self.quick_mode {
-            let stages = [("Конфигурация", 500), ("Компиляция ядра", 2000), ("Компиляция модулей", 1500), ("Установка модулей", 800), ("Установка ядра", 400)];
-            for (stage, duration) in stages {
-                print!("    {} {} ", "→".dimmed(), stage);
-                io::stdout().flush().unwrap();
-                let pb = ProgressBar::new(100);
-                pb.set_style(ProgressStyle::default_bar().template("[{bar:20.cyan/blue}]").unwrap().progress_chars("█▓░"));
-                let steps = 20;
-                for i in 0..=steps { pb.set_position((i * 5) as u64); thread::sleep(Duration::from_millis(duration / steps)); }
-                pb.finish_and_clear();
-                println!("{}", "✓".green());
+        for (cmd, &line) in phase.commands.iter().zip(&phase.command_lines) {
+            if let Command::Progress(pct) = cmd {
+                match max_progress {
+                    Some((max, _)) if *pct < max => findings.push(Diagnostic {
+                        line,
+                        severity: Severity::Warning,
+                        message: format!("progress {}: уже было достигнуто {}%, значение не будет видно как рост", pct, max),
+                    }),
+                    _ => max_progress = Some((*pct, line)),
+                }
             }
         }
-        println!("    {} Ядро {} скомпилировано", "✓".green(), version);
-        Ok(())
-    }
-
-    fn mount_partition(&mut self, device: &str, mount_point: &str) -> Result<(), String> {
-        print!("  {} Монтирование {} → {} ... ", "💾".normal(), device.yellow(), mount_point.cyan());
-        io::stdout().flush().unwrap();
-        if !self.quick_mode { thread::sleep(Duration::from_millis(300)); }
-        if self.verbose { println!(); println!("    {} mount {} {}", "$".dimmed(), device, mount_point); }
-        println!("{}", "OK".green());
-        Ok(())
-    }
-
-    fn unmount_partition(&mut self, mount_point: &str) -> Result<(), String> {
-        print!("  {} Размонтирование {} ... ", "⏏".normal(), mount_point.cyan());
-        io::stdout().flush().unwrap();
-        if !self.quick_mode { thread::sleep(Duration::from_millis(200)); }
-        println!("{}", "OK".green());
-        Ok(())
-    }
-
-    fn format_partition(&mut self, device: &str, fs_type: &str) -> Result<(), String> {
-        println!("  {} Форматирование {} в {}", "💿".normal(), device.yellow(), fs_type.cyan());
-        if !self.quick_mode {
-            print!("    {} Создание файловой системы ", "→".dimmed());
-            io::stdout().flush().unwrap();
-            let pb = ProgressBar::new(100);
-            pb.set_style(ProgressStyle::default_bar().template("[{bar:30.yellow/white}] {percent}%").unwrap().progress_chars("█▓░"));
-            for i in 0..=100 { pb.set_position(i); thread::sleep(Duration::from_millis(20)); }
-            pb.finish_and_clear();
-            println!("{}", "✓".green());
-            if self.verbose { println!("    {} mkfs.{} {}", "$".dimmed(), fs_type, device); }
-        }
-        Ok(())
     }
 
-    fn create_partition(&mut self, device: &str, size: &str) -> Result<(), String> {
-        println!("  {} Создание раздела на {} ({})", "📀".normal(), device.yellow(), size.cyan());
-        if !self.quick_mode {
-            thread::sleep(Duration::from_millis(500));
-            if self.verbose { println!("    {} parted {} mkpart primary 0% {}", "$".dimmed(), device, size); }
-        }
-        println!("    {} Раздел создан", "✓".green());
-        Ok(())
+    if let Some((max, line)) = max_progress.filter(|(max, _)| *max < 100) {
+        findings.push(Diagnostic {
+            line,
+            severity: Severity::Warning,
+            message: format!("прогресс не достигает 100% (максимум {}%)", max),
+        });
     }
 
-    fn generate_fstab(&mut self) -> Result<(), String> {
-        println!("  {} Генерация /etc/fstab", "📝".normal());
-        if !self.quick_mode {
-            let entries = [("UUID=xxxx-xxxx", "/", "ext4", "defaults", "0 1"), ("UUID=yyyy-yyyy", "/boot/efi", "vfat", "umask=0077", "0 2"), ("UUID=zzzz-zzzz", "/home", "ext4", "defaults", "0 2"), ("tmpfs", "/tmp", "tmpfs", "defaults,nosuid,nodev", "0 0")];
-            for (device, mount, fs, opts, dump) in entries {
-                println!("    {} {} {} {} {} {}", "+".dimmed(), device.yellow(), mount.cyan(), fs, opts.dimmed(), dump.dimmed());
-                thread::sleep(Duration::from_millis(150));
+    findings
+}
+
+/// Первый Ctrl+C только поднимает INTERRUPTED — установка остановится на ближайшей проверке
+/// и покажет итог. Второй завершает процесс сразу, с кодом 130, как при SIGINT
+#[cfg(unix)]
+fn install_interrupt_handler() {
+    extern "C" fn on_sigint(_: libc::c_int) {
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            // В обработчике сигнала можно только write и _exit
+            const SHOW_CURSOR: &[u8] = b"\x1b[?25h\n";
+            unsafe {
+                libc::write(2, SHOW_CURSOR.as_ptr().cast(), SHOW_CURSOR.len());
+                libc::_exit(130);
             }
         }
-        println!("    {} fstab сгенерирован", "✓".green());
-        Ok(())
     }
-
-    fn create_user(&mut self, username: &str, groups: &str) -> Result<(), String> {
-        println!("  {} Создание пользователя: {}", "👤".normal(), username.cyan());
-        if !self.quick_mode { thread::sleep(Duration::from_millis(300)); }
-        println!("    {} Группы: {}", "→".dimmed(), groups.yellow());
-        if self.verbose { println!("    {} useradd -m -G {} {}", "$".dimmed(), groups, username); }
-        println!("    {} Пользователь создан", "✓".green());
-        Ok(())
+    let handler: extern "C" fn(libc::c_int) = on_sigint;
+    unsafe {
+        libc::signal(libc::SIGINT, handler as libc::sighandler_t);
     }
+}
 
-    fn manage_service(&mut self, service: &str, action: &str) -> Result<(), String> {
-        let (icon, verb) = match action {
-            "enable" => ("🔛", "Включение"), "disable" => ("🔚", "Отключение"),
-            "start" => ("▶", "Запуск"), "stop" => ("⏹", "Остановка"), _ => ("⚙", "Управление"),
-        };
-        print!("  {} {} сервиса: {} ... ", icon, verb, service.cyan());
-        io::stdout().flush().unwrap();
-        if !self.quick_mode { thread::sleep(Duration::from_millis(200)); }
-        if self.verbose { println!(); println!("    {} systemctl {} {}", "$".dimmed(), action, service); }
-        println!("{}", "OK".green());
-        Ok(())
-    }
-
-    fn install_bootloader(&mut self, target: &str) -> Result<(), String> {
-        println!("  {} Установка загрузчика на {}", "🔧".normal(), target.yellow());
-        if !self.quick_mode {
-            let steps = ["Проверка EFI/BIOS режима...", "Установка загрузочных файлов...", "Создание записи в NVRAM...", "Генерация конфигурации..."];
-            for step in steps {
-                print!("    {} {}", "→".dimmed(), step);
-                io::stdout().flush().unwrap();
-                thread::sleep(Duration::from_millis(400));
-                println!(" {}", "✓".green());
-            }
-        }
-        println!("    {} GRUB установлен на {}", "✓".green(), target);
-        Ok(())
-    }
-
-    fn check_integrity(&mut self, target: &str) -> Result<(), String> {
-        println!("  {} Проверка целостности: {}", "🔍".normal(), target.cyan());
-        if !self.quick_mode {
-            print!("    {} Вычисление контрольных сумм ", "→".dimmed());
-            io::stdout().flush().unwrap();
-            let pb = ProgressBar::new(100);
-            pb.set_style(ProgressStyle::default_bar().template("[{bar:25.cyan/white}]").unwrap().progress_chars("█▓░"));
-            for i in 0..=100 { pb.set_position(i); thread::sleep(Duration::from_millis(15)); }
-            pb.finish_and_clear();
-            println!("{}", "OK".green());
+#[cfg(not(unix))]
+fn install_interrupt_handler() {}
+
+fn main() {
+    let mut args = Args::parse_cli();
+
+    // Вне терминала цвета только мешают, если их не просят явно через CLICOLOR_FORCE
+    let env_set = |name: &str| std::env::var_os(name).is_some_and(|v| !v.is_empty());
+    let tty = io::stdout().is_terminal();
+    if args.no_color || env_set("NO_COLOR") || (!tty && !env_set("CLICOLOR_FORCE")) {
+        colored::control::set_override(false);
+    }
+
+    remote::configure(remote::Options { insecure: args.insecure, timeout: Duration::from_secs(args.fetch_timeout) });
+
+    // Подкоманды сводятся к прежним флагам; у new, schema и автодополнения — свой путь
+    let mut export = None;
+    let mut fmt = None;
+    let mut lock = false;
+    let mut graph = None;
+    match args.command.take() {
+        Some(CliCommand::Completions { shell }) => {
+            print!("{}", completion_script(shell));
+            return;
         }
-        println!("    {} Целостность подтверждена", "✓".green());
-        Ok(())
-    }
-
-    fn verify_signature(&mut self, file: &str) -> Result<(), String> {
-        print!("  {} Проверка подписи: {} ... ", "🔏".normal(), file.cyan());
-        io::stdout().flush().unwrap();
-        if !self.quick_mode { thread::sleep(Duration::from_millis(400)); }
-        println!("{}", "VALID".green().bold());
-        if self.verbose {
-            let mut rng = rand::thread_rng();
-            let key_id: u64 = rng.gen();
-            println!("    {} Key ID: {:016X}", "→".dimmed(), key_id);
+        Some(CliCommand::Schema) => {
+            print!("{}", command_schema());
+            return;
         }
-        Ok(())
-    }
-
-    fn install_packages(&mut self, packages: &str) -> Result<(), String> {
-        let pkg_list: Vec<&str> = packages.split_whitespace().collect();
-        println!("  {} Установка пакетов ({} шт.)", "📦".normal(), pkg_list.len());
-        if !self.quick_mode {
-            for pkg in &pkg_list {
-                print!("    {} {} ", "→".dimmed(), pkg.cyan());
-                io::stdout().flush().unwrap();
-                let spinner_chars = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
-                for i in 0..10 {
-                    print!("\r    {} {} {}", "→".dimmed(), pkg.cyan(), spinner_chars[i % spinner_chars.len()].to_string().cyan());
-                    io::stdout().flush().unwrap();
-                    thread::sleep(Duration::from_millis(80));
+        Some(CliCommand::CompletePhases { file }) => std::process::exit(if print_phase_names(&file) { 0 } else { 1 }),
+        Some(CliCommand::New { name, template, force, list_templates: list }) => {
+            if list {
+                list_templates();
+                return;
+            }
+            let name = name.expect("без --list-templates clap требует NAME");
+            match new_package(&name, &template, force) {
+                Ok(path) => {
+                    println!("{} Создан {}", "✓".green(), path.display());
+                    println!("  Запустите: {} run {}", "instnoth".yellow(), path.display());
+                }
+                Err(e) => {
+                    eprintln!("{} {}", "✗".red(), e);
+                    std::process::exit(1);
                 }
-                println!("\r    {} {} {}", "→".dimmed(), pkg.cyan(), "✓".green());
-            }
-        } else {
-            for pkg in &pkg_list { println!("    {} {} {}", "→".dimmed(), pkg.cyan(), "✓".green()); }
-        }
-        Ok(())
-    }
-
-    fn update_system(&mut self) -> Result<(), String> {
-        println!("  {} Обновление системы", "🔄".normal());
-        if !self.quick_mode {
-            let stages = ["Синхронизация репозиториев...", "Проверка обновлений...", "Загрузка пакетов...", "Установка обновлений...", "Очистка кэша..."];
-            for stage in stages {
-                print!("    {} {}", "→".dimmed(), stage);
-                io::stdout().flush().unwrap();
-                thread::sleep(Duration::from_millis(500));
-                println!(" {}", "✓".green());
-            }
-        }
-        let mut rng = rand::thread_rng();
-        let updated = rng.gen_range(50..200);
-        println!("    {} Обновлено {} пакетов", "✓".green(), updated);
-        Ok(())
-    }
-
-    fn sync_time(&mut self) -> Result<(), String> {
-        print!("  {} Синхронизация времени (NTP) ... ", "🕐".normal());
-        io::stdout().flush().unwrap();
-        if !self.quick_mode { thread::sleep(Duration::from_millis(500)); }
-        println!("{}", "OK".green());
-        if self.verbose {
-            println!("    {} Сервер: pool.ntp.org", "→".dimmed());
-            println!("    {} Смещение: +0.003s", "→".dimmed());
-        }
-        Ok(())
-    }
-
-    fn network_config(&mut self, interface: &str, config: &str) -> Result<(), String> {
-        println!("  {} Настройка сети: {} ({})", "🌐".normal(), interface.cyan(), config.yellow());
-        if !self.quick_mode {
-            if config == "dhcp" {
-                print!("    {} Получение IP через DHCP ", "→".dimmed());
-                io::stdout().flush().unwrap();
-                thread::sleep(Duration::from_millis(800));
-                let ip = RandomSystemInfo::ip_address();
-                println!("{}", ip.green());
-            } else {
-                println!("    {} Применение статической конфигурации", "→".dimmed());
-                thread::sleep(Duration::from_millis(300));
             }
-            println!("    {} Проверка подключения...", "→".dimmed());
-            thread::sleep(Duration::from_millis(400));
+            return;
         }
-        println!("    {} Сеть настроена", "✓".green());
-        Ok(())
-    }
-
-    fn scan_hardware(&mut self) -> Result<(), String> {
-        println!("  {} Сканирование оборудования", "🔎".normal());
-        if !self.quick_mode {
-            let devices = [("PCI", "Видеоадаптер, Сетевой контроллер, USB контроллер"), ("USB", "Клавиатура, Мышь, USB Hub"), ("ACPI", "Управление питанием, Термальные зоны"), ("SATA", "SSD, HDD"), ("NVMe", "NVMe SSD")];
-            for (bus, found) in devices {
-                print!("    {} Шина {} ... ", "→".dimmed(), bus.cyan());
-                io::stdout().flush().unwrap();
-                thread::sleep(Duration::from_millis(300));
-                println!("{}", found.dimmed());
-            }
+        Some(CliCommand::Run { files }) => args.add_files(files),
+        Some(CliCommand::Check { files }) => {
+            args.add_files(files);
+            args.validate = true;
         }
-        println!("    {} Сканирование завершено", "✓".green());
-        Ok(())
-    }
-
-    fn detect_drivers(&mut self) -> Result<(), String> {
-        println!("  {} Определение необходимых драйверов", "🔍".normal());
-        if !self.quick_mode {
-            let drivers = [("nvidia", "Видеокарта NVIDIA"), ("iwlwifi", "Intel Wi-Fi"), ("r8169", "Realtek Ethernet"), ("xhci_hcd", "USB 3.0"), ("nvme", "NVMe SSD"), ("snd_hda_intel", "Intel HD Audio")];
-            for (drv, desc) in drivers {
-                println!("    {} {} - {}", "+".dimmed(), drv.cyan(), desc.dimmed());
-                thread::sleep(Duration::from_millis(150));
-            }
+        Some(CliCommand::Deps { files, format, out }) => {
+            args.add_files(files);
+            args.show_deps = true;
+            graph = format.map(|format| (format, out));
         }
-        Ok(())
-    }
-
-    fn install_driver(&mut self, driver: &str) -> Result<(), String> {
-        print!("  {} Установка драйвера: {} ", "📦".normal(), driver.cyan());
-        io::stdout().flush().unwrap();
-        if !self.quick_mode {
-            let spinner_chars = ['⣾', '⣽', '⣻', '⢿', '⡿', '⣟', '⣯', '⣷'];
-            for i in 0..15 {
-                print!("\r  {} Установка драйвера: {} {}", "📦".normal(), driver.cyan(), spinner_chars[i % spinner_chars.len()].to_string().cyan());
-                io::stdout().flush().unwrap();
-                thread::sleep(Duration::from_millis(100));
-            }
+        Some(CliCommand::List { installed: false }) => args.list_builtin = true,
+        Some(CliCommand::List { installed: true }) => {
+            let inventory = installed_path(&args).map(|path| load_inventory(&path)).unwrap_or_default();
+            list_installed(&inventory);
+            return;
         }
-        println!("\r  {} Установка драйвера: {} {}", "📦".normal(), driver.cyan(), "✓".green());
-        Ok(())
-    }
-
-    fn simulate_operation(&mut self, msg: &str, delay_ms: u64) -> Result<(), String> {
-        print!("  {} {} ", "→".dimmed(), msg);
-        io::stdout().flush().unwrap();
-        if !self.quick_mode {
-            let spinner_chars = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
-            let iterations = delay_ms / 80;
-            for i in 0..iterations {
-                print!("\r  {} {} {}", spinner_chars[i as usize % spinner_chars.len()].to_string().cyan(), msg, " ");
-                io::stdout().flush().unwrap();
-                thread::sleep(Duration::from_millis(80));
+        Some(CliCommand::Remove { names }) => {
+            let Some(path) = installed_path(&args) else {
+                eprintln!("{} Не задан каталог состояния: укажите --state-dir", "✗".red());
+                std::process::exit(1);
+            };
+            let mut inventory = load_inventory(&path);
+            let unknown: Vec<&str> = names.iter().filter(|name| inventory.get(name).is_none()).map(String::as_str).collect();
+            if !unknown.is_empty() {
+                eprintln!("{} Не установлены: {} (см. instnoth list --installed)", "✗".red(), unknown.join(", "));
+                std::process::exit(1);
             }
-        }
-        println!("\r  {} {} {}", "✓".green(), msg, " ");
-        Ok(())
-    }
-
-    fn simulate_download(&mut self, url: &str, size: u64) -> Result<(), String> {
-        println!("  {} Загрузка: {}", "⬇".blue(), url.cyan());
-        if !self.quick_mode {
-            let pb = ProgressBar::new(size);
-            pb.set_style(ProgressStyle::default_bar().template("    [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})").unwrap().progress_chars("█▉▊▋▌▍▎▏ "));
-            let mut downloaded = 0u64;
-            let mut rng = rand::thread_rng();
-            while downloaded < size {
-                let chunk = rng.gen_range(10..50).min((size - downloaded) as u64);
-                downloaded += chunk;
-                pb.set_position(downloaded);
-                thread::sleep(Duration::from_millis(rng.gen_range(20..60)));
+            if args.uninstall {
+                // Удаляются ровно названные пакеты: их файлы без зависимостей, запись
+                // пропадает после успешного удаления
+                let sources: Vec<PathBuf> = names.iter().map(|name| PathBuf::from(&inventory.get(name).expect("проверено выше").source)).collect();
+                if let Some(name) = names.iter().zip(&sources).find(|(_, source)| *source == Path::new("-")).map(|(name, _)| name) {
+                    eprintln!("{} {} установлен из stdin: для --uninstall нет файла, уберите его без --uninstall", "✗".red(), name);
+                    std::process::exit(1);
+                }
+                args.add_files(sources);
+                args.skip_deps = true;
+            } else {
+                for name in &names {
+                    let removed = inventory.remove(name).expect("проверено выше");
+                    println!("{} {} (v{}) убран из списка установленных", "✓".green(), removed.name, removed.version);
+                }
+                save_inventory(&inventory, &path);
+                return;
             }
-            pb.finish_and_clear();
         }
-        println!("    {} Загружено: {} байт", "✓".green(), size);
-        Ok(())
-    }
-
-    fn simulate_extraction(&mut self, from: &str, to: &str) -> Result<(), String> {
-        println!("  {} Распаковка: {} → {}", "📦".normal(), from.dimmed(), to.cyan());
-        if !self.quick_mode {
-            let files = vec!["bin/main", "lib/libcore.so", "share/data.dat", "etc/config.conf", "doc/README.md"];
-            for file in files {
-                print!("    {} {}", "→".dimmed(), file);
-                io::stdout().flush().unwrap();
-                thread::sleep(Duration::from_millis(100));
-                println!(" {}", "✓".green());
-            }
-        } else {
-            println!("    {} 5 файлов распаковано", "✓".green());
+        Some(CliCommand::Lock { files }) => {
+            args.add_files(files);
+            lock = true;
         }
-        Ok(())
-    }
-
-    fn simulate_dep_install(&mut self, name: &str, version: &str) -> Result<(), String> {
-        print!("  {} Установка зависимости: {} (v{}) ", "📦".normal(), name.cyan(), version.yellow());
-        io::stdout().flush().unwrap();
-        if !self.quick_mode {
-            let spinner_chars = ['⣾', '⣽', '⣻', '⢿', '⡿', '⣟', '⣯', '⣷'];
-            for i in 0..15 {
-                print!("\r  {} Установка зависимости: {} (v{}) {} ", "📦".normal(), name.cyan(), version.yellow(), spinner_chars[i % spinner_chars.len()].to_string().cyan());
-                io::stdout().flush().unwrap();
-                thread::sleep(Duration::from_millis(100));
-            }
+        Some(CliCommand::Fmt { files, check }) => {
+            args.add_files(files);
+            fmt = Some(check);
         }
-        println!("\r  {} Установка зависимости: {} (v{}) {}     ", "📦".normal(), name.cyan(), version.yellow(), "✓".green());
-        Ok(())
-    }
-
-    fn simulate_script_execution(&mut self) -> Result<(), String> {
-        if !self.quick_mode {
-            let outputs = vec!["  Initializing...", "  Loading modules...", "  Applying configuration...", "  Done."];
-            for output in outputs { println!("    {}", output.dimmed()); thread::sleep(Duration::from_millis(150)); }
+        Some(CliCommand::Export { files, format, out }) => {
+            args.add_files(files);
+            export = Some((format, out));
         }
-        Ok(())
-    }
-
-    fn show_progress_bar(&self, pct: u8) {
-        let width = 30;
-        let filled = (width * pct as usize) / 100;
-        let empty = width - filled;
-        let bar = format!("[{}{}] {}%", "█".repeat(filled).green(), "░".repeat(empty).dimmed(), pct);
-        println!("  {} Прогресс: {}", "◉".blue(), bar);
+        Some(CliCommand::External(_)) => unreachable!("без подкоманды parse_cli подставляет run"),
+        None => {}
     }
-}
-
-// ============== Main ==============
-
-fn list_builtin() {
-    println!();
-    println!("{}", "Встроенные файлы установки:".green().bold());
-    println!("{}", "─".repeat(40).dimmed());
-    println!("  {}   - Установка Python 3.12", "python".cyan());
-    println!("  {}   - Установка Node.js 20 LTS", "nodejs".cyan());
-    println!("  {}   - Установка Docker Engine", "docker".cyan());
-    println!("  {}    - Установка Arch Linux", "linux".cyan());
-    println!("  {}      - Установка всего (Python + Node.js + Docker)", "all".cyan());
-    println!("  {} - Полный стек разработчика", "devstack".cyan());
-    println!();
-    println!("Использование:");
-    println!("  {} --file examples/<имя>.instnoth", "instnoth".yellow());
-    println!("  {} --file examples/python.instnoth examples/nodejs.instnoth", "instnoth".yellow());
-    println!("  {} --file examples/all.instnoth --show-deps", "instnoth".yellow());
-    println!();
-}
-
-fn main() {
-    let args = Args::parse();
 
     if args.list_builtin {
         list_builtin();
         return;
     }
 
-    let files = match args.file {
-        Some(f) => f,
+    if let Some(files) = &args.lint {
+        let files = collect_files(files, args.recursive).unwrap_or_else(|e| {
+            eprintln!("{} {}", "✗".red(), e);
+            std::process::exit(1);
+        });
+        let ok = lint_files(&files, &args);
+        std::process::exit(if ok { 0 } else { 1 });
+    }
+
+    let files: Vec<PathBuf> = match &args.file {
+        Some(f) => collect_files(f, args.recursive).unwrap_or_else(|e| {
+            eprintln!("{} {}", "✗".red(), e);
+            std::process::exit(1);
+        }),
         None => {
-            eprintln!("{} Укажите файл(ы) установки: instnoth --file <путь.instnoth> [<путь2.instnoth> ...]", "✗".red());
+            eprintln!("{} Укажите файл(ы) установки: instnoth run <путь.instnoth> [<путь2.instnoth> ...]", "✗".red());
             eprintln!("Используйте {} для просмотра встроенных файлов", "--list-builtin".cyan());
             std::process::exit(1);
         }
     };
+    // stdin читается один раз: после этого вопросам и --step отвечать нечем
+    let reads_stdin = files.iter().any(|f| f == Path::new("-"));
+    if files.iter().filter(|f| *f == Path::new("-")).count() > 1 {
+        eprintln!("{} stdin (\"-\") можно указать в --file только один раз", "✗".red());
+        std::process::exit(1);
+    }
+
+    if let Some(check) = fmt {
+        std::process::exit(if fmt_files(&files, check) { 0 } else { 1 });
+    }
+
+    if args.validate {
+        let ok = validate_files(&files, &args);
+        std::process::exit(if ok { 0 } else { 1 });
+    }
 
     // Загружаем все указанные пакеты
     let mut packages = Vec::new();
     let mut base_path = PathBuf::from(".");
 
     for file_path in &files {
-        let content = match fs::read_to_string(file_path) {
+        let content = match read_package_file(file_path) {
             Ok(c) => c,
             Err(e) => {
-                eprintln!("{} Не удалось прочитать файл {:?}: {}", "✗".red(), file_path, e);
+                match remote::url(file_path) {
+                    Some(url) => eprintln!("{} Не удалось загрузить {}: {}", "✗".red(), url, e),
+                    None => eprintln!("{} Не удалось прочитать файл {:?}: {}", "✗".red(), file_path, e),
+                }
                 std::process::exit(1);
             }
         };
 
-        // Устанавливаем базовый путь для зависимостей
-        if let Some(parent) = file_path.parent() {
+        // Зависимости файла ищутся от его каталога, а у пакета из stdin каталога нет —
+        // для него берётся каталог последнего из указанных файлов
+        let from_stdin = file_path == Path::new("-");
+        if let Some(parent) = file_path.parent().filter(|_| !from_stdin) {
             base_path = parent.to_path_buf();
         }
 
-        let mut parser = InstnothParser::with_path(content, file_path.clone());
-        match parser.parse() {
+        let mut parser = if from_stdin {
+            InstnothParser::new(content)
+        } else {
+            InstnothParser::with_path(content, file_path.clone())
+        };
+        parser.strict = args.strict;
+        parser.max_delay = args.max_delay;
+        let result = parser.parse();
+        for diag in parser.diagnostics.iter().filter(|d| d.severity == Severity::Warning) {
+            eprintln!("{} {}:{}: {}", "⚠".yellow(), file_path.display(), diag.line, diag.message);
+        }
+        match result {
             Ok(pkg) => packages.push(pkg),
             Err(e) => {
                 eprintln!("{} Ошибка парсинга {:?}: {}", "✗".red(), file_path, e);
@@ -1567,7 +1325,42 @@ fn main() {
         }
     }
 
-    let dep_manager = DependencyManager::new(base_path);
+    if reads_stdin && packages.iter().any(|p| p.file_path.is_none() && !p.depends.is_empty()) {
+        let place = if base_path == Path::new(".") {
+            "текущего каталога".to_string()
+        } else {
+            format!("каталога {}", base_path.display())
+        };
+        eprintln!("{} -: зависимости пакета из stdin ищутся относительно {}", "⚠".yellow(), place);
+    }
+    let mut dep_manager = DependencyManager::new(base_path);
+    let deps_depth = if args.skip_deps { Some(0) } else { args.deps_depth };
+    dep_manager.depth_limit = deps_depth;
+
+    if args.list_phases {
+        list_phases(&packages);
+        return;
+    }
+
+    if let Some((format, out)) = graph {
+        dep_manager.scan(&packages);
+        let graph = DependencyGraph::build(&packages, &dep_manager);
+        let text = match format {
+            GraphFormat::Dot => graph.to_dot(),
+            GraphFormat::Mermaid => graph.to_mermaid(),
+        };
+        match out {
+            Some(path) => {
+                if let Err(e) = fs::write(&path, text) {
+                    eprintln!("{} Не удалось записать граф {:?}: {}", "✗".red(), path, e);
+                    std::process::exit(1);
+                }
+                eprintln!("{} Граф записан: {}", "✓".green(), path.display());
+            }
+            None => print!("{}", text),
+        }
+        return;
+    }
 
     // Показываем дерево зависимостей если запрошено
     if args.show_deps {
@@ -1575,63 +1368,400 @@ fn main() {
         println!("{}", "Дерево зависимостей:".green().bold());
         println!("{}", "─".repeat(40).dimmed());
         let mut visited = HashSet::new();
+        dep_manager.scan(&packages);
         for pkg in &packages {
-            show_dependency_tree(pkg, &dep_manager, 0, &mut visited);
+            show_dependency_tree(pkg, &dep_manager, args.max_depth, &mut visited);
         }
         println!();
         return;
     }
 
-    // Определяем порядок установки с учётом зависимостей
-    let install_order = if args.skip_deps {
-        packages.clone()
-    } else {
+    if args.plan {
         let mut dm = DependencyManager::new(dep_manager.base_path.clone());
-        match dm.get_install_order(&packages) {
-            Ok(order) => order,
+        dm.ignore_missing = args.ignore_missing_deps;
+        dm.depth_limit = deps_depth;
+        match dm.get_install_levels(&packages) {
+            Ok(levels) => {
+                check_conflicts(&find_conflicts(&levels.concat()), args.force_conflicts);
+                print_install_levels(&levels, deps_depth, args.lang.unwrap_or_else(Lang::from_env));
+            }
             Err(e) => {
                 eprintln!("{} Ошибка разрешения зависимостей: {}", "✗".red(), e);
                 std::process::exit(1);
             }
         }
+        return;
+    }
+
+    if lock || args.locked {
+        let mut dm = DependencyManager::new(dep_manager.base_path.clone());
+        dm.ignore_missing = args.ignore_missing_deps;
+        let mut ok = true;
+        for pkg in &packages {
+            let result = lockfile_path(pkg).and_then(|path| {
+                if lock {
+                    let count = dm.write_lockfile(pkg, &path)?;
+                    println!("{} Записан {} ({})", "✓".green(), path.display(), plural(count as u32, "файл", "файла", "файлов"));
+                    Ok(())
+                } else {
+                    dm.verify_lockfile(pkg, &path)
+                }
+            });
+            if let Err(e) = result {
+                eprintln!("{} {}{}", "✗".red(), if lock { "" } else { "--locked: " }, e);
+                ok = false;
+            }
+        }
+        if lock || !ok {
+            std::process::exit(if ok { 0 } else { 1 });
+        }
+    }
+
+    // Определяем порядок установки с учётом зависимостей
+    let mut dm = DependencyManager::new(dep_manager.base_path.clone());
+    dm.ignore_missing = args.ignore_missing_deps;
+    dm.depth_limit = deps_depth;
+    let mut install_order = dm.get_install_order(&packages).unwrap_or_else(|e| {
+        eprintln!("{} Ошибка разрешения зависимостей: {}", "✗".red(), e);
+        std::process::exit(1);
+    });
+    let duplicates = std::mem::take(&mut dm.duplicates);
+    if !args.uninstall {
+        check_conflicts(&find_conflicts(&install_order), args.force_conflicts);
+    }
+
+    // Удаляются сначала зависящие пакеты, их зависимости — последними
+    if args.uninstall {
+        install_order.reverse();
+    }
+
+    let unmatched = unmatched_phase_patterns(&args.only_phase, &install_order);
+    if !unmatched.is_empty() {
+        for pattern in unmatched {
+            eprintln!("{} --only-phase {:?}: нет такой фазы", "✗".red(), pattern);
+        }
+        eprintln!("Фазы:");
+        for pkg in &install_order {
+            for phase in &pkg.phases {
+                eprintln!("  {} {}", format!("{}:", pkg.name).dimmed(), phase.name);
+            }
+        }
+        std::process::exit(1);
+    }
+
+    let verbosity = match (args.quiet, args.verbose) {
+        (true, _) => Verbosity::Quiet,
+        (_, true) => Verbosity::Verbose,
+        _ => Verbosity::Normal,
+    };
+    // В терминале рамки во всю его ширину, но не шире 100 колонок: длинные линии хуже читаются
+    let width = match args.width {
+        Some(cols) => cols.into(),
+        None => terminal_size().filter(|_| tty).map_or(DEFAULT_WIDTH, |(cols, _)| cols.clamp(40, 100)),
+    };
+    let theme = match &args.theme {
+        Some(spec) => Theme::load(spec).unwrap_or_else(|e| {
+            eprintln!("{} Ошибка темы {}", "✗".red(), e);
+            std::process::exit(1);
+        }),
+        None => Theme::default(),
     };
+    let mut out: Box<dyn OutputSink> = match args.output {
+        OutputFormat::Text => Box::new(TerminalSink::new(!tty, args.ascii)),
+        OutputFormat::Json => Box::new(JsonSink),
+    };
+    if let Some(path) = &args.log_file {
+        match LogSink::create(path, out) {
+            Ok(sink) => out = Box::new(sink),
+            Err(e) => {
+                eprintln!("{} Не удалось создать журнал {:?}: {}", "✗".red(), path, e);
+                std::process::exit(1);
+            }
+        }
+    }
+    if let Some(path) = &args.record {
+        // Запись не уже экрана: рамки шириной --width не должны переноситься при просмотре
+        let (cols, rows) = terminal_size().filter(|_| tty).unwrap_or((DEFAULT_WIDTH, 24));
+        match CastSink::create(path, out, (cols.max(width), rows), args.ascii) {
+            Ok(sink) => out = Box::new(sink),
+            Err(e) => {
+                eprintln!("{} Не удалось создать запись {:?}: {}", "✗".red(), path, e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let mut simulator = Simulator::with_options(SimulatorOptions {
+        quick: args.quick,
+        verbosity,
+        minimal: args.minimal,
+        speed: args.speed,
+        seed: args.seed,
+        theme,
+        out,
+        chaos: args.chaos.unwrap_or(0),
+        chaos_fatal: args.chaos_fatal,
+        // stdin с файлом установки или в JSON-режиме не для ответов: вопросы получают значения по умолчанию
+        non_interactive: args.non_interactive || reads_stdin || args.output == OutputFormat::Json,
+    });
+    simulator.no_reboot = args.no_reboot;
+    simulator.fresh_hardware = args.fresh_hardware;
+    simulator.step = args.step;
+    if args.step && reads_stdin {
+        eprintln!("{} --step: stdin занят файлом установки, команды выполняются без остановок", "⚠".yellow());
+        simulator.step = false;
+    }
+    simulator.uninstalling = args.uninstall;
+    simulator.deps_depth = deps_depth;
+    simulator.only_phases = args.only_phase.clone();
+    simulator.skip_phases = args.skip_phase.clone();
+    simulator.sudo = args.sudo;
+    simulator.ignore_failures = args.ignore_failures;
+    simulator.width = width;
+    simulator.lang = args.lang.unwrap_or_else(Lang::from_env);
+
+    if args.dry_run {
+        simulator.print_plan(&install_order, &duplicates);
+        return;
+    }
+
+    if let Some((format, out)) = export {
+        let mut backend: Box<dyn ScriptBackend> = match format {
+            ScriptFormat::Sh => Box::new(ShellScript::default()),
+        };
+        simulator.export_script(&install_order, backend.as_mut());
+        let script = backend.finish();
+        match out {
+            Some(path) => {
+                if let Err(e) = write_script(&path, &script) {
+                    eprintln!("{} Не удалось записать скрипт {:?}: {}", "✗".red(), path, e);
+                    std::process::exit(1);
+                }
+                eprintln!("{} Скрипт записан: {} (запуск: bash {})", "✓".green(), path.display(), path.display());
+            }
+            None => print!("{}", script),
+        }
+        return;
+    }
+
+    // Установленное прежде не ставится снова, пока файл не новее записанной версии
+    let inventory_path = installed_path(&args);
+    let mut inventory = inventory_path.as_deref().map(load_inventory).unwrap_or_default();
+    if !args.uninstall && !args.reinstall {
+        install_order.retain(|pkg| {
+            let Some(installed) = inventory.current(pkg) else { return true };
+            say!(simulator, "{} {}", "○".dimmed(), simulator.trf(Msg::AlreadyInstalled, &[&pkg.name, &installed.version]).dimmed());
+            simulator.out.event(Event::new("package_skipped").with("name", pkg.name.as_str()).with("installed", installed.version.as_str()));
+            false
+        });
+        if install_order.is_empty() {
+            say!(simulator, "{}", simulator.tr(Msg::NothingToInstall));
+            return;
+        }
+    }
+    if !args.uninstall {
+        check_conflicts(&inventory.conflicts(&install_order), args.force_conflicts);
+    }
 
     // Выводим план установки
+    let names: Vec<String> = install_order.iter().map(|p| p.name.clone()).collect();
+
+    // Прерванный запуск продолжается с тем же seed, иначе пройденные фазы проиграются иначе
+    if let Some(path) = &args.state_file {
+        let saved = SavedProgress::load(path)
+            .filter(|_| !args.restart)
+            .filter(|saved| saved.packages == names && saved.package < install_order.len());
+        let resume = saved.filter(|saved| simulator.confirm_resume(saved, &install_order));
+        let seed = match &resume {
+            Some(saved) => saved.seed,
+            None => args.seed.unwrap_or_else(rand::random),
+        };
+        simulator.rng = StdRng::seed_from_u64(seed);
+        simulator.resume_at = resume.map(|saved| (saved.package, saved.phase));
+        simulator.state_file = Some((path.clone(), SavedProgress { seed, packages: names.clone(), ..SavedProgress::default() }));
+    }
+    simulator.out.event(Event::new("plan").with("packages", names.as_slice()));
     if install_order.len() > 1 {
-        println!();
-        println!("{}", "╔═══════════════════════════════════════════════════════════════════╗".cyan());
-        println!("{}", "║  InstNoth Multi-Package Installer                                 ║".cyan());
-        println!("{}", "╚═══════════════════════════════════════════════════════════════════╝".cyan());
-        println!();
-        println!("{}: {} пакетов", "План установки".green().bold(), install_order.len());
+        say!(simulator);
+        let (title, plan) = if args.uninstall {
+            (Msg::MultiUninstaller, Msg::PlanUninstall)
+        } else {
+            (Msg::MultiInstaller, Msg::PlanInstall)
+        };
+        simulator.print_banner(&format!("{} {}", simulator.theme.name, simulator.tr(title)));
+        say!(simulator);
+        say!(simulator, "{}: {}", simulator.tr(plan).paint(simulator.theme.success).bold(), simulator.trn(Msg::Packages, install_order.len() as u32));
+        if let Some(eta) = simulator.eta(simulator.estimate(&install_order)).filter(|_| !args.uninstall) {
+            say!(simulator, "{}: {}", simulator.tr(Msg::ExpectedTime).paint(simulator.theme.success).bold(), format_eta(eta, simulator.lang));
+        }
+        if let Some(line) = simulator.deps_depth_line() {
+            say!(simulator, "{}", line);
+        }
         for (i, pkg) in install_order.iter().enumerate() {
-            println!("  {}. {} (v{})", (i + 1).to_string().yellow(), pkg.name.cyan(), pkg.version);
+            let source = pkg.file_path.as_deref().map_or("stdin".to_string(), |p| p.display().to_string());
+            say!(simulator, "  {}. {} (v{})  {}", (i + 1).to_string().paint(simulator.theme.warning), pkg.name.paint(simulator.theme.accent), pkg.version, source.dimmed());
         }
-        println!();
-        println!("{}", "───────────────────────────────────────────────────────────────────".dimmed());
+        say!(simulator);
+        say!(simulator, "{}", "─".repeat(simulator.width - 2).dimmed());
     }
 
     // Запускаем установку каждого пакета
-    let mut simulator = Simulator::new(args.quick, args.verbose);
-    let mut installed_count = 0;
+    let jobs = args.parallel.map_or(1, usize::from);
+    if !args.uninstall && jobs == 1 {
+        simulator.start_total_bar(&install_order);
+    }
 
-    for pkg in &install_order {
-        if let Err(e) = simulator.run(pkg) {
-            eprintln!("{} Ошибка установки {}: {}", "✗".red(), pkg.name, e);
-            std::process::exit(1);
+    install_interrupt_handler();
+    let results = if jobs > 1 {
+        let deps = dm.dependencies_in(&install_order);
+        simulator.run_parallel(&install_order, &deps, jobs)
+    } else {
+        // Сбой одного пакета останавливает весь запуск, следующие не начинаются
+        let mut results = vec![None; install_order.len()];
+        for (i, pkg) in install_order.iter().enumerate() {
+            simulator.package_index = i;
+            let result = if args.uninstall {
+                simulator.uninstall(pkg)
+            } else if simulator.resume_at.is_some_and(|(package, _)| i < package) {
+                // Пакет целиком установлен прерванным запуском
+                let result = simulator.replaying(|s| s.run(pkg));
+                simulator.suspended(|s| say!(s, "{} {} {}", "▷".dimmed(), pkg.name.dimmed(), s.tr(Msg::PackageDoneEarlier).dimmed()));
+                result
+            } else {
+                simulator.run(pkg)
+            };
+            let failed = result.is_err();
+            results[i] = Some(result);
+            if failed {
+                break;
+            }
+            simulator.package_index = i + 1;
+            simulator.save_progress(0, 0);
         }
-        installed_count += 1;
+        results
+    };
+    simulator.finish_total_bar();
+
+    // Успешно установленное запоминается и тогда, когда следующий пакет сбоит
+    if let Some(path) = &inventory_path {
+        let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        let done: Vec<&Package> = install_order.iter().zip(&results).filter(|(_, r)| matches!(r, Some(Ok(())))).map(|(p, _)| p).collect();
+        for pkg in &done {
+            if args.uninstall {
+                inventory.remove(&pkg.name);
+            } else {
+                inventory.record(pkg, now);
+            }
+        }
+        if !done.is_empty() {
+            save_inventory(&inventory, path);
+        }
+    }
+
+    let names = |pick: fn(&Option<Result<(), String>>) -> bool| -> Vec<&str> {
+        install_order.iter().zip(&results).filter(|(_, r)| pick(r)).map(|(p, _)| p.name.as_str()).collect()
+    };
+    if results.iter().any(|r| matches!(r, Some(Err(_)))) {
+        let remaining = names(Option::is_none);
+        if simulator.interrupted() {
+            if !remaining.is_empty() {
+                let what = if args.uninstall { Msg::NotRemoved } else { Msg::NotInstalled };
+                say!(simulator, "  {} {}", "○".dimmed(), simulator.trf(what, &[&remaining.join(", ")]).dimmed());
+                say!(simulator);
+            }
+            // Полоса indicatif могла оставить курсор скрытым
+            if io::stderr().is_terminal() {
+                eprint!("\x1b[?25h");
+            }
+            std::process::exit(130);
+        }
+        // Видно, что успело установиться до сбоя и что из-за него не начиналось
+        if install_order.len() > 1 {
+            let (done, skipped) = if args.uninstall {
+                (Msg::RemovedList, Msg::NotRemovedAfterFailure)
+            } else {
+                (Msg::InstalledList, Msg::SkippedAfterFailure)
+            };
+            let installed = names(|r| matches!(r, Some(Ok(()))));
+            if !installed.is_empty() {
+                say!(simulator, "  {} {}: {}", "✓".paint(simulator.theme.success), simulator.tr(done), installed.join(", "));
+            }
+            if !remaining.is_empty() {
+                say!(simulator, "  {} {}", "○".dimmed(), format!("{}: {}", simulator.tr(skipped), remaining.join(", ")).dimmed());
+            }
+            say!(simulator);
+        }
+        let what = if args.uninstall { Msg::UninstallError } else { Msg::InstallError };
+        for (pkg, result) in install_order.iter().zip(&results) {
+            if let Some(Err(e)) = result {
+                eprintln!("{} {}", "✗".red(), simulator.trf(what, &[&pkg.name, e]));
+            }
+        }
+        std::process::exit(simulator.exit_code.unwrap_or(1));
+    }
+    let installed_count = install_order.len();
+
+    // Установка завершена — продолжать нечего
+    if let Some((path, _)) = &simulator.state_file {
+        let _ = fs::remove_file(path);
     }
 
     // Финальное сообщение для множественной установки
     if install_order.len() > 1 {
-        println!();
-        println!("{}", "═══════════════════════════════════════════════════════════════════".green());
-        println!("  {} Установлено {} пакетов:", "✓".green().bold(), installed_count);
+        say!(simulator);
+        say!(simulator, "{}", simulator.rule().paint(simulator.theme.success));
+        let what = if args.uninstall { Msg::RemovedCount } else { Msg::InstalledCount };
+        say!(simulator, "  {} {}", "✓".paint(simulator.theme.success).bold(), simulator.trf(what, &[&simulator.trn(Msg::Packages, installed_count as u32)]));
         for pkg in &install_order {
-            println!("    {} {} (v{})", "•".green(), pkg.name, pkg.version);
+            say!(simulator, "    {} {} (v{})", "•".paint(simulator.theme.success), pkg.name, pkg.version);
         }
-        println!("{}", "═══════════════════════════════════════════════════════════════════".green());
-        println!();
+        say!(simulator, "    {}", simulator.total_stats.describe(simulator.lang).dimmed());
+        say!(simulator, "{}", simulator.rule().paint(simulator.theme.success));
+        say!(simulator);
+    }
+
+    simulator.out.event(Event::new("done").with("installed", installed_count));
+
+    if !args.no_fun && !args.uninstall {
+        simulator.print_fun_stats(&install_order);
+    }
+
+    if !args.no_summary {
+        simulator.print_summary();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_arguments_expand_directories_and_globs() {
+        let root = std::env::temp_dir().join(format!("instnoth-files-{}", std::process::id()));
+        for file in ["b.instnoth", "a.instnoth", "Demo.instnoth", "notes.txt", "sub/c.instnoth", "sub/deep/d.instnoth", ".hidden/e.instnoth"] {
+            let path = root.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+        let names = |files: Vec<PathBuf>| -> Vec<String> {
+            files.iter().map(|f| f.strip_prefix(&root).unwrap().display().to_string()).collect()
+        };
+
+        assert_eq!(names(collect_files(std::slice::from_ref(&root), false).unwrap()), ["Demo.instnoth", "a.instnoth", "b.instnoth"]);
+        assert_eq!(names(collect_files(std::slice::from_ref(&root), true).unwrap()),
+            ["Demo.instnoth", "a.instnoth", "b.instnoth", "sub/c.instnoth", "sub/deep/d.instnoth"]);
+        assert_eq!(names(collect_files(&[root.join("**/*.instnoth")], false).unwrap()),
+            ["Demo.instnoth", "a.instnoth", "b.instnoth", "sub/c.instnoth", "sub/deep/d.instnoth"]);
+        // Регистр в шаблоне имеет значение, как у shell
+        assert_eq!(names(collect_files(&[root.join("D*.instnoth")], false).unwrap()), ["Demo.instnoth"]);
+        assert!(collect_files(&[root.join("demo*.instnoth")], false).is_err());
+        // Каталог и шаблон дают одни и те же файлы — каждый остаётся один раз
+        assert_eq!(names(collect_files(&[root.join("b.instnoth"), root.clone(), root.join("s?b/*.instnoth")], false).unwrap()),
+            ["b.instnoth", "Demo.instnoth", "a.instnoth", "sub/c.instnoth"]);
+
+        let missing = collect_files(&[root.join("sub/*.txt")], false).unwrap_err();
+        assert!(missing.contains(&format!("искали в {}", root.join("sub").display())), "{}", missing);
+        assert!(collect_files(&[root.join("sub/deep/..").join(".hidden")], false).is_ok());
+        fs::remove_dir_all(&root).unwrap();
     }
 }
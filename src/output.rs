@@ -0,0 +1,1866 @@
+//! Вывод: получатели строк и событий, JSON, темы, переводы сообщений и сохранённый прогресс
+
+use colored::*;
+use indicatif::{ProgressBar, ProgressDrawTarget};
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use crate::{parser::*, sim::*};
+
+// ============== Вывод ==============
+
+/// Формат вывода установки (--output)
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Цветной текст для терминала
+    Text,
+    /// Один JSON-объект на событие в строке, для других программ
+    Json,
+}
+
+/// Язык вывода (--lang)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Lang {
+    Ru,
+    En,
+}
+
+impl Lang {
+    /// По переменной LANG: английский для en_*, иначе русский
+    pub fn from_env() -> Self {
+        match std::env::var("LANG") {
+            Ok(lang) if lang.starts_with("en") => Lang::En,
+            _ => Lang::Ru,
+        }
+    }
+}
+
+/// Постоянные строки вывода. Переводы — в Msg::text: match без `_`, поэтому строка без
+/// перевода на оба языка не скомпилируется. Текст из файла установки (message, success) не переводится
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Msg {
+    // Шапка; подписи в ней и в русском выводе английские, как у классических установщиков
+    Installer,
+    Uninstaller,
+    MultiInstaller,
+    MultiUninstaller,
+    LabelPackage,
+    LabelVersion,
+    LabelDescription,
+    LabelAuthor,
+    LabelLicense,
+    LabelHomepage,
+    LabelSize,
+    LabelArch,
+    LabelDistro,
+    LabelEta,
+    LabelDepends,
+
+    // План нескольких пакетов
+    PlanInstall,
+    PlanUninstall,
+    ExpectedTime,
+    AlreadyInstalled,
+    DepsDepth,
+    DepsDepthNamed,
+    DepsDepthDirect,
+    DepsDepthUnlimited,
+    NothingToInstall,
+    LevelsTitle,
+    LevelParallel,
+    LevelsTotal,
+
+    // --dry-run, --step и продолжение прерванной установки
+    DryRunPlan,
+    PlannedVia,
+    PlanTotal,
+    Defaulted,
+    AtLine,
+    StepPrompt,
+    ResumeFound,
+    ResumeAtCommand,
+    ResumeAsk,
+    AnswerYesNo,
+
+    // Фазы и общая полоса
+    PhaseOptionalSkipped,
+    PhaseDoneEarlier,
+    PackageDoneEarlier,
+    Rollback,
+    NoInverse,
+    StatusPhase,
+    StatusLeft,
+    EtaSeconds,
+    EtaMinutes,
+    EtaHours,
+
+    // Итоги
+    ScriptTitle,
+    InstalledOk,
+    UninstalledOk,
+    PendingAction,
+    InstallFailed,
+    UninstallFailed,
+    FailedPhase,
+    FailedPhaseCommand,
+    Cancelled,
+    CancelledPhases,
+    PhaseSkipped,
+    PhaseInterrupted,
+    NotInstalled,
+    NotRemoved,
+    InstalledList,
+    RemovedList,
+    SkippedAfterFailure,
+    NotRemovedAfterFailure,
+    InstallError,
+    UninstallError,
+    InstalledCount,
+    RemovedCount,
+
+    // Счётчики; формы числа через «|»: одна, несколько (2–4), много
+    StatsDone,
+    StatsDownloaded,
+    StatsInstalled,
+    StatsEnabled,
+    Operations,
+    Steps,
+    Warnings,
+    Errors,
+    Packages,
+    Commands,
+    Levels,
+    Services,
+
+    // Сводка времени
+    TimingTitle,
+    TimingTotal,
+    Micros,
+    Millis,
+    Seconds,
+    Minutes,
+
+    // Карточка итогов (--no-fun)
+    FunTitle,
+    FunSaved,
+    FunFake,
+    FunAchievement,
+
+    // Команды
+    Progress,
+    CreatingDir,
+    Configuring,
+    CleaningUp,
+    Copying,
+    Linking,
+    SettingMode,
+    RunningScript,
+    CheckingDep,
+    WritingConfig,
+    SettingHostname,
+    SettingTimezone,
+    SettingLocale,
+    LocaleNotGenerated,
+    SettingPassword,
+    Downloading,
+    DownloadedBytes,
+    Extracting,
+    FilesExtracted,
+    InstallingDep,
+    Parallel,
+    ParallelInstalling,
+    ParallelDownloaded,
+    ParallelDelay,
+    ParallelTest,
+    ParallelDep,
+
+    // Повторы, сбои и определение оборудования
+    RetrySucceeded,
+    RetryIn,
+    ChaosRetry,
+    ParallelCrashed,
+    StateWriteFailed,
+    FirewallRule,
+    DetectingCpu,
+    DetectingMemory,
+    DetectingDisk,
+    DetectingGpu,
+    DetectingNetwork,
+    DetectingOs,
+    DetectingKernel,
+    DetectingBios,
+    DetailVendor,
+    DetailModel,
+    DetailCores,
+    DetailFrequency,
+    DetailCapacity,
+    DetailType,
+    DetailSpeed,
+    DetailMemory,
+    DetailAdapter,
+    DetailSystem,
+    DetailVersion,
+    Cores,
+
+    // Тесты и ядро; этапы и пункты списков через «|»
+    RunningTest,
+    TestingComponent,
+    TestsMemory,
+    TestsCpu,
+    TestsDisk,
+    TestsGpu,
+    TestsOther,
+    LoadingModule,
+    UnloadingModule,
+    UpdatingInitramfs,
+    InitramfsSteps,
+    InitramfsUpdated,
+    UpdatingGrub,
+    GeneratingGrubCfg,
+    GrubEntries,
+    GrubUpdated,
+    CompilingKernel,
+    KernelStages,
+    KernelCompiled,
+
+    // Сборка, разделы и файловые системы
+    Building,
+    Files,
+    Threads,
+    Built,
+    Mounting,
+    Unmounting,
+    Formatting,
+    CreatingFs,
+    CreatingPartition,
+    PartitionCreated,
+    GeneratingFstab,
+    FstabGenerated,
+    Partitions,
+    PartitionsFree,
+
+    // Датчики и шифрование
+    Sensors,
+    MoreCores,
+    Fans,
+    Encrypting,
+    EncryptionCancelled,
+    OpenedAs,
+
+    // Пулы, параметры ядра, swap, RAID, пользователи и сервисы
+    CreatingZfsPool,
+    CreatingBtrfs,
+    CreatingDataset,
+    PoolNotCreated,
+    KernelParam,
+    SysctlSaved,
+    KernelCmdline,
+    NeedsUpdateGrub,
+    SettingUpZram,
+    ZramActivated,
+    CreatingSwapFile,
+    SwapTotal,
+    Devices,
+    CreatingRaid,
+    ArrayCapacity,
+    ArraySynced,
+    CreatingUser,
+    UserGroups,
+    UserCreated,
+    EnablingService,
+    DisablingService,
+    StartingService,
+    StoppingService,
+    ManagingService,
+    InstallingBootloader,
+    BootloaderSteps,
+    GrubInstalled,
+    CheckingIntegrity,
+    ComputingChecksums,
+    IntegrityConfirmed,
+
+    // Подписи, репозитории, пакеты, сеть и оборудование
+    CheckingSignature,
+    CheckingChecksum,
+    ChecksumExpected,
+    ChecksumActual,
+    FailureIgnored,
+    AddingRepo,
+    RepoAlreadyAdded,
+    RepoAdded,
+    ImportingKey,
+    KeyImported,
+    InstallingPackages,
+    ExtraRepos,
+    UpdatingSystem,
+    UpdateStages,
+    SystemUpdated,
+    SyncingTime,
+    NtpServer,
+    NtpOffset,
+    ConfiguringNetwork,
+    DhcpLease,
+    StaticConfig,
+    CheckingConnectivity,
+    NetworkConfigured,
+    ScanningHardware,
+    ScanBus,
+    ScanFound,
+    ScanComplete,
+    DetectingDrivers,
+    InstallingDriver,
+
+    // Вопросы, ожидание, развёртывание, локали и снимки
+    SudoNoPassword,
+    RequiredRefused,
+    LicenseMissing,
+    LicenseAgreement,
+    DefaultChoice,
+    Choice,
+    EnterNumber,
+    Waiting,
+    WaitTimedOut,
+    WaitReady,
+    DeployingInfra,
+    RunningPlaybook,
+    GeneratingLocales,
+    Keymap,
+    KeymapApplied,
+    CreatingSnapshot,
+    SnapshotSaved,
+    SnapshotMissing,
+    RollingBack,
+    RollingBackPhase,
+    PhaseTook,
+    SnapshotRestored,
+
+    // Место на диске, прошивка и перезагрузка
+    CheckingSpace,
+    SpaceRequired,
+    SpaceAvailable,
+    SpaceEnough,
+    SpaceShort,
+    UpdatingFirmware,
+    FirmwareDevice,
+    FirmwareCurrent,
+    FirmwareNew,
+    FirmwareWarning,
+    FirmwareNeedsReboot,
+    RebootIn,
+    Rebooting,
+
+    // Зеркала, контейнеры, ключи, SSH и базы данных
+    MirrorNotSelected,
+    SpeedTest,
+    SpeedTestServer,
+    Mirrors,
+    SelectingMirror,
+    MirrorsShort,
+    MirrorSelected,
+    Layers,
+    PulledLayers,
+    CreatingVm,
+    StartingContainer,
+    ContainerStarted,
+    GeneratingSshKey,
+    GeneratingCert,
+    CollectingEntropy,
+    CertInstalled,
+    HardeningSsh,
+    Unchanged,
+    WasValue,
+    SshdReloaded,
+    InitializingDb,
+    DbPassword,
+    ShownOnce,
+    DbReady,
+    ApplyingMigrations,
+    NoMigrations,
+    MigrationsApplied,
+    UpdatingSubmodules,
+    MoreLines,
+    Lines,
+
+    // Обратные команды (--uninstall)
+    RemovingDir,
+    RemovingFile,
+    RemovingLink,
+    RemovingDownload,
+    RemovingExtracted,
+    RemovingDep,
+    RemovingPackages,
+    RemovingDriver,
+    RemovingBootloader,
+    RemovingUser,
+    RemovingSnapshot,
+    RemovingRepo,
+    RemovingImage,
+    RemovingClone,
+    StoppingContainer,
+    RemovingVm,
+    RevertingStep,
+
+    // Сбои --chaos
+    ChaosDownload,
+    ChaosInstallDep,
+    ChaosConfigure,
+    ChaosScript,
+    ChaosTest,
+    ChaosSnapshot,
+    ChaosRollback,
+    ChaosChecksum,
+    ChaosRepo,
+    ChaosMirrors,
+    ChaosBenchmark,
+    ChaosCmdline,
+    ChaosWait,
+}
+
+impl Msg {
+    pub(crate) fn text(self, lang: Lang) -> &'static str {
+        let (ru, en) = match self {
+            // Шапка; подписи в ней и в русском выводе английские, как у классических установщиков
+            Msg::Installer => ("Installer v1.0", "Installer v1.0"),
+            Msg::Uninstaller => ("Uninstaller v1.0", "Uninstaller v1.0"),
+            Msg::MultiInstaller => ("Multi-Package Installer", "Multi-Package Installer"),
+            Msg::MultiUninstaller => ("Multi-Package Uninstaller", "Multi-Package Uninstaller"),
+            Msg::LabelPackage => ("Package", "Package"),
+            Msg::LabelVersion => ("Version", "Version"),
+            Msg::LabelDescription => ("Description", "Description"),
+            Msg::LabelAuthor => ("Author", "Author"),
+            Msg::LabelLicense => ("License", "License"),
+            Msg::LabelHomepage => ("Homepage", "Homepage"),
+            Msg::LabelSize => ("Size", "Size"),
+            Msg::LabelArch => ("Arch", "Arch"),
+            Msg::LabelDistro => ("Distro", "Distro"),
+            Msg::LabelEta => ("ETA", "ETA"),
+            Msg::LabelDepends => ("Depends", "Depends"),
+
+            // План нескольких пакетов
+            Msg::PlanInstall => ("План установки", "Installation plan"),
+            Msg::PlanUninstall => ("План удаления", "Removal plan"),
+            Msg::ExpectedTime => ("Ожидаемое время", "Estimated time"),
+            Msg::DepsDepth => ("Глубина зависимостей", "Dependency depth"),
+            Msg::DepsDepthNamed => ("0 (только указанные пакеты)", "0 (named packages only)"),
+            Msg::DepsDepthDirect => ("1 (только прямые зависимости)", "1 (direct dependencies only)"),
+            Msg::DepsDepthUnlimited => ("без ограничения", "unlimited"),
+            Msg::AlreadyInstalled => ("{} уже установлен (v{})", "{} is already installed (v{})"),
+            Msg::NothingToInstall => ("Все пакеты уже установлены (--reinstall — установить заново)", "All packages are already installed (--reinstall to install again)"),
+            Msg::LevelsTitle => ("Установка по уровням зависимостей:", "Installation by dependency level:"),
+            Msg::LevelParallel => ("Можно устанавливать параллельно: {}", "Can be installed in parallel: {}"),
+            Msg::LevelsTotal => ("{}, {}; --parallel N ставит пакеты одного уровня одновременно", "{}, {}; --parallel N installs packages of one level at once"),
+
+            // --dry-run, --step и продолжение прерванной установки
+            Msg::DryRunPlan => ("План установки (--dry-run: ничего не выполняется)", "Installation plan (--dry-run: nothing is executed)"),
+            Msg::PlannedVia => ("(уже в плане через {})", "(already planned via {})"),
+            Msg::PlanTotal => ("Всего: {}, {}", "Total: {}, {}"),
+            Msg::Defaulted => ("  (по умолчанию: {})", "  (defaults: {})"),
+            Msg::AtLine => ("строка {}: ", "line {}: "),
+            Msg::StepPrompt => ("[Enter] выполнить, s — пропустить, p — пропустить фазу, q — прервать:", "[Enter] run, s — skip, p — skip phase, q — abort:"),
+            Msg::ResumeFound => ("Обнаружена незавершённая установка {}: выполнено фаз {} из {}", "Unfinished installation of {} found: {} of {} phases completed"),
+            Msg::ResumeAtCommand => (", прервана на команде {} фазы «{}»", ", interrupted at command {} of phase \"{}\""),
+            Msg::ResumeAsk => ("Продолжить с фазы {} «{}»?", "Continue from phase {} \"{}\"?"),
+            Msg::AnswerYesNo => ("Ответьте y или n:", "Please answer y or n:"),
+
+            // Фазы и общая полоса
+            Msg::PhaseOptionalSkipped => ("(пропущена: необязательная фаза)", "(skipped: optional phase)"),
+            Msg::PhaseDoneEarlier => ("(выполнена ранее)", "(completed earlier)"),
+            Msg::PackageDoneEarlier => ("(установлен ранее)", "(installed earlier)"),
+            Msg::Rollback => ("Откат: {}", "Rollback: {}"),
+            Msg::NoInverse => ("(нет обратной операции)", "(no inverse operation)"),
+            Msg::StatusPhase => ("Фаза {}/{}", "Phase {}/{}"),
+            Msg::StatusLeft => (" · Осталось {}", " · {} left"),
+            Msg::EtaSeconds => ("~{}с", "~{}s"),
+            Msg::EtaMinutes => ("~{}м {}с", "~{}m {}s"),
+            Msg::EtaHours => ("~{}ч {}м", "~{}h {}m"),
+
+            // Итоги
+            Msg::ScriptTitle => ("Установка {}", "Installing {}"),
+            Msg::InstalledOk => ("{} установлен успешно!", "{} installed successfully!"),
+            Msg::UninstalledOk => ("{} удалён успешно!", "{} removed successfully!"),
+            Msg::PendingAction => ("Изменения не вступят в силу без {}", "Changes will not take effect without {}"),
+            Msg::InstallFailed => ("Установка {} прервана", "Installation of {} aborted"),
+            Msg::UninstallFailed => ("Удаление {} прервана", "Removal of {} aborted"),
+            Msg::FailedPhase => ("Фаза «{}»", "Phase \"{}\""),
+            Msg::FailedPhaseCommand => ("Фаза «{}», {}", "Phase \"{}\", {}"),
+            Msg::Cancelled => ("Установка прервана пользователем", "Installation cancelled by user"),
+            Msg::CancelledPhases => ("{} (выполнено фаз: {} из {})", "{} (phases completed: {} of {})"),
+            Msg::PhaseSkipped => ("{} (пропущена)", "{} (skipped)"),
+            Msg::PhaseInterrupted => ("{} (прервана)", "{} (interrupted)"),
+            Msg::NotInstalled => ("Не установлены: {}", "Not installed: {}"),
+            Msg::NotRemoved => ("Не удалены: {}", "Not removed: {}"),
+            Msg::InstalledList => ("Установлены", "Installed"),
+            Msg::RemovedList => ("Удалены", "Removed"),
+            Msg::SkippedAfterFailure => ("Пропущены из-за сбоя", "Skipped due to failure"),
+            Msg::NotRemovedAfterFailure => ("Не удалены из-за сбоя", "Not removed due to failure"),
+            Msg::InstallError => ("Ошибка установки {}: {}", "Failed to install {}: {}"),
+            Msg::UninstallError => ("Ошибка удаления {}: {}", "Failed to remove {}: {}"),
+            Msg::InstalledCount => ("Установлено {}:", "Installed {}:"),
+            Msg::RemovedCount => ("Удалено {}:", "Removed {}:"),
+
+            // Счётчики; формы числа через «|»: одна, несколько (2–4), много
+            Msg::StatsDone => ("Выполнено {}", "Completed {}"),
+            Msg::StatsDownloaded => ("загружено {}", "downloaded {}"),
+            Msg::StatsInstalled => ("установлено {}", "installed {}"),
+            Msg::StatsEnabled => ("включено {}", "enabled {}"),
+            Msg::Operations => ("операция|операции|операций", "operation|operations"),
+            Msg::Steps => ("шаг|шага|шагов", "step|steps"),
+            Msg::Warnings => ("предупреждение|предупреждения|предупреждений", "warning|warnings"),
+            Msg::Errors => ("ошибка|ошибки|ошибок", "error|errors"),
+            Msg::Packages => ("пакет|пакета|пакетов", "package|packages"),
+            Msg::Commands => ("команда|команды|команд", "command|commands"),
+            Msg::Levels => ("уровень|уровня|уровней", "level|levels"),
+            Msg::Services => ("сервис|сервиса|сервисов", "service|services"),
+
+            // Сводка времени
+            Msg::TimingTitle => ("Время установки", "Installation time"),
+            Msg::TimingTotal => ("Всего", "Total"),
+            Msg::Micros => ("{} мкс", "{} µs"),
+            Msg::Millis => ("{} мс", "{} ms"),
+            Msg::Seconds => ("{} с", "{} s"),
+            Msg::Minutes => ("{} мин {} с", "{} min {} s"),
+
+            // Карточка итогов (--no-fun)
+            Msg::FunTitle => ("Итоги", "Results"),
+            Msg::FunSaved => ("Вы сэкономили {} места на диске (ничего не установлено)", "You saved {} of disk space (nothing was installed)"),
+            Msg::FunFake => ("Ненастоящих пакетов: {} · «загружено» {}", "Fake packages: {} · \"downloaded\" {}"),
+            Msg::FunAchievement => ("Достижение получено: {}", "Achievement unlocked: {}"),
+
+            // Команды
+            Msg::Progress => ("Прогресс: {}", "Progress: {}"),
+            Msg::CreatingDir => ("Создание директории: {}", "Creating directory: {}"),
+            Msg::Configuring => ("Конфигурация: {}={}", "Configuring: {}={}"),
+            Msg::CleaningUp => ("Очистка временных файлов...", "Cleaning up temporary files..."),
+            Msg::Copying => ("Копирование: {} → {}", "Copying: {} → {}"),
+            Msg::Linking => ("Создание ссылки: {} → {}", "Creating link: {} → {}"),
+            Msg::SettingMode => ("Установка прав {} для {}", "Setting mode {} on {}"),
+            Msg::RunningScript => ("Выполнение скрипта: {}", "Running script: {}"),
+            Msg::CheckingDep => ("Проверка зависимости: {} ... ", "Checking dependency: {} ... "),
+            Msg::WritingConfig => ("Запись конфигурации: {}", "Writing config: {}"),
+            Msg::SettingHostname => ("Установка имени хоста: {}", "Setting hostname: {}"),
+            Msg::SettingTimezone => ("Установка часового пояса: {}", "Setting timezone: {}"),
+            Msg::SettingLocale => ("Установка локали: {}", "Setting locale: {}"),
+            Msg::LocaleNotGenerated => ("Локаль {} не сгенерирована, добавьте её в generate_locales", "Locale {} is not generated, add it to generate_locales"),
+            Msg::SettingPassword => ("Установка пароля для {} ... ", "Setting password for {} ... "),
+            Msg::Downloading => ("Загрузка: {}", "Downloading: {}"),
+            Msg::DownloadedBytes => ("Загружено: {} байт", "Downloaded: {} bytes"),
+            Msg::Extracting => ("Распаковка: {} → {}", "Extracting: {} → {}"),
+            Msg::FilesExtracted => ("{} файлов распаковано", "{} files extracted"),
+            Msg::InstallingDep => ("Установка зависимости: {} (v{})", "Installing dependency: {} (v{})"),
+            Msg::Parallel => ("Параллельное выполнение (задач: {})", "Running in parallel ({} tasks)"),
+            Msg::ParallelInstalling => ("установка", "installing"),
+            Msg::ParallelDownloaded => ("Загружено: {} ({} байт)", "Downloaded: {} ({} bytes)"),
+            Msg::ParallelDelay => ("Пауза {} мс", "Pause {} ms"),
+            Msg::ParallelTest => ("Тест: {} {}", "Test: {} {}"),
+            Msg::ParallelDep => ("Зависимость: {} (v{})", "Dependency: {} (v{})"),
+
+            // Повторы, сбои и определение оборудования
+            Msg::RetrySucceeded => ("Успешно с попытки {}/{}", "Succeeded on attempt {}/{}"),
+            Msg::RetryIn => ("Повтор {}/{} через 2с... ({})", "Retry {}/{} in 2s... ({})"),
+            Msg::ChaosRetry => ("Повтор через 1с...", "Retrying in 1s..."),
+            Msg::ParallelCrashed => ("parallel: задача завершилась аварийно", "parallel: task crashed"),
+            Msg::StateWriteFailed => ("Не удалось записать состояние {}: {}", "Failed to write state {}: {}"),
+            Msg::FirewallRule => ("Добавление правила firewall: {}", "Adding firewall rule: {}"),
+            Msg::DetectingCpu => ("Определение процессора ... ", "Detecting CPU ... "),
+            Msg::DetectingMemory => ("Определение памяти ... ", "Detecting memory ... "),
+            Msg::DetectingDisk => ("Определение накопителей ... ", "Detecting storage ... "),
+            Msg::DetectingGpu => ("Определение видеокарты ... ", "Detecting GPU ... "),
+            Msg::DetectingNetwork => ("Определение сетевых адаптеров ... ", "Detecting network adapters ... "),
+            Msg::DetectingOs => ("Определение операционной системы ... ", "Detecting operating system ... "),
+            Msg::DetectingKernel => ("Определение версии ядра ... ", "Detecting kernel version ... "),
+            Msg::DetectingBios => ("Определение BIOS/UEFI ... ", "Detecting BIOS/UEFI ... "),
+            Msg::DetailVendor => ("Производитель:", "Vendor:"),
+            Msg::DetailModel => ("Модель:", "Model:"),
+            Msg::DetailCores => ("Ядра:", "Cores:"),
+            Msg::DetailFrequency => ("Частота:", "Frequency:"),
+            Msg::DetailCapacity => ("Объём:", "Capacity:"),
+            Msg::DetailType => ("Тип:", "Type:"),
+            Msg::DetailSpeed => ("Скорость:", "Speed:"),
+            Msg::DetailMemory => ("Память:", "Memory:"),
+            Msg::DetailAdapter => ("Адаптер:", "Adapter:"),
+            Msg::DetailSystem => ("Система:", "System:"),
+            Msg::DetailVersion => ("Версия:", "Version:"),
+            Msg::Cores => ("ядро|ядра|ядер", "core|cores"),
+
+            // Тесты и ядро; этапы и пункты списков через «|»
+            Msg::RunningTest => ("Тест: {} ", "Test: {} "),
+            Msg::TestingComponent => ("Тестирование {}", "Testing {}"),
+            Msg::TestsMemory => ("Проверка ячеек памяти|Тест чтения/записи|Стресс-тест", "Memory cell check|Read/write test|Stress test"),
+            Msg::TestsCpu => ("Арифметические операции|SIMD инструкции|Температурный мониторинг", "Arithmetic operations|SIMD instructions|Temperature monitoring"),
+            Msg::TestsDisk => ("Проверка секторов|Тест SMART|Скорость чтения/записи", "Sector check|SMART test|Read/write speed"),
+            Msg::TestsGpu => ("Рендеринг|Вычисления CUDA/OpenCL|Температура", "Rendering|CUDA/OpenCL compute|Temperature"),
+            Msg::TestsOther => ("Базовый тест|Функциональный тест", "Basic test|Functional test"),
+            Msg::LoadingModule => ("Загрузка модуля ядра: {} ... ", "Loading kernel module: {} ... "),
+            Msg::UnloadingModule => ("Выгрузка модуля ядра: {} ... ", "Unloading kernel module: {} ... "),
+            Msg::UpdatingInitramfs => ("Обновление initramfs...", "Updating initramfs..."),
+            Msg::InitramfsSteps => ("Сборка модулей...|Генерация образа...|Сжатие (gzip)...|Запись /boot/initramfs.img...", "Building modules...|Generating image...|Compressing (gzip)...|Writing /boot/initramfs.img..."),
+            Msg::InitramfsUpdated => ("initramfs обновлён", "initramfs updated"),
+            Msg::UpdatingGrub => ("Обновление GRUB...", "Updating GRUB..."),
+            Msg::GeneratingGrubCfg => ("Генерация grub.cfg...", "Generating grub.cfg..."),
+            Msg::GrubEntries => ("Обнаруженные записи:", "Found entries:"),
+            Msg::GrubUpdated => ("GRUB обновлён", "GRUB updated"),
+            Msg::CompilingKernel => ("Компиляция ядра {}", "Compiling kernel {}"),
+            Msg::KernelStages => ("Конфигурация|Компиляция ядра|Компиляция модулей|Установка модулей|Установка ядра", "Configuration|Compiling kernel|Compiling modules|Installing modules|Installing kernel"),
+            Msg::KernelCompiled => ("Ядро {} скомпилировано", "Kernel {} compiled"),
+
+            // Сборка, разделы и файловые системы
+            Msg::Building => ("Сборка {} ({}, {})", "Building {} ({}, {})"),
+            Msg::Files => ("файл|файла|файлов", "file|files"),
+            Msg::Threads => ("поток|потока|потоков", "thread|threads"),
+            Msg::Built => ("{} собран: bin/{} ({} MB, {} файлов/с, {} с)", "{} built: bin/{} ({} MB, {} files/s, {} s)"),
+            Msg::Mounting => ("Монтирование {} → {} ... ", "Mounting {} → {} ... "),
+            Msg::Unmounting => ("Размонтирование {} ... ", "Unmounting {} ... "),
+            Msg::Formatting => ("Форматирование {} в {}", "Formatting {} as {}"),
+            Msg::CreatingFs => ("Создание файловой системы ", "Creating filesystem "),
+            Msg::CreatingPartition => ("Создание раздела на {} ({})", "Creating partition on {} ({})"),
+            Msg::PartitionCreated => ("Раздел создан", "Partition created"),
+            Msg::GeneratingFstab => ("Генерация /etc/fstab", "Generating /etc/fstab"),
+            Msg::FstabGenerated => ("fstab сгенерирован", "fstab generated"),
+            Msg::Partitions => ("Разделы {} ({}, {} GB)", "Partitions of {} ({}, {} GB)"),
+            Msg::PartitionsFree => ("Разделов: {}, свободно {}", "Partitions: {}, {} free"),
+
+            // Датчики и шифрование
+            Msg::Sensors => ("Датчики", "Sensors"),
+            Msg::MoreCores => ("... ещё {}", "... plus {}"),
+            Msg::Fans => ("Вентиляторы", "Fans"),
+            Msg::Encrypting => ("Шифрование {} (LUKS2, {})", "Encrypting {} (LUKS2, {})"),
+            Msg::EncryptionCancelled => ("шифрование {} отменено", "encryption of {} cancelled"),
+            Msg::OpenedAs => ("Открыто как {}", "Opened as {}"),
+
+            // Пулы, параметры ядра, swap, RAID, пользователи и сервисы
+            Msg::CreatingZfsPool => ("Создание пула ZFS {}", "Creating ZFS pool {}"),
+            Msg::CreatingBtrfs => ("Создание файловой системы Btrfs {}", "Creating Btrfs filesystem {}"),
+            Msg::CreatingDataset => ("Создание набора данных {}", "Creating dataset {}"),
+            Msg::PoolNotCreated => ("пул {} не был создан в этом запуске", "pool {} was not created in this run"),
+            Msg::KernelParam => ("Параметр ядра: {} = {}", "Kernel parameter: {} = {}"),
+            Msg::SysctlSaved => ("Сохранено в /etc/sysctl.d/99-instnoth.conf", "Saved to /etc/sysctl.d/99-instnoth.conf"),
+            Msg::KernelCmdline => ("Параметры загрузки ядра", "Kernel boot parameters"),
+            Msg::NeedsUpdateGrub => ("Для применения нужен update_grub", "Run update_grub to apply"),
+            Msg::SettingUpZram => ("Настройка zram-swap ({})", "Setting up zram swap ({})"),
+            Msg::ZramActivated => ("/dev/zram0 активирован", "/dev/zram0 activated"),
+            Msg::CreatingSwapFile => ("Создание swap-файла {} ({})", "Creating swap file {} ({})"),
+            Msg::SwapTotal => ("Swap всего: {}", "Total swap: {}"),
+            Msg::Devices => ("устройства|устройств|устройств", "device|devices"),
+            Msg::CreatingRaid => ("Создание RAID{} {} из {}", "Creating RAID{} {} from {}"),
+            Msg::ArrayCapacity => ("Ёмкость массива: {} GB", "Array capacity: {} GB"),
+            Msg::ArraySynced => ("{} синхронизирован", "{} synchronized"),
+            Msg::CreatingUser => ("Создание пользователя: {}", "Creating user: {}"),
+            Msg::UserGroups => ("Группы: {}", "Groups: {}"),
+            Msg::UserCreated => ("Пользователь создан", "User created"),
+            Msg::EnablingService => ("Включение сервиса: {} ... ", "Enabling service: {} ... "),
+            Msg::DisablingService => ("Отключение сервиса: {} ... ", "Disabling service: {} ... "),
+            Msg::StartingService => ("Запуск сервиса: {} ... ", "Starting service: {} ... "),
+            Msg::StoppingService => ("Остановка сервиса: {} ... ", "Stopping service: {} ... "),
+            Msg::ManagingService => ("Управление сервисом: {} ... ", "Managing service: {} ... "),
+            Msg::InstallingBootloader => ("Установка загрузчика на {}", "Installing bootloader to {}"),
+            Msg::BootloaderSteps => ("Проверка EFI/BIOS режима...|Установка загрузочных файлов...|Создание записи в NVRAM...|Генерация конфигурации...", "Checking EFI/BIOS mode...|Installing boot files...|Creating NVRAM entry...|Generating configuration..."),
+            Msg::GrubInstalled => ("GRUB установлен на {}", "GRUB installed to {}"),
+            Msg::CheckingIntegrity => ("Проверка целостности: {}", "Checking integrity: {}"),
+            Msg::ComputingChecksums => ("Вычисление контрольных сумм ", "Computing checksums "),
+            Msg::IntegrityConfirmed => ("Целостность подтверждена", "Integrity confirmed"),
+
+            // Подписи, репозитории, пакеты, сеть и оборудование
+            Msg::CheckingSignature => ("Проверка подписи: {} ... ", "Verifying signature: {} ... "),
+            Msg::CheckingChecksum => ("Проверка {}: {}", "Verifying {}: {}"),
+            Msg::ChecksumExpected => ("ожидается: {}", "expected: {}"),
+            Msg::ChecksumActual => ("получено:  {}", "actual:   {}"),
+            Msg::FailureIgnored => ("(сбой проигнорирован, код {})", "(failure ignored, code {})"),
+            Msg::AddingRepo => ("Добавление репозитория {}", "Adding repository {}"),
+            Msg::RepoAlreadyAdded => ("репозиторий {} уже был добавлен", "repository {} was already added"),
+            Msg::RepoAdded => ("Репозиторий {} добавлен ({})", "Repository {} added ({})"),
+            Msg::ImportingKey => ("Импорт ключа {}", "Importing key {}"),
+            Msg::KeyImported => ("Ключ {} импортирован", "Key {} imported"),
+            Msg::InstallingPackages => ("Установка пакетов ({} шт.)", "Installing packages ({})"),
+            Msg::ExtraRepos => ("Дополнительные репозитории: {}", "Extra repositories: {}"),
+            Msg::UpdatingSystem => ("Обновление системы", "Updating system"),
+            Msg::UpdateStages => ("Синхронизация репозиториев...|Проверка обновлений...|Загрузка пакетов...|Установка обновлений...|Очистка кэша...", "Syncing repositories...|Checking for updates...|Downloading packages...|Installing updates...|Cleaning cache..."),
+            Msg::SystemUpdated => ("Обновлено {}", "Updated {}"),
+            Msg::SyncingTime => ("Синхронизация времени (NTP) ... ", "Synchronizing time (NTP) ... "),
+            Msg::NtpServer => ("Сервер: pool.ntp.org", "Server: pool.ntp.org"),
+            Msg::NtpOffset => ("Смещение: +0.003s", "Offset: +0.003s"),
+            Msg::ConfiguringNetwork => ("Настройка сети: {} ({})", "Configuring network: {} ({})"),
+            Msg::DhcpLease => ("Получение IP через DHCP ", "Obtaining IP via DHCP "),
+            Msg::StaticConfig => ("Применение статической конфигурации", "Applying static configuration"),
+            Msg::CheckingConnectivity => ("Проверка подключения...", "Checking connectivity..."),
+            Msg::NetworkConfigured => ("Сеть настроена", "Network configured"),
+            Msg::ScanningHardware => ("Сканирование оборудования", "Scanning hardware"),
+            Msg::ScanBus => ("Шина {} ... ", "Bus {} ... "),
+            Msg::ScanFound => ("Видеоадаптер, Сетевой контроллер, USB контроллер|Клавиатура, Мышь, USB Hub|Управление питанием, Термальные зоны|SSD, HDD|NVMe SSD", "Display adapter, Network controller, USB controller|Keyboard, Mouse, USB Hub|Power management, Thermal zones|SSD, HDD|NVMe SSD"),
+            Msg::ScanComplete => ("Сканирование завершено", "Scan complete"),
+            Msg::DetectingDrivers => ("Определение необходимых драйверов", "Detecting required drivers"),
+            Msg::InstallingDriver => ("Установка драйвера: {}", "Installing driver: {}"),
+
+            // Вопросы, ожидание, развёртывание, локали и снимки
+            Msg::SudoNoPassword => ("sudo: пароль не введён", "sudo: no password entered"),
+            Msg::RequiredRefused => ("отказ от обязательного вопроса \"{}\"", "required question \"{}\" was declined"),
+            Msg::LicenseMissing => ("Файл лицензии {} не найден, пропускаем", "License file {} not found, skipping"),
+            Msg::LicenseAgreement => ("Лицензионное соглашение: {}", "License agreement: {}"),
+            Msg::DefaultChoice => ("(по умолчанию)", "(default)"),
+            Msg::Choice => ("Выбор", "Choice"),
+            Msg::EnterNumber => ("Введите число от 1 до {}: ", "Enter a number from 1 to {}: "),
+            Msg::Waiting => ("Ожидание {}", "Waiting for {}"),
+            Msg::WaitTimedOut => ("Истекло время ожидания {} ({}s)", "Timed out waiting for {} ({}s)"),
+            Msg::WaitReady => ("готов ({}s)", "ready ({}s)"),
+            Msg::DeployingInfra => ("Развёртывание инфраструктуры hashicorp/{} (ресурсов: {})", "Deploying infrastructure hashicorp/{} (resources: {})"),
+            Msg::RunningPlaybook => ("Выполнение плейбука {} (хостов: {})", "Running playbook {} (hosts: {})"),
+            Msg::GeneratingLocales => ("Генерация локалей", "Generating locales"),
+            Msg::Keymap => ("Раскладка клавиатуры: {}", "Keyboard layout: {}"),
+            Msg::KeymapApplied => ("Раскладка {} применена к консоли", "Layout {} applied to the console"),
+            Msg::CreatingSnapshot => ("Создание снимка {}", "Creating snapshot {}"),
+            Msg::SnapshotSaved => ("Снимок {} сохранён, ID {}", "Snapshot {} saved, ID {}"),
+            Msg::SnapshotMissing => ("rollback_to: снимок \"{}\" не создавался", "rollback_to: snapshot \"{}\" was never created"),
+            Msg::RollingBack => ("Откат к снимку {} ({})", "Rolling back to snapshot {} ({})"),
+            Msg::RollingBackPhase => ("Откат фазы {} ... ", "Rolling back phase {} ... "),
+            Msg::PhaseTook => ("(фаза шла {}s)", "(phase took {}s)"),
+            Msg::SnapshotRestored => ("Система возвращена к снимку {}", "System restored to snapshot {}"),
+
+            // Место на диске, прошивка и перезагрузка
+            Msg::CheckingSpace => ("Проверка свободного места на {}", "Checking free space on {}"),
+            Msg::SpaceRequired => ("Требуется: {}", "Required:  {}"),
+            Msg::SpaceAvailable => ("Доступно:  {}", "Available: {}"),
+            Msg::SpaceEnough => ("Места достаточно", "Enough space"),
+            Msg::SpaceShort => ("Недостаточно места на {}: не хватает {}", "Not enough space on {}: {} short"),
+            Msg::UpdatingFirmware => ("Обновление прошивки {}", "Updating firmware {}"),
+            Msg::FirmwareDevice => ("Устройство:     {}", "Device:          {}"),
+            Msg::FirmwareCurrent => ("Текущая версия: {}", "Current version: {}"),
+            Msg::FirmwareNew => ("Новая версия:   {}", "New version:     {}"),
+            Msg::FirmwareWarning => ("Не выключайте компьютер и не отключайте питание.|Прерывание обновления может повредить устройство.", "Do not turn off the computer or unplug the power.|Interrupting the update may damage the device."),
+            Msg::FirmwareNeedsReboot => ("Для завершения обновления {} требуется перезагрузка", "A reboot is required to finish updating {}"),
+            Msg::RebootIn => ("Система будет перезагружена через {} сек.", "The system will reboot in {} s."),
+            Msg::Rebooting => ("Перезагрузка...", "Rebooting..."),
+
+            // Зеркала, контейнеры, ключи, SSH и базы данных
+            Msg::MirrorNotSelected => ("mirror:// без select_mirror — зеркало не выбрано", "mirror:// without select_mirror — no mirror selected"),
+            Msg::SpeedTest => ("Тест скорости сети", "Network speed test"),
+            Msg::SpeedTestServer => ("Сервер: {} ({}) — {} km", "Server: {} ({}) — {} km"),
+            Msg::Mirrors => ("зеркало|зеркала|зеркал", "mirror|mirrors"),
+            Msg::SelectingMirror => ("Выбор зеркала ({}, {})", "Selecting a mirror ({}, {})"),
+            Msg::MirrorsShort => ("для {} известно только {} из запрошенных {}", "{} has only {} of the {} requested"),
+            Msg::MirrorSelected => ("Выбрано зеркало {} ({} ms)", "Selected mirror {} ({} ms)"),
+            Msg::Layers => ("слоя|слоёв|слоёв", "layer|layers"),
+            Msg::PulledLayers => ("Загружено: {} ({} из {})", "Downloaded: {} ({} of {})"),
+            Msg::CreatingVm => ("Создание виртуальной машины {} ({} vCPU, {} RAM, диск {})", "Creating virtual machine {} ({} vCPU, {} RAM, disk {})"),
+            Msg::StartingContainer => ("Запуск контейнера {}", "Starting container {}"),
+            Msg::ContainerStarted => ("Контейнер {} запущен, IP {}", "Container {} started, IP {}"),
+            Msg::GeneratingSshKey => ("Генерация SSH-ключа {} ({} бит)", "Generating SSH key {} ({} bits)"),
+            Msg::GeneratingCert => ("Генерация сертификата для {} ({}, {} дн.)", "Generating certificate for {} ({}, {} days)"),
+            Msg::CollectingEntropy => ("Сбор энтропии... {} бит", "Collecting entropy... {} bits"),
+            Msg::CertInstalled => ("Сертификат установлен, действует до {}", "Certificate installed, valid until {}"),
+            Msg::HardeningSsh => ("Усиление настроек SSH", "Hardening SSH settings"),
+            Msg::Unchanged => ("(без изменений)", "(unchanged)"),
+            Msg::WasValue => ("(было: {})", "(was: {})"),
+            Msg::SshdReloaded => ("Конфигурация sshd проверена и перезагружена", "sshd configuration checked and reloaded"),
+            Msg::InitializingDb => ("Инициализация базы данных {}", "Initializing database {}"),
+            Msg::DbPassword => ("Пароль {}: {}", "Password for {}: {}"),
+            Msg::ShownOnce => ("(показывается один раз)", "(shown only once)"),
+            Msg::DbReady => ("База данных {} готова", "Database {} is ready"),
+            Msg::ApplyingMigrations => ("Применение миграций ({})", "Applying migrations ({})"),
+            Msg::NoMigrations => ("Нет новых миграций", "No new migrations"),
+            Msg::MigrationsApplied => ("Применено миграций: {} за {}s", "Migrations applied: {} in {}s"),
+            Msg::UpdatingSubmodules => ("Обновление подмодулей git", "Updating git submodules"),
+            Msg::MoreLines => ("... ещё {} ...", "... {} more ..."),
+            Msg::Lines => ("строка|строки|строк", "line|lines"),
+
+            // Обратные команды (--uninstall)
+            Msg::RemovingDir => ("Удаление директории", "Removing directory"),
+            Msg::RemovingFile => ("Удаление файла", "Removing file"),
+            Msg::RemovingLink => ("Удаление ссылки", "Removing link"),
+            Msg::RemovingDownload => ("Удаление загруженного файла", "Removing downloaded file"),
+            Msg::RemovingExtracted => ("Удаление распакованных файлов", "Removing extracted files"),
+            Msg::RemovingDep => ("Удаление зависимости", "Removing dependency"),
+            Msg::RemovingPackages => ("Удаление пакетов", "Removing packages"),
+            Msg::RemovingDriver => ("Удаление драйвера", "Removing driver"),
+            Msg::RemovingBootloader => ("Удаление загрузчика", "Removing bootloader"),
+            Msg::RemovingUser => ("Удаление пользователя", "Removing user"),
+            Msg::RemovingSnapshot => ("Удаление снимка", "Removing snapshot"),
+            Msg::RemovingRepo => ("Удаление репозитория", "Removing repository"),
+            Msg::RemovingImage => ("Удаление образа", "Removing image"),
+            Msg::RemovingClone => ("Удаление клона", "Removing clone"),
+            Msg::StoppingContainer => ("Остановка контейнера", "Stopping container"),
+            Msg::RemovingVm => ("Удаление виртуальной машины", "Removing virtual machine"),
+            Msg::RevertingStep => ("Откат шага", "Reverting step"),
+
+            // Сбои --chaos
+            Msg::ChaosDownload => ("Тайм-аут загрузки {}: connection timed out after 30000 ms", "Download of {} timed out: connection timed out after 30000 ms"),
+            Msg::ChaosInstallDep => ("Не удалось установить {} {}: dependency resolution failed", "Failed to install {} {}: dependency resolution failed"),
+            Msg::ChaosConfigure => ("Не удалось применить {}: файл конфигурации заблокирован другим процессом", "Failed to apply {}: the configuration file is locked by another process"),
+            Msg::ChaosScript => ("{}: завершился с кодом 127", "{}: exited with code 127"),
+            Msg::ChaosTest => ("Тест {}: FAILED (превышено время ожидания)", "Test {}: FAILED (timed out)"),
+            Msg::ChaosSnapshot => ("timeshift: не удалось создать снимок {}: No space left on device", "timeshift: failed to create snapshot {}: No space left on device"),
+            Msg::ChaosRollback => ("timeshift: снимок {} повреждён", "timeshift: snapshot {} is corrupted"),
+            Msg::ChaosChecksum => ("{}: FAILED (контрольная сумма не совпадает)", "{}: FAILED (checksum mismatch)"),
+            Msg::ChaosRepo => ("Не удалось получить метаданные репозитория {}: 404 Not Found", "Failed to fetch metadata for repository {}: 404 Not Found"),
+            Msg::ChaosMirrors => ("Все зеркала недоступны: connection refused", "All mirrors are unreachable: connection refused"),
+            Msg::ChaosBenchmark => ("Бенчмарк прерван: обнаружен перегрев (thermal throttling)", "Benchmark aborted: overheating detected (thermal throttling)"),
+            Msg::ChaosCmdline => ("Не удалось записать /etc/kernel/cmdline: Read-only file system", "Failed to write /etc/kernel/cmdline: Read-only file system"),
+            Msg::ChaosWait => ("Истекло время ожидания {}", "Timed out waiting for {}"),
+        };
+        match lang {
+            Lang::Ru => ru,
+            Lang::En => en,
+        }
+    }
+
+    /// Текст со значениями вместо {} по порядку
+    pub(crate) fn fill(self, lang: Lang, args: &[&dyn fmt::Display]) -> String {
+        let mut parts = self.text(lang).split("{}");
+        let mut result = parts.next().unwrap_or_default().to_string();
+        for (i, part) in parts.enumerate() {
+            if let Some(arg) = args.get(i) {
+                result.push_str(&arg.to_string());
+            }
+            result.push_str(part);
+        }
+        result
+    }
+
+    /// Число со словом в нужной форме: «3 шага», «3 steps»
+    pub(crate) fn count(self, lang: Lang, n: u32) -> String {
+        let forms: Vec<&str> = self.text(lang).split('|').collect();
+        match forms.as_slice() {
+            [one, few, many] => plural(n, one, few, many),
+            [one, other] => format!("{} {}", n, if n == 1 { one } else { other }),
+            _ => format!("{} {}", n, self.text(lang)),
+        }
+    }
+}
+
+/// Значение строки «Глубина зависимостей» для --deps-depth: 0 и 1 — с пояснением
+pub(crate) fn deps_depth_value(lang: Lang, depth: Option<usize>) -> String {
+    match depth {
+        None => Msg::DepsDepthUnlimited.text(lang).to_string(),
+        Some(0) => Msg::DepsDepthNamed.text(lang).to_string(),
+        Some(1) => Msg::DepsDepthDirect.text(lang).to_string(),
+        Some(n) => n.to_string(),
+    }
+}
+
+/// Значение поля события
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonValue {
+    Str(String),
+    Int(i64),
+    Bool(bool),
+    List(Vec<JsonValue>),
+    Object(Vec<(String, JsonValue)>),
+}
+
+impl From<&str> for JsonValue {
+    fn from(s: &str) -> Self {
+        JsonValue::Str(s.to_string())
+    }
+}
+
+impl From<String> for JsonValue {
+    fn from(s: String) -> Self {
+        JsonValue::Str(s)
+    }
+}
+
+impl From<bool> for JsonValue {
+    fn from(b: bool) -> Self {
+        JsonValue::Bool(b)
+    }
+}
+
+impl From<&[String]> for JsonValue {
+    fn from(items: &[String]) -> Self {
+        JsonValue::List(items.iter().map(|s| JsonValue::from(s.as_str())).collect())
+    }
+}
+
+macro_rules! json_int {
+    ($($t:ty),*) => {
+        $(impl From<$t> for JsonValue {
+            fn from(n: $t) -> Self {
+                JsonValue::Int(n as i64)
+            }
+        })*
+    };
+}
+
+json_int!(u8, u32, u64, usize, i32);
+
+impl fmt::Display for JsonValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonValue::Str(s) => {
+                write!(f, "\"")?;
+                for c in s.chars() {
+                    match c {
+                        '"' => write!(f, "\\\"")?,
+                        '\\' => write!(f, "\\\\")?,
+                        '\n' => write!(f, "\\n")?,
+                        '\r' => write!(f, "\\r")?,
+                        '\t' => write!(f, "\\t")?,
+                        c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+                        c => write!(f, "{}", c)?,
+                    }
+                }
+                write!(f, "\"")
+            }
+            JsonValue::Int(n) => write!(f, "{}", n),
+            JsonValue::Bool(b) => write!(f, "{}", b),
+            JsonValue::List(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            JsonValue::Object(fields) => {
+                write!(f, "{{")?;
+                for (i, (name, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}:{}", JsonValue::from(name.as_str()), value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+/// Читатель JSON ровно в объёме JsonValue: строки, целые, true/false, списки и объекты.
+/// Обратный к Display: то, что записано, читается обратно без потерь
+pub(crate) struct JsonReader<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+}
+
+impl<'a> JsonReader<'a> {
+    pub(crate) fn new(text: &'a str) -> Self {
+        Self { chars: text.chars().peekable() }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+    }
+
+    fn expect(&mut self, c: char) -> Option<()> {
+        self.skip_whitespace();
+        self.chars.next_if_eq(&c).map(|_| ())
+    }
+
+    fn string(&mut self) -> Option<String> {
+        self.expect('"')?;
+        let mut s = String::new();
+        loop {
+            match self.chars.next()? {
+                '"' => return Some(s),
+                '\\' => s.push(match self.chars.next()? {
+                    'n' => '\n',
+                    'r' => '\r',
+                    't' => '\t',
+                    'u' => {
+                        let hex: String = (0..4).filter_map(|_| self.chars.next()).collect();
+                        char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?
+                    }
+                    c => c,
+                }),
+                c => s.push(c),
+            }
+        }
+    }
+
+    pub(crate) fn value(&mut self) -> Option<JsonValue> {
+        self.skip_whitespace();
+        match *self.chars.peek()? {
+            '"' => self.string().map(JsonValue::Str),
+            '{' => self.fields().map(JsonValue::Object),
+            '[' => {
+                self.chars.next();
+                let mut items = Vec::new();
+                if self.expect(']').is_some() {
+                    return Some(JsonValue::List(items));
+                }
+                loop {
+                    items.push(self.value()?);
+                    if self.expect(',').is_none() {
+                        self.expect(']')?;
+                        return Some(JsonValue::List(items));
+                    }
+                }
+            }
+            c if c == '-' || c.is_ascii_digit() => {
+                let mut number = String::new();
+                while let Some(c) = self.chars.next_if(|c| *c == '-' || c.is_ascii_digit()) {
+                    number.push(c);
+                }
+                number.parse().ok().map(JsonValue::Int)
+            }
+            _ => {
+                let word: String = std::iter::from_fn(|| self.chars.next_if(|c| c.is_ascii_alphabetic())).collect();
+                match word.as_str() {
+                    "true" => Some(JsonValue::Bool(true)),
+                    "false" => Some(JsonValue::Bool(false)),
+                    _ => None,
+                }
+            }
+        }
+    }
+
+    fn fields(&mut self) -> Option<Vec<(String, JsonValue)>> {
+        self.expect('{')?;
+        let mut fields = Vec::new();
+        if self.expect('}').is_none() {
+            loop {
+                let name = self.string()?;
+                self.expect(':')?;
+                fields.push((name, self.value()?));
+                if self.expect(',').is_none() {
+                    self.expect('}')?;
+                    break;
+                }
+            }
+        }
+        Some(fields)
+    }
+
+    /// Объект верхнего уровня как поля в порядке записи; None — не объект или мусор после него
+    pub(crate) fn object(mut self) -> Option<Vec<(String, JsonValue)>> {
+        let fields = self.fields()?;
+        self.skip_whitespace();
+        self.chars.next().is_none().then_some(fields)
+    }
+}
+
+/// --state-file: докуда дошла установка. Файл переписывается после каждой фазы, а при
+/// продолжении по нему выполненные фазы проигрываются молча с тем же seed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SavedProgress {
+    pub seed: u64,
+    /// Пакеты в порядке установки: продолжить можно только тот же набор
+    pub packages: Vec<String>,
+    /// Пакет (индекс в порядке установки) и число завершённых фаз в нём
+    pub package: usize,
+    pub phase: usize,
+    /// Команда верхнего уровня фазы phase, на которой запуск оборвался
+    pub command: usize,
+}
+
+impl SavedProgress {
+    fn to_json(&self) -> String {
+        // seed — строкой: u64 не помещается в целые JsonValue
+        let fields = [
+            ("seed", JsonValue::from(self.seed.to_string())),
+            ("packages", JsonValue::from(self.packages.as_slice())),
+            ("package", JsonValue::from(self.package)),
+            ("phase", JsonValue::from(self.phase)),
+            ("command", JsonValue::from(self.command)),
+        ];
+        let fields: Vec<String> = fields.iter().map(|(name, value)| format!("{}:{}", JsonValue::from(*name), value)).collect();
+        format!("{{{}}}", fields.join(","))
+    }
+
+    fn from_json(text: &str) -> Option<Self> {
+        let mut progress = SavedProgress::default();
+        for (name, value) in JsonReader::new(text).object()? {
+            let index = || match value {
+                JsonValue::Int(n) => usize::try_from(n).ok(),
+                _ => None,
+            };
+            match (name.as_str(), &value) {
+                ("seed", JsonValue::Str(s)) => progress.seed = s.parse().ok()?,
+                ("packages", JsonValue::List(items)) => {
+                    progress.packages = items.iter()
+                        .map(|item| match item {
+                            JsonValue::Str(s) => Some(s.clone()),
+                            _ => None,
+                        })
+                        .collect::<Option<_>>()?;
+                }
+                ("package", _) => progress.package = index()?,
+                ("phase", _) => progress.phase = index()?,
+                ("command", _) => progress.command = index()?,
+                ("seed" | "packages", _) => return None,
+                _ => {}
+            }
+        }
+        Some(progress)
+    }
+
+    /// Сохранённое состояние; None — файла нет или он не читается как состояние
+    pub fn load(path: &Path) -> Option<Self> {
+        Self::from_json(&fs::read_to_string(path).ok()?)
+    }
+
+    pub(crate) fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_json() + "\n")
+    }
+}
+
+/// Событие установки: тип и поля объекта в порядке вывода
+#[derive(Debug, Clone)]
+pub struct Event {
+    kind: &'static str,
+    fields: Vec<(&'static str, JsonValue)>,
+}
+
+impl Event {
+    pub fn new(kind: &'static str) -> Self {
+        Self { kind, fields: Vec::new() }
+    }
+
+    pub fn with(mut self, name: &'static str, value: impl Into<JsonValue>) -> Self {
+        self.fields.push((name, value.into()));
+        self
+    }
+
+    pub(crate) fn extend(mut self, fields: Vec<(&'static str, JsonValue)>) -> Self {
+        self.fields.extend(fields);
+        self
+    }
+
+    /// Строковое поле события
+    fn text(&self, name: &str) -> Option<&str> {
+        self.fields.iter().find_map(|(field, value)| match value {
+            JsonValue::Str(s) if *field == name => Some(s.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Объект события в одну строку; ts — время в миллисекундах Unix
+    pub(crate) fn to_json(&self, ts: u64) -> String {
+        let mut json = format!("{{\"type\":{},\"ts\":{}", JsonValue::from(self.kind), ts);
+        for (name, value) in &self.fields {
+            json.push_str(&format!(",{}:{}", JsonValue::from(*name), value));
+        }
+        json.push('}');
+        json
+    }
+}
+
+/// Куда уходит вывод симулятора. Все методы берут &self и Sync, потому что задачи
+/// parallel пишут из своих потоков, а Send — потому что с --parallel пакеты ставятся в потоках
+pub trait OutputSink: Send + Sync {
+    /// Строка текстового вывода
+    fn line(&self, text: &str);
+    /// Текст без перевода строки: продолжение («OK», «done») напечатает следующий вызов
+    fn inline(&self, text: &str);
+    /// Машиночитаемое событие; текстовым получателям хватает строк
+    fn event(&self, _event: Event) {}
+    /// Рисовать ли анимацию indicatif — вне терминала она перемешалась бы с выводом
+    fn animated(&self) -> bool {
+        false
+    }
+    /// Полоса на len шагов: видимая, если получатель анимирован, иначе скрытая
+    fn progress_start(&self, len: u64) -> ProgressBar {
+        if self.animated() {
+            ProgressBar::new(len)
+        } else {
+            ProgressBar::with_draw_target(Some(len), ProgressDrawTarget::hidden())
+        }
+    }
+    /// Общий прогресс пакета командой progress; line — готовая строка с полосой
+    fn progress_update(&self, _pct: u8, line: &str) {
+        self.line(line);
+    }
+    /// --quiet: пока muted, вывод команд на экран не попадает (в журнал --log-file — попадает)
+    fn mute(&self, _muted: bool) {}
+}
+
+/// Строки текста в том виде, в каком они остаются на экране: от перерисованной через \r
+/// строки (кадры спиннера) — только то, что после последнего \r
+fn settled_lines(text: &str) -> impl Iterator<Item = &str> {
+    text.split('\n').map(|line| line.rsplit('\r').next().unwrap_or_default().trim_end())
+}
+
+/// Ширина рамок, когда вывод идёт не в терминал: при ней разделитель фазы — 50 символов, полосы — 30
+pub const DEFAULT_WIDTH: usize = 69;
+
+/// Колонки и строки терминала, в который идёт stdout; None — не терминал или размер неизвестен
+#[cfg(unix)]
+pub fn terminal_size() -> Option<(usize, usize)> {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    let ok = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0;
+    Some((size.ws_col as usize, size.ws_row as usize)).filter(|&(cols, rows)| ok && cols > 0 && rows > 0)
+}
+
+#[cfg(not(unix))]
+pub fn terminal_size() -> Option<(usize, usize)> {
+    let var = |name: &str| std::env::var(name).ok()?.parse().ok();
+    Some((var("COLUMNS")?, var("LINES")?))
+}
+
+/// Строка из терминала без эха, для пароля sudo. Пока строка не введена, раз в 100 мс
+/// проверяется interrupt: по Ctrl+C эхо возвращается и результат — None, как и при ошибке чтения
+#[cfg(unix)]
+pub(crate) fn read_hidden_line(interrupt: &AtomicBool) -> Option<String> {
+    /// Возвращает сохранённые настройки терминала при любом выходе
+    struct Echo(libc::termios);
+    impl Drop for Echo {
+        fn drop(&mut self) {
+            unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.0) };
+        }
+    }
+
+    let mut saved: libc::termios = unsafe { std::mem::zeroed() };
+    if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut saved) } != 0 {
+        return None;
+    }
+    let mut hidden = saved;
+    hidden.c_lflag &= !libc::ECHO;
+    let _echo = Echo(saved);
+    unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &hidden) };
+
+    // Терминал в каноническом режиме: stdin готов к чтению, когда нажат Enter
+    let mut pollfd = libc::pollfd { fd: libc::STDIN_FILENO, events: libc::POLLIN, revents: 0 };
+    while unsafe { libc::poll(&mut pollfd, 1, 100) } <= 0 {
+        if interrupt.load(Ordering::SeqCst) {
+            return None;
+        }
+    }
+    let mut line = String::new();
+    io::stdin().lock().read_line(&mut line).ok()?;
+    Some(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Без termios эхо не отключить: пароль виден при вводе
+#[cfg(not(unix))]
+pub(crate) fn read_hidden_line(_interrupt: &AtomicBool) -> Option<String> {
+    let mut line = String::new();
+    io::stdin().lock().read_line(&mut line).ok()?;
+    Some(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Текст, разбитый по словам на строки не длиннее width символов; слово длиннее строки режется
+pub(crate) fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let mut word: Vec<char> = word.chars().collect();
+        let used = line.chars().count();
+        if used > 0 && used + 1 + word.len() > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        while word.len() > width {
+            lines.push(word.drain(..width).collect());
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.extend(word);
+    }
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// Текст без управляющих последовательностей ANSI (цвета, стили)
+fn strip_ansi(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\u{1b}' {
+            result.push(c);
+            continue;
+        }
+        // CSI: ESC [ параметры … завершающий символ из диапазона @..~
+        if chars.next() == Some('[') {
+            for c in chars.by_ref() {
+                if ('@'..='~').contains(&c) {
+                    break;
+                }
+            }
+        }
+    }
+    result
+}
+
+/// Текстовый вывод в stdout. В терминале — с анимацией: кадры спиннеров перерисовывают строку
+/// через \r, полосы indicatif идут в stderr. Вне терминала (plain) строка копится до конца
+/// и печатается один раз — с итоговым состоянием, без кадров; полосы скрыты
+#[derive(Default)]
+pub struct TerminalSink {
+    plain: bool,
+    /// --ascii: значки и псевдографика заменяются символами ASCII
+    pub(crate) ascii: bool,
+    /// Начатая строка в режиме plain
+    pending: Mutex<String>,
+    /// Вывод команд в --quiet
+    muted: AtomicBool,
+}
+
+impl TerminalSink {
+    pub fn new(plain: bool, ascii: bool) -> Self {
+        Self { plain, ascii, ..Self::default() }
+    }
+
+    fn muted(&self) -> bool {
+        self.muted.load(Ordering::SeqCst)
+    }
+
+    fn convert(&self, text: &str) -> String {
+        if self.ascii { ascii_text(text) } else { text.to_string() }
+    }
+}
+
+impl OutputSink for TerminalSink {
+    fn line(&self, text: &str) {
+        if self.muted() {
+            return;
+        }
+        if self.plain {
+            let full = std::mem::take(&mut *self.pending.lock().unwrap()) + text;
+            let shown: Vec<&str> = settled_lines(&full).collect();
+            println!("{}", self.convert(&shown.join("\n")));
+        } else {
+            println!("{}", self.convert(text));
+        }
+    }
+
+    fn inline(&self, text: &str) {
+        if self.muted() {
+            return;
+        }
+        if self.plain {
+            self.pending.lock().unwrap().push_str(text);
+        } else {
+            print!("{}", self.convert(text));
+            io::stdout().flush().unwrap();
+        }
+    }
+
+    fn animated(&self) -> bool {
+        !self.plain && !self.muted()
+    }
+
+    fn mute(&self, muted: bool) {
+        self.muted.store(muted, Ordering::SeqCst);
+    }
+}
+
+/// Замена значков для --ascii; остальные эмодзи и символы становятся «*», кириллица не трогается
+const ASCII_MARKERS: &[(char, &str)] = &[
+    ('✓', "+"), ('✗', "x"), ('⚠', "!"), ('→', "->"), ('▶', ">"), ('▷', ">"), ('◀', "<"), ('›', ">"), ('⇉', "=>"),
+    ('├', "|"), ('└', "`"), ('○', "o"), ('◉', "*"), ('•', "*"), ('⟳', "~"), ('↺', "~"), ('🔄', "~"), ('×', "x"), ('°', ""),
+    ('⸨', "(("), ('⸩', "))"), ('█', "#"), ('▓', "#"), ('▒', ":"), ('░', "."),
+];
+
+fn ascii_text(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        if let Some((_, marker)) = ASCII_MARKERS.iter().find(|(symbol, _)| *symbol == c) {
+            result.push_str(marker);
+            continue;
+        }
+        match c as u32 {
+            // Псевдографика рамок и таблиц
+            0x2550 => result.push('='),
+            0x2500..=0x2501 => result.push('-'),
+            0x2502..=0x2503 | 0x2551 => result.push('|'),
+            0x2504..=0x254F | 0x2552..=0x257F => result.push('+'),
+            // Части полос ▉..▏
+            0x2580..=0x259F => result.push('#'),
+            // Кадры спиннеров из символов Брайля тоже крутятся
+            0x2800..=0x28FF => result.push(['|', '/', '-', '\\'][c as usize % 4]),
+            // Вариационный селектор эмодзи
+            0xFE0F => {}
+            0x2190..=0x2BFF | 0x2E00..=0x2E7F | 0x1F000..=0x1FAFF => result.push('*'),
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+/// Встроенные темы --theme
+const BUILTIN_THEMES: &[(&str, &str)] = &[
+    ("default", include_str!("../themes/default.toml")),
+    ("plain", include_str!("../themes/plain.toml")),
+];
+
+/// Оформление вывода (--theme): цвета, значки, символы полос и название в рамках шапки.
+/// Цвет None — текст без цвета (монохромная тема)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    pub name: String,
+    /// Имена пакетов, ссылки, рамки шапки
+    pub accent: Option<Color>,
+    pub success: Option<Color>,
+    pub warning: Option<Color>,
+    pub(crate) error: Option<Color>,
+    /// Заголовки фаз
+    pub(crate) heading: Option<Color>,
+    /// Символы полос прогресса в формате indicatif: заполненная часть, промежуточные, пустая
+    pub(crate) bar: String,
+    /// То же для плавных полос загрузок
+    pub(crate) smooth: String,
+    /// Замены значков; применяются к каждой строке вывода до ascii
+    markers: Vec<(char, String)>,
+    /// Остальные значки и псевдографика — символами ASCII, как --ascii
+    pub(crate) ascii: bool,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            name: "InstNoth".to_string(),
+            accent: Some(Color::Cyan),
+            success: Some(Color::Green),
+            warning: Some(Color::Yellow),
+            error: Some(Color::Red),
+            heading: Some(Color::Blue),
+            bar: "█▓░".to_string(),
+            smooth: "█▉▊▋▌▍▎▏ ".to_string(),
+            markers: Vec::new(),
+            ascii: false,
+        }
+    }
+}
+
+impl Theme {
+    /// Встроенная тема по имени или файл темы
+    pub fn load(spec: &str) -> Result<Self, String> {
+        if let Some((_, source)) = BUILTIN_THEMES.iter().find(|(name, _)| *name == spec) {
+            return Self::parse(source);
+        }
+        let source = fs::read_to_string(spec).map_err(|e| {
+            let names: Vec<&str> = BUILTIN_THEMES.iter().map(|(name, _)| *name).collect();
+            format!("{}: {} (встроенные темы: {})", spec, e, names.join(", "))
+        })?;
+        Self::parse(&source).map_err(|e| format!("{}: {}", spec, e))
+    }
+
+    /// Тема из файла в подмножестве TOML: таблицы [colors], [progress], [symbols], [markers],
+    /// строки ключ = "значение", комментарии #. Чего нет в файле — как в теме по умолчанию
+    pub(crate) fn parse(source: &str) -> Result<Self, String> {
+        let mut theme = Self::default();
+        let mut table = String::new();
+        for (i, raw) in source.lines().enumerate() {
+            let err = |msg: String| format!("строка {}: {}", i + 1, msg);
+            let line = InstnothParser::strip_comment(raw).trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                if !["colors", "progress", "symbols", "markers"].contains(&name.trim()) {
+                    return Err(err(format!("неизвестная таблица [{}]", name.trim())));
+                }
+                table = name.trim().to_string();
+                continue;
+            }
+            let (key, value) = line.split_once('=').ok_or_else(|| err(format!("ожидается ключ = значение: {}", line)))?;
+            let key = unquote(key.trim()).unwrap_or(key.trim());
+            let value = value.trim();
+            let text = || unquote(value).map(str::to_string).ok_or_else(|| err(format!("{}: значение должно быть в кавычках", key)));
+            match (table.as_str(), key) {
+                ("", "name") => theme.name = text()?,
+                ("colors", "accent" | "success" | "warning" | "error" | "heading") => {
+                    let color = parse_theme_color(&text()?).ok_or_else(|| err(format!("{}: неизвестный цвет {}", key, value)))?;
+                    match key {
+                        "accent" => theme.accent = color,
+                        "success" => theme.success = color,
+                        "warning" => theme.warning = color,
+                        "error" => theme.error = color,
+                        _ => theme.heading = color,
+                    }
+                }
+                ("progress", "bar" | "smooth") => {
+                    let chars = text()?;
+                    if chars.chars().count() < 2 {
+                        return Err(err(format!("{}: нужно хотя бы два символа", key)));
+                    }
+                    if key == "bar" { theme.bar = chars } else { theme.smooth = chars }
+                }
+                ("symbols", "ascii") => {
+                    theme.ascii = value.parse().map_err(|_| err(format!("ascii: ожидается true или false, а не {}", value)))?;
+                }
+                ("markers", symbol) => {
+                    let mut chars = symbol.chars();
+                    let (Some(c), None) = (chars.next(), chars.next()) else {
+                        return Err(err(format!("значок {:?} должен быть одним символом", symbol)));
+                    };
+                    let marker = text()?;
+                    theme.markers.retain(|(old, _)| *old != c);
+                    theme.markers.push((c, marker));
+                }
+                _ => {
+                    let place = if table.is_empty() { String::new() } else { format!(" в [{}]", table) };
+                    return Err(err(format!("неизвестный ключ {}{}", key, place)));
+                }
+            }
+        }
+        Ok(theme)
+    }
+
+    /// Строка вывода со значками темы
+    pub fn apply(&self, text: &str) -> String {
+        if self.markers.is_empty() && !self.ascii {
+            return text.to_string();
+        }
+        let mut result = String::with_capacity(text.len());
+        for c in text.chars() {
+            match self.markers.iter().find(|(symbol, _)| *symbol == c) {
+                Some((_, marker)) => result.push_str(marker),
+                None => result.push(c),
+            }
+        }
+        if self.ascii { ascii_text(&result) } else { result }
+    }
+
+    /// Строка полосы командой progress: первый символ bar — заполненная часть, последний — пустая
+    pub(crate) fn progress_line(&self, pct: u8, width: usize, lang: Lang) -> String {
+        let filled = (width * pct as usize) / 100;
+        let full = self.bar.chars().next().unwrap_or('#').to_string();
+        let empty = self.bar.chars().last().unwrap_or('.').to_string();
+        let bar = format!("[{}{}] {}%", full.repeat(filled).paint(self.success), empty.repeat(width - filled).dimmed(), pct);
+        format!("  {} {}", "◉".paint(self.heading), Msg::Progress.fill(lang, &[&bar]))
+    }
+}
+
+/// Строка в двойных кавычках без кавычек; экранирование не поддерживается
+fn unquote(value: &str) -> Option<&str> {
+    value.strip_prefix('"')?.strip_suffix('"')
+}
+
+/// Цвет темы: имя colored, "#rrggbb" или "none"; Some(None) — без цвета
+fn parse_theme_color(name: &str) -> Option<Option<Color>> {
+    if name.eq_ignore_ascii_case("none") {
+        return Some(None);
+    }
+    if let Some(hex) = name.strip_prefix('#').filter(|h| h.len() == 6) {
+        let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+        return Some(Some(Color::TrueColor { r: channel(0)?, g: channel(2)?, b: channel(4)? }));
+    }
+    name.parse().ok().map(Some)
+}
+
+/// Цвет из темы: None оставляет текст без цвета, но со стилями вроде bold
+pub trait Paint: Colorize + Sized {
+    fn paint(self, color: Option<Color>) -> ColoredString {
+        match color {
+            Some(color) => self.color(color),
+            None => self.normal(),
+        }
+    }
+}
+
+impl<T: Colorize> Paint for T {}
+
+/// --output json: строка JSON на событие, текст не выводится
+pub struct JsonSink;
+
+impl OutputSink for JsonSink {
+    fn line(&self, _text: &str) {}
+
+    fn inline(&self, _text: &str) {}
+
+    fn event(&self, event: Event) {
+        let ts = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        let mut out = io::stdout().lock();
+        writeln!(out, "{}", event.to_json(ts)).unwrap();
+        out.flush().unwrap();
+    }
+
+    fn progress_update(&self, pct: u8, _line: &str) {
+        self.event(Event::new("progress").with("pct", pct));
+    }
+}
+
+/// Выполненные ранее фазы при продолжении --state-file проигрываются без вывода
+pub(crate) struct NullSink;
+
+impl OutputSink for NullSink {
+    fn line(&self, _text: &str) {}
+
+    fn inline(&self, _text: &str) {}
+}
+
+/// --parallel: вывод одного из одновременно устанавливаемых пакетов. Каждая строка целиком,
+/// с префиксом [пакет], уходит в общий получатель; начатая inline строка копится до конца,
+/// чтобы строки пакетов не перемешивались посередине. Анимации нет — кадры спиннеров
+/// разных пакетов всё равно не поделили бы одну строку терминала
+pub(crate) struct LaneSink {
+    prefix: String,
+    inner: Arc<dyn OutputSink>,
+    pending: Mutex<String>,
+    /// --quiet глушит только свой пакет, а не общий получатель
+    muted: AtomicBool,
+}
+
+impl LaneSink {
+    pub(crate) fn new(prefix: String, inner: Arc<dyn OutputSink>) -> Self {
+        Self { prefix, inner, pending: Mutex::new(String::new()), muted: AtomicBool::new(false) }
+    }
+}
+
+impl OutputSink for LaneSink {
+    fn line(&self, text: &str) {
+        let text = std::mem::take(&mut *self.pending.lock().unwrap()) + text;
+        if self.muted.load(Ordering::SeqCst) {
+            return;
+        }
+        for line in settled_lines(&text) {
+            if self.prefix.is_empty() || line.is_empty() {
+                self.inner.line(&format!("{}{}", self.prefix, line));
+            } else {
+                self.inner.line(&format!("{} {}", self.prefix, line));
+            }
+        }
+    }
+
+    fn inline(&self, text: &str) {
+        self.pending.lock().unwrap().push_str(text);
+    }
+
+    fn event(&self, event: Event) {
+        self.inner.event(event);
+    }
+
+    // Событие — для --output json, строка — для текста: каждый получатель возьмёт своё
+    fn progress_update(&self, pct: u8, line: &str) {
+        self.inner.event(Event::new("progress").with("pct", pct));
+        self.line(line);
+    }
+
+    fn mute(&self, muted: bool) {
+        self.muted.store(muted, Ordering::SeqCst);
+    }
+}
+
+/// --log-file: копия вывода в файл — без цветов, с отметкой времени от начала запуска.
+/// Кадры спиннеров (текст до последнего \r) в файл не попадают, только итоговая строка.
+/// Файл пишется построчно без буфера, поэтому при прерывании записанное не теряется.
+/// Полосы indicatif печатают итоги мимо получателя, поэтому с журналом они отключены, как с --record
+pub struct LogSink {
+    inner: Box<dyn OutputSink>,
+    started: Instant,
+    /// Файл и начатая inline строка, которую допишет следующий line
+    log: Mutex<(fs::File, String)>,
+}
+
+impl LogSink {
+    pub fn create(path: &Path, inner: Box<dyn OutputSink>) -> io::Result<Self> {
+        let mut file = fs::File::create(path)?;
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        writeln!(file, "# InstNoth: журнал установки, начат {}Z", iso_date(now))?;
+        Ok(Self { inner, started: Instant::now(), log: Mutex::new((file, String::new())) })
+    }
+
+    /// Дописывает text к начатой строке и записывает результат с отметкой времени
+    fn write_line(&self, text: &str) {
+        let elapsed = self.started.elapsed();
+        let secs = elapsed.as_secs();
+        let stamp = format!("[{:02}:{:02}:{:02}.{:03}]", secs / 3600, secs / 60 % 60, secs % 60, elapsed.subsec_millis());
+        let mut log = self.log.lock().unwrap();
+        let (file, pending) = &mut *log;
+        let full = std::mem::take(pending) + text;
+        for line in settled_lines(&full) {
+            // Ошибку записи в журнал не превращаем в сбой установки
+            let _ = writeln!(file, "{} {}", stamp, strip_ansi(line).trim_end());
+        }
+    }
+}
+
+impl OutputSink for LogSink {
+    fn line(&self, text: &str) {
+        self.inner.line(text);
+        self.write_line(text);
+    }
+
+    fn inline(&self, text: &str) {
+        self.inner.inline(text);
+        self.log.lock().unwrap().1.push_str(text);
+    }
+
+    fn event(&self, event: Event) {
+        let name = event.text("name").unwrap_or_default().to_string();
+        let boundary = match event.kind {
+            "package_start" => Some(format!("──── Пакет {} {} ────", name, event.text("version").unwrap_or_default())),
+            "package_done" => Some(format!("──── {}: установлен ────", name)),
+            "package_failed" => Some(format!("──── {}: установка прервана ────", name)),
+            "package_cancelled" => Some(format!("──── {}: установка прервана пользователем ────", name)),
+            _ => None,
+        };
+        if let Some(boundary) = boundary {
+            self.write_line(&boundary);
+        }
+        self.inner.event(event);
+    }
+
+    fn progress_update(&self, pct: u8, line: &str) {
+        self.inner.progress_update(pct, line);
+        self.write_line(line);
+    }
+
+    fn mute(&self, muted: bool) {
+        self.inner.mute(muted);
+    }
+}
+
+/// --record: запись asciicast v2 для asciinema. Первая строка — заголовок с размером
+/// терминала, дальше по событию ["секунды", "o", "текст"] на каждый вывод, как он ушёл на
+/// экран: с цветами и кадрами спиннера через \r. Секунды — по настоящим часам, поэтому паузы
+/// в записи те же, что с --speed на экране. Полосы indicatif рисуются мимо получателя и в
+/// запись не попали бы, поэтому при записи они отключены, как вне терминала
+pub struct CastSink {
+    inner: Box<dyn OutputSink>,
+    started: Instant,
+    /// --ascii: в запись идут те же замены значков, что и на экран
+    pub(crate) ascii: bool,
+    muted: AtomicBool,
+    pub(crate) file: Mutex<fs::File>,
+}
+
+impl CastSink {
+    pub fn create(path: &Path, inner: Box<dyn OutputSink>, (width, height): (usize, usize), ascii: bool) -> io::Result<Self> {
+        let mut file = fs::File::create(path)?;
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        writeln!(file, "{{\"version\": 2, \"width\": {}, \"height\": {}, \"timestamp\": {}}}", width, height, now)?;
+        Ok(Self { inner, started: Instant::now(), ascii, muted: AtomicBool::new(false), file: Mutex::new(file) })
+    }
+
+    /// Событие вывода; терминал в записи сырой, поэтому перевод строки — \r\n
+    pub(crate) fn write(&self, text: &str) {
+        if text.is_empty() || self.muted.load(Ordering::SeqCst) {
+            return;
+        }
+        let text = if self.ascii { ascii_text(text) } else { text.to_string() };
+        let data = JsonValue::from(text.replace('\n', "\r\n").as_str());
+        // Ошибку записи не превращаем в сбой установки
+        let _ = writeln!(self.file.lock().unwrap(), "[{:.6}, \"o\", {}]", self.started.elapsed().as_secs_f64(), data);
+    }
+}
+
+impl OutputSink for CastSink {
+    fn line(&self, text: &str) {
+        self.inner.line(text);
+        self.write(&format!("{}\n", text));
+    }
+
+    fn inline(&self, text: &str) {
+        self.inner.inline(text);
+        self.write(text);
+    }
+
+    fn event(&self, event: Event) {
+        self.inner.event(event);
+    }
+
+    fn progress_update(&self, pct: u8, line: &str) {
+        self.inner.progress_update(pct, line);
+        self.write(&format!("{}\n", line));
+    }
+
+    fn mute(&self, muted: bool) {
+        self.muted.store(muted, Ordering::SeqCst);
+        self.inner.mute(muted);
+    }
+}
+
+/// Строка вывода симулятора: say!(sim) — пустая, say!(sim, "{}", x) — как println!
+#[macro_export]
+macro_rules! say {
+    ($sim:expr) => {
+        $sim.out.line("")
+    };
+    ($sim:expr, $($arg:tt)*) => {{
+        let text = format!($($arg)*);
+        $sim.out.line(&$sim.theme.apply(&text))
+    }};
+}
+
+/// Как print!: строка без перевода, которую допишет следующий say!
+#[macro_export]
+macro_rules! say_inline {
+    ($sim:expr, $($arg:tt)*) => {{
+        let text = format!($($arg)*);
+        $sim.out.inline(&$sim.theme.apply(&text))
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settled_lines_drop_spinner_frames() {
+        let text = "  → Очистка \r  ⠋ Очистка  \r  ✓ Очистка  \nготово";
+        assert_eq!(settled_lines(text).collect::<Vec<_>>(), ["  ✓ Очистка", "готово"]);
+    }
+
+    #[test]
+    fn ascii_replaces_markers_and_keeps_cyrillic() {
+        assert_eq!(ascii_text("  ✓ Готово"), "  + Готово");
+        assert_eq!(ascii_text("  🔍 Определение — «CPU»"), "  * Определение — «CPU»");
+        assert_eq!(ascii_text("╔══╗ [██░░] 50%"), "+==+ [##..] 50%");
+        assert_eq!(ascii_text("    ├ Ядра:\n    └ Частота:"), "    | Ядра:\n    ` Частота:");
+    }
+
+    #[test]
+    fn strip_ansi_removes_colors() {
+        assert_eq!(strip_ansi("\u{1b}[1;32m✓\u{1b}[0m \u{1b}[36mготово\u{1b}[0m"), "✓ готово");
+    }
+
+    #[test]
+    fn saved_progress_round_trips_through_json() {
+        let progress = SavedProgress {
+            seed: u64::MAX,
+            packages: vec!["Arch \"Linux\"".to_string(), "Python".to_string()],
+            package: 1,
+            phase: 3,
+            command: 7,
+        };
+        assert_eq!(SavedProgress::from_json(&progress.to_json()), Some(progress));
+        let spaced = SavedProgress::from_json("{ \"seed\": \"5\", \"packages\": [ ], \"phase\": 2 }\n");
+        assert_eq!(spaced.map(|p| (p.seed, p.phase)), Some((5, 2)));
+        assert_eq!(SavedProgress::from_json("{\"seed\":5}"), None);
+        assert_eq!(SavedProgress::from_json("{\"phase\":-1}"), None);
+        assert_eq!(SavedProgress::from_json("{} мусор"), None);
+    }
+
+    #[test]
+    fn wrap_breaks_on_words_and_cuts_long_ones() {
+        assert_eq!(wrap("ОС + Python + Node.js", 12), ["ОС + Python", "+ Node.js"]);
+        assert_eq!(wrap("abcdefghij x", 4), ["abcd", "efgh", "ij x"]);
+        assert_eq!(wrap("", 10), [""]);
+    }
+
+    #[test]
+    fn builtin_default_theme_matches_defaults() {
+        assert_eq!(Theme::load("default"), Ok(Theme::default()));
+        let plain = Theme::load("plain").unwrap();
+        assert_eq!((plain.accent, plain.ascii), (None, true));
+        assert_eq!(plain.apply("  ✓ Готово → 📦"), "  [ok] Готово -> [pkg]");
+    }
+
+    #[test]
+    fn theme_file_overrides_only_given_keys() {
+        let theme = Theme::parse("name = \"ACME\"\n[colors]\naccent = \"#ff8800\" # фирменный\n[markers]\n\"✓\" = \"OK\"\n").unwrap();
+        assert_eq!(theme.name, "ACME");
+        assert_eq!(theme.accent, Some(Color::TrueColor { r: 255, g: 136, b: 0 }));
+        assert_eq!(theme.success, Theme::default().success);
+        assert_eq!(theme.apply("✓ ⚠"), "OK ⚠");
+        assert_eq!(Theme::parse("[colors]\naccent = \"pink\"").unwrap_err(), "строка 2: accent: неизвестный цвет \"pink\"");
+        assert_eq!(Theme::parse("[sizes]").unwrap_err(), "строка 1: неизвестная таблица [sizes]");
+        assert_eq!(Theme::parse("[colors]\nbar = \"#\"").unwrap_err(), "строка 2: неизвестный ключ bar в [colors]");
+    }
+
+    #[test]
+    fn catalog_fills_values_and_plural_forms() {
+        assert_eq!(Msg::Steps.count(Lang::Ru, 3), "3 шага");
+        assert_eq!(Msg::Steps.count(Lang::En, 1), "1 step");
+        assert_eq!(Msg::Steps.count(Lang::En, 11), "11 steps");
+        assert_eq!(Msg::CancelledPhases.fill(Lang::En, &[&"demo", &2, &5]), "demo (phases completed: 2 of 5)");
+        let stats = RunStats { commands: 1, warnings: 2, ..RunStats::default() };
+        assert_eq!(stats.describe(Lang::En), "Completed 1 operation · 2 warnings");
+    }
+}
@@ -0,0 +1,3383 @@
+//! Файл установки: пакет, фазы и команды, их разбор из текста .instnoth и проверка
+
+use colored::*;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::time::Duration;
+use crate::{output::*, toml_format};
+
+// ============== Структуры данных ==============
+
+#[derive(Debug, Clone)]
+pub struct Package {
+    pub name: String,
+    pub version: String,
+    pub description: String,
+    pub author: String,
+    pub license: String,
+    pub homepage: String,
+    /// Размер установки в человекочитаемом виде ("1.2 GB")
+    pub size: String,
+    pub arch: String,
+    /// Пакетный менеджер, в стиле которого оформляется вывод
+    pub distro: Option<Distro>,
+    /// on_error: "abort" — любая команда error прерывает установку, а не только error fatal=true
+    pub abort_on_error: bool,
+    /// require_root: true — перед первой фазой спрашивается пароль sudo
+    pub require_root: bool,
+    pub depends: Vec<Dependency>,
+    /// provides: "webserver" — имена, под которыми пакет годится в `depends:` других пакетов
+    pub provides: Vec<String>,
+    /// conflicts: "apache" — пакеты (по имени или provides), с которыми этот не ставится
+    pub conflicts: Vec<String>,
+    pub phases: Vec<Phase>,
+    pub file_path: Option<PathBuf>,
+    /// Параметры команд, не указанные в строке и взятые по умолчанию, по номерам строк (для --dry-run)
+    pub(crate) defaulted: HashMap<usize, Vec<String>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Dependency {
+    pub path: String,
+    pub constraint: Option<VersionConstraint>,
+    /// Строка с `depends:`, где объявлена зависимость
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ConstraintOp {
+    GreaterEq,
+    LessEq,
+    Exact,
+    Tilde,
+    /// Версия без оператора: "3.12" совпадает с 3.12, 3.12.1, 3.12.7 ...
+    Prefix,
+}
+
+#[derive(Debug, Clone)]
+pub struct VersionConstraint {
+    op: ConstraintOp,
+    pub version: String,
+}
+
+impl VersionConstraint {
+    pub(crate) fn parse(s: &str) -> Result<Self, String> {
+        let s = s.trim();
+        let (op, rest) = if let Some(rest) = s.strip_prefix(">=") {
+            (ConstraintOp::GreaterEq, rest)
+        } else if let Some(rest) = s.strip_prefix("<=") {
+            (ConstraintOp::LessEq, rest)
+        } else if let Some(rest) = s.strip_prefix('=') {
+            (ConstraintOp::Exact, rest)
+        } else if let Some(rest) = s.strip_prefix('~') {
+            (ConstraintOp::Tilde, rest)
+        } else if s.starts_with(|c: char| c.is_ascii_digit()) {
+            (ConstraintOp::Prefix, s)
+        } else {
+            return Err(format!("Некорректное ограничение версии: {}", s));
+        };
+
+        let version = rest.trim();
+        if version.is_empty() || !version.starts_with(|c: char| c.is_ascii_digit()) {
+            return Err(format!("Некорректное ограничение версии: {}", s));
+        }
+        Ok(Self { op, version: version.to_string() })
+    }
+
+    pub fn matches(&self, version: &str) -> bool {
+        let have = parse_version(version);
+        let want = parse_version(&self.version);
+        match self.op {
+            ConstraintOp::GreaterEq => compare_versions(&have, &want).is_ge(),
+            ConstraintOp::LessEq => compare_versions(&have, &want).is_le(),
+            ConstraintOp::Exact => compare_versions(&have, &want).is_eq(),
+            ConstraintOp::Prefix => {
+                have.len() >= want.len() && have[..want.len()] == want[..]
+            }
+            ConstraintOp::Tilde => {
+                // Как в cargo: ~1.2.3 → >=1.2.3, <1.3.0; ~1 → >=1.0.0, <2.0.0
+                let fixed = if want.len() > 1 { 2 } else { 1 };
+                let same_prefix = (0..fixed).all(|i| have.get(i).copied().unwrap_or(0) == want[i]);
+                same_prefix && compare_versions(&have, &want).is_ge()
+            }
+        }
+    }
+}
+
+impl fmt::Display for VersionConstraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self.op {
+            ConstraintOp::GreaterEq => ">= ",
+            ConstraintOp::LessEq => "<= ",
+            ConstraintOp::Exact => "= ",
+            ConstraintOp::Tilde => "~ ",
+            ConstraintOp::Prefix => "",
+        };
+        write!(f, "{}{}", op, self.version)
+    }
+}
+
+impl fmt::Display for Dependency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.constraint {
+            Some(c) => write!(f, "{} {}", self.path, c),
+            None => write!(f, "{}", self.path),
+        }
+    }
+}
+
+/// Числовые компоненты версии: "6.6.8-arch1" → [6, 6, 8]
+pub(crate) fn parse_version(version: &str) -> Vec<u64> {
+    version
+        .split(['-', '+', ' '])
+        .next()
+        .unwrap_or("")
+        .split('.')
+        .map(|part| {
+            let digits: String = part.chars().take_while(|c| c.is_ascii_digit()).collect();
+            digits.parse().unwrap_or(0)
+        })
+        .collect()
+}
+
+pub(crate) fn compare_versions(a: &[u64], b: &[u64]) -> std::cmp::Ordering {
+    let len = a.len().max(b.len());
+    for i in 0..len {
+        let x = a.get(i).copied().unwrap_or(0);
+        let y = b.get(i).copied().unwrap_or(0);
+        if x != y {
+            return x.cmp(&y);
+        }
+    }
+    std::cmp::Ordering::Equal
+}
+
+/// Ближайшее известное ключевое слово для опечатки (по расстоянию Левенштейна)
+fn suggest_keyword(word: &str) -> Option<&'static str> {
+    let max_distance = (word.chars().count() / 3).max(2);
+    COMMANDS.iter()
+        .map(|spec| (edit_distance(word, spec.keyword), spec.keyword))
+        .filter(|(d, _)| *d <= max_distance)
+        .min_by_key(|(d, _)| *d)
+        .map(|(_, k)| k)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut row = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            row.push((prev[j] + cost).min(prev[j + 1] + 1).min(row[j] + 1));
+        }
+        prev = row;
+    }
+    prev[b.len()]
+}
+
+/// Разбор размера вида "1.2 GB", "512M", "8G", "100 MiB" в байты
+pub(crate) fn parse_human_size(s: &str) -> Option<u64> {
+    let s = s.trim();
+    let split = s.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let value: f64 = number.parse().ok()?;
+    let multiplier: u64 = match unit.trim().to_lowercase().as_str() {
+        "" | "b" | "б" => 1,
+        "k" | "kb" | "kib" | "кб" => 1 << 10,
+        "m" | "mb" | "mib" | "мб" => 1 << 20,
+        "g" | "gb" | "gib" | "гб" => 1 << 30,
+        "t" | "tb" | "tib" | "тб" => 1 << 40,
+        _ => return None,
+    };
+    Some((value * multiplier as f64) as u64)
+}
+
+/// Множитель --speed: конечное число больше нуля, можно с «x» на конце (1.5x)
+pub fn parse_speed(s: &str) -> Result<f32, String> {
+    let value: f32 = s.trim().trim_end_matches(['x', 'X']).parse().map_err(|_| format!("не число: {}", s))?;
+    if value.is_finite() && value > 0.0 {
+        Ok(value)
+    } else {
+        Err("множитель должен быть больше нуля; для установки без пауз есть --quick".to_string())
+    }
+}
+
+/// Дата по числу дней от 1970-01-01: (год, месяц, день)
+pub(crate) fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Время в формате openssl: "Oct 14 09:30:00 2027 GMT"
+pub(crate) fn openssl_date(unix_secs: u64) -> String {
+    const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+    let (year, month, day) = civil_from_days((unix_secs / 86_400) as i64);
+    let secs = unix_secs % 86_400;
+    format!("{} {:>2} {:02}:{:02}:{:02} {} GMT", MONTHS[month as usize - 1], day, secs / 3600, secs / 60 % 60, secs % 60, year)
+}
+
+/// Время в формате ISO 8601: "2027-10-14T09:30:00"
+pub fn iso_date(unix_secs: u64) -> String {
+    let (year, month, day) = civil_from_days((unix_secs / 86_400) as i64);
+    let secs = unix_secs % 86_400;
+    format!("{}-{:02}-{:02}T{:02}:{:02}:{:02}", year, month, day, secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+/// Версия по дате, как у rolling-release дистрибутивов: "2027.10.14"
+pub fn date_version(unix_secs: u64) -> String {
+    let (year, month, day) = civil_from_days((unix_secs / 86_400) as i64);
+    format!("{}.{:02}.{:02}", year, month, day)
+}
+
+/// Имя текущего пользователя из $USER или $LOGNAME, иначе "user"
+pub fn user_name() -> String {
+    ["USER", "LOGNAME"].iter()
+        .find_map(|name| std::env::var(name).ok().filter(|u| !u.is_empty()))
+        .unwrap_or_else(|| "user".to_string())
+}
+
+/// Base64 без выравнивания "=", как в отпечатках OpenSSH
+pub(crate) fn base64_unpadded(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |acc, (i, &b)| acc | (b as u32) << (16 - 8 * i));
+        for i in 0..=chunk.len() {
+            out.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+        }
+    }
+    out
+}
+
+/// Картинка ssh-keygen (алгоритм «пьяного слона») по байтам отпечатка: рамка 17×9
+pub(crate) fn randomart(title: &str, digest: &[u8]) -> Vec<String> {
+    const WIDTH: usize = 17;
+    const HEIGHT: usize = 9;
+    const SYMBOLS: &[u8] = b" .o+=*BOX@%&#/^";
+    let mut field = [[0usize; WIDTH]; HEIGHT];
+    let (mut x, mut y) = (WIDTH / 2, HEIGHT / 2);
+    // Каждый байт — четыре хода по диагонали, младшие биты первыми
+    for &byte in digest {
+        for step in 0..4 {
+            let bits = byte >> (step * 2);
+            x = if bits & 1 == 1 { (x + 1).min(WIDTH - 1) } else { x.saturating_sub(1) };
+            y = if bits & 2 == 2 { (y + 1).min(HEIGHT - 1) } else { y.saturating_sub(1) };
+            field[y][x] += 1;
+        }
+    }
+    let border = |label: &str| {
+        let left = (WIDTH - label.len()) / 2;
+        format!("+{}{}{}+", "-".repeat(left), label, "-".repeat(WIDTH - label.len() - left))
+    };
+    let mut lines = vec![border(&format!("[{}]", title))];
+    for (row, cells) in field.iter().enumerate() {
+        let line: String = cells.iter().enumerate().map(|(col, &visits)| {
+            if (col, row) == (WIDTH / 2, HEIGHT / 2) {
+                'S'
+            } else if (col, row) == (x, y) {
+                'E'
+            } else {
+                SYMBOLS[visits.min(SYMBOLS.len() - 1)] as char
+            }
+        }).collect();
+        lines.push(format!("|{}|", line));
+    }
+    lines.push(border("[SHA256]"));
+    lines
+}
+
+#[derive(Debug, Clone)]
+pub struct Phase {
+    pub name: String,
+    pub commands: Vec<Command>,
+    /// Номера строк исходного файла для каждой команды из `commands`
+    pub command_lines: Vec<usize>,
+    /// Строка объявления фазы
+    pub line: usize,
+    pub optional: bool,
+    pub weight: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Message(String),
+    Delay(u64),
+    Progress(u8),
+    CreateDir(String),
+    Download { url: String, size: u64 },
+    Extract { from: String, to: String },
+    InstallDep { name: String, version: String },
+    Configure { key: String, value: String },
+    Cleanup,
+    Success(String),
+    /// fatal — прервать ли установку (None — как велит on_error пакета), exit — код выхода при этом
+    Error { message: String, fatal: Option<bool>, exit: i32 },
+    Warning(String),
+    CopyFile { from: String, to: String },
+    Symlink { from: String, to: String },
+    SetPermission { path: String, mode: String },
+    RunScript(String),
+    CheckDep(String),
+    WriteConfig { path: String, content: String },
+    DetectCpu,
+    DetectMemory,
+    DetectDisk,
+    DetectGpu,
+    DetectNetwork,
+    DetectOs,
+    DetectKernel,
+    DetectBios,
+    RunTest { name: String, duration: u64 },
+    LoadKernelModule(String),
+    UnloadKernelModule(String),
+    UpdateInitramfs,
+    UpdateGrub,
+    MountPartition { device: String, mount_point: String },
+    UnmountPartition(String),
+    FormatPartition { device: String, fs_type: String },
+    CreatePartition { device: String, size: String },
+    SetHostname(String),
+    SetTimezone(String),
+    SetLocale(String),
+    GenerateLocales(Vec<String>),
+    CreateSnapshot(String),
+    RollbackTo(String),
+    SetKeymap(String),
+    CreateUser { username: String, groups: String },
+    SetPassword(String),
+    EnableService(String),
+    DisableService(String),
+    StartService(String),
+    StopService(String),
+    InstallBootloader(String),
+    GenerateFstab,
+    CheckIntegrity(String),
+    VerifySignature(String),
+    CompileKernel { version: String },
+    InstallPackages(String),
+    UpdateSystem,
+    SyncTime,
+    TestHardware(String),
+    BenchmarkCpu,
+    BenchmarkMemory,
+    BenchmarkDisk,
+    NetworkConfig { interface: String, config: String },
+    FirewallRule(String),
+    ScanHardware,
+    DetectDrivers,
+    InstallDriver(String),
+    Reboot { delay_secs: u64 },
+    TypeText { text: String, cps: u64, style: TextStyle },
+    LogTail { lines: u64, style: LogStyle },
+    Compile { name: String, files: u64, jobs: u64 },
+    Checksum { file: String, algo: ChecksumAlgo },
+    Fail { message: String, code: i32 },
+    DockerPull { image: String, layers: u64 },
+    GitClone { url: String, objects: u64 },
+    GitSubmoduleUpdate,
+    SystemdBoot { units: u64, failures: u64 },
+    ShowPartitions(String),
+    /// fail_chance — вероятность оценки FAILED в процентах
+    SmartReport { device: String, fail_chance: u8 },
+    Sensors,
+    CreateRaid { level: u8, devices: Vec<String> },
+    CreatePool { name: String, devices: Vec<String>, kind: PoolKind },
+    CreateSubvolume(String),
+    /// size в байтах; при zram path не используется
+    SetupSwap { size: u64, path: String, zram: bool },
+    SetKernelParam { key: String, value: String },
+    SetCmdline(String),
+    InstallLangDeps { manager: LangManager, packages: String },
+    /// memory и disk в байтах
+    CreateVm { name: String, cpus: u64, memory: u64, disk: u64 },
+    StartContainer(String),
+    /// domain уже с префиксом "*." при wildcard=true
+    GenerateCert { domain: String, days: u64, key_type: KeyType },
+    HardenSsh,
+    /// bits — размер ключа: для rsa задаётся параметром, для ecdsa — кривая, для ed25519 всегда 256
+    GenerateSshKey { key_type: KeyType, bits: u64, comment: String },
+    ProvisionCloud { provider: CloudProvider, resources: u64 },
+    RunPlaybook { playbook: String, hosts: u64 },
+    /// engine вне postgresql/mysql/sqlite/mongodb выводится в общем стиле
+    InitDatabase { engine: String, version: String },
+    RunMigrations { count: u64 },
+    ShowLicense { id_or_path: LicenseSource },
+    SpeedTest,
+    UpdateFirmware { component: String, version: String },
+    /// expect_timeout — ожидание нарочно доходит до таймаута (expect=timeout)
+    WaitFor { what: String, timeout_secs: u64, expect_timeout: bool },
+    /// need — требуемый объём в байтах; force_fail — проверка нарочно не проходит
+    CheckSpace { path: String, need: u64, force_fail: bool },
+    /// country — двухбуквенный код страны в верхнем регистре
+    SelectMirror { country: String, count: u64 },
+    /// flavor не задан — используется distro: пакета
+    AddRepo { name: String, flavor: Option<Distro> },
+    ImportKey(String),
+    /// name — имя отображения в /dev/mapper; пустое выбирается при выполнении
+    EncryptPartition { device: String, cipher: String, name: String },
+    /// Блок `retry N { ... }`; body_lines параллелен body, как command_lines у фазы
+    Retry { attempts: u32, body: Vec<Command>, body_lines: Vec<usize> },
+    /// Только команды, для которых parallel_safe — true
+    Parallel(Vec<Command>),
+    /// Сбой с вероятностью chance процентов
+    FailRandom { message: String, chance: u8, code: i32 },
+    /// Первая строка rows — заголовок таблицы
+    Table { title: String, rows: Vec<Vec<String>> },
+    Ask { prompt: String, default: bool, required: bool },
+    Choose { prompt: String, options: Vec<String>, default: usize, var: String },
+    /// Обратная операция --uninstall, у которой нет своей команды: «Удаление директории: /opt/app».
+    /// Парсер её не создаёт
+    Revert { action: String, target: String },
+}
+
+impl Command {
+    /// Имя варианта, как в --output json: "Download", "DetectCpu"
+    pub(crate) fn kind(&self) -> String {
+        format!("{:?}", self).chars().take_while(|c| c.is_alphanumeric()).collect()
+    }
+
+    /// Событие command для --output json: имя варианта и его параметры
+    pub(crate) fn event(&self) -> Event {
+        Event::new("command").with("kind", self.kind()).extend(self.fields())
+    }
+
+    /// Параметры команды для события command
+    fn fields(&self) -> Vec<(&'static str, JsonValue)> {
+        let lower = |v: &dyn fmt::Debug| JsonValue::from(format!("{:?}", v).to_lowercase());
+        let kinds = |body: &[Command]| JsonValue::List(body.iter().map(|c| JsonValue::from(c.kind())).collect());
+        match self {
+            Command::Message(text) | Command::Success(text) | Command::Warning(text) => {
+                vec![("text", text.as_str().into())]
+            }
+            Command::Error { message, fatal, exit } => {
+                let mut fields = vec![("text", message.as_str().into())];
+                if let Some(fatal) = fatal {
+                    fields.push(("fatal", (*fatal).into()));
+                }
+                if *exit != 1 {
+                    fields.push(("exit", (*exit).into()));
+                }
+                fields
+            }
+            Command::Delay(ms) => vec![("ms", (*ms).into())],
+            Command::Progress(pct) => vec![("pct", (*pct).into())],
+            Command::CreateDir(path) | Command::CreateSubvolume(path) => vec![("path", path.as_str().into())],
+            Command::Download { url, size } => vec![("url", url.as_str().into()), ("size", (*size).into())],
+            Command::Extract { from, to } | Command::CopyFile { from, to } | Command::Symlink { from, to } => {
+                vec![("from", from.as_str().into()), ("to", to.as_str().into())]
+            }
+            Command::InstallDep { name, version } => vec![("name", name.as_str().into()), ("version", version.as_str().into())],
+            Command::Configure { key, value } | Command::SetKernelParam { key, value } => {
+                vec![("key", key.as_str().into()), ("value", value.as_str().into())]
+            }
+            Command::SetPermission { path, mode } => vec![("path", path.as_str().into()), ("mode", mode.as_str().into())],
+            Command::RunScript(script) => vec![("script", script.as_str().into())],
+            Command::CheckDep(dep) => vec![("dep", dep.as_str().into())],
+            Command::WriteConfig { path, content } => vec![("path", path.as_str().into()), ("content", content.as_str().into())],
+            Command::RunTest { name, duration } => vec![("name", name.as_str().into()), ("duration", (*duration).into())],
+            Command::LoadKernelModule(module) | Command::UnloadKernelModule(module) => vec![("module", module.as_str().into())],
+            Command::MountPartition { device, mount_point } => {
+                vec![("device", device.as_str().into()), ("mount_point", mount_point.as_str().into())]
+            }
+            Command::UnmountPartition(mount_point) => vec![("mount_point", mount_point.as_str().into())],
+            Command::FormatPartition { device, fs_type } => vec![("device", device.as_str().into()), ("fs_type", fs_type.as_str().into())],
+            Command::CreatePartition { device, size } => vec![("device", device.as_str().into()), ("size", size.as_str().into())],
+            Command::SetHostname(hostname) => vec![("hostname", hostname.as_str().into())],
+            Command::SetTimezone(tz) => vec![("timezone", tz.as_str().into())],
+            Command::SetLocale(locale) => vec![("locale", locale.as_str().into())],
+            Command::GenerateLocales(locales) => vec![("locales", locales.as_slice().into())],
+            Command::SetKeymap(keymap) => vec![("keymap", keymap.as_str().into())],
+            Command::CreateSnapshot(name) | Command::RollbackTo(name) => vec![("name", name.as_str().into())],
+            Command::CreateUser { username, groups } => vec![("username", username.as_str().into()), ("groups", groups.as_str().into())],
+            Command::SetPassword(user) => vec![("user", user.as_str().into())],
+            Command::EnableService(service) | Command::DisableService(service)
+            | Command::StartService(service) | Command::StopService(service) => vec![("service", service.as_str().into())],
+            Command::InstallBootloader(target) | Command::CheckIntegrity(target) => vec![("target", target.as_str().into())],
+            Command::VerifySignature(file) => vec![("file", file.as_str().into())],
+            Command::CompileKernel { version } => vec![("version", version.as_str().into())],
+            Command::InstallPackages(packages) => vec![("packages", packages.as_str().into())],
+            Command::TestHardware(component) => vec![("component", component.as_str().into())],
+            Command::NetworkConfig { interface, config } => vec![("interface", interface.as_str().into()), ("config", config.as_str().into())],
+            Command::FirewallRule(rule) => vec![("rule", rule.as_str().into())],
+            Command::InstallDriver(driver) => vec![("driver", driver.as_str().into())],
+            Command::Reboot { delay_secs } => vec![("delay_secs", (*delay_secs).into())],
+            Command::TypeText { text, cps, style } => vec![("text", text.as_str().into()), ("cps", (*cps).into()), ("style", lower(style))],
+            Command::LogTail { lines, style } => vec![("lines", (*lines).into()), ("style", lower(style))],
+            Command::Compile { name, files, jobs } => vec![("name", name.as_str().into()), ("files", (*files).into()), ("jobs", (*jobs).into())],
+            Command::Checksum { file, algo } => vec![("file", file.as_str().into()), ("algo", algo.name().into())],
+            Command::Fail { message, code } => vec![("message", message.as_str().into()), ("code", (*code).into())],
+            Command::DockerPull { image, layers } => vec![("image", image.as_str().into()), ("layers", (*layers).into())],
+            Command::GitClone { url, objects } => vec![("url", url.as_str().into()), ("objects", (*objects).into())],
+            Command::SystemdBoot { units, failures } => vec![("units", (*units).into()), ("failures", (*failures).into())],
+            Command::ShowPartitions(device) => vec![("device", device.as_str().into())],
+            Command::SmartReport { device, fail_chance } => vec![("device", device.as_str().into()), ("fail_chance", (*fail_chance).into())],
+            Command::CreateRaid { level, devices } => vec![("level", (*level).into()), ("devices", devices.as_slice().into())],
+            Command::CreatePool { name, devices, kind } => {
+                vec![("name", name.as_str().into()), ("devices", devices.as_slice().into()), ("fs", lower(kind))]
+            }
+            Command::SetupSwap { size, path, zram } => vec![("size", (*size).into()), ("path", path.as_str().into()), ("zram", (*zram).into())],
+            Command::SetCmdline(cmdline) => vec![("cmdline", cmdline.as_str().into())],
+            Command::InstallLangDeps { manager, packages } => vec![("manager", lower(manager)), ("packages", packages.as_str().into())],
+            Command::CreateVm { name, cpus, memory, disk } => vec![
+                ("name", name.as_str().into()), ("cpus", (*cpus).into()), ("memory", (*memory).into()), ("disk", (*disk).into()),
+            ],
+            Command::StartContainer(image) => vec![("image", image.as_str().into())],
+            Command::GenerateCert { domain, days, key_type } => {
+                vec![("domain", domain.as_str().into()), ("days", (*days).into()), ("key_type", key_type.name().into())]
+            }
+            Command::GenerateSshKey { key_type, bits, comment } => {
+                vec![("key_type", key_type.name().into()), ("bits", (*bits).into()), ("comment", comment.as_str().into())]
+            }
+            Command::ProvisionCloud { provider, resources } => vec![("provider", provider.name().into()), ("resources", (*resources).into())],
+            Command::RunPlaybook { playbook, hosts } => vec![("playbook", playbook.as_str().into()), ("hosts", (*hosts).into())],
+            Command::InitDatabase { engine, version } => vec![("engine", engine.as_str().into()), ("version", version.as_str().into())],
+            Command::RunMigrations { count } => vec![("count", (*count).into())],
+            Command::ShowLicense { id_or_path } => match id_or_path {
+                LicenseSource::Spdx(id) => vec![("spdx", id.as_str().into())],
+                LicenseSource::File(path) => vec![("path", path.as_str().into())],
+            },
+            Command::UpdateFirmware { component, version } => {
+                vec![("component", component.as_str().into()), ("version", version.as_str().into())]
+            }
+            Command::WaitFor { what, timeout_secs, expect_timeout } => vec![
+                ("what", what.as_str().into()), ("timeout_secs", (*timeout_secs).into()), ("expect_timeout", (*expect_timeout).into()),
+            ],
+            Command::CheckSpace { path, need, force_fail } => {
+                vec![("path", path.as_str().into()), ("need", (*need).into()), ("force_fail", (*force_fail).into())]
+            }
+            Command::SelectMirror { country, count } => vec![("country", country.as_str().into()), ("count", (*count).into())],
+            Command::AddRepo { name, flavor } => {
+                let mut fields = vec![("name", name.as_str().into())];
+                if let Some(distro) = flavor {
+                    fields.push(("distro", distro.name().into()));
+                }
+                fields
+            }
+            Command::ImportKey(key) => vec![("key", key.as_str().into())],
+            Command::EncryptPartition { device, cipher, name } => {
+                vec![("device", device.as_str().into()), ("cipher", cipher.as_str().into()), ("name", name.as_str().into())]
+            }
+            Command::Retry { attempts, body, .. } => vec![("attempts", (*attempts).into()), ("commands", kinds(body))],
+            Command::Parallel(children) => vec![("commands", kinds(children))],
+            Command::FailRandom { message, chance, code } => {
+                vec![("message", message.as_str().into()), ("chance", (*chance).into()), ("code", (*code).into())]
+            }
+            Command::Table { title, rows } => vec![
+                ("title", title.as_str().into()),
+                ("rows", JsonValue::List(rows.iter().map(|row| row.as_slice().into()).collect())),
+            ],
+            Command::Ask { prompt, default, required } => {
+                vec![("prompt", prompt.as_str().into()), ("default", (*default).into()), ("required", (*required).into())]
+            }
+            Command::Choose { prompt, options, default, var } => vec![
+                ("prompt", prompt.as_str().into()), ("options", options.as_slice().into()),
+                ("default", (*default).into()), ("var", var.as_str().into()),
+            ],
+            Command::Revert { action, target } => vec![("action", action.as_str().into()), ("target", target.as_str().into())],
+            Command::Cleanup | Command::DetectCpu | Command::DetectMemory | Command::DetectDisk | Command::DetectGpu
+            | Command::DetectNetwork | Command::DetectOs | Command::DetectKernel | Command::DetectBios
+            | Command::UpdateInitramfs | Command::UpdateGrub | Command::GenerateFstab | Command::UpdateSystem
+            | Command::SyncTime | Command::BenchmarkCpu | Command::BenchmarkMemory | Command::BenchmarkDisk
+            | Command::ScanHardware | Command::DetectDrivers | Command::GitSubmoduleUpdate | Command::Sensors
+            | Command::HardenSsh | Command::SpeedTest => Vec::new(),
+        }
+    }
+}
+
+/// Правдоподобная ошибка команды для --chaos; None — команда не может «сломаться»
+/// (вывод текста, паузы, вопросы, управляющие блоки)
+pub(crate) fn failure_message(cmd: &Command, lang: Lang) -> Option<String> {
+    let message = match cmd {
+        Command::Download { url, .. } => Msg::ChaosDownload.fill(lang, &[url]),
+        Command::Extract { from, .. } => format!("{}: unexpected end of archive (gzip: stdin: unexpected end of file)", from),
+        Command::InstallDep { name, version } => Msg::ChaosInstallDep.fill(lang, &[name, version]),
+        Command::Configure { key, .. } => Msg::ChaosConfigure.fill(lang, &[key]),
+        Command::CreateDir(path) => format!("mkdir: cannot create directory '{}': Read-only file system", path),
+        Command::CopyFile { to, .. } => format!("cp: error writing '{}': No space left on device", to),
+        Command::Symlink { to, .. } => format!("ln: failed to create symbolic link '{}': File exists", to),
+        Command::SetPermission { path, .. } => format!("chmod: changing permissions of '{}': Operation not permitted", path),
+        Command::WriteConfig { path, .. } => format!("{}: Permission denied", path),
+        Command::RunScript(script) => Msg::ChaosScript.fill(lang, &[script]),
+        Command::CheckDep(dep) => format!("{}: command not found", dep),
+        Command::DetectCpu | Command::DetectMemory | Command::DetectDisk | Command::DetectGpu | Command::DetectNetwork
+        | Command::DetectOs | Command::DetectKernel | Command::DetectBios | Command::ScanHardware
+        | Command::DetectDrivers | Command::Sensors => "ACPI BIOS Error (bug): Could not resolve symbol [\\_SB.PCI0], AE_NOT_FOUND".to_string(),
+        Command::RunTest { name, .. } | Command::TestHardware(name) => Msg::ChaosTest.fill(lang, &[name]),
+        Command::LoadKernelModule(module) => format!("modprobe: ERROR: could not insert '{}': Unknown symbol in module", module),
+        Command::UnloadKernelModule(module) => format!("modprobe: FATAL: Module {} is in use", module),
+        Command::UpdateInitramfs => "mkinitcpio: ERROR: failed to generate image '/boot/initramfs-linux.img'".to_string(),
+        Command::UpdateGrub | Command::InstallBootloader(_) => "grub-install: error: cannot find EFI directory".to_string(),
+        Command::MountPartition { device, mount_point } => format!("mount: {}: can't read superblock on {}", mount_point, device),
+        Command::UnmountPartition(path) => format!("umount: {}: target is busy", path),
+        Command::FormatPartition { device, fs_type } => format!("mkfs.{}: I/O error while writing inode tables on {}", fs_type, device),
+        Command::CreatePartition { device, .. } => format!("parted: Error: Partition(s) on {} are being used", device),
+        Command::SetHostname(_) => "hostnamectl: Could not set hostname: Connection timed out".to_string(),
+        Command::SetTimezone(_) => "timedatectl: Failed to set time zone: Access denied".to_string(),
+        Command::SetLocale(_) | Command::GenerateLocales(_) => "locale-gen: I/O error writing /usr/lib/locale/locale-archive".to_string(),
+        Command::SetKeymap(_) => "localectl: Failed to set keymap: Connection timed out".to_string(),
+        Command::CreateSnapshot(name) => Msg::ChaosSnapshot.fill(lang, &[name]),
+        Command::RollbackTo(name) => Msg::ChaosRollback.fill(lang, &[name]),
+        Command::CreateUser { .. } => "useradd: cannot lock /etc/passwd; try again later.".to_string(),
+        Command::SetPassword(_) => "passwd: Authentication token manipulation error".to_string(),
+        Command::EnableService(service) | Command::StartService(service) => format!(
+            "Job for {}.service failed because the control process exited with error code.", service),
+        Command::DisableService(service) | Command::StopService(service) => format!("Failed to stop {}.service: Unit is masked.", service),
+        Command::GenerateFstab => "genfstab: failed to read /proc/self/mountinfo".to_string(),
+        Command::CheckIntegrity(file) | Command::Checksum { file, .. } => Msg::ChaosChecksum.fill(lang, &[file]),
+        Command::VerifySignature(file) => format!("gpg: {}: BAD signature", file),
+        Command::CompileKernel { .. } => "make: *** [Makefile:1234: vmlinux] Error 2".to_string(),
+        Command::Compile { name, .. } => format!("{}: error: linker command failed with exit code 1", name),
+        Command::InstallPackages(_) | Command::UpdateSystem | Command::InstallDriver(_) => {
+            "error: failed to commit transaction (conflicting files)".to_string()
+        }
+        Command::InstallLangDeps { .. } => "ERR! network request failed: ETIMEDOUT".to_string(),
+        Command::AddRepo { name, .. } => Msg::ChaosRepo.fill(lang, &[name]),
+        Command::ImportKey(_) => "gpg: keyserver receive failed: No route to host".to_string(),
+        Command::SelectMirror { .. } => Msg::ChaosMirrors.text(lang).to_string(),
+        Command::SyncTime => "ntpd: no server suitable for synchronization found".to_string(),
+        Command::BenchmarkCpu | Command::BenchmarkMemory | Command::BenchmarkDisk => Msg::ChaosBenchmark.text(lang).to_string(),
+        Command::NetworkConfig { interface, .. } => format!("{}: link is not ready", interface),
+        Command::FirewallRule(_) => "iptables: Resource temporarily unavailable.".to_string(),
+        Command::DockerPull { .. } => "Error response from daemon: Get \"https://registry-1.docker.io/v2/\": net/http: TLS handshake timeout".to_string(),
+        Command::GitClone { url, .. } => format!("fatal: unable to access '{}': Could not resolve host", url),
+        Command::GitSubmoduleUpdate => "fatal: clone of submodule failed".to_string(),
+        Command::SmartReport { device, .. } => format!("{}: SMART overall-health self-assessment test result: FAILED!", device),
+        Command::CreateRaid { devices, .. } => format!("mdadm: cannot open {}: Device or resource busy", devices.first().map(String::as_str).unwrap_or("/dev/sda")),
+        Command::CreatePool { name, .. } => format!("cannot create '{}': one or more devices is currently unavailable", name),
+        Command::CreateSubvolume(path) => format!("ERROR: cannot create subvolume '{}': No such file or directory", path),
+        Command::SetupSwap { path, .. } => format!("swapon: {}: swapon failed: Invalid argument", path),
+        Command::SetKernelParam { key, .. } => format!("sysctl: cannot stat /proc/sys/{}: No such file or directory", key.replace('.', "/")),
+        Command::SetCmdline(_) => Msg::ChaosCmdline.text(lang).to_string(),
+        Command::CreateVm { name, .. } => format!("error: Failed to start domain '{}': internal error: process exited while connecting to monitor", name),
+        Command::StartContainer(_) => "Error response from daemon: driver failed programming external connectivity".to_string(),
+        Command::GenerateCert { domain, .. } => format!("certbot: Challenge failed for domain {}", domain),
+        Command::HardenSsh => "sshd: /etc/ssh/sshd_config: line 1: Bad configuration option".to_string(),
+        Command::GenerateSshKey { .. } => "ssh-keygen: Saving key failed: Permission denied".to_string(),
+        Command::ProvisionCloud { .. } => "Error: creating resource: RequestLimitExceeded: Request limit exceeded.".to_string(),
+        Command::RunPlaybook { .. } => "fatal: [host-1]: UNREACHABLE! => ssh: connect to host port 22: Connection timed out".to_string(),
+        Command::InitDatabase { engine, .. } => format!("{}: could not bind IPv4 address: Address already in use", engine),
+        Command::RunMigrations { .. } => "migration failed: deadlock detected".to_string(),
+        Command::SpeedTest => "speedtest: Cannot retrieve speedtest configuration".to_string(),
+        Command::UpdateFirmware { component, .. } => format!("fwupdmgr: {}: failed to write firmware: device busy", component),
+        Command::WaitFor { what, .. } => Msg::ChaosWait.fill(lang, &[what]),
+        Command::CheckSpace { path, .. } => format!("df: {}: Input/output error", path),
+        Command::EncryptPartition { device, .. } => format!("cryptsetup: Cannot wipe header on device {}", device),
+        Command::Revert { target, .. } => format!("{}: Device or resource busy", target),
+        Command::Message(_) | Command::Delay(_) | Command::Progress(_) | Command::Cleanup | Command::Success(_)
+        | Command::Error { .. } | Command::Warning(_) | Command::Reboot { .. } | Command::TypeText { .. }
+        | Command::LogTail { .. } | Command::Fail { .. } | Command::FailRandom { .. } | Command::SystemdBoot { .. }
+        | Command::ShowPartitions(_) | Command::ShowLicense { .. } | Command::Retry { .. } | Command::Parallel(_)
+        | Command::Table { .. } | Command::Ask { .. } | Command::Choose { .. } => return None,
+    };
+    Some(message)
+}
+
+/// Примерная длительность команды в мс при --speed 1 для оценки оставшегося времени.
+/// Где длительность задана параметрами (delay, download, run_test, log_tail ...), она считается
+/// по ним, для остальных команд взято типичное время по замерам
+pub(crate) fn estimated_ms(cmd: &Command) -> u64 {
+    match cmd {
+        Command::Message(_) | Command::Success(_) | Command::Error { .. } | Command::Warning(_)
+        | Command::Progress(_) | Command::Fail { .. } | Command::FailRandom { .. } => 0,
+        Command::Delay(ms) => *ms,
+        // Порции по 10..50 байт раз в 20..60 мс
+        Command::Download { size, .. } => size * 4 / 3,
+        Command::RunTest { duration, .. } => *duration,
+        Command::Reboot { delay_secs } => delay_secs * 1000 + 3000,
+        Command::TypeText { text, cps, .. } => text.chars().count() as u64 * 1000 / (*cps).max(1),
+        Command::LogTail { lines, .. } => lines * 90,
+        Command::Compile { files, jobs, .. } => 1000 + files * 70 / (*jobs).max(1),
+        Command::SystemdBoot { units, .. } => units * 140,
+        Command::RunMigrations { count } => count * 140,
+        Command::ProvisionCloud { resources, .. } => resources * 600,
+        Command::RunPlaybook { hosts, .. } => hosts * 1000,
+        Command::WaitFor { timeout_secs, expect_timeout, .. } => {
+            if *expect_timeout { timeout_secs * 1000 } else { timeout_secs * 600 }
+        }
+        Command::InstallPackages(packages) => packages.split_whitespace().count() as u64 * 800,
+        Command::Retry { body, .. } => body.iter().map(estimated_ms).sum(),
+        Command::Parallel(children) => children.iter().map(estimated_ms).max().unwrap_or(0),
+        Command::UpdateFirmware { .. } | Command::SpeedTest | Command::CompileKernel { .. } => 6000,
+        Command::GitClone { .. } | Command::CreateRaid { .. } => 4000,
+        Command::UpdateSystem | Command::CreateVm { .. } | Command::GenerateCert { .. } | Command::InitDatabase { .. }
+        | Command::InstallLangDeps { .. } | Command::FormatPartition { .. } => 2200,
+        Command::CheckIntegrity(_) | Command::InstallDep { .. } | Command::InstallDriver(_) | Command::BenchmarkCpu
+        | Command::BenchmarkMemory | Command::BenchmarkDisk | Command::UpdateInitramfs | Command::InstallBootloader(_)
+        | Command::ScanHardware | Command::TestHardware(_) | Command::CreatePool { .. } | Command::GitSubmoduleUpdate => 1500,
+        Command::Configure { .. } | Command::WriteConfig { .. } | Command::Symlink { .. } | Command::SetPermission { .. }
+        | Command::CopyFile { .. } | Command::SetLocale(_) | Command::SetTimezone(_) | Command::SetHostname(_)
+        | Command::FirewallRule(_) | Command::CreateDir(_) => 100,
+        _ => 400,
+    }
+}
+
+/// «~2м 10с» для оценки оставшегося времени
+pub fn format_eta(d: Duration, lang: Lang) -> String {
+    let secs = d.as_secs();
+    if secs < 60 {
+        Msg::EtaSeconds.fill(lang, &[&secs])
+    } else if secs < 3600 {
+        Msg::EtaMinutes.fill(lang, &[&(secs / 60), &format!("{:02}", secs % 60)])
+    } else {
+        Msg::EtaHours.fill(lang, &[&(secs / 3600), &format!("{:02}", secs % 3600 / 60)])
+    }
+}
+
+/// Обратная команда для --uninstall; None — отменять нечего (вывод, проверки, детекция)
+pub(crate) fn invert(cmd: &Command, lang: Lang) -> Option<Command> {
+    let revert = |action: Msg, target: &str| Command::Revert { action: action.text(lang).to_string(), target: target.to_string() };
+    let inverse = match cmd {
+        Command::CreateDir(path) => revert(Msg::RemovingDir, path),
+        Command::CopyFile { to, .. } | Command::WriteConfig { path: to, .. } => revert(Msg::RemovingFile, to),
+        Command::Symlink { to, .. } => revert(Msg::RemovingLink, to),
+        Command::Download { url, .. } => revert(Msg::RemovingDownload, url.rsplit('/').next().unwrap_or(url)),
+        Command::Extract { to, .. } => revert(Msg::RemovingExtracted, to),
+        Command::InstallDep { name, version } => revert(Msg::RemovingDep, &format!("{} (v{})", name, version)),
+        Command::InstallPackages(packages) | Command::InstallLangDeps { packages, .. } => revert(Msg::RemovingPackages, packages),
+        Command::InstallDriver(driver) => revert(Msg::RemovingDriver, driver),
+        Command::InstallBootloader(target) => revert(Msg::RemovingBootloader, target),
+        Command::CreateUser { username, .. } => revert(Msg::RemovingUser, username),
+        Command::CreateSnapshot(name) => revert(Msg::RemovingSnapshot, name),
+        Command::AddRepo { name, .. } => revert(Msg::RemovingRepo, name),
+        Command::DockerPull { image, .. } => revert(Msg::RemovingImage, image),
+        Command::GitClone { url, .. } => revert(Msg::RemovingClone, url),
+        Command::StartContainer(image) => revert(Msg::StoppingContainer, image),
+        Command::CreateVm { name, .. } => revert(Msg::RemovingVm, name),
+        Command::EnableService(service) => Command::DisableService(service.clone()),
+        Command::DisableService(service) => Command::EnableService(service.clone()),
+        Command::StartService(service) => Command::StopService(service.clone()),
+        Command::StopService(service) => Command::StartService(service.clone()),
+        Command::MountPartition { mount_point, .. } => Command::UnmountPartition(mount_point.clone()),
+        Command::LoadKernelModule(module) => Command::UnloadKernelModule(module.clone()),
+        Command::UnloadKernelModule(module) => Command::LoadKernelModule(module.clone()),
+        Command::Configure { .. } | Command::SetPermission { .. } | Command::RunScript(_) | Command::UpdateInitramfs
+        | Command::UpdateGrub | Command::UnmountPartition(_) | Command::FormatPartition { .. }
+        | Command::CreatePartition { .. } | Command::SetHostname(_) | Command::SetTimezone(_) | Command::SetLocale(_)
+        | Command::GenerateLocales(_) | Command::RollbackTo(_) | Command::SetKeymap(_) | Command::SetPassword(_)
+        | Command::GenerateFstab | Command::CompileKernel { .. } | Command::UpdateSystem | Command::NetworkConfig { .. }
+        | Command::FirewallRule(_) | Command::Compile { .. } | Command::GitSubmoduleUpdate | Command::CreateRaid { .. }
+        | Command::CreatePool { .. } | Command::CreateSubvolume(_) | Command::SetupSwap { .. }
+        | Command::SetKernelParam { .. } | Command::SetCmdline(_) | Command::GenerateCert { .. } | Command::HardenSsh
+        | Command::GenerateSshKey { .. } | Command::ProvisionCloud { .. } | Command::RunPlaybook { .. }
+        | Command::InitDatabase { .. } | Command::RunMigrations { .. } | Command::UpdateFirmware { .. }
+        | Command::SelectMirror { .. } | Command::ImportKey(_) | Command::EncryptPartition { .. } => {
+            revert(Msg::RevertingStep, &cmd.to_string())
+        }
+        Command::Message(_) | Command::Delay(_) | Command::Progress(_) | Command::Cleanup | Command::Success(_)
+        | Command::Error { .. } | Command::Warning(_) | Command::CheckDep(_) | Command::DetectCpu | Command::DetectMemory
+        | Command::DetectDisk | Command::DetectGpu | Command::DetectNetwork | Command::DetectOs | Command::DetectKernel
+        | Command::DetectBios | Command::RunTest { .. } | Command::CheckIntegrity(_) | Command::VerifySignature(_)
+        | Command::SyncTime | Command::TestHardware(_) | Command::BenchmarkCpu | Command::BenchmarkMemory
+        | Command::BenchmarkDisk | Command::ScanHardware | Command::DetectDrivers | Command::Reboot { .. }
+        | Command::TypeText { .. } | Command::LogTail { .. } | Command::Checksum { .. } | Command::Fail { .. }
+        | Command::SystemdBoot { .. } | Command::ShowPartitions(_) | Command::SmartReport { .. } | Command::Sensors
+        | Command::ShowLicense { .. } | Command::SpeedTest | Command::WaitFor { .. } | Command::CheckSpace { .. }
+        | Command::Retry { .. } | Command::Parallel(_) | Command::FailRandom { .. } | Command::Table { .. }
+        | Command::Ask { .. } | Command::Choose { .. } | Command::Revert { .. } => return None,
+    };
+    Some(inverse)
+}
+
+/// Команда в разобранном виде: имя варианта и параметры, `Download url="https://..." size=1024`
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.kind())?;
+        for (name, value) in self.fields() {
+            write!(f, " {}={}", name, value)?;
+        }
+        Ok(())
+    }
+}
+
+// ============== Таблица команд ==============
+
+/// Тип значения параметра команды
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParamType {
+    /// Строка в кавычках
+    Text,
+    /// Неотрицательное целое без кавычек
+    Number,
+    /// true или false
+    Bool,
+    /// Размер вида 20G или 512M
+    Size,
+    /// Несколько значений одной строкой: через пробел, у choose — через запятую, у table — через |
+    List,
+    /// Одно из перечисленных слов
+    Choice(&'static [&'static str]),
+}
+
+impl ParamType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ParamType::Text => "string",
+            ParamType::Number => "integer",
+            ParamType::Bool => "boolean",
+            ParamType::Size => "size",
+            ParamType::List => "list",
+            ParamType::Choice(_) => "enum",
+        }
+    }
+}
+
+/// Значение команды: позиционное в кавычках или `имя=значение`
+#[derive(Debug, Clone, Copy)]
+pub struct ParamSpec {
+    pub name: &'static str,
+    pub kind: ParamType,
+    /// Что берётся, если значения нет в строке
+    pub default: Option<&'static str>,
+    /// Без значения команда не разбирается или проверка (--validate) сообщает о нём
+    pub required: bool,
+}
+
+const fn arg(name: &'static str, kind: ParamType) -> ParamSpec {
+    ParamSpec { name, kind, default: None, required: true }
+}
+
+const fn param(name: &'static str, kind: ParamType, default: &'static str) -> ParamSpec {
+    ParamSpec { name, kind, default: Some(default), required: false }
+}
+
+const fn optional(name: &'static str, kind: ParamType) -> ParamSpec {
+    ParamSpec { name, kind, default: None, required: false }
+}
+
+/// Команда языка. По этой таблице parse_command разбирает строку, а instnoth schema
+/// описывает язык, поэтому описание не может разойтись с разбором
+pub struct CommandSpec {
+    pub keyword: &'static str,
+    /// Значения в кавычках без имени, по порядку
+    pub positional: &'static [ParamSpec],
+    pub params: &'static [ParamSpec],
+    /// Отдельные слова вроде required у ask
+    pub flags: &'static [&'static str],
+    /// После команды идёт блок `{ ... }` с вложенными командами
+    pub block: bool,
+    build: fn(&mut CommandArgs) -> Result<Command, String>,
+}
+
+const fn command(
+    keyword: &'static str,
+    positional: &'static [ParamSpec],
+    params: &'static [ParamSpec],
+    build: fn(&mut CommandArgs) -> Result<Command, String>,
+) -> CommandSpec {
+    CommandSpec { keyword, positional, params, flags: &[], block: false, build }
+}
+
+use ParamType::{Bool, List, Number, Size, Text};
+
+const DISTROS: ParamType = ParamType::Choice(&["apt", "dnf", "pacman", "zypper", "apk", "emerge"]);
+const KEY_TYPES: ParamType = ParamType::Choice(&["rsa", "ecdsa", "ed25519"]);
+
+/// Все команды. Порядок — порядок подсказок для опечаток при равном расстоянии
+pub const COMMANDS: &[CommandSpec] = &[
+    command("message", &[arg("text", Text)], &[], |a| Ok(Command::Message(a.quoted()?))),
+    command("delay", &[param("ms", Number, "100")], &[], |a| Ok(Command::Delay(a.number_arg()))),
+    command("progress", &[param("pct", Number, "0")], &[], |a| {
+        let pct: u32 = a.number_arg();
+        if pct > 100 {
+            a.issue(format!("progress {}: значение должно быть от 0 до 100", pct));
+        }
+        Ok(Command::Progress(pct.min(100) as u8))
+    }),
+    command("create_dir", &[arg("path", Text)], &[], |a| Ok(Command::CreateDir(a.quoted()?))),
+    command("download", &[arg("url", Text)], &[param("size", Number, "1024")], |a| {
+        let url = a.quoted()?;
+        Ok(Command::Download { url, size: a.number("size") })
+    }),
+    command("extract", &[arg("from", Text)], &[arg("to", Text)], |a| {
+        let from = a.quoted()?;
+        Ok(Command::Extract { from, to: a.text_or_default("to") })
+    }),
+    command("install_dep", &[arg("name", Text)], &[param("version", Text, "latest")], |a| {
+        let name = a.quoted()?;
+        Ok(Command::InstallDep { name, version: a.text_or_default("version") })
+    }),
+    command("configure", &[], &[arg("key", Text), optional("value", Text)], |a| {
+        let key = a.text_or_default("key");
+        Ok(Command::Configure { key, value: a.text_or_default("value") })
+    }),
+    command("cleanup", &[], &[], |_| Ok(Command::Cleanup)),
+    command("success", &[arg("text", Text)], &[], |a| Ok(Command::Success(a.quoted()?))),
+    command("error", &[arg("text", Text)], &[optional("fatal", Bool), param("exit", Number, "1")], |a| {
+        let message = a.quoted()?;
+        let fatal = a.boolean("fatal")?;
+        Ok(Command::Error { message, fatal, exit: a.exit_code("exit") })
+    }),
+    command("warning", &[arg("text", Text)], &[], |a| Ok(Command::Warning(a.quoted()?))),
+    command("copy_file", &[arg("from", Text)], &[arg("to", Text)], |a| {
+        let from = a.quoted()?;
+        Ok(Command::CopyFile { from, to: a.text_or_default("to") })
+    }),
+    command("symlink", &[arg("from", Text)], &[arg("to", Text)], |a| {
+        let from = a.quoted()?;
+        Ok(Command::Symlink { from, to: a.text_or_default("to") })
+    }),
+    command("set_permission", &[arg("path", Text)], &[param("mode", Text, "755")], |a| {
+        let path = a.quoted()?;
+        Ok(Command::SetPermission { path, mode: a.text_or_default("mode") })
+    }),
+    command("run_script", &[arg("script", Text)], &[], |a| Ok(Command::RunScript(a.quoted()?))),
+    command("check_dep", &[arg("dep", Text)], &[], |a| Ok(Command::CheckDep(a.quoted()?))),
+    command("write_config", &[arg("path", Text)], &[optional("content", Text)], |a| {
+        let path = a.quoted()?;
+        Ok(Command::WriteConfig { path, content: a.text_or_default("content") })
+    }),
+    command("detect_cpu", &[], &[], |_| Ok(Command::DetectCpu)),
+    command("detect_memory", &[], &[], |_| Ok(Command::DetectMemory)),
+    command("detect_disk", &[], &[], |_| Ok(Command::DetectDisk)),
+    command("detect_gpu", &[], &[], |_| Ok(Command::DetectGpu)),
+    command("detect_network", &[], &[], |_| Ok(Command::DetectNetwork)),
+    command("detect_os", &[], &[], |_| Ok(Command::DetectOs)),
+    command("detect_kernel", &[], &[], |_| Ok(Command::DetectKernel)),
+    command("detect_bios", &[], &[], |_| Ok(Command::DetectBios)),
+    command("run_test", &[arg("name", Text)], &[param("duration", Number, "1000")], |a| {
+        let name = a.quoted()?;
+        Ok(Command::RunTest { name, duration: a.number("duration") })
+    }),
+    command("load_module", &[arg("module", Text)], &[], |a| Ok(Command::LoadKernelModule(a.quoted()?))),
+    command("unload_module", &[arg("module", Text)], &[], |a| Ok(Command::UnloadKernelModule(a.quoted()?))),
+    command("update_initramfs", &[], &[], |_| Ok(Command::UpdateInitramfs)),
+    command("update_grub", &[], &[], |_| Ok(Command::UpdateGrub)),
+    command("mount", &[arg("device", Text)], &[arg("to", Text)], |a| {
+        let device = a.quoted()?;
+        Ok(Command::MountPartition { device, mount_point: a.text_or_default("to") })
+    }),
+    command("unmount", &[arg("mount_point", Text)], &[], |a| Ok(Command::UnmountPartition(a.quoted()?))),
+    command("format", &[arg("device", Text)], &[param("fs", Text, "ext4")], |a| {
+        let device = a.quoted()?;
+        Ok(Command::FormatPartition { device, fs_type: a.text_or_default("fs") })
+    }),
+    command("create_partition", &[arg("device", Text)], &[param("size", Text, "100%")], |a| {
+        let device = a.quoted()?;
+        Ok(Command::CreatePartition { device, size: a.text_or_default("size") })
+    }),
+    command("set_hostname", &[arg("hostname", Text)], &[], |a| Ok(Command::SetHostname(a.quoted()?))),
+    command("set_timezone", &[arg("timezone", Text)], &[], |a| Ok(Command::SetTimezone(a.quoted()?))),
+    command("set_locale", &[arg("locale", Text)], &[], |a| Ok(Command::SetLocale(a.quoted()?))),
+    command("create_user", &[arg("username", Text)], &[param("groups", Text, "users")], |a| {
+        let username = a.quoted()?;
+        Ok(Command::CreateUser { username, groups: a.text_or_default("groups") })
+    }),
+    command("set_password", &[arg("user", Text)], &[], |a| Ok(Command::SetPassword(a.quoted()?))),
+    command("enable_service", &[arg("service", Text)], &[], |a| Ok(Command::EnableService(a.quoted()?))),
+    command("disable_service", &[arg("service", Text)], &[], |a| Ok(Command::DisableService(a.quoted()?))),
+    command("start_service", &[arg("service", Text)], &[], |a| Ok(Command::StartService(a.quoted()?))),
+    command("stop_service", &[arg("service", Text)], &[], |a| Ok(Command::StopService(a.quoted()?))),
+    command("install_bootloader", &[arg("target", Text)], &[], |a| Ok(Command::InstallBootloader(a.quoted()?))),
+    command("generate_fstab", &[], &[], |_| Ok(Command::GenerateFstab)),
+    command("check_integrity", &[arg("target", Text)], &[], |a| Ok(Command::CheckIntegrity(a.quoted()?))),
+    command("verify_signature", &[arg("file", Text)], &[], |a| Ok(Command::VerifySignature(a.quoted()?))),
+    command("compile_kernel", &[arg("version", Text)], &[], |a| Ok(Command::CompileKernel { version: a.quoted()? })),
+    command("install_packages", &[arg("packages", List)], &[], |a| Ok(Command::InstallPackages(a.quoted()?))),
+    command("update_system", &[], &[], |_| Ok(Command::UpdateSystem)),
+    command("sync_time", &[], &[], |_| Ok(Command::SyncTime)),
+    command("test_hardware", &[arg("component", Text)], &[], |a| Ok(Command::TestHardware(a.quoted()?))),
+    command("benchmark_cpu", &[], &[], |_| Ok(Command::BenchmarkCpu)),
+    command("benchmark_memory", &[], &[], |_| Ok(Command::BenchmarkMemory)),
+    command("benchmark_disk", &[], &[], |_| Ok(Command::BenchmarkDisk)),
+    command("network_config", &[arg("interface", Text)], &[param("config", Text, "dhcp")], |a| {
+        let interface = a.quoted()?;
+        Ok(Command::NetworkConfig { interface, config: a.text_or_default("config") })
+    }),
+    command("firewall_rule", &[arg("rule", Text)], &[], |a| Ok(Command::FirewallRule(a.quoted()?))),
+    command("scan_hardware", &[], &[], |_| Ok(Command::ScanHardware)),
+    command("detect_drivers", &[], &[], |_| Ok(Command::DetectDrivers)),
+    command("install_driver", &[arg("driver", Text)], &[], |a| Ok(Command::InstallDriver(a.quoted()?))),
+    command("reboot", &[], &[param("delay", Number, "10")], |a| Ok(Command::Reboot { delay_secs: a.number("delay") })),
+    CommandSpec {
+        flags: &["required"],
+        ..command("ask", &[arg("prompt", Text)], &[param("default", ParamType::Choice(&["yes", "no"]), "yes")], |a| {
+            let prompt = a.quoted()?;
+            let answer = a.text_or_default("default");
+            let default = InstnothParser::parse_answer(&answer)
+                .ok_or_else(|| format!("ask: некорректное значение default=\"{}\" (ожидается yes или no)", answer))?;
+            Ok(Command::Ask { prompt, default, required: a.flag("required") })
+        })
+    },
+    command("choose", &[arg("prompt", Text)], &[arg("options", List), optional("default", Text), arg("var", Text)], |a| {
+        let prompt = a.quoted()?;
+        let options: Vec<String> = a.text_or_default("options")
+            .split(',')
+            .map(|o| o.trim().to_string())
+            .filter(|o| !o.is_empty())
+            .collect();
+        if options.is_empty() {
+            return Err("choose: отсутствует обязательный параметр options=".to_string());
+        }
+        // Без default= выбран первый вариант
+        let default = match a.text("default") {
+            Some(value) => options.iter().position(|o| *o == value)
+                .ok_or_else(|| format!("choose: default=\"{}\" нет среди options=", value))?,
+            None => 0,
+        };
+        Ok(Command::Choose { prompt, options, default, var: a.text_or_default("var") })
+    }),
+    command("type", &[arg("text", Text)], &[
+        param("speed", Number, "30"),
+        param("style", ParamType::Choice(&["message", "success", "warning", "error"]), "message"),
+    ], |a| {
+        let text = a.quoted()?;
+        let cps = a.number("speed");
+        if cps == 0 {
+            a.issue("type: speed= должна быть больше 0, используется 30".to_string());
+        }
+        let name = a.text_or_default("style");
+        let style = TextStyle::parse(&name).ok_or_else(|| format!(
+            "type: неизвестный style=\"{}\" (ожидается message, success, warning или error)", name
+        ))?;
+        Ok(Command::TypeText { text, cps: if cps == 0 { 30 } else { cps }, style })
+    }),
+    command("log_tail", &[], &[
+        param("lines", Number, "40"),
+        param("style", ParamType::Choice(&["gcc", "make", "dpkg", "journal"]), "gcc"),
+    ], |a| {
+        let lines = a.number("lines");
+        let name = a.text_or_default("style");
+        let style = LogStyle::parse(&name).ok_or_else(|| format!(
+            "log_tail: неизвестный style=\"{}\" (ожидается gcc, make, dpkg или journal)", name
+        ))?;
+        Ok(Command::LogTail { lines, style })
+    }),
+    // Краткая форма: table "Заголовок" "a|b|c" "1|2|3"; иначе строки — командами row ниже
+    command("table", &[arg("title", Text), optional("rows", List)], &[], |a| {
+        let mut literals = InstnothParser::literals(a.line)?.into_iter();
+        let title = literals.next()
+            .ok_or_else(|| format!("Не удалось извлечь значение из: {}", a.line))?;
+        let rows = literals.map(|row| InstnothParser::split_cells(&row)).collect();
+        Ok(Command::Table { title, rows })
+    }),
+    command("row", &[optional("cells", List)], &[], |_| Err("row: строка таблицы без предшествующей команды table".to_string())),
+    command("compile", &[arg("name", Text)], &[param("files", Number, "500"), param("jobs", Number, "4")], |a| {
+        let name = a.quoted()?;
+        let files = a.number("files");
+        let jobs = a.number("jobs");
+        if jobs == 0 {
+            a.issue("compile: jobs= должно быть больше 0, используется 1".to_string());
+        }
+        Ok(Command::Compile { name, files, jobs: jobs.max(1) })
+    }),
+    command("checksum", &[arg("file", Text)], &[param("algo", ParamType::Choice(&["md5", "sha1", "sha256", "sha512"]), "sha256")], |a| {
+        let file = a.quoted()?;
+        let name = a.text_or_default("algo");
+        let algo = ChecksumAlgo::parse(&name).ok_or_else(|| format!(
+            "checksum: неизвестный algo=\"{}\" (ожидается md5, sha1, sha256 или sha512)", name
+        ))?;
+        Ok(Command::Checksum { file, algo })
+    }),
+    command("fail", &[arg("message", Text)], &[param("code", Number, "1")], |a| {
+        let message = a.quoted()?;
+        Ok(Command::Fail { message, code: a.exit_code("code") })
+    }),
+    command("fail_random", &[arg("message", Text)], &[param("code", Number, "1"), param("chance", Number, "10")], |a| {
+        let message = a.quoted()?;
+        let code = a.exit_code("code");
+        let chance = a.number("chance");
+        if chance > 100 {
+            a.issue(format!("fail_random chance={}: значение должно быть от 0 до 100", chance));
+        }
+        Ok(Command::FailRandom { message, chance: chance.min(100) as u8, code })
+    }),
+    CommandSpec {
+        block: true,
+        ..command("retry", &[arg("attempts", Number)], &[], |_| Err("retry: ожидается блок вида `retry N {` ... `}`".to_string()))
+    },
+    command("docker_pull", &[arg("image", Text)], &[param("layers", Number, "5")], |a| {
+        let image = a.quoted()?;
+        Ok(Command::DockerPull { image, layers: a.number("layers") })
+    }),
+    command("git_clone", &[arg("url", Text)], &[param("objects", Number, "5000")], |a| {
+        let url = a.quoted()?;
+        Ok(Command::GitClone { url, objects: a.number("objects") })
+    }),
+    command("git_submodule_update", &[], &[], |_| Ok(Command::GitSubmoduleUpdate)),
+    command("systemd_boot", &[], &[param("units", Number, "25"), param("failures", Number, "0")], |a| {
+        let units = a.number("units");
+        let failures = a.number("failures");
+        if failures > units {
+            a.issue(format!("systemd_boot: failures={} больше units={}", failures, units));
+        }
+        Ok(Command::SystemdBoot { units, failures: failures.min(units) })
+    }),
+    command("show_partitions", &[arg("device", Text)], &[], |a| Ok(Command::ShowPartitions(a.quoted()?))),
+    command("smart_report", &[arg("device", Text)], &[param("fail_chance", Number, "2")], |a| {
+        let device = a.quoted()?;
+        let fail_chance = a.number("fail_chance");
+        if fail_chance > 100 {
+            a.issue(format!("smart_report fail_chance={}: значение должно быть от 0 до 100", fail_chance));
+        }
+        Ok(Command::SmartReport { device, fail_chance: fail_chance.min(100) as u8 })
+    }),
+    command("sensors", &[], &[], |_| Ok(Command::Sensors)),
+    command("create_raid", &[], &[param("level", Number, "1"), optional("devices", List)], |a| {
+        let level = a.number("level");
+        let devices: Vec<String> = a.text_or_default("devices").split_whitespace().map(str::to_string).collect();
+        let min_devices = match level {
+            0 | 1 => 2,
+            5 => 3,
+            6 | 10 => 4,
+            _ => return Err(format!("create_raid: неподдерживаемый level={} (ожидается 0, 1, 5, 6 или 10)", level)),
+        };
+        if devices.len() < min_devices {
+            a.warn(format!(
+                "create_raid: для RAID{} нужно не меньше {} устройств, указано {}",
+                level, min_devices, devices.len()
+            ));
+        } else if level == 10 && !devices.len().is_multiple_of(2) {
+            a.warn(format!("create_raid: для RAID10 нужно чётное число устройств, указано {}", devices.len()));
+        }
+        Ok(Command::CreateRaid { level: level as u8, devices })
+    }),
+    command("encrypt_partition", &[arg("device", Text)], &[param("cipher", Text, "aes-xts-plain64"), optional("name", Text)], |a| {
+        let device = a.quoted()?;
+        let cipher = a.text_or_default("cipher");
+        Ok(Command::EncryptPartition { device, cipher, name: a.text_or_default("name") })
+    }),
+    command("create_pool", &[arg("name", Text)], &[arg("devices", List), param("type", ParamType::Choice(&["zfs", "btrfs"]), "zfs")], |a| {
+        let name = a.quoted()?;
+        if name.is_empty() || name.contains(char::is_whitespace) || name.contains('/') {
+            return Err(format!("create_pool: некорректное имя пула \"{}\" (без пробелов и '/')", name));
+        }
+        let devices: Vec<String> = a.text_or_default("devices").split_whitespace().map(str::to_string).collect();
+        if devices.is_empty() {
+            return Err("create_pool: отсутствует обязательный параметр devices=".to_string());
+        }
+        let kind = match a.text_or_default("type").as_str() {
+            "zfs" => PoolKind::Zfs,
+            "btrfs" => PoolKind::Btrfs,
+            other => return Err(format!("create_pool: неизвестный type=\"{}\" (ожидается zfs или btrfs)", other)),
+        };
+        Ok(Command::CreatePool { name, devices, kind })
+    }),
+    command("create_subvolume", &[arg("path", Text)], &[], |a| {
+        let path = a.quoted()?;
+        if path.contains(char::is_whitespace) || !path.contains('/') {
+            return Err(format!("create_subvolume: ожидается путь вида \"пул/имя\", получено \"{}\"", path));
+        }
+        Ok(Command::CreateSubvolume(path))
+    }),
+    command("setup_swap", &[], &[param("size", Size, "2G"), param("path", Text, "/swapfile"), param("zram", Bool, "false")], |a| {
+        let size = a.size("size")?;
+        let path = a.text_or_default("path");
+        let zram = a.boolean("zram")? == Some(true);
+        Ok(Command::SetupSwap { size, path, zram })
+    }),
+    command("set_kernel_param", &[arg("key", Text), arg("value", Text)], &[], |a| {
+        let values = InstnothParser::literals(a.line)?;
+        match values.as_slice() {
+            [key, value] => Ok(Command::SetKernelParam { key: key.clone(), value: value.clone() }),
+            _ => Err("set_kernel_param: ожидается два значения: \"ключ\" \"значение\"".to_string()),
+        }
+    }),
+    command("set_cmdline", &[arg("cmdline", Text)], &[], |a| Ok(Command::SetCmdline(a.quoted()?))),
+    command("add_repo", &[arg("name", Text)], &[optional("flavor", DISTROS)], |a| {
+        let name = a.quoted()?;
+        let flavor = a.text("flavor")
+            .map(|v| Distro::parse(&v)
+                .ok_or_else(|| format!("add_repo: неизвестный flavor=\"{}\" (ожидается apt, dnf, pacman, zypper, apk или emerge)", v)))
+            .transpose()?;
+        Ok(Command::AddRepo { name, flavor })
+    }),
+    command("import_key", &[arg("key", Text)], &[], |a| {
+        let key = a.quoted()?;
+        let digits = key.strip_prefix("0x").or_else(|| key.strip_prefix("0X")).unwrap_or(&key);
+        if !(8..=40).contains(&digits.len()) || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("import_key: некорректный идентификатор ключа \"{}\" (ожидается от 8 до 40 шестнадцатеричных цифр)", key));
+        }
+        Ok(Command::ImportKey(key))
+    }),
+    command("install_pydeps", &[arg("packages", List)], &[], |a| {
+        Ok(Command::InstallLangDeps { manager: LangManager::Pip, packages: a.quoted()? })
+    }),
+    command("install_npmdeps", &[arg("packages", List)], &[], |a| {
+        Ok(Command::InstallLangDeps { manager: LangManager::Npm, packages: a.quoted()? })
+    }),
+    command("install_crates", &[arg("packages", List)], &[], |a| {
+        Ok(Command::InstallLangDeps { manager: LangManager::Cargo, packages: a.quoted()? })
+    }),
+    command("create_vm", &[arg("name", Text)], &[param("cpus", Number, "2"), param("memory", Size, "2G"), param("disk", Size, "20G")], |a| {
+        let name = a.quoted()?;
+        if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_') {
+            return Err(format!("create_vm: некорректное имя ВМ \"{}\" (буквы, цифры, '-' и '_')", name));
+        }
+        let cpus = a.number("cpus");
+        let memory = a.size("memory")?;
+        let disk = a.size("disk")?;
+        Ok(Command::CreateVm { name, cpus, memory, disk })
+    }),
+    command("start_container", &[arg("image", Text)], &[], |a| Ok(Command::StartContainer(a.quoted()?))),
+    command("generate_cert", &[arg("domain", Text)], &[param("days", Number, "365"), param("type", KEY_TYPES, "rsa"), param("wildcard", Bool, "false")], |a| {
+        let mut domain = a.quoted()?;
+        if domain.is_empty() || domain.contains(char::is_whitespace) {
+            return Err(format!("generate_cert: некорректный домен \"{}\"", domain));
+        }
+        let days = a.number("days");
+        if days == 0 {
+            return Err("generate_cert: days= должно быть больше нуля".to_string());
+        }
+        let name = a.text_or_default("type");
+        let key_type = KeyType::parse(&name)
+            .ok_or_else(|| format!("generate_cert: неизвестный type=\"{}\" (ожидается rsa, ecdsa или ed25519)", name))?;
+        if a.boolean("wildcard")? == Some(true) && !domain.starts_with("*.") {
+            domain = format!("*.{}", domain);
+        }
+        Ok(Command::GenerateCert { domain, days, key_type })
+    }),
+    command("harden_ssh", &[], &[], |_| Ok(Command::HardenSsh)),
+    command("init_database", &[arg("engine", Text)], &[optional("version", Text)], |a| {
+        let engine = a.quoted()?.to_lowercase();
+        if !["postgresql", "mysql", "sqlite", "mongodb"].contains(&engine.as_str()) {
+            a.warn(format!(
+                "init_database: неизвестная СУБД \"{}\" (известны postgresql, mysql, sqlite, mongodb), вывод будет общим",
+                engine
+            ));
+        }
+        Ok(Command::InitDatabase { engine, version: a.text_or_default("version") })
+    }),
+    command("run_migrations", &[], &[param("count", Number, "10")], |a| Ok(Command::RunMigrations { count: a.number("count") })),
+    // Идентификатор SPDX не нужен, если текст лицензии берётся из file=
+    command("show_license", &[optional("spdx", Text)], &[optional("file", Text)], |a| {
+        let id_or_path = match a.text("file") {
+            Some(path) => LicenseSource::File(path),
+            None => {
+                let id = a.quoted()
+                    .map_err(|_| "show_license: ожидается идентификатор SPDX в кавычках или file=\"путь\"".to_string())?;
+                if !LICENSE_EXCERPTS.iter().any(|(known, _)| known.eq_ignore_ascii_case(&id)) {
+                    let known: Vec<&str> = LICENSE_EXCERPTS.iter().map(|(k, _)| *k).collect();
+                    a.warn(format!(
+                        "show_license: текст лицензии \"{}\" не встроен (есть {}), будет показан только идентификатор",
+                        id, known.join(", ")
+                    ));
+                }
+                LicenseSource::Spdx(id)
+            }
+        };
+        Ok(Command::ShowLicense { id_or_path })
+    }),
+    command("speed_test", &[], &[], |_| Ok(Command::SpeedTest)),
+    command("select_mirror", &[], &[param("country", Text, "DE"), param("count", Number, "5")], |a| {
+        let country = a.text_or_default("country").to_uppercase();
+        if country.len() != 2 || !country.chars().all(|c| c.is_ascii_alphabetic()) {
+            return Err(format!("select_mirror: country=\"{}\" (ожидается двухбуквенный код страны, например DE)", country));
+        }
+        let count = a.number("count");
+        if count == 0 {
+            return Err("select_mirror: count= должно быть больше нуля".to_string());
+        }
+        Ok(Command::SelectMirror { country, count })
+    }),
+    command("update_firmware", &[arg("component", Text)], &[arg("version", Text)], |a| {
+        let component = a.quoted()?;
+        let version = a.text("version")
+            .ok_or("update_firmware: отсутствует обязательный параметр version=")?;
+        Ok(Command::UpdateFirmware { component, version })
+    }),
+    command("wait_for", &[arg("what", Text)], &[param("timeout", Number, "30"), param("expect", ParamType::Choice(&["success", "timeout"]), "success")], |a| {
+        let what = a.quoted()?;
+        let timeout_secs = a.number("timeout");
+        if timeout_secs == 0 {
+            return Err("wait_for: timeout= должно быть больше нуля".to_string());
+        }
+        let expect_timeout = match a.text_or_default("expect").as_str() {
+            "success" => false,
+            "timeout" => true,
+            other => return Err(format!("wait_for: expect=\"{}\" (ожидается success или timeout)", other)),
+        };
+        Ok(Command::WaitFor { what, timeout_secs, expect_timeout })
+    }),
+    command("check_space", &[arg("path", Text)], &[arg("need", Size), param("force_fail", Bool, "false")], |a| {
+        let path = a.quoted()?;
+        if !path.starts_with('/') {
+            return Err(format!("check_space: путь \"{}\" должен быть абсолютным", path));
+        }
+        let text = a.text("need")
+            .ok_or_else(|| "check_space: не указан need= (например, need=\"20G\")".to_string())?;
+        let need = parse_human_size(&text)
+            .ok_or_else(|| format!("check_space: некорректный need=\"{}\" (ожидается, например, 20G или 512M)", text))?;
+        let force_fail = a.boolean("force_fail")? == Some(true);
+        Ok(Command::CheckSpace { path, need, force_fail })
+    }),
+    command("provision_cloud", &[], &[arg("provider", ParamType::Choice(&["aws", "gcp", "azure"])), param("resources", Number, "5")], |a| {
+        let provider = match a.text("provider") {
+            None => return Err("provision_cloud: не указан provider= (aws, gcp или azure)".to_string()),
+            Some(v) => CloudProvider::parse(&v)
+                .ok_or_else(|| format!("provision_cloud: неизвестный provider=\"{}\" (ожидается aws, gcp или azure)", v))?,
+        };
+        let resources = a.number("resources");
+        if resources == 0 {
+            return Err("provision_cloud: resources= должно быть больше нуля".to_string());
+        }
+        Ok(Command::ProvisionCloud { provider, resources })
+    }),
+    command("run_playbook", &[arg("playbook", Text)], &[param("hosts", Number, "3")], |a| {
+        let playbook = a.quoted()?;
+        let hosts = a.number("hosts");
+        if hosts == 0 {
+            return Err("run_playbook: hosts= должно быть больше нуля".to_string());
+        }
+        Ok(Command::RunPlaybook { playbook, hosts })
+    }),
+    // bits= по умолчанию зависит от type=: 3072 у rsa, 256 у ecdsa, у ed25519 он фиксирован
+    command("generate_ssh_key", &[], &[param("type", KEY_TYPES, "ed25519"), optional("bits", Number), param("comment", Text, "root@localhost")], |a| {
+        let name = a.text_or_default("type");
+        let key_type = KeyType::parse(&name)
+            .ok_or_else(|| format!("generate_ssh_key: неизвестный type=\"{}\" (ожидается rsa, ecdsa или ed25519)", name))?;
+        let bits = match key_type {
+            KeyType::Rsa => {
+                let bits = a.number_or("bits", 3072);
+                if !(1024..=16384).contains(&bits) {
+                    return Err(format!("generate_ssh_key: bits={} для rsa должно быть от 1024 до 16384", bits));
+                }
+                bits
+            }
+            KeyType::Ecdsa => {
+                let bits = a.number_or("bits", 256);
+                if ![256, 384, 521].contains(&bits) {
+                    return Err(format!("generate_ssh_key: bits={} для ecdsa (ожидается 256, 384 или 521)", bits));
+                }
+                bits
+            }
+            KeyType::Ed25519 => {
+                if a.text("bits").is_some() {
+                    a.warn("generate_ssh_key: у ed25519 размер ключа фиксирован, bits= игнорируется".to_string());
+                }
+                256
+            }
+        };
+        Ok(Command::GenerateSshKey { key_type, bits, comment: a.text_or_default("comment") })
+    }),
+    command("generate_locales", &[arg("locales", List)], &[], |a| {
+        let locales: Vec<String> = a.quoted()?.split_whitespace().map(String::from).collect();
+        if locales.is_empty() {
+            return Err("generate_locales: список локалей пуст".to_string());
+        }
+        Ok(Command::GenerateLocales(locales))
+    }),
+    command("set_keymap", &[arg("keymap", Text)], &[], |a| Ok(Command::SetKeymap(a.quoted()?))),
+    command("create_snapshot", &[arg("name", Text)], &[], |a| Ok(Command::CreateSnapshot(a.quoted()?))),
+    command("rollback_to", &[arg("name", Text)], &[], |a| Ok(Command::RollbackTo(a.quoted()?))),
+    // Однострочная форма parallel { ... }; многострочный блок разбирает parse_nested_block
+    CommandSpec {
+        block: true,
+        ..command("parallel", &[], &[], |a| {
+            let line = a.line;
+            let inner = line.find('{').zip(line.rfind('}')).filter(|(open, close)| open < close)
+                .map(|(open, close)| &line[open + 1..close])
+                .ok_or_else(|| "parallel: ожидается блок `parallel { ... }`".to_string())?;
+            let mut children = Vec::new();
+            for segment in InstnothParser::split_inline_commands(inner) {
+                let cmd = a.parser.parse_command(segment, a.line_no)?;
+                if InstnothParser::parallel_safe(&cmd) {
+                    children.push(cmd);
+                } else {
+                    a.parser.error(a.line_no, InstnothParser::parallel_unsafe_message(segment));
+                }
+            }
+            Ok(Command::Parallel(children))
+        })
+    },
+];
+
+/// Описание команды по ключевому слову (в нижнем регистре)
+pub fn command_spec(keyword: &str) -> Option<&'static CommandSpec> {
+    COMMANDS.iter().find(|spec| spec.keyword == keyword)
+}
+
+/// instnoth schema: все команды с позиционными значениями, параметрами, их типами и
+/// значениями по умолчанию — JSON для редакторов и других инструментов
+pub fn command_schema() -> String {
+    fn object(fields: &[(&str, String)]) -> String {
+        let fields: Vec<String> = fields.iter().map(|(name, value)| format!("{}:{}", JsonValue::from(*name), value)).collect();
+        format!("{{{}}}", fields.join(","))
+    }
+    fn params(specs: &[ParamSpec]) -> String {
+        let items: Vec<String> = specs.iter().map(|p| {
+            let mut fields = vec![("name", JsonValue::from(p.name).to_string()), ("type", JsonValue::from(p.kind.name()).to_string())];
+            if let ParamType::Choice(values) = p.kind {
+                let values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+                fields.push(("values", JsonValue::from(values.as_slice()).to_string()));
+            }
+            if let Some(default) = p.default {
+                let value = match p.kind {
+                    ParamType::Number => JsonValue::Int(default.parse().expect("число по умолчанию")),
+                    ParamType::Bool => JsonValue::Bool(default == "true"),
+                    _ => JsonValue::from(default),
+                };
+                fields.push(("default", value.to_string()));
+            }
+            fields.push(("required", p.required.to_string()));
+            object(&fields)
+        }).collect();
+        format!("[{}]", items.join(","))
+    }
+
+    let commands: Vec<String> = COMMANDS.iter().map(|spec| {
+        let flags: Vec<String> = spec.flags.iter().map(|f| f.to_string()).collect();
+        format!("  {}", object(&[
+            ("keyword", JsonValue::from(spec.keyword).to_string()),
+            ("positional", params(spec.positional)),
+            ("params", params(spec.params)),
+            ("flags", JsonValue::from(flags.as_slice()).to_string()),
+            ("block", spec.block.to_string()),
+        ]))
+    }).collect();
+    format!("{{\"version\":1,\"commands\":[\n{}\n]}}\n", commands.join(",\n"))
+}
+
+/// Строка команды для конструктора из COMMANDS. Значения читаются только по описанию
+/// команды: значения по умолчанию — из таблицы, а параметр вне таблицы — ошибка в коде
+pub(crate) struct CommandArgs<'p> {
+    parser: &'p mut InstnothParser,
+    spec: &'static CommandSpec,
+    line: &'p str,
+    args: &'p str,
+    line_no: usize,
+}
+
+impl CommandArgs<'_> {
+    fn param(&self, name: &str) -> &'static ParamSpec {
+        self.spec.params.iter().chain(self.spec.positional).find(|p| p.name == name)
+            .unwrap_or_else(|| panic!("{}: параметр {} не описан в COMMANDS", self.spec.keyword, name))
+    }
+
+    /// Первое значение в кавычках
+    fn quoted(&self) -> Result<String, String> {
+        InstnothParser::extract_quoted_value(self.line)
+    }
+
+    fn text(&mut self, name: &str) -> Option<String> {
+        self.param(name);
+        self.parser.extract_string_param(self.args, name, self.line_no)
+    }
+
+    /// Значение или значение по умолчанию из таблицы; без него — пустая строка
+    fn text_or_default(&mut self, name: &str) -> String {
+        let default = self.param(name).default.unwrap_or("");
+        self.text(name).unwrap_or_else(|| default.to_string())
+    }
+
+    fn number(&mut self, name: &str) -> u64 {
+        let default = self.param(name).default.and_then(|d| d.parse().ok())
+            .unwrap_or_else(|| panic!("{}: у числового параметра {} нет значения по умолчанию", self.spec.keyword, name));
+        self.number_or(name, default)
+    }
+
+    // Для параметров, у которых значение по умолчанию зависит от других
+    fn number_or(&mut self, name: &str, default: u64) -> u64 {
+        self.param(name);
+        self.parser.number_param(self.spec.keyword, self.args, name, default, self.line_no)
+    }
+
+    fn exit_code(&mut self, name: &str) -> i32 {
+        self.param(name);
+        self.parser.exit_code_param(self.spec.keyword, self.args, name, self.line_no)
+    }
+
+    /// Число сразу после ключевого слова (delay 500, progress 50)
+    fn number_arg<T: std::str::FromStr>(&mut self) -> T {
+        let default = self.spec.positional[0].default.and_then(|d| d.parse().ok())
+            .unwrap_or_else(|| panic!("{}: у числа нет значения по умолчанию", self.spec.keyword));
+        self.parser.number_arg(self.spec.keyword, self.args, default, self.line_no)
+    }
+
+    /// true/false; без значения в строке — значение по умолчанию из таблицы, если оно есть
+    fn boolean(&mut self, name: &str) -> Result<Option<bool>, String> {
+        let default = self.param(name).default;
+        match self.text(name).as_deref().or(default) {
+            None => Ok(None),
+            Some("true") => Ok(Some(true)),
+            Some("false") => Ok(Some(false)),
+            Some(other) => Err(format!("{}: {}=\"{}\" (ожидается true или false)", self.spec.keyword, name, other)),
+        }
+    }
+
+    fn size(&mut self, name: &str) -> Result<u64, String> {
+        let text = self.text_or_default(name);
+        parse_human_size(&text).ok_or_else(|| format!(
+            "{}: некорректный {}=\"{}\" (ожидается, например, 8G или 512M)", self.spec.keyword, name, text
+        ))
+    }
+
+    fn flag(&self, name: &str) -> bool {
+        assert!(self.spec.flags.contains(&name), "{}: флаг {} не описан в COMMANDS", self.spec.keyword, name);
+        InstnothParser::has_flag(self.args, name)
+    }
+
+    fn warn(&mut self, message: String) {
+        self.parser.warn(self.line_no, message);
+    }
+
+    fn issue(&mut self, message: String) {
+        self.parser.validation_issue(self.line_no, message);
+    }
+}
+
+// ============== Парсер ==============
+
+pub(crate) const HEADER_KEYS: &[&str] = &[
+    "package", "version", "description", "author", "license", "homepage", "size", "arch", "distro", "depends",
+    "provides", "conflicts", "on_error", "require_root",
+];
+
+/// Встроенные отрывки лицензий для show_license по идентификатору SPDX
+pub(crate) const LICENSE_EXCERPTS: &[(&str, &str)] = &[
+    ("GPL-3.0", "                    GNU GENERAL PUBLIC LICENSE
+                       Version 3, 29 June 2007
+
+ Copyright (C) 2007 Free Software Foundation, Inc. <https://fsf.org/>
+ Everyone is permitted to copy and distribute verbatim copies
+ of this license document, but changing it is not allowed.
+
+                            Preamble
+
+  The GNU General Public License is a free, copyleft license for
+software and other kinds of works.
+
+  The licenses for most software and other practical works are designed
+to take away your freedom to share and change the works.  By contrast,
+the GNU General Public License is intended to guarantee your freedom to
+share and change all versions of a program--to make sure it remains free
+software for all its users.  We, the Free Software Foundation, use the
+GNU General Public License for most of our software; it applies also to
+any other work released this way by its authors.  You can apply it to
+your programs, too.
+
+  When we speak of free software, we are referring to freedom, not
+price.  Our General Public Licenses are designed to make sure that you
+have the freedom to distribute copies of free software (and charge for
+them if you wish), that you receive source code or can get it if you
+want it, that you can change the software or use pieces of it in new
+free programs, and that you know you can do these things.
+
+  To protect your rights, we need to prevent others from denying you
+these rights or asking you to surrender the rights.  Therefore, you have
+certain responsibilities if you distribute copies of the software, or if
+you modify it: responsibilities to respect the freedom of others.
+
+                       TERMS AND CONDITIONS
+
+  0. Definitions.
+
+  \"This License\" refers to version 3 of the GNU General Public License."),
+    ("MIT", "MIT License
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the \"Software\"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED \"AS IS\", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE."),
+    ("Apache-2.0", "                                 Apache License
+                           Version 2.0, January 2004
+                        http://www.apache.org/licenses/
+
+   TERMS AND CONDITIONS FOR USE, REPRODUCTION, AND DISTRIBUTION
+
+   1. Definitions.
+
+      \"License\" shall mean the terms and conditions for use, reproduction,
+      and distribution as defined by Sections 1 through 9 of this document.
+
+      \"Licensor\" shall mean the copyright owner or entity authorized by
+      the copyright owner that is granting the License.
+
+      \"Legal Entity\" shall mean the union of the acting entity and all
+      other entities that control, are controlled by, or are under common
+      control with that entity. For the purposes of this definition,
+      \"control\" means (i) the power, direct or indirect, to cause the
+      direction or management of such entity, whether by contract or
+      otherwise, or (ii) ownership of fifty percent (50%) or more of the
+      outstanding shares, or (iii) beneficial ownership of such entity.
+
+      \"You\" (or \"Your\") shall mean an individual or Legal Entity
+      exercising permissions granted by this License."),
+    ("BSD-3-Clause", "BSD 3-Clause License
+
+Redistribution and use in source and binary forms, with or without
+modification, are permitted provided that the following conditions are met:
+
+1. Redistributions of source code must retain the above copyright notice, this
+   list of conditions and the following disclaimer.
+
+2. Redistributions in binary form must reproduce the above copyright notice,
+   this list of conditions and the following disclaimer in the documentation
+   and/or other materials provided with the distribution.
+
+3. Neither the name of the copyright holder nor the names of its
+   contributors may be used to endorse or promote products derived from
+   this software without specific prior written permission.
+
+THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS \"AS IS\"
+AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+DISCLAIMED."),
+];
+
+/// Откуда show_license берёт текст
+#[derive(Debug, Clone, PartialEq)]
+pub enum LicenseSource {
+    /// Идентификатор SPDX из LICENSE_EXCERPTS
+    Spdx(String),
+    /// Путь к файлу; относительный считается от файла пакета
+    File(String),
+}
+
+/// Оформление текста, общее для message/success/warning/error и type
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextStyle {
+    Message,
+    Success,
+    Warning,
+    Error,
+}
+
+impl TextStyle {
+    pub(crate) fn parse(s: &str) -> Option<Self> {
+        match s {
+            "message" => Some(TextStyle::Message),
+            "success" => Some(TextStyle::Success),
+            "warning" => Some(TextStyle::Warning),
+            "error" => Some(TextStyle::Error),
+            _ => None,
+        }
+    }
+
+    /// Цвет стиля в теме; у message цвета нет
+    fn color(&self, theme: &Theme) -> Option<Color> {
+        match self {
+            TextStyle::Message => None,
+            TextStyle::Success => theme.success,
+            TextStyle::Warning => theme.warning,
+            TextStyle::Error => theme.error,
+        }
+    }
+
+    pub(crate) fn symbol(&self, theme: &Theme) -> ColoredString {
+        match self {
+            TextStyle::Message => "→".dimmed(),
+            TextStyle::Success => "✓".paint(self.color(theme)).bold(),
+            TextStyle::Warning => "⚠".paint(self.color(theme)).bold(),
+            TextStyle::Error => "✗".paint(self.color(theme)).bold(),
+        }
+    }
+
+    pub(crate) fn paint(&self, text: &str, theme: &Theme) -> ColoredString {
+        text.paint(self.color(theme))
+    }
+}
+
+/// Вид фальшивого лога для log_tail
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogStyle {
+    Gcc,
+    Make,
+    Dpkg,
+    Journal,
+}
+
+impl LogStyle {
+    pub(crate) fn parse(s: &str) -> Option<Self> {
+        match s {
+            "gcc" => Some(LogStyle::Gcc),
+            "make" => Some(LogStyle::Make),
+            "dpkg" => Some(LogStyle::Dpkg),
+            "journal" => Some(LogStyle::Journal),
+            _ => None,
+        }
+    }
+}
+
+/// Алгоритм контрольной суммы для checksum
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChecksumAlgo {
+    Md5,
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl ChecksumAlgo {
+    pub(crate) fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "md5" => Some(ChecksumAlgo::Md5),
+            "sha1" => Some(ChecksumAlgo::Sha1),
+            "sha256" => Some(ChecksumAlgo::Sha256),
+            "sha512" => Some(ChecksumAlgo::Sha512),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            ChecksumAlgo::Md5 => "md5",
+            ChecksumAlgo::Sha1 => "sha1",
+            ChecksumAlgo::Sha256 => "sha256",
+            ChecksumAlgo::Sha512 => "sha512",
+        }
+    }
+
+    /// Длина суммы в шестнадцатеричных символах
+    pub(crate) fn hex_len(&self) -> usize {
+        match self {
+            ChecksumAlgo::Md5 => 32,
+            ChecksumAlgo::Sha1 => 40,
+            ChecksumAlgo::Sha256 => 64,
+            ChecksumAlgo::Sha512 => 128,
+        }
+    }
+}
+
+/// Действие, которое нужно выполнить позже, чтобы изменения вступили в силу
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum PendingAction {
+    UpdateGrub,
+    UpdateInitramfs,
+    Reboot,
+}
+
+impl PendingAction {
+    pub(crate) fn command(&self) -> &'static str {
+        match self {
+            PendingAction::UpdateGrub => "update_grub",
+            PendingAction::UpdateInitramfs => "update_initramfs",
+            PendingAction::Reboot => "reboot",
+        }
+    }
+}
+
+/// Файловая система пула для create_pool
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PoolKind {
+    Zfs,
+    Btrfs,
+}
+
+/// Пакетный менеджер для add_repo, import_key и заголовка distro:
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Distro {
+    Apt,
+    Dnf,
+    Pacman,
+    Zypper,
+    Apk,
+    Emerge,
+}
+
+impl Distro {
+    pub(crate) fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "apt" => Some(Distro::Apt),
+            "dnf" => Some(Distro::Dnf),
+            "pacman" => Some(Distro::Pacman),
+            "zypper" => Some(Distro::Zypper),
+            "apk" => Some(Distro::Apk),
+            "emerge" => Some(Distro::Emerge),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Distro::Apt => "apt",
+            Distro::Dnf => "dnf",
+            Distro::Pacman => "pacman",
+            Distro::Zypper => "zypper",
+            Distro::Apk => "apk",
+            Distro::Emerge => "emerge",
+        }
+    }
+}
+
+/// Менеджер зависимостей языка для install_pydeps/install_npmdeps/install_crates
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LangManager {
+    Pip,
+    Npm,
+    Cargo,
+}
+
+impl LangManager {
+    /// Имена, из которых выбираются транзитивные зависимости
+    pub(crate) fn dependency_pool(&self) -> &'static [&'static str] {
+        match self {
+            LangManager::Pip => &[
+                "urllib3", "certifi", "idna", "charset-normalizer", "Werkzeug", "Jinja2", "itsdangerous",
+                "click", "blinker", "MarkupSafe", "six", "packaging", "typing-extensions", "python-dateutil",
+                "pytz", "attrs", "pyparsing", "tzdata", "platformdirs", "filelock", "colorama", "pydantic-core",
+            ],
+            LangManager::Npm => &[
+                "ms", "debug", "inherits", "safe-buffer", "mime-types", "mime-db", "depd", "statuses", "qs",
+                "bytes", "iconv-lite", "semver", "lodash", "chalk", "ansi-styles", "supports-color", "has-flag",
+                "color-convert", "escape-html", "encodeurl", "on-finished", "side-channel", "call-bind", "once",
+            ],
+            LangManager::Cargo => &[
+                "libc", "cfg-if", "memchr", "proc-macro2", "quote", "syn", "unicode-ident", "serde_derive",
+                "itoa", "ryu", "once_cell", "bitflags", "log", "autocfg", "smallvec", "lazy_static", "pin-project-lite",
+                "bytes", "futures-core", "tracing-core", "hashbrown", "equivalent", "indexmap", "scopeguard",
+            ],
+        }
+    }
+
+    /// Имя и версия из "flask==3.0.0", "express@4.18.2", "serde@1.0.193"
+    pub(crate) fn split_spec<'a>(&self, spec: &'a str) -> (&'a str, Option<&'a str>) {
+        let parts = match self {
+            LangManager::Pip => spec.split_once("==")
+                .or_else(|| spec.find(['<', '>', '!', '~', '=']).map(|i| (&spec[..i], ""))),
+            LangManager::Npm | LangManager::Cargo => spec.get(1..)
+                .and_then(|rest| rest.find('@'))
+                .map(|i| (&spec[..i + 1], &spec[i + 2..])),
+        };
+        match parts {
+            Some((name, version)) => (name, Some(version).filter(|v| !v.is_empty())),
+            None => (spec, None),
+        }
+    }
+}
+
+/// Тип ключа для generate_cert
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyType {
+    Rsa,
+    Ecdsa,
+    Ed25519,
+}
+
+impl KeyType {
+    pub(crate) fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "rsa" => Some(KeyType::Rsa),
+            "ecdsa" | "ec" => Some(KeyType::Ecdsa),
+            "ed25519" => Some(KeyType::Ed25519),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            KeyType::Rsa => "rsa",
+            KeyType::Ecdsa => "ecdsa",
+            KeyType::Ed25519 => "ed25519",
+        }
+    }
+}
+
+/// Облачный провайдер для provision_cloud
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CloudProvider {
+    Aws,
+    Gcp,
+    Azure,
+}
+
+impl CloudProvider {
+    pub(crate) fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "aws" => Some(CloudProvider::Aws),
+            "gcp" | "google" => Some(CloudProvider::Gcp),
+            "azure" | "azurerm" => Some(CloudProvider::Azure),
+            _ => None,
+        }
+    }
+
+    /// Имя провайдера в реестре Terraform: hashicorp/NAME
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            CloudProvider::Aws => "aws",
+            CloudProvider::Gcp => "google",
+            CloudProvider::Azure => "azurerm",
+        }
+    }
+
+    /// Ресурс виртуальной машины из resource_pool, который размножается через count
+    pub(crate) fn instance(&self) -> &'static str {
+        match self {
+            CloudProvider::Aws => "aws_instance.web",
+            CloudProvider::Gcp => "google_compute_instance.web",
+            CloudProvider::Azure => "azurerm_linux_virtual_machine.web",
+        }
+    }
+
+    /// Ресурсы в порядке создания: (адрес, начало идентификатора)
+    pub(crate) fn resource_pool(&self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            CloudProvider::Aws => &[
+                ("aws_vpc.main", "vpc-"), ("aws_internet_gateway.gw", "igw-"), ("aws_subnet.public", "subnet-"),
+                ("aws_route_table.public", "rtb-"), ("aws_security_group.web", "sg-"), ("aws_iam_role.app", "AROA"),
+                ("aws_s3_bucket.assets", "instnoth-assets-"), ("aws_db_instance.main", "db-"),
+                ("aws_instance.web", "i-"), ("aws_eip.web", "eipalloc-"), ("aws_lb.front", "lb-"),
+                ("aws_route53_record.www", "Z"),
+            ],
+            CloudProvider::Gcp => &[
+                ("google_compute_network.main", "projects/instnoth/global/networks/main"),
+                ("google_compute_subnetwork.public", "projects/instnoth/regions/europe-west1/subnetworks/public"),
+                ("google_compute_firewall.web", "projects/instnoth/global/firewalls/web"),
+                ("google_service_account.app", "projects/instnoth/serviceAccounts/app@instnoth.iam.gserviceaccount.com"),
+                ("google_storage_bucket.assets", "instnoth-assets"),
+                ("google_sql_database_instance.main", "projects/instnoth/instances/main"),
+                ("google_compute_instance.web", "projects/instnoth/zones/europe-west1-b/instances/web"),
+                ("google_compute_address.web", "projects/instnoth/regions/europe-west1/addresses/web"),
+                ("google_compute_forwarding_rule.front", "projects/instnoth/regions/europe-west1/forwardingRules/front"),
+                ("google_dns_record_set.www", "projects/instnoth/managedZones/main/rrsets/www.instnoth.dev./A"),
+            ],
+            CloudProvider::Azure => &[
+                ("azurerm_resource_group.main", "/resourceGroups/rg-main"),
+                ("azurerm_virtual_network.main", "/resourceGroups/rg-main/providers/Microsoft.Network/virtualNetworks/vnet-main"),
+                ("azurerm_subnet.public", "/resourceGroups/rg-main/providers/Microsoft.Network/virtualNetworks/vnet-main/subnets/public"),
+                ("azurerm_network_security_group.web", "/resourceGroups/rg-main/providers/Microsoft.Network/networkSecurityGroups/nsg-web"),
+                ("azurerm_storage_account.assets", "/resourceGroups/rg-main/providers/Microsoft.Storage/storageAccounts/instnothassets"),
+                ("azurerm_public_ip.web", "/resourceGroups/rg-main/providers/Microsoft.Network/publicIPAddresses/pip-web"),
+                ("azurerm_network_interface.web", "/resourceGroups/rg-main/providers/Microsoft.Network/networkInterfaces/nic-web"),
+                ("azurerm_linux_virtual_machine.web", "/resourceGroups/rg-main/providers/Microsoft.Compute/virtualMachines/vm-web"),
+                ("azurerm_postgresql_flexible_server.main", "/resourceGroups/rg-main/providers/Microsoft.DBforPostgreSQL/flexibleServers/psql-main"),
+                ("azurerm_lb.front", "/resourceGroups/rg-main/providers/Microsoft.Network/loadBalancers/lb-front"),
+            ],
+        }
+    }
+}
+
+/// Как читать значение параметра `name=`
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ParamKind {
+    Number,
+    Text,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub line: usize,
+    pub severity: Severity,
+    pub message: String,
+}
+
+pub struct InstnothParser {
+    pub(crate) content: String,
+    pub(crate) file_path: Option<PathBuf>,
+    pub strict: bool,
+    /// Режим --validate: нарушения проверки значений считаются ошибками
+    pub validate: bool,
+    pub max_delay: u64,
+    pub diagnostics: Vec<Diagnostic>,
+    /// Группы команд `define имя { ... }`, уже разобранные к текущей строке
+    definitions: HashMap<String, Definition>,
+    /// Все имена из `define` в файле со строками объявления (для сообщений об use до define)
+    declared_definitions: HashMap<String, usize>,
+    /// Параметры, которых не оказалось в строке команды, по номерам строк
+    defaulted: HashMap<usize, Vec<String>>,
+}
+
+struct Definition {
+    line: usize,
+    commands: Vec<(Command, usize)>,
+}
+
+impl InstnothParser {
+    pub fn new(content: String) -> Self {
+        Self {
+            content,
+            file_path: None,
+            strict: false,
+            validate: false,
+            max_delay: 60_000,
+            diagnostics: Vec::new(),
+            definitions: HashMap::new(),
+            declared_definitions: HashMap::new(),
+            defaulted: HashMap::new(),
+        }
+    }
+
+    pub fn with_path(content: String, path: PathBuf) -> Self {
+        Self {
+            content,
+            file_path: Some(path),
+            strict: false,
+            validate: false,
+            max_delay: 60_000,
+            diagnostics: Vec::new(),
+            definitions: HashMap::new(),
+            declared_definitions: HashMap::new(),
+            defaulted: HashMap::new(),
+        }
+    }
+
+    fn warn(&mut self, line: usize, message: String) {
+        self.diagnostics.push(Diagnostic { line, severity: Severity::Warning, message });
+    }
+
+    fn error(&mut self, line: usize, message: String) {
+        self.diagnostics.push(Diagnostic { line, severity: Severity::Error, message });
+    }
+
+    // Нарушение, которое в строгом режиме становится ошибкой
+    fn strict_violation(&mut self, line: usize, message: String) {
+        let severity = if self.strict { Severity::Error } else { Severity::Warning };
+        self.diagnostics.push(Diagnostic { line, severity, message });
+    }
+
+    // Некорректное значение команды: ошибка в режиме --validate
+    fn validation_issue(&mut self, line: usize, message: String) {
+        let severity = if self.validate { Severity::Error } else { Severity::Warning };
+        self.diagnostics.push(Diagnostic { line, severity, message });
+    }
+
+    /// Разбирает файл целиком. Err называет все ошибки с номерами строк; предупреждения
+    /// остаются в diagnostics и разбору не мешают
+    ///
+    /// ```
+    /// use instnoth::parser::{Command, InstnothParser};
+    ///
+    /// let mut parser = InstnothParser::new("package: \"demo\"\nphase \"Загрузка\" {\n    download \"https://example.com/demo.tar\" size=2048\n}\n".to_string());
+    /// let package = parser.parse().unwrap();
+    /// assert_eq!(package.phases[0].commands, [Command::Download { url: "https://example.com/demo.tar".to_string(), size: 2048 }]);
+    ///
+    /// let error = InstnothParser::new("phase \"Без имени\" {\n}\n".to_string()).parse().unwrap_err();
+    /// assert_eq!(error, "Не указано имя пакета");
+    /// ```
+    pub fn parse(&mut self) -> Result<Package, String> {
+        let package = self.parse_package();
+
+        let errors: Vec<String> = self.diagnostics.iter()
+            .filter(|d| d.severity == Severity::Error)
+            .map(|d| format!("строка {}: {}", d.line, d.message))
+            .collect();
+        if !errors.is_empty() {
+            return Err(errors.join("; "));
+        }
+
+        if package.name.is_empty() {
+            return Err("Не указано имя пакета".to_string());
+        }
+
+        Ok(package)
+    }
+
+    // Разбор без прерывания на ошибках: все проблемы собираются в diagnostics.
+    // Файл с первой строкой #!instnoth-toml или расширением .instnoth.toml — в формате TOML
+    pub fn parse_package(&mut self) -> Package {
+        if toml_format::is_toml(&self.content, self.file_path.as_deref()) {
+            return toml_format::parse_package(self);
+        }
+        self.parse_text()
+    }
+
+    pub(crate) fn parse_text(&mut self) -> Package {
+        let mut package = Package {
+            name: String::new(),
+            version: String::new(),
+            description: String::new(),
+            author: String::new(),
+            license: String::new(),
+            homepage: String::new(),
+            size: String::new(),
+            arch: String::new(),
+            distro: None,
+            abort_on_error: false,
+            require_root: false,
+            depends: Vec::new(),
+            provides: Vec::new(),
+            conflicts: Vec::new(),
+            phases: Vec::new(),
+            file_path: self.file_path.clone(),
+            defaulted: HashMap::new(),
+        };
+
+        let content = self.content.clone();
+        let lines: Vec<&str> = content.lines().collect();
+        let mut seen_headers: HashMap<&str, usize> = HashMap::new();
+        let mut seen_phases: HashMap<String, (usize, usize)> = HashMap::new();
+        let mut i = 0;
+
+        self.definitions.clear();
+        self.defaulted.clear();
+        self.declared_definitions = lines.iter()
+            .enumerate()
+            .filter_map(|(n, l)| {
+                let name = l.trim().strip_prefix("define ")?.split('{').next()?.trim();
+                Some((name.to_string(), n + 1))
+            })
+            .collect();
+
+        while i < lines.len() {
+            let line = Self::strip_comment(lines[i]).trim();
+            let line_no = i + 1;
+
+            if line.is_empty() || line.starts_with('#') {
+                i += 1;
+                continue;
+            }
+
+            let header_key = HEADER_KEYS
+                .iter()
+                .copied()
+                .find(|key| line.strip_prefix(key).is_some_and(|rest| rest.starts_with(':')));
+
+            if let Some(key) = header_key {
+                if let Some(first) = seen_headers.insert(key, line_no) {
+                    let note = if self.strict { "" } else { ", используется последнее значение" };
+                    self.strict_violation(line_no, format!(
+                        "поле {}: указано повторно (строки {} и {}){}",
+                        key, first, line_no, note
+                    ));
+                }
+            }
+
+            let header = if line.starts_with("package:") {
+                Self::extract_quoted_value(line).map(|v| package.name = v)
+            } else if line.starts_with("version:") {
+                Self::extract_quoted_value(line).map(|v| package.version = v)
+            } else if line.starts_with("description:") {
+                Self::extract_quoted_value(line).map(|v| package.description = v)
+            } else if line.starts_with("author:") {
+                Self::extract_quoted_value(line).map(|v| package.author = v)
+            } else if line.starts_with("license:") {
+                Self::extract_quoted_value(line).map(|v| package.license = v)
+            } else if line.starts_with("homepage:") {
+                Self::extract_quoted_value(line).map(|v| package.homepage = v)
+            } else if line.starts_with("arch:") {
+                Self::extract_quoted_value(line).map(|v| package.arch = v)
+            } else if line.starts_with("distro:") {
+                Self::extract_quoted_value(line).and_then(|v| {
+                    let distro = Distro::parse(&v)
+                        .ok_or_else(|| format!("distro: неизвестное значение \"{}\" (ожидается apt, dnf, pacman, zypper, apk или emerge)", v))?;
+                    package.distro = Some(distro);
+                    Ok(())
+                })
+            } else if line.starts_with("on_error:") {
+                Self::extract_quoted_value(line).and_then(|v| {
+                    package.abort_on_error = match v.as_str() {
+                        "continue" => false,
+                        "abort" => true,
+                        _ => return Err(format!("on_error: неизвестное значение \"{}\" (ожидается continue или abort)", v)),
+                    };
+                    Ok(())
+                })
+            } else if let Some(value) = line.strip_prefix("require_root:") {
+                // Логическое значение можно писать без кавычек: require_root: true
+                let value = Self::extract_quoted_value(line).unwrap_or_else(|_| value.trim().to_string());
+                match value.as_str() {
+                    "true" | "false" => {
+                        package.require_root = value == "true";
+                        Ok(())
+                    }
+                    _ => Err(format!("require_root: неизвестное значение \"{}\" (ожидается true или false)", value)),
+                }
+            } else if line.starts_with("size:") {
+                Self::extract_quoted_value(line).map(|v| {
+                    if parse_human_size(&v).is_none() {
+                        self.warn(line_no, format!("size: не удалось разобрать размер \"{}\" (ожидается, например, \"1.2 GB\")", v));
+                    }
+                    package.size = v;
+                })
+            } else if let Some(deps_str) = line.strip_prefix("depends:") {
+                // Парсим зависимости: depends: "file1.instnoth" "file2.instnoth >= 1.2"
+                // или depends: "file1.instnoth, file2.instnoth"
+                Self::parse_depends(deps_str)
+                    .and_then(|specs| {
+                        specs.iter()
+                            .map(|d| Self::parse_dependency(d, line_no))
+                            .collect::<Result<_, _>>()
+                    })
+                    .map(|deps| package.depends = deps)
+            } else if let Some(names) = line.strip_prefix("provides:") {
+                // Имена, как и зависимости, — несколько строк или список через запятую
+                Self::parse_depends(names).map(|names| package.provides = names)
+            } else if let Some(names) = line.strip_prefix("conflicts:") {
+                Self::parse_depends(names).map(|names| package.conflicts = names)
+            } else {
+                // Неизвестные поля пропускаются, чтобы старые версии могли читать новые файлы
+                if header_key.is_none() && !line.starts_with("phase") {
+                    if let Some(key) = Self::header_like_key(line) {
+                        self.warn(line_no, format!("неизвестное поле {}: игнорируется", key));
+                    }
+                }
+                Ok(())
+            };
+            if let Err(e) = header {
+                self.error(line_no, e);
+            }
+
+            if let Some(rest) = line.strip_prefix("define ") {
+                // define имя { ... } — именованная группа команд для `use имя`
+                let name = rest.split('{').next().unwrap_or("").trim().to_string();
+                if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-') {
+                    self.error(line_no, format!("некорректное имя группы команд: \"{}\"", name));
+                }
+                let body = self.parse_block(&lines, &mut i, Some(&name));
+                if let Some(first) = self.definitions.get(&name).map(|d| d.line) {
+                    self.strict_violation(line_no, format!(
+                        "группа команд {} определена повторно (строки {} и {})", name, first, line_no
+                    ));
+                }
+                self.definitions.insert(name, Definition { line: line_no, commands: body });
+            } else if line.starts_with("phase") {
+                let phase_name = Self::extract_phase_name(line).unwrap_or_else(|e| {
+                    self.error(line_no, e);
+                    String::new()
+                });
+                let mut phase = Phase {
+                    name: phase_name,
+                    commands: Vec::new(),
+                    command_lines: Vec::new(),
+                    line: line_no,
+                    optional: false,
+                    weight: 1,
+                };
+                let append = self.parse_phase_attributes(line, line_no, &mut phase);
+
+                for (cmd, cmd_line) in self.parse_block(&lines, &mut i, None) {
+                    phase.commands.push(cmd);
+                    phase.command_lines.push(cmd_line);
+                }
+
+                match seen_phases.get(&phase.name) {
+                    // phase "имя" append { ... } дописывает команды в уже объявленную фазу
+                    Some(&(_, index)) if append => {
+                        package.phases[index].commands.extend(phase.commands);
+                        package.phases[index].command_lines.extend(phase.command_lines);
+                    }
+                    Some(&(first, _)) => {
+                        self.strict_violation(line_no, format!(
+                            "фаза \"{}\" объявлена повторно (строки {} и {}); используйте phase \"{}\" append {{ ... }} для объединения",
+                            phase.name, first, line_no, phase.name
+                        ));
+                        package.phases.push(phase);
+                    }
+                    None => {
+                        if append {
+                            self.warn(line_no, format!(
+                                "append для фазы \"{}\", которая ещё не объявлена; создана новая фаза",
+                                phase.name
+                            ));
+                        }
+                        seen_phases.insert(phase.name.clone(), (line_no, package.phases.len()));
+                        package.phases.push(phase);
+                    }
+                }
+            }
+
+            i += 1;
+        }
+
+        for phase in &package.phases {
+            self.validate_phase(phase);
+        }
+        self.diagnostics.sort_by_key(|d| d.line);
+        package.defaulted = std::mem::take(&mut self.defaulted);
+
+        package
+    }
+
+    // Тело блока `{ ... }`, начиная со строки заголовка lines[*i].
+    // Вложенные блоки разбираются рекурсивно, фигурные скобки внутри строк не учитываются.
+    // По завершении *i указывает на строку с закрывающей '}'.
+    fn parse_block(&mut self, lines: &[&str], i: &mut usize, defining: Option<&str>) -> Vec<(Command, usize)> {
+        let mut commands = Vec::new();
+        let header_line = *i + 1;
+
+        // Однострочный блок `phase "A" { message "a" }` закрывается на той же строке
+        if let Some(inner) = Self::inline_block_body(Self::strip_comment(lines[*i])) {
+            return self.parse_inline_body(inner, header_line, defining);
+        }
+
+        // Открывающая скобка может стоять на следующих строках после заголовка
+        while *i < lines.len() && Self::brace_delta(Self::strip_comment(lines[*i])) <= 0 {
+            *i += 1;
+        }
+        *i += 1;
+
+        loop {
+            if *i >= lines.len() {
+                self.error(header_line, "блок не закрыт: нет парной '}'".to_string());
+                break;
+            }
+            let line_no = *i + 1;
+            let cmd_line = Self::strip_comment(lines[*i]).trim();
+            if cmd_line.starts_with('}') {
+                break;
+            }
+            if cmd_line.is_empty() {
+                *i += 1;
+                continue;
+            }
+            if let Err(e) = Self::check_quotes(cmd_line) {
+                self.error(line_no, e);
+                *i += 1;
+                continue;
+            }
+            if Self::brace_delta(cmd_line) > 0 {
+                if let Some(cmd) = self.parse_nested_block(lines, i, defining) {
+                    commands.push((cmd, line_no));
+                }
+                *i += 1;
+                continue;
+            }
+            *i += 1;
+            if let Some(name) = cmd_line.strip_prefix("use ").map(str::trim) {
+                self.expand_use(name, line_no, defining, &mut commands);
+                continue;
+            }
+            // Строки row дописываются в таблицу, объявленную командой table выше
+            if let Some((Command::Table { rows, .. }, _)) = commands.last_mut() {
+                if cmd_line.split_whitespace().next().is_some_and(|kw| kw.eq_ignore_ascii_case("row")) {
+                    match Self::table_cells(cmd_line) {
+                        Ok(cells) => rows.push(cells),
+                        Err(e) => self.error(line_no, e),
+                    }
+                    continue;
+                }
+            }
+            match self.parse_command(cmd_line, line_no) {
+                Ok(cmd) => commands.push((cmd, line_no)),
+                // Команда пропускается, как и раньше, но теперь об этом сообщается
+                Err(e) => self.validation_issue(line_no, e),
+            }
+        }
+
+        commands
+    }
+
+    // Все строковые литералы строки по порядку
+    pub(crate) fn literals(line: &str) -> Result<Vec<String>, String> {
+        let mut values = Vec::new();
+        let mut pos = 0;
+        while pos < line.len() {
+            if Self::is_literal_start(line, pos) {
+                let (value, end) = Self::read_literal(line, pos)?;
+                values.push(value);
+                pos = end;
+            } else {
+                pos += line[pos..].chars().next().map_or(1, char::len_utf8);
+            }
+        }
+        Ok(values)
+    }
+
+    fn split_cells(row: &str) -> Vec<String> {
+        row.split('|').map(|c| c.trim().to_string()).collect()
+    }
+
+    // Ячейки строки `row`: каждый литерал — ячейка, либо один литерал с ячейками через `|`
+    fn table_cells(line: &str) -> Result<Vec<String>, String> {
+        let cells = Self::literals(line)?;
+        if cells.len() == 1 && cells[0].contains('|') {
+            return Ok(Self::split_cells(&cells[0]));
+        }
+        Ok(cells)
+    }
+
+    // Вложенный блок `ключевое_слово ... {` внутри фазы
+    fn parse_nested_block(&mut self, lines: &[&str], i: &mut usize, defining: Option<&str>) -> Option<Command> {
+        let line_no = *i + 1;
+        let header = Self::strip_comment(lines[*i]).trim().to_string();
+        let mut words = header.split_whitespace();
+        let keyword = words.next().unwrap_or("").to_lowercase();
+        let argument = words.next().unwrap_or("");
+        // Тело разбирается в любом случае, чтобы фаза не закончилась раньше времени
+        let (body, body_lines): (Vec<Command>, Vec<usize>) = self.parse_block(lines, i, defining).into_iter().unzip();
+
+        match keyword.as_str() {
+            "retry" => {
+                let attempts = self.retry_attempts(argument, line_no);
+                Some(Command::Retry { attempts, body, body_lines })
+            }
+            "parallel" => {
+                let mut children = Vec::new();
+                for (cmd, line) in body.into_iter().zip(body_lines) {
+                    if Self::parallel_safe(&cmd) {
+                        children.push(cmd);
+                    } else {
+                        self.error(line, Self::parallel_unsafe_message(Self::strip_comment(lines[line - 1]).trim()));
+                    }
+                }
+                Some(Command::Parallel(children))
+            }
+            _ => {
+                self.error(line_no, format!("неизвестный блок: {}", keyword));
+                None
+            }
+        }
+    }
+
+    fn retry_attempts(&mut self, argument: &str, line_no: usize) -> u32 {
+        match argument.parse::<u32>() {
+            Ok(n) if n > 0 => n,
+            _ => {
+                self.validation_issue(line_no, format!("retry {}: ожидается число попыток больше 0, используется 3", argument));
+                3
+            }
+        }
+    }
+
+    // Тело однострочного блока: каждая команда разбирается как строка многострочного,
+    // use подставляет группу, retry со своими скобками становится вложенным блоком
+    fn parse_inline_body(&mut self, body: &str, line_no: usize, defining: Option<&str>) -> Vec<(Command, usize)> {
+        let mut commands = Vec::new();
+        for segment in Self::split_inline_commands(body) {
+            if let Some(name) = segment.strip_prefix("use ").map(str::trim) {
+                self.expand_use(name, line_no, defining, &mut commands);
+                continue;
+            }
+            let mut words = segment.split_whitespace();
+            if words.next().is_some_and(|kw| kw.eq_ignore_ascii_case("retry")) {
+                if let Some(inner) = Self::inline_block_body(segment) {
+                    let argument = words.next().unwrap_or("");
+                    let (body, body_lines) = self.parse_inline_body(inner, line_no, defining).into_iter().unzip();
+                    let attempts = self.retry_attempts(argument, line_no);
+                    commands.push((Command::Retry { attempts, body, body_lines }, line_no));
+                    continue;
+                }
+            }
+            match self.parse_command(segment, line_no) {
+                Ok(cmd) => commands.push((cmd, line_no)),
+                Err(e) => self.validation_issue(line_no, e),
+            }
+        }
+        commands
+    }
+
+    /// Команды, которые parallel может выполнять одновременно
+    fn parallel_safe(cmd: &Command) -> bool {
+        matches!(cmd, Command::Download { .. } | Command::Delay(_) | Command::RunTest { .. } | Command::InstallDep { .. })
+    }
+
+    fn parallel_unsafe_message(line: &str) -> String {
+        let keyword = line.split_whitespace().next().unwrap_or("");
+        format!("parallel: команда {} не может выполняться параллельно (допустимы download, delay, run_test, install_dep)", keyword)
+    }
+
+    // Команды однострочного блока `parallel { a ... b ... }`: новая команда начинается
+    // с ключевого слова или use вне строковых литералов и вложенных скобок, а также со
+    // слова, которое уже не может быть значением предыдущей команды (её позиционные
+    // значения исчерпаны, это не name=... и не флаг). Такой текст и текст до первого
+    // ключевого слова остаются отдельными командами, чтобы о них сообщил разбор
+    fn split_inline_commands(body: &str) -> Vec<&str> {
+        let mut starts = Vec::new();
+        let mut spec: Option<&CommandSpec> = None;
+        // Сколько ещё значений без имени ждёт текущая команда; None — слова до следующего
+        // ключевого слова принадлежат нераспознанной команде
+        let mut expected: Option<usize> = None;
+        let mut depth = 0usize;
+        let mut pos = 0;
+        while pos < body.len() {
+            if body[pos..].starts_with(char::is_whitespace) {
+                pos += body[pos..].chars().next().map_or(1, char::len_utf8);
+                continue;
+            }
+            // Слово до пробела; литералы внутри него (name="a b") пропускаются целиком
+            let start = pos;
+            let word_depth = depth;
+            let literal = Self::is_literal_start(body, pos);
+            while pos < body.len() && !body[pos..].starts_with(char::is_whitespace) {
+                if Self::is_literal_start(body, pos) {
+                    pos = Self::read_literal(body, pos).map_or(body.len(), |(_, end)| end);
+                    continue;
+                }
+                match body[pos..].chars().next() {
+                    Some('{') => depth += 1,
+                    Some('}') => depth = depth.saturating_sub(1),
+                    _ => {}
+                }
+                pos += body[pos..].chars().next().map_or(1, char::len_utf8);
+            }
+            if word_depth > 0 {
+                continue;
+            }
+            let word = &body[start..pos];
+            let keyword = if literal { None } else { command_spec(&word.to_lowercase()) };
+            if word == "use" || keyword.is_some() {
+                starts.push(start);
+                spec = keyword;
+                expected = Some(keyword.map_or(1, |k| k.positional.len()));
+            } else if literal || word.starts_with(['{', '}']) || word.contains('=')
+                || spec.is_some_and(|k| k.flags.iter().any(|f| f.eq_ignore_ascii_case(word)))
+            {
+                if literal {
+                    expected = expected.map(|n| n.saturating_sub(1));
+                }
+            } else if let Some(n) = expected {
+                if n > 0 {
+                    expected = Some(n - 1);
+                } else {
+                    starts.push(start);
+                    spec = None;
+                    expected = None;
+                }
+            }
+        }
+        if starts.first() != Some(&0) && !body[..starts.first().copied().unwrap_or(body.len())].trim().is_empty() {
+            starts.insert(0, 0);
+        }
+        starts.iter().enumerate()
+            .map(|(n, &start)| body[start..starts.get(n + 1).copied().unwrap_or(body.len())].trim())
+            .collect()
+    }
+
+    // Текст между '{' и парной '}' на одной строке (скобки в строковых литералах не считаются)
+    fn inline_block_body(line: &str) -> Option<&str> {
+        let mut open = None;
+        let mut depth = 0;
+        let mut pos = 0;
+        while pos < line.len() {
+            if Self::is_literal_start(line, pos) {
+                pos = Self::read_literal(line, pos).ok()?.1;
+                continue;
+            }
+            let c = line[pos..].chars().next()?;
+            match c {
+                '{' => {
+                    open.get_or_insert(pos);
+                    depth += 1;
+                }
+                '}' if depth > 0 => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return open.map(|open| &line[open + 1..pos]);
+                    }
+                }
+                _ => {}
+            }
+            pos += c.len_utf8();
+        }
+        None
+    }
+
+    // Баланс фигурных скобок вне строковых литералов
+    pub(crate) fn brace_delta(line: &str) -> i32 {
+        let mut delta = 0;
+        let mut pos = 0;
+        while pos < line.len() {
+            if Self::is_literal_start(line, pos) {
+                match Self::read_literal(line, pos) {
+                    Ok((_, end)) => {
+                        pos = end;
+                        continue;
+                    }
+                    Err(_) => break,
+                }
+            }
+            let c = line[pos..].chars().next().unwrap_or(' ');
+            match c {
+                '{' => delta += 1,
+                '}' => delta -= 1,
+                _ => {}
+            }
+            pos += c.len_utf8();
+        }
+        delta
+    }
+
+    // use имя — подстановка команд из define; группа должна быть определена выше
+    fn expand_use(&mut self, name: &str, line_no: usize, defining: Option<&str>, out: &mut Vec<(Command, usize)>) {
+        if defining == Some(name) {
+            self.error(line_no, format!("рекурсивное определение: группа {} использует саму себя", name));
+            return;
+        }
+        match self.definitions.get(name) {
+            Some(def) => out.extend(def.commands.iter().cloned()),
+            None => {
+                let message = match self.declared_definitions.get(name) {
+                    Some(def_line) => format!(
+                        "группа команд {} используется до определения (define на строке {})", name, def_line
+                    ),
+                    None => format!("группа команд {} не определена", name),
+                };
+                self.error(line_no, message);
+            }
+        }
+    }
+
+    // "homepage_url: ..." → Some("homepage_url") для строк вида `ключ: значение`
+    pub(crate) fn header_like_key(line: &str) -> Option<&str> {
+        let (key, _) = line.split_once(':')?;
+        let is_ident = !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        is_ident.then_some(key)
+    }
+
+    fn parse_depends(deps_str: &str) -> Result<Vec<String>, String> {
+        let mut deps = Vec::new();
+        let mut pos = 0;
+
+        while pos < deps_str.len() {
+            if Self::is_literal_start(deps_str, pos) {
+                let (value, end) = Self::read_literal(deps_str, pos)?;
+                // Внутри кавычек допускается список через запятую
+                deps.extend(
+                    value.split(',')
+                        .map(str::trim)
+                        .filter(|d| !d.is_empty())
+                        .map(str::to_string),
+                );
+                pos = end;
+            } else {
+                pos += deps_str[pos..].chars().next().map_or(1, char::len_utf8);
+            }
+        }
+
+        Ok(deps)
+    }
+
+    // "python.instnoth >= 3.12" → путь и необязательное ограничение версии
+    pub(crate) fn parse_dependency(spec: &str, line: usize) -> Result<Dependency, String> {
+        // Ограничение — только в конце: "lib.instnoth >= 1.2", "lib.instnoth 3.12";
+        // в самом пути могут быть пробелы: "my dir/lib.instnoth"
+        fn split_last(s: &str) -> Option<(&str, &str)> {
+            s.rfind(char::is_whitespace).map(|pos| (s[..pos].trim_end(), &s[pos + 1..]))
+        }
+        const OPERATORS: [&str; 4] = [">=", "<=", "=", "~"];
+        let is_version = |token: &str| {
+            token.starts_with(|c: char| c.is_ascii_digit())
+                && !token.ends_with(".instnoth")
+                && token.chars().all(|c| c.is_ascii_alphanumeric() || ".-+".contains(c))
+        };
+
+        // Оператор не из списка (">", "==", "!=" ...) не должен молча стать частью пути
+        fn operator(token: &str) -> &str {
+            let len = token.find(|c: char| !"<>=!~^".contains(c)).unwrap_or(token.len());
+            &token[..len]
+        }
+        let unknown = |op: &str, path: &str| {
+            format!("неизвестный оператор версии '{}' (допустимы {}) (зависимость {})", op, OPERATORS.join(", "), path)
+        };
+
+        let spec = spec.trim();
+        if let Some((head, last)) = split_last(spec) {
+            let op = operator(last);
+            if !op.is_empty() && !OPERATORS.contains(&op) && last[op.len()..].starts_with(|c: char| c.is_ascii_digit()) {
+                return Err(unknown(op, head));
+            }
+            if let Some((path, op)) = split_last(head) {
+                if !op.is_empty() && operator(op) == op && !OPERATORS.contains(&op) {
+                    return Err(unknown(op, path));
+                }
+            }
+        }
+        let (path, rest) = match split_last(spec) {
+            Some((head, last)) if OPERATORS.iter().any(|op| last.starts_with(op)) => (head, last.to_string()),
+            Some((head, last)) => match split_last(head) {
+                Some((path, op)) if OPERATORS.contains(&op) => (path, format!("{} {}", op, last)),
+                _ if is_version(last) => (head, last.to_string()),
+                _ => (spec, String::new()),
+            },
+            None => (spec, String::new()),
+        };
+        let constraint = if rest.is_empty() {
+            None
+        } else {
+            Some(VersionConstraint::parse(&rest).map_err(|e| format!("{} (зависимость {})", e, path))?)
+        };
+        Ok(Dependency { path: path.to_string(), constraint, line })
+    }
+
+    // Строковый литерал: "...", '...' или сырой r"..." / r'...' (без обработки escape-последовательностей)
+    pub(crate) fn is_literal_start(s: &str, pos: usize) -> bool {
+        let rest = &s[pos..];
+        if rest.starts_with('"') || rest.starts_with('\'') {
+            return true;
+        }
+        // r" считается началом сырой строки только в начале слова: r"..." или key=r"..."
+        let at_word_start = s[..pos].chars().next_back()
+            .is_none_or(|c| c.is_whitespace() || c == '=' || c == ',');
+        at_word_start && (rest.starts_with("r\"") || rest.starts_with("r'"))
+    }
+
+    // Читает литерал, начинающийся в позиции start; возвращает значение и позицию после него
+    pub(crate) fn read_literal(s: &str, start: usize) -> Result<(String, usize), String> {
+        let raw = s[start..].starts_with('r');
+        let quote_pos = if raw { start + 1 } else { start };
+        let quote = s[quote_pos..].chars().next().unwrap_or('"');
+        let body_start = quote_pos + quote.len_utf8();
+
+        let mut value = String::new();
+        let mut chars = s[body_start..].char_indices();
+        while let Some((offset, c)) = chars.next() {
+            if c == quote {
+                return Ok((value, body_start + offset + c.len_utf8()));
+            }
+            if c == '\\' && !raw {
+                match chars.next() {
+                    Some((_, 'n')) => value.push('\n'),
+                    Some((_, 't')) => value.push('\t'),
+                    Some((_, e @ ('\\' | '"' | '\''))) => value.push(e),
+                    Some((_, other)) => {
+                        value.push('\\');
+                        value.push(other);
+                    }
+                    None => break,
+                }
+            } else {
+                value.push(c);
+            }
+        }
+
+        Err(format!("незакрытая кавычка {} в: {}", quote, s.trim()))
+    }
+
+    // Проверяет, что все кавычки в строке закрыты
+    fn check_quotes(line: &str) -> Result<(), String> {
+        let mut pos = 0;
+        while pos < line.len() {
+            if Self::is_literal_start(line, pos) {
+                pos = Self::read_literal(line, pos)?.1;
+            } else {
+                pos += line[pos..].chars().next().map_or(1, char::len_utf8);
+            }
+        }
+        Ok(())
+    }
+
+    // Отрезает комментарий `# ...` в конце строки. Решётка внутри кавычек сохраняется,
+    // а комментарий начинается только с начала строки или после пробела.
+    pub(crate) fn strip_comment(line: &str) -> &str {
+        let mut pos = 0;
+        let mut prev_is_space = true;
+
+        while pos < line.len() {
+            if Self::is_literal_start(line, pos) {
+                match Self::read_literal(line, pos) {
+                    Ok((_, end)) => {
+                        pos = end;
+                        prev_is_space = false;
+                        continue;
+                    }
+                    // Незакрытая кавычка: строку целиком разберёт и отклонит check_quotes
+                    Err(_) => return line,
+                }
+            }
+            let c = line[pos..].chars().next().unwrap_or(' ');
+            if c == '#' && prev_is_space {
+                return &line[..pos];
+            }
+            prev_is_space = c.is_whitespace();
+            pos += c.len_utf8();
+        }
+
+        line
+    }
+
+    // Первый строковый литерал в строке
+    fn first_literal(line: &str) -> Option<Result<(String, usize), String>> {
+        line.char_indices()
+            .map(|(pos, _)| pos)
+            .find(|&pos| Self::is_literal_start(line, pos))
+            .map(|pos| Self::read_literal(line, pos))
+    }
+
+    pub(crate) fn extract_quoted_value(line: &str) -> Result<String, String> {
+        match Self::first_literal(line) {
+            Some(result) => result.map(|(value, _)| value),
+            None => Err(format!("Не удалось извлечь значение из: {}", line)),
+        }
+    }
+
+    fn extract_phase_name(line: &str) -> Result<String, String> {
+        match Self::first_literal(line) {
+            Some(result) => result.map(|(value, _)| value),
+            None => Err("Не удалось извлечь имя фазы".to_string()),
+        }
+    }
+
+    // Атрибуты фазы между именем и '{': phase "Тесты" optional weight=30 {
+    // Возвращает true для формы `phase "имя" append {`
+    fn parse_phase_attributes(&mut self, line: &str, line_no: usize, phase: &mut Phase) -> bool {
+        let after_name = match Self::first_literal(line) {
+            Some(Ok((_, end))) => &line[end..],
+            _ => return false,
+        };
+        let attrs = after_name.split('{').next().unwrap_or("");
+        let mut append = false;
+
+        for attr in attrs.split_whitespace() {
+            if attr == "optional" {
+                phase.optional = true;
+            } else if attr == "append" {
+                append = true;
+            } else if let Some(value) = attr.strip_prefix("weight=") {
+                match value.parse::<u32>() {
+                    Ok(w) => phase.weight = w,
+                    Err(_) => self.warn(line_no, format!(
+                        "некорректный вес фазы \"{}\": {}", phase.name, value
+                    )),
+                }
+            } else {
+                self.warn(line_no, format!("неизвестный атрибут фазы \"{}\": {}", phase.name, attr));
+            }
+        }
+
+        append
+    }
+
+    // Проверка значений после разбора: диапазоны, обязательные параметры, формат прав
+    fn validate_phase(&mut self, phase: &Phase) {
+        let mut last_progress: Option<u8> = None;
+        self.validate_commands(&phase.name, &phase.commands, &phase.command_lines, &mut last_progress);
+    }
+
+    fn validate_commands(&mut self, phase_name: &str, commands: &[Command], lines: &[usize], last_progress: &mut Option<u8>) {
+        for (cmd, &line) in commands.iter().zip(lines) {
+            match cmd {
+                Command::Progress(pct) => {
+                    if let Some(prev) = last_progress.filter(|prev| pct < prev) {
+                        self.validation_issue(line, format!(
+                            "progress {}: прогресс уменьшается (ранее в фазе \"{}\" было {})",
+                            pct, phase_name, prev
+                        ));
+                    }
+                    *last_progress = Some(*pct);
+                }
+                Command::Retry { body, body_lines, .. } => {
+                    self.validate_commands(phase_name, body, body_lines, last_progress);
+                }
+                Command::Parallel(children) => {
+                    let lines = vec![line; children.len()];
+                    self.validate_commands(phase_name, children, &lines, last_progress);
+                }
+                Command::Delay(ms) if *ms > self.max_delay => {
+                    self.validation_issue(line, format!("delay {}: превышает максимум {} мс", ms, self.max_delay));
+                }
+                Command::RunTest { duration, .. } if *duration > self.max_delay => {
+                    self.validation_issue(line, format!("run_test duration={}: превышает максимум {} мс", duration, self.max_delay));
+                }
+                Command::WaitFor { timeout_secs, .. } if timeout_secs.saturating_mul(1000) > self.max_delay => {
+                    self.validation_issue(line, format!("wait_for timeout={}: превышает максимум {} мс", timeout_secs, self.max_delay));
+                }
+                Command::Reboot { delay_secs } if delay_secs.saturating_mul(1000) > self.max_delay => {
+                    self.validation_issue(line, format!("reboot delay={}: превышает максимум {} мс", delay_secs, self.max_delay));
+                }
+                Command::SetPermission { mode, .. } if !Self::is_octal_mode(mode) => {
+                    self.validation_issue(line, format!("set_permission mode=\"{}\": ожидаются восьмеричные права (например, 755)", mode));
+                }
+                Command::Extract { to, .. } if to.is_empty() => {
+                    self.validation_issue(line, "extract: отсутствует обязательный параметр to=".to_string());
+                }
+                Command::CopyFile { to, .. } if to.is_empty() => {
+                    self.validation_issue(line, "copy_file: отсутствует обязательный параметр to=".to_string());
+                }
+                Command::Symlink { to, .. } if to.is_empty() => {
+                    self.validation_issue(line, "symlink: отсутствует обязательный параметр to=".to_string());
+                }
+                Command::MountPartition { mount_point, .. } if mount_point.is_empty() => {
+                    self.validation_issue(line, "mount: отсутствует обязательный параметр to=".to_string());
+                }
+                Command::Choose { var, .. } if var.is_empty() => {
+                    self.validation_issue(line, "choose: отсутствует обязательный параметр var=".to_string());
+                }
+                Command::Configure { key, .. } if key.is_empty() => {
+                    self.validation_issue(line, "configure: отсутствует обязательный параметр key=".to_string());
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn is_octal_mode(mode: &str) -> bool {
+        (3..=4).contains(&mode.len()) && mode.chars().all(|c| ('0'..='7').contains(&c))
+    }
+
+    // Числовой аргумент команды; некорректное значение заменяется значением по умолчанию с предупреждением
+    fn number_arg<T: std::str::FromStr>(&mut self, cmd: &str, value: &str, default: T, line_no: usize) -> T {
+        match value.trim().parse() {
+            Ok(v) => v,
+            Err(_) => {
+                self.validation_issue(line_no, format!(
+                    "{} {}: ожидается неотрицательное целое число",
+                    cmd, value.trim()
+                ));
+                default
+            }
+        }
+    }
+
+    // Числовой параметр name=N; если он указан, но не является числом — предупреждение
+    fn number_param(&mut self, cmd: &str, args: &str, name: &str, default: u64, line_no: usize) -> u64 {
+        match self.extract_param(args, name, line_no) {
+            Some(Ok(v)) => v,
+            Some(Err(_)) => {
+                self.validation_issue(line_no, format!(
+                    "{}: некорректное значение {}= (ожидается неотрицательное целое число), используется {}",
+                    cmd, name, default
+                ));
+                default
+            }
+            None => default,
+        }
+    }
+
+    /// Код выхода процесса из параметра name=: от 1 до 255, по умолчанию 1
+    fn exit_code_param(&mut self, cmd: &str, args: &str, name: &str, line_no: usize) -> i32 {
+        let code = self.number_param(cmd, args, name, 1, line_no);
+        match i32::try_from(code) {
+            Ok(code) if code > 0 && code < 256 => code,
+            _ => {
+                self.validation_issue(line_no, format!("{}: {}={} должен быть от 1 до 255, используется 1", cmd, name, code));
+                1
+            }
+        }
+    }
+
+    pub(crate) fn parse_command(&mut self, line: &str, line_no: usize) -> Result<Command, String> {
+        let parts: Vec<&str> = line.splitn(2, ' ').collect();
+        let args = if parts.len() > 1 { parts[1] } else { "" };
+
+        let lowered = parts[0].to_lowercase();
+        let Some(spec) = command_spec(&lowered) else {
+            return Err(match suggest_keyword(&lowered) {
+                Some(hint) => format!("Неизвестная команда: {} (возможно, вы имели в виду {}?)", parts[0], hint),
+                None => format!("Неизвестная команда: {}", parts[0]),
+            });
+        };
+        (spec.build)(&mut CommandArgs { parser: self, spec, line, args, line_no })
+    }
+
+    // Позиция значения параметра name=... (вне строковых литералов, в начале слова)
+    /// Есть ли среди аргументов отдельное слово `flag` вне строковых литералов
+    fn has_flag(args: &str, flag: &str) -> bool {
+        let mut pos = 0;
+        while pos < args.len() {
+            if Self::is_literal_start(args, pos) {
+                pos = Self::read_literal(args, pos).map_or(args.len(), |(_, end)| end);
+                continue;
+            }
+            let rest = &args[pos..];
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            if end == 0 {
+                pos += rest.chars().next().map_or(1, char::len_utf8);
+                continue;
+            }
+            if &rest[..end] == flag {
+                return true;
+            }
+            pos += end;
+        }
+        false
+    }
+
+    pub(crate) fn parse_answer(answer: &str) -> Option<bool> {
+        match answer.to_lowercase().as_str() {
+            "y" | "yes" | "д" | "да" => Some(true),
+            "n" | "no" | "н" | "нет" => Some(false),
+            _ => None,
+        }
+    }
+
+    /// Позиции значений всех вхождений `name=` вне строковых литералов
+    fn find_params(args: &str, name: &str) -> Vec<usize> {
+        let pattern = format!("{}=", name);
+        let mut found = Vec::new();
+        let mut pos = 0;
+        let mut prev_is_space = true;
+
+        while pos < args.len() {
+            if Self::is_literal_start(args, pos) {
+                pos = Self::read_literal(args, pos).map_or(args.len(), |(_, end)| end);
+                prev_is_space = false;
+                continue;
+            }
+            if prev_is_space && args[pos..].starts_with(&pattern) {
+                found.push(pos + pattern.len());
+            }
+            let c = args[pos..].chars().next().unwrap_or(' ');
+            prev_is_space = c.is_whitespace();
+            pos += c.len_utf8();
+        }
+
+        found
+    }
+
+    /// Значение параметра `name=`: строковый литерал или слово до пробела.
+    /// Числовые параметры принимаются только без кавычек. При повторе параметра
+    /// берётся последнее вхождение.
+    fn param_value(&mut self, args: &str, name: &str, kind: ParamKind, line_no: usize) -> Option<String> {
+        let positions = Self::find_params(args, name);
+        let Some(&start) = positions.last() else {
+            let names = self.defaulted.entry(line_no).or_default();
+            if !names.iter().any(|n| n == name) {
+                names.push(name.to_string());
+            }
+            return None;
+        };
+        if positions.len() > 1 {
+            self.warn(line_no, format!(
+                "параметр {}= указан несколько раз ({}), используется последнее значение",
+                name, positions.len()
+            ));
+        }
+
+        if Self::is_literal_start(args, start) {
+            return match kind {
+                ParamKind::Text => Self::read_literal(args, start).ok().map(|(value, _)| value),
+                ParamKind::Number => Some(args[start..].split_whitespace().next().unwrap_or("").to_string()),
+            };
+        }
+        let rest = &args[start..];
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        Some(rest[..end].to_string())
+    }
+
+    fn extract_param(&mut self, args: &str, name: &str, line_no: usize) -> Option<Result<u64, String>> {
+        self.param_value(args, name, ParamKind::Number, line_no)
+            .map(|raw| raw.parse().map_err(|_| raw))
+    }
+
+    fn extract_string_param(&mut self, args: &str, name: &str, line_no: usize) -> Option<String> {
+        self.param_value(args, name, ParamKind::Text, line_no)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_source(header: &str, body: &str) -> (Package, Vec<Diagnostic>) {
+        let source = format!("package: \"demo\"\nversion: \"1.0\"\n{}{}", header, body);
+        let mut parser = InstnothParser::new(source);
+        let package = parser.parse_package();
+        (package, parser.diagnostics)
+    }
+
+    fn phases(package: &Package) -> Vec<(&str, Vec<Command>)> {
+        package.phases.iter().map(|p| (p.name.as_str(), p.commands.clone())).collect()
+    }
+
+    fn message(text: &str) -> Command {
+        Command::Message(text.to_string())
+    }
+
+    #[test]
+    fn named_params_read_quoted_and_unquoted_values_alike() {
+        // Значения без пробелов, которые проходят проверки команд: их можно писать и без кавычек
+        let value = |p: &ParamSpec, last: bool| match (p.name, p.kind) {
+            ("country", _) => if last { "DE" } else { "FR" }.to_string(),
+            ("path", _) => if last { "/srv/a" } else { "/srv/b" }.to_string(),
+            ("bits", _) => if last { "4096" } else { "2048" }.to_string(),
+            (_, ParamType::Text | ParamType::List) => if last { "ab12cd34" } else { "ab12cd56" }.to_string(),
+            (_, ParamType::Number) => if last { p.default.unwrap_or("2") } else { "7" }.to_string(),
+            (_, ParamType::Bool) => last.to_string(),
+            (_, ParamType::Size) => if last { "1G" } else { "2G" }.to_string(),
+            (_, ParamType::Choice(values)) => if last { values[0] } else { values[values.len() - 1] }.to_string(),
+        };
+        let parse = |line: String| {
+            let mut parser = InstnothParser::new(String::new());
+            let cmd = parser.parse_command(&line, 1);
+            let messages: Vec<String> = parser.diagnostics.into_iter().map(|d| d.message).collect();
+            (line, cmd, messages)
+        };
+
+        let mut checked = 0;
+        for spec in COMMANDS.iter().filter(|spec| !spec.params.is_empty()) {
+            for p in spec.params {
+                // Строка со всеми параметрами; проверяемый записан так, как вернёт variant
+                let line = |variant: &str| {
+                    let mut line = spec.keyword.to_string();
+                    for q in spec.positional {
+                        line.push_str(&format!(" \"{}\"", value(q, true)));
+                    }
+                    for q in spec.params {
+                        if q.name == p.name {
+                            line.push_str(&format!(" {}", variant));
+                        } else {
+                            line.push_str(&format!(" {}={}", q.name, value(q, true)));
+                        }
+                    }
+                    line
+                };
+                let plain = parse(line(&format!("{}={}", p.name, value(p, true))));
+                assert!(plain.1.is_ok(), "{:?}", plain);
+                let quoted = parse(line(&format!("{}=\"{}\"", p.name, value(p, true))));
+                if p.kind == ParamType::Number {
+                    let rejected = format!("{}: некорректное значение {}=", spec.keyword, p.name);
+                    assert!(quoted.2.iter().any(|m| m.starts_with(&rejected)), "{:?}", quoted);
+                } else {
+                    assert_eq!((&plain.1, &plain.2), (&quoted.1, &quoted.2), "{}", quoted.0);
+                }
+
+                let repeated = parse(line(&format!("{}={} {}={}", p.name, value(p, false), p.name, value(p, true))));
+                assert_eq!(repeated.1, plain.1, "{}", repeated.0);
+                let warning = format!("параметр {}= указан несколько раз (2), используется последнее значение", p.name);
+                assert!(repeated.2.contains(&warning), "{:?}", repeated);
+                checked += 1;
+            }
+        }
+        assert!(checked > 20, "{}", checked);
+    }
+
+    #[test]
+    fn version_constraint_operators() {
+        let matches = |constraint: &str, version: &str| VersionConstraint::parse(constraint).unwrap().matches(version);
+        assert!(matches(">= 1.2", "1.2") && matches(">=1.2", "1.10.0") && !matches(">= 1.2", "1.1.9"));
+        assert!(matches("<= 2.0", "2.0.0") && matches("<= 2.0", "1.9") && !matches("<= 2.0", "2.0.1"));
+        assert!(matches("= 3.1", "3.1.0") && !matches("= 3.1", "3.1.1"));
+        assert!(matches("~ 1.2.3", "1.2.9") && !matches("~ 1.2.3", "1.3.0") && !matches("~ 1.2.3", "1.2.2"));
+        assert!(matches("~1", "1.9.9") && !matches("~1", "2.0.0"));
+        assert!(matches("3.12", "3.12.7") && matches("3.12", "3.12") && !matches("3.12", "3.1") && !matches("3.12", "3.13.0"));
+        assert!(matches(">= 6.6", "6.6.8-arch1"));
+
+        assert_eq!(VersionConstraint::parse(">=1.2").unwrap().to_string(), ">= 1.2");
+        assert_eq!(VersionConstraint::parse("3.12").unwrap().to_string(), "3.12");
+        for bad in [">=", "> 1.0", "latest", "~ x"] {
+            assert!(VersionConstraint::parse(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn dependency_constraint_is_split_off_the_end() {
+        let dependency = |spec: &str| {
+            let dep = InstnothParser::parse_dependency(spec, 1).unwrap();
+            (dep.path, dep.constraint.map(|c| c.to_string()))
+        };
+        let some = |s: &str| Some(s.to_string());
+        assert_eq!(dependency("lib.instnoth"), ("lib.instnoth".to_string(), None));
+        assert_eq!(dependency("lib.instnoth >= 1.2"), ("lib.instnoth".to_string(), some(">= 1.2")));
+        assert_eq!(dependency("lib.instnoth >=1.2"), ("lib.instnoth".to_string(), some(">= 1.2")));
+        assert_eq!(dependency("lib.instnoth 3.12"), ("lib.instnoth".to_string(), some("3.12")));
+        assert_eq!(dependency("my dir/lib.instnoth"), ("my dir/lib.instnoth".to_string(), None));
+        assert_eq!(dependency("my dir/lib.instnoth ~ 2.1"), ("my dir/lib.instnoth".to_string(), some("~ 2.1")));
+        assert_eq!(dependency("my dir/2 lib.instnoth"), ("my dir/2 lib.instnoth".to_string(), None));
+
+        let err = InstnothParser::parse_dependency("lib.instnoth >= latest", 1).unwrap_err();
+        assert_eq!(err, "Некорректное ограничение версии: >= latest (зависимость lib.instnoth)");
+    }
+
+    #[test]
+    fn dependency_with_unknown_operator_is_an_error() {
+        for (spec, op) in [("lib.instnoth > 1.2", ">"), ("lib.instnoth == 1.2", "=="), ("my dir/lib.instnoth != 2", "!="),
+            ("lib.instnoth ^ 1", "^"), ("lib.instnoth >1.2", ">"), ("lib.instnoth <2", "<")] {
+            let err = InstnothParser::parse_dependency(spec, 1).unwrap_err();
+            assert!(err.starts_with(&format!("неизвестный оператор версии '{}' (допустимы >=, <=, =, ~)", op)), "{}: {}", spec, err);
+        }
+    }
+
+    #[test]
+    fn append_merges_into_first_phase_with_that_name() {
+        let (package, diagnostics) = parse_source("", "phase \"A\" {\n    message \"a\"\n}\nphase \"B\" {\n    message \"b\"\n}\nphase \"A\" append {\n    message \"a2\"\n}\n");
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics.iter().map(|d| &d.message).collect::<Vec<_>>());
+        assert_eq!(phases(&package), vec![("A", vec![message("a"), message("a2")]), ("B", vec![message("b")])]);
+        assert_eq!(package.phases[0].command_lines, vec![4, 10]);
+
+        let (package, diagnostics) = parse_source("", "phase \"A\" append {\n    message \"a\"\n}\n");
+        assert_eq!(phases(&package), vec![("A", vec![message("a")])]);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!((diagnostics[0].line, &diagnostics[0].severity), (3, &Severity::Warning));
+        assert_eq!(diagnostics[0].message, "append для фазы \"A\", которая ещё не объявлена; создана новая фаза");
+    }
+
+    #[test]
+    fn duplicate_phase_and_header_are_reported_with_both_lines() {
+        let source = "package: \"demo\"\nversion: \"1.0\"\nversion: \"2.0\"\nphase \"A\" {\n    message \"a\"\n}\nphase \"A\" {\n    message \"b\"\n}\n";
+        let mut parser = InstnothParser::new(source.to_string());
+        let package = parser.parse().unwrap();
+        assert_eq!(package.version, "2.0");
+        assert_eq!(phases(&package), vec![("A", vec![message("a")]), ("A", vec![message("b")])]);
+        let found: Vec<(usize, Severity, &str)> = parser.diagnostics.iter().map(|d| (d.line, d.severity, d.message.as_str())).collect();
+        assert_eq!(found, vec![
+            (3, Severity::Warning, "поле version: указано повторно (строки 2 и 3), используется последнее значение"),
+            (7, Severity::Warning, "фаза \"A\" объявлена повторно (строки 4 и 7); используйте phase \"A\" append { ... } для объединения"),
+        ]);
+
+        let mut parser = InstnothParser::new(source.to_string());
+        parser.strict = true;
+        assert_eq!(parser.parse().unwrap_err(), "строка 3: поле version: указано повторно (строки 2 и 3); \
+            строка 7: фаза \"A\" объявлена повторно (строки 4 и 7); используйте phase \"A\" append { ... } для объединения");
+    }
+
+    #[test]
+    fn comments_are_stripped_outside_strings_only() {
+        let cases = [
+            ("message \"a # b\"", "message \"a # b\""),
+            ("message \"a # b\" # note", "message \"a # b\""),
+            ("delay 500 # wait", "delay 500"),
+            ("delay 500# wait", "delay 500# wait"),
+            ("configure key=\"a\" value=\"b\" # note", "configure key=\"a\" value=\"b\""),
+            ("message r\"C:\\#tmp\\\" # note", "message r\"C:\\#tmp\\\""),
+            ("message 'a # \"b\"' # note", "message 'a # \"b\"'"),
+            ("# only a comment", ""),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(InstnothParser::strip_comment(line).trim_end(), expected, "{}", line);
+        }
+
+        let (package, diagnostics) = parse_source("", "phase \"A\" {\n    message \"a # b\"\n    delay 500 # wait\n    configure key=\"a\" value=\"b\" # note\n    message r\"x # y\" # raw\n    message 'x # \"y\"'\n} # end\n");
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics.iter().map(|d| &d.message).collect::<Vec<_>>());
+        assert_eq!(phases(&package), vec![("A", vec![
+            message("a # b"),
+            Command::Delay(500),
+            Command::Configure { key: "a".to_string(), value: "b".to_string() },
+            message("x # y"),
+            message("x # \"y\""),
+        ])]);
+    }
+
+    #[test]
+    fn braces_inside_strings_do_not_close_phase() {
+        let (package, diagnostics) = parse_source("", "phase \"A\" {\n    write_config \"/etc/a.conf\" content=\"a { b }\"\n    message \"}\"\n    message \"{\"\n    message \"after\"\n}\nphase \"B\" {\n    message \"b\"\n}\n");
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics.iter().map(|d| &d.message).collect::<Vec<_>>());
+        assert_eq!(phases(&package), vec![
+            ("A", vec![
+                Command::WriteConfig { path: "/etc/a.conf".to_string(), content: "a { b }".to_string() },
+                message("}"),
+                message("{"),
+                message("after"),
+            ]),
+            ("B", vec![message("b")]),
+        ]);
+        assert_eq!(package.phases[0].command_lines, vec![4, 5, 6, 7]);
+    }
+
+    #[test]
+    fn nested_block_keeps_phase_open() {
+        let (package, diagnostics) = parse_source("", "phase \"A\" {\n    retry 2 {\n        message \"}\"\n        delay 10\n    }\n    message \"after\"\n}\nphase \"B\" {\n    message \"b\"\n}\n");
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics.iter().map(|d| &d.message).collect::<Vec<_>>());
+        assert_eq!(phases(&package), vec![
+            ("A", vec![
+                Command::Retry { attempts: 2, body: vec![message("}"), Command::Delay(10)], body_lines: vec![5, 6] },
+                message("after"),
+            ]),
+            ("B", vec![message("b")]),
+        ]);
+    }
+
+    #[test]
+    fn one_line_phase_closes_on_its_own_line() {
+        let (package, diagnostics) = parse_source("", "phase \"One\" { message \"a } b\" delay 5 }\nphase \"Two\" {\n    message \"b\"\n}\n");
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics.iter().map(|d| &d.message).collect::<Vec<_>>());
+        assert_eq!(phases(&package), vec![
+            ("One", vec![message("a } b"), Command::Delay(5)]),
+            ("Two", vec![message("b")]),
+        ]);
+        assert_eq!(package.phases[0].command_lines, vec![3, 3]);
+
+        // Незакрытый блок по-прежнему ошибка, а не молча проглоченный конец файла
+        let (_, diagnostics) = parse_source("", "phase \"A\" {\n    message \"}\"\n");
+        assert!(diagnostics.iter().any(|d| d.severity == Severity::Error && d.line == 3 && d.message.contains("блок не закрыт")));
+    }
+
+    #[test]
+    fn one_line_phase_expands_use_and_nested_retry() {
+        let (package, diagnostics) = parse_source("",
+            "define grp {\n    message \"g\"\n}\nphase \"A\" { use grp delay 5 }\nphase \"B\" { retry 2 { message \"x\" } delay 1 }\n");
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics.iter().map(|d| &d.message).collect::<Vec<_>>());
+        assert_eq!(phases(&package), vec![
+            ("A", vec![message("g"), Command::Delay(5)]),
+            ("B", vec![Command::Retry { attempts: 2, body: vec![message("x")], body_lines: vec![7] }, Command::Delay(1)]),
+        ]);
+        assert_eq!(package.phases[1].command_lines, vec![7, 7]);
+    }
+
+    #[test]
+    fn one_line_phase_reports_what_it_cannot_parse() {
+        // Опечатка и неизвестная группа дают то же, что и в многострочном блоке
+        for (inner, multi_line) in [
+            ("mesage \"x\"", "mesage \"x\""),
+            ("use nope", "use nope"),
+            ("message \"a\" mesage \"b\"", "message \"a\"\n    mesage \"b\""),
+            ("retry 0 { message \"x\" }", "retry 0 {\n        message \"x\"\n    }"),
+        ] {
+            let (_, expected) = parse_source("", &format!("phase \"A\" {{\n    {multi_line}\n}}\n"));
+            let (_, diagnostics) = parse_source("", &format!("phase \"A\" {{ {inner} }}\n"));
+            assert!(!diagnostics.is_empty(), "{inner}");
+            assert_eq!(
+                diagnostics.iter().map(|d| (d.severity, d.message.as_str())).collect::<Vec<_>>(),
+                expected.iter().map(|d| (d.severity, d.message.as_str())).collect::<Vec<_>>(),
+                "{inner}"
+            );
+        }
+        let (_, diagnostics) = parse_source("", "phase \"A\" { mesage \"x\" }\n");
+        assert!(diagnostics[0].message.starts_with("Неизвестная команда: mesage"), "{}", diagnostics[0].message);
+    }
+
+    #[test]
+    fn invert_maps_commands_to_inverse_operations() {
+        assert!(matches!(invert(&Command::EnableService("sshd".to_string()), Lang::Ru), Some(Command::DisableService(s)) if s == "sshd"));
+        let mount = Command::MountPartition { device: "/dev/sda2".to_string(), mount_point: "/mnt".to_string() };
+        assert!(matches!(invert(&mount, Lang::Ru), Some(Command::UnmountPartition(p)) if p == "/mnt"));
+        assert_eq!(invert(&Command::SetHostname("box".to_string()), Lang::Ru).map(|c| c.to_string()),
+            Some("Revert action=\"Откат шага\" target=\"SetHostname hostname=\\\"box\\\"\"".to_string()));
+        assert!(invert(&Command::DetectCpu, Lang::Ru).is_none());
+    }
+
+    #[test]
+    fn command_display_shows_kind_and_fields() {
+        let cmd = Command::Download { url: "https://example.com/a.tar".to_string(), size: 1024 };
+        assert_eq!(cmd.to_string(), "Download url=\"https://example.com/a.tar\" size=1024");
+        assert_eq!(Command::DetectCpu.to_string(), "DetectCpu");
+    }
+
+    #[test]
+    fn command_event_serializes_fields() {
+        let cmd = Command::Download { url: "https://example.com/a \"b\".tar".to_string(), size: 1024 };
+        assert_eq!(cmd.event().to_json(7),
+            r#"{"type":"command","ts":7,"kind":"Download","url":"https://example.com/a \"b\".tar","size":1024}"#);
+        let locales = Command::GenerateLocales(vec!["en_US.UTF-8".to_string(), "ru_RU.UTF-8".to_string()]);
+        assert_eq!(locales.event().to_json(0), r#"{"type":"command","ts":0,"kind":"GenerateLocales","locales":["en_US.UTF-8","ru_RU.UTF-8"]}"#);
+    }
+
+    #[test]
+    fn command_table_describes_what_parser_reads() {
+        let sample = |p: &ParamSpec| match p.kind {
+            ParamType::Text => "\"/pool/a\"".to_string(),
+            ParamType::Number => p.default.unwrap_or("2").to_string(),
+            ParamType::Bool => "true".to_string(),
+            ParamType::Size => "\"1G\"".to_string(),
+            ParamType::List => "\"a b c d\"".to_string(),
+            ParamType::Choice(values) => format!("\"{}\"", values[values.len() - 1]),
+        };
+        for spec in COMMANDS {
+            for p in spec.positional.iter().chain(spec.params) {
+                match p.kind {
+                    ParamType::Number => assert!(p.default.is_none_or(|d| d.parse::<u64>().is_ok()), "{}.{}", spec.keyword, p.name),
+                    ParamType::Choice(values) => assert!(p.default.is_none_or(|d| values.contains(&d)), "{}.{}", spec.keyword, p.name),
+                    _ => {}
+                }
+            }
+            // Конструктор команды паникует, если читает параметр не из таблицы
+            let mut line = spec.keyword.to_string();
+            for p in spec.positional {
+                line.push_str(&format!(" {}", sample(p)));
+            }
+            for p in spec.params {
+                line.push_str(&format!(" {}={}", p.name, sample(p)));
+            }
+            for flag in spec.flags {
+                line.push_str(&format!(" {}", flag));
+            }
+            let parsed = InstnothParser::new(String::new()).parse_command(&line, 1);
+            // Ошибка допустима только от проверки значений самой командой
+            if let Err(e) = parsed {
+                assert!(e.starts_with(&format!("{}:", spec.keyword)), "{}: {}", line, e);
+            }
+        }
+
+        let schema = command_schema();
+        assert_eq!(schema.lines().count(), COMMANDS.len() + 2);
+        assert!(schema.contains(r#"{"keyword":"delay","positional":[{"name":"ms","type":"integer","default":100,"required":false}],"params":[],"flags":[],"block":false}"#));
+        assert!(schema.contains(r#"{"name":"default","type":"enum","values":["yes","no"],"default":"yes","required":false}],"flags":["required"]"#));
+    }
+}
@@ -0,0 +1,5489 @@
+//! Симулятор: показывает установку пакета, ничего не устанавливая
+
+use colored::*;
+use indicatif::{DecimalBytes, HumanBytes, MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::{deps::*, export::*, output::*, parser::*, sysinfo::*};
+
+// ============== Симулятор ==============
+
+/// Раздел, «созданный» в ходе запуска
+#[derive(Debug, Clone)]
+struct PartitionInfo {
+    device: String,
+    size_bytes: u64,
+    fs_type: String,
+    mount_point: String,
+    uuid: String,
+    /// Смонтирован командой mount (или подключён как swap) и попадает в fstab
+    mounted: bool,
+}
+
+/// Пакет, который install_packages «скачивает» в стиле distro:
+#[derive(Debug, Clone)]
+struct PackageFile {
+    name: String,
+    version: String,
+    /// Размер загрузки и размер после установки, в байтах
+    download: u64,
+    installed: u64,
+    /// Репозиторий из add_repo, если пакет пришёл оттуда
+    repo: Option<String>,
+}
+
+/// Сколько по настоящим часам шла одна фаза
+#[derive(Debug, Clone)]
+struct PhaseTiming {
+    package: String,
+    phase: String,
+    duration: Duration,
+}
+
+/// Ход запуска по всем пакетам: из него считаются общая полоса, оставшееся время
+/// и сводка print_summary
+#[derive(Debug, Default)]
+struct RunProgress {
+    /// Последний процент, выставленный командой progress
+    percent: u8,
+    /// Сумма весов фаз, которые будут выполнены во всех пакетах, и вес уже завершённых
+    total_weight: u32,
+    done_weight: u32,
+    /// Номер выполняемой фазы среди всех выполняемых и их число
+    phase_number: usize,
+    phase_count: usize,
+    /// Оценка оставшейся работы, мс при --speed 1
+    eta_left: u64,
+    /// Длительность выполненных фаз всех пакетов
+    timings: Vec<PhaseTiming>,
+}
+
+/// Состояние одного запуска: то, что команды «сделали» и что должны видеть последующие команды
+#[derive(Debug, Default)]
+struct RunState {
+    /// Разделы по дискам в порядке создания
+    partitions: HashMap<String, Vec<PartitionInfo>>,
+    /// Модель и размер (ГБ) диска, выбранные при первом обращении к нему
+    disks: HashMap<String, (String, u64)>,
+    /// Открытые LUKS-отображения: /dev/mapper/имя → зашифрованный раздел
+    mappings: HashMap<String, String>,
+    /// Текущая строка GRUB_CMDLINE_LINUX; пустая — значение по умолчанию
+    cmdline: String,
+    /// Пулы ZFS/Btrfs, созданные командой create_pool
+    pools: HashMap<String, PoolKind>,
+    /// Созданные RAID-массивы: /dev/md0, /dev/md1, ...
+    raid_arrays: Vec<String>,
+    /// Репозитории, добавленные add_repo, в порядке добавления
+    repos: Vec<String>,
+    /// IP-адреса ВМ и контейнеров по имени: повторное обращение показывает тот же адрес
+    vms: HashMap<String, String>,
+    containers: HashMap<String, String>,
+    /// Зеркало из select_mirror, подставляется вместо mirror:// в download
+    mirror: Option<String>,
+    /// Начатые фазы и время их начала, в порядке выполнения
+    phases: Vec<(String, Instant)>,
+    /// Снимки create_snapshot: имя → (идентификатор, число начатых к тому моменту фаз)
+    snapshots: HashMap<String, (String, usize)>,
+    /// Локали, сгенерированные generate_locales
+    locales: Vec<String>,
+    /// Железо запуска: выбирается при первом обращении (см. Simulator::hardware)
+    hardware: Option<SystemProfile>,
+}
+
+impl RunState {
+    /// Диск, которому принадлежит раздел: /dev/sda2 → /dev/sda, /dev/nvme0n1p2 → /dev/nvme0n1
+    fn parent_device(partition: &str) -> &str {
+        let trimmed = partition.trim_end_matches(|c: char| c.is_ascii_digit());
+        if trimmed.len() == partition.len() {
+            return partition;
+        }
+        match trimmed.strip_suffix('p') {
+            Some(disk) if disk.ends_with(|c: char| c.is_ascii_digit()) => disk,
+            _ => trimmed,
+        }
+    }
+
+    /// Модель и размер диска; первым диском считается накопитель из железа запуска, если
+    /// оно уже выбрано, остальные выбираются случайно
+    fn disk(&mut self, device: &str, rng: &mut impl Rng) -> (String, u64) {
+        let primary = self.disks.is_empty().then(|| self.hardware.as_ref().map(|hw| hw.disk)).flatten();
+        self.disks.entry(device.to_string())
+            .or_insert_with(|| {
+                let (vendor, model, size, _) = primary.unwrap_or_else(|| RandomSystemInfo::disk(rng));
+                (format!("{} {}", vendor, model), size)
+            })
+            .clone()
+    }
+
+    fn partition_mut(&mut self, device: &str) -> Option<&mut PartitionInfo> {
+        self.partitions.get_mut(Self::parent_device(device))?
+            .iter_mut()
+            .find(|p| p.device == device)
+    }
+
+    /// Добавляет раздел; размер "N%" считается от ещё не занятого места на диске
+    fn add_partition(&mut self, device: &str, size: &str, rng: &mut impl Rng) {
+        let disk = Self::parent_device(device).to_string();
+        let (_, disk_gb) = self.disk(&disk, rng);
+        let entries = self.partitions.entry(disk).or_default();
+        let used: u64 = entries.iter().map(|p| p.size_bytes).sum();
+        let free = (disk_gb * 1_000_000_000).saturating_sub(used);
+        let size_bytes = match size.trim().strip_suffix('%') {
+            Some(pct) => pct.trim().parse::<u64>().map_or(free, |pct| (free as u128 * pct.min(100) as u128 / 100) as u64),
+            None => parse_human_size(size).unwrap_or(0).min(free),
+        };
+        entries.retain(|p| p.device != device);
+        entries.push(PartitionInfo {
+            device: device.to_string(),
+            size_bytes,
+            fs_type: String::new(),
+            mount_point: String::new(),
+            uuid: RandomSystemInfo::uuid(rng),
+            mounted: false,
+        });
+    }
+
+    /// Разделы диска; если их не создавали, придумывается типичная разметка
+    fn partitions_of(&mut self, disk: &str, rng: &mut impl Rng) -> Vec<PartitionInfo> {
+        if !self.partitions.contains_key(disk) {
+            let (_, disk_gb) = self.disk(disk, rng);
+            let sep = if disk.ends_with(|c: char| c.is_ascii_digit()) { "p" } else { "" };
+            let swap_gb = [4, 8, 16, 32][rng.gen_range(0..4)].min(disk_gb / 8);
+            let root_pct = rng.gen_range(20..40);
+            let layout = [("512MiB", "vfat", "/boot/efi"), (&*format!("{}%", root_pct), "ext4", "/"), (&*format!("{}G", swap_gb), "swap", "[SWAP]"), ("100%", "ext4", "/home")];
+            for (n, (size, fs_type, mount_point)) in layout.iter().enumerate() {
+                let device = format!("{}{}{}", disk, sep, n + 1);
+                self.add_partition(&device, size, rng);
+                if let Some(part) = self.partition_mut(&device) {
+                    part.fs_type = fs_type.to_string();
+                    part.mount_point = mount_point.to_string();
+                    if *fs_type == "vfat" {
+                        part.uuid = RandomSystemInfo::vfat_id(rng);
+                    }
+                }
+            }
+        }
+        // Имя вроде /dev/md1p само похоже на раздел: придуманные разделы уходят диску /dev/md1
+        self.partitions.get(disk).cloned().unwrap_or_default()
+    }
+}
+
+/// Поднимается обработчиком Ctrl+C; симулятор проверяет его между командами и в длинных циклах
+pub static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Длительность паузы в ms при множителе скорости speed
+fn scaled(ms: u64, speed: f32) -> Duration {
+    Duration::from_secs_f64(ms as f64 / 1000.0 / speed as f64)
+}
+
+/// Число с запятыми между тысячами, как в выводе бенчмарков: 98432 → "98,432"
+fn grouped(n: u64) -> String {
+    let digits = n.to_string();
+    let mut out = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Подробность вывода: --quiet, обычная, --verbose
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
+}
+
+/// Что произошло в выполняемой фазе — для строки фазы в --quiet
+#[derive(Debug, Clone, Copy, Default)]
+struct PhaseStats {
+    /// Выполненные команды верхнего уровня
+    steps: u32,
+    warnings: u32,
+    errors: u32,
+}
+
+impl PhaseStats {
+    fn note(&mut self, style: TextStyle) {
+        match style {
+            TextStyle::Warning => self.warnings += 1,
+            TextStyle::Error => self.errors += 1,
+            TextStyle::Message | TextStyle::Success => {}
+        }
+    }
+
+    /// «(14 шагов, 1 предупреждение)»; нулевые предупреждения и ошибки не упоминаются
+    fn describe(&self, lang: Lang) -> String {
+        let mut parts = vec![Msg::Steps.count(lang, self.steps)];
+        if self.warnings > 0 {
+            parts.push(Msg::Warnings.count(lang, self.warnings));
+        }
+        if self.errors > 0 {
+            parts.push(Msg::Errors.count(lang, self.errors));
+        }
+        format!("({})", parts.join(", "))
+    }
+}
+
+/// Итог пакета для рамки print_footer; при нескольких пакетах main складывает их в общий
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RunStats {
+    /// Выполненные команды, включая тела retry и задачи parallel
+    pub commands: u32,
+    pub warnings: u32,
+    pub errors: u32,
+    /// Сумма size= выполненных download, в байтах
+    pub downloaded: u64,
+    /// Пакеты install_packages и зависимости install_dep
+    pub installed: u32,
+    /// Сервисы, включённые enable_service
+    pub services: u32,
+}
+
+impl RunStats {
+    fn add(&mut self, other: &RunStats) {
+        self.commands += other.commands;
+        self.warnings += other.warnings;
+        self.errors += other.errors;
+        self.downloaded += other.downloaded;
+        self.installed += other.installed;
+        self.services += other.services;
+    }
+
+    /// Предупреждения и ошибки считаются по фазам, поэтому добавляются по её окончании
+    fn add_phase(&mut self, phase: &PhaseStats) {
+        self.warnings += phase.warnings;
+        self.errors += phase.errors;
+    }
+
+    /// Успешно выполненная команда
+    pub(crate) fn count(&mut self, cmd: &Command) {
+        match cmd {
+            // Команды тел учтены по отдельности
+            Command::Retry { .. } | Command::Parallel(_) => return,
+            Command::Download { size, .. } => self.downloaded += size,
+            Command::InstallDep { .. } => self.installed += 1,
+            Command::InstallPackages(packages) => self.installed += packages.split_whitespace().count() as u32,
+            Command::EnableService(_) => self.services += 1,
+            _ => {}
+        }
+        self.commands += 1;
+    }
+
+    /// «Выполнено 87 операций · 2 предупреждения · загружено 1.40 GB»; нулевое не упоминается
+    pub fn describe(&self, lang: Lang) -> String {
+        let mut parts = vec![Msg::StatsDone.fill(lang, &[&Msg::Operations.count(lang, self.commands)])];
+        if self.warnings > 0 {
+            parts.push(Msg::Warnings.count(lang, self.warnings));
+        }
+        if self.errors > 0 {
+            parts.push(Msg::Errors.count(lang, self.errors));
+        }
+        if self.downloaded > 0 {
+            parts.push(Msg::StatsDownloaded.fill(lang, &[&DecimalBytes(self.downloaded)]));
+        }
+        if self.installed > 0 {
+            parts.push(Msg::StatsInstalled.fill(lang, &[&Msg::Packages.count(lang, self.installed)]));
+        }
+        if self.services > 0 {
+            parts.push(Msg::StatsEnabled.fill(lang, &[&Msg::Services.count(lang, self.services)]));
+        }
+        parts.join(" · ")
+    }
+}
+
+/// Достижение: название и признак команды, которая должна была выполниться за запуск
+type Achievement = (&'static str, fn(&Command) -> bool);
+
+/// Достижения карточки итогов
+const ACHIEVEMENTS: &[Achievement] = &[
+    ("Kernel Whisperer", |c| matches!(c, Command::CompileKernel { .. })),
+    ("Compiler Tamer", |c| matches!(c, Command::Compile { .. })),
+    ("Container Hoarder", |c| matches!(c, Command::DockerPull { .. } | Command::StartContainer(_))),
+    ("Clone Trooper", |c| matches!(c, Command::GitClone { .. } | Command::GitSubmoduleUpdate)),
+    ("Have You Tried Turning It Off", |c| matches!(c, Command::Reboot { .. })),
+    ("Paranoid Android", |c| matches!(c, Command::EncryptPartition { .. } | Command::HardenSsh)),
+    ("Point of No Return", |c| matches!(c, Command::FormatPartition { .. })),
+    ("Time Traveller", |c| matches!(c, Command::CreateSnapshot(_) | Command::RollbackTo(_))),
+    ("Benchmark Addict", |c| matches!(c, Command::BenchmarkCpu | Command::BenchmarkMemory | Command::BenchmarkDisk | Command::SpeedTest)),
+    ("Firmware Daredevil", |c| matches!(c, Command::UpdateFirmware { .. })),
+    ("Bootloader Hero", |c| matches!(c, Command::InstallBootloader(_) | Command::SystemdBoot { .. } | Command::UpdateGrub)),
+    ("Cloud Whisperer", |c| matches!(c, Command::ProvisionCloud { .. } | Command::CreateVm { .. })),
+    ("Never Give Up", |c| matches!(c, Command::Retry { .. })),
+];
+
+/// Достижение, когда ни одна подходящая команда не выполнялась
+const FALLBACK_ACHIEVEMENT: &str = "Professional Procrastinator";
+
+/// Какие достижения получены за запуск, для карточки итогов
+#[derive(Debug, Clone, Default, PartialEq)]
+struct FunStats {
+    /// Номера в ACHIEVEMENTS; упорядочены, чтобы выбор с --seed был воспроизводим
+    unlocked: BTreeSet<usize>,
+}
+
+impl FunStats {
+    fn add(&mut self, other: &FunStats) {
+        self.unlocked.extend(&other.unlocked);
+    }
+
+    /// Выполняемая команда; задачи parallel тоже учитываются
+    fn record(&mut self, cmd: &Command) {
+        if let Command::Parallel(children) = cmd {
+            children.iter().for_each(|c| self.record(c));
+        }
+        for (i, (_, ran)) in ACHIEVEMENTS.iter().enumerate() {
+            if ran(cmd) {
+                self.unlocked.insert(i);
+            }
+        }
+    }
+}
+
+/// Число со словом в нужной форме: 1 шаг, 3 шага, 11 шагов
+pub fn plural(n: u32, one: &str, few: &str, many: &str) -> String {
+    let word = match (n % 10, n % 100) {
+        (_, 11..=14) => many,
+        (1, _) => one,
+        (2..=4, _) => few,
+        _ => many,
+    };
+    format!("{} {}", n, word)
+}
+
+/// Настройки для [`Simulator::with_options`]: то, что программа берёт из --quick,
+/// --verbose/--quiet, --minimal, --speed, --seed, --theme, --output, --chaos и
+/// --non-interactive. Остальное — поля Simulator
+pub struct SimulatorOptions {
+    /// Без пауз и анимаций
+    pub quick: bool,
+    pub verbosity: Verbosity,
+    /// Пропускать фазы с атрибутом optional
+    pub minimal: bool,
+    /// Множитель скорости пауз: 2.0 — вдвое быстрее
+    pub speed: f32,
+    /// Зерно случайных значений: с одним seed вывод повторяется. None — случайное
+    pub seed: Option<u64>,
+    /// Цвета, значки и символы полос
+    pub theme: Theme,
+    /// Куда идёт вывод: по умолчанию цветной текст в терминал
+    pub out: Box<dyn OutputSink>,
+    /// Вероятность сбоя каждой команды в процентах; с chaos_fatal сбой прерывает установку
+    pub chaos: u8,
+    pub chaos_fatal: bool,
+    /// Отвечать на ask/choose значениями по умолчанию, даже если stdin — терминал.
+    /// Не из терминала ответы не читаются и без этого
+    pub non_interactive: bool,
+}
+
+impl Default for SimulatorOptions {
+    fn default() -> Self {
+        Self {
+            quick: false,
+            verbosity: Verbosity::Normal,
+            minimal: false,
+            speed: 1.0,
+            seed: None,
+            theme: Theme::default(),
+            out: Box::new(TerminalSink::default()),
+            chaos: 0,
+            chaos_fatal: false,
+            non_interactive: false,
+        }
+    }
+}
+
+pub struct Simulator {
+    quick_mode: bool,
+    verbosity: Verbosity,
+    pub minimal: bool,
+    /// Не выполнять команды reboot
+    pub no_reboot: bool,
+    /// Шаблоны имён фаз --only-phase и --skip-phase
+    pub only_phases: Vec<String>,
+    pub skip_phases: Vec<String>,
+    /// Отвечать на ask/choose значениями по умолчанию, не читая stdin
+    pub non_interactive: bool,
+    /// --sudo: спрашивать пароль перед первой фазой у каждого пакета, а не только с require_root
+    pub sudo: bool,
+    /// Пароль sudo уже «принят» — как настоящий sudo, второй раз за запуск не спрашивается
+    sudo_accepted: bool,
+    /// Переменные для подстановки ${...}, записанные командами choose
+    variables: HashMap<String, String>,
+    /// --uninstall: пакеты удаляются — фазы в обратном порядке, команды заменены обратными
+    pub uninstalling: bool,
+    /// --deps-depth (--skip-deps — 0): до какой глубины взяты зависимости, для плана
+    pub deps_depth: Option<usize>,
+    /// --fresh-hardware: каждая команда выбирает железо заново, а не берёт общее для запуска
+    pub fresh_hardware: bool,
+    /// --step: спрашивать перед каждой командой
+    pub step: bool,
+    /// --chaos: вероятность сбоя каждой команды в процентах; с chaos_fatal сбой прерывает установку
+    pub chaos: u8,
+    pub chaos_fatal: bool,
+    /// В --step ответили p: оставшиеся команды фазы не выполняются
+    skip_phase_rest: bool,
+    /// Строки файла для команд верхнего уровня выполняемой фазы
+    pub(crate) command_lines: Vec<usize>,
+    /// Превращать fail/fail_random и фатальные error в предупреждения
+    pub ignore_failures: bool,
+    /// on_error: "abort" выполняемого пакета
+    abort_on_error: bool,
+    /// Команда верхнего уровня, на которой оборвалась фаза: «строка N: команда» для итога
+    failed_command: Option<String>,
+    /// Код выхода, заданный последней сработавшей командой fail
+    pub exit_code: Option<i32>,
+    state: RunState,
+    /// Действия, которые команды пакета потребовали, но которые ещё не выполнены
+    pending: Vec<PendingAction>,
+    /// distro: выполняемого пакета
+    distro: Option<Distro>,
+    /// Каталог файла выполняемого пакета, от него считаются относительные пути
+    package_dir: Option<PathBuf>,
+    /// Ход запуска: фазы, веса, оценка времени и длительности для сводки
+    progress: RunProgress,
+    /// Общая полоса установки внизу экрана (все фазы всех пакетов); None — не запущена
+    total_bar: Option<ProgressBar>,
+    /// Строка состояния на общей полосе: «пакет › фаза»
+    status: String,
+    /// Единственный источник случайности запуска; с --seed вывод воспроизводим
+    pub rng: StdRng,
+    /// Весь вывод идёт через say!/say_inline! сюда: текст или события --output json
+    pub out: Box<dyn OutputSink>,
+    /// Множитель скорости пауз (--speed): 2.0 — вдвое быстрее
+    pub speed: f32,
+    /// Флаг отмены: в запуске — INTERRUPTED, в тестах — свой
+    interrupt: &'static AtomicBool,
+    /// Счётчики текущей фазы
+    phase_stats: PhaseStats,
+    /// Итог выполняемого пакета и всех пакетов запуска
+    stats: RunStats,
+    pub total_stats: RunStats,
+    /// Достижения для карточки итогов
+    fun: FunStats,
+    /// --state-file: куда после каждой фазы записывается прогресс и что в нём, кроме позиции
+    pub state_file: Option<(PathBuf, SavedProgress)>,
+    /// Продолжение прерванного запуска: (пакет, фаза), до которых фазы проигрываются молча
+    pub resume_at: Option<(usize, usize)>,
+    /// Номер выполняемого пакета в порядке установки
+    pub package_index: usize,
+    /// Ширина рамок и разделителей: --width, ширина терминала или DEFAULT_WIDTH
+    pub width: usize,
+    /// Цвета, значки и символы полос (--theme)
+    pub theme: Theme,
+    /// Язык постоянных строк вывода (--lang)
+    pub lang: Lang,
+}
+
+impl Simulator {
+    #[deprecated(note = "Simulator::with_options(SimulatorOptions { quick, verbosity, minimal, ..Default::default() })")]
+    pub fn new(quick_mode: bool, verbosity: Verbosity, minimal: bool) -> Self {
+        Self::with_options(SimulatorOptions { quick: quick_mode, verbosity, minimal, ..Default::default() })
+    }
+
+    /// Симулятор с заданными настройками; без них — как программа без флагов
+    ///
+    /// ```
+    /// use instnoth::sim::{Simulator, SimulatorOptions, Verbosity};
+    ///
+    /// let simulator = Simulator::with_options(SimulatorOptions { quick: true, verbosity: Verbosity::Quiet, ..Default::default() });
+    /// assert!(simulator.quiet());
+    /// ```
+    pub fn with_options(options: SimulatorOptions) -> Self {
+        Self {
+            quick_mode: options.quick,
+            verbosity: options.verbosity,
+            minimal: options.minimal,
+            no_reboot: false,
+            only_phases: Vec::new(),
+            skip_phases: Vec::new(),
+            non_interactive: options.non_interactive || !io::stdin().is_terminal(),
+            sudo: false,
+            sudo_accepted: false,
+            variables: HashMap::new(),
+            uninstalling: false,
+            deps_depth: None,
+            fresh_hardware: false,
+            step: false,
+            chaos: options.chaos,
+            chaos_fatal: options.chaos_fatal,
+            skip_phase_rest: false,
+            command_lines: Vec::new(),
+            ignore_failures: false,
+            abort_on_error: false,
+            failed_command: None,
+            exit_code: None,
+            state: RunState::default(),
+            pending: Vec::new(),
+            distro: None,
+            package_dir: None,
+            progress: RunProgress::default(),
+            total_bar: None,
+            status: String::new(),
+            rng: options.seed.map_or_else(StdRng::from_entropy, StdRng::seed_from_u64),
+            out: options.out,
+            speed: options.speed,
+            interrupt: &INTERRUPTED,
+            phase_stats: PhaseStats::default(),
+            stats: RunStats::default(),
+            total_stats: RunStats::default(),
+            fun: FunStats::default(),
+            state_file: None,
+            resume_at: None,
+            package_index: 0,
+            width: DEFAULT_WIDTH,
+            theme: options.theme,
+            lang: Lang::Ru,
+        }
+    }
+
+    pub fn verbose(&self) -> bool {
+        self.verbosity == Verbosity::Verbose
+    }
+
+    pub fn quiet(&self) -> bool {
+        self.verbosity == Verbosity::Quiet
+    }
+
+    /// Предупреждение команды: печатается с отступом indent и считается в статистике фазы
+    fn warn(&mut self, indent: &str, text: &str) {
+        self.phase_stats.note(TextStyle::Warning);
+        say!(self, "{}{} {}", indent, "⚠".paint(self.theme.warning), text.paint(self.theme.warning));
+    }
+
+    /// Генератор для одной команды, выведенный из общего: его можно держать,
+    /// вызывая другие методы self, а последовательность всё равно задаётся seed
+    pub(crate) fn rng(&mut self) -> StdRng {
+        StdRng::from_rng(&mut self.rng).expect("StdRng не может не засеяться")
+    }
+
+    /// Железо, которое показывают detect_*, бенчмарки и датчики: одно на весь запуск,
+    /// выбирается при первом обращении. С fresh_hardware — новое при каждом обращении
+    fn hardware(&mut self) -> SystemProfile {
+        let mut rng = self.rng();
+        if self.fresh_hardware {
+            return SystemProfile::random(&mut rng);
+        }
+        self.state.hardware.get_or_insert_with(|| SystemProfile::random(&mut rng)).clone()
+    }
+
+    /// Пауза анимации или имитации работы: в --quick пропускается, с --speed делится на множитель
+    fn pause(&self, ms: u64) {
+        if !self.quick_mode {
+            thread::sleep(scaled(ms, self.speed));
+        }
+    }
+
+    pub fn interrupted(&self) -> bool {
+        self.interrupt.load(Ordering::SeqCst)
+    }
+
+    /// Err после Ctrl+C — команда прекращается так же, как при сбое, но без повторов retry
+    fn check_interrupt(&self) -> Result<(), String> {
+        if self.interrupted() {
+            Err(self.tr(Msg::Cancelled).to_string())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// MultiProgress для нескольких полос; скрытый, если получатель вывода не анимирован
+    fn multi(&self) -> MultiProgress {
+        if self.out.animated() {
+            MultiProgress::new()
+        } else {
+            MultiProgress::with_draw_target(ProgressDrawTarget::hidden())
+        }
+    }
+
+    /// Оценка длительности фаз пакетов, которые будут выполнены, в мс при --speed 1
+    pub fn estimate(&self, packages: &[Package]) -> u64 {
+        packages.iter()
+            .flat_map(|p| &p.phases)
+            .filter(|p| self.selected(p) && !self.should_skip(p))
+            .flat_map(|p| &p.commands)
+            .map(estimated_ms)
+            .sum()
+    }
+
+    /// Сколько займут ms работы с учётом --speed; в --quick пауз нет и оценивать нечего
+    pub fn eta(&self, ms: u64) -> Option<Duration> {
+        (!self.quick_mode).then(|| scaled(ms, self.speed))
+    }
+
+    /// Строка общей полосы: «Фаза 3/7 · Осталось ~2м 10с · пакет › фаза». Номер и оценка
+    /// впереди: в узком терминале indicatif обрезает конец строки
+    fn refresh_status(&self) {
+        if let Some(bar) = &self.total_bar {
+            let mut status = self.trf(Msg::StatusPhase, &[&self.progress.phase_number, &self.progress.phase_count]);
+            if let Some(left) = self.eta(self.progress.eta_left) {
+                status.push_str(&self.trf(Msg::StatusLeft, &[&format_eta(left, self.lang)]));
+            }
+            bar.set_message(format!("{} · {}", status, self.status));
+        }
+    }
+
+    /// Запускает общую полосу по всем фазам пакетов, которые будут установлены
+    pub fn start_total_bar(&mut self, packages: &[Package]) {
+        if !self.out.animated() {
+            return;
+        }
+        self.progress.phase_count = packages.iter()
+            .flat_map(|p| &p.phases)
+            .filter(|p| self.selected(p) && !self.should_skip(p))
+            .count();
+        self.progress.phase_number = 0;
+        self.progress.eta_left = self.estimate(packages);
+        self.progress.total_weight = packages.iter()
+            .flat_map(|p| &p.phases)
+            .filter(|p| !self.should_skip(p))
+            .map(|p| p.weight)
+            .sum();
+        self.progress.done_weight = 0;
+        let bar = ProgressBar::new(1000);
+        let template = format!("{{spinner:.cyan}} [{{bar:{}.cyan/blue}}] {{percent:>3}}% {{msg}}", self.scaled(30));
+        bar.set_style(ProgressStyle::default_bar()
+            .template(&template).unwrap()
+            .progress_chars(&self.theme.bar));
+        bar.enable_steady_tick(Duration::from_millis(100));
+        self.total_bar = Some(bar);
+    }
+
+    pub fn finish_total_bar(&mut self) {
+        if let Some(bar) = self.total_bar.take() {
+            bar.finish_and_clear();
+        }
+    }
+
+    /// Выполняет f, убрав на это время общую полосу, чтобы её не разорвал обычный вывод
+    pub fn suspended<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        match self.total_bar.clone() {
+            Some(bar) => bar.suspend(|| f(self)),
+            None => f(self),
+        }
+    }
+
+    /// Общая полоса после step из steps команд фазы с весом weight
+    fn advance_total_bar(&self, weight: u32, step: usize, steps: usize) {
+        if let Some(bar) = &self.total_bar {
+            let done = self.progress.done_weight as f64 + weight as f64 * step as f64 / steps.max(1) as f64;
+            bar.set_position((done * 1000.0 / self.progress.total_weight.max(1) as f64) as u64);
+        }
+    }
+
+    /// Выполняет f без вывода, пауз и вопросов: так проигрываются фазы, пройденные до прерывания,
+    /// чтобы состояние запуска и генератор случайных чисел дошли до того же места
+    pub fn replaying<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        let out = std::mem::replace(&mut self.out, Box::new(NullSink));
+        let (speed, non_interactive, step) = (self.speed, self.non_interactive, self.step);
+        let timings = self.progress.timings.len();
+        self.speed = f32::INFINITY;
+        self.non_interactive = true;
+        self.step = false;
+        let result = f(self);
+        self.progress.timings.truncate(timings);
+        self.out = out;
+        self.speed = speed;
+        self.non_interactive = non_interactive;
+        self.step = step;
+        result
+    }
+
+    /// Фаза index выполняемого пакета уже пройдена прерванным запуском
+    fn replayed(&self, index: usize) -> bool {
+        self.resume_at.is_some_and(|at| (self.package_index, index) < at)
+    }
+
+    /// Записывает в --state-file, что в выполняемом пакете завершено phase фаз
+    pub fn save_progress(&mut self, phase: usize, command: usize) {
+        let Some((path, progress)) = &mut self.state_file else {
+            return;
+        };
+        progress.package = self.package_index;
+        progress.phase = phase;
+        progress.command = command;
+        if let Err(e) = progress.save(path) {
+            eprintln!("{} {}", "⚠".paint(self.theme.warning), Msg::StateWriteFailed.fill(self.lang, &[&format!("{:?}", path), &e]));
+        }
+    }
+
+    /// Спрашивает, продолжить ли прерванную установку; без ответа — продолжить
+    pub fn confirm_resume(&mut self, saved: &SavedProgress, packages: &[Package]) -> bool {
+        let package = &packages[saved.package];
+        let Some(phase) = package.phases.get(saved.phase) else {
+            return false;
+        };
+        say!(self);
+        let mut found = self.trf(Msg::ResumeFound, &[&package.name, &saved.phase, &package.phases.len()]);
+        if saved.command > 0 {
+            found.push_str(&self.trf(Msg::ResumeAtCommand, &[&(saved.command + 1), &phase.name]));
+        }
+        say!(self, "  {} {}", "⟳".paint(self.theme.accent), found);
+        say_inline!(self, "  {} {} {} ", "?".paint(self.theme.accent), self.trf(Msg::ResumeAsk, &[&(saved.phase + 1), &phase.name]), "[Y/n]".dimmed());
+        loop {
+            match self.read_answer() {
+                None => {
+                    if self.non_interactive || self.quick_mode {
+                        self.type_answer("y");
+                    }
+                    return true;
+                }
+                Some(input) => match InstnothParser::parse_answer(&input) {
+                    Some(answer) => return answer,
+                    None => say_inline!(self, "    {} {} ", "⚠".paint(self.theme.warning), self.tr(Msg::AnswerYesNo)),
+                },
+            }
+        }
+    }
+
+    /// Показывает установку пакета: заголовок, фазы по очереди и итог. Err — установка
+    /// прервана сбоем команды или Ctrl+C
+    pub fn run(&mut self, package: &Package) -> Result<(), String> {
+        self.pending.clear();
+        self.distro = package.distro;
+        self.abort_on_error = package.abort_on_error;
+        self.stats = RunStats::default();
+        self.package_dir = package.file_path.as_deref().and_then(Path::parent).map(Path::to_path_buf);
+        self.out.event(Event::new("package_start")
+            .with("name", package.name.as_str())
+            .with("version", package.version.as_str()));
+        self.suspended(|s| s.print_header(package));
+        if package.require_root || self.sudo {
+            self.suspended(|s| s.ask_sudo_password())?;
+        }
+
+        // Общий прогресс считается по весам фаз, которые будут выполнены
+        let total_weight: u32 = package.phases.iter()
+            .filter(|p| !self.should_skip(p))
+            .map(|p| p.weight)
+            .sum();
+        let mut done_weight: u32 = 0;
+
+        for (index, phase) in package.phases.iter().enumerate() {
+            if !self.selected(phase) {
+                continue;
+            }
+            if self.should_skip(phase) {
+                self.suspended(|s| {
+                    say!(s);
+                    say!(s, "{} {} {}", "▷".dimmed(), phase.name.dimmed(), s.tr(Msg::PhaseOptionalSkipped).dimmed());
+                });
+                self.out.event(Event::new("phase_skipped").with("name", phase.name.as_str()));
+                continue;
+            }
+            let overall = (done_weight * 100).checked_div(total_weight).unwrap_or(100);
+            self.progress.phase_number += 1;
+            if self.replayed(index) {
+                let result = self.replaying(|s| s.run_phase(phase, overall));
+                self.stats.add_phase(&self.phase_stats);
+                if let Err(e) = result {
+                    self.suspended(|s| s.print_failure_footer(package, phase, &e));
+                    return Err(e);
+                }
+                self.suspended(|s| {
+                    say!(s);
+                    say!(s, "{} {} {}", "▷".dimmed(), phase.name.dimmed(), s.tr(Msg::PhaseDoneEarlier).dimmed());
+                });
+                done_weight += phase.weight;
+                self.progress.done_weight += phase.weight;
+                continue;
+            }
+            self.status = format!("{} › {}", package.name, phase.name);
+            self.refresh_status();
+            let started = Instant::now();
+            let result = self.run_phase(phase, overall);
+            self.stats.add_phase(&self.phase_stats);
+            let duration = started.elapsed();
+            self.out.event(Event::new("phase_done")
+                .with("name", phase.name.as_str())
+                .with("ok", result.is_ok())
+                .with("duration_ms", duration.as_millis() as u64));
+            self.progress.timings.push(PhaseTiming {
+                package: package.name.clone(),
+                phase: phase.name.clone(),
+                duration,
+            });
+            if let Err(e) = result {
+                self.save_progress(index, self.phase_stats.steps as usize);
+                if self.interrupted() {
+                    self.suspended(|s| s.print_cancel_footer(package, index));
+                    self.out.event(Event::new("package_cancelled")
+                        .with("name", package.name.as_str())
+                        .with("phase", phase.name.as_str()));
+                    return Err(e);
+                }
+                self.suspended(|s| s.print_failure_footer(package, phase, &e));
+                self.out.event(Event::new("package_failed")
+                    .with("name", package.name.as_str())
+                    .with("reason", e.as_str())
+                    .with("code", self.exit_code.unwrap_or(1)));
+                return Err(e);
+            }
+            done_weight += phase.weight;
+            self.progress.done_weight += phase.weight;
+            self.save_progress(index + 1, 0);
+        }
+
+        self.total_stats.add(&self.stats);
+        self.suspended(|s| s.print_footer(package));
+        let pending: Vec<String> = self.pending.iter().map(|a| a.command().to_string()).collect();
+        self.out.event(Event::new("package_done")
+            .with("name", package.name.as_str())
+            .with("version", package.version.as_str())
+            .with("pending", pending.as_slice()));
+        Ok(())
+    }
+
+    /// Строка каталога на языке вывода
+    pub fn tr(&self, msg: Msg) -> &'static str {
+        msg.text(self.lang)
+    }
+
+    /// Строка каталога со значениями вместо {}
+    pub fn trf(&self, msg: Msg, args: &[&dyn fmt::Display]) -> String {
+        msg.fill(self.lang, args)
+    }
+
+    /// Число со словом каталога в нужной форме: «3 пакета», «3 packages»
+    pub fn trn(&self, msg: Msg, n: u32) -> String {
+        msg.count(self.lang, n)
+    }
+
+    /// Размер, заданный для ширины DEFAULT_WIDTH, пересчитанный на текущую ширину
+    fn scaled(&self, size: usize) -> usize {
+        size * self.width / DEFAULT_WIDTH
+    }
+
+    /// Двойная линия итогов: как рамка шапки без углов
+    pub fn rule(&self) -> String {
+        "═".repeat(self.width - 2)
+    }
+
+    /// Линия под заголовком фазы
+    fn separator(&self) -> String {
+        "─".repeat(self.scaled(50))
+    }
+
+    /// Рамка во всю ширину с заголовком; длинный заголовок переносится по словам
+    pub fn print_banner(&self, title: &str) {
+        let inner = self.width - 2;
+        say!(self, "{}", format!("╔{}╗", "═".repeat(inner)).paint(self.theme.accent));
+        for line in wrap(title, inner - 2) {
+            say!(self, "{}", format!("║  {:<w$}║", line, w = inner - 2).paint(self.theme.accent));
+        }
+        say!(self, "{}", format!("╚{}╝", "═".repeat(inner)).paint(self.theme.accent));
+    }
+
+    fn print_header(&self, package: &Package) {
+        say!(self);
+        let title = if self.uninstalling { Msg::Uninstaller } else { Msg::Installer };
+        self.print_banner(&format!("{} {}", self.theme.name, self.tr(title)));
+        say!(self);
+        say!(self, "{}:    {}", self.tr(Msg::LabelPackage).paint(self.theme.success).bold(), package.name.white().bold());
+        say!(self, "{}:    {}", self.tr(Msg::LabelVersion).paint(self.theme.success).bold(), package.version.white());
+        if !package.description.is_empty() {
+            say!(self, "{}:", self.tr(Msg::LabelDescription).paint(self.theme.success).bold());
+            for line in wrap(&package.description, self.width - 2) {
+                say!(self, "  {}", line.white().dimmed());
+            }
+        }
+        if !package.author.is_empty() {
+            say!(self, "{}:     {}", self.tr(Msg::LabelAuthor).paint(self.theme.success).bold(), package.author.white());
+        }
+        if !package.license.is_empty() {
+            say!(self, "{}:    {}", self.tr(Msg::LabelLicense).paint(self.theme.success).bold(), package.license.white());
+        }
+        if !package.homepage.is_empty() {
+            say!(self, "{}:   {}", self.tr(Msg::LabelHomepage).paint(self.theme.success).bold(), package.homepage.paint(self.theme.accent).underline());
+        }
+        if !package.size.is_empty() {
+            say!(self, "{}:       {}", self.tr(Msg::LabelSize).paint(self.theme.success).bold(), package.size.white());
+        }
+        if !package.arch.is_empty() {
+            say!(self, "{}:       {}", self.tr(Msg::LabelArch).paint(self.theme.success).bold(), package.arch.white());
+        }
+        if let Some(distro) = package.distro {
+            say!(self, "{}:     {}", self.tr(Msg::LabelDistro).paint(self.theme.success).bold(), distro.name().white());
+        }
+        if let Some(eta) = self.eta(self.estimate(std::slice::from_ref(package))).filter(|_| !self.uninstalling) {
+            say!(self, "{}:        {}", self.tr(Msg::LabelEta).paint(self.theme.success).bold(), format_eta(eta, self.lang).white());
+        }
+        if !package.depends.is_empty() {
+            let depends: Vec<String> = package.depends.iter().map(|d| d.to_string()).collect();
+            say!(self, "{}:   {}", self.tr(Msg::LabelDepends).paint(self.theme.success).bold(), depends.join(", ").paint(self.theme.warning));
+        }
+        say!(self);
+        say!(self, "{}", "─".repeat(self.width - 2).dimmed());
+        say!(self);
+    }
+
+    /// instnoth export: пакеты в порядке установки с теми же фазами, что прошли бы
+    /// --only-phase, --skip-phase и --minimal
+    pub fn export_script(&self, packages: &[Package], out: &mut dyn ScriptBackend) {
+        let names: Vec<String> = packages.iter().map(|p| format!("{} {}", p.name, p.version)).collect();
+        out.begin(&self.trf(Msg::ScriptTitle, &[&names.join(", ")]));
+        for package in packages {
+            out.line(ScriptStyle::Plain, "");
+            out.line(ScriptStyle::Heading, &"═".repeat(self.width));
+            out.line(ScriptStyle::Heading, &format!("  {} {}", package.name, package.version));
+            if !package.description.is_empty() {
+                out.line(ScriptStyle::Dim, &format!("  {}", package.description));
+            }
+            out.line(ScriptStyle::Heading, &"═".repeat(self.width));
+            let phases: Vec<&Phase> = package.phases.iter().filter(|p| !self.should_skip(p)).collect();
+            let total_weight: u32 = phases.iter().map(|p| p.weight).sum();
+            let mut done_weight = 0;
+            for (index, phase) in phases.iter().enumerate() {
+                out.line(ScriptStyle::Plain, "");
+                out.line(ScriptStyle::Heading, &format!("[{}/{}] {}", index + 1, phases.len(), phase.name));
+                for cmd in &phase.commands {
+                    export_command(cmd, out);
+                }
+                // Фаза без своих progress — общий прогресс по весам фаз, как у полосы установки
+                done_weight += phase.weight;
+                if !phase.commands.iter().any(|c| matches!(c, Command::Progress(_))) {
+                    out.progress((done_weight * 100).checked_div(total_weight).unwrap_or(100) as u8);
+                }
+            }
+            out.line(ScriptStyle::Plain, "");
+            out.line(ScriptStyle::Success, &format!("✓ {}", self.trf(Msg::InstalledOk, &[&package.name])));
+        }
+        out.line(ScriptStyle::Plain, "");
+    }
+
+    /// Симулятор для одного пакета --parallel: те же настройки, свой вывод, своё состояние
+    /// и свой генератор, выведенный из общего (с --seed запуск всё равно воспроизводим)
+    fn lane(&mut self, out: Box<dyn OutputSink>) -> Simulator {
+        let mut lane = Simulator::with_options(SimulatorOptions {
+            quick: self.quick_mode,
+            verbosity: self.verbosity,
+            minimal: self.minimal,
+            speed: self.speed,
+            seed: None,
+            theme: self.theme.clone(),
+            out,
+            chaos: self.chaos,
+            chaos_fatal: self.chaos_fatal,
+            // Несколько пакетов не могут по очереди спрашивать одно stdin
+            non_interactive: true,
+        });
+        lane.no_reboot = self.no_reboot;
+        lane.only_phases = self.only_phases.clone();
+        lane.skip_phases = self.skip_phases.clone();
+        lane.sudo = self.sudo;
+        lane.sudo_accepted = self.sudo_accepted;
+        lane.ignore_failures = self.ignore_failures;
+        lane.interrupt = self.interrupt;
+        lane.width = self.width;
+        lane.lang = self.lang;
+        lane.rng = self.rng();
+        // Пакеты в соседних потоках ставятся на ту же машину
+        lane.fresh_hardware = self.fresh_hardware;
+        if !self.fresh_hardware {
+            lane.state.hardware = Some(self.hardware());
+        }
+        lane
+    }
+
+    /// --parallel: до jobs пакетов одновременно, каждый в своём потоке. Пакет начинается,
+    /// когда установлены все его зависимости (deps — номера в packages); после сбоя новые
+    /// пакеты не начинаются, а начатые доустанавливаются. Результат по каждому пакету;
+    /// None — пакет не начинался
+    pub fn run_parallel(&mut self, packages: &[Package], deps: &[Vec<usize>], jobs: usize) -> Vec<Option<Result<(), String>>> {
+        const LANE_COLORS: [Color; 5] = [Color::Cyan, Color::Magenta, Color::Yellow, Color::Green, Color::Blue];
+        let shared: Arc<dyn OutputSink> = Arc::from(std::mem::replace(&mut self.out, Box::new(NullSink)));
+        let mut results: Vec<Option<Result<(), String>>> = vec![None; packages.len()];
+        let mut started = vec![false; packages.len()];
+        let (tx, rx) = mpsc::channel();
+
+        thread::scope(|scope| {
+            let mut running = 0;
+            let mut failed = false;
+            loop {
+                for (i, pkg) in packages.iter().enumerate() {
+                    let ready = deps[i].iter().all(|&d| matches!(results[d], Some(Ok(()))));
+                    if running == jobs || failed || self.interrupted() || started[i] || !ready {
+                        continue;
+                    }
+                    let prefix = format!("[{}]", pkg.name).color(LANE_COLORS[i % LANE_COLORS.len()]).to_string();
+                    let mut lane = self.lane(Box::new(LaneSink::new(prefix, Arc::clone(&shared))));
+                    let tx = tx.clone();
+                    scope.spawn(move || {
+                        let result = lane.run(pkg);
+                        // Получатель ждёт, пока running > 0, поэтому отправка не теряется
+                        tx.send((i, lane, result)).unwrap();
+                    });
+                    started[i] = true;
+                    running += 1;
+                }
+                if running == 0 {
+                    break;
+                }
+                let (i, lane, result) = rx.recv().unwrap();
+                running -= 1;
+                self.total_stats.add(&lane.total_stats);
+                self.fun.add(&lane.fun);
+                self.progress.timings.extend(lane.progress.timings);
+                if result.is_err() {
+                    failed = true;
+                    self.exit_code = self.exit_code.or(lane.exit_code);
+                }
+                results[i] = Some(result);
+            }
+        });
+
+        // Потоки завершены, последняя ссылка на получатель снова у симулятора
+        self.out = Box::new(LaneSink::new(String::new(), shared));
+        results
+    }
+
+    /// --uninstall: фазы пакета в обратном порядке, в каждой — обратные команды от последней к первой
+    pub fn uninstall(&mut self, package: &Package) -> Result<(), String> {
+        self.distro = package.distro;
+        self.stats = RunStats::default();
+        self.package_dir = package.file_path.as_deref().and_then(Path::parent).map(Path::to_path_buf);
+        self.suspended(|s| s.print_header(package));
+        if package.require_root || self.sudo {
+            self.suspended(|s| s.ask_sudo_password())?;
+        }
+
+        let phases: Vec<&Phase> = package.phases.iter().rev().filter(|p| !self.should_skip(p)).collect();
+        for phase in phases {
+            self.out.mute(self.quiet());
+            say!(self);
+            say!(self, "{} {}", "◀".paint(self.theme.heading).bold(), self.trf(Msg::Rollback, &[&phase.name]).paint(self.theme.heading).bold());
+            say!(self, "{}", self.separator().dimmed());
+            self.phase_stats = PhaseStats::default();
+            self.failed_command = None;
+            let started = Instant::now();
+            let result = self.uninstall_commands(&phase.commands);
+            self.stats.add_phase(&self.phase_stats);
+            self.progress.timings.push(PhaseTiming {
+                package: package.name.clone(),
+                phase: phase.name.clone(),
+                duration: started.elapsed(),
+            });
+            if self.quiet() {
+                self.out.mute(false);
+                let status = if result.is_ok() { "✓".paint(self.theme.success) } else { "✗".paint(self.theme.error) };
+                say!(self, "{} {} {} {} {}", "◀".paint(self.theme.heading).bold(), self.trf(Msg::Rollback, &[&phase.name]).paint(self.theme.heading).bold(), "...".dimmed(), status, self.phase_stats.describe(self.lang).dimmed());
+            }
+            if let Err(e) = result {
+                self.print_failure_footer(package, phase, &e);
+                return Err(e);
+            }
+        }
+
+        self.total_stats.add(&self.stats);
+        say!(self);
+        say!(self, "{}", self.rule().paint(self.theme.success));
+        say!(self, "  {} {}", "✓".paint(self.theme.success).bold(), self.trf(Msg::UninstalledOk, &[&package.name]).paint(self.theme.success));
+        say!(self, "    {}", self.stats.describe(self.lang).dimmed());
+        say!(self, "{}", self.rule().paint(self.theme.success));
+        Ok(())
+    }
+
+    /// Обратные команды для commands в обратном порядке; тела retry и parallel разворачиваются на месте
+    fn uninstall_commands(&mut self, commands: &[Command]) -> Result<(), String> {
+        for cmd in commands.iter().rev() {
+            match cmd {
+                Command::Retry { body, .. } | Command::Parallel(body) => self.uninstall_commands(body)?,
+                _ => match invert(cmd, self.lang) {
+                    Some(inverse) => {
+                        self.execute_command(&inverse)?;
+                        self.phase_stats.steps += 1;
+                    }
+                    None if self.verbose() => {
+                        say!(self, "  {} {} {}", "▷".dimmed(), cmd.kind().dimmed(), self.tr(Msg::NoInverse).dimmed());
+                    }
+                    None => {}
+                },
+            }
+        }
+        Ok(())
+    }
+
+    fn print_footer(&self, package: &Package) {
+        say!(self);
+        say!(self, "{}", self.rule().paint(self.theme.success));
+        say!(self, "  {} {}", "✓".paint(self.theme.success).bold(), self.trf(Msg::InstalledOk, &[&package.name]).paint(self.theme.success));
+        say!(self, "    {}", self.stats.describe(self.lang).dimmed());
+        for action in &self.pending {
+            say!(self, "  {} {}", "⚠".paint(self.theme.warning), self.trf(Msg::PendingAction, &[&action.command()]).paint(self.theme.warning));
+        }
+        say!(self, "{}", self.rule().paint(self.theme.success));
+        say!(self);
+    }
+
+    /// Шуточная карточка после установки: «сэкономленное» место — размеры пакетов и
+    /// загрузок, достижение — случайное из полученных
+    pub fn print_fun_stats(&mut self, packages: &[Package]) {
+        let saved = packages.iter().filter_map(|p| parse_human_size(&p.size)).sum::<u64>() + self.total_stats.downloaded;
+        let fake = packages.len() as u32 + self.total_stats.installed;
+        let achievement = match self.fun.unlocked.len() {
+            0 => FALLBACK_ACHIEVEMENT,
+            n => ACHIEVEMENTS[*self.fun.unlocked.iter().nth(self.rng.gen_range(0..n)).unwrap()].0,
+        };
+        say!(self, "  {} {}", "★".paint(self.theme.accent), self.tr(Msg::FunTitle).bold());
+        say!(self, "    {}", self.trf(Msg::FunSaved, &[&DecimalBytes(saved)]));
+        say!(self, "    {}", self.trf(Msg::FunFake, &[&fake, &DecimalBytes(self.total_stats.downloaded)]));
+        say!(self, "    {} {}", "🏆", self.trf(Msg::FunAchievement, &[&achievement]).paint(self.theme.warning).bold());
+        say!(self);
+    }
+
+    /// Сводка времени: фазы с длительностью, при нескольких пакетах — с итогом по каждому
+    pub fn print_summary(&self) {
+        if self.progress.timings.is_empty() {
+            return;
+        }
+        // Короткие интервалы (в --quick) показываются в миллисекундах, а не округляются до нуля
+        let format = |d: Duration| {
+            let secs = d.as_secs_f64();
+            if secs < 0.001 {
+                self.trf(Msg::Micros, &[&d.as_micros()])
+            } else if secs < 1.0 {
+                self.trf(Msg::Millis, &[&format!("{:.1}", secs * 1000.0)])
+            } else if secs < 60.0 {
+                self.trf(Msg::Seconds, &[&format!("{:.1}", secs)])
+            } else {
+                self.trf(Msg::Minutes, &[&(d.as_secs() / 60), &format!("{:02}", d.as_secs() % 60)])
+            }
+        };
+        let mut packages: Vec<&str> = Vec::new();
+        for timing in &self.progress.timings {
+            if !packages.contains(&timing.package.as_str()) {
+                packages.push(&timing.package);
+            }
+        }
+        let multi = packages.len() > 1;
+        let indent = if multi { 6 } else { 4 };
+        let width = self.progress.timings.iter().map(|t| t.phase.chars().count() + indent)
+            .chain(packages.iter().map(|p| p.chars().count() + 4))
+            .max()
+            .unwrap_or(0)
+            .max(10);
+
+        say!(self, "  {} {}", "⏱".normal(), self.tr(Msg::TimingTitle));
+        for package in &packages {
+            let phases: Vec<&PhaseTiming> = self.progress.timings.iter().filter(|t| t.package == *package).collect();
+            if multi {
+                let subtotal: Duration = phases.iter().map(|t| t.duration).sum();
+                say!(self, "    {}{}", format!("{:<w$}", package, w = width - 4).paint(self.theme.accent).bold(), format!("{:>12}", format(subtotal)).paint(self.theme.accent));
+            }
+            for timing in phases {
+                say!(self, "{}{:<w$}{:>12}", " ".repeat(indent), timing.phase, format(timing.duration), w = width - indent);
+            }
+        }
+        let total: Duration = self.progress.timings.iter().map(|t| t.duration).sum();
+        say!(self, "    {}", "─".repeat(width - 4 + 12).dimmed());
+        say!(self, "    {}{}", format!("{:<w$}", self.tr(Msg::TimingTotal), w = width - 4).bold(), format!("{:>12}", format(total)).bold());
+        say!(self);
+    }
+
+    fn require(&mut self, action: PendingAction) {
+        if !self.pending.contains(&action) {
+            self.pending.push(action);
+        }
+    }
+
+    /// Итог сбоя: на какой фазе и, если известно, на какой команде остановилась установка
+    fn print_failure_footer(&self, package: &Package, phase: &Phase, reason: &str) {
+        say!(self);
+        say!(self, "{}", self.rule().paint(self.theme.error));
+        let what = if self.uninstalling { Msg::UninstallFailed } else { Msg::InstallFailed };
+        say!(self, "  {} {}", "✗".paint(self.theme.error).bold(), self.trf(what, &[&package.name]).paint(self.theme.error));
+        let place = match &self.failed_command {
+            Some(cmd) => self.trf(Msg::FailedPhaseCommand, &[&phase.name, cmd]),
+            None => self.trf(Msg::FailedPhase, &[&phase.name]),
+        };
+        say!(self, "    {}", place.paint(self.theme.error));
+        say!(self, "    {}", reason.paint(self.theme.error));
+        say!(self, "{}", self.rule().paint(self.theme.error));
+        say!(self);
+    }
+
+    /// Итог после Ctrl+C: какие фазы пакета выполнены, на какой остановились и что осталось
+    fn print_cancel_footer(&self, package: &Package, current: usize) {
+        say!(self);
+        say!(self, "{}", self.rule().paint(self.theme.warning));
+        say!(self, "  {} {}", "⚠".paint(self.theme.warning).bold(), self.tr(Msg::Cancelled).paint(self.theme.warning).bold());
+        let done = package.phases[..current].iter().filter(|p| !self.should_skip(p)).count();
+        let total = package.phases.iter().filter(|p| !self.should_skip(p)).count();
+        say!(self, "    {}", self.trf(Msg::CancelledPhases, &[&package.name, &done, &total]));
+        for (i, phase) in package.phases.iter().enumerate() {
+            if !self.selected(phase) {
+                continue;
+            }
+            if self.should_skip(phase) {
+                say!(self, "    {} {}", "▷".dimmed(), self.trf(Msg::PhaseSkipped, &[&phase.name]).dimmed());
+            } else if i < current {
+                say!(self, "    {} {}", "✓".paint(self.theme.success), phase.name);
+            } else if i == current {
+                say!(self, "    {} {}", "✗".paint(self.theme.warning), self.trf(Msg::PhaseInterrupted, &[&phase.name]).paint(self.theme.warning));
+            } else {
+                say!(self, "    {} {}", "○".dimmed(), phase.name.dimmed());
+            }
+        }
+        say!(self, "{}", self.rule().paint(self.theme.warning));
+        say!(self);
+    }
+
+    /// --dry-run: дерево пакетов, фаз и команд с разобранными параметрами, без выполнения;
+    /// повторно указанные пакеты — строкой о том, через что они уже в плане
+    pub fn print_plan(&self, packages: &[Package], duplicates: &[Duplicate]) {
+        say!(self);
+        say!(self, "{}", self.tr(Msg::DryRunPlan).paint(self.theme.success).bold());
+        if let Some(line) = self.deps_depth_line() {
+            say!(self, "{}", line);
+        }
+        let mut commands = 0;
+        for pkg in packages {
+            say!(self);
+            let file = pkg.file_path.as_deref().map(|p| format!("({})", p.display())).unwrap_or_default();
+            say!(self, "{} {} {}", pkg.name.white().bold(), pkg.version, file.dimmed());
+            let phases: Vec<&Phase> = pkg.phases.iter().filter(|p| self.selected(p)).collect();
+            for (i, phase) in phases.iter().enumerate() {
+                let (branch, indent) = if i + 1 == phases.len() { ("└─", "   ") } else { ("├─", "│  ") };
+                let weight = format!("weight={}", phase.weight);
+                if self.should_skip(phase) {
+                    say!(self, "{} {} {} {}", branch, phase.name.dimmed(), weight.dimmed(), self.tr(Msg::PhaseOptionalSkipped).dimmed());
+                    continue;
+                }
+                say!(self, "{} {} {}", branch, phase.name.paint(self.theme.heading).bold(), weight.dimmed());
+                commands += self.print_plan_commands(pkg, &phase.commands, &phase.command_lines, indent);
+            }
+        }
+        if !duplicates.is_empty() {
+            say!(self);
+        }
+        for duplicate in duplicates {
+            say!(self, "{} {}", duplicate.package.white().bold(), self.trf(Msg::PlannedVia, &[&duplicate.via]).dimmed());
+        }
+        say!(self);
+        say!(self, "{}", self.trf(Msg::PlanTotal, &[&self.trn(Msg::Packages, packages.len() as u32), &self.trn(Msg::Commands, commands)]));
+    }
+
+    /// «Глубина зависимостей: 1 (только прямые)» для планов; без ограничения — None
+    pub fn deps_depth_line(&self) -> Option<String> {
+        let depth = deps_depth_value(self.lang, Some(self.deps_depth?));
+        Some(format!("{}: {}", self.tr(Msg::DepsDepth).paint(self.theme.success).bold(), depth))
+    }
+
+    /// Ветви дерева --dry-run для команд; тела retry и parallel — вложенными ветвями
+    fn print_plan_commands(&self, pkg: &Package, commands: &[Command], lines: &[usize], prefix: &str) -> u32 {
+        let mut count = 0;
+        for (i, cmd) in commands.iter().enumerate() {
+            let (branch, indent) = if i + 1 == commands.len() { ("└─", "   ") } else { ("├─", "│  ") };
+            let line = lines.get(i);
+            let place = line.map(|l| format!("{:>4} ", l)).unwrap_or_default();
+            let defaults = line.and_then(|l| pkg.defaulted.get(l))
+                .map(|names| self.trf(Msg::Defaulted, &[&names.join(", ")]))
+                .unwrap_or_default();
+            say!(self, "{}{} {}{}{}", prefix, branch, place.dimmed(), cmd, defaults.paint(self.theme.warning));
+            count += 1;
+            let nested = format!("{}{}", prefix, indent);
+            count += match cmd {
+                Command::Retry { body, body_lines, .. } => self.print_plan_commands(pkg, body, body_lines, &nested),
+                Command::Parallel(children) => self.print_plan_commands(pkg, children, &[], &nested),
+                _ => 0,
+            };
+        }
+        count
+    }
+
+    fn should_skip(&self, phase: &Phase) -> bool {
+        !self.selected(phase) || (self.minimal && phase.optional)
+    }
+
+    /// Фаза проходит --only-phase и --skip-phase; отобранные ими фазы в запуск не входят вовсе
+    fn selected(&self, phase: &Phase) -> bool {
+        let matches = |patterns: &[String]| patterns.iter().any(|p| glob_match(p, &phase.name));
+        (self.only_phases.is_empty() || matches(&self.only_phases)) && !matches(&self.skip_phases)
+    }
+
+    fn run_phase(&mut self, phase: &Phase, overall: u32) -> Result<(), String> {
+        // --quiet: команды выполняются молча (полный вывод остаётся в журнале),
+        // от фазы на экране одна строка с итогом
+        self.out.mute(self.quiet());
+        self.suspended(|s| {
+            say!(s);
+            say!(s, "{} {} {}", "▶".paint(s.theme.heading).bold(), phase.name.paint(s.theme.heading).bold(), format!("[{}%]", overall).dimmed());
+            say!(s, "{}", s.separator().dimmed());
+        });
+        self.out.event(Event::new("phase_start")
+            .with("name", phase.name.as_str())
+            .with("weight", phase.weight)
+            .with("overall", overall));
+
+        self.state.phases.push((phase.name.clone(), Instant::now()));
+        self.phase_stats = PhaseStats::default();
+        self.failed_command = None;
+        self.skip_phase_rest = false;
+        self.command_lines = phase.command_lines.clone();
+        if !self.quiet() {
+            return self.execute_commands(&phase.commands, Some(phase.weight));
+        }
+
+        let result = self.execute_commands(&phase.commands, Some(phase.weight));
+        self.out.mute(false);
+        let status = if result.is_ok() { "✓".paint(self.theme.success) } else { "✗".paint(self.theme.error) };
+        self.suspended(|s| {
+            say!(s, "{} {} {} {} {}", "▶".paint(s.theme.heading).bold(), phase.name.paint(s.theme.heading).bold(), "...".dimmed(), status, s.phase_stats.describe(s.lang).dimmed());
+        });
+        result
+    }
+
+    /// phase_weight задан для команд верхнего уровня фазы: после каждой сдвигается общая полоса
+    fn execute_commands(&mut self, commands: &[Command], phase_weight: Option<u32>) -> Result<(), String> {
+        for (i, cmd) in commands.iter().enumerate() {
+            if self.skip_phase_rest {
+                break;
+            }
+            if self.step {
+                let line = phase_weight.and_then(|_| self.command_lines.get(i).copied());
+                if !self.suspended(|s| s.step_through(cmd, line))? {
+                    continue;
+                }
+            }
+            // В --quiet вопросы всё равно показываются, иначе отвечать было бы не на что
+            let asks = self.quiet() && !self.non_interactive
+                && matches!(cmd, Command::Ask { .. } | Command::Choose { .. } | Command::ShowLicense { .. });
+            if asks {
+                self.out.mute(false);
+            }
+            // За лицензией сразу идёт ask — вопрос заменяет финальную паузу (END)
+            let result = if let Command::ShowLicense { id_or_path } = cmd {
+                let ask_follows = matches!(commands.get(i + 1), Some(Command::Ask { .. }));
+                self.suspended(|s| s.show_license(id_or_path, ask_follows))
+            } else if let Command::Delay(_) = cmd {
+                // Пауза ничего не выводит — общая полоса остаётся на экране
+                self.execute_command(cmd)
+            } else {
+                self.suspended(|s| s.execute_command(cmd))
+            };
+            if let Err(e) = result {
+                if phase_weight.is_some() {
+                    let place = self.command_lines.get(i).map(|l| self.trf(Msg::AtLine, &[l])).unwrap_or_default();
+                    self.failed_command = Some(format!("{}{}", place, cmd));
+                }
+                return Err(e);
+            }
+            if asks {
+                self.out.mute(true);
+            }
+            if let Some(weight) = phase_weight {
+                self.phase_stats.steps += 1;
+                self.advance_total_bar(weight, i + 1, commands.len());
+                self.progress.eta_left = self.progress.eta_left.saturating_sub(estimated_ms(cmd));
+                self.refresh_status();
+            }
+        }
+        Ok(())
+    }
+
+    /// --step: показывает команду и ждёт решения в stdin. Ok(false) — команду пропустить,
+    /// q или конец ввода прерывают установку так же, как Ctrl+C
+    fn step_through(&mut self, cmd: &Command, line: Option<usize>) -> Result<bool, String> {
+        let place = line.map(|l| self.trf(Msg::AtLine, &[&l])).unwrap_or_default();
+        eprintln!("  {} {}{}", "⏸".paint(self.theme.accent), place.dimmed(), cmd.to_string().bold());
+        loop {
+            eprint!("    {} ", self.tr(Msg::StepPrompt).dimmed());
+            let mut input = String::new();
+            let answer = match io::stdin().lock().read_line(&mut input) {
+                Ok(0) | Err(_) => "q",
+                Ok(_) => input.trim(),
+            };
+            match answer {
+                "" => return Ok(true),
+                "s" => return Ok(false),
+                "p" => {
+                    self.skip_phase_rest = true;
+                    return Ok(false);
+                }
+                "q" => {
+                    self.interrupt.store(true, Ordering::SeqCst);
+                    return Err(self.tr(Msg::Cancelled).to_string());
+                }
+                _ => {}
+            }
+        }
+    }
+
+    pub(crate) fn parallel(&mut self, children: &[Command]) -> Result<(), String> {
+        say!(self, "  {} {}", "⇉".paint(self.theme.accent), self.trf(Msg::Parallel, &[&children.len()]));
+        // Без анимации задачи выполняются по очереди, каждая печатает свой итог
+        if self.quick_mode {
+            for child in children {
+                self.execute_command(child)?;
+            }
+            return Ok(());
+        }
+
+        let multi = self.multi();
+        // Каждой задаче — свой генератор из общего, чтобы не делить его между потоками
+        let jobs: Vec<(Command, ProgressBar, String, StdRng)> = children.iter().map(|child| {
+            let child = match child {
+                Command::Download { url, size } => Command::Download { url: self.resolve_url(url), size: *size },
+                other => other.clone(),
+            };
+            let pb = multi.add(self.parallel_bar(&child));
+            let done = self.parallel_done(&child);
+            (child, pb, done, self.rng())
+        }).collect();
+
+        let crashed = self.tr(Msg::ParallelCrashed);
+        let results: Vec<Result<(), String>> = thread::scope(|scope| {
+            let handles: Vec<_> = jobs.into_iter()
+                .map(|(child, pb, done, mut rng)| {
+                    let (multi, out, speed) = (&multi, &*self.out, self.speed);
+                    scope.spawn(move || Self::parallel_child(&child, &pb, multi, out, speed, done, &mut rng))
+                })
+                .collect();
+            handles.into_iter()
+                .map(|h| h.join().unwrap_or_else(|_| Err(crashed.to_string())))
+                .collect()
+        });
+        results.into_iter().collect::<Result<(), String>>()?;
+        // Задачи выполнялись в обход execute_command
+        for child in children {
+            self.stats.count(child);
+        }
+        Ok(())
+    }
+
+    // Полоса задачи parallel настраивается до добавления в MultiProgress, чтобы не мелькал стиль по умолчанию
+    fn parallel_bar(&self, cmd: &Command) -> ProgressBar {
+        let width = self.scaled(30);
+        let bar = |len: u64, colors: &str, suffix: &str| ProgressBar::new(len)
+            .with_style(ProgressStyle::default_bar()
+                .template(&format!("    {{prefix:<24!}} [{{bar:{}.{}}}] {}", width, colors, suffix)).unwrap()
+                .progress_chars(&self.theme.smooth));
+        match cmd {
+            Command::Download { url, size } => bar(*size, "cyan/blue", "{bytes}/{total_bytes}")
+                .with_prefix(url.rsplit('/').next().unwrap_or(url).to_string()),
+            Command::Delay(ms) => bar(*ms, "white/black", "{elapsed}")
+                .with_prefix(format!("delay {}", ms)),
+            Command::RunTest { name, .. } => bar(100, "green/white", "{percent}%")
+                .with_prefix(name.clone()),
+            Command::InstallDep { name, version } => ProgressBar::new_spinner()
+                .with_style(ProgressStyle::default_spinner().template(&format!("    {{prefix:<24!}} {{spinner:.cyan}} {}", self.tr(Msg::ParallelInstalling))).unwrap()
+                    .tick_chars("⣾⣽⣻⢿⡿⣟⣯⣷ "))
+                .with_prefix(format!("{} v{}", name, version)),
+            _ => ProgressBar::hidden(),
+        }
+    }
+
+    // Итоговая строка задачи parallel; готовится заранее, в потоке задачи нет ни темы, ни языка
+    fn parallel_done(&self, cmd: &Command) -> String {
+        let theme = &self.theme;
+        let done = match cmd {
+            Command::Download { url, size } => self.trf(Msg::ParallelDownloaded, &[&url.paint(theme.accent), size]),
+            Command::Delay(ms) => self.trf(Msg::ParallelDelay, &[ms]),
+            Command::RunTest { name, .. } => self.trf(Msg::ParallelTest, &[&name.paint(theme.accent), &"PASSED".paint(theme.success).bold()]),
+            Command::InstallDep { name, version } => self.trf(Msg::ParallelDep, &[&name.paint(theme.accent), &version.paint(theme.warning)]),
+            _ => return String::new(),
+        };
+        theme.apply(&format!("    {} {}", "✓".paint(theme.success), done))
+    }
+
+    // Одна задача parallel в своём потоке: анимация на своей полосе, итог через multi.println
+    fn parallel_child(cmd: &Command, pb: &ProgressBar, multi: &MultiProgress, out: &dyn OutputSink, speed: f32, done: String, rng: &mut impl Rng) -> Result<(), String> {
+        out.event(cmd.event());
+        match cmd {
+            Command::Download { size, .. } => {
+                let mut downloaded = 0u64;
+                while downloaded < *size {
+                    let chunk = rng.gen_range(10..50).min(size - downloaded);
+                    downloaded += chunk;
+                    pb.set_position(downloaded);
+                    thread::sleep(scaled(rng.gen_range(20..60), speed));
+                }
+            }
+            Command::Delay(ms) => {
+                for elapsed in (0..*ms).step_by(50) {
+                    pb.set_position(elapsed);
+                    thread::sleep(scaled(50.min(ms - elapsed), speed));
+                }
+            }
+            Command::RunTest { duration, .. } => {
+                for i in 0..=20 {
+                    pb.set_position(i * 5);
+                    thread::sleep(scaled(duration / 20, speed));
+                }
+            }
+            Command::InstallDep { .. } => {
+                pb.enable_steady_tick(Duration::from_millis(100));
+                thread::sleep(scaled(1500, speed));
+            }
+            _ => return Err("parallel: команда не может выполняться параллельно".to_string()),
+        }
+        // Скрытый MultiProgress (вывод не в терминал) не печатает и println
+        let printed = if multi.is_hidden() {
+            out.line(&done);
+            Ok(())
+        } else {
+            multi.println(done).map_err(|e| e.to_string())
+        };
+        pb.finish_and_clear();
+        printed
+    }
+
+    fn retry(&mut self, attempts: u32, body: &[Command]) -> Result<(), String> {
+        for attempt in 1..=attempts {
+            match self.execute_commands(body, None) {
+                Ok(()) => {
+                    if attempt > 1 {
+                        say!(self, "  {} {}", "✓".paint(self.theme.success), self.trf(Msg::RetrySucceeded, &[&attempt, &attempts]));
+                    }
+                    return Ok(());
+                }
+                Err(e) if attempt < attempts && !self.interrupted() => {
+                    // Сбой перехвачен: код выхода fail не должен пережить удачный повтор
+                    self.exit_code = None;
+                    say!(self, "  {} {}", "⟳".paint(self.theme.warning), self.trf(Msg::RetryIn, &[&(attempt + 1), &attempts, &e]).paint(self.theme.warning));
+                    self.pause(2000);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    fn execute_command(&mut self, cmd: &Command) -> Result<(), String> {
+        self.check_interrupt()?;
+        self.emit_command(cmd);
+        self.fun.record(cmd);
+        self.inject_chaos(cmd)?;
+        match cmd {
+            Command::Message(msg) => {
+                say!(self, "  {} {}", TextStyle::Message.symbol(&self.theme), self.interpolate(msg));
+            }
+            Command::Delay(ms) => {
+                if !self.quick_mode {
+                    // Долгая пауза прерывается по Ctrl+C, не дожидаясь конца
+                    for elapsed in (0..*ms).step_by(50) {
+                        self.check_interrupt()?;
+                        self.pause(50.min(ms - elapsed));
+                    }
+                }
+            }
+            Command::Progress(pct) => {
+                self.progress.percent = *pct;
+                let line = self.theme.apply(&self.theme.progress_line(*pct, self.scaled(30), self.lang));
+                self.out.progress_update(*pct, &line);
+            }
+            Command::CreateDir(path) => {
+                self.simulate_operation(&self.trf(Msg::CreatingDir, &[path]), 200)?;
+                if self.verbose() {
+                    say!(self, "    {} mkdir -p {}", "$".dimmed(), path.paint(self.theme.warning));
+                }
+            }
+            Command::Download { url, size } => {
+                self.simulate_download(url, *size)?;
+            }
+            Command::Extract { from, to } => {
+                self.simulate_extraction(from, to)?;
+            }
+            Command::InstallDep { name, version } => {
+                self.simulate_dep_install(name, version)?;
+            }
+            Command::Configure { key, value } => {
+                say!(self, "  {} {}", "⚙".paint(self.theme.accent), self.trf(Msg::Configuring, &[&key.paint(self.theme.warning), &value.paint(self.theme.success)]));
+                self.pause(100);
+            }
+            Command::Cleanup => {
+                self.simulate_operation(self.tr(Msg::CleaningUp), 300)?;
+                if self.verbose() {
+                    say!(self, "    {} rm -rf /tmp/instnoth_*", "$".dimmed());
+                }
+            }
+            Command::Success(msg) => {
+                say!(self, "  {} {}", TextStyle::Success.symbol(&self.theme), TextStyle::Success.paint(&self.interpolate(msg), &self.theme));
+            }
+            Command::Error { message, fatal, exit } => {
+                self.phase_stats.note(TextStyle::Error);
+                let message = self.interpolate(message);
+                say!(self, "  {} {}", TextStyle::Error.symbol(&self.theme), TextStyle::Error.paint(&message, &self.theme));
+                // --ignore-failures действует и на фатальные ошибки: они остаются просто сообщением
+                if fatal.unwrap_or(self.abort_on_error) && !self.ignore_failures {
+                    self.exit_code = Some(*exit);
+                    return Err(message);
+                }
+            }
+            Command::Warning(msg) => {
+                self.phase_stats.note(TextStyle::Warning);
+                say!(self, "  {} {}", TextStyle::Warning.symbol(&self.theme), TextStyle::Warning.paint(&self.interpolate(msg), &self.theme));
+            }
+            Command::CopyFile { from, to } => {
+                say!(self, "  {} {}", "📄".normal(), self.trf(Msg::Copying, &[&from.dimmed(), &to.paint(self.theme.accent)]));
+                if self.verbose() {
+                    say!(self, "    {} cp {} {}", "$".dimmed(), from, to);
+                }
+                self.pause(150);
+            }
+            Command::Symlink { from, to } => {
+                say!(self, "  {} {}", "🔗".normal(), self.trf(Msg::Linking, &[&from.dimmed(), &to.paint(self.theme.accent)]));
+                if self.verbose() {
+                    say!(self, "    {} ln -s {} {}", "$".dimmed(), from, to);
+                }
+                self.pause(100);
+            }
+            Command::SetPermission { path, mode } => {
+                say!(self, "  {} {}", "🔐".normal(), self.trf(Msg::SettingMode, &[&mode.paint(self.theme.warning), &path.paint(self.theme.accent)]));
+                if self.verbose() {
+                    say!(self, "    {} chmod {} {}", "$".dimmed(), mode, path);
+                }
+                self.pause(50);
+            }
+            Command::RunScript(script) => {
+                say!(self, "  {} {}", "▷".paint(self.theme.accent), self.trf(Msg::RunningScript, &[&script.paint(self.theme.warning)]));
+                self.simulate_script_execution()?;
+            }
+            Command::CheckDep(dep) => {
+                say_inline!(self, "  {} {}", "?".paint(self.theme.heading), self.trf(Msg::CheckingDep, &[&dep.paint(self.theme.accent)]));
+                self.pause(200);
+                say!(self, "{}", "OK".paint(self.theme.success).bold());
+            }
+            Command::WriteConfig { path, content } => {
+                say!(self, "  {} {}", "📝".normal(), self.trf(Msg::WritingConfig, &[&path.paint(self.theme.accent)]));
+                if self.verbose() && !content.is_empty() {
+                    for line in content.lines().take(3) {
+                        say!(self, "    {}", line.dimmed());
+                    }
+                    if content.lines().count() > 3 {
+                        say!(self, "    {}", "...".dimmed());
+                    }
+                }
+                self.pause(100);
+            }
+            Command::DetectCpu => { self.detect_cpu()?; }
+            Command::DetectMemory => { self.detect_memory()?; }
+            Command::DetectDisk => { self.detect_disk()?; }
+            Command::DetectGpu => { self.detect_gpu()?; }
+            Command::DetectNetwork => { self.detect_network()?; }
+            Command::DetectOs => { self.detect_os()?; }
+            Command::DetectKernel => { self.detect_kernel()?; }
+            Command::DetectBios => { self.detect_bios()?; }
+            Command::RunTest { name, duration } => { self.run_test(name, *duration)?; }
+            Command::LoadKernelModule(module) => { self.load_kernel_module(module)?; }
+            Command::UnloadKernelModule(module) => { self.unload_kernel_module(module)?; }
+            Command::UpdateInitramfs => {
+                self.pending.retain(|a| *a != PendingAction::UpdateInitramfs);
+                self.update_initramfs()?;
+            }
+            Command::UpdateGrub => {
+                self.pending.retain(|a| *a != PendingAction::UpdateGrub);
+                self.update_grub()?;
+            }
+            Command::MountPartition { device, mount_point } => { self.mount_partition(device, mount_point)?; }
+            Command::UnmountPartition(mount_point) => { self.unmount_partition(mount_point)?; }
+            Command::FormatPartition { device, fs_type } => { self.format_partition(device, fs_type)?; }
+            Command::CreatePartition { device, size } => { self.create_partition(device, size)?; }
+            Command::SetHostname(hostname) => {
+                say!(self, "  {} {}", "🖥".normal(), self.trf(Msg::SettingHostname, &[&hostname.paint(self.theme.accent)]));
+                if self.verbose() {
+                    say!(self, "    {} hostnamectl set-hostname {}", "$".dimmed(), hostname);
+                }
+                self.pause(100);
+            }
+            Command::SetTimezone(tz) => {
+                say!(self, "  {} {}", "🌍".normal(), self.trf(Msg::SettingTimezone, &[&tz.paint(self.theme.accent)]));
+                if self.verbose() {
+                    say!(self, "    {} timedatectl set-timezone {}", "$".dimmed(), tz);
+                }
+                self.pause(100);
+            }
+            Command::SetLocale(locale) => {
+                say!(self, "  {} {}", "🌐".normal(), self.trf(Msg::SettingLocale, &[&locale.paint(self.theme.accent)]));
+                if self.verbose() {
+                    say!(self, "    {} localectl set-locale LANG={}", "$".dimmed(), locale);
+                }
+                if !self.state.locales.contains(locale) {
+                    self.warn("    ", &self.trf(Msg::LocaleNotGenerated, &[locale]));
+                }
+                self.pause(100);
+            }
+            Command::GenerateLocales(locales) => { self.generate_locales(locales)?; }
+            Command::SetKeymap(keymap) => { self.set_keymap(keymap)?; }
+            Command::CreateSnapshot(name) => { self.create_snapshot(name)?; }
+            Command::RollbackTo(name) => { self.rollback_to(name)?; }
+            Command::CreateUser { username, groups } => { self.create_user(username, groups)?; }
+            Command::SetPassword(user) => {
+                say_inline!(self, "  {} {}", "🔑".normal(), self.trf(Msg::SettingPassword, &[&user.paint(self.theme.accent)]));
+                self.pause(300);
+                say!(self, "{}", "OK".paint(self.theme.success));
+            }
+            Command::EnableService(service) => { self.manage_service(service, "enable")?; }
+            Command::DisableService(service) => { self.manage_service(service, "disable")?; }
+            Command::StartService(service) => { self.manage_service(service, "start")?; }
+            Command::StopService(service) => { self.manage_service(service, "stop")?; }
+            Command::InstallBootloader(target) => { self.install_bootloader(target)?; }
+            Command::GenerateFstab => { self.generate_fstab()?; }
+            Command::ShowPartitions(device) => { self.show_partitions(device)?; }
+            Command::SmartReport { device, fail_chance } => { self.smart_report(device, *fail_chance)?; }
+            Command::Sensors => { self.sensors()?; }
+            Command::CreateRaid { level, devices } => { self.create_raid(*level, devices)?; }
+            Command::AddRepo { name, flavor } => { self.add_repo(name, *flavor)?; }
+            Command::ImportKey(key) => { self.import_key(key)?; }
+            Command::CreatePool { name, devices, kind } => { self.create_pool(name, devices, *kind)?; }
+            Command::CreateSubvolume(path) => { self.create_subvolume(path)?; }
+            Command::SetupSwap { size, path, zram } => { self.setup_swap(*size, path, *zram)?; }
+            Command::SetKernelParam { key, value } => { self.set_kernel_param(key, value)?; }
+            Command::SetCmdline(cmdline) => { self.set_cmdline(cmdline)?; }
+            Command::EncryptPartition { device, cipher, name } => { self.encrypt_partition(device, cipher, name)?; }
+            Command::CheckIntegrity(target) => { self.check_integrity(target)?; }
+            Command::VerifySignature(file) => { self.verify_signature(file)?; }
+            Command::CompileKernel { version } => { self.compile_kernel(version)?; }
+            Command::Compile { name, files, jobs } => { self.compile(name, *files, *jobs)?; }
+            Command::Checksum { file, algo } => { self.checksum(file, *algo)?; }
+            Command::Fail { message, code } => { self.fail(message, *code)?; }
+            Command::Retry { attempts, body, .. } => { self.retry(*attempts, body)?; }
+            Command::Parallel(children) => { self.parallel(children)?; }
+            Command::DockerPull { image, layers } => { self.docker_pull(image, *layers)?; }
+            Command::GitClone { url, objects } => { self.git_clone(url, *objects)?; }
+            Command::GitSubmoduleUpdate => { self.git_submodule_update()?; }
+            Command::SystemdBoot { units, failures } => { self.systemd_boot(*units, *failures)?; }
+            Command::FailRandom { message, chance, code } => {
+                // Вероятность не зависит от --quick
+                if self.rng.gen_range(0..100) < *chance {
+                    self.fail(message, *code)?;
+                }
+            }
+            Command::InstallPackages(packages) => { self.install_packages(packages)?; }
+            Command::InstallLangDeps { manager, packages } => { self.install_lang_deps(*manager, packages)?; }
+            Command::CreateVm { name, cpus, memory, disk } => { self.create_vm(name, *cpus, *memory, *disk)?; }
+            Command::StartContainer(image) => { self.start_container(image)?; }
+            Command::GenerateCert { domain, days, key_type } => { self.generate_cert(domain, *days, *key_type)?; }
+            Command::HardenSsh => { self.harden_ssh()?; }
+            Command::GenerateSshKey { key_type, bits, comment } => { self.generate_ssh_key(*key_type, *bits, comment)?; }
+            Command::ProvisionCloud { provider, resources } => { self.provision_cloud(*provider, *resources)?; }
+            Command::RunPlaybook { playbook, hosts } => { self.run_playbook(playbook, *hosts)?; }
+            Command::InitDatabase { engine, version } => { self.init_database(engine, version)?; }
+            Command::RunMigrations { count } => { self.run_migrations(*count)?; }
+            Command::ShowLicense { id_or_path } => { self.show_license(id_or_path, false)?; }
+            Command::SpeedTest => { self.speed_test()?; }
+            Command::UpdateFirmware { component, version } => { self.update_firmware(component, version)?; }
+            Command::WaitFor { what, timeout_secs, expect_timeout } => { self.wait_for(what, *timeout_secs, *expect_timeout)?; }
+            Command::CheckSpace { path, need, force_fail } => { self.check_space(path, *need, *force_fail)?; }
+            Command::SelectMirror { country, count } => { self.select_mirror(country, *count)?; }
+            Command::UpdateSystem => { self.update_system()?; }
+            Command::SyncTime => { self.sync_time()?; }
+            Command::TestHardware(component) => { self.test_hardware(component)?; }
+            Command::BenchmarkCpu => { self.benchmark_cpu()?; }
+            Command::BenchmarkMemory => { self.benchmark_memory()?; }
+            Command::BenchmarkDisk => { self.benchmark_disk()?; }
+            Command::NetworkConfig { interface, config } => { self.network_config(interface, config)?; }
+            Command::FirewallRule(rule) => {
+                say!(self, "  {} {}", "🛡".normal(), self.trf(Msg::FirewallRule, &[&rule.paint(self.theme.warning)]));
+                self.pause(100);
+            }
+            Command::ScanHardware => { self.scan_hardware()?; }
+            Command::DetectDrivers => { self.detect_drivers()?; }
+            Command::InstallDriver(driver) => { self.install_driver(driver)?; }
+            Command::Ask { prompt, default, required } => { self.ask(prompt, *default, *required)?; }
+            Command::Choose { prompt, options, default, var } => { self.choose(prompt, options, *default, var)?; }
+            Command::TypeText { text, cps, style } => { self.type_text(text, *cps, *style)?; }
+            Command::LogTail { lines, style } => { self.log_tail(*lines, *style)?; }
+            Command::Table { title, rows } => { self.print_table(title, rows)?; }
+            Command::Revert { action, target } => {
+                self.simulate_operation(&format!("{}: {}", action, self.interpolate(target)), 200)?;
+            }
+            Command::Reboot { delay_secs } => {
+                if !self.no_reboot {
+                    self.pending.retain(|a| *a != PendingAction::Reboot);
+                    self.reboot(*delay_secs)?;
+                }
+            }
+        }
+        self.stats.count(cmd);
+        Ok(())
+    }
+
+    /// Событие command для --output json; progress сообщает сам получатель через progress_update
+    /// --chaos: с вероятностью chaos% команда «сбоит» со своей ошибкой. Без --chaos-fatal
+    /// следует повтор, и команда выполняется как обычно; с ним — сбой прерывает установку
+    fn inject_chaos(&mut self, cmd: &Command) -> Result<(), String> {
+        if self.chaos == 0 {
+            return Ok(());
+        }
+        let Some(message) = failure_message(cmd, self.lang) else {
+            return Ok(());
+        };
+        if self.rng.gen_range(0..100) >= self.chaos {
+            return Ok(());
+        }
+        self.out.event(Event::new("chaos")
+            .with("kind", cmd.kind())
+            .with("message", message.as_str())
+            .with("fatal", self.chaos_fatal));
+        say!(self, "  {} {}", "✗".paint(self.theme.error).bold(), message.paint(self.theme.error));
+        if self.chaos_fatal {
+            self.phase_stats.note(TextStyle::Error);
+            return Err(message);
+        }
+        self.phase_stats.note(TextStyle::Warning);
+        say!(self, "  {} {}", "⟳".paint(self.theme.warning), self.tr(Msg::ChaosRetry).paint(self.theme.warning));
+        self.pause(1000);
+        Ok(())
+    }
+
+    fn emit_command(&self, cmd: &Command) {
+        if !matches!(cmd, Command::Progress(_)) {
+            self.out.event(cmd.event());
+        }
+    }
+
+    // ===== Методы детекции =====
+
+    fn detect_cpu(&mut self) -> Result<(), String> {
+        say_inline!(self, "  {} {}", "🔍".normal(), self.tr(Msg::DetectingCpu));
+        self.pause(500);
+        let (vendor, model, cores, freq) = self.hardware().cpu;
+        self.out.event(Event::new("detected").with("component", "cpu")
+            .with("vendor", vendor).with("model", model).with("cores", cores).with("mhz", freq));
+        say!(self);
+        say!(self, "    {} {} {}", "├".dimmed(), self.tr(Msg::DetailVendor).dimmed(), vendor.paint(self.theme.accent));
+        say!(self, "    {} {} {}", "├".dimmed(), self.tr(Msg::DetailModel).dimmed(), model.white().bold());
+        say!(self, "    {} {} {}", "├".dimmed(), self.tr(Msg::DetailCores).dimmed(), self.trn(Msg::Cores, cores).paint(self.theme.warning));
+        say!(self, "    {} {} {} MHz", "└".dimmed(), self.tr(Msg::DetailFrequency).dimmed(), freq.to_string().paint(self.theme.success));
+        Ok(())
+    }
+
+    fn detect_memory(&mut self) -> Result<(), String> {
+        say_inline!(self, "  {} {}", "🔍".normal(), self.tr(Msg::DetectingMemory));
+        self.pause(400);
+        let (size, mem_type, speed) = self.hardware().memory;
+        self.out.event(Event::new("detected").with("component", "memory")
+            .with("size_gb", size).with("mem_type", mem_type).with("mhz", speed));
+        say!(self);
+        say!(self, "    {} {} {} GB", "├".dimmed(), self.tr(Msg::DetailCapacity).dimmed(), size.to_string().white().bold());
+        say!(self, "    {} {} {}", "├".dimmed(), self.tr(Msg::DetailType).dimmed(), mem_type.paint(self.theme.accent));
+        say!(self, "    {} {} {} MHz", "└".dimmed(), self.tr(Msg::DetailSpeed).dimmed(), speed.to_string().paint(self.theme.success));
+        Ok(())
+    }
+
+    fn detect_disk(&mut self) -> Result<(), String> {
+        say_inline!(self, "  {} {}", "🔍".normal(), self.tr(Msg::DetectingDisk));
+        self.pause(600);
+        let (vendor, model, size, disk_type) = self.hardware().disk;
+        self.out.event(Event::new("detected").with("component", "disk")
+            .with("vendor", vendor).with("model", model).with("size_gb", size).with("disk_type", disk_type));
+        say!(self);
+        say!(self, "    {} {} {}", "├".dimmed(), self.tr(Msg::DetailVendor).dimmed(), vendor.paint(self.theme.accent));
+        say!(self, "    {} {} {}", "├".dimmed(), self.tr(Msg::DetailModel).dimmed(), model.white().bold());
+        say!(self, "    {} {} {} GB", "├".dimmed(), self.tr(Msg::DetailCapacity).dimmed(), size.to_string().paint(self.theme.warning));
+        say!(self, "    {} {} {}", "└".dimmed(), self.tr(Msg::DetailType).dimmed(), disk_type.paint(self.theme.success));
+        Ok(())
+    }
+
+    fn detect_gpu(&mut self) -> Result<(), String> {
+        say_inline!(self, "  {} {}", "🔍".normal(), self.tr(Msg::DetectingGpu));
+        self.pause(500);
+        let (vendor, model, vram) = self.hardware().gpu;
+        self.out.event(Event::new("detected").with("component", "gpu")
+            .with("vendor", vendor).with("model", model).with("vram_gb", vram));
+        say!(self);
+        say!(self, "    {} {} {}", "├".dimmed(), self.tr(Msg::DetailVendor).dimmed(), vendor.paint(self.theme.accent));
+        say!(self, "    {} {} {}", "├".dimmed(), self.tr(Msg::DetailModel).dimmed(), model.white().bold());
+        say!(self, "    {} {} {} GB VRAM", "└".dimmed(), self.tr(Msg::DetailMemory).dimmed(), vram.to_string().paint(self.theme.success));
+        Ok(())
+    }
+
+    fn detect_network(&mut self) -> Result<(), String> {
+        say_inline!(self, "  {} {}", "🔍".normal(), self.tr(Msg::DetectingNetwork));
+        self.pause(500);
+        let SystemProfile { network: (vendor, model, speed), mac, ip, .. } = self.hardware();
+        self.out.event(Event::new("detected").with("component", "network")
+            .with("vendor", vendor).with("model", model).with("speed", speed).with("mac", mac.as_str()).with("ip", ip.as_str()));
+        say!(self);
+        say!(self, "    {} {} {}", "├".dimmed(), self.tr(Msg::DetailAdapter).dimmed(), format!("{} {}", vendor, model).white().bold());
+        say!(self, "    {} {} {}", "├".dimmed(), self.tr(Msg::DetailSpeed).dimmed(), speed.paint(self.theme.success));
+        say!(self, "    {} {} {}", "├".dimmed(), "MAC:".dimmed(), mac.paint(self.theme.warning));
+        say!(self, "    {} {} {}", "└".dimmed(), "IP:".dimmed(), ip.paint(self.theme.accent));
+        Ok(())
+    }
+
+    fn detect_os(&mut self) -> Result<(), String> {
+        say_inline!(self, "  {} {}", "🔍".normal(), self.tr(Msg::DetectingOs));
+        self.pause(300);
+        let (name, version) = self.hardware().os;
+        self.out.event(Event::new("detected").with("component", "os").with("name", name).with("version", version));
+        say!(self);
+        say!(self, "    {} {} {}", "├".dimmed(), self.tr(Msg::DetailSystem).dimmed(), name.white().bold());
+        say!(self, "    {} {} {}", "└".dimmed(), self.tr(Msg::DetailVersion).dimmed(), version.paint(self.theme.accent));
+        Ok(())
+    }
+
+    fn detect_kernel(&mut self) -> Result<(), String> {
+        say_inline!(self, "  {} {}", "🔍".normal(), self.tr(Msg::DetectingKernel));
+        self.pause(200);
+        let kernel = self.hardware().kernel;
+        self.out.event(Event::new("detected").with("component", "kernel").with("version", kernel));
+        say!(self, "{}", kernel.paint(self.theme.success));
+        Ok(())
+    }
+
+    fn detect_bios(&mut self) -> Result<(), String> {
+        say_inline!(self, "  {} {}", "🔍".normal(), self.tr(Msg::DetectingBios));
+        self.pause(400);
+        let (vendor, bios_type, version) = self.hardware().bios;
+        self.out.event(Event::new("detected").with("component", "bios")
+            .with("vendor", vendor).with("bios_type", bios_type).with("version", version));
+        say!(self);
+        say!(self, "    {} {} {}", "├".dimmed(), self.tr(Msg::DetailVendor).dimmed(), vendor.paint(self.theme.accent));
+        say!(self, "    {} {} {}", "├".dimmed(), self.tr(Msg::DetailType).dimmed(), bios_type.white().bold());
+        say!(self, "    {} {} {}", "└".dimmed(), self.tr(Msg::DetailVersion).dimmed(), version.paint(self.theme.success));
+        Ok(())
+    }
+
+    fn run_test(&mut self, name: &str, duration: u64) -> Result<(), String> {
+        say_inline!(self, "  {} {}", "🧪".normal(), self.trf(Msg::RunningTest, &[&name.paint(self.theme.accent)]));
+        if !self.quick_mode {
+            let pb = self.out.progress_start(100);
+            pb.set_style(ProgressStyle::default_bar()
+                .template("[{bar:20.green/white}] {percent}%").unwrap()
+                .progress_chars(&self.theme.bar));
+            let steps = 20;
+            let step_duration = duration / steps;
+            for i in 0..=steps { pb.set_position(i * 5); self.pause(step_duration); }
+            pb.finish_and_clear();
+        }
+        say!(self, "{}", "PASSED".paint(self.theme.success).bold());
+        Ok(())
+    }
+
+    fn test_hardware(&mut self, component: &str) -> Result<(), String> {
+        say!(self, "  {} {}", "🔬".normal(), self.trf(Msg::TestingComponent, &[&component.paint(self.theme.accent)]));
+        let tests = match component {
+            "memory" | "ram" => Msg::TestsMemory,
+            "cpu" => Msg::TestsCpu,
+            "disk" | "storage" => Msg::TestsDisk,
+            "gpu" => Msg::TestsGpu,
+            _ => Msg::TestsOther,
+        };
+        for test in self.tr(tests).split('|') { self.run_test(test, 500)?; }
+        Ok(())
+    }
+
+    fn benchmark_cpu(&mut self) -> Result<(), String> {
+        say!(self, "  {} CPU Benchmark", "📊".normal());
+        let mut rng = self.rng();
+        let scores = self.hardware().cpu_scores(&mut rng);
+        if !self.quick_mode {
+            for (name, score) in scores {
+                self.check_interrupt()?;
+                say_inline!(self, "    {} {} ... ", "→".dimmed(), name);
+                self.pause(400);
+                say!(self, "{} points", grouped(score).paint(self.theme.success).bold());
+            }
+        } else {
+            say!(self, "    {} Score: {} points", "→".dimmed(), grouped(scores[1].1).paint(self.theme.success).bold());
+        }
+        Ok(())
+    }
+
+    fn benchmark_memory(&mut self) -> Result<(), String> {
+        say!(self, "  {} Memory Benchmark", "📊".normal());
+        if !self.quick_mode {
+            let mut rng = self.rng();
+            let (read, write, copy, latency) = self.hardware().memory_bandwidth(&mut rng);
+            let tests = [
+                ("Read", format!("{} MB/s", grouped(read))),
+                ("Write", format!("{} MB/s", grouped(write))),
+                ("Copy", format!("{} MB/s", grouped(copy))),
+                ("Latency", format!("{:.1} ns", latency)),
+            ];
+            for (name, result) in tests {
+                self.check_interrupt()?;
+                say_inline!(self, "    {} {} ... ", "→".dimmed(), name);
+                self.pause(300);
+                say!(self, "{}", result.paint(self.theme.success).bold());
+            }
+        }
+        Ok(())
+    }
+
+    fn benchmark_disk(&mut self) -> Result<(), String> {
+        say!(self, "  {} Disk Benchmark", "📊".normal());
+        if !self.quick_mode {
+            let mut rng = self.rng();
+            let (read, write, random_read, random_write) = self.hardware().disk_speeds(&mut rng);
+            let tests = [
+                ("Sequential Read", format!("{} MB/s", grouped(read))),
+                ("Sequential Write", format!("{} MB/s", grouped(write))),
+                ("Random Read 4K", format!("{} IOPS", grouped(random_read))),
+                ("Random Write 4K", format!("{} IOPS", grouped(random_write))),
+            ];
+            for (name, result) in tests {
+                self.check_interrupt()?;
+                say_inline!(self, "    {} {} ... ", "→".dimmed(), name);
+                self.pause(400);
+                say!(self, "{}", result.paint(self.theme.success).bold());
+            }
+        }
+        Ok(())
+    }
+
+    fn load_kernel_module(&mut self, module: &str) -> Result<(), String> {
+        say_inline!(self, "  {} {}", "📦".normal(), self.trf(Msg::LoadingModule, &[&module.paint(self.theme.accent)]));
+        self.pause(300);
+        if self.verbose() { say!(self); say!(self, "    {} modprobe {}", "$".dimmed(), module); }
+        say!(self, "{}", "OK".paint(self.theme.success));
+        Ok(())
+    }
+
+    fn unload_kernel_module(&mut self, module: &str) -> Result<(), String> {
+        say_inline!(self, "  {} {}", "📤".normal(), self.trf(Msg::UnloadingModule, &[&module.paint(self.theme.accent)]));
+        self.pause(200);
+        say!(self, "{}", "OK".paint(self.theme.success));
+        Ok(())
+    }
+
+    fn update_initramfs(&mut self) -> Result<(), String> {
+        say!(self, "  {} {}", "🔄".normal(), self.tr(Msg::UpdatingInitramfs));
+        if !self.quick_mode {
+            for step in self.tr(Msg::InitramfsSteps).split('|') {
+                say_inline!(self, "    {} {}", "→".dimmed(), step);
+                self.pause(400);
+                say!(self, " {}", "✓".paint(self.theme.success));
+            }
+        }
+        say!(self, "    {} {}", "✓".paint(self.theme.success), self.tr(Msg::InitramfsUpdated));
+        Ok(())
+    }
+
+    fn update_grub(&mut self) -> Result<(), String> {
+        say!(self, "  {} {}", "🔄".normal(), self.tr(Msg::UpdatingGrub));
+        if !self.quick_mode {
+            let entries = ["Linux 6.6.8-arch1-1", "Linux 6.6.8-arch1-1 (fallback)", "Windows Boot Manager", "UEFI Firmware Settings"];
+            say!(self, "    {} {}", "→".dimmed(), self.tr(Msg::GeneratingGrubCfg));
+            self.pause(300);
+            say!(self, "    {} {}", "→".dimmed(), self.tr(Msg::GrubEntries));
+            for entry in entries { self.pause(150); say!(self, "      {} {}", "•".dimmed(), entry); }
+        }
+        say!(self, "    {} {}", "✓".paint(self.theme.success), self.tr(Msg::GrubUpdated));
+        Ok(())
+    }
+
+    fn compile_kernel(&mut self, version: &str) -> Result<(), String> {
+        say!(self, "  {} {}", "🔨".normal(), self.trf(Msg::CompilingKernel, &[&version.paint(self.theme.accent)]));
+        if !self.quick_mode {
+            let stages = self.tr(Msg::KernelStages).split('|').zip([500, 2000, 1500, 800, 400]);
+            for (stage, duration) in stages {
+                say_inline!(self, "    {} {} ", "→".dimmed(), stage);
+                let pb = self.out.progress_start(100);
+                pb.set_style(self.compile_bar_style(""));
+                let steps = 20;
+                for i in 0..=steps {
+                    if self.interrupted() {
+                        pb.finish_and_clear();
+                        return Err(self.tr(Msg::Cancelled).to_string());
+                    }
+                    pb.set_position(i * 5);
+                    self.pause(duration / steps);
+                }
+                pb.finish_and_clear();
+                say!(self, "{}", "✓".paint(self.theme.success));
+            }
+        }
+        say!(self, "    {} {}", "✓".paint(self.theme.success), self.trf(Msg::KernelCompiled, &[&version]));
+        Ok(())
+    }
+
+    /// Стиль полосы прогресса сборки, общий для compile_kernel и compile
+    fn compile_bar_style(&self, suffix: &str) -> ProgressStyle {
+        ProgressStyle::default_bar()
+            .template(&format!("[{{bar:20.cyan/blue}}]{}", suffix))
+            .unwrap()
+            .progress_chars(&self.theme.bar)
+    }
+
+    fn compile(&mut self, name: &str, files: u64, jobs: u64) -> Result<(), String> {
+        let mut rng = self.rng();
+        // Скорость сборки растёт с числом потоков, но не линейно
+        let rate = (jobs as f64).sqrt() * rng.gen_range(18.0..30.0);
+        let seconds = files as f64 / rate;
+        let size_mb = files as f64 * rng.gen_range(0.02..0.08);
+
+        say!(self, "  {} {}", "🔨".normal(), self.trf(Msg::Building, &[&name.paint(self.theme.accent), &self.trn(Msg::Files, files as u32), &self.trn(Msg::Threads, jobs as u32)]));
+        if !self.quick_mode && files > 0 {
+            // На экран попадает не больше 60 строк, каждая означает группу файлов
+            let shown = files.min(60);
+            let pb = self.out.progress_start(files);
+            pb.set_style(self.compile_bar_style(" {pos}/{len}"));
+            let pause = ((seconds * 1000.0) as u64 / shown).clamp(10, 80);
+
+            // Без терминала полоса скрыта, и pb.println ничего бы не вывел
+            let emit = |line: String| if pb.is_hidden() { say!(self, "{}", line) } else { pb.println(line) };
+
+            for step in 1..=shown {
+                if self.interrupted() {
+                    pb.finish_and_clear();
+                    return Err(self.tr(Msg::Cancelled).to_string());
+                }
+                let done = files * step / shown;
+                let file = FakeLog::source_file(&mut rng);
+                emit(format!(
+                    "    {} Building CXX object {}/CMakeFiles/{}.dir/{}.o",
+                    format!("[{:>3}%]", done * 100 / files).dimmed(), name, name, file
+                ));
+                if rng.gen_bool(0.05) {
+                    emit(format!("    {}", format!("{}: warning: unused parameter 'ctx' [-Wunused-parameter]", file).paint(self.theme.warning)));
+                }
+                pb.set_position(done);
+                self.pause(pause);
+            }
+            pb.finish_and_clear();
+            say!(self, "    {} Linking CXX executable bin/{}", "[100%]".dimmed(), name);
+            self.pause(300);
+        }
+        let built = self.trf(Msg::Built, &[&name, &name, &format!("{:.1}", size_mb), &format!("{:.0}", rate), &format!("{:.1}", seconds)]);
+        say!(self, "    {} {}", "✓".paint(self.theme.success), built);
+        Ok(())
+    }
+
+    fn mount_partition(&mut self, device: &str, mount_point: &str) -> Result<(), String> {
+        if let Some(part) = self.state.partition_mut(device) {
+            part.mount_point = mount_point.to_string();
+            part.mounted = true;
+        }
+        say_inline!(self, "  {} {}", "💾".normal(), self.trf(Msg::Mounting, &[&device.paint(self.theme.warning), &mount_point.paint(self.theme.accent)]));
+        self.pause(300);
+        if self.verbose() { say!(self); say!(self, "    {} mount {} {}", "$".dimmed(), device, mount_point); }
+        say!(self, "{}", "OK".paint(self.theme.success));
+        Ok(())
+    }
+
+    fn unmount_partition(&mut self, mount_point: &str) -> Result<(), String> {
+        say_inline!(self, "  {} {}", "⏏".normal(), self.trf(Msg::Unmounting, &[&mount_point.paint(self.theme.accent)]));
+        self.pause(200);
+        say!(self, "{}", "OK".paint(self.theme.success));
+        Ok(())
+    }
+
+    fn format_partition(&mut self, device: &str, fs_type: &str) -> Result<(), String> {
+        if let Some(part) = self.state.partition_mut(device) {
+            part.fs_type = fs_type.to_string();
+            part.uuid = if fs_type == "vfat" { RandomSystemInfo::vfat_id(&mut self.rng) } else { RandomSystemInfo::uuid(&mut self.rng) };
+            if fs_type == "swap" {
+                part.mount_point = "[SWAP]".to_string();
+                part.mounted = true;
+            }
+        }
+        say!(self, "  {} {}", "💿".normal(), self.trf(Msg::Formatting, &[&device.paint(self.theme.warning), &fs_type.paint(self.theme.accent)]));
+        if !self.quick_mode {
+            say_inline!(self, "    {} {}", "→".dimmed(), self.tr(Msg::CreatingFs));
+            let pb = self.out.progress_start(100);
+            pb.set_style(ProgressStyle::default_bar().template("[{bar:30.yellow/white}] {percent}%").unwrap().progress_chars(&self.theme.bar));
+            for i in 0..=100 { pb.set_position(i); self.pause(20); }
+            pb.finish_and_clear();
+            say!(self, "{}", "✓".paint(self.theme.success));
+            if self.verbose() { say!(self, "    {} mkfs.{} {}", "$".dimmed(), fs_type, device); }
+        }
+        Ok(())
+    }
+
+    fn create_partition(&mut self, device: &str, size: &str) -> Result<(), String> {
+        self.state.add_partition(device, size, &mut self.rng);
+        say!(self, "  {} {}", "📀".normal(), self.trf(Msg::CreatingPartition, &[&device.paint(self.theme.warning), &size.paint(self.theme.accent)]));
+        if !self.quick_mode {
+            self.pause(500);
+            if self.verbose() { say!(self, "    {} parted {} mkpart primary 0% {}", "$".dimmed(), device, size); }
+        }
+        say!(self, "    {} {}", "✓".paint(self.theme.success), self.tr(Msg::PartitionCreated));
+        Ok(())
+    }
+
+    fn generate_fstab(&mut self) -> Result<(), String> {
+        say!(self, "  {} {}", "📝".normal(), self.tr(Msg::GeneratingFstab));
+        if !self.quick_mode {
+            let mut entries = self.fstab_entries();
+            // Без смонтированных в этом запуске разделов показывается типичный fstab
+            if entries.iter().all(|(_, mount, ..)| mount == "none") {
+                let defaults = [
+                    ("UUID=xxxx-xxxx".to_string(), "/".to_string(), "ext4".to_string(), "defaults", "0 1"),
+                    ("UUID=yyyy-yyyy".to_string(), "/boot/efi".to_string(), "vfat".to_string(), "umask=0077", "0 2"),
+                    ("UUID=zzzz-zzzz".to_string(), "/home".to_string(), "ext4".to_string(), "defaults", "0 2"),
+                ];
+                entries.splice(0..0, defaults);
+            }
+            entries.push(("tmpfs".to_string(), "/tmp".to_string(), "tmpfs".to_string(), "defaults,nosuid,nodev", "0 0"));
+            for (device, mount, fs, opts, dump) in entries {
+                say!(self, "    {} {} {} {} {} {}", "+".dimmed(), device.paint(self.theme.warning), mount.paint(self.theme.accent), fs, opts.dimmed(), dump.dimmed());
+                self.pause(150);
+            }
+        }
+        say!(self, "    {} {}", "✓".paint(self.theme.success), self.tr(Msg::FstabGenerated));
+        Ok(())
+    }
+
+    /// Строки fstab для смонтированных в этом запуске разделов. Точки монтирования
+    /// под /mnt считаются путями будущей системы: /mnt → /, /mnt/boot → /boot
+    fn fstab_entries(&self) -> Vec<(String, String, String, &'static str, &'static str)> {
+        let mut entries: Vec<_> = self.state.partitions.values()
+            .flatten()
+            .filter(|p| p.mounted)
+            .map(|p| {
+                let fs = if p.fs_type.is_empty() { "ext4".to_string() } else { p.fs_type.clone() };
+                // Зашифрованные разделы и swap-файлы указываются путём, а не по UUID
+                let by_path = self.state.mappings.contains_key(&p.device) || !p.device.starts_with("/dev/");
+                let source = if by_path { p.device.clone() } else { format!("UUID={}", p.uuid) };
+                if p.mount_point == "[SWAP]" {
+                    return (source, "none".to_string(), "swap".to_string(), "sw", "0 0");
+                }
+                let mount = match p.mount_point.strip_prefix("/mnt") {
+                    Some("") => "/".to_string(),
+                    Some(rest) if rest.starts_with('/') => rest.to_string(),
+                    _ => p.mount_point.clone(),
+                };
+                let (opts, dump) = match (mount.as_str(), fs.as_str()) {
+                    ("/", _) => ("defaults", "0 1"),
+                    (_, "vfat") => ("umask=0077", "0 2"),
+                    _ => ("defaults", "0 2"),
+                };
+                (source, mount, fs, opts, dump)
+            })
+            .collect();
+        // Корень первым, как в настоящем fstab; swap в конце
+        entries.sort_by(|a, b| (a.1 == "none", &a.1).cmp(&(b.1 == "none", &b.1)));
+        entries
+    }
+
+    fn show_partitions(&mut self, disk: &str) -> Result<(), String> {
+        let (model, disk_gb) = self.state.disk(disk, &mut self.rng);
+        let parts = self.state.partitions_of(disk, &mut self.rng);
+        say!(self, "  {} {}", "🗂".normal(), self.trf(Msg::Partitions, &[&disk.paint(self.theme.warning), &model, &disk_gb]));
+        self.pause(300);
+        if self.verbose() { say!(self, "    {} lsblk -o NAME,SIZE,FSTYPE,MOUNTPOINT,UUID {}", "$".dimmed(), disk); }
+
+        let name_width = parts.iter().map(|p| p.device.len()).max().unwrap_or(0).max(4);
+        let fs_width = parts.iter().map(|p| p.fs_type.len()).max().unwrap_or(0).max(6);
+        let mount_width = parts.iter().map(|p| p.mount_point.len()).max().unwrap_or(0).max(10);
+        say!(self, "    {}", format!(
+            "{:<nw$}  {:>10}  {:<fw$}  {:<mw$}  {}",
+            "NAME", "SIZE", "FSTYPE", "MOUNTPOINT", "UUID", nw = name_width, fw = fs_width, mw = mount_width
+        ).bold());
+        for p in &parts {
+            say!(self,
+                "    {:<nw$}  {:>10}  {:<fw$}  {:<mw$}  {}",
+                p.device.paint(self.theme.accent), HumanBytes(p.size_bytes).to_string(), p.fs_type, p.mount_point, p.uuid.dimmed(),
+                nw = name_width, fw = fs_width, mw = mount_width
+            );
+        }
+        let used: u64 = parts.iter().map(|p| p.size_bytes).sum();
+        let free = (disk_gb * 1_000_000_000).saturating_sub(used);
+        say!(self, "    {} {}", "→".dimmed(), self.trf(Msg::PartitionsFree, &[&parts.len(), &HumanBytes(free)]));
+        Ok(())
+    }
+
+    fn smart_report(&mut self, device: &str, fail_chance: u8) -> Result<(), String> {
+        let mut rng = self.rng();
+        let failed = rng.gen_range(0..100) < fail_chance;
+        let (model, size_gb) = self.state.disk(RunState::parent_device(device), &mut rng);
+
+        say!(self, "  {} SMART: {}", "🩺".normal(), device.paint(self.theme.warning));
+        if self.verbose() { say!(self, "    {} smartctl -a {}", "$".dimmed(), device); }
+        if !self.quick_mode {
+            self.pause(500);
+            say!(self, "    {:<18} {}", "Device Model:".dimmed(), model.white().bold());
+            say!(self, "    {:<18} S{:X}", "Serial Number:".dimmed(), rng.gen::<u32>());
+            say!(self, "    {:<18} {} GB", "User Capacity:".dimmed(), size_gb);
+            say!(self);
+
+            let reallocated: u64 = if failed { rng.gen_range(80..2000) } else if rng.gen_bool(0.2) { rng.gen_range(1..20) } else { 0 };
+            let pending: u64 = if failed { rng.gen_range(10..200) } else { 0 };
+            let power_on: u64 = rng.gen_range(50..40_000);
+            let temperature: u64 = rng.gen_range(28..if failed { 68 } else { 55 });
+            let cycles: u64 = rng.gen_range(10..5000);
+
+            // (id, имя, нормированное значение, порог, сырое значение, уровень: 0 норма, 1 внимание, 2 плохо)
+            let level = |raw: u64, warn: u64, bad: u64| if raw >= bad { 2 } else if raw >= warn { 1 } else { 0 };
+            let attributes = [
+                // При отказе нормированное значение опускается ниже порога, как у настоящего диска
+                (5, "Reallocated_Sector_Ct", if failed { rng.gen_range(1..10) } else { 100 - reallocated }, 10, reallocated, level(reallocated, 1, 50)),
+                (9, "Power_On_Hours", 100 - power_on / 1000, 0, power_on, level(power_on, 30_000, 1_000_000)),
+                (12, "Power_Cycle_Count", 99, 0, cycles, 0),
+                (194, "Temperature_Celsius", 100 - temperature, 0, temperature, level(temperature, 50, 60)),
+                (197, "Current_Pending_Sector", 100 - (pending / 5).min(95), 0, pending, level(pending, 1, 50)),
+                (199, "UDMA_CRC_Error_Count", 200, 0, 0, 0),
+            ];
+            say!(self, "    {}", format!("{:>3} {:<24} {:>5} {:>6} {:>9}", "ID#", "ATTRIBUTE_NAME", "VALUE", "THRESH", "RAW_VALUE").bold());
+            for (id, name, value, thresh, raw, level) in attributes {
+                let row = format!("{:>3} {:<24} {:>5} {:>6} {:>9}", id, name, value, thresh, raw);
+                let row = match level {
+                    2 => row.paint(self.theme.error).bold(),
+                    1 => row.paint(self.theme.warning),
+                    _ => row.normal(),
+                };
+                say!(self, "    {}", row);
+            }
+            say!(self);
+        }
+
+        let verdict = if failed { "FAILED!".paint(self.theme.error).bold() } else { "PASSED".paint(self.theme.success).bold() };
+        say!(self, "    SMART overall-health self-assessment test result: {}", verdict);
+        Ok(())
+    }
+
+    fn sensors(&mut self) -> Result<(), String> {
+        let mut rng = self.rng();
+        let SystemProfile { cpu: (_, cpu_model, cores, _), gpu: (_, gpu_model, _), .. } = self.hardware();
+
+        let paint_temp = |t: u32| {
+            let text = format!("+{}.0°C", t);
+            match t {
+                0..=59 => text.paint(self.theme.success),
+                60..=79 => text.paint(self.theme.warning),
+                _ => text.paint(self.theme.error).bold(),
+            }
+        };
+
+        say!(self, "  {} {}", "🌡".normal(), self.tr(Msg::Sensors));
+        if self.verbose() { say!(self, "    {} sensors", "$".dimmed()); }
+        self.pause(300);
+
+        let package = rng.gen_range(40..90);
+        say!(self, "    {} {}", cpu_model.white().bold(), "(coretemp-isa-0000)".dimmed());
+        say!(self, "      {:<14} {}", "Package id 0:", paint_temp(package));
+        // На экран попадают первые 8 ядер, остальные сворачиваются в одну строку
+        let shown = cores.min(8);
+        for core in 0..shown {
+            let temp = (package as i32 + rng.gen_range(-8..4)).max(30) as u32;
+            say!(self, "      {:<14} {}", format!("Core {}:", core), paint_temp(temp));
+        }
+        if cores > shown {
+            say!(self, "      {}", self.trf(Msg::MoreCores, &[&self.trn(Msg::Cores, cores - shown)]).dimmed());
+        }
+
+        say!(self, "    {} {}", gpu_model.white().bold(), "(gpu-pci-0100)".dimmed());
+        say!(self, "      {:<14} {}", "edge:", paint_temp(rng.gen_range(35..88)));
+
+        say!(self, "    {}", self.tr(Msg::Fans).white().bold());
+        let fans = [("CPU fan:", 600..2400), ("GPU fan:", 0..3000), ("Chassis fan1:", 500..1500), ("Chassis fan2:", 500..1500)];
+        for (name, range) in fans {
+            say!(self, "      {:<14} {} RPM", name, rng.gen_range(range).to_string().paint(self.theme.accent));
+        }
+        Ok(())
+    }
+
+    /// Ввод пользователя или ответ по умолчанию, «набранный» на экране в неинтерактивном режиме
+    fn prompt_or(&self, shown_default: &str) -> Option<String> {
+        let input = self.read_answer();
+        if input.is_none() {
+            if self.non_interactive || self.quick_mode {
+                self.type_answer(shown_default);
+            } else {
+                say!(self);
+            }
+        }
+        input
+    }
+
+    fn encrypt_partition(&mut self, device: &str, cipher: &str, name: &str) -> Result<(), String> {
+        let name = if !name.is_empty() {
+            name.to_string()
+        } else if self.state.mappings.is_empty() {
+            "cryptroot".to_string()
+        } else {
+            format!("crypt{}", device.rsplit('/').next().unwrap_or(device))
+        };
+        let mapper = format!("/dev/mapper/{}", name);
+
+        say!(self, "  {} {}", "🔒".normal(), self.trf(Msg::Encrypting, &[&device.paint(self.theme.warning), &cipher.paint(self.theme.accent)]));
+        if self.verbose() { say!(self, "    {} cryptsetup luksFormat --type luks2 --cipher {} {}", "$".dimmed(), cipher, device); }
+        say!(self);
+        say!(self, "    {}", "WARNING!".paint(self.theme.warning).bold());
+        say!(self, "    {}", "========".paint(self.theme.warning).bold());
+        say!(self, "    This will overwrite data on {} irrevocably.", device);
+        say!(self);
+        say_inline!(self, "    Are you sure? (Type 'yes' in capital letters): ");
+        if let Some(answer) = self.prompt_or("YES") {
+            if answer != "YES" {
+                say!(self, "    {}", "Operation aborted.".paint(self.theme.error));
+                return Err(self.trf(Msg::EncryptionCancelled, &[&device]));
+            }
+        }
+        // Парольная фраза, как и пароль sudo, читается без эха; автоответ — звёздочки одной длины
+        let length = self.rng.gen_range(12..=20);
+        for prompt in [format!("Enter passphrase for {}: ", device), "Verify passphrase: ".to_string()] {
+            say_inline!(self, "    {}", prompt);
+            if self.non_interactive || self.quick_mode {
+                self.type_answer(&"*".repeat(length));
+            } else {
+                read_hidden_line(self.interrupt);
+                say!(self);
+                self.check_interrupt()?;
+            }
+        }
+        self.pause(1500);
+        say!(self, "    Key slot 0 created.");
+        say!(self, "    Command successful.");
+
+        if self.verbose() { say!(self, "    {} cryptsetup open {} {}", "$".dimmed(), device, name); }
+        self.pause(500);
+        say!(self, "    {} {}", "✓".paint(self.theme.success), self.trf(Msg::OpenedAs, &[&mapper.paint(self.theme.accent)]));
+
+        // Без пересборки initramfs зашифрованный корень не откроется при загрузке
+        self.require(PendingAction::UpdateInitramfs);
+
+        // Отображение становится «разделом», на который ссылаются format, mount и generate_fstab
+        let size_bytes = match self.state.partition_mut(device) {
+            Some(part) => {
+                part.fs_type = "crypto_LUKS".to_string();
+                part.size_bytes
+            }
+            None => 0,
+        };
+        self.state.mappings.insert(mapper.clone(), device.to_string());
+        self.state.partitions.insert(mapper.clone(), vec![PartitionInfo {
+            device: mapper,
+            size_bytes,
+            fs_type: String::new(),
+            mount_point: String::new(),
+            uuid: RandomSystemInfo::uuid(&mut self.rng),
+            mounted: false,
+        }]);
+        Ok(())
+    }
+
+    fn create_pool(&mut self, name: &str, devices: &[String], kind: PoolKind) -> Result<(), String> {
+        self.state.pools.insert(name.to_string(), kind);
+        let short: Vec<&str> = devices.iter().map(|d| d.rsplit('/').next().unwrap_or(d)).collect();
+
+        match kind {
+            PoolKind::Zfs => {
+                // Два диска — зеркало, три и больше — raidz1
+                let vdev = match devices.len() {
+                    1 => None,
+                    2 => Some("mirror-0"),
+                    _ => Some("raidz1-0"),
+                };
+                say!(self, "  {} {}", "🗄".normal(), self.trf(Msg::CreatingZfsPool, &[&name.paint(self.theme.accent)]));
+                if self.verbose() {
+                    let layout = vdev.map(|v| v.trim_end_matches("-0")).unwrap_or("");
+                    say!(self, "    {} zpool create {} {} {}", "$".dimmed(), name, layout, devices.join(" "));
+                }
+                self.pause(600);
+                say!(self, "      pool: {}", name);
+                say!(self, "     state: {}", "ONLINE".paint(self.theme.success));
+                say!(self, "    config:");
+                say!(self, "    {}", format!("    {:<12} {:<8} {:>4} {:>5} {:>5}", "NAME", "STATE", "READ", "WRITE", "CKSUM").bold());
+                let row = |label: &str, indent: usize| {
+                    say!(self, "    {}{:<w$} {:<8} {:>4} {:>5} {:>5}", " ".repeat(4 + indent), label, "ONLINE".paint(self.theme.success), 0, 0, 0, w = 12 - indent);
+                };
+                row(name, 0);
+                let member_indent = if let Some(vdev) = vdev { row(vdev, 2); 4 } else { 2 };
+                for dev in &short {
+                    row(dev, member_indent);
+                }
+            }
+            PoolKind::Btrfs => {
+                let profile = if devices.len() > 1 { "raid1" } else { "single" };
+                say!(self, "  {} {}", "🗄".normal(), self.trf(Msg::CreatingBtrfs, &[&name.paint(self.theme.accent)]));
+                if self.verbose() { say!(self, "    {} mkfs.btrfs -L {} -d {} -m {} {}", "$".dimmed(), name, profile, profile, devices.join(" ")); }
+                self.pause(600);
+                say!(self, "    Label:              {}", name);
+                say!(self, "    UUID:               {}", RandomSystemInfo::uuid(&mut self.rng));
+                say!(self, "    Data profile:       {}", profile);
+                say!(self, "    Metadata profile:   {}", profile);
+                say!(self, "    Number of devices:  {}", devices.len());
+                say!(self, "    Devices:");
+                say!(self, "    {}", "   ID        SIZE  PATH".bold());
+                for (id, dev) in devices.iter().enumerate() {
+                    let (_, gb) = self.state.disk(RunState::parent_device(dev), &mut self.rng);
+                    say!(self, "    {:>5} {:>11}  {}", id + 1, HumanBytes(gb * 1_000_000_000).to_string(), dev);
+                }
+            }
+        }
+
+        self.scrub(name, kind);
+        Ok(())
+    }
+
+    fn scrub(&self, name: &str, kind: PoolKind) {
+        let label = match kind {
+            PoolKind::Zfs => format!("zpool scrub {}", name),
+            PoolKind::Btrfs => format!("btrfs scrub start /{}", name),
+        };
+        if !self.quick_mode {
+            for pct in (0..=100).step_by(10) {
+                say_inline!(self, "\r    {} {} {:>3}%", "→".dimmed(), label, pct);
+                self.pause(80);
+            }
+            say!(self);
+        }
+        say!(self, "    {} scrub repaired 0B with 0 errors", "✓".paint(self.theme.success));
+    }
+
+    fn create_subvolume(&mut self, path: &str) -> Result<(), String> {
+        let pool = path.split('/').next().unwrap_or(path);
+        let kind = self.state.pools.get(pool).copied();
+
+        match kind {
+            Some(PoolKind::Btrfs) => {
+                say!(self, "  {} Create subvolume '/{}'", "📁".normal(), path.paint(self.theme.accent));
+                if self.verbose() { say!(self, "    {} btrfs subvolume create /{}", "$".dimmed(), path); }
+            }
+            _ => {
+                say!(self, "  {} {}", "📁".normal(), self.trf(Msg::CreatingDataset, &[&path.paint(self.theme.accent)]));
+                if self.verbose() { say!(self, "    {} zfs create {}", "$".dimmed(), path); }
+            }
+        }
+        if kind.is_none() {
+            self.warn("    ", &self.trf(Msg::PoolNotCreated, &[&pool]));
+        }
+        self.pause(200);
+        Ok(())
+    }
+
+    fn set_kernel_param(&mut self, key: &str, value: &str) -> Result<(), String> {
+        say!(self, "  {} {}", "⚙".paint(self.theme.accent), self.trf(Msg::KernelParam, &[&key.paint(self.theme.warning), &value.paint(self.theme.success)]));
+        if self.verbose() { say!(self, "    {} sysctl -w {}={}", "$".dimmed(), key, value); }
+        self.pause(100);
+        say!(self, "    {} {}", "✓".paint(self.theme.success), self.tr(Msg::SysctlSaved));
+        Ok(())
+    }
+
+    fn set_cmdline(&mut self, cmdline: &str) -> Result<(), String> {
+        if self.state.cmdline.is_empty() {
+            self.state.cmdline = "quiet splash".to_string();
+        }
+        let before = std::mem::replace(&mut self.state.cmdline, cmdline.to_string());
+
+        say!(self, "  {} {}", "⚙".paint(self.theme.accent), self.tr(Msg::KernelCmdline));
+        self.pause(150);
+        say!(self, "    {}", "/etc/default/grub".dimmed());
+        say!(self, "    {}", format!("- GRUB_CMDLINE_LINUX=\"{}\"", before).paint(self.theme.error));
+        say!(self, "    {}", format!("+ GRUB_CMDLINE_LINUX=\"{}\"", cmdline).paint(self.theme.success));
+        say!(self, "    {} {}", "→".dimmed(), self.tr(Msg::NeedsUpdateGrub));
+        self.require(PendingAction::UpdateGrub);
+        Ok(())
+    }
+
+    fn setup_swap(&mut self, size: u64, path: &str, zram: bool) -> Result<(), String> {
+        if zram {
+            say!(self, "  {} {}", "💤".normal(), self.trf(Msg::SettingUpZram, &[&HumanBytes(size).to_string().paint(self.theme.accent)]));
+            self.pause(300);
+            say!(self, "    {} /etc/systemd/zram-generator.conf", "+".dimmed());
+            say!(self, "      {}", "[zram0]".dimmed());
+            say!(self, "      {}", format!("zram-size = {}", size / 1024 / 1024).dimmed());
+            say!(self, "      {}", "compression-algorithm = zstd".dimmed());
+            if self.verbose() { say!(self, "    {} systemctl start systemd-zram-setup@zram0.service", "$".dimmed()); }
+            say!(self, "    {} {}", "✓".paint(self.theme.success), self.tr(Msg::ZramActivated));
+            return Ok(());
+        }
+
+        let uuid = RandomSystemInfo::uuid(&mut self.rng);
+        say!(self, "  {} {}", "💤".normal(), self.trf(Msg::CreatingSwapFile, &[&path.paint(self.theme.accent), &HumanBytes(size).to_string().paint(self.theme.warning)]));
+        for step in [
+            format!("fallocate -l {} {}", size, path),
+            format!("chmod 600 {}", path),
+            format!("mkswap {}", path),
+            format!("swapon {}", path),
+        ] {
+            if self.verbose() { say!(self, "    {} {}", "$".dimmed(), step); }
+            self.pause(200);
+        }
+        say!(self, "    Setting up swapspace version 1, size = {}", HumanBytes(size));
+        say!(self, "    no label, UUID={}", uuid.dimmed());
+
+        // Swap-файл попадает в состояние как «раздел», чтобы его увидел generate_fstab
+        self.state.partitions.insert(path.to_string(), vec![PartitionInfo {
+            device: path.to_string(),
+            size_bytes: size,
+            fs_type: "swap".to_string(),
+            mount_point: "[SWAP]".to_string(),
+            uuid,
+            mounted: true,
+        }]);
+        let total: u64 = self.state.partitions.values()
+            .flatten()
+            .filter(|p| p.fs_type == "swap" && p.mounted)
+            .map(|p| p.size_bytes)
+            .sum();
+        say!(self, "    {} {}", "✓".paint(self.theme.success), self.trf(Msg::SwapTotal, &[&HumanBytes(total)]));
+        Ok(())
+    }
+
+    fn create_raid(&mut self, level: u8, devices: &[String]) -> Result<(), String> {
+        let mut rng = self.rng();
+        let array = format!("/dev/md{}", self.state.raid_arrays.len());
+        self.state.raid_arrays.push(array.clone());
+
+        // Объём массива считается по самому маленькому диску
+        let member_gb = devices.iter().map(|d| self.state.disk(RunState::parent_device(d), &mut rng).1).min().unwrap_or(0);
+        let count = devices.len() as u64;
+        let data_disks = match level {
+            0 => count,
+            1 => count.min(1),
+            5 => count.saturating_sub(1),
+            6 => count.saturating_sub(2),
+            _ => count / 2,
+        };
+        let member_kb = member_gb * 1_000_000_000 / 1024;
+
+        say!(self, "  {} {}", "🧱".normal(), self.trf(Msg::CreatingRaid, &[&level, &array.paint(self.theme.accent), &self.trn(Msg::Devices, count as u32)]));
+        if self.verbose() {
+            say!(self, "    {} mdadm --create {} --level={} --raid-devices={} {}", "$".dimmed(), array, level, count, devices.join(" "));
+        }
+        self.pause(400);
+        if level == 5 || level == 6 {
+            say!(self, "    mdadm: layout defaults to left-symmetric");
+        }
+        if level != 1 {
+            say!(self, "    mdadm: chunk size defaults to 512K");
+        }
+        say!(self, "    mdadm: size set to {}K", member_kb);
+        say!(self, "    mdadm: array {} started.", array);
+        say!(self, "    {} {}", "→".dimmed(), self.trf(Msg::ArrayCapacity, &[&(member_gb * data_disks)]));
+
+        if level == 0 {
+            return Ok(());
+        }
+        if self.quick_mode {
+            say!(self, "    {} resync deferred", "⏸".paint(self.theme.warning));
+            return Ok(());
+        }
+
+        let speed_kb: u64 = rng.gen_range(120_000..220_000);
+        let steps = 40;
+        for step in 0..=steps {
+            let pct = step as f64 * 100.0 / steps as f64;
+            let done = (member_kb as f64 * pct / 100.0) as u64;
+            let filled = step * 19 / steps;
+            let bar = format!("[{}>{}]", "=".repeat(filled), ".".repeat(19 - filled));
+            let finish_min = (member_kb - done) as f64 / speed_kb as f64 / 60.0;
+            say_inline!(self,
+                "\r    {}  resync = {:>5.1}% ({}/{}) finish={:.1}min speed={}K/sec   ",
+                bar.paint(self.theme.accent), pct, done, member_kb, finish_min, speed_kb + rng.gen_range(0..20_000)
+            );
+            self.pause(80);
+        }
+        say!(self);
+        say!(self, "    {} {}", "✓".paint(self.theme.success), self.trf(Msg::ArraySynced, &[&array]));
+        Ok(())
+    }
+
+    fn create_user(&mut self, username: &str, groups: &str) -> Result<(), String> {
+        say!(self, "  {} {}", "👤".normal(), self.trf(Msg::CreatingUser, &[&username.paint(self.theme.accent)]));
+        self.pause(300);
+        say!(self, "    {} {}", "→".dimmed(), self.trf(Msg::UserGroups, &[&groups.paint(self.theme.warning)]));
+        if self.verbose() { say!(self, "    {} useradd -m -G {} {}", "$".dimmed(), groups, username); }
+        say!(self, "    {} {}", "✓".paint(self.theme.success), self.tr(Msg::UserCreated));
+        Ok(())
+    }
+
+    fn manage_service(&mut self, service: &str, action: &str) -> Result<(), String> {
+        let (icon, msg) = match action {
+            "enable" => ("🔛", Msg::EnablingService), "disable" => ("🔚", Msg::DisablingService),
+            "start" => ("▶", Msg::StartingService), "stop" => ("⏹", Msg::StoppingService), _ => ("⚙", Msg::ManagingService),
+        };
+        say_inline!(self, "  {} {}", icon, self.trf(msg, &[&service.paint(self.theme.accent)]));
+        self.pause(200);
+        if self.verbose() { say!(self); say!(self, "    {} systemctl {} {}", "$".dimmed(), action, service); }
+        say!(self, "{}", "OK".paint(self.theme.success));
+        Ok(())
+    }
+
+    fn install_bootloader(&mut self, target: &str) -> Result<(), String> {
+        say!(self, "  {} {}", "🔧".normal(), self.trf(Msg::InstallingBootloader, &[&target.paint(self.theme.warning)]));
+        if !self.quick_mode {
+            for step in self.tr(Msg::BootloaderSteps).split('|') {
+                say_inline!(self, "    {} {}", "→".dimmed(), step);
+                self.pause(400);
+                say!(self, " {}", "✓".paint(self.theme.success));
+            }
+        }
+        say!(self, "    {} {}", "✓".paint(self.theme.success), self.trf(Msg::GrubInstalled, &[&target]));
+        Ok(())
+    }
+
+    fn check_integrity(&mut self, target: &str) -> Result<(), String> {
+        say!(self, "  {} {}", "🔍".normal(), self.trf(Msg::CheckingIntegrity, &[&target.paint(self.theme.accent)]));
+        if !self.quick_mode {
+            say_inline!(self, "    {} {}", "→".dimmed(), self.tr(Msg::ComputingChecksums));
+            let pb = self.out.progress_start(100);
+            pb.set_style(ProgressStyle::default_bar().template("[{bar:25.cyan/white}]").unwrap().progress_chars(&self.theme.bar));
+            for i in 0..=100 { pb.set_position(i); self.pause(15); }
+            pb.finish_and_clear();
+            say!(self, "{}", "OK".paint(self.theme.success));
+        }
+        say!(self, "    {} {}", "✓".paint(self.theme.success), self.tr(Msg::IntegrityConfirmed));
+        Ok(())
+    }
+
+    fn verify_signature(&mut self, file: &str) -> Result<(), String> {
+        say_inline!(self, "  {} {}", "🔏".normal(), self.trf(Msg::CheckingSignature, &[&file.paint(self.theme.accent)]));
+        self.pause(400);
+        say!(self, "{}", "VALID".paint(self.theme.success).bold());
+        if self.verbose() {
+            let mut rng = self.rng();
+            let key_id: u64 = rng.gen();
+            say!(self, "    {} Key ID: {:016X}", "→".dimmed(), key_id);
+        }
+        Ok(())
+    }
+
+    fn checksum(&mut self, file: &str, algo: ChecksumAlgo) -> Result<(), String> {
+        let sum = RandomSystemInfo::checksum(file, algo);
+        let label = self.trf(Msg::CheckingChecksum, &[&algo.name(), &file.paint(self.theme.accent)]);
+        say_inline!(self, "  {} {} ", "🔐".normal(), label);
+        if !self.quick_mode {
+            let spinner_chars = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+            for i in 0..8 {
+                say_inline!(self, "\r  {} {} {}", "🔐".normal(), label, spinner_chars[i % spinner_chars.len()].to_string().paint(self.theme.accent));
+                self.pause(80);
+            }
+        }
+        say!(self, "\r  {} {} {}", "🔐".normal(), label, "OK".paint(self.theme.success));
+        if self.verbose() {
+            say!(self, "    {} {}", "→".dimmed(), self.trf(Msg::ChecksumExpected, &[&sum.dimmed()]));
+            say!(self, "    {} {}", "→".dimmed(), self.trf(Msg::ChecksumActual, &[&sum.dimmed()]));
+        } else {
+            say!(self, "    {}  {}", sum.dimmed(), file);
+        }
+        Ok(())
+    }
+
+    fn fail(&mut self, message: &str, code: i32) -> Result<(), String> {
+        let message = self.interpolate(message);
+        if self.ignore_failures {
+            self.phase_stats.note(TextStyle::Warning);
+            say!(self, "  {} {} {}", "⚠".paint(self.theme.warning).bold(), message.paint(self.theme.warning), self.trf(Msg::FailureIgnored, &[&code]).dimmed());
+            return Ok(());
+        }
+        say!(self, "  {} {}", "✗".paint(self.theme.error).bold(), message.paint(self.theme.error).bold());
+        self.exit_code = Some(code);
+        Err(message)
+    }
+
+    /// Адрес и короткое имя репозитория: ppa:user/name → Launchpad, URL как есть
+    fn repo_source(name: &str) -> (String, String) {
+        if let Some(ppa) = name.strip_prefix("ppa:") {
+            let (user, archive) = ppa.split_once('/').unwrap_or((ppa, "ppa"));
+            (format!("https://ppa.launchpadcontent.net/{}/{}/ubuntu", user, archive), format!("{}-{}", user, archive))
+        } else if name.contains("://") {
+            let alias = Self::repo_dir(name);
+            (name.trim_end_matches('/').to_string(), alias.strip_suffix(".repo").unwrap_or(alias).to_string())
+        } else {
+            (format!("https://download.{}.org/linux", name), name.to_string())
+        }
+    }
+
+    // Короткая загрузка (индекс репозитория, образ ВМ); итоговую строку печатает вызывающий
+    fn transfer_bar(&mut self, label: &str, bytes: u64) {
+        if self.quick_mode {
+            return;
+        }
+        let mut rng = self.rng();
+        let pb = self.out.progress_start(bytes);
+        pb.set_style(ProgressStyle::default_bar()
+            .template("    {prefix} [{bar:20.cyan/blue}] {bytes}/{total_bytes}")
+            .unwrap()
+            .progress_chars(&self.theme.smooth));
+        pb.set_prefix(label.to_string());
+        while pb.position() < bytes {
+            pb.inc(rng.gen_range(bytes / 20..bytes / 8 + 1));
+            self.pause(40);
+        }
+        pb.finish_and_clear();
+    }
+
+    fn add_repo(&mut self, name: &str, flavor: Option<Distro>) -> Result<(), String> {
+        let mut rng = self.rng();
+        let distro = flavor.or(self.distro).unwrap_or(Distro::Apt);
+        let (url, alias) = Self::repo_source(name);
+        let index_size = rng.gen_range(8_000..400_000);
+
+        say!(self, "  {} {}", "📋".normal(), self.trf(Msg::AddingRepo, &[&name.paint(self.theme.accent)]));
+        match distro {
+            Distro::Apt => {
+                if self.verbose() { say!(self, "    {} add-apt-repository -y {}", "$".dimmed(), name); }
+                say!(self, "    Repository: 'deb {} jammy main'", url);
+                say!(self, "    Adding repository.");
+                say!(self, "    Hit:1 http://archive.ubuntu.com/ubuntu jammy InRelease");
+                say!(self, "    Hit:2 http://security.ubuntu.com/ubuntu jammy-security InRelease");
+                say!(self, "    Get:3 {} jammy InRelease [{}]", url, HumanBytes(rng.gen_range(17_000..24_000)));
+                self.transfer_bar("Get:4", index_size);
+                say!(self, "    Get:4 {} jammy/main amd64 Packages [{}]", url, HumanBytes(index_size));
+                say!(self, "    Fetched {} in {}s", HumanBytes(index_size + 20_000), rng.gen_range(1..4));
+                say!(self, "    Reading package lists... Done");
+            }
+            Distro::Dnf => {
+                if self.verbose() { say!(self, "    {} dnf config-manager --add-repo {}", "$".dimmed(), name); }
+                say!(self, "    Adding repo from: {}", url);
+                self.transfer_bar(&alias, index_size);
+                say!(self, "    {:<36} {:>5} kB/s | {:>9}     00:0{}",
+                    alias, rng.gen_range(200..4000), HumanBytes(index_size).to_string(), rng.gen_range(1..4));
+                say!(self, "    Metadata cache created.");
+            }
+            Distro::Pacman => {
+                say!(self, "    {} /etc/pacman.conf", "+".dimmed());
+                say!(self, "      {}", format!("[{}]", alias).dimmed());
+                say!(self, "      {}", format!("Server = {}/$arch", url).dimmed());
+                if self.verbose() { say!(self, "    {} pacman -Sy", "$".dimmed()); }
+                say!(self, "    {} Synchronizing package databases...", "::".paint(self.theme.heading).bold());
+                say!(self, "     core is up to date");
+                say!(self, "     extra is up to date");
+                self.transfer_bar(&alias, index_size);
+                say!(self, "     {:<20} {:>10}  {}/s 00:00 [######################] 100%",
+                    alias, HumanBytes(index_size).to_string(), HumanBytes(rng.gen_range(500_000..5_000_000)));
+            }
+            Distro::Zypper => {
+                if self.verbose() { say!(self, "    {} zypper addrepo {} {}", "$".dimmed(), url, alias); }
+                say!(self, "    Adding repository '{}' ....................[done]", alias);
+                say!(self, "    Repository '{}' successfully added", alias);
+                say!(self);
+                say!(self, "    URI         : {}", url);
+                say!(self, "    Enabled     : Yes");
+                say!(self, "    GPG Check   : Yes");
+                say!(self, "    Autorefresh : No");
+                say!(self);
+                self.transfer_bar(&alias, index_size);
+                say!(self, "    Retrieving repository '{}' metadata ........[done]", alias);
+                say!(self, "    Building repository '{}' cache .............[done]", alias);
+            }
+            Distro::Apk => {
+                say!(self, "    {} /etc/apk/repositories", "+".dimmed());
+                say!(self, "      {}", url.dimmed());
+                if self.verbose() { say!(self, "    {} apk update", "$".dimmed()); }
+                say!(self, "    fetch https://dl-cdn.alpinelinux.org/alpine/v3.19/main/x86_64/APKINDEX.tar.gz");
+                self.transfer_bar("APKINDEX", index_size);
+                say!(self, "    fetch {}/x86_64/APKINDEX.tar.gz", url);
+                say!(self, "    OK: {} distinct packages available", rng.gen_range(14_000..26_000));
+            }
+            Distro::Emerge => {
+                if self.verbose() { say!(self, "    {} eselect repository add {} git {}", "$".dimmed(), alias, url); }
+                say!(self, "    Adding {} to /etc/portage/repos.conf/eselect-repo.conf ...", alias.paint(self.theme.accent));
+                say!(self, "    Repository {} added", alias);
+                if self.verbose() { say!(self, "    {} emaint sync -r {}", "$".dimmed(), alias); }
+                say!(self, "    {} Syncing repository '{}' into '/var/db/repos/{}'...", ">>>".paint(self.theme.success), alias, alias);
+                self.transfer_bar(&alias, index_size);
+                say!(self, "    {} Sync completed for {}", "===".paint(self.theme.success), alias);
+            }
+        }
+
+        if self.state.repos.iter().any(|r| r == name) {
+            self.warn("    ", &self.trf(Msg::RepoAlreadyAdded, &[&name]));
+        } else {
+            self.state.repos.push(name.to_string());
+        }
+        say!(self, "    {} {}", "✓".paint(self.theme.success), self.trf(Msg::RepoAdded, &[&alias, &distro.name()]));
+        Ok(())
+    }
+
+    /// 40-символьный отпечаток GPG, оканчивающийся идентификатором ключа: "3A8F 31F1 ...  ... 6A75 5776"
+    fn key_fingerprint(key: &str) -> String {
+        let id = key.trim_start_matches("0x").trim_start_matches("0X").to_uppercase();
+        let digest = RandomSystemInfo::checksum(&id, ChecksumAlgo::Sha1).to_uppercase();
+        let full = format!("{}{}", &digest[..40 - id.len()], id);
+        let groups: Vec<&str> = (0..10).map(|i| &full[i * 4..i * 4 + 4]).collect();
+        format!("{}  {}", groups[..5].join(" "), groups[5..].join(" "))
+    }
+
+    fn import_key(&mut self, key: &str) -> Result<(), String> {
+        let distro = self.distro.unwrap_or(Distro::Apt);
+        let fingerprint = Self::key_fingerprint(key);
+        let compact = fingerprint.replace(' ', "");
+        let long_id = &compact[24..];
+        // Владелец ключа — последний добавленный репозиторий
+        let owner = match self.state.repos.last() {
+            Some(repo) => match repo.strip_prefix("ppa:") {
+                Some(ppa) => format!("Launchpad PPA for {}", ppa.split('/').next().unwrap_or(ppa)),
+                None => format!("{} Signing Key", Self::repo_source(repo).1),
+            },
+            None => "Package Signing Key".to_string(),
+        };
+
+        say!(self, "  {} {}", "🔑".normal(), self.trf(Msg::ImportingKey, &[&key.paint(self.theme.accent)]));
+        self.pause(300);
+        match distro {
+            Distro::Dnf | Distro::Zypper => {
+                if self.verbose() { say!(self, "    {} rpm --import {}", "$".dimmed(), key); }
+                say!(self, "    Importing GPG key 0x{}:", &long_id[8..]);
+                say!(self, "     Userid     : \"{}\"", owner);
+                say!(self, "     Fingerprint: {}", fingerprint);
+                say!(self, "    Key imported successfully");
+            }
+            Distro::Pacman => {
+                if self.verbose() { say!(self, "    {} pacman-key --recv-keys {}", "$".dimmed(), key); }
+                say!(self, "    gpg: key {}: public key \"{}\" imported", long_id, owner);
+                say!(self, "    {} Updating trust database...", "==>".paint(self.theme.success).bold());
+                say!(self, "    {} Locally signing key {}...", "  ->".paint(self.theme.heading).bold(), compact);
+                say!(self, "    {} Locally signed 1 keys.", "==>".paint(self.theme.success).bold());
+            }
+            Distro::Apt | Distro::Apk | Distro::Emerge => {
+                if self.verbose() { say!(self, "    {} gpg --keyserver keyserver.ubuntu.com --recv-keys {}", "$".dimmed(), key); }
+                say!(self, "    gpg: key {}: public key \"{}\" imported", long_id, owner);
+                say!(self, "    gpg: Total number processed: 1");
+                say!(self, "    gpg:               imported: 1");
+                say!(self, "    Fingerprint: {}", fingerprint);
+            }
+        }
+        say!(self, "    {} {}", "✓".paint(self.theme.success), self.trf(Msg::KeyImported, &[&&long_id[8..]]));
+        Ok(())
+    }
+
+    fn install_packages(&mut self, packages: &str) -> Result<(), String> {
+        let pkg_list: Vec<&str> = packages.split_whitespace().collect();
+        say!(self, "  {} {}", "📦".normal(), self.trf(Msg::InstallingPackages, &[&pkg_list.len()]));
+        if !self.state.repos.is_empty() {
+            let aliases: Vec<String> = self.state.repos.iter().map(|r| Self::repo_source(r).1).collect();
+            say!(self, "    {} {}", "→".dimmed(), self.trf(Msg::ExtraRepos, &[&aliases.join(", ").paint(self.theme.accent)]));
+        }
+        if let Some(distro) = self.distro {
+            let files = self.package_files(distro, &pkg_list);
+            match distro {
+                Distro::Apt => self.apt_install(&files),
+                Distro::Dnf => self.dnf_install(&files),
+                Distro::Pacman => self.pacman_install(&files),
+                Distro::Zypper => self.zypper_install(&files),
+                Distro::Apk => self.apk_install(&files),
+                Distro::Emerge => self.emerge_install(&files),
+            }
+            return Ok(());
+        }
+        if !self.quick_mode {
+            for pkg in &pkg_list {
+                say_inline!(self, "    {} {} ", "→".dimmed(), pkg.paint(self.theme.accent));
+                let spinner_chars = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+                for i in 0..10 {
+                    say_inline!(self, "\r    {} {} {}", "→".dimmed(), pkg.paint(self.theme.accent), spinner_chars[i % spinner_chars.len()].to_string().paint(self.theme.accent));
+                    self.pause(80);
+                }
+                say!(self, "\r    {} {} {}", "→".dimmed(), pkg.paint(self.theme.accent), "✓".paint(self.theme.success));
+            }
+        } else {
+            for pkg in &pkg_list { say!(self, "    {} {} {}", "→".dimmed(), pkg.paint(self.theme.accent), "✓".paint(self.theme.success)); }
+        }
+        Ok(())
+    }
+
+    /// Версии и размеры пакетов; версия оформлена так, как её пишет пакетный менеджер
+    fn package_files(&mut self, distro: Distro, names: &[&str]) -> Vec<PackageFile> {
+        let mut rng = self.rng();
+        names.iter()
+            .map(|name| {
+                let base = format!("{}.{}.{}", rng.gen_range(1..10), rng.gen_range(0..40), rng.gen_range(0..10));
+                let release = rng.gen_range(1..4);
+                let version = match distro {
+                    Distro::Apt => format!("{}-{}ubuntu{}", base, release, rng.gen_range(1..3)),
+                    Distro::Dnf => format!("{}-{}.fc39", base, release),
+                    Distro::Pacman => format!("{}-{}", base, release),
+                    Distro::Zypper => format!("{}-{}.{}", base, release, rng.gen_range(1..3)),
+                    Distro::Apk => format!("{}-r{}", base, release - 1),
+                    Distro::Emerge => base,
+                };
+                // Кратно KiB, чтобы суммы в KiB у emerge сходились с построчными
+                let download = rng.gen_range(40..25_000) * 1024;
+                let repo = if !self.state.repos.is_empty() && rng.gen_bool(0.5) {
+                    Some(Self::repo_source(&self.state.repos[rng.gen_range(0..self.state.repos.len())]).1)
+                } else {
+                    None
+                };
+                PackageFile { name: name.to_string(), version, download, installed: download * rng.gen_range(25..45) / 10, repo }
+            })
+            .collect()
+    }
+
+    fn apt_install(&mut self, files: &[PackageFile]) {
+        let mut rng = self.rng();
+        let total: u64 = files.iter().map(|f| f.download).sum();
+        let installed: u64 = files.iter().map(|f| f.installed).sum();
+        let names: Vec<&str> = files.iter().map(|f| f.name.as_str()).collect();
+
+        if self.verbose() { say!(self, "    {} apt-get install -y {}", "$".dimmed(), names.join(" ")); }
+        say!(self, "    Reading package lists... Done");
+        say!(self, "    Building dependency tree... Done");
+        say!(self, "    Reading state information... Done");
+        say!(self, "    The following NEW packages will be installed:");
+        say!(self, "      {}", names.join(" "));
+        say!(self, "    0 upgraded, {} newly installed, 0 to remove and 0 not upgraded.", files.len());
+        say!(self, "    Need to get {} of archives.", DecimalBytes(total));
+        say!(self, "    After this operation, {} of additional disk space will be used.", DecimalBytes(installed));
+        for (i, f) in files.iter().enumerate() {
+            let mirror = match &f.repo {
+                Some(repo) => self.state.repos.iter()
+                    .map(|r| Self::repo_source(r))
+                    .find(|(_, alias)| alias == repo)
+                    .map(|(url, _)| url)
+                    .unwrap_or_default(),
+                None => "http://archive.ubuntu.com/ubuntu".to_string(),
+            };
+            self.pause(f.download / 100_000 + 30);
+            say!(self, "    Get:{} {} jammy/main amd64 {} amd64 {} [{} kB]", i + 1, mirror, f.name, f.version, f.download / 1000);
+        }
+        say!(self, "    Fetched {} in {}s ({}/s)", DecimalBytes(total), total / 8_000_000 + 1, DecimalBytes(total / (total / 8_000_000 + 1)));
+        let mut database = rng.gen_range(120_000..260_000);
+        for f in files {
+            say!(self, "    Selecting previously unselected package {}.", f.name);
+            say!(self, "    (Reading database ... {} files and directories currently installed.)", database);
+            say!(self, "    Preparing to unpack .../{}_{}_amd64.deb ...", f.name, f.version);
+            self.pause(rng.gen_range(80..250));
+            say!(self, "    Unpacking {} ({}) ...", f.name, f.version);
+            database += f.installed / 40_000 + 1;
+        }
+        for f in files {
+            self.pause(rng.gen_range(60..200));
+            say!(self, "    Setting up {} ({}) ...", f.name, f.version);
+        }
+        say!(self, "    Processing triggers for man-db (2.10.2-1) ...");
+    }
+
+    fn dnf_install(&mut self, files: &[PackageFile]) {
+        let mut rng = self.rng();
+        let total: u64 = files.iter().map(|f| f.download).sum();
+        let installed: u64 = files.iter().map(|f| f.installed).sum();
+        let n = files.len();
+        let rule = "=".repeat(72);
+        let rpm = |f: &PackageFile| format!("{}-{}.x86_64", f.name, f.version);
+
+        if self.verbose() {
+            let names: Vec<&str> = files.iter().map(|f| f.name.as_str()).collect();
+            say!(self, "    {} dnf install -y {}", "$".dimmed(), names.join(" "));
+        }
+        say!(self, "    Dependencies resolved.");
+        say!(self, "    {}", rule);
+        say!(self, "     {:<22} {:<8} {:<20} {:<16} {:>9}", "Package", "Arch", "Version", "Repository", "Size");
+        say!(self, "    {}", rule);
+        say!(self, "    Installing:");
+        for f in files {
+            let repo = f.repo.clone().unwrap_or_else(|| if rng.gen_bool(0.5) { "fedora" } else { "updates" }.to_string());
+            say!(self, "     {:<22} {:<8} {:<20} {:<16} {:>9}", f.name.bold(), "x86_64", f.version, repo, HumanBytes(f.download).to_string());
+        }
+        say!(self);
+        say!(self, "    Transaction Summary");
+        say!(self, "    {}", rule);
+        say!(self, "    Install  {} Package{}", n, if n == 1 { "" } else { "s" });
+        say!(self);
+        say!(self, "    Total download size: {}", HumanBytes(total));
+        say!(self, "    Installed size: {}", HumanBytes(installed));
+        say!(self, "    Downloading Packages:");
+        for (i, f) in files.iter().enumerate() {
+            self.pause(f.download / 100_000 + 30);
+            say!(self, "    ({}/{}): {:<40} {:>9}/s | {:>9}     00:00",
+                i + 1, n, format!("{}.rpm", rpm(f)), HumanBytes(rng.gen_range(2_000_000..20_000_000)).to_string(), HumanBytes(f.download).to_string());
+        }
+        say!(self, "    {}", "-".repeat(72));
+        say!(self, "    {:<46} {:>9}/s | {:>9}     00:{:02}",
+            "Total", HumanBytes(total / (total / 10_000_000 + 1)).to_string(), HumanBytes(total).to_string(), total / 10_000_000 + 1);
+        say!(self, "    Running transaction check");
+        say!(self, "    Transaction check succeeded.");
+        say!(self, "    Running transaction test");
+        say!(self, "    Transaction test succeeded.");
+        say!(self, "    Running transaction");
+        say!(self, "      {:<17}: {:<44} {:>5}", "Preparing", "", "1/1");
+        for (i, f) in files.iter().enumerate() {
+            self.pause(rng.gen_range(80..250));
+            say!(self, "      {:<17}: {:<44} {:>5}", "Installing", rpm(f), format!("{}/{}", i + 1, n));
+        }
+        for (i, f) in files.iter().enumerate() {
+            say!(self, "      {:<17}: {:<44} {:>5}", "Verifying", rpm(f), format!("{}/{}", i + 1, n));
+        }
+        say!(self);
+        say!(self, "    Installed:");
+        for f in files {
+            say!(self, "      {}", rpm(f));
+        }
+        say!(self);
+        say!(self, "    Complete!");
+    }
+
+    fn pacman_install(&mut self, files: &[PackageFile]) {
+        let mut rng = self.rng();
+        let total: u64 = files.iter().map(|f| f.download).sum();
+        let installed: u64 = files.iter().map(|f| f.installed).sum();
+        let n = files.len();
+        let full_bar = "[######################] 100%";
+
+        if self.verbose() {
+            let names: Vec<&str> = files.iter().map(|f| f.name.as_str()).collect();
+            say!(self, "    {} pacman -S --noconfirm {}", "$".dimmed(), names.join(" "));
+        }
+        say!(self, "    resolving dependencies...");
+        say!(self, "    looking for conflicting packages...");
+        say!(self);
+        let list: Vec<String> = files.iter().map(|f| format!("{}-{}", f.name, f.version)).collect();
+        say!(self, "    Packages ({}) {}", n, list.join("  "));
+        say!(self);
+        say!(self, "    Total Download Size:   {}", HumanBytes(total));
+        say!(self, "    Total Installed Size:  {}", HumanBytes(installed));
+        say!(self);
+        say!(self, "    {} Proceed with installation? [Y/n] y", "::".paint(self.theme.heading).bold());
+        say!(self, "    {} Retrieving packages...", "::".paint(self.theme.heading).bold());
+        for f in files {
+            self.pause(f.download / 100_000 + 30);
+            say!(self, "     {:<30} {:>10}  {:>9}/s 00:00 {}",
+                format!("{}-{}-x86_64", f.name, f.version), HumanBytes(f.download).to_string(),
+                HumanBytes(rng.gen_range(2_000_000..30_000_000)).to_string(), full_bar);
+        }
+        say!(self, "     {:<30} {:>10}  {:>9}/s 00:{:02} {}",
+            format!("Total ({}/{})", n, n), HumanBytes(total).to_string(),
+            HumanBytes(total / (total / 10_000_000 + 1)).to_string(), total / 10_000_000 + 1, full_bar);
+        for stage in ["checking keys in keyring", "checking package integrity", "loading package files",
+                      "checking for file conflicts", "checking available disk space"] {
+            self.pause(120);
+            say!(self, "    ({}/{}) {:<40} {}", n, n, stage, full_bar);
+        }
+        say!(self, "    {} Processing package changes...", "::".paint(self.theme.heading).bold());
+        for (i, f) in files.iter().enumerate() {
+            self.pause(rng.gen_range(80..250));
+            say!(self, "    ({}/{}) {:<40} {}", i + 1, n, format!("installing {}", f.name), full_bar);
+        }
+        say!(self, "    {} Running post-transaction hooks...", "::".paint(self.theme.heading).bold());
+        say!(self, "    (1/2) Arming ConditionNeedsUpdate...");
+        say!(self, "    (2/2) Updating the info directory file...");
+    }
+
+    fn zypper_install(&mut self, files: &[PackageFile]) {
+        let mut rng = self.rng();
+        let total: u64 = files.iter().map(|f| f.download).sum();
+        let installed: u64 = files.iter().map(|f| f.installed).sum();
+        let n = files.len();
+        let rpm = |f: &PackageFile| format!("{}-{}.x86_64", f.name, f.version);
+
+        let names: Vec<&str> = files.iter().map(|f| f.name.as_str()).collect();
+        if self.verbose() { say!(self, "    {} zypper --non-interactive install {}", "$".dimmed(), names.join(" ")); }
+        say!(self, "    Loading repository data...");
+        say!(self, "    Reading installed packages...");
+        say!(self, "    Resolving package dependencies...");
+        say!(self);
+        say!(self, "    The following {} NEW package{} going to be installed:", n, if n == 1 { " is" } else { "s are" });
+        say!(self, "      {}", names.join(" ").paint(self.theme.success));
+        say!(self);
+        say!(self, "    {} new package{} to install.", n, if n == 1 { "" } else { "s" });
+        say!(self, "    Overall download size: {}. Already cached: 0 B. After the operation, additional {} will be used.",
+            HumanBytes(total), HumanBytes(installed));
+        say!(self, "    Continue? [y/n/v/...? shows all options] (y): y");
+        for (i, f) in files.iter().enumerate() {
+            let repo = f.repo.clone().unwrap_or("Main Repository (OSS)".to_string());
+            self.pause(f.download / 100_000 + 30);
+            say!(self, "    Retrieving: {} ({}) ({}/{}), {:>9} [done ({}/s)]",
+                rpm(f), repo, i + 1, n, HumanBytes(f.download).to_string(), HumanBytes(rng.gen_range(2_000_000..30_000_000)));
+        }
+        say!(self, "    Checking for file conflicts: ...........................[done]");
+        for (i, f) in files.iter().enumerate() {
+            self.pause(rng.gen_range(80..250));
+            say!(self, "    ({}/{}) Installing: {} ...[done]", i + 1, n, rpm(f));
+        }
+    }
+
+    fn apk_install(&mut self, files: &[PackageFile]) {
+        let mut rng = self.rng();
+        let installed: u64 = files.iter().map(|f| f.installed).sum();
+        let n = files.len();
+
+        if self.verbose() {
+            let names: Vec<&str> = files.iter().map(|f| f.name.as_str()).collect();
+            say!(self, "    {} apk add {}", "$".dimmed(), names.join(" "));
+        }
+        for (i, f) in files.iter().enumerate() {
+            self.pause(f.download / 100_000 + 50);
+            say!(self, "    ({}/{}) Installing {} ({})", i + 1, n, f.name, f.version);
+        }
+        say!(self, "    Executing busybox-1.36.1-r15.trigger");
+        let base: u64 = rng.gen_range(20_000_000..200_000_000);
+        say!(self, "    OK: {} in {} packages", HumanBytes(base + installed), rng.gen_range(20..120) + n);
+    }
+
+    fn emerge_install(&mut self, files: &[PackageFile]) {
+        let mut rng = self.rng();
+        let categories = ["app-misc", "app-editors", "dev-libs", "dev-vcs", "net-misc", "sys-apps", "media-libs", "x11-libs"];
+        let total: u64 = files.iter().map(|f| f.download).sum();
+        let n = files.len();
+        let atoms: Vec<String> = files.iter()
+            .map(|f| {
+                let name = if f.name.contains('/') {
+                    f.name.clone()
+                } else {
+                    format!("{}/{}", categories[rng.gen_range(0..categories.len())], f.name)
+                };
+                format!("{}-{}::{}", name, f.version, f.repo.as_deref().unwrap_or("gentoo"))
+            })
+            .collect();
+
+        if self.verbose() {
+            let names: Vec<&str> = files.iter().map(|f| f.name.as_str()).collect();
+            say!(self, "    {} emerge --ask=n {}", "$".dimmed(), names.join(" "));
+        }
+        say!(self);
+        say!(self, "    These are the packages that would be merged, in order:");
+        say!(self);
+        say_inline!(self, "    Calculating dependencies... ");
+        self.pause(600);
+        say!(self, "done!");
+        for (atom, f) in atoms.iter().zip(files) {
+            say!(self, "    [{}  {}     ] {} {} KiB", "ebuild".paint(self.theme.success), "N".paint(self.theme.success).bold(), atom.paint(self.theme.success), f.download / 1024);
+        }
+        say!(self);
+        say!(self, "    Total: {} package{} ({} new), Size of downloads: {} KiB", n, if n == 1 { "" } else { "s" }, n, total / 1024);
+        say!(self);
+        say!(self, "    {} Verifying ebuild manifests", ">>>".paint(self.theme.success));
+        for (i, atom) in atoms.iter().enumerate() {
+            say!(self);
+            say!(self, "    {} Emerging ({} of {}) {}", ">>>".paint(self.theme.success), i + 1, n, atom);
+            self.pause(rng.gen_range(200..600));
+            say!(self, "    {} Installing ({} of {}) {}", ">>>".paint(self.theme.success), i + 1, n, atom);
+            say!(self, "    {} Completed ({} of {}) {}", ">>>".paint(self.theme.success), i + 1, n, atom);
+        }
+        say!(self);
+        say!(self, "    {} Jobs: {} of {} complete                       Load avg: {:.2}, {:.2}, {:.2}",
+            ">>>".paint(self.theme.success), n, n, rng.gen_range(0.5..4.0), rng.gen_range(0.5..3.5), rng.gen_range(0.5..3.0));
+    }
+
+    fn install_lang_deps(&mut self, manager: LangManager, packages: &str) -> Result<(), String> {
+        let mut rng = self.rng();
+        let version = |rng: &mut StdRng| format!("{}.{}.{}", rng.gen_range(0..4), rng.gen_range(0..30), rng.gen_range(0..20));
+
+        // Запрошенные пакеты, затем их транзитивные зависимости: (имя, версия, индекс запросившего)
+        let requested: Vec<(String, String)> = packages.split_whitespace()
+            .map(|spec| {
+                let (name, pinned) = manager.split_spec(spec);
+                (name.to_string(), pinned.map(str::to_string).unwrap_or_else(|| version(&mut rng)))
+            })
+            .collect();
+        let mut deps: Vec<(String, String, usize)> = Vec::new();
+        let pool = manager.dependency_pool();
+        for parent in 0..requested.len() {
+            for _ in 0..rng.gen_range(3..=8) {
+                let free: Vec<&str> = pool.iter().copied()
+                    .filter(|d| !deps.iter().any(|(n, _, _)| n == d) && !requested.iter().any(|(n, _)| n == d))
+                    .collect();
+                if free.is_empty() {
+                    break;
+                }
+                deps.push((free[rng.gen_range(0..free.len())].to_string(), version(&mut rng), parent));
+            }
+        }
+
+        let names: Vec<&str> = requested.iter().map(|(n, _)| n.as_str()).collect();
+        match manager {
+            LangManager::Pip => {
+                say!(self, "  {} pip install {}", "🐍".normal(), packages.paint(self.theme.accent));
+                // Пакеты с C-расширениями приходят платформенными колёсами
+                let native = ["numpy", "pandas", "scipy", "lxml", "pillow", "cryptography", "psycopg2", "pyyaml",
+                              "markupsafe", "charset-normalizer", "pydantic-core", "grpcio", "torch"];
+                let all = requested.iter().map(|(n, v)| (n, v, None))
+                    .chain(deps.iter().map(|(n, v, p)| (n, v, Some(*p))));
+                for (name, ver, parent) in all {
+                    let is_native = native.contains(&name.to_lowercase().as_str());
+                    let wheel = if is_native {
+                        format!("{}-{}-cp312-cp312-manylinux_2_17_x86_64.whl", name.replace('-', "_"), ver)
+                    } else {
+                        format!("{}-{}-py3-none-any.whl", name.replace('-', "_"), ver)
+                    };
+                    let size = if is_native { rng.gen_range(2_000_000..40_000_000) } else { rng.gen_range(10_000..600_000) };
+                    match parent {
+                        Some(p) => say!(self, "    Collecting {}>={} (from {})", name, ver, requested[p].0),
+                        None => say!(self, "    Collecting {}", name),
+                    }
+                    say!(self, "      Downloading {} ({})", wheel, DecimalBytes(size));
+                    self.pause(size / 200_000 + 40);
+                    say!(self, "         {} {}/{} {}/s eta 0:00:00", "━".repeat(40).paint(self.theme.success),
+                        DecimalBytes(size), DecimalBytes(size), DecimalBytes(rng.gen_range(2_000_000..30_000_000)));
+                }
+                let collected: Vec<&str> = deps.iter().map(|(n, _, _)| n.as_str()).chain(names.iter().copied()).collect();
+                say!(self, "    Installing collected packages: {}", collected.join(", "));
+                self.pause(300 + collected.len() as u64 * 60);
+                let installed: Vec<String> = deps.iter().map(|(n, v, _)| format!("{}-{}", n, v))
+                    .chain(requested.iter().map(|(n, v)| format!("{}-{}", n, v)))
+                    .collect();
+                say!(self, "    Successfully installed {}", installed.join(" "));
+            }
+            LangManager::Npm => {
+                say!(self, "  {} npm install {}", "📦".normal(), packages.paint(self.theme.accent));
+                let total = requested.len() + deps.len();
+                if !self.quick_mode {
+                    let spinner_chars = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+                    let steps = deps.iter().map(|(n, _, _)| n).chain(requested.iter().map(|(n, _)| n));
+                    for (i, name) in steps.enumerate() {
+                        let filled = (i + 1) * 18 / total;
+                        say_inline!(self, "\r    ⸨{}{}⸩ {} reify:{}: timing reifyNode:node_modules/{}\x1b[K",
+                            "#".repeat(filled), "░".repeat(18 - filled),
+                            spinner_chars[i % spinner_chars.len()].to_string().paint(self.theme.accent), name, name);
+                        self.pause(rng.gen_range(40..120));
+                    }
+                    say_inline!(self, "\r\x1b[K");
+                }
+                if rng.gen_bool(0.4) {
+                    say!(self, "    {} {} inflight@1.0.6: This module is not supported, and leaks memory.",
+                        "npm".normal(), "WARN deprecated".on_yellow().black());
+                }
+                say!(self);
+                say!(self, "    added {} packages, and audited {} packages in {}s", total, total + 1, total / 10 + 1);
+                say!(self);
+                let funding = rng.gen_range(0..=total / 3);
+                if funding > 0 {
+                    say!(self, "    {} package{} looking for funding", funding, if funding == 1 { " is" } else { "s are" });
+                    say!(self, "      run `npm fund` for details");
+                    say!(self);
+                }
+                say!(self, "    found {} vulnerabilities", "0".paint(self.theme.success).bold());
+            }
+            LangManager::Cargo => {
+                say!(self, "  {} cargo build --release ({})", "🦀".normal(), packages.paint(self.theme.accent));
+                let tag = |word: &str| format!("{:>12}", word).paint(self.theme.success).bold();
+                say!(self, "    {} crates.io index", tag("Updating"));
+                self.pause(400);
+                let all: Vec<(&str, &str)> = deps.iter().map(|(n, v, _)| (n.as_str(), v.as_str()))
+                    .chain(requested.iter().map(|(n, v)| (n.as_str(), v.as_str())))
+                    .collect();
+                let mut bytes = 0;
+                for (name, ver) in &all {
+                    bytes += rng.gen_range(8_000..400_000);
+                    self.pause(rng.gen_range(20..60));
+                    say!(self, "    {} {} v{}", tag("Downloaded"), name, ver);
+                }
+                say!(self, "    {} {} crates ({}) in {:.2}s", tag("Downloaded"), all.len(), DecimalBytes(bytes), rng.gen_range(0.3..2.5));
+                let mut seconds = 0.0;
+                for (name, ver) in &all {
+                    let cost: f64 = rng.gen_range(0.2..3.0);
+                    seconds += cost;
+                    self.pause((cost * 100.0) as u64);
+                    say!(self, "    {} {} v{}", tag("Compiling"), name, ver);
+                }
+                say!(self, "    {} release [optimized] target(s) in {:.2}s", tag("Finished"), seconds);
+            }
+        }
+        Ok(())
+    }
+
+    fn update_system(&mut self) -> Result<(), String> {
+        say!(self, "  {} {}", "🔄".normal(), self.tr(Msg::UpdatingSystem));
+        if !self.quick_mode {
+            for stage in self.tr(Msg::UpdateStages).split('|') {
+                say_inline!(self, "    {} {}", "→".dimmed(), stage);
+                self.pause(500);
+                say!(self, " {}", "✓".paint(self.theme.success));
+            }
+        }
+        let mut rng = self.rng();
+        let updated = rng.gen_range(50..200);
+        say!(self, "    {} {}", "✓".paint(self.theme.success), self.trf(Msg::SystemUpdated, &[&self.trn(Msg::Packages, updated)]));
+        Ok(())
+    }
+
+    fn sync_time(&mut self) -> Result<(), String> {
+        say_inline!(self, "  {} {}", "🕐".normal(), self.tr(Msg::SyncingTime));
+        self.pause(500);
+        say!(self, "{}", "OK".paint(self.theme.success));
+        if self.verbose() {
+            say!(self, "    {} {}", "→".dimmed(), self.tr(Msg::NtpServer));
+            say!(self, "    {} {}", "→".dimmed(), self.tr(Msg::NtpOffset));
+        }
+        Ok(())
+    }
+
+    fn network_config(&mut self, interface: &str, config: &str) -> Result<(), String> {
+        say!(self, "  {} {}", "🌐".normal(), self.trf(Msg::ConfiguringNetwork, &[&interface.paint(self.theme.accent), &config.paint(self.theme.warning)]));
+        if !self.quick_mode {
+            if config == "dhcp" {
+                say_inline!(self, "    {} {}", "→".dimmed(), self.tr(Msg::DhcpLease));
+                self.pause(800);
+                let ip = RandomSystemInfo::ip_address(&mut self.rng);
+                say!(self, "{}", ip.paint(self.theme.success));
+            } else {
+                say!(self, "    {} {}", "→".dimmed(), self.tr(Msg::StaticConfig));
+                self.pause(300);
+            }
+            say!(self, "    {} {}", "→".dimmed(), self.tr(Msg::CheckingConnectivity));
+            self.pause(400);
+        }
+        say!(self, "    {} {}", "✓".paint(self.theme.success), self.tr(Msg::NetworkConfigured));
+        Ok(())
+    }
+
+    fn scan_hardware(&mut self) -> Result<(), String> {
+        say!(self, "  {} {}", "🔎".normal(), self.tr(Msg::ScanningHardware));
+        if !self.quick_mode {
+            let buses = ["PCI", "USB", "ACPI", "SATA", "NVMe"];
+            for (bus, found) in buses.into_iter().zip(self.tr(Msg::ScanFound).split('|')) {
+                say_inline!(self, "    {} {}", "→".dimmed(), self.trf(Msg::ScanBus, &[&bus.paint(self.theme.accent)]));
+                self.pause(300);
+                say!(self, "{}", found.dimmed());
+            }
+        }
+        say!(self, "    {} {}", "✓".paint(self.theme.success), self.tr(Msg::ScanComplete));
+        Ok(())
+    }
+
+    fn detect_drivers(&mut self) -> Result<(), String> {
+        say!(self, "  {} {}", "🔍".normal(), self.tr(Msg::DetectingDrivers));
+        if !self.quick_mode {
+            for (drv, desc) in self.hardware().drivers() {
+                say!(self, "    {} {} - {}", "+".dimmed(), drv.paint(self.theme.accent), desc.dimmed());
+                self.pause(150);
+            }
+        }
+        Ok(())
+    }
+
+    fn print_table(&mut self, title: &str, rows: &[Vec<String>]) -> Result<(), String> {
+        say!(self, "  {}", self.interpolate(title).white().bold());
+        let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+        if columns == 0 {
+            return Ok(());
+        }
+
+        // Короткие строки дополняются пустыми ячейками
+        let rows: Vec<Vec<String>> = rows.iter()
+            .map(|row| (0..columns).map(|c| row.get(c).map(|cell| self.interpolate(cell)).unwrap_or_default()).collect())
+            .collect();
+        let widths: Vec<usize> = (0..columns)
+            .map(|c| rows.iter().map(|row| row[c].chars().count()).max().unwrap_or(0))
+            .collect();
+
+        let border = |left: &str, mid: &str, right: &str| {
+            let segments: Vec<String> = widths.iter().map(|w| "─".repeat(w + 2)).collect();
+            format!("  {}{}{}", left, segments.join(mid), right).paint(self.theme.accent)
+        };
+        let line = |row: &[String], header: bool| {
+            let cells: Vec<String> = row.iter().zip(&widths)
+                .map(|(cell, w)| {
+                    let padded = format!(" {}{} ", cell, " ".repeat(w - cell.chars().count()));
+                    if header { padded.paint(self.theme.success).bold().to_string() } else { padded }
+                })
+                .collect();
+            let bar = "│".paint(self.theme.accent).to_string();
+            format!("  {}{}{}", bar, cells.join(&bar), bar)
+        };
+
+        say!(self, "{}", border("┌", "┬", "┐"));
+        say!(self, "{}", line(&rows[0], true));
+        if rows.len() > 1 {
+            say!(self, "{}", border("├", "┼", "┤"));
+            for row in &rows[1..] {
+                say!(self, "{}", line(row, false));
+            }
+        }
+        say!(self, "{}", border("└", "┴", "┘"));
+        self.pause(200);
+        Ok(())
+    }
+
+    fn log_tail(&mut self, lines: u64, style: LogStyle) -> Result<(), String> {
+        let mut rng = self.rng();
+        // В быстром режиме показываем только хвост лога
+        let shown = if self.quick_mode { lines.min(5) } else { lines };
+        let mut elapsed_ms: u64 = rng.gen_range(0..1000);
+
+        for _ in 0..shown {
+            let line = FakeLog::line(style, &mut rng);
+            let pause = rng.gen_range(20..150);
+            elapsed_ms += pause;
+            if self.verbose() {
+                let stamp = format!("[{:02}:{:02}.{:03}]", elapsed_ms / 60_000, elapsed_ms / 1000 % 60, elapsed_ms % 1000);
+                say!(self, "    {} {}", stamp.dimmed(), line.dimmed());
+            } else {
+                say!(self, "    {}", line.dimmed());
+            }
+            self.pause(pause);
+        }
+        Ok(())
+    }
+
+    fn install_driver(&mut self, driver: &str) -> Result<(), String> {
+        let label = self.trf(Msg::InstallingDriver, &[&driver.paint(self.theme.accent)]);
+        say_inline!(self, "  {} {} ", "📦".normal(), label);
+        if !self.quick_mode {
+            let spinner_chars = ['⣾', '⣽', '⣻', '⢿', '⡿', '⣟', '⣯', '⣷'];
+            for i in 0..15 {
+                say_inline!(self, "\r  {} {} {}", "📦".normal(), label, spinner_chars[i % spinner_chars.len()].to_string().paint(self.theme.accent));
+                self.pause(100);
+            }
+        }
+        say!(self, "\r  {} {} {}", "📦".normal(), label, "✓".paint(self.theme.success));
+        Ok(())
+    }
+
+    // ===== Интерактивные команды =====
+
+    /// Подставляет значения переменных вместо `${имя}`; неизвестные переменные остаются как есть
+    fn interpolate(&self, text: &str) -> String {
+        let mut result = String::new();
+        let mut rest = text;
+        while let Some(start) = rest.find("${") {
+            result.push_str(&rest[..start]);
+            let after = &rest[start + 2..];
+            match after.find('}') {
+                Some(end) => {
+                    match self.variables.get(&after[..end]) {
+                        Some(value) => result.push_str(value),
+                        None => result.push_str(&rest[start..start + 2 + end + 1]),
+                    }
+                    rest = &after[end + 1..];
+                }
+                None => {
+                    result.push_str(&rest[start..]);
+                    rest = "";
+                }
+            }
+        }
+        result.push_str(rest);
+        result
+    }
+
+    /// Читает строку ответа из stdin; None — ответ по умолчанию
+    fn read_answer(&self) -> Option<String> {
+        if self.non_interactive || self.quick_mode {
+            return None;
+        }
+        let mut input = String::new();
+        io::stdin().lock().read_line(&mut input).ok()?;
+        let input = input.trim();
+        if input.is_empty() { None } else { Some(input.to_string()) }
+    }
+
+    /// Приглашение sudo. В терминале пароль читается без эха и выбрасывается; пустой ввод —
+    /// «Sorry, try again», после трёх пустых — отказ, как у sudo. Без терминала и в --quick
+    /// пароль «набирается» звёздочками. Никаких прав, разумеется, не получается
+    fn ask_sudo_password(&mut self) -> Result<(), String> {
+        if self.sudo_accepted {
+            return Ok(());
+        }
+        let user = user_name();
+        let prompt = format!("[sudo] password for {}: ", user);
+        if self.non_interactive || self.quick_mode {
+            say_inline!(self, "{}", prompt);
+            let length = self.rng.gen_range(8..=14);
+            self.type_answer(&"*".repeat(length));
+        } else {
+            let mut attempts = 0;
+            loop {
+                say_inline!(self, "{}", prompt);
+                let password = read_hidden_line(self.interrupt);
+                say!(self);
+                self.check_interrupt()?;
+                if password.is_some_and(|p| !p.is_empty()) {
+                    break;
+                }
+                attempts += 1;
+                if attempts == 3 {
+                    say!(self, "sudo: 3 incorrect password attempts");
+                    return Err(self.tr(Msg::SudoNoPassword).to_string());
+                }
+                self.pause(1500);
+                say!(self, "Sorry, try again.");
+            }
+        }
+        self.sudo_accepted = true;
+        Ok(())
+    }
+
+    /// Печатает автоматический ответ посимвольно, как будто его набирают
+    fn type_answer(&self, answer: &str) {
+        self.pause(600);
+        for c in answer.chars() {
+            say_inline!(self, "{}", c.to_string().paint(self.theme.accent));
+            self.pause(120);
+        }
+        say!(self);
+    }
+
+    /// Печатает текст посимвольно со скоростью `cps` символов в секунду.
+    /// Каждая строка многострочного текста выводится с отступом, как продолжение первой.
+    fn type_text(&mut self, text: &str, cps: u64, style: TextStyle) -> Result<(), String> {
+        let text = self.interpolate(text);
+        let char_delay = (1000 / cps).max(1);
+        self.phase_stats.note(style);
+
+        for (i, line) in text.split('\n').enumerate() {
+            if i == 0 {
+                say_inline!(self, "  {} ", style.symbol(&self.theme));
+            } else {
+                say_inline!(self, "    ");
+            }
+            if self.quick_mode {
+                say_inline!(self, "{}", style.paint(line, &self.theme));
+            } else {
+                for c in line.chars() {
+                    say_inline!(self, "{}", style.paint(&c.to_string(), &self.theme));
+                    self.pause(char_delay);
+                }
+            }
+            say!(self);
+        }
+        Ok(())
+    }
+
+    fn ask(&mut self, prompt: &str, default: bool, required: bool) -> Result<(), String> {
+        let hint = if default { "[Y/n]" } else { "[y/N]" };
+        say_inline!(self, "  {} {} {} ", "?".paint(self.theme.accent).bold(), self.interpolate(prompt), hint.dimmed());
+
+        let answer = loop {
+            match self.read_answer() {
+                None => {
+                    if self.non_interactive || self.quick_mode {
+                        self.type_answer(if default { "y" } else { "n" });
+                    }
+                    break default;
+                }
+                Some(input) => match InstnothParser::parse_answer(&input) {
+                    Some(answer) => break answer,
+                    None => {
+                        say_inline!(self, "    {} {} ", "⚠".paint(self.theme.warning), self.tr(Msg::AnswerYesNo));
+                    }
+                },
+            }
+        };
+
+        if !answer && required {
+            say!(self, "  {} {}", "✗".paint(self.theme.error).bold(), self.tr(Msg::Cancelled).paint(self.theme.error).bold());
+            return Err(self.trf(Msg::RequiredRefused, &[&prompt]));
+        }
+        Ok(())
+    }
+
+    fn show_license(&mut self, source: &LicenseSource, ask_follows: bool) -> Result<(), String> {
+        const PAGE: usize = 20;
+        let (title, text) = match source {
+            LicenseSource::Spdx(id) => {
+                let text = LICENSE_EXCERPTS.iter()
+                    .find(|(known, _)| known.eq_ignore_ascii_case(id))
+                    .map(|(_, text)| text.to_string())
+                    .unwrap_or_else(|| format!("SPDX-License-Identifier: {}", id));
+                (id.clone(), text)
+            }
+            LicenseSource::File(path) => {
+                let resolved = match &self.package_dir {
+                    Some(dir) if Path::new(path).is_relative() => dir.join(path),
+                    _ => PathBuf::from(path),
+                };
+                match fs::read_to_string(&resolved) {
+                    Ok(text) => (path.clone(), text),
+                    Err(_) => {
+                        self.warn("  ", &self.trf(Msg::LicenseMissing, &[&resolved.display()]));
+                        return Ok(());
+                    }
+                }
+            }
+        };
+
+        let lines: Vec<&str> = text.lines().collect();
+        say!(self, "  {} {}", "📜".normal(), self.trf(Msg::LicenseAgreement, &[&title.paint(self.theme.accent)]));
+        say!(self, "{}", self.separator().dimmed());
+        for (i, line) in lines.iter().enumerate() {
+            say!(self, "    {}", line.replace('\t', "    "));
+            let shown = i + 1;
+            if shown % PAGE != 0 || shown == lines.len() {
+                continue;
+            }
+            let more = format!("--More-- ({}%)", shown * 100 / lines.len());
+            say_inline!(self, "{}", more.reversed());
+            if self.non_interactive || self.quick_mode {
+                // Автопрокрутка: подсказка висит немного и стирается
+                self.pause(700);
+                say_inline!(self, "\r\x1b[K");
+            } else {
+                let quit = self.read_answer().is_some_and(|a| a.eq_ignore_ascii_case("q"));
+                // Стираем подсказку вместе с переводом строки от Enter
+                say_inline!(self, "\x1b[1A\x1b[K");
+                if quit {
+                    break;
+                }
+            }
+        }
+        say!(self, "{}", self.separator().dimmed());
+        if !ask_follows && !self.non_interactive && !self.quick_mode {
+            say_inline!(self, "{}", "(END)".reversed());
+            self.read_answer();
+            say_inline!(self, "\x1b[1A\x1b[K");
+        }
+        Ok(())
+    }
+
+    fn choose(&mut self, prompt: &str, options: &[String], default: usize, var: &str) -> Result<(), String> {
+        say!(self, "  {} {}", "?".paint(self.theme.accent).bold(), self.interpolate(prompt));
+        for (i, option) in options.iter().enumerate() {
+            let marker = if i == default { self.tr(Msg::DefaultChoice).dimmed().to_string() } else { String::new() };
+            say!(self, "    {} {} {}", format!("{})", i + 1).paint(self.theme.accent), option, marker);
+        }
+        say_inline!(self, "    {} [1-{}]: ", self.tr(Msg::Choice).dimmed(), options.len());
+
+        let selected = loop {
+            match self.read_answer() {
+                None => {
+                    if self.non_interactive || self.quick_mode {
+                        self.type_answer(&(default + 1).to_string());
+                    }
+                    break default;
+                }
+                Some(input) => match input.parse::<usize>() {
+                    Ok(n) if (1..=options.len()).contains(&n) => break n - 1,
+                    _ => {
+                        say_inline!(self, "    {} {}", "⚠".paint(self.theme.warning), self.trf(Msg::EnterNumber, &[&options.len()]));
+                    }
+                },
+            }
+        };
+
+        say!(self, "    {} {}", "✓".paint(self.theme.success), options[selected].white().bold());
+        if !var.is_empty() {
+            self.variables.insert(var.to_string(), options[selected].clone());
+        }
+        Ok(())
+    }
+
+    fn wait_for(&mut self, what: &str, timeout_secs: u64, expect_timeout: bool) -> Result<(), String> {
+        let mut rng = self.rng();
+        let timeout_ms = timeout_secs * 1000;
+        // Условие выполняется через случайную долю таймаута
+        let took_ms = if expect_timeout { timeout_ms } else { timeout_ms * rng.gen_range(10..70) / 100 };
+
+        if !self.quick_mode {
+            let spinner_chars = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+            for (i, elapsed) in (0..took_ms).step_by(100).enumerate() {
+                say_inline!(self, "\r  {} {} {} {}",
+                    "⏳".normal(), self.trf(Msg::Waiting, &[&what.paint(self.theme.accent)]), spinner_chars[i % spinner_chars.len()].to_string().paint(self.theme.accent),
+                    format!("{:.1}s / {}s", elapsed as f64 / 1000.0, timeout_secs).dimmed());
+                self.pause(100);
+            }
+            say_inline!(self, "\r\x1b[K");
+        }
+        if expect_timeout {
+            self.warn("  ", &self.trf(Msg::WaitTimedOut, &[&what, &format!("{:.1}", took_ms as f64 / 1000.0)]));
+        } else {
+            say!(self, "  {} {} {}", "✓".paint(self.theme.success), what.paint(self.theme.accent), self.trf(Msg::WaitReady, &[&format!("{:.1}", took_ms as f64 / 1000.0)]).dimmed());
+        }
+        Ok(())
+    }
+
+    fn provision_cloud(&mut self, provider: CloudProvider, resources: u64) -> Result<(), String> {
+        let mut rng = self.rng();
+        let pool = provider.resource_pool();
+        let count = resources as usize;
+        // Если ресурсов больше, чем в пуле, лишние становятся копиями виртуальной машины: aws_instance.web[0], [1], ...
+        let mut addresses: Vec<(String, &str, Option<usize>)> = Vec::new();
+        for &(address, prefix) in pool.iter().take(count) {
+            if address == provider.instance() && count > pool.len() {
+                for i in 0..=count - pool.len() {
+                    addresses.push((format!("{}[{}]", address, i), prefix, Some(i)));
+                }
+            } else {
+                addresses.push((address.to_string(), prefix, None));
+            }
+        }
+        let subscription = RandomSystemInfo::uuid(&mut rng);
+
+        say!(self, "  {} {}", "☁".normal(), self.trf(Msg::DeployingInfra, &[&provider.name().paint(self.theme.accent), &resources]));
+        if self.verbose() { say!(self, "    {} terraform init && terraform apply -auto-approve", "$".dimmed()); }
+        if !self.quick_mode {
+            say!(self, "    - Installing hashicorp/{} v{}.{}.0...", provider.name(), rng.gen_range(3..6), rng.gen_range(0..40));
+            say!(self, "    {}", "Terraform has been successfully initialized!".paint(self.theme.success));
+            say!(self, "    Terraform will perform the following actions:");
+            for (address, _, _) in &addresses {
+                say!(self, "      {} {} will be created", "#".dimmed(), address.bold());
+            }
+        }
+        say!(self, "    {} {} to add, 0 to change, 0 to destroy.", "Plan:".bold(), resources);
+
+        if !self.quick_mode {
+            for (address, prefix, index) in &addresses {
+                say!(self, "    {}: Creating...", address.bold());
+                let took = rng.gen_range(2..60);
+                self.pause(200);
+                for elapsed in (10..took).step_by(10) {
+                    say!(self, "    {}: Still creating... [{}s elapsed]", address.bold(), elapsed);
+                    self.pause(150);
+                }
+                // У AWS идентификаторы из префикса и 17 hex-цифр, у GCP и Azure — путь к ресурсу (копии получают -N)
+                let suffix = index.map(|i| format!("-{}", i)).unwrap_or_default();
+                let id = match provider {
+                    CloudProvider::Aws => {
+                        let hex: String = (0..17).map(|_| std::char::from_digit(rng.gen_range(0..16), 16).unwrap()).collect();
+                        if prefix.chars().all(|c| c.is_ascii_uppercase()) { format!("{}{}", prefix, hex.to_uppercase()) } else { format!("{}{}", prefix, hex) }
+                    }
+                    CloudProvider::Gcp => format!("{}{}", prefix, suffix),
+                    CloudProvider::Azure => format!("/subscriptions/{}{}{}", subscription, prefix, suffix),
+                };
+                say!(self, "    {}: Creation complete after {}s [id={}]", address.bold(), took, id.dimmed());
+            }
+        }
+        say!(self, "    {}", format!("Apply complete! Resources: {} added, 0 changed, 0 destroyed.", resources).paint(self.theme.success).bold());
+        Ok(())
+    }
+
+    fn run_playbook(&mut self, playbook: &str, hosts: u64) -> Result<(), String> {
+        let mut rng = self.rng();
+        let tasks = [
+            "common : Update package cache", "common : Install base packages", "common : Set timezone",
+            "users : Create deploy user", "users : Add authorized keys", "ssh : Harden sshd_config",
+            "firewall : Allow HTTP and HTTPS", "nginx : Install nginx", "nginx : Deploy site config",
+            "app : Checkout application", "app : Install dependencies", "app : Run migrations",
+            "app : Enable systemd unit",
+        ];
+        // Подмножество задач в исходном порядке; сбор фактов всегда первый
+        let mut selected: Vec<&str> = vec!["Gathering Facts"];
+        selected.extend(tasks.iter().filter(|_| rng.gen_bool(0.7)));
+        let host_names: Vec<String> = (1..=hosts).map(|i| format!("web-{:02}", i)).collect();
+        let width = 78;
+        let banner = |title: String| format!("{} {}", title, "*".repeat(width - title.chars().count().min(width - 3) - 1));
+        // Счётчики по хостам: ok, changed, skipped
+        let mut recap = vec![(0u32, 0u32, 0u32); host_names.len()];
+
+        say!(self, "  {} {}", "📜".normal(), self.trf(Msg::RunningPlaybook, &[&playbook.paint(self.theme.accent), &hosts]));
+        if self.verbose() { say!(self, "    {} ansible-playbook -i inventory {}", "$".dimmed(), playbook); }
+        if !self.quick_mode {
+            say!(self);
+            say!(self, "    {}", banner("PLAY [all]".to_string()));
+        }
+        for task in &selected {
+            let gather = *task == "Gathering Facts";
+            let skippable = !gather && rng.gen_bool(0.15);
+            if !self.quick_mode {
+                say!(self);
+                say!(self, "    {}", banner(format!("TASK [{}]", task)));
+            }
+            for (host, counts) in host_names.iter().zip(recap.iter_mut()) {
+                if skippable && rng.gen_bool(0.5) {
+                    counts.2 += 1;
+                    if !self.quick_mode { say!(self, "    {}", format!("skipping: [{}]", host).paint(self.theme.accent)); }
+                } else if !gather && rng.gen_bool(0.4) {
+                    counts.0 += 1;
+                    counts.1 += 1;
+                    if !self.quick_mode { say!(self, "    {}", format!("changed: [{}]", host).paint(self.theme.warning)); }
+                } else {
+                    counts.0 += 1;
+                    if !self.quick_mode { say!(self, "    {}", format!("ok: [{}]", host).paint(self.theme.success)); }
+                }
+                self.pause(rng.gen_range(40..160));
+            }
+        }
+
+        say!(self);
+        say!(self, "    {}", banner("PLAY RECAP".to_string()));
+        for (host, (ok, changed, skipped)) in host_names.iter().zip(&recap) {
+            let name = format!("{:<26}", host);
+            let name = if *changed > 0 { name.paint(self.theme.warning) } else { name.paint(self.theme.success) };
+            say!(self, "    {} : {} {} unreachable=0    failed=0    {} rescued=0    ignored=0",
+                name,
+                format!("{:<6}", format!("ok={}", ok)).paint(self.theme.success),
+                format!("{:<11}", format!("changed={}", changed)).paint(self.theme.warning),
+                format!("{:<11}", format!("skipped={}", skipped)).paint(self.theme.accent));
+        }
+        Ok(())
+    }
+
+    fn generate_locales(&mut self, locales: &[String]) -> Result<(), String> {
+        say!(self, "  {} {}", "🌐".normal(), self.tr(Msg::GeneratingLocales));
+        if self.verbose() { say!(self, "    {} locale-gen", "$".dimmed()); }
+        say!(self, "    Generating locales (this might take a while)...");
+        for locale in locales {
+            say_inline!(self, "      {}...", locale);
+            self.pause(300);
+            say!(self, " {}", "done".paint(self.theme.success));
+            if !self.state.locales.contains(locale) {
+                self.state.locales.push(locale.clone());
+            }
+        }
+        say!(self, "    Generation complete.");
+        Ok(())
+    }
+
+    fn set_keymap(&mut self, keymap: &str) -> Result<(), String> {
+        // de-latin1 → раскладка de, вариант latin1
+        let (layout, variant) = keymap.split_once('-').unwrap_or((keymap, ""));
+        say!(self, "  {} {}", "⌨".normal(), self.trf(Msg::Keymap, &[&keymap.paint(self.theme.accent)]));
+        if self.verbose() { say!(self, "    {} localectl set-keymap {}", "$".dimmed(), keymap); }
+        say!(self, "    Writing /etc/default/keyboard");
+        say!(self, "      XKBMODEL=\"pc105\"");
+        say!(self, "      XKBLAYOUT=\"{}\"", layout);
+        say!(self, "      XKBVARIANT=\"{}\"", variant);
+        self.pause(200);
+        say!(self, "    Setting up console-setup ... {}", "done".paint(self.theme.success));
+        say!(self, "    {} {}", "✓".paint(self.theme.success), self.trf(Msg::KeymapApplied, &[&keymap.paint(self.theme.accent)]));
+        Ok(())
+    }
+
+    fn create_snapshot(&mut self, name: &str) -> Result<(), String> {
+        let mut rng = self.rng();
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        // Timeshift называет снимки по времени создания: 2026-10-14_09-30-00
+        let id = iso_date(now).replace('T', "_").replace(':', "-");
+        let subvol = 256 + self.state.snapshots.len() as u32 * 2 + rng.gen_range(1..8);
+        let path = format!("/run/timeshift/backup/timeshift-btrfs/snapshots/{}", id);
+
+        say!(self, "  {} {}", "📸".normal(), self.trf(Msg::CreatingSnapshot, &[&name.paint(self.theme.accent)]));
+        if self.verbose() { say!(self, "    {} timeshift --create --comments \"{}\" --tags O", "$".dimmed(), name); }
+        say!(self, "    Creating new backup...(BTRFS)");
+        say!(self, "    Saving to device: /dev/sda2, mounted at path: /run/timeshift/backup");
+        self.pause(400);
+        say!(self, "    Created directory: {}", path.dimmed());
+        say!(self, "    Created subvolume snapshot: {}/@ (ID {})", path.dimmed(), subvol);
+        say!(self, "    Tagged snapshot '{}': ondemand", id);
+        say!(self, "  {} {}", "✓".paint(self.theme.success), self.trf(Msg::SnapshotSaved, &[&name.paint(self.theme.accent), &id.bold()]));
+        self.state.snapshots.insert(name.to_string(), (id, self.state.phases.len()));
+        Ok(())
+    }
+
+    fn rollback_to(&mut self, name: &str) -> Result<(), String> {
+        let (id, started) = self.state.snapshots.get(name).cloned()
+            .ok_or_else(|| Msg::SnapshotMissing.fill(self.lang, &[&name]))?;
+        // Откатываются фазы от той, где сделан снимок, до предыдущей перед текущей;
+        // если снимок сделан в текущей фазе — только она
+        let current = self.state.phases.len().saturating_sub(1);
+        let first = started.saturating_sub(1);
+        let last = if current > first { current - 1 } else { current };
+
+        say!(self, "  {} {}", "⏪".normal(), self.trf(Msg::RollingBack, &[&name.paint(self.theme.accent), &id.dimmed()]));
+        if self.verbose() { say!(self, "    {} timeshift --restore --snapshot '{}' --yes", "$".dimmed(), id); }
+        for index in (first..=last).rev() {
+            let Some((phase, start)) = self.state.phases.get(index) else { continue };
+            let end = self.state.phases.get(index + 1).map_or_else(Instant::now, |(_, next)| *next);
+            let took = end.duration_since(*start);
+            say_inline!(self, "    {} {}", "↺".paint(self.theme.warning), self.trf(Msg::RollingBackPhase, &[&phase.paint(self.theme.accent)]));
+            // Откат короче исходной фазы
+            self.pause((took.as_millis() as u64 / 4).min(600));
+            say!(self, "{} {}", "OK".paint(self.theme.success), self.trf(Msg::PhaseTook, &[&format!("{:.1}", took.as_secs_f64())]).dimmed());
+        }
+        say!(self, "  {} {}", "✓".paint(self.theme.success), self.trf(Msg::SnapshotRestored, &[&name.paint(self.theme.accent)]));
+        Ok(())
+    }
+
+    fn check_space(&mut self, path: &str, need: u64, force_fail: bool) -> Result<(), String> {
+        let mut rng = self.rng();
+        // Объём файловой системы: смонтированный в path раздел, иначе накопитель из железа запуска
+        let mounted = self.state.partitions.values().flatten()
+            .find(|p| p.mounted && p.mount_point == path)
+            .map(|p| (p.device.clone(), p.size_bytes));
+        let (device, total) = match mounted {
+            Some(found) => found,
+            None => {
+                let gb = self.hardware().disk.2;
+                ("/dev/sda2".to_string(), gb * 1_000_000_000)
+            }
+        };
+        // Свободное место — случайная доля диска; если требование помещается на диск, проверка проходит
+        let mut avail = total * rng.gen_range(15..85) / 100;
+        if force_fail {
+            avail = need * rng.gen_range(20..90) / 100;
+        } else if need <= total {
+            avail = avail.max(need + (total - need) * rng.gen_range(5..50) / 100);
+        }
+
+        say!(self, "  {} {}", "💽".normal(), self.trf(Msg::CheckingSpace, &[&path.paint(self.theme.accent)]));
+        if self.verbose() {
+            // df -h: степени 1024 с одной буквой, как у coreutils
+            let df = |bytes: u64| {
+                let units = ["", "K", "M", "G", "T"];
+                let mut value = bytes as f64;
+                let mut unit = 0;
+                while value >= 1024.0 && unit < units.len() - 1 {
+                    value /= 1024.0;
+                    unit += 1;
+                }
+                if value < 10.0 { format!("{:.1}{}", value, units[unit]) } else { format!("{:.0}{}", value, units[unit]) }
+            };
+            say!(self, "    {} df -h {}", "$".dimmed(), path);
+            say!(self, "    {}", "Filesystem      Size  Used Avail Use% Mounted on".dimmed());
+            let used = total.saturating_sub(avail);
+            say!(self, "    {}", format!("{:<15} {:>4} {:>5} {:>5} {:>3}% {}",
+                device, df(total), df(used), df(avail), used * 100 / total.max(1), path).dimmed());
+        }
+        say!(self, "    {}", self.trf(Msg::SpaceRequired, &[&HumanBytes(need).to_string().white().bold()]));
+        let avail_text = HumanBytes(avail).to_string();
+        if avail >= need {
+            say!(self, "    {}", self.trf(Msg::SpaceAvailable, &[&avail_text.paint(self.theme.success)]));
+            say!(self, "  {} {}", "✓".paint(self.theme.success), self.tr(Msg::SpaceEnough));
+            Ok(())
+        } else {
+            say!(self, "    {}", self.trf(Msg::SpaceAvailable, &[&avail_text.paint(self.theme.error)]));
+            self.fail(&self.trf(Msg::SpaceShort, &[&path, &HumanBytes(need - avail)]), 1)
+        }
+    }
+
+    fn update_firmware(&mut self, component: &str, version: &str) -> Result<(), String> {
+        let mut rng = self.rng();
+        let system = ["bios", "uefi", "system firmware"].contains(&component.to_lowercase().as_str());
+        let (vendor, current) = if system {
+            let (vendor, kind, version) = self.hardware().bios;
+            (format!("{} {}", vendor, kind), version.to_string())
+        } else {
+            (component.to_string(), format!("{}.{}.{}", rng.gen_range(1..5), rng.gen_range(0..20), rng.gen_range(0..100)))
+        };
+        let device = if system { "System Firmware" } else { component };
+
+        say!(self, "  {} {}", "💾".normal(), self.trf(Msg::UpdatingFirmware, &[&component.paint(self.theme.accent)]));
+        if self.verbose() { say!(self, "    {} fwupdmgr update", "$".dimmed()); }
+        say!(self, "    {}", self.trf(Msg::FirmwareDevice, &[&vendor]));
+        say!(self, "    {}", self.trf(Msg::FirmwareCurrent, &[&current.paint(self.theme.warning)]));
+        say!(self, "    {}", self.trf(Msg::FirmwareNew, &[&version.paint(self.theme.success)]));
+        say!(self, "    Upgrade available for {} from {} to {}", device, current, version);
+
+        let warning: Vec<&str> = std::iter::once("DO NOT POWER OFF").chain(self.tr(Msg::FirmwareWarning).split('|')).collect();
+        let width = warning.iter().map(|l| l.chars().count()).max().unwrap_or(0) + 4;
+        say!(self, "    {}", format!("╔{}╗", "═".repeat(width)).paint(self.theme.error).bold());
+        for line in warning {
+            let pad = width - 2 - line.chars().count();
+            say!(self, "    {}", format!("║  {}{}║", line, " ".repeat(pad)).paint(self.theme.error).bold());
+        }
+        say!(self, "    {}", format!("╚{}╝", "═".repeat(width)).paint(self.theme.error).bold());
+
+        if !self.quick_mode {
+            for stage in ["Decompressing…", "Authenticating…", "Restarting device…"] {
+                say!(self, "    {}", stage);
+                self.pause(rng.gen_range(300..700));
+            }
+            let pb = self.out.progress_start(100);
+            pb.set_style(ProgressStyle::default_bar()
+                .template("    Writing…            [{bar:40.red/yellow}] {pos:>3}%")
+                .unwrap()
+                .progress_chars(&self.theme.bar));
+            for pos in 1..=99 {
+                pb.set_position(pos);
+                self.pause(rng.gen_range(30..90));
+            }
+            // Классическое «зависание» на 99%
+            self.pause(1000);
+            pb.set_position(100);
+            pb.finish_and_clear();
+        }
+        say!(self, "    Writing…            {}", "100%".paint(self.theme.success));
+        say!(self, "    Verifying…          {}", "OK".paint(self.theme.success));
+        say!(self, "    {} Successfully installed firmware", "✓".paint(self.theme.success));
+        self.warn("    ", &self.trf(Msg::FirmwareNeedsReboot, &[&component]));
+        self.require(PendingAction::Reboot);
+        Ok(())
+    }
+
+    fn reboot(&mut self, delay_secs: u64) -> Result<(), String> {
+        say!(self);
+        if self.quick_mode {
+            say!(self, "  {} {}", "⟳".paint(self.theme.warning).bold(), self.trf(Msg::RebootIn, &[&delay_secs]));
+        } else {
+            for left in (1..=delay_secs).rev() {
+                say_inline!(self, "\r  {} {}  ", "⟳".paint(self.theme.warning).bold(), self.trf(Msg::RebootIn, &[&left.to_string().paint(self.theme.warning).bold()]));
+                self.pause(1000);
+            }
+            say!(self);
+        }
+        say!(self, "  {} {}", "⟳".paint(self.theme.warning).bold(), self.tr(Msg::Rebooting));
+        self.pause(800);
+
+        let (vendor, kind, version) = self.hardware().bios;
+        say!(self);
+        say!(self, "    {}", format!("{} {} {}", vendor, kind, version).white().bold());
+        say!(self, "    {}", "Press DEL to enter Setup, F12 for Boot Menu".dimmed());
+        self.pause(1000);
+
+        let boot_lines = [
+            "GRUB loading.",
+            "Loading Linux linux ...",
+            "Loading initial ramdisk ...",
+            "[    0.000000] Linux version booting",
+            "[    1.204113] systemd[1]: Detected architecture x86-64.",
+            "[  OK  ] Reached target Graphical Interface.",
+        ];
+        for line in boot_lines {
+            say!(self, "    {}", line.dimmed());
+            self.pause(250);
+        }
+        Ok(())
+    }
+
+    fn simulate_operation(&mut self, msg: &str, delay_ms: u64) -> Result<(), String> {
+        say_inline!(self, "  {} {} ", "→".dimmed(), msg);
+        if !self.quick_mode {
+            let spinner_chars = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+            let iterations = delay_ms / 80;
+            for i in 0..iterations {
+                say_inline!(self, "\r  {} {}  ", spinner_chars[i as usize % spinner_chars.len()].to_string().paint(self.theme.accent), msg);
+                self.pause(80);
+            }
+        }
+        say!(self, "\r  {} {}  ", "✓".paint(self.theme.success), msg);
+        Ok(())
+    }
+
+    /// Адрес загрузки с подставленным зеркалом вместо mirror://
+    fn resolve_url(&mut self, url: &str) -> String {
+        match (url.strip_prefix("mirror://"), &self.state.mirror) {
+            (Some(path), Some(mirror)) => format!("{}/{}", mirror, path.trim_start_matches('/')),
+            (Some(_), None) => {
+                self.warn("  ", self.tr(Msg::MirrorNotSelected));
+                url.to_string()
+            }
+            (None, _) => url.to_string(),
+        }
+    }
+
+    fn simulate_download(&mut self, url: &str, size: u64) -> Result<(), String> {
+        let url = self.resolve_url(url);
+        say!(self, "  {} {}", "⬇".paint(self.theme.heading), self.trf(Msg::Downloading, &[&url.paint(self.theme.accent)]));
+        if !self.quick_mode {
+            let pb = self.out.progress_start(size);
+            pb.set_style(ProgressStyle::default_bar().template("    [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})").unwrap().progress_chars(&self.theme.smooth));
+            let mut downloaded = 0u64;
+            let mut rng = self.rng();
+            while downloaded < size {
+                if self.interrupted() {
+                    pb.finish_and_clear();
+                    return Err(self.tr(Msg::Cancelled).to_string());
+                }
+                let chunk = rng.gen_range(10..50).min(size - downloaded);
+                downloaded += chunk;
+                pb.set_position(downloaded);
+                self.pause(rng.gen_range(20..60));
+            }
+            pb.finish_and_clear();
+        }
+        say!(self, "    {} {}", "✓".paint(self.theme.success), self.trf(Msg::DownloadedBytes, &[&size]));
+        Ok(())
+    }
+
+    // Число «бегает» вокруг итогового значения и постепенно на нём успокаивается
+    fn settle_value(&mut self, label: &str, value: f64, unit: &str) {
+        let mut rng = self.rng();
+        if !self.quick_mode {
+            let frames = 25;
+            for frame in 0..frames {
+                let spread = 1.0 - frame as f64 / frames as f64;
+                let shown = value * (1.0 + rng.gen_range(-0.6..0.6) * spread);
+                say_inline!(self, "\r    {:<10} {:>8.2} {}", label, shown, unit);
+                self.pause(60);
+            }
+        }
+        say!(self, "\r    {:<10} {} {}", label, format!("{:>8.2}", value).bold(), unit);
+    }
+
+    fn speed_test(&mut self) -> Result<(), String> {
+        let mut rng = self.rng();
+        let servers = [
+            ("Hetzner Online", "Falkenstein"), ("Vodafone", "Frankfurt"), ("Rostelecom", "Moscow"),
+            ("Comcast", "Chicago, IL"), ("Orange", "Paris"), ("Telia", "Stockholm"),
+        ];
+        let (provider, city) = servers[rng.gen_range(0..servers.len())];
+        let ping: f64 = rng.gen_range(3.0..40.0);
+
+        say!(self, "  {} {}", "📶".normal(), self.tr(Msg::SpeedTest));
+        if self.verbose() { say!(self, "    {} speedtest --accept-license --format=human-readable", "$".dimmed()); }
+        say!(self, "    {}", self.trf(Msg::SpeedTestServer, &[&provider, &city, &format!("{:.1}", rng.gen_range(2.0..300.0))]));
+        self.settle_value("Ping:", ping, "ms");
+        self.settle_value("Jitter:", ping * rng.gen_range(0.02..0.2), "ms");
+        let download: f64 = rng.gen_range(50.0..950.0);
+        self.settle_value("Download:", download, "Mbit/s");
+        self.settle_value("Upload:", download * rng.gen_range(0.1..0.9), "Mbit/s");
+        say!(self, "    Packet Loss: {:.1}%", if rng.gen_bool(0.8) { 0.0 } else { rng.gen_range(0.1..2.0) });
+        Ok(())
+    }
+
+    fn select_mirror(&mut self, country: &str, count: u64) -> Result<(), String> {
+        let mut rng = self.rng();
+        let cc = country.to_lowercase();
+        let local: &[&str] = match country {
+            "DE" => &["ftp.fau.de", "mirror.netcologne.de", "ftp.halifax.rwth-aachen.de", "mirror.informatik.tu-freiburg.de", "ftp.gwdg.de"],
+            "US" => &["mirrors.kernel.org", "mirrors.mit.edu", "mirrors.ocf.berkeley.edu", "mirror.math.princeton.edu", "mirror.cs.uchicago.edu"],
+            "RU" => &["mirror.yandex.ru", "mirror.truenetwork.ru", "mirror.corbina.net", "mirror.docker.ru", "ftp.psn.ru"],
+            "FR" => &["mirror.ibcp.fr", "ftp.u-strasbg.fr", "mirrors.ircam.fr", "mirror.cyberbits.eu"],
+            _ => &[],
+        };
+        // Общие зеркала есть в любой стране; ими добирается count
+        let generic = [
+            format!("ftp.{}.debian.org", cc), format!("mirror.{}.leaseweb.net", cc), format!("{}.mirrors.cicku.me", cc),
+            format!("mirror-{}.xtom.com", cc), format!("{}.archive.ubuntu.com", cc), format!("mirror.{}.cdn-perfprod.com", cc),
+        ];
+        let hosts: Vec<String> = local.iter().map(|h| h.to_string()).chain(generic).take(count as usize).collect();
+
+        say!(self, "  {} {}", "🌐".normal(), self.trf(Msg::SelectingMirror, &[&country.paint(self.theme.accent), &self.trn(Msg::Mirrors, hosts.len() as u32)]));
+        if (count as usize) > hosts.len() {
+            self.warn("    ", &self.trf(Msg::MirrorsShort, &[&country, &self.trn(Msg::Mirrors, hosts.len() as u32), &count]));
+        }
+        let mut mirrors: Vec<(String, u64)> = Vec::new();
+        for host in &hosts {
+            let url = format!("https://{}", host);
+            let latency = rng.gen_range(4..180);
+            if !self.quick_mode {
+                say_inline!(self, "    {} {} ...", "→".dimmed(), url);
+                self.pause(latency * 2 + 50);
+                say!(self, " {} ms", latency);
+            }
+            mirrors.push((url, latency));
+        }
+        mirrors.sort_by_key(|(_, latency)| *latency);
+        if !self.quick_mode { say!(self); }
+        for (i, (url, latency)) in mirrors.iter().enumerate() {
+            let line = format!("{:>2}. {:<48} {:>4} ms", i + 1, url, latency);
+            if i == 0 {
+                say!(self, "    {}", line.paint(self.theme.success).bold());
+            } else {
+                say!(self, "    {}", line.dimmed());
+            }
+        }
+        let (chosen, latency) = mirrors.swap_remove(0);
+        say!(self, "    {} {}", "✓".paint(self.theme.success), self.trf(Msg::MirrorSelected, &[&chosen.paint(self.theme.accent), &latency]));
+        self.state.mirror = Some(chosen);
+        Ok(())
+    }
+
+    fn docker_pull(&mut self, image: &str, layers: u64) -> Result<(), String> {
+        let mut rng = self.rng();
+        let (name, tag) = image.split_once(':').unwrap_or((image, "latest"));
+        let repository = if name.contains('/') { name.to_string() } else { format!("library/{}", name) };
+        let reference = format!("{}:{}", name, tag);
+
+        // Слой: (id, размер, уже есть локально)
+        let layer_list: Vec<(String, u64, bool)> = (0..layers)
+            .map(|_| {
+                let id: String = (0..12).map(|_| char::from_digit(rng.gen_range(0..16), 16).unwrap()).collect();
+                (id, rng.gen_range(200_000..60_000_000), rng.gen_bool(0.3))
+            })
+            .collect();
+        let total: u64 = layer_list.iter().filter(|(_, _, exists)| !exists).map(|(_, size, _)| size).sum();
+
+        say!(self, "  {} docker pull {}", "🐳".normal(), reference.paint(self.theme.accent));
+        if !self.quick_mode {
+            say!(self, "    {}: Pulling from {}", tag, repository);
+            for (id, _, _) in layer_list.iter().filter(|(_, _, exists)| *exists) {
+                say!(self, "    {}: {}", id.dimmed(), "Already exists".dimmed());
+            }
+
+            let multi = self.multi();
+            let style = ProgressStyle::default_bar()
+                .template("    {prefix}: Downloading [{bar:30.cyan/blue}] {bytes}/{total_bytes}")
+                .unwrap()
+                .progress_chars(&self.theme.smooth);
+            let mut bars: Vec<(ProgressBar, u64)> = layer_list.iter()
+                .filter(|(_, _, exists)| !exists)
+                .map(|(id, size, _)| {
+                    let pb = multi.add(ProgressBar::new(*size));
+                    pb.set_style(style.clone());
+                    pb.set_prefix(id.clone());
+                    (pb, *size)
+                })
+                .collect();
+
+            // Слои качаются одновременно, каждый со своей скоростью
+            while bars.iter().any(|(pb, size)| pb.position() < *size) {
+                for (pb, size) in &mut bars {
+                    if pb.position() < *size {
+                        pb.inc(rng.gen_range(*size / 40..*size / 8 + 1));
+                    }
+                }
+                self.pause(50);
+            }
+            for (pb, _) in &bars {
+                pb.finish_and_clear();
+            }
+            for (id, _, _) in layer_list.iter().filter(|(_, _, exists)| !exists) {
+                say!(self, "    {}: {}", id.dimmed(), "Pull complete".paint(self.theme.success));
+            }
+        }
+
+        let digest = RandomSystemInfo::checksum(&reference, ChecksumAlgo::Sha256);
+        say!(self, "    Digest: sha256:{}", digest.dimmed());
+        say!(self, "    Status: Downloaded newer image for {}", reference);
+        let pulled = layer_list.iter().filter(|(_, _, exists)| !exists).count();
+        say!(self, "    {} {}", "✓".paint(self.theme.success),
+            self.trf(Msg::PulledLayers, &[&HumanBytes(total), &pulled, &self.trn(Msg::Layers, layers as u32)]));
+        Ok(())
+    }
+
+    /// Каталог, в который git clone кладёт репозиторий: последний сегмент URL без .git
+    fn repo_dir(url: &str) -> &str {
+        let last = url.trim_end_matches('/').rsplit(['/', ':']).next().unwrap_or(url);
+        last.strip_suffix(".git").unwrap_or(last)
+    }
+
+    // Строка git с живым процентом: "Counting objects:  42% (420/1000)"
+    fn git_counter(&self, label: &str, total: u64) {
+        if !self.quick_mode {
+            for pct in (0..100).step_by(7) {
+                say_inline!(self, "\r    {}: {:>3}% ({}/{})", label, pct, total * pct / 100, total);
+                self.pause(40);
+            }
+        }
+        say!(self, "\r    {}: 100% ({}/{}), done.", label, total, total);
+    }
+
+    fn git_clone(&mut self, url: &str, objects: u64) -> Result<(), String> {
+        let mut rng = self.rng();
+        let dir = Self::repo_dir(url);
+        let deltas = objects * rng.gen_range(40..70) / 100;
+        let bytes = objects * rng.gen_range(800..3000);
+
+        say!(self, "  {} Cloning into '{}'...", "📥".normal(), dir.paint(self.theme.accent));
+        if self.verbose() { say!(self, "    {} git clone {}", "$".dimmed(), url); }
+        say!(self, "    remote: Enumerating objects: {}, done.", objects);
+        self.git_counter("remote: Counting objects", objects);
+        self.git_counter("remote: Compressing objects", objects * rng.gen_range(30..60) / 100);
+
+        if !self.quick_mode {
+            let pb = self.out.progress_start(objects);
+            pb.set_style(ProgressStyle::default_bar()
+                .template("    Receiving objects: {percent:>3}% ({pos}/{len}) [{bar:30.cyan/blue}] {msg}")
+                .unwrap()
+                .progress_chars(&self.theme.smooth));
+            let steps = 50;
+            for i in 1..=steps {
+                pb.set_position(objects * i / steps);
+                pb.set_message(format!("{}", HumanBytes(bytes * i / steps)));
+                self.pause(rng.gen_range(20..60));
+            }
+            pb.finish_and_clear();
+        }
+        say!(self, "    Receiving objects: 100% ({}/{}), {} | {}/s, done.", objects, objects, HumanBytes(bytes), HumanBytes(rng.gen_range(5_000_000..40_000_000)));
+        self.git_counter("Resolving deltas", deltas);
+        self.git_counter("Updating files", objects / 10 + 1);
+        Ok(())
+    }
+
+    fn create_vm(&mut self, name: &str, cpus: u64, memory: u64, disk: u64) -> Result<(), String> {
+        let mut rng = self.rng();
+        let image = "jammy-server-cloudimg-amd64.img";
+
+        say!(self, "  {} {}", "🖥".normal(),
+            self.trf(Msg::CreatingVm, &[&name.paint(self.theme.accent), &cpus, &HumanBytes(memory), &HumanBytes(disk)]));
+        if let Some(ip) = self.state.vms.get(name).cloned() {
+            self.warn("    ", &format!("domain '{}' already exists", name));
+            say!(self, "    {} VM ready, ssh root@{}", "✓".paint(self.theme.success), ip.bold());
+            return Ok(());
+        }
+        if self.verbose() {
+            say!(self, "    {} virt-install --name {} --vcpus {} --memory {} --disk size={} --import --os-variant ubuntu22.04 --cloud-init",
+                "$".dimmed(), name, cpus, memory / 1024 / 1024, disk / 1024 / 1024 / 1024);
+        }
+
+        let image_size = rng.gen_range(600_000_000..700_000_000);
+        self.transfer_bar(image, image_size);
+        say!(self, "    Retrieving '{}' {}", image, HumanBytes(image_size).to_string().dimmed());
+        say!(self, "    Formatting '/var/lib/libvirt/images/{}.qcow2', fmt=qcow2 cluster_size=65536 size={} backing_file=/var/lib/libvirt/images/{}",
+            name, disk, image);
+        say!(self, "    Starting install...");
+        self.pause(400);
+        say!(self, "    Creating domain...");
+        say!(self, "    Domain '{}' started", name);
+
+        let ip = RandomSystemInfo::ip_address(&mut rng);
+        let mac = format!("52:54:00:{:02x}:{:02x}:{:02x}", rng.gen::<u8>(), rng.gen::<u8>(), rng.gen::<u8>());
+        let mut uptime: f64 = rng.gen_range(1.5..3.0);
+        for (stage, pid) in [("init-local", 412), ("init", 598), ("modules:config", 731), ("modules:final", 802)] {
+            self.pause(rng.gen_range(200..500));
+            say!(self, "    {} cloud-init[{}]: Cloud-init v. 23.4 running '{}'", format!("[{:>8.3}]", uptime).dimmed(), pid, stage);
+            if stage == "init" {
+                say!(self, "    {}", format!("ci-info: | eth0 | True | {} | 255.255.255.0 | global | {} |", ip, mac).dimmed());
+            }
+            uptime += rng.gen_range(1.0..4.0);
+        }
+        say!(self, "    Cloud-init v. 23.4 finished. Datasource DataSourceNoCloud. Up {:.2} seconds", uptime);
+        say!(self, "    {} VM ready, ssh root@{}", "✓".paint(self.theme.success), ip.bold());
+
+        self.variables.insert(format!("vm.{}.ip", name), ip.clone());
+        self.state.vms.insert(name.to_string(), ip);
+        Ok(())
+    }
+
+    fn start_container(&mut self, image: &str) -> Result<(), String> {
+        let mut rng = self.rng();
+        let reference = if image.contains(':') { image.to_string() } else { format!("{}:latest", image) };
+        let name = Self::repo_dir(reference.rsplit_once(':').map(|(n, _)| n).unwrap_or(&reference)).to_string();
+
+        say!(self, "  {} {}", "🐳".normal(), self.trf(Msg::StartingContainer, &[&reference.paint(self.theme.accent)]));
+        if let Some(ip) = self.state.containers.get(&name).cloned() {
+            self.warn("    ", &format!("container {} is already running", name));
+            say!(self, "    {} {}", "✓".paint(self.theme.success), self.trf(Msg::ContainerStarted, &[&name, &ip.bold()]));
+            return Ok(());
+        }
+        if self.verbose() { say!(self, "    {} ctr image pull {}", "$".dimmed(), reference); }
+
+        let done = format!("|{}|", "+".repeat(30));
+        say!(self, "    {:<40} resolved       {}", format!("{}:", reference), done);
+        let mut total = 0;
+        for (i, kind) in ["index", "manifest", "config", "layer", "layer", "layer"].iter().enumerate() {
+            let digest = RandomSystemInfo::checksum(&format!("{}#{}", reference, i), ChecksumAlgo::Sha256);
+            let size: u64 = if *kind == "layer" { rng.gen_range(2_000_000..60_000_000) } else { rng.gen_range(500..8_000) };
+            total += size;
+            self.pause(size / 500_000 + 60);
+            say!(self, "    {:<40} done           {}", format!("{}-sha256:{}:", kind, &digest[..12]).dimmed(), done);
+        }
+        let elapsed: f64 = rng.gen_range(1.5..6.0);
+        say!(self, "    elapsed: {:.1} s{:>48}", elapsed, format!("total: {} ({}/s)", HumanBytes(total), HumanBytes((total as f64 / elapsed) as u64)));
+        let unpack = RandomSystemInfo::checksum(&reference, ChecksumAlgo::Sha256);
+        say!(self, "    unpacking linux/amd64 sha256:{}...done: {}ms", &unpack[..16], rng.gen_range(200..1500));
+        if self.verbose() { say!(self, "    {} ctr run -d {} {}", "$".dimmed(), reference, name); }
+
+        let ip = RandomSystemInfo::ip_address(&mut rng);
+        let id = RandomSystemInfo::checksum(&format!("{}@{}", reference, ip), ChecksumAlgo::Sha256);
+        say!(self, "    {}", id.dimmed());
+        say!(self, "    {} {}", "✓".paint(self.theme.success), self.trf(Msg::ContainerStarted, &[&name, &ip.bold()]));
+
+        self.variables.insert(format!("container.{}.ip", name), ip.clone());
+        self.state.containers.insert(name, ip);
+        Ok(())
+    }
+
+    fn generate_ssh_key(&mut self, key_type: KeyType, bits: u64, comment: &str) -> Result<(), String> {
+        let mut rng = self.rng();
+        let path = format!("/root/.ssh/id_{}", key_type.name());
+        let digest: Vec<u8> = (0..32).map(|_| rng.gen()).collect();
+
+        say!(self, "  {} {}", "🔑".normal(), self.trf(Msg::GeneratingSshKey, &[&key_type.name().paint(self.theme.accent), &bits]));
+        if self.verbose() {
+            let size = if key_type == KeyType::Ed25519 { String::new() } else { format!(" -b {}", bits) };
+            say!(self, "    {} ssh-keygen -t {}{} -C \"{}\" -f {} -N \"\"", "$".dimmed(), key_type.name(), size, comment, path);
+        }
+        say!(self, "    Generating public/private {} key pair.", key_type.name());
+        if !self.quick_mode {
+            // RSA генерируется заметно дольше
+            let delay = if key_type == KeyType::Rsa { 300 + bits / 4 } else { 200 };
+            self.pause(delay);
+        }
+        if self.verbose() {
+            say!(self, "    Your identification has been saved in {}", path);
+            say!(self, "    Your public key has been saved in {}.pub", path);
+        }
+        say!(self, "    The key fingerprint is:");
+        say!(self, "    {} {}", format!("SHA256:{}", base64_unpadded(&digest)).paint(self.theme.success), comment);
+        say!(self, "    The key's randomart image is:");
+        let title = format!("{} {}", key_type.name().to_uppercase(), bits);
+        for line in randomart(&title, &digest) {
+            say!(self, "    {}", line);
+        }
+        Ok(())
+    }
+
+    fn generate_cert(&mut self, domain: &str, days: u64, key_type: KeyType) -> Result<(), String> {
+        let mut rng = self.rng();
+        // Файлы для *.example.com называются по example.com
+        let file = domain.trim_start_matches("*.");
+        let key_path = format!("/etc/ssl/private/{}.key", file);
+        let cert_path = format!("/etc/ssl/certs/{}.crt", file);
+
+        say!(self, "  {} {}", "🔐".normal(), self.trf(Msg::GeneratingCert, &[&domain.paint(self.theme.accent), &key_type.name(), &days]));
+        if self.verbose() {
+            let genkey = match key_type {
+                KeyType::Rsa => "openssl genrsa 2048".to_string(),
+                KeyType::Ecdsa => "openssl ecparam -genkey -name prime256v1".to_string(),
+                KeyType::Ed25519 => "openssl genpkey -algorithm ed25519".to_string(),
+            };
+            say!(self, "    {} {} -out {}", "$".dimmed(), genkey, key_path);
+        }
+        if !self.quick_mode {
+            let spinner_chars = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+            let steps = if key_type == KeyType::Rsa { 30 } else { 12 };
+            for i in 0..steps {
+                say_inline!(self, "\r    {} {}", spinner_chars[i % spinner_chars.len()].to_string().paint(self.theme.accent), self.trf(Msg::CollectingEntropy, &[&((i + 1) * 256 / steps)]));
+                self.pause(60);
+            }
+            say_inline!(self, "\r\x1b[K");
+        }
+        match key_type {
+            KeyType::Rsa => {
+                say!(self, "    Generating RSA private key, 2048 bit long modulus (2 primes)");
+                say!(self, "    {}+++++", ".".repeat(rng.gen_range(8..40)));
+                say!(self, "    {}+++++", ".".repeat(rng.gen_range(8..40)));
+                say!(self, "    e is 65537 (0x010001)");
+            }
+            KeyType::Ecdsa => say!(self, "    using curve name prime256v1 instead of secp256r1"),
+            KeyType::Ed25519 => {}
+        }
+        say!(self, "    {} {}", "+".dimmed(), key_path);
+
+        if self.verbose() { say!(self, "    {} openssl req -new -key {} -subj \"/CN={}\" -out /tmp/{}.csr", "$".dimmed(), key_path, domain, file); }
+        say!(self, "    Certificate request: CN={}, subjectAltName=DNS:{}", domain, domain);
+        self.pause(400);
+
+        // Сроки до 90 дней выдаёт Let's Encrypt, более долгие — самоподписанный сертификат
+        let acme = days <= 90;
+        if acme {
+            if self.verbose() { say!(self, "    {} certbot certonly --csr /tmp/{}.csr", "$".dimmed(), file); }
+            say!(self, "    Requesting a certificate for {}", domain);
+            self.pause(800);
+            say!(self, "    Successfully received certificate.");
+        }
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let serial: Vec<String> = (0..16).map(|_| format!("{:02x}", rng.gen::<u8>())).collect();
+        let digest = RandomSystemInfo::checksum(&format!("{}/{}", domain, now), ChecksumAlgo::Sha256).to_uppercase();
+        let fingerprint: Vec<&str> = (0..32).map(|i| &digest[i * 2..i * 2 + 2]).collect();
+        let issuer = if acme { "C=US, O=Let's Encrypt, CN=R11".to_string() } else { format!("CN={}", domain) };
+
+        say!(self, "    Certificate:");
+        say!(self, "        Serial Number: {}", serial.join(":"));
+        say!(self, "        Issuer: {}", issuer);
+        say!(self, "        Subject: CN={}", domain);
+        say!(self, "        Not Before: {}", openssl_date(now));
+        say!(self, "        Not After : {}", openssl_date(now + days * 86_400).paint(self.theme.warning));
+        say!(self, "        SHA256 Fingerprint={}", fingerprint.join(":").dimmed());
+        say!(self, "    {} {}", "+".dimmed(), cert_path);
+        if self.verbose() { say!(self, "    {} chmod 600 {}", "$".dimmed(), key_path); }
+        let (year, month, day) = civil_from_days(((now + days * 86_400) / 86_400) as i64);
+        say!(self, "    {} {}", "✓".paint(self.theme.success), self.trf(Msg::CertInstalled, &[&format!("{}-{:02}-{:02}", year, month, day)]));
+        Ok(())
+    }
+
+    fn harden_ssh(&mut self) -> Result<(), String> {
+        let changes = [
+            ("PermitRootLogin", "yes", "no"),
+            ("PasswordAuthentication", "yes", "no"),
+            ("PubkeyAuthentication", "yes", "yes"),
+            ("X11Forwarding", "yes", "no"),
+            ("AllowAgentForwarding", "yes", "no"),
+            ("MaxAuthTries", "6", "3"),
+            ("LoginGraceTime", "120", "30"),
+            ("ClientAliveInterval", "0", "300"),
+            ("KexAlgorithms", "default", "curve25519-sha256,curve25519-sha256@libssh.org"),
+        ];
+
+        say!(self, "  {} {}", "🛡".normal(), self.tr(Msg::HardeningSsh));
+        say!(self, "    {}", "/etc/ssh/sshd_config".dimmed());
+        for (option, before, after) in changes {
+            self.pause(120);
+            if before == after {
+                say!(self, "    {} {} {} {}", "✓".paint(self.theme.success), option, after, self.tr(Msg::Unchanged).dimmed());
+            } else {
+                say!(self, "    {} {} {} {}", "✓".paint(self.theme.success), option, after, self.trf(Msg::WasValue, &[&before]).dimmed());
+            }
+        }
+        if self.verbose() {
+            say!(self, "    {} sshd -t", "$".dimmed());
+            say!(self, "    {} systemctl reload sshd", "$".dimmed());
+        }
+        say!(self, "    {} {}", "✓".paint(self.theme.success), self.tr(Msg::SshdReloaded));
+        Ok(())
+    }
+
+    fn init_database(&mut self, engine: &str, version: &str) -> Result<(), String> {
+        let mut rng = self.rng();
+        let default_version = match engine {
+            "postgresql" => "16",
+            "mysql" => "8.0.36",
+            "sqlite" => "3.45.1",
+            "mongodb" => "7.0.5",
+            _ => "",
+        };
+        let version = if version.is_empty() { default_version } else { version };
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let pid = rng.gen_range(1000..40_000);
+        let password = RandomSystemInfo::password(16, &mut rng);
+
+        say!(self, "  {} {}", "🗄".normal(), self.trf(Msg::InitializingDb, &[&format!("{} {}", engine, version).trim_end().paint(self.theme.accent)]));
+        let superuser = match engine {
+            "postgresql" => {
+                let data_dir = format!("/var/lib/postgresql/{}/main", version);
+                if self.verbose() { say!(self, "    {} sudo -u postgres initdb -D {}", "$".dimmed(), data_dir); }
+                say!(self, "    The files belonging to this database system will be owned by user \"postgres\".");
+                say!(self, "    The database cluster will be initialized with locale \"en_US.UTF-8\".");
+                say!(self, "    The default database encoding has accordingly been set to \"UTF8\".");
+                say!(self);
+                for (step, result) in [
+                    (format!("creating directory {}", data_dir), "ok".to_string()),
+                    ("creating subdirectories".to_string(), "ok".to_string()),
+                    ("selecting dynamic shared memory implementation".to_string(), "posix".to_string()),
+                    ("selecting default max_connections".to_string(), "100".to_string()),
+                    ("selecting default shared_buffers".to_string(), "128MB".to_string()),
+                    ("selecting default time zone".to_string(), "Etc/UTC".to_string()),
+                    ("creating configuration files".to_string(), "ok".to_string()),
+                    ("running bootstrap script".to_string(), "ok".to_string()),
+                    ("performing post-bootstrap initialization".to_string(), "ok".to_string()),
+                    ("syncing data to disk".to_string(), "ok".to_string()),
+                ] {
+                    self.pause(rng.gen_range(80..300));
+                    say!(self, "    {} ... {}", step, result);
+                }
+                say!(self);
+                say!(self, "    Success. You can now start the database server using:");
+                say!(self, "        pg_ctl -D {} -l logfile start", data_dir);
+                Some("postgres")
+            }
+            "mysql" => {
+                if self.verbose() { say!(self, "    {} mysqld --initialize --user=mysql --datadir=/var/lib/mysql", "$".dimmed()); }
+                // offset — миллисекунды от начала, чтобы отметки времени шли по возрастанию
+                let log = |offset: u64, level: &str, code: u32, subsystem: &str, message: String| {
+                    say!(self, "    {}.{:06}Z 0 [{}] [MY-{:06}] [{}] {}",
+                        iso_date(now + offset / 1000), offset % 1000 * 1000 + pid % 1000, level, code, subsystem, message);
+                };
+                log(0, "System", 13169, "Server",
+                    format!("/usr/sbin/mysqld (mysqld {}) initializing of server in progress as process {}", version, pid));
+                self.pause(300);
+                log(12, "System", 13576, "InnoDB", "InnoDB initialization has started.".to_string());
+                self.pause(600);
+                log(640, "System", 13577, "InnoDB", "InnoDB initialization has ended.".to_string());
+                self.pause(400);
+                log(1_093, "Note", 10454, "Server", "A temporary password is generated for root@localhost".to_string());
+                Some("root@localhost")
+            }
+            "sqlite" => {
+                let path = "/var/lib/app/app.db";
+                if self.verbose() { say!(self, "    {} sqlite3 {} \"PRAGMA journal_mode=WAL;\"", "$".dimmed(), path); }
+                say!(self, "    SQLite version {}", version);
+                self.pause(150);
+                say!(self, "    wal");
+                say!(self, "    {} {}", "+".dimmed(), path);
+                // У SQLite нет пользователей — пароль не нужен
+                None
+            }
+            "mongodb" => {
+                if self.verbose() { say!(self, "    {} mongod --dbpath /var/lib/mongodb --bind_ip 127.0.0.1", "$".dimmed()); }
+                let log = |offset: u64, component: &str, id: u32, msg: &str, attr: String| {
+                    say!(self, "    {}", format!(
+                        "{{\"t\":{{\"$date\":\"{}.{:03}+00:00\"}},\"s\":\"I\",\"c\":\"{}\",\"id\":{},\"ctx\":\"main\",\"msg\":\"{}\"{}}}",
+                        iso_date(now + offset / 1000), offset % 1000, component, id, msg, attr
+                    ).dimmed());
+                };
+                log(0, "CONTROL", 4615611, "MongoDB starting",
+                    format!(",\"attr\":{{\"pid\":{},\"port\":27017,\"dbPath\":\"/var/lib/mongodb\"}}", pid));
+                self.pause(300);
+                log(38, "STORAGE", 22315, "Opening WiredTiger", String::new());
+                self.pause(500);
+                let wired_tiger = rng.gen_range(200..900);
+                log(38 + wired_tiger, "STORAGE", 4795906, "WiredTiger opened",
+                    format!(",\"attr\":{{\"durationMillis\":{}}}", wired_tiger));
+                log(90 + wired_tiger, "NETWORK", 23016, "Waiting for connections", ",\"attr\":{\"port\":27017,\"ssl\":\"off\"}".to_string());
+                say!(self, "    Successfully added user: {{ \"user\" : \"admin\", \"roles\" : [ {{ \"role\" : \"root\", \"db\" : \"admin\" }} ] }}");
+                Some("admin")
+            }
+            _ => {
+                let data_dir = format!("/var/lib/{}", engine);
+                if self.verbose() { say!(self, "    {} {}-init --datadir={}", "$".dimmed(), engine, data_dir); }
+                for step in [format!("creating data directory {}", data_dir), "writing configuration".to_string(), "bootstrapping system catalog".to_string()] {
+                    self.pause(rng.gen_range(100..300));
+                    say!(self, "    {} ... ok", step);
+                }
+                Some("admin")
+            }
+        };
+
+        if let Some(user) = superuser {
+            say!(self, "    {} {} {}", "🔑".normal(), self.trf(Msg::DbPassword, &[&user, &password.bold()]), self.tr(Msg::ShownOnce).dimmed());
+        }
+        say!(self, "    {} {}", "✓".paint(self.theme.success), self.trf(Msg::DbReady, &[&engine]));
+        Ok(())
+    }
+
+    fn run_migrations(&mut self, count: u64) -> Result<(), String> {
+        let mut rng = self.rng();
+        let tables = ["users", "orders", "products", "sessions", "invoices", "payments", "comments", "tags", "settings", "audit_log"];
+        let columns = ["email", "created_at", "status", "deleted_at", "owner_id", "price", "locale", "metadata"];
+        let width = count.to_string().len().max(3);
+
+        say!(self, "  {} {}", "🗄".normal(), self.trf(Msg::ApplyingMigrations, &[&count]));
+        if count == 0 {
+            say!(self, "    {} {}", "→".dimmed(), self.tr(Msg::NoMigrations));
+            return Ok(());
+        }
+        let mut total_ms = 0;
+        for n in 1..=count {
+            let table = tables[rng.gen_range(0..tables.len())];
+            let column = columns[rng.gen_range(0..columns.len())];
+            // Первые миграции создают таблицы, дальше — изменения схемы
+            let action = if n <= 3 {
+                format!("create_{}", tables[(n as usize - 1) % tables.len()])
+            } else {
+                match rng.gen_range(0..5) {
+                    0 => format!("create_{}", table),
+                    1 => format!("add_{}_to_{}", column, table),
+                    2 => format!("add_index_on_{}", table),
+                    3 => format!("drop_{}_from_{}", column, table),
+                    _ => format!("backfill_{}_{}", table, column),
+                }
+            };
+            let ms = if action.starts_with("backfill") { rng.gen_range(200..3000) } else { rng.gen_range(3..120) };
+            total_ms += ms;
+            let name = format!("{:0width$}_{}.sql", n, action, width = width);
+            self.pause(ms.min(400));
+            say!(self, "    {} {} {} {}", name, ".".repeat(44usize.saturating_sub(name.len()).max(3)).dimmed(), "OK".paint(self.theme.success), format!("({} ms)", ms).dimmed());
+        }
+        say!(self, "    {} {}", "✓".paint(self.theme.success), self.trf(Msg::MigrationsApplied, &[&count, &format!("{:.2}", total_ms as f64 / 1000.0)]));
+        Ok(())
+    }
+
+    fn git_submodule_update(&mut self) -> Result<(), String> {
+        let mut rng = self.rng();
+        let submodules = [
+            ("third_party/googletest", "https://github.com/google/googletest.git"),
+            ("third_party/abseil-cpp", "https://github.com/abseil/abseil-cpp.git"),
+            ("third_party/protobuf", "https://github.com/protocolbuffers/protobuf.git"),
+            ("third_party/zlib", "https://github.com/madler/zlib.git"),
+            ("deps/libuv", "https://github.com/libuv/libuv.git"),
+            ("vendor/json", "https://github.com/nlohmann/json.git"),
+        ];
+        let count = rng.gen_range(2..=4);
+
+        say!(self, "  {} {}", "📥".normal(), self.tr(Msg::UpdatingSubmodules));
+        if self.verbose() { say!(self, "    {} git submodule update --init --recursive", "$".dimmed()); }
+        for (path, url) in submodules.iter().take(count) {
+            say!(self, "    Submodule '{}' ({}) registered for path '{}'", path, url.dimmed(), path);
+        }
+        for (path, url) in submodules.iter().take(count) {
+            say!(self, "    Cloning into '{}'...", path.paint(self.theme.accent));
+            self.pause(rng.gen_range(200..600));
+            let commit = RandomSystemInfo::checksum(url, ChecksumAlgo::Sha1);
+            say!(self, "    Submodule path '{}': checked out '{}'", path, commit.dimmed());
+        }
+        Ok(())
+    }
+
+    fn systemd_boot(&mut self, units: u64, failures: u64) -> Result<(), String> {
+        let mut rng = self.rng();
+        let pool = RandomSystemInfo::systemd_units();
+        let targets = RandomSystemInfo::systemd_targets();
+
+        // Номера юнитов, которые не запустятся
+        let mut failed = HashSet::new();
+        while (failed.len() as u64) < failures {
+            failed.insert(rng.gen_range(0..units));
+        }
+
+        let ok = format!("[  {}  ]", "OK".paint(self.theme.success));
+        let mut lines = Vec::new();
+        for n in 0..units {
+            // Когда пул заканчивается, дальше идут пользовательские сессии
+            let (service, unit) = match pool.get(n as usize) {
+                Some(&(service, unit)) => (service.to_string(), unit.to_string()),
+                None => {
+                    let uid = 1000 + n - pool.len() as u64;
+                    (format!("user@{}", uid), format!("User Manager for UID {}", uid))
+                }
+            };
+            if failed.contains(&n) {
+                if rng.gen_bool(0.5) {
+                    lines.push(format!("[{}] Failed to start {}.", "FAILED".paint(self.theme.error).bold(), unit));
+                    lines.push(format!("         See 'systemctl status {}.service' for details.", service));
+                } else {
+                    lines.push(format!("[ {} ] Timed out waiting for {}.", "TIME".paint(self.theme.error), unit));
+                }
+            } else {
+                lines.push(format!("         Starting {}...", unit));
+                lines.push(format!("{} Started {}.", ok, unit));
+            }
+            // Цели достигаются равномерно по ходу загрузки
+            let target = ((n + 1) * targets.len() as u64 / units.max(1)) as usize;
+            if target > 0 && (n * targets.len() as u64 / units.max(1)) as usize != target {
+                lines.push(format!("{} Reached target {}.", ok, targets[target - 1]));
+            }
+        }
+
+        say!(self);
+        if self.quick_mode && lines.len() > 6 {
+            for line in &lines[..3] {
+                say!(self, "{}", line);
+            }
+            say!(self, "         {}", self.trf(Msg::MoreLines, &[&self.trn(Msg::Lines, (lines.len() - 6) as u32)]).dimmed());
+            for line in &lines[lines.len() - 3..] {
+                say!(self, "{}", line);
+            }
+        } else {
+            for line in &lines {
+                say!(self, "{}", line);
+                self.pause(rng.gen_range(15..120));
+            }
+        }
+        say!(self);
+        Ok(())
+    }
+
+    fn simulate_extraction(&mut self, from: &str, to: &str) -> Result<(), String> {
+        say!(self, "  {} {}", "📦".normal(), self.trf(Msg::Extracting, &[&from.dimmed(), &to.paint(self.theme.accent)]));
+        if !self.quick_mode {
+            let files = vec!["bin/main", "lib/libcore.so", "share/data.dat", "etc/config.conf", "doc/README.md"];
+            for file in files {
+                say_inline!(self, "    {} {}", "→".dimmed(), file);
+                self.pause(100);
+                say!(self, " {}", "✓".paint(self.theme.success));
+            }
+        } else {
+            say!(self, "    {} {}", "✓".paint(self.theme.success), self.trf(Msg::FilesExtracted, &[&5]));
+        }
+        Ok(())
+    }
+
+    fn simulate_dep_install(&mut self, name: &str, version: &str) -> Result<(), String> {
+        let label = self.trf(Msg::InstallingDep, &[&name.paint(self.theme.accent), &version.paint(self.theme.warning)]);
+        say_inline!(self, "  {} {} ", "📦".normal(), label);
+        if !self.quick_mode {
+            let spinner_chars = ['⣾', '⣽', '⣻', '⢿', '⡿', '⣟', '⣯', '⣷'];
+            for i in 0..15 {
+                say_inline!(self, "\r  {} {} {} ", "📦".normal(), label, spinner_chars[i % spinner_chars.len()].to_string().paint(self.theme.accent));
+                self.pause(100);
+            }
+        }
+        say!(self, "\r  {} {} {}     ", "📦".normal(), label, "✓".paint(self.theme.success));
+        Ok(())
+    }
+
+    fn simulate_script_execution(&mut self) -> Result<(), String> {
+        if !self.quick_mode {
+            let outputs = vec!["  Initializing...", "  Loading modules...", "  Applying configuration...", "  Done."];
+            for output in outputs { say!(self, "    {}", output.dimmed()); self.pause(150); }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Получатель для тестов: копит текст (кроме заглушённого mute), клон смотрит в тот же буфер
+    #[derive(Clone, Default)]
+    struct BufferSink(Arc<Mutex<String>>, Arc<AtomicBool>);
+
+    impl OutputSink for BufferSink {
+        fn line(&self, text: &str) {
+            if !self.1.load(Ordering::SeqCst) {
+                let mut buf = self.0.lock().unwrap();
+                buf.push_str(text);
+                buf.push('\n');
+            }
+        }
+
+        fn inline(&self, text: &str) {
+            if !self.1.load(Ordering::SeqCst) {
+                self.0.lock().unwrap().push_str(text);
+            }
+        }
+
+        fn mute(&self, muted: bool) {
+            self.1.store(muted, Ordering::SeqCst);
+        }
+    }
+
+    impl BufferSink {
+        fn text(&self) -> String {
+            self.0.lock().unwrap().clone()
+        }
+    }
+
+    fn simulator(verbosity: Verbosity) -> (Simulator, BufferSink) {
+        colored::control::set_override(false);
+        let sink = BufferSink::default();
+        let sim = Simulator::with_options(SimulatorOptions {
+            quick: true,
+            verbosity,
+            seed: Some(1),
+            out: Box::new(sink.clone()),
+            non_interactive: true,
+            ..Default::default()
+        });
+        (sim, sink)
+    }
+
+    fn render(cmd: Command) -> String {
+        let (mut sim, sink) = simulator(Verbosity::Normal);
+        sim.execute_command(&cmd).unwrap();
+        sink.text()
+    }
+
+    fn render_verbose(cmd: Command) -> String {
+        let (mut sim, sink) = simulator(Verbosity::Verbose);
+        sim.execute_command(&cmd).unwrap();
+        sink.text()
+    }
+
+    #[test]
+    fn message_interpolates_variables() {
+        let (mut sim, sink) = simulator(Verbosity::Normal);
+        sim.variables.insert("edition".to_string(), "Pro".to_string());
+        sim.execute_command(&Command::Message("Выбрано: ${edition}, ${missing}".to_string())).unwrap();
+        assert_eq!(sink.text(), "  → Выбрано: Pro, ${missing}\n");
+    }
+
+    #[test]
+    fn success_error_warning_symbols() {
+        assert_eq!(render(Command::Success("готово".to_string())), "  ✓ готово\n");
+        assert_eq!(render(Command::Error { message: "сбой".to_string(), fatal: None, exit: 1 }), "  ✗ сбой\n");
+        assert_eq!(render(Command::Warning("осторожно".to_string())), "  ⚠ осторожно\n");
+    }
+
+    #[test]
+    fn delay_prints_nothing() {
+        assert_eq!(render(Command::Delay(5000)), "");
+    }
+
+    #[test]
+    fn progress_bar_fills_proportionally() {
+        let expected = format!("  ◉ Прогресс: [{}{}] 50%\n", "█".repeat(15), "░".repeat(15));
+        assert_eq!(render(Command::Progress(50)), expected);
+        assert!(render(Command::Progress(100)).contains(&format!("[{}] 100%", "█".repeat(30))));
+    }
+
+    #[test]
+    fn configure_shows_key_and_value() {
+        let cmd = Command::Configure { key: "prefix".to_string(), value: "/usr/local".to_string() };
+        assert_eq!(render(cmd), "  ⚙ Конфигурация: prefix=/usr/local\n");
+    }
+
+    #[test]
+    fn create_dir_shows_mkdir_when_verbose() {
+        let cmd = Command::CreateDir("/opt/app".to_string());
+        assert_eq!(render(cmd.clone()), "  → Создание директории: /opt/app \r  ✓ Создание директории: /opt/app  \n");
+        assert!(render_verbose(cmd).ends_with("    $ mkdir -p /opt/app\n"));
+    }
+
+    #[test]
+    fn copy_file_and_symlink_show_commands_when_verbose() {
+        let copy = Command::CopyFile { from: "a.conf".to_string(), to: "/etc/a.conf".to_string() };
+        assert_eq!(render(copy.clone()), "  📄 Копирование: a.conf → /etc/a.conf\n");
+        assert_eq!(render_verbose(copy), "  📄 Копирование: a.conf → /etc/a.conf\n    $ cp a.conf /etc/a.conf\n");
+        let link = Command::Symlink { from: "/opt/app/bin".to_string(), to: "/usr/bin/app".to_string() };
+        assert_eq!(render_verbose(link), "  🔗 Создание ссылки: /opt/app/bin → /usr/bin/app\n    $ ln -s /opt/app/bin /usr/bin/app\n");
+    }
+
+    #[test]
+    fn set_permission_shows_chmod_when_verbose() {
+        let cmd = Command::SetPermission { path: "/opt/app".to_string(), mode: "755".to_string() };
+        assert_eq!(render_verbose(cmd), "  🔐 Установка прав 755 для /opt/app\n    $ chmod 755 /opt/app\n");
+    }
+
+    #[test]
+    fn check_dep_and_set_password_finish_on_same_line() {
+        assert_eq!(render(Command::CheckDep("gcc".to_string())), "  ? Проверка зависимости: gcc ... OK\n");
+        assert_eq!(render(Command::SetPassword("root".to_string())), "  🔑 Установка пароля для root ... OK\n");
+    }
+
+    #[test]
+    fn write_config_shows_first_three_lines_when_verbose() {
+        let cmd = Command::WriteConfig { path: "/etc/app.conf".to_string(), content: "a=1\nb=2\nc=3\nd=4".to_string() };
+        assert_eq!(render(cmd.clone()), "  📝 Запись конфигурации: /etc/app.conf\n");
+        assert_eq!(render_verbose(cmd), "  📝 Запись конфигурации: /etc/app.conf\n    a=1\n    b=2\n    c=3\n    ...\n");
+    }
+
+    #[test]
+    fn set_hostname_and_timezone() {
+        assert_eq!(render_verbose(Command::SetHostname("box".to_string())),
+            "  🖥 Установка имени хоста: box\n    $ hostnamectl set-hostname box\n");
+        assert_eq!(render(Command::SetTimezone("Europe/Moscow".to_string())), "  🌍 Установка часового пояса: Europe/Moscow\n");
+    }
+
+    #[test]
+    fn set_locale_warns_until_generated() {
+        let locale = "ru_RU.UTF-8".to_string();
+        let warning = "Локаль ru_RU.UTF-8 не сгенерирована";
+        assert!(render(Command::SetLocale(locale.clone())).contains(warning));
+
+        let (mut sim, sink) = simulator(Verbosity::Normal);
+        sim.execute_command(&Command::GenerateLocales(vec![locale.clone()])).unwrap();
+        sim.execute_command(&Command::SetLocale(locale)).unwrap();
+        assert!(!sink.text().contains(warning));
+    }
+
+    #[test]
+    fn firewall_rule_and_run_script() {
+        assert_eq!(render(Command::FirewallRule("allow 22/tcp".to_string())), "  🛡 Добавление правила firewall: allow 22/tcp\n");
+        assert_eq!(render(Command::RunScript("setup.sh".to_string())), "  ▷ Выполнение скрипта: setup.sh\n");
+    }
+
+    #[test]
+    fn detect_kernel_is_reproducible_with_seed() {
+        let first = render(Command::DetectKernel);
+        assert!(first.starts_with("  🔍 Определение версии ядра ... "));
+        assert_eq!(first, render(Command::DetectKernel));
+    }
+
+    #[test]
+    fn hardware_profile_is_shared_within_run_unless_fresh() {
+        let (mut sim, sink) = simulator(Verbosity::Normal);
+        for _ in 0..5 {
+            sim.execute_command(&Command::DetectCpu).unwrap();
+        }
+        let text = sink.text();
+        let models: HashSet<&str> = text.lines().filter(|line| line.contains("Модель:")).collect();
+        assert_eq!(models.len(), 1, "{}", text);
+        let profile = sim.hardware();
+        assert!(text.contains(profile.cpu.1), "{}", text);
+        assert!(text.contains(&format!("{} MHz", profile.cpu.3)), "{}", text);
+
+        // Без общего профиля каждая команда выдумывает своё железо заново
+        let (mut sim, sink) = simulator(Verbosity::Normal);
+        sim.fresh_hardware = true;
+        for _ in 0..20 {
+            sim.execute_command(&Command::DetectCpu).unwrap();
+        }
+        let text = sink.text();
+        let models: HashSet<&str> = text.lines().filter(|line| line.contains("Модель:")).collect();
+        assert!(models.len() > 1, "{}", text);
+    }
+
+    #[test]
+    fn benchmark_numbers_follow_hardware() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut profile = SystemProfile::random(&mut rng);
+        profile.disk.3 = "NVMe";
+        let nvme = profile.disk_speeds(&mut rng);
+        profile.disk.3 = "HDD";
+        let hdd = profile.disk_speeds(&mut rng);
+        assert!(hdd.0 < nvme.0 && hdd.2 < nvme.2, "{:?} {:?}", hdd, nvme);
+
+        profile.cpu.3 = 2000;
+        let slow = profile.cpu_scores(&mut rng);
+        profile.cpu.3 = 5000;
+        let fast = profile.cpu_scores(&mut rng);
+        assert!(slow[0].1 < fast[0].1 && slow[1].1 > slow[0].1, "{:?} {:?}", slow, fast);
+
+        assert_eq!(grouped(98432), "98,432");
+        assert_eq!(grouped(1234567), "1,234,567");
+        assert_eq!(grouped(512), "512");
+    }
+
+    #[test]
+    fn interrupt_stops_before_next_command_and_lists_phases() {
+        let source = "package: \"demo\"\nversion: \"1.0\"\nphase \"Загрузка\" {\n message \"a\"\n}\nphase \"Установка\" {\n message \"b\"\n}\n";
+        let package = InstnothParser::new(source.to_string()).parse().unwrap();
+        let (mut sim, sink) = simulator(Verbosity::Normal);
+        sim.interrupt = Box::leak(Box::new(AtomicBool::new(true)));
+
+        assert_eq!(sim.run(&package), Err(Msg::Cancelled.text(Lang::Ru).to_string()));
+        let text = sink.text();
+        assert!(!text.contains("→ a"));
+        assert!(text.contains("  ⚠ Установка прервана пользователем\n    demo (выполнено фаз: 0 из 2)\n    ✗ Загрузка (прервана)\n    ○ Установка\n"));
+    }
+
+    #[test]
+    fn quiet_prints_one_line_per_phase_with_counts() {
+        let source = "package: \"demo\"\nversion: \"1.0\"\nphase \"Настройка\" {\n message \"a\"\n warning \"b\"\n message \"c\"\n}\n";
+        let package = InstnothParser::new(source.to_string()).parse().unwrap();
+        let (mut sim, sink) = simulator(Verbosity::Quiet);
+
+        sim.run(&package).unwrap();
+        let text = sink.text();
+        assert!(text.contains("▶ Настройка ... ✓ (3 шага, 1 предупреждение)\n"), "{}", text);
+        assert!(!text.contains("→ a") && !text.contains("[0%]"));
+        assert!(text.contains("demo установлен успешно!"));
+    }
+
+    #[test]
+    fn only_and_skip_phase_exclude_phases_from_run() {
+        let source = "package: \"demo\"\nversion: \"1.0\"\nphase \"Настройка сети\" {\n message \"сеть\"\n}\nphase \"Настройка диска\" {\n message \"диск\"\n}\nphase \"Тесты\" {\n message \"тест\"\n}\n";
+        let package = InstnothParser::new(source.to_string()).parse().unwrap();
+        let (mut sim, sink) = simulator(Verbosity::Normal);
+        sim.only_phases = vec!["настройка*".to_string()];
+        sim.skip_phases = vec!["*диска".to_string()];
+
+        sim.run(&package).unwrap();
+        let text = sink.text();
+        assert!(text.contains("→ сеть") && !text.contains("→ диск") && !text.contains("→ тест"));
+        assert!(!text.contains("пропущена"));
+        assert_eq!(unmatched_phase_patterns(&["тест?".to_string(), "сборка".to_string()], &[package]), ["сборка"]);
+    }
+
+    #[test]
+    fn plural_picks_russian_form() {
+        let forms = |n| plural(n, "шаг", "шага", "шагов");
+        assert_eq!([forms(1), forms(3), forms(5), forms(11), forms(14), forms(21), forms(102)],
+            ["1 шаг", "3 шага", "5 шагов", "11 шагов", "14 шагов", "21 шаг", "102 шага"]);
+    }
+
+    #[test]
+    fn retry_does_not_repeat_after_interrupt() {
+        let (mut sim, sink) = simulator(Verbosity::Normal);
+        sim.interrupt = Box::leak(Box::new(AtomicBool::new(true)));
+        let body = vec![Command::Message("шаг".to_string())];
+        assert_eq!(sim.retry(3, &body), Err(Msg::Cancelled.text(Lang::Ru).to_string()));
+        assert!(!sink.text().contains("Повтор"));
+    }
+
+    #[test]
+    fn speed_scales_pauses() {
+        assert_eq!(parse_speed("1.5x"), Ok(1.5));
+        assert_eq!(parse_speed("0.5"), Ok(0.5));
+        for bad in ["0", "-1", "NaN", "inf", "быстро"] {
+            assert!(parse_speed(bad).is_err(), "{}", bad);
+        }
+        assert_eq!(scaled(300, 1.5), Duration::from_millis(200));
+        assert_eq!(scaled(100, 0.5), Duration::from_millis(200));
+    }
+
+    #[test]
+    fn log_file_keeps_final_spinner_state_and_package_boundaries() {
+        let path = std::env::temp_dir().join(format!("instnoth-log-{}.log", std::process::id()));
+        let log = LogSink::create(&path, Box::new(BufferSink::default())).unwrap();
+        log.event(Event::new("package_start").with("name", "demo").with("version", "1.0"));
+        log.inline("  → Очистка ");
+        log.inline("\r  ⠋ Очистка  ");
+        log.line("\r  ✓ Очистка  ");
+        log.line("первая\nвторая");
+        drop(log);
+
+        let content = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        let lines: Vec<&str> = content.lines().skip(1).collect();
+        assert_eq!(lines.len(), 4);
+        for line in &lines {
+            assert!(line.starts_with("[00:00:00.") && line.as_bytes()[13] == b']', "{}", line);
+        }
+        let texts: Vec<&str> = lines.iter().map(|l| &l[15..]).collect();
+        assert_eq!(texts, ["──── Пакет demo 1.0 ────", "  ✓ Очистка", "первая", "вторая"]);
+    }
+
+    #[test]
+    fn log_file_captures_parallel_results_from_animated_terminal() {
+        struct AnimatedSink;
+        impl OutputSink for AnimatedSink {
+            fn line(&self, _text: &str) {}
+            fn inline(&self, _text: &str) {}
+            fn animated(&self) -> bool {
+                true
+            }
+        }
+
+        let path = std::env::temp_dir().join(format!("instnoth-log-parallel-{}.log", std::process::id()));
+        let log = LogSink::create(&path, Box::new(AnimatedSink)).unwrap();
+        // Итоги полос indicatif в журнал не попали бы
+        assert!(!log.animated());
+        // Без --quick: задачи идут в потоках, итог каждой печатается по завершении
+        let mut sim = Simulator::with_options(SimulatorOptions {
+            speed: 50.0,
+            seed: Some(1),
+            out: Box::new(log),
+            non_interactive: true,
+            ..Default::default()
+        });
+        sim.execute_command(&Command::Parallel(vec![Command::Delay(300), Command::Delay(200)])).unwrap();
+        drop(sim);
+
+        let content = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(content.contains("✓ Пауза 300 мс") && content.contains("✓ Пауза 200 мс"), "{}", content);
+    }
+
+    #[test]
+    fn record_writes_asciicast_with_monotonic_timestamps() {
+        let path = std::env::temp_dir().join(format!("instnoth-cast-{}.cast", std::process::id()));
+        let cast = CastSink::create(&path, Box::new(BufferSink::default()), (80, 24), false).unwrap();
+        cast.line("\x1b[36m╔══╗\x1b[0m");
+        cast.inline("  ⠋ Очистка ");
+        thread::sleep(Duration::from_millis(5));
+        cast.inline("\r  ⠙ Очистка ");
+        cast.line("\r  ✓ Очистка");
+        cast.mute(true);
+        cast.line("не на экране");
+        cast.mute(false);
+        cast.line("первая\nвторая");
+        drop(cast);
+
+        let content = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        let mut lines = content.lines();
+        let header = JsonReader::new(lines.next().unwrap()).object().unwrap();
+        assert_eq!(header[..3], [
+            ("version".to_string(), JsonValue::Int(2)),
+            ("width".to_string(), JsonValue::Int(80)),
+            ("height".to_string(), JsonValue::Int(24)),
+        ]);
+        assert_eq!(header[3].0, "timestamp");
+
+        let mut last = 0.0;
+        let mut screen = String::new();
+        for line in lines {
+            // Событие: [секунды, "o", текст]; секунды — дробное, его JsonReader не читает
+            let (time, rest) = line.strip_prefix('[').and_then(|l| l.split_once(", ")).unwrap();
+            let time: f64 = time.parse().unwrap();
+            assert!(time >= last, "{}", content);
+            last = time;
+            let Some(JsonValue::List(items)) = JsonReader::new(&format!("[{}", rest)).value() else { panic!("{}", line) };
+            let [JsonValue::Str(kind), JsonValue::Str(data)] = &items[..] else { panic!("{}", line) };
+            assert_eq!(kind, "o");
+            screen.push_str(data);
+        }
+        assert!(last >= 0.005, "{}", content);
+        assert_eq!(screen, "\x1b[36m╔══╗\x1b[0m\r\n  ⠋ Очистка \r  ⠙ Очистка \r  ✓ Очистка\r\nпервая\r\nвторая\r\n");
+    }
+
+    #[test]
+    fn dry_run_marks_defaulted_parameters() {
+        let source = "package: \"demo\"\nversion: \"1.0\"\nphase \"Диск\" {\n format \"/dev/sda1\"\n retry 2 {\n  format \"/dev/sda2\" fs=xfs\n }\n}\n";
+        let package = InstnothParser::new(source.to_string()).parse().unwrap();
+        assert_eq!(package.defaulted.get(&4), Some(&vec!["fs".to_string()]));
+        assert_eq!(package.defaulted.get(&6), None);
+
+        let (sim, sink) = simulator(Verbosity::Normal);
+        sim.print_plan(&[package], &[]);
+        let text = sink.text();
+        assert!(text.contains("   ├─    4 FormatPartition device=\"/dev/sda1\" fs_type=\"ext4\"  (по умолчанию: fs)\n"), "{}", text);
+        assert!(text.contains("      └─    6 FormatPartition device=\"/dev/sda2\" fs_type=\"xfs\"\n"), "{}", text);
+        assert!(text.contains("Всего: 1 пакет, 3 команды"));
+    }
+
+    #[test]
+    fn error_aborts_when_fatal_or_package_says_abort() {
+        let source = "package: \"demo\"\nversion: \"1.0\"\non_error: \"abort\"\nphase \"Проверка\" {\n error \"мелочь\" fatal=false\n error \"Диск не найден\" exit=3\n message \"после\"\n}\nphase \"Дальше\" {\n message \"c\"\n}\n";
+        let package = InstnothParser::new(source.to_string()).parse().unwrap();
+        let (mut sim, sink) = simulator(Verbosity::Normal);
+
+        assert_eq!(sim.run(&package), Err("Диск не найден".to_string()));
+        assert_eq!(sim.exit_code, Some(3));
+        let text = sink.text();
+        assert!(text.contains("  ✗ мелочь\n  ✗ Диск не найден\n") && !text.contains("после") && !text.contains("Дальше"), "{}", text);
+        assert!(text.contains("✗ Установка demo прервана\n    Фаза «Проверка», строка 6: Error text=\"Диск не найден\" exit=3\n"), "{}", text);
+
+        let (mut sim, _) = simulator(Verbosity::Normal);
+        let fatal = InstnothParser::new("error \"x\" fatal=true".to_string()).parse_command("error \"x\" fatal=true", 1).unwrap();
+        assert_eq!(sim.execute_command(&fatal), Err("x".to_string()));
+        assert_eq!(sim.execute_command(&Command::Error { message: "x".to_string(), fatal: None, exit: 1 }), Ok(()));
+    }
+
+    #[test]
+    fn footer_sums_commands_downloads_and_services() {
+        let source = "package: \"demo\"\nversion: \"1.0\"\nphase \"Загрузка\" {\n download \"https://example.com/a.tar\" size=1400000000\n retry attempts=2 {\n  warning \"медленно\"\n }\n}\nphase \"Сервисы\" {\n install_packages \"nginx curl\"\n enable_service \"nginx\"\n}\n";
+        let package = InstnothParser::new(source.to_string()).parse().unwrap();
+        let (mut sim, sink) = simulator(Verbosity::Normal);
+
+        sim.run(&package).unwrap();
+        let stats = RunStats { commands: 4, warnings: 1, errors: 0, downloaded: 1_400_000_000, installed: 2, services: 1 };
+        assert_eq!(sim.stats, stats);
+        assert_eq!(sim.total_stats, stats);
+        assert!(sink.text().contains("✓ demo установлен успешно!\n    Выполнено 4 операции · 1 предупреждение · загружено 1.40 GB · установлено 2 пакета · включено 1 сервис\n"), "{}", sink.text());
+    }
+
+    #[test]
+    fn estimate_follows_parameters_and_speed() {
+        let source = "package: \"demo\"\nversion: \"1.0\"\nphase \"Загрузка\" {\n message \"a\"\n delay 1500\n download \"https://example.com/a.tar\" size=3000\n}\nphase \"Тесты\" optional {\n run_test \"t\" duration=60000\n}\n";
+        let package = InstnothParser::new(source.to_string()).parse().unwrap();
+        let (mut sim, _) = simulator(Verbosity::Normal);
+        assert_eq!(sim.estimate(std::slice::from_ref(&package)), 1500 + 4000 + 60000);
+        sim.minimal = true;
+        assert_eq!(sim.estimate(std::slice::from_ref(&package)), 5500);
+
+        assert_eq!(sim.eta(5500), None);
+        sim.quick_mode = false;
+        sim.speed = 2.0;
+        assert_eq!(sim.eta(5500), Some(Duration::from_millis(2750)));
+        assert_eq!(format_eta(Duration::from_secs(130), Lang::Ru), "~2м 10с");
+        assert_eq!(format_eta(Duration::from_secs(45), Lang::Ru), "~45с");
+        assert_eq!(format_eta(Duration::from_secs(3900), Lang::Ru), "~1ч 05м");
+    }
+
+    #[test]
+    fn chaos_failure_retries_or_aborts() {
+        let cmd = Command::FormatPartition { device: "/dev/sda1".to_string(), fs_type: "ext4".to_string() };
+        let (mut sim, sink) = simulator(Verbosity::Normal);
+        sim.chaos = 100;
+        sim.execute_command(&cmd).unwrap();
+        sim.execute_command(&Command::Message("текст".to_string())).unwrap();
+        let text = sink.text();
+        assert!(text.starts_with("  ✗ mkfs.ext4: I/O error while writing inode tables on /dev/sda1\n  ⟳ Повтор через 1с...\n"), "{}", text);
+        assert!(text.ends_with("  → текст\n"));
+        assert_eq!(sim.phase_stats.warnings, 1);
+
+        sim.chaos_fatal = true;
+        assert_eq!(sim.execute_command(&cmd), Err("mkfs.ext4: I/O error while writing inode tables on /dev/sda1".to_string()));
+    }
+
+    #[test]
+    fn uninstall_reverses_phases_and_commands() {
+        let source = "package: \"demo\"\nversion: \"1.0\"\nphase \"Файлы\" {\n create_dir \"/opt/demo\"\n copy_file \"a\" to=\"/opt/demo/a\"\n}\nphase \"Сервисы\" {\n message \"x\"\n enable_service \"demo\"\n}\n";
+        let package = InstnothParser::new(source.to_string()).parse().unwrap();
+        let (mut sim, sink) = simulator(Verbosity::Normal);
+        sim.uninstalling = true;
+
+        sim.uninstall(&package).unwrap();
+        let text = sink.text();
+        let order = ["Откат: Сервисы", "Отключение сервиса: demo", "Откат: Файлы", "Удаление файла: /opt/demo/a", "Удаление директории: /opt/demo", "demo удалён успешно!"];
+        let positions: Vec<usize> = order.iter().map(|s| text.find(s).unwrap_or_else(|| panic!("{}: {}", s, text))).collect();
+        assert!(positions.windows(2).all(|w| w[0] < w[1]), "{}", text);
+        assert!(text.contains("InstNoth Uninstaller v1.0") && !text.contains("→ x"));
+    }
+
+    #[test]
+    fn resume_replays_completed_phases_silently() {
+        let source = "package: \"demo\"\nversion: \"1.0\"\nphase \"Ядро\" {\n message \"a\"\n detect_kernel\n}\nphase \"Итог\" {\n detect_kernel\n}\n";
+        let package = InstnothParser::new(source.to_string()).parse().unwrap();
+        let (mut full, full_sink) = simulator(Verbosity::Normal);
+        full.run(&package).unwrap();
+        let (mut resumed, sink) = simulator(Verbosity::Normal);
+        resumed.resume_at = Some((0, 1));
+
+        resumed.run(&package).unwrap();
+        let text = sink.text();
+        assert!(text.contains("▷ Ядро (выполнена ранее)") && !text.contains("→ a"), "{}", text);
+        // Пропущенная фаза всё равно потратила свою долю случайности: дальше вывод как без прерывания
+        let rest = |text: &str| text[text.find("▶ Итог").unwrap()..].to_string();
+        assert_eq!(rest(&text), rest(&full_sink.text()));
+    }
+
+    #[test]
+    fn parallel_waits_for_dependencies_and_stops_after_failure() {
+        let package = |name: &str, body: &str| {
+            let source = format!("package: \"{}\"\nversion: \"1.0\"\nphase \"Ядро\" {{\n {}\n}}\n", name, body);
+            InstnothParser::new(source).parse().unwrap()
+        };
+        let packages = [package("a", "message \"один\""), package("b", "message \"два\""), package("c", "error \"сбой\" fatal=true")];
+        let (mut sim, sink) = simulator(Verbosity::Normal);
+
+        let results = sim.run_parallel(&packages[..2], &[vec![], vec![0]], 2);
+        assert!(results.iter().all(|r| matches!(r, Some(Ok(())))));
+        let text = sink.text();
+        assert!(text.lines().all(|line| line.starts_with("[a]") || line.starts_with("[b]")), "{}", text);
+        // b начался только после того, как a установлен до конца
+        assert!(text.rfind("[a]").unwrap() < text.find("[b]").unwrap(), "{}", text);
+
+        let (mut sim, _) = simulator(Verbosity::Normal);
+        let results = sim.run_parallel(&[packages[2].clone(), packages[1].clone()], &[vec![], vec![0]], 2);
+        assert!(matches!(results[0], Some(Err(_))) && results[1].is_none());
+    }
+
+    #[test]
+    fn banner_and_rules_follow_width() {
+        let (mut sim, sink) = simulator(Verbosity::Normal);
+        sim.width = 40;
+        sim.print_banner("InstNoth Multi-Package Installer для очень длинного набора");
+        say!(sim, "{}", sim.rule());
+        let text = sink.text();
+        assert!(text.lines().all(|line| line.chars().count() == 40 || line.chars().count() == 38), "{}", text);
+        assert_eq!(text.lines().count(), 5, "{}", text);
+    }
+
+    #[test]
+    fn english_output_translates_fixed_strings_only() {
+        let (mut sim, sink) = simulator(Verbosity::Normal);
+        sim.lang = Lang::En;
+        sim.execute_command(&Command::CreateDir("/opt/демо".to_string())).unwrap();
+        sim.execute_command(&Command::Success("Готово".to_string())).unwrap();
+        let text = sink.text();
+        assert!(text.contains("Creating directory: /opt/демо") && text.contains("✓ Готово"), "{}", text);
+
+        let source = "package: \"demo\"\nversion: \"1.0\"\nphase \"Диск\" {\n format \"/dev/sda1\"\n}\n";
+        let package = InstnothParser::new(source.to_string()).parse().unwrap();
+        let (mut sim, sink) = simulator(Verbosity::Normal);
+        sim.lang = Lang::En;
+        let duplicate = Duplicate { package: "lib.instnoth".to_string(), via: "demo.instnoth".to_string() };
+        sim.print_plan(&[package], &[duplicate]);
+        let text = sink.text();
+        assert!(text.contains("Installation plan (--dry-run: nothing is executed)\n"), "{}", text);
+        assert!(text.contains("└─    4 FormatPartition device=\"/dev/sda1\" fs_type=\"ext4\"  (defaults: fs)\n"), "{}", text);
+        assert!(text.contains("lib.instnoth (already planned via demo.instnoth)\n"), "{}", text);
+        assert!(text.contains("Total: 1 package, 1 command\n"), "{}", text);
+    }
+
+    #[test]
+    fn english_output_has_no_russian_in_command_strings() {
+        let (mut sim, sink) = simulator(Verbosity::Verbose);
+        sim.lang = Lang::En;
+        let commands = [
+            Command::CreateUser { username: "dev".to_string(), groups: "wheel".to_string() },
+            Command::EnableService("sshd".to_string()),
+            Command::InstallPackages("git curl".to_string()),
+            Command::UpdateSystem,
+            Command::SetKernelParam { key: "vm.swappiness".to_string(), value: "10".to_string() },
+            Command::CheckSpace { path: "/".to_string(), need: 1024, force_fail: false },
+            Command::CreateSnapshot("base".to_string()),
+            Command::RollbackTo("base".to_string()),
+            Command::Progress(50),
+            Command::Reboot { delay_secs: 1 },
+        ];
+        for cmd in &commands {
+            sim.execute_command(cmd).unwrap();
+        }
+        for cmd in &commands {
+            if let Some(inverse) = invert(cmd, Lang::En) {
+                sim.execute_command(&inverse).unwrap();
+            }
+        }
+        let text = sink.text();
+        assert!(text.contains("Creating user: dev") && text.contains("Enabling service: sshd"), "{}", text);
+        assert!(!text.chars().any(|c| matches!(c, 'А'..='я' | 'Ё' | 'ё')), "{}", text);
+    }
+
+    #[test]
+    fn fun_stats_award_achievements_for_commands_that_ran() {
+        let (mut sim, sink) = simulator(Verbosity::Normal);
+        sim.print_fun_stats(&[]);
+        assert!(sink.text().contains("Достижение получено: Professional Procrastinator"), "{}", sink.text());
+
+        let (mut sim, sink) = simulator(Verbosity::Normal);
+        sim.execute_command(&Command::CompileKernel { version: "6.9".to_string() }).unwrap();
+        sim.execute_command(&Command::Download { url: "https://example.com/a.tar".to_string(), size: 2_300_000_000 }).unwrap();
+        sim.total_stats.add(&sim.stats);
+        sim.print_fun_stats(&[]);
+        let text = sink.text();
+        assert!(text.contains("Вы сэкономили 2.30 GB места на диске"), "{}", text);
+        assert!(text.contains("Достижение получено: Kernel Whisperer"), "{}", text);
+    }
+
+    #[test]
+    fn require_root_asks_sudo_password_once_per_run() {
+        let source = "package: \"demo\"\nrequire_root: true\nphase \"Установка\" {\n message \"готово\"\n}\n";
+        let package = InstnothParser::new(source.to_string()).parse().unwrap();
+        assert!(package.require_root);
+        let (mut sim, sink) = simulator(Verbosity::Normal);
+        sim.run(&package).unwrap();
+        sim.run(&package).unwrap();
+        let text = sink.text();
+        assert_eq!(text.matches("[sudo] password for ").count(), 1, "{}", text);
+        assert!(text.contains(": ********"), "{}", text);
+
+        let mut parser = InstnothParser::new("package: \"demo\"\nrequire_root: yes\n".to_string());
+        assert!(parser.parse().is_err());
+    }
+
+    #[test]
+    fn templates_parse_cleanly_before_and_after_rendering() {
+        for (name, _, source) in templates::all() {
+            let rendered = templates::render(source, "demo-app", &date_version(1_792_000_000), "alice");
+            assert!(!rendered.contains("{{"), "{}: не все подстановки заменены", name);
+            for text in [source.to_string(), rendered] {
+                let mut parser = InstnothParser::new(text);
+                let package = parser.parse().unwrap_or_else(|e| panic!("{}: {}", name, e));
+                let diagnostics: Vec<&String> = parser.diagnostics.iter().map(|d| &d.message).collect();
+                assert!(diagnostics.is_empty(), "{}: {:?}", name, diagnostics);
+                assert!(package.phases.len() >= 2, "{}", name);
+            }
+        }
+        assert_eq!(date_version(1_792_000_000), "2026.10.14");
+        let minimal = templates::render(templates::get("minimal").unwrap(), "demo-app", "2026.10.14", "alice");
+        let package = InstnothParser::new(minimal).parse().unwrap();
+        assert_eq!((package.name.as_str(), package.version.as_str(), package.author.as_str()), ("demo-app", "2026.10.14", "alice"));
+    }
+
+    #[test]
+    fn export_renders_commands_as_shell_with_quoting_and_skipped_phases() {
+        let source = "package: \"demo\"\nphase \"Подготовка\" optional {\n message \"пропущено\"\n}\n\
+            phase \"Установка\" {\n message \"it's $HOME\"\n download \"https://example.com/a.tar\" size=1024\n\
+            fail_random \"сбой\" chance=25 code=3\n}\n";
+        let package = InstnothParser::new(source.to_string()).parse().unwrap();
+        let (mut sim, _) = simulator(Verbosity::Normal);
+        sim.minimal = true;
+        let mut script = ShellScript::default();
+        sim.export_script(&[package], &mut script);
+        let script = script.finish();
+        assert!(script.starts_with("#!/usr/bin/env bash\n"));
+        assert!(script.contains("say \"\" '  it'\\''s $HOME'"), "{}", script);
+        assert!(script.contains("bar 'a.tar (1.02 kB)' "), "{}", script);
+        assert!(script.contains("if ((RANDOM % 100 < 25)); then fail 3 'сбой'; fi"), "{}", script);
+        assert!(script.contains("[1/1] Установка") && !script.contains("пропущено"), "{}", script);
+        assert!(script.contains("progress 100"), "{}", script);
+    }
+
+    #[test]
+    fn show_partitions_accepts_disk_names_that_look_like_partitions() {
+        let (mut sim, sink) = simulator(Verbosity::Normal);
+        sim.execute_command(&Command::ShowPartitions("/dev/md1p".to_string())).unwrap();
+        assert!(sink.text().contains("/dev/md1p"), "{}", sink.text());
+        sim.execute_command(&Command::ShowPartitions("/dev/nvme0n1".to_string())).unwrap();
+        assert!(sink.text().contains("/dev/nvme0n1p2"), "{}", sink.text());
+    }
+
+    #[test]
+    fn encrypt_partition_masks_the_typed_passphrase() {
+        let (mut sim, sink) = simulator(Verbosity::Normal);
+        let cmd = Command::EncryptPartition { device: "/dev/sda2".to_string(), cipher: "aes-xts-plain64".to_string(), name: String::new() };
+        sim.execute_command(&cmd).unwrap();
+        let text = sink.text();
+        let typed: Vec<&str> = text.lines()
+            .filter_map(|line| line.split_once("passphrase").map(|(_, rest)| rest.rsplit(": ").next().unwrap_or("")))
+            .collect();
+        assert_eq!(typed.len(), 2, "{}", text);
+        assert!(typed[0].len() >= 12 && typed[0].chars().all(|c| c == '*') && typed[0] == typed[1], "{}", text);
+    }
+}
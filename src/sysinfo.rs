@@ -0,0 +1,430 @@
+//! Правдоподобные случайные данные о железе и системе и строки журналов сборки
+
+use rand::Rng;
+use crate::parser::*;
+
+// ============== Генераторы случайных данных ==============
+
+/// Случайное железо и система: модели, частоты, адреса. С одним и тем же генератором
+/// значения повторяются
+///
+/// ```
+/// use instnoth::sysinfo::RandomSystemInfo;
+/// use rand::{rngs::StdRng, SeedableRng};
+///
+/// let (vendor, model, cores, _mhz) = RandomSystemInfo::cpu(&mut StdRng::seed_from_u64(1));
+/// assert_eq!(RandomSystemInfo::cpu(&mut StdRng::seed_from_u64(1)), (vendor, model, cores, _mhz));
+/// assert!(cores > 0);
+/// ```
+pub struct RandomSystemInfo;
+
+impl RandomSystemInfo {
+    pub fn cpu(rng: &mut impl Rng) -> (&'static str, &'static str, u32, u32) {
+        let cpus = [
+            ("Intel", "Core i9-13900K", 24, 5800),
+            ("Intel", "Core i7-12700K", 12, 5000),
+            ("Intel", "Core i5-13600K", 14, 5100),
+            ("Intel", "Xeon E5-2699 v4", 22, 3600),
+            ("AMD", "Ryzen 9 7950X", 16, 5700),
+            ("AMD", "Ryzen 7 7800X3D", 8, 5000),
+            ("AMD", "Ryzen 5 7600X", 6, 5300),
+            ("AMD", "EPYC 7742", 64, 3400),
+            ("AMD", "Threadripper 3990X", 64, 4300),
+            ("Apple", "M2 Ultra", 24, 3500),
+        ];
+        let idx = rng.gen_range(0..cpus.len());
+        cpus[idx]
+    }
+
+    pub fn memory(rng: &mut impl Rng) -> (u64, &'static str, u32) {
+        let configs = [
+            (8, "DDR4", 2666),
+            (16, "DDR4", 3200),
+            (32, "DDR4", 3600),
+            (32, "DDR5", 4800),
+            (64, "DDR5", 5600),
+            (128, "DDR5", 6000),
+            (16, "DDR5", 5200),
+            (64, "DDR4", 3200),
+        ];
+        let idx = rng.gen_range(0..configs.len());
+        configs[idx]
+    }
+
+    pub fn disk(rng: &mut impl Rng) -> (&'static str, &'static str, u64, &'static str) {
+        let disks = [
+            ("Samsung", "990 PRO", 2000, "NVMe"),
+            ("Samsung", "870 EVO", 1000, "SATA"),
+            ("WD", "Black SN850X", 2000, "NVMe"),
+            ("WD", "Blue SN570", 500, "NVMe"),
+            ("Seagate", "Barracuda", 2000, "HDD"),
+            ("Crucial", "MX500", 1000, "SATA"),
+            ("Kingston", "NV2", 1000, "NVMe"),
+            ("Toshiba", "X300", 4000, "HDD"),
+            ("Intel", "Optane 905P", 960, "NVMe"),
+        ];
+        let idx = rng.gen_range(0..disks.len());
+        disks[idx]
+    }
+
+    pub fn gpu(rng: &mut impl Rng) -> (&'static str, &'static str, u32) {
+        let gpus = [
+            ("NVIDIA", "GeForce RTX 4090", 24),
+            ("NVIDIA", "GeForce RTX 4080", 16),
+            ("NVIDIA", "GeForce RTX 4070 Ti", 12),
+            ("NVIDIA", "GeForce RTX 3080", 10),
+            ("AMD", "Radeon RX 7900 XTX", 24),
+            ("AMD", "Radeon RX 7800 XT", 16),
+            ("AMD", "Radeon RX 6800", 16),
+            ("Intel", "Arc A770", 16),
+            ("Intel", "Arc A380", 6),
+            ("NVIDIA", "Quadro RTX 8000", 48),
+        ];
+        let idx = rng.gen_range(0..gpus.len());
+        gpus[idx]
+    }
+
+    pub fn network(rng: &mut impl Rng) -> (&'static str, &'static str, &'static str) {
+        let nics = [
+            ("Intel", "I225-V 2.5GbE", "2.5 Gbps"),
+            ("Intel", "X710 10GbE", "10 Gbps"),
+            ("Realtek", "RTL8125", "2.5 Gbps"),
+            ("Realtek", "RTL8111", "1 Gbps"),
+            ("Broadcom", "BCM57416", "10 Gbps"),
+            ("Mellanox", "ConnectX-6", "100 Gbps"),
+            ("Intel", "Wi-Fi 6E AX211", "2.4 Gbps"),
+            ("Qualcomm", "Atheros AR9485", "300 Mbps"),
+        ];
+        let idx = rng.gen_range(0..nics.len());
+        nics[idx]
+    }
+
+    pub fn bios(rng: &mut impl Rng) -> (&'static str, &'static str, &'static str) {
+        let bioses = [
+            ("American Megatrends", "UEFI", "3.5.2"),
+            ("Phoenix", "UEFI", "2.1.0"),
+            ("Insyde", "UEFI", "5.0"),
+            ("Award", "Legacy BIOS", "6.0"),
+            ("AMI", "Aptio V", "1.24"),
+            ("Dell", "UEFI", "2.8.1"),
+            ("HP", "UEFI", "F.47"),
+            ("Lenovo", "UEFI", "N24ET82W"),
+        ];
+        let idx = rng.gen_range(0..bioses.len());
+        bioses[idx]
+    }
+
+    pub fn kernel(rng: &mut impl Rng) -> &'static str {
+        let kernels = [
+            "6.6.8-arch1-1",
+            "6.5.0-14-generic",
+            "6.1.52-gentoo",
+            "5.15.0-91-generic",
+            "6.6.6-200.fc39.x86_64",
+            "6.4.12-1-MANJARO",
+            "5.10.0-27-amd64",
+            "6.2.16-300.fc38.x86_64",
+        ];
+        let idx = rng.gen_range(0..kernels.len());
+        kernels[idx]
+    }
+
+    pub fn os(rng: &mut impl Rng) -> (&'static str, &'static str) {
+        let systems = [
+            ("Ubuntu", "22.04.3 LTS (Jammy Jellyfish)"),
+            ("Fedora", "39 (Workstation Edition)"),
+            ("Debian", "12 (Bookworm)"),
+            ("Arch Linux", "Rolling Release"),
+            ("openSUSE", "Tumbleweed"),
+            ("Linux Mint", "21.2 (Victoria)"),
+            ("Pop!_OS", "22.04 LTS"),
+            ("Manjaro", "23.1 (Vulcan)"),
+            ("CentOS Stream", "9"),
+            ("Rocky Linux", "9.3"),
+        ];
+        let idx = rng.gen_range(0..systems.len());
+        systems[idx]
+    }
+
+    pub fn mac_address(rng: &mut impl Rng) -> String {
+        format!(
+            "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+            rng.gen::<u8>(), rng.gen::<u8>(), rng.gen::<u8>(),
+            rng.gen::<u8>(), rng.gen::<u8>(), rng.gen::<u8>()
+        )
+    }
+
+    /// Фальшивая контрольная сумма: зависит только от имени файла и алгоритма,
+    /// поэтому одинакова во всех запусках (FNV-1a, растянутый до нужной длины)
+    pub fn checksum(file: &str, algo: ChecksumAlgo) -> String {
+        let mut hex = String::new();
+        let mut block: u64 = 0;
+        while hex.len() < algo.hex_len() {
+            let mut hash: u64 = 0xcbf29ce484222325;
+            for byte in algo.name().bytes().chain(file.bytes()).chain(block.to_le_bytes()) {
+                hash ^= byte as u64;
+                hash = hash.wrapping_mul(0x100000001b3);
+            }
+            hex.push_str(&format!("{:016x}", hash));
+            block += 1;
+        }
+        hex.truncate(algo.hex_len());
+        hex
+    }
+
+    /// Юниты systemd (имя, описание) в порядке, похожем на настоящую загрузку
+    pub fn systemd_units() -> &'static [(&'static str, &'static str)] {
+        &[
+            ("systemd-journald", "Journal Service"),
+            ("systemd-modules-load", "Load Kernel Modules"),
+            ("systemd-sysctl", "Apply Kernel Variables"),
+            ("systemd-tmpfiles-setup-dev", "Create Static Device Nodes in /dev"),
+            ("systemd-udevd", "Rule-based Manager for Device Events and Files"),
+            ("systemd-remount-fs", "Remount Root and Kernel File Systems"),
+            ("systemd-fsck-root", "File System Check on Root Device"),
+            ("systemd-random-seed", "Load/Save Random Seed"),
+            ("systemd-tmpfiles-setup", "Create Volatile Files and Directories"),
+            ("systemd-timesyncd", "Network Time Synchronization"),
+            ("systemd-update-utmp", "Update UTMP about System Boot/Shutdown"),
+            ("dbus", "D-Bus System Message Bus"),
+            ("NetworkManager", "Network Manager"),
+            ("wpa_supplicant", "WPA supplicant"),
+            ("systemd-logind", "User Login Management"),
+            ("systemd-user-sessions", "Permit User Sessions"),
+            ("polkit", "Authorization Manager"),
+            ("avahi-daemon", "Avahi mDNS/DNS-SD Stack"),
+            ("cups", "CUPS Scheduler"),
+            ("sshd", "OpenBSD Secure Shell server"),
+            ("cron", "Regular background program processing daemon"),
+            ("rsyslog", "System Logging Service"),
+            ("udisks2", "Disk Manager"),
+            ("ModemManager", "Modem Manager"),
+            ("accounts-daemon", "Accounts Service"),
+            ("power-profiles-daemon", "Power Profiles daemon"),
+            ("bluetooth", "Bluetooth service"),
+            ("thermald", "Thermal Daemon Service"),
+            ("gdm", "GNOME Display Manager"),
+        ]
+    }
+
+    pub fn systemd_targets() -> &'static [&'static str] {
+        &["Local File Systems", "System Initialization", "Basic System", "Network", "Multi-User System", "Graphical Interface"]
+    }
+
+    pub fn uuid(rng: &mut impl Rng) -> String {
+        format!(
+            "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+            rng.gen::<u32>(), rng.gen::<u16>(), rng.gen::<u16>() & 0x0fff | 0x4000,
+            rng.gen::<u16>() & 0x3fff | 0x8000, rng.gen::<u64>() & 0xffff_ffff_ffff
+        )
+    }
+
+    /// Пароль из букв и цифр без похожих символов (0/O, 1/l)
+    pub fn password(len: usize, rng: &mut impl Rng) -> String {
+        const CHARS: &[u8] = b"abcdefghijkmnopqrstuvwxyzABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+        (0..len).map(|_| CHARS[rng.gen_range(0..CHARS.len())] as char).collect()
+    }
+
+    /// Серийный номер тома FAT: "1A2B-3C4D"
+    pub fn vfat_id(rng: &mut impl Rng) -> String {
+        format!("{:04X}-{:04X}", rng.gen::<u16>(), rng.gen::<u16>())
+    }
+
+    pub fn ip_address(rng: &mut impl Rng) -> String {
+        format!(
+            "192.168.{}.{}",
+            rng.gen_range(0..255),
+            rng.gen_range(1..254)
+        )
+    }
+}
+
+/// Железо одного запуска, выбранное из RandomSystemInfo разом: detect_*, бенчмарки и датчики
+/// в разных фазах и пакетах описывают одну машину. Производные числа — баллы процессора,
+/// скорость памяти и диска — считаются из выбранного железа с небольшим разбросом
+///
+/// ```
+/// use instnoth::sysinfo::SystemProfile;
+/// use rand::{rngs::StdRng, SeedableRng};
+///
+/// let mut rng = StdRng::seed_from_u64(3);
+/// let profile = SystemProfile::random(&mut rng);
+/// let scores = profile.cpu_scores(&mut rng);
+/// assert!(scores[1].1 > scores[0].1, "многопоток быстрее однопотока");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct SystemProfile {
+    /// Производитель, модель, число ядер, частота (MHz)
+    pub cpu: (&'static str, &'static str, u32, u32),
+    /// Объём (GB), тип (DDR4 или DDR5), частота (MHz)
+    pub memory: (u64, &'static str, u32),
+    /// Производитель, модель, объём (GB), тип (NVMe, SATA или HDD)
+    pub disk: (&'static str, &'static str, u64, &'static str),
+    /// Производитель, модель, видеопамять (GB)
+    pub gpu: (&'static str, &'static str, u32),
+    /// Производитель, модель, скорость сетевого адаптера
+    pub network: (&'static str, &'static str, &'static str),
+    pub mac: String,
+    pub ip: String,
+    pub os: (&'static str, &'static str),
+    pub kernel: &'static str,
+    /// Производитель, тип, версия прошивки
+    pub bios: (&'static str, &'static str, &'static str),
+}
+
+// ±4 %: повторный бенчмарк той же машины даёт близкие, но не одинаковые числа
+fn jitter(value: f64, rng: &mut impl Rng) -> u64 {
+    (value * rng.gen_range(0.96..1.04)).round() as u64
+}
+
+impl SystemProfile {
+    pub fn random(rng: &mut impl Rng) -> Self {
+        Self {
+            cpu: RandomSystemInfo::cpu(rng),
+            memory: RandomSystemInfo::memory(rng),
+            disk: RandomSystemInfo::disk(rng),
+            gpu: RandomSystemInfo::gpu(rng),
+            network: RandomSystemInfo::network(rng),
+            mac: RandomSystemInfo::mac_address(rng),
+            ip: RandomSystemInfo::ip_address(rng),
+            os: RandomSystemInfo::os(rng),
+            kernel: RandomSystemInfo::kernel(rng),
+            bios: RandomSystemInfo::bios(rng),
+        }
+    }
+
+    /// Баллы benchmark_cpu: однопоток растёт с частотой, многопоток — с числом ядер, но
+    /// медленнее линейного, как у настоящих процессоров
+    pub fn cpu_scores(&self, rng: &mut impl Rng) -> [(&'static str, u64); 4] {
+        let (_, _, cores, mhz) = self.cpu;
+        let single = mhz as f64 * 2.2;
+        let multi = single * (cores as f64).powf(0.85);
+        [
+            ("Single-thread", jitter(single, rng)),
+            ("Multi-thread", jitter(multi, rng)),
+            ("Floating point", jitter(multi * 0.46, rng)),
+            ("Integer ops", jitter(multi * 0.69, rng)),
+        ]
+    }
+
+    /// benchmark_memory: чтение, запись и копирование (MB/s) — доли пика двух каналов на
+    /// частоте памяти, и задержка (ns): у DDR5 она выше, чем у DDR4
+    pub fn memory_bandwidth(&self, rng: &mut impl Rng) -> (u64, u64, u64, f64) {
+        let (_, mem_type, mhz) = self.memory;
+        let peak = mhz as f64 * 16.0;
+        let latency = if mem_type == "DDR5" { rng.gen_range(72.0..90.0) } else { rng.gen_range(60.0..75.0) };
+        (jitter(peak * 0.88, rng), jitter(peak * 0.80, rng), jitter(peak * 0.75, rng), (latency * 10.0_f64).round() / 10.0)
+    }
+
+    /// benchmark_disk: последовательные чтение и запись (MB/s) и случайные 4K (IOPS) — по
+    /// типу накопителя: NVMe на порядок быстрее SATA, а HDD медленнее обоих
+    pub fn disk_speeds(&self, rng: &mut impl Rng) -> (u64, u64, u64, u64) {
+        let (read, iops) = match self.disk.3 {
+            "NVMe" => (rng.gen_range(3200.0..7400.0), rng.gen_range(400_000.0..1_000_000.0)),
+            "SATA" => (rng.gen_range(500.0..560.0), rng.gen_range(80_000.0..98_000.0)),
+            _ => (rng.gen_range(140.0..260.0), rng.gen_range(90.0..200.0)),
+        };
+        (jitter(read, rng), jitter(read * 0.88, rng), jitter(iops, rng), jitter(iops * 0.85, rng))
+    }
+
+    /// detect_drivers: модуль ядра и устройство для видеокарты, сетевого адаптера и накопителя
+    pub fn drivers(&self) -> Vec<(&'static str, String)> {
+        let (gpu_vendor, gpu_model, _) = self.gpu;
+        let gpu = match gpu_vendor {
+            "NVIDIA" => "nvidia",
+            "AMD" => "amdgpu",
+            _ => "i915",
+        };
+        let (nic_vendor, nic_model, _) = self.network;
+        let nic = match (nic_vendor, nic_model) {
+            (_, model) if model.contains("Wi-Fi") => "iwlwifi",
+            ("Intel", model) if model.starts_with("X710") => "i40e",
+            ("Intel", _) => "igc",
+            ("Realtek", _) => "r8169",
+            ("Broadcom", _) => "bnxt_en",
+            ("Mellanox", _) => "mlx5_core",
+            _ => "ath9k",
+        };
+        let (disk_vendor, disk_model, _, disk_type) = self.disk;
+        let disk = if disk_type == "NVMe" { "nvme" } else { "ahci" };
+        vec![
+            (gpu, format!("Видеокарта {} {}", gpu_vendor, gpu_model)),
+            (nic, format!("Сетевой адаптер {} {}", nic_vendor, nic_model)),
+            (disk, format!("Накопитель {} {} ({})", disk_vendor, disk_model, disk_type)),
+            ("xhci_hcd", "USB 3.0".to_string()),
+            ("snd_hda_intel", "Intel HD Audio".to_string()),
+        ]
+    }
+}
+
+/// Генератор правдоподобных строк логов сборки и установки
+pub struct FakeLog;
+
+impl FakeLog {
+    pub fn line(style: LogStyle, rng: &mut impl Rng) -> String {
+        match style {
+            LogStyle::Gcc => Self::gcc(rng),
+            LogStyle::Make => Self::make(rng),
+            LogStyle::Dpkg => Self::dpkg(rng),
+            LogStyle::Journal => Self::journal(rng),
+        }
+    }
+
+    fn pick<'a>(items: &[&'a str], rng: &mut impl Rng) -> &'a str {
+        items[rng.gen_range(0..items.len())]
+    }
+
+    /// Путь к исходнику в духе крупных C++ проектов
+    pub(crate) fn source_file(rng: &mut impl Rng) -> String {
+        let dirs = ["lib/Transforms/Scalar", "lib/CodeGen", "lib/Analysis", "src/core", "src/util", "tools/driver", "lib/Support"];
+        let stems = ["LoopUnroll", "RegAlloc", "InstCombine", "Parser", "Lexer", "Allocator", "ThreadPool", "Hashing", "Dominators"];
+        format!("{}/{}.cpp", Self::pick(&dirs, rng), Self::pick(&stems, rng))
+    }
+
+    fn gcc(rng: &mut impl Rng) -> String {
+        let files = ["src/main.c", "lib/parser.c", "drivers/net/e1000.c", "kernel/sched/core.c", "mm/page_alloc.c", "fs/ext4/inode.c"];
+        let file = Self::pick(&files, rng);
+        if rng.gen_bool(0.3) {
+            let warnings = [
+                "unused variable 'ret' [-Wunused-variable]",
+                "comparison of integer expressions of different signedness [-Wsign-compare]",
+                "implicit declaration of function 'kfree' [-Wimplicit-function-declaration]",
+                "this 'if' clause does not guard... [-Wmisleading-indentation]",
+            ];
+            format!("{}:{}:{}: warning: {}", file, rng.gen_range(10..2000), rng.gen_range(1..80), Self::pick(&warnings, rng))
+        } else {
+            format!("gcc -O2 -Wall -fPIC -c {} -o {}", file, file.replace(".c", ".o"))
+        }
+    }
+
+    fn make(rng: &mut impl Rng) -> String {
+        let dirs = ["lib", "src", "drivers/gpu", "tools/perf", "scripts/kconfig", "arch/x86/boot"];
+        let dir = Self::pick(&dirs, rng);
+        match rng.gen_range(0..3) {
+            0 => format!("make[{}]: Entering directory '/build/{}'", rng.gen_range(1..4), dir),
+            1 => format!("  CC      {}/{}.o", dir, Self::pick(&["core", "util", "main", "init", "io"], rng)),
+            _ => format!("  LD      {}/built-in.a", dir),
+        }
+    }
+
+    fn dpkg(rng: &mut impl Rng) -> String {
+        let packages = ["libc6", "libssl3", "coreutils", "systemd", "python3.12", "openssh-server", "linux-firmware", "zlib1g"];
+        let package = Self::pick(&packages, rng);
+        let version = format!("{}.{}.{}-{}", rng.gen_range(1..4), rng.gen_range(0..20), rng.gen_range(0..10), rng.gen_range(1..6));
+        match rng.gen_range(0..3) {
+            0 => format!("Preparing to unpack .../{}_{}_amd64.deb ...", package, version),
+            1 => format!("Unpacking {} ({}) ...", package, version),
+            _ => format!("Setting up {} ({}) ...", package, version),
+        }
+    }
+
+    fn journal(rng: &mut impl Rng) -> String {
+        let units = ["NetworkManager.service", "sshd.service", "systemd-udevd.service", "cron.service", "systemd-journald.service"];
+        let unit = Self::pick(&units, rng);
+        match rng.gen_range(0..3) {
+            0 => format!("systemd[1]: Starting {}...", unit),
+            1 => format!("systemd[1]: Started {}.", unit),
+            _ => format!("kernel: [{:>5}.{:06}] usb 1-{}: new high-speed USB device", rng.gen_range(1..999), rng.gen_range(0..999_999), rng.gen_range(1..8)),
+        }
+    }
+}
@@ -0,0 +1,855 @@
+//! Файлы установки в формате TOML: чтение документа и перевод в текст .instnoth для общего разбора
+
+use std::collections::HashMap;
+use std::path::Path;
+use crate::{format::*, parser::*};
+
+// ============== Документ TOML ==============
+
+/// Первая строка, по которой файл считается TOML и без расширения .instnoth.toml
+pub const TOML_MARKER: &str = "#!instnoth-toml";
+
+/// Значение TOML в объёме, нужном файлам установки: без дат и времени
+#[derive(Debug, Clone, PartialEq)]
+pub enum TomlValue {
+    Str(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Array(Vec<TomlValue>),
+    Table(TomlTable),
+}
+
+/// Таблица TOML: ключи в порядке записи вместе со строками, где они заданы
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TomlTable {
+    /// Строка заголовка `[таблицы]` или начала встроенной таблицы
+    pub line: usize,
+    pub entries: Vec<(String, TomlValue, usize)>,
+}
+
+impl TomlValue {
+    fn type_name(&self) -> &'static str {
+        match self {
+            TomlValue::Str(_) => "строка",
+            TomlValue::Int(_) => "целое число",
+            TomlValue::Float(_) => "дробное число",
+            TomlValue::Bool(_) => "логическое значение",
+            TomlValue::Array(_) => "массив",
+            TomlValue::Table(_) => "таблица",
+        }
+    }
+
+    /// Строка, число или true/false как текст; у массивов и таблиц — None
+    fn scalar(&self) -> Option<String> {
+        match self {
+            TomlValue::Str(s) => Some(s.clone()),
+            TomlValue::Int(n) => Some(n.to_string()),
+            TomlValue::Float(x) => Some(x.to_string()),
+            TomlValue::Bool(b) => Some(b.to_string()),
+            TomlValue::Array(_) | TomlValue::Table(_) => None,
+        }
+    }
+}
+
+impl TomlTable {
+    pub fn get(&self, key: &str) -> Option<&TomlValue> {
+        self.entries.iter().find(|(k, ..)| k == key).map(|(_, value, _)| value)
+    }
+
+    fn get_mut(&mut self, key: &str) -> Option<&mut TomlValue> {
+        self.entries.iter_mut().find(|(k, ..)| k == key).map(|(_, value, _)| value)
+    }
+
+    fn insert(&mut self, key: &str, value: TomlValue, line: usize) -> Result<(), (usize, String)> {
+        if let Some((.., first)) = self.entries.iter().find(|(k, ..)| k == key) {
+            return Err((line, format!("ключ {} уже задан на строке {}", key, first)));
+        }
+        self.entries.push((key.to_string(), value, line));
+        Ok(())
+    }
+}
+
+/// Вложенная таблица по пути ключей; недостающие создаются, у массива таблиц берётся последняя
+fn table_at<'t>(table: &'t mut TomlTable, path: &[String], line: usize) -> Result<&'t mut TomlTable, (usize, String)> {
+    let Some((first, rest)) = path.split_first() else {
+        return Ok(table);
+    };
+    if table.get(first).is_none() {
+        table.entries.push((first.clone(), TomlValue::Table(TomlTable { line, entries: Vec::new() }), line));
+    }
+    let child = match table.get_mut(first) {
+        Some(TomlValue::Table(child)) => child,
+        Some(TomlValue::Array(items)) => match items.last_mut() {
+            Some(TomlValue::Table(child)) => child,
+            _ => return Err((line, format!("ключ {} — массив значений, а не таблиц", first))),
+        },
+        _ => return Err((line, format!("ключ {} уже задан значением, а не таблицей", first))),
+    };
+    table_at(child, rest, line)
+}
+
+/// Разбирает документ TOML. Err — номер строки и описание первой ошибки
+///
+/// ```
+/// use instnoth::toml_format::{parse_document, TomlValue};
+///
+/// let document = parse_document("[package]\nname = \"demo\"\n\n[[phase]]\nname = \"Установка\"\n").unwrap();
+/// let TomlValue::Table(package) = document.get("package").unwrap() else { panic!() };
+/// assert_eq!(package.get("name"), Some(&TomlValue::Str("demo".to_string())));
+/// assert_eq!(parse_document("name = \"demo\"\nname = \"again\"\n").unwrap_err().0, 2);
+/// ```
+pub fn parse_document(text: &str) -> Result<TomlTable, (usize, String)> {
+    let mut reader = TomlReader { chars: text.chars().collect(), pos: 0, line: 1 };
+    let mut root = TomlTable { line: 1, entries: Vec::new() };
+    let mut current: Vec<String> = Vec::new();
+
+    loop {
+        reader.skip_blank();
+        let line = reader.line;
+        match reader.peek() {
+            None => return Ok(root),
+            Some('[') => {
+                let array = reader.starts_with("[[");
+                let close = if array { "]]" } else { "]" };
+                reader.pos += close.len();
+                let path = reader.key_path()?;
+                reader.skip_spaces();
+                if !reader.starts_with(close) {
+                    return reader.fail(format!("ожидается {} после имени таблицы", close));
+                }
+                reader.pos += close.len();
+                reader.end_of_line()?;
+
+                if array {
+                    let (last, parent) = path.split_last().expect("путь из key_path не пуст");
+                    let parent = table_at(&mut root, parent, line)?;
+                    let table = TomlValue::Table(TomlTable { line, entries: Vec::new() });
+                    match parent.get_mut(last) {
+                        Some(TomlValue::Array(items)) if items.iter().all(|i| matches!(i, TomlValue::Table(_))) => items.push(table),
+                        Some(_) => return Err((line, format!("ключ {} уже задан и не является массивом таблиц", path.join(".")))),
+                        None => parent.entries.push((last.clone(), TomlValue::Array(vec![table]), line)),
+                    }
+                } else {
+                    table_at(&mut root, &path, line)?;
+                }
+                current = path;
+            }
+            Some(_) => {
+                let (path, value) = reader.key_value()?;
+                reader.end_of_line()?;
+                let (last, parent) = path.split_last().expect("путь из key_path не пуст");
+                let table = table_at(&mut root, &current, line)?;
+                table_at(table, parent, line)?.insert(last, value, line)?;
+            }
+        }
+    }
+}
+
+struct TomlReader {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+
+impl TomlReader {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn starts_with(&self, s: &str) -> bool {
+        s.chars().enumerate().all(|(i, c)| self.chars.get(self.pos + i) == Some(&c))
+    }
+
+    fn fail<T>(&self, message: String) -> Result<T, (usize, String)> {
+        Err((self.line, message))
+    }
+
+    fn skip_spaces(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.pos += 1;
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        if self.peek() == Some('#') {
+            while !matches!(self.peek(), None | Some('\n')) {
+                self.pos += 1;
+            }
+        }
+    }
+
+    // Пробелы, комментарии и переводы строк: между записями и внутри массивов
+    fn skip_blank(&mut self) {
+        loop {
+            self.skip_spaces();
+            self.skip_comment();
+            if !matches!(self.peek(), Some('\n' | '\r')) {
+                break;
+            }
+            self.bump();
+        }
+    }
+
+    // После значения или заголовка таблицы до конца строки может быть только комментарий
+    fn end_of_line(&mut self) -> Result<(), (usize, String)> {
+        self.skip_spaces();
+        self.skip_comment();
+        if self.starts_with("\r\n") {
+            self.pos += 1;
+        }
+        match self.peek() {
+            None => Ok(()),
+            Some('\n') => {
+                self.bump();
+                Ok(())
+            }
+            Some(c) => self.fail(format!("лишние символы после значения: '{}'", c)),
+        }
+    }
+
+    // ключ, "ключ в кавычках" или a.b.c
+    fn key_path(&mut self) -> Result<Vec<String>, (usize, String)> {
+        let mut path = Vec::new();
+        loop {
+            self.skip_spaces();
+            let key = match self.peek() {
+                Some('"') => self.basic_string()?,
+                Some('\'') => self.literal_string()?,
+                _ => {
+                    let start = self.pos;
+                    while self.peek().is_some_and(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+                        self.pos += 1;
+                    }
+                    if start == self.pos {
+                        return self.fail(match self.peek() {
+                            Some(c) if c != '\n' => format!("ожидается ключ, а не '{}'", c),
+                            _ => "ожидается ключ".to_string(),
+                        });
+                    }
+                    self.chars[start..self.pos].iter().collect()
+                }
+            };
+            path.push(key);
+            self.skip_spaces();
+            if self.peek() != Some('.') {
+                return Ok(path);
+            }
+            self.pos += 1;
+        }
+    }
+
+    fn key_value(&mut self) -> Result<(Vec<String>, TomlValue), (usize, String)> {
+        let path = self.key_path()?;
+        self.skip_spaces();
+        if self.peek() != Some('=') {
+            return self.fail(format!("ожидается = после ключа {}", path.join(".")));
+        }
+        self.pos += 1;
+        Ok((path, self.value()?))
+    }
+
+    fn value(&mut self) -> Result<TomlValue, (usize, String)> {
+        self.skip_spaces();
+        match self.peek() {
+            Some('"') => self.basic_string().map(TomlValue::Str),
+            Some('\'') => self.literal_string().map(TomlValue::Str),
+            Some('[') => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                loop {
+                    self.skip_blank();
+                    if self.peek() == Some(']') {
+                        self.pos += 1;
+                        return Ok(TomlValue::Array(items));
+                    }
+                    items.push(self.value()?);
+                    self.skip_blank();
+                    match self.peek() {
+                        Some(',') => self.pos += 1,
+                        Some(']') => {}
+                        _ => return self.fail("ожидается , или ] в массиве".to_string()),
+                    }
+                }
+            }
+            Some('{') => {
+                let mut table = TomlTable { line: self.line, entries: Vec::new() };
+                self.pos += 1;
+                loop {
+                    self.skip_blank();
+                    if self.peek() == Some('}') {
+                        self.pos += 1;
+                        return Ok(TomlValue::Table(table));
+                    }
+                    let line = self.line;
+                    let (path, value) = self.key_value()?;
+                    let (last, parent) = path.split_last().expect("путь из key_path не пуст");
+                    table_at(&mut table, parent, line)?.insert(last, value, line)?;
+                    self.skip_blank();
+                    match self.peek() {
+                        Some(',') => self.pos += 1,
+                        Some('}') => {}
+                        _ => return self.fail("ожидается , или } во встроенной таблице".to_string()),
+                    }
+                }
+            }
+            _ => {
+                let start = self.pos;
+                while self.peek().is_some_and(|c| !c.is_whitespace() && !",]}#".contains(c)) {
+                    self.pos += 1;
+                }
+                let word: String = self.chars[start..self.pos].iter().collect();
+                let digits = word.replace('_', "");
+                let number = digits.strip_prefix('+').unwrap_or(&digits);
+                match word.as_str() {
+                    "" => self.fail("ожидается значение".to_string()),
+                    "true" => Ok(TomlValue::Bool(true)),
+                    "false" => Ok(TomlValue::Bool(false)),
+                    _ => if let Some(hex) = number.strip_prefix("0x") {
+                        i64::from_str_radix(hex, 16).map(TomlValue::Int).or_else(|_| self.fail(format!("некорректное число: {}", word)))
+                    } else if let Ok(n) = number.parse::<i64>() {
+                        Ok(TomlValue::Int(n))
+                    } else if number.contains(['.', 'e', 'E']) || number.ends_with("inf") || number.ends_with("nan") {
+                        number.parse::<f64>().map(TomlValue::Float).or_else(|_| self.fail(format!("некорректное число: {}", word)))
+                    } else {
+                        self.fail(format!("некорректное значение: {} (строки пишутся в кавычках, даты не поддерживаются)", word))
+                    },
+                }
+            }
+        }
+    }
+
+    // Перевод строки сразу после открывающих кавычек многострочной строки не входит в значение
+    fn skip_first_newline(&mut self) {
+        if self.starts_with("\r\n") {
+            self.pos += 1;
+        }
+        if self.peek() == Some('\n') {
+            self.bump();
+        }
+    }
+
+    // "..." с экранированием или многострочная """..."""
+    fn basic_string(&mut self) -> Result<String, (usize, String)> {
+        let start_line = self.line;
+        let multiline = self.starts_with("\"\"\"");
+        self.pos += if multiline { 3 } else { 1 };
+        if multiline {
+            self.skip_first_newline();
+        }
+        let mut s = String::new();
+        loop {
+            let Some(c) = self.bump() else {
+                return Err((start_line, "строка не закрыта".to_string()));
+            };
+            match c {
+                '"' if !multiline => return Ok(s),
+                '"' if self.starts_with("\"\"") => {
+                    self.pos += 2;
+                    return Ok(s);
+                }
+                '\n' if !multiline => return Err((start_line, "строка не закрыта".to_string())),
+                '\\' => match self.bump() {
+                    Some('n') => s.push('\n'),
+                    Some('t') => s.push('\t'),
+                    Some('r') => s.push('\r'),
+                    Some('b') => s.push('\u{8}'),
+                    Some('f') => s.push('\u{c}'),
+                    Some('e') => s.push('\u{1b}'),
+                    Some('"') => s.push('"'),
+                    Some('\\') => s.push('\\'),
+                    Some(u @ ('u' | 'U')) => {
+                        let len = if u == 'u' { 4 } else { 8 };
+                        let hex: String = (0..len).filter_map(|_| self.bump()).collect();
+                        match u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+                            Some(c) => s.push(c),
+                            None => return self.fail(format!("некорректный код символа \\{}{}", u, hex)),
+                        }
+                    }
+                    // Обратная косая черта в конце строки склеивает её со следующей
+                    Some(' ' | '\t' | '\r' | '\n') if multiline => {
+                        self.pos -= 1;
+                        while matches!(self.peek(), Some(' ' | '\t' | '\r' | '\n')) {
+                            self.bump();
+                        }
+                    }
+                    Some(other) => return self.fail(format!("неизвестное экранирование \\{}", other)),
+                    None => return Err((start_line, "строка не закрыта".to_string())),
+                },
+                c => s.push(c),
+            }
+        }
+    }
+
+    // '...' без экранирования или многострочная '''...'''
+    fn literal_string(&mut self) -> Result<String, (usize, String)> {
+        let start_line = self.line;
+        let multiline = self.starts_with("'''");
+        self.pos += if multiline { 3 } else { 1 };
+        if multiline {
+            self.skip_first_newline();
+        }
+        let mut s = String::new();
+        loop {
+            match self.bump() {
+                Some('\'') if !multiline => return Ok(s),
+                Some('\'') if self.starts_with("''") => {
+                    self.pos += 2;
+                    return Ok(s);
+                }
+                None => return Err((start_line, "строка не закрыта".to_string())),
+                Some('\n') if !multiline => return Err((start_line, "строка не закрыта".to_string())),
+                Some(c) => s.push(c),
+            }
+        }
+    }
+}
+
+// ============== Перевод в текст .instnoth ==============
+
+/// Документ, переписанный строками .instnoth
+#[derive(Default)]
+struct Translation {
+    /// Строка текста и строка TOML, из которой она получена
+    lines: Vec<(String, usize)>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Translation {
+    fn push(&mut self, text: String, line: usize) {
+        self.lines.push((text, line));
+    }
+
+    fn warn(&mut self, line: usize, message: String) {
+        self.diagnostics.push(Diagnostic { line, severity: Severity::Warning, message });
+    }
+
+    fn error(&mut self, line: usize, message: String) {
+        self.diagnostics.push(Diagnostic { line, severity: Severity::Error, message });
+    }
+
+    fn document(&mut self, document: &TomlTable) {
+        for (key, value, line) in &document.entries {
+            match (key.as_str(), value) {
+                ("package", TomlValue::Table(header)) => self.header(header),
+                ("phase", TomlValue::Array(phases)) => {
+                    for phase in phases {
+                        match phase {
+                            TomlValue::Table(phase) => self.phase(phase),
+                            other => self.error(*line, format!("phase: ожидается массив таблиц [[phase]], а не {}", other.type_name())),
+                        }
+                    }
+                }
+                ("package", other) => self.error(*line, format!("package: ожидается таблица [package], а не {}", other.type_name())),
+                ("phase", other) => self.error(*line, format!("phase: ожидается массив таблиц [[phase]], а не {}", other.type_name())),
+                _ => self.warn(*line, format!("неизвестный ключ {}: игнорируется", key)),
+            }
+        }
+    }
+
+    // [package] → строки заголовка; name пишется как package:
+    fn header(&mut self, header: &TomlTable) {
+        for (key, value, line) in &header.entries {
+            let field = if key == "name" { "package" } else { key.as_str() };
+            if key == "package" || !HEADER_KEYS.contains(&field) {
+                self.warn(*line, format!("[package]: неизвестный ключ {}: игнорируется", key));
+                continue;
+            }
+            let text = match value {
+                TomlValue::Array(items) if matches!(field, "depends" | "provides" | "conflicts") => {
+                    let specs: Option<Vec<String>> = items.iter()
+                        .map(|item| matches!(item, TomlValue::Str(_)).then(|| item.scalar()).flatten())
+                        .map(|spec| spec.map(|s| quote_literal(&s)))
+                        .collect();
+                    match specs {
+                        Some(specs) => specs.join(" "),
+                        None => {
+                            self.error(*line, format!("[package].{}: ожидается строка или массив строк", field));
+                            continue;
+                        }
+                    }
+                }
+                _ => match value.scalar() {
+                    Some(value) if field == "require_root" => value,
+                    Some(value) => quote_literal(&value),
+                    None => {
+                        self.error(*line, format!("[package].{}: ожидается строка, а не {}", key, value.type_name()));
+                        continue;
+                    }
+                },
+            };
+            self.push(format!("{}: {}", field, text), *line);
+        }
+    }
+
+    fn phase(&mut self, phase: &TomlTable) {
+        let mut head = String::from("phase");
+        match phase.get("name") {
+            Some(name) => match name.scalar() {
+                Some(name) => head = format!("phase {}", quote_literal(&name)),
+                None => self.error(phase.line, format!("[[phase]].name: ожидается строка, а не {}", name.type_name())),
+            },
+            None => self.error(phase.line, "[[phase]]: не указано имя фазы (name)".to_string()),
+        }
+        for (key, value, line) in &phase.entries {
+            match (key.as_str(), value) {
+                ("name" | "commands", _) => {}
+                ("optional" | "append", TomlValue::Bool(set)) => {
+                    if *set {
+                        head.push(' ');
+                        head.push_str(key);
+                    }
+                }
+                ("optional" | "append", other) => {
+                    self.error(*line, format!("[[phase]].{}: ожидается true или false, а не {}", key, other.type_name()));
+                }
+                // Вес проверяет разбор атрибутов фазы, как и weight= в тексте
+                ("weight", value) => head.push_str(&format!(" weight={}", value.scalar().unwrap_or_default())),
+                _ => self.warn(*line, format!("[[phase]]: неизвестный ключ {}: игнорируется", key)),
+            }
+        }
+        self.push(format!("{} {{", head), phase.line);
+        self.commands(phase, "[[phase]]");
+        self.push("}".to_string(), phase.line);
+    }
+
+    // commands = [ { cmd = "...", ... }, ... ] или [[phase.commands]]
+    fn commands(&mut self, owner: &TomlTable, name: &str) {
+        let Some(list) = owner.get("commands") else {
+            return;
+        };
+        let line = owner.entries.iter().find(|(k, ..)| k == "commands").map_or(owner.line, |(.., line)| *line);
+        let TomlValue::Array(items) = list else {
+            self.error(line, format!("{}.commands: ожидается массив таблиц команд, а не {}", name, list.type_name()));
+            return;
+        };
+        for item in items {
+            match item {
+                TomlValue::Table(command) => self.command(command),
+                other => self.error(line, format!("{}.commands: ожидается таблица команды, а не {}", name, other.type_name())),
+            }
+        }
+    }
+
+    fn command(&mut self, command: &TomlTable) {
+        let keyword = match command.get("cmd") {
+            Some(TomlValue::Str(keyword)) => keyword.to_lowercase(),
+            Some(other) => {
+                self.error(command.line, format!("cmd: ожидается строка с именем команды, а не {}", other.type_name()));
+                return;
+            }
+            None => {
+                self.error(command.line, "команда без ключа cmd (например, { cmd = \"message\", text = \"...\" })".to_string());
+                return;
+            }
+        };
+        // Неизвестную команду отвергнет разбор текста, подсказав похожую
+        let Some(spec) = command_spec(&keyword) else {
+            return self.simple_command(&keyword, None, command);
+        };
+        // Ключи — имена из описания команды, как их показывает instnoth schema
+        for (key, _, line) in &command.entries {
+            let known = key == "cmd"
+                || (spec.block && key == "commands")
+                || spec.flags.contains(&key.as_str())
+                || spec.positional.iter().chain(spec.params).any(|p| p.name == key);
+            if !known {
+                self.warn(*line, format!("{}: неизвестный ключ {}: игнорируется", keyword, key));
+            }
+        }
+
+        match keyword.as_str() {
+            "retry" | "parallel" => {
+                let head = match (keyword.as_str(), command.get("attempts")) {
+                    ("parallel", _) => "parallel {".to_string(),
+                    (_, Some(attempts)) => format!("retry {} {{", attempts.scalar().unwrap_or_default()),
+                    (_, None) => {
+                        self.warn(command.line, "retry: не указан attempts, используется 3".to_string());
+                        "retry 3 {".to_string()
+                    }
+                };
+                self.push(head, command.line);
+                self.commands(command, &keyword);
+                self.push("}".to_string(), command.line);
+            }
+            "table" => {
+                let title = command.get("title").and_then(TomlValue::scalar).unwrap_or_default();
+                self.push(format!("table {}", quote_literal(&title)), command.line);
+                let line = command.entries.iter().find(|(k, ..)| k == "rows").map_or(command.line, |(.., line)| *line);
+                match command.get("rows") {
+                    None => {}
+                    Some(TomlValue::Array(rows)) => {
+                        for row in rows {
+                            // Строка таблицы — массив ячеек или одна строка с ячейками через |
+                            let cells: Option<Vec<String>> = match row {
+                                TomlValue::Array(cells) => cells.iter().map(TomlValue::scalar).collect(),
+                                other => other.scalar().map(|cell| vec![cell]),
+                            };
+                            match cells {
+                                Some(cells) => {
+                                    let cells: Vec<String> = cells.iter().map(|c| quote_literal(c)).collect();
+                                    self.push(format!("row {}", cells.join(" ")), line);
+                                }
+                                None => self.error(line, "table.rows: ожидается массив строк таблицы".to_string()),
+                            }
+                        }
+                    }
+                    Some(other) => self.error(line, format!("table.rows: ожидается массив, а не {}", other.type_name())),
+                }
+            }
+            _ => self.simple_command(&keyword, Some(spec), command),
+        }
+    }
+
+    // Однострочная команда: позиционные значения, флаги, затем имя=значение
+    fn simple_command(&mut self, keyword: &str, spec: Option<&CommandSpec>, command: &TomlTable) {
+        let positional = spec.map_or(&[][..], |spec| spec.positional);
+        let mut text = keyword.to_string();
+
+        for ParamSpec { name, .. } in positional {
+            if let Some(value) = command.get(name) {
+                match (value, value.scalar()) {
+                    (TomlValue::Str(s), _) => text.push_str(&format!(" {}", quote_literal(s))),
+                    (_, Some(plain)) => text.push_str(&format!(" {}", plain)),
+                    (other, None) => self.error(command.line, format!("{}.{}: ожидается строка или число, а не {}", keyword, name, other.type_name())),
+                }
+            }
+        }
+        for (key, value, line) in &command.entries {
+            if key == "cmd" || positional.iter().any(|p| p.name == key) {
+                continue;
+            }
+            // Неизвестный ключ уже отмечен; в тексте он сбил бы, например, число после delay
+            let Some(spec) = spec.filter(|spec| spec.flags.contains(&key.as_str()) || spec.params.iter().any(|p| p.name == key)) else {
+                continue;
+            };
+            if spec.flags.contains(&key.as_str()) {
+                match value {
+                    TomlValue::Bool(true) => text.push_str(&format!(" {}", key)),
+                    TomlValue::Bool(false) => {}
+                    other => self.error(*line, format!("{}.{}: ожидается true или false, а не {}", keyword, key, other.type_name())),
+                }
+                continue;
+            }
+            // Строки — в кавычках, числа и true/false — как есть: так их читают параметры текста
+            match (value, value.scalar()) {
+                (TomlValue::Str(s), _) => text.push_str(&format!(" {}={}", key, quote_literal(s))),
+                (_, Some(plain)) => text.push_str(&format!(" {}={}", key, plain)),
+                (other, None) => {
+                    self.error(*line, format!(
+                        "{}.{}: ожидается строка, число или логическое значение, а не {} (списки пишутся строкой, как в тексте)",
+                        keyword, key, other.type_name()
+                    ));
+                    continue;
+                }
+            }
+        }
+
+        self.push(text, command.line);
+    }
+}
+
+/// Файл установки в формате TOML: по первой строке #!instnoth-toml или по расширению .instnoth.toml
+pub fn is_toml(content: &str, path: Option<&Path>) -> bool {
+    content.lines().next().is_some_and(|first| first.trim_end() == TOML_MARKER)
+        || path.is_some_and(|p| p.to_string_lossy().ends_with(".instnoth.toml"))
+}
+
+/// Разбор файла TOML тем же InstnothParser: документ переписывается текстом .instnoth, так что
+/// значения по умолчанию и проверки у обоих форматов общие, а номера строк в пакете и
+/// диагностике затем возвращаются к строкам TOML
+pub(crate) fn parse_package(parser: &mut InstnothParser) -> Package {
+    let source = std::mem::take(&mut parser.content);
+    let mut translation = Translation::default();
+    match parse_document(&source) {
+        Ok(document) => translation.document(&document),
+        Err((line, message)) => translation.error(line, message),
+    }
+
+    let first = parser.diagnostics.len();
+    parser.content = translation.lines.iter().map(|(text, _)| text.as_str()).collect::<Vec<_>>().join("\n");
+    let mut package = parser.parse_text();
+    parser.content = source;
+
+    let map: Vec<usize> = translation.lines.iter().map(|(_, line)| *line).collect();
+    let at = |line: usize| line.checked_sub(1).and_then(|i| map.get(i)).copied().unwrap_or(line);
+    for diagnostic in &mut parser.diagnostics[first..] {
+        diagnostic.message = remap_message(&diagnostic.message, at);
+        diagnostic.line = at(diagnostic.line);
+    }
+    for dependency in &mut package.depends {
+        dependency.line = at(dependency.line);
+    }
+    for phase in &mut package.phases {
+        phase.line = at(phase.line);
+        for line in &mut phase.command_lines {
+            *line = at(*line);
+        }
+        remap_commands(&mut phase.commands, &at);
+    }
+    let mut defaulted: HashMap<usize, Vec<String>> = HashMap::new();
+    for (line, names) in std::mem::take(&mut package.defaulted) {
+        defaulted.entry(at(line)).or_default().extend(names);
+    }
+    package.defaulted = defaulted;
+
+    parser.diagnostics.append(&mut translation.diagnostics);
+    parser.diagnostics.sort_by_key(|d| d.line);
+    package
+}
+
+fn remap_commands(commands: &mut [Command], at: &impl Fn(usize) -> usize) {
+    for cmd in commands {
+        if let Command::Retry { body, body_lines, .. } = cmd {
+            for line in body_lines.iter_mut() {
+                *line = at(*line);
+            }
+            remap_commands(body, at);
+        }
+    }
+}
+
+// «(строки 3 и 10)» в сообщениях о повторах — тоже номера строк текста
+fn remap_message(message: &str, at: impl Fn(usize) -> usize) -> String {
+    const PREFIX: &str = "(строки ";
+    let remapped = message.find(PREFIX).and_then(|start| {
+        let (numbers, tail) = message[start + PREFIX.len()..].split_once(')')?;
+        let (a, b) = numbers.split_once(" и ")?;
+        Some(format!("{}{}{} и {}){}", &message[..start], PREFIX, at(a.parse().ok()?), at(b.parse().ok()?), tail))
+    });
+    remapped.unwrap_or_else(|| message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> (Package, Vec<Diagnostic>) {
+        let mut parser = InstnothParser::new(source.to_string());
+        let package = parser.parse_package();
+        (package, parser.diagnostics)
+    }
+
+    #[test]
+    fn toml_package_matches_text_package() {
+        let toml = r#"#!instnoth-toml
+[package]
+name = "demo"
+version = "1.2"
+depends = ["base.instnoth", "tools.instnoth >= 2"]
+provides = ["demo-api", "demo-cli"]
+conflicts = "legacy-demo"
+require_root = true
+
+[[phase]]
+name = "Загрузка"
+weight = 3
+commands = [
+    { cmd = "download", url = "https://example.com/demo.tar", size = 5000 },
+    { cmd = "delay", ms = 200 },
+    { cmd = "ask", prompt = "Продолжить?", default = "no", required = true },
+]
+
+[[phase]]
+name = "Настройка"
+optional = true
+
+[[phase.commands]]
+cmd = "set_kernel_param"
+key = "vm.swappiness"
+value = "10"
+
+[[phase.commands]]
+cmd = "retry"
+attempts = 2
+commands = [{ cmd = "run_test", name = "smoke" }]
+
+[[phase.commands]]
+cmd = "table"
+title = "Итог"
+rows = [["a", "b"], "1|2"]
+
+[[phase.commands]]
+cmd = "create_user"
+username = """
+dev"""
+"#;
+        let text = r#"package: "demo"
+version: "1.2"
+depends: "base.instnoth" "tools.instnoth >= 2"
+provides: "demo-api" "demo-cli"
+conflicts: "legacy-demo"
+require_root: true
+
+phase "Загрузка" weight=3 {
+    download "https://example.com/demo.tar" size=5000
+    delay 200
+    ask "Продолжить?" default="no" required
+}
+
+phase "Настройка" optional {
+    set_kernel_param "vm.swappiness" "10"
+    retry 2 {
+        run_test "smoke"
+    }
+    table "Итог"
+    row "a" "b"
+    row "1|2"
+    create_user "dev"
+}
+"#;
+        let (from_toml, diagnostics) = parse(toml);
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+        let (from_text, _) = parse(text);
+        assert_eq!(from_toml.to_instnoth(), from_text.to_instnoth());
+
+        // Строки — из документа TOML: фаза на [[phase]], команда на своей таблице
+        assert_eq!(from_toml.phases[0].line, 10);
+        assert_eq!(from_toml.phases[0].command_lines, [14, 15, 16]);
+        assert_eq!(from_toml.phases[1].command_lines, [23, 28, 33, 38]);
+        let Command::Retry { body_lines, .. } = &from_toml.phases[1].commands[1] else { panic!() };
+        assert_eq!(body_lines, &[31]);
+        // Непереданные параметры те же, что у текста: у create_user — groups
+        assert_eq!(from_toml.defaulted.get(&38), Some(&vec!["groups".to_string()]));
+        assert_eq!(from_toml.provides, ["demo-api", "demo-cli"]);
+        assert_eq!(from_toml.conflicts, ["legacy-demo"]);
+    }
+
+    #[test]
+    fn unknown_keys_warn_with_toml_lines() {
+        let (package, diagnostics) = parse(r#"#!instnoth-toml
+colour = "red"
+
+[package]
+name = "demo"
+homepage_url = "https://example.com"
+
+[[phase]]
+name = "Шаг"
+commands = [
+    { cmd = "download", url = "https://example.com/a", sizes = 10 },
+    { cmd = "compile", name = "app", jobs = 0 },
+]
+"#);
+        let warnings: Vec<(usize, &str)> = diagnostics.iter().map(|d| (d.line, d.message.as_str())).collect();
+        assert_eq!(warnings, [
+            (2, "неизвестный ключ colour: игнорируется"),
+            (6, "[package]: неизвестный ключ homepage_url: игнорируется"),
+            (11, "download: неизвестный ключ sizes: игнорируется"),
+            (12, "compile: jobs= должно быть больше 0, используется 1"),
+        ]);
+        assert_eq!(package.phases[0].commands[0], Command::Download { url: "https://example.com/a".to_string(), size: 1024 });
+
+        let (_, diagnostics) = parse("#!instnoth-toml\n[package]\nname = \"demo\n");
+        assert_eq!((diagnostics[0].line, diagnostics[0].message.as_str()), (3, "строка не закрыта"));
+    }
+}
@@ -0,0 +1,294 @@
+mod common;
+
+use common::{instnoth, stdout};
+use std::process::Command;
+
+#[test]
+fn run_and_bare_file_match_file_flag() {
+    let flags = ["--quick", "--no-summary", "--no-fun", "--seed", "7"];
+    let expected = stdout(&[&["--file", "examples/python.instnoth"][..], &flags].concat());
+    assert_eq!(stdout(&[&["run", "examples/python.instnoth"][..], &flags].concat()), expected);
+    // Флаги общие: до подкоманды, после неё и без неё
+    assert_eq!(stdout(&[&flags[..], &["run", "examples/python.instnoth"]].concat()), expected);
+    assert_eq!(stdout(&[&["examples/python.instnoth"][..], &flags].concat()), expected);
+}
+
+#[test]
+fn subcommands_map_to_flags() {
+    assert_eq!(stdout(&["deps", "examples/all.instnoth"]), stdout(&["--show-deps", "--file", "examples/all.instnoth"]));
+    assert_eq!(stdout(&["list"]), stdout(&["--list-builtin"]));
+    assert!(stdout(&["check", "examples/python.instnoth"]).contains("ошибок: 0"));
+    // Пакет, который уже в плане как зависимость, второй раз не устанавливается
+    let plan = stdout(&["--dry-run", "--file", "examples/all.instnoth", "examples/python.instnoth"]);
+    assert_eq!(plan.matches("Python 3.12.1 (examples/python.instnoth)").count(), 1, "{plan}");
+    assert!(plan.contains("examples/python.instnoth (уже в плане через examples/all.instnoth)"), "{plan}");
+    let broken = std::env::temp_dir().join(format!("instnoth-check-{}.instnoth", std::process::id()));
+    std::fs::write(&broken, "package: \"broken\"\nphase \"A\" {\n    mesage \"typo\"\n}\n").unwrap();
+    let output = instnoth(&["check", broken.to_str().unwrap()]);
+    std::fs::remove_file(&broken).unwrap();
+    assert!(!output.status.success());
+}
+
+#[test]
+fn schema_describes_every_command() {
+    let schema = stdout(&["schema"]);
+    assert!(schema.starts_with("{\"version\":1,\"commands\":["), "{schema}");
+    for keyword in ["message", "download", "retry", "parallel", "generate_ssh_key"] {
+        assert!(schema.contains(&format!("{{\"keyword\":\"{keyword}\"")), "{keyword}");
+    }
+    assert!(schema.contains(r#"{"name":"size","type":"integer","default":1024,"required":false}"#));
+}
+
+#[test]
+fn new_scaffolds_from_templates_without_clobbering() {
+    let dir = std::env::temp_dir().join(format!("instnoth-new-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let new = |args: &[&str]| Command::new(env!("CARGO_BIN_EXE_instnoth")).arg("new").args(args).current_dir(&dir).env("USER", "alice").output().unwrap();
+    assert!(new(&["my-app"]).status.success());
+    let file = dir.join("my-app.instnoth");
+    let content = std::fs::read_to_string(&file).unwrap();
+    assert!(content.contains("package: \"my-app\""), "{content}");
+    assert!(content.contains("author: \"alice\""), "{content}");
+    // Без --force файл не перезаписывается, с ним — заменяется другой заготовкой
+    assert!(!new(&["my-app", "--template", "webserver"]).status.success());
+    assert_eq!(std::fs::read_to_string(&file).unwrap(), content);
+    assert!(new(&["my-app", "--template", "webserver", "--force"]).status.success());
+    assert!(std::fs::read_to_string(&file).unwrap().contains("nginx"));
+    assert!(stdout(&["run", file.to_str().unwrap(), "--quick", "--no-fun"]).contains("my-app"));
+    assert!(!new(&["x", "--template", "nope"]).status.success());
+    std::fs::remove_dir_all(dir).unwrap();
+
+    let list = stdout(&["new", "--list-templates"]);
+    for template in ["minimal", "webserver", "os-install", "devtools"] {
+        assert!(list.contains(template), "{list}");
+    }
+}
+
+#[test]
+fn fmt_check_reports_and_fmt_rewrites() {
+    let file = std::env::temp_dir().join(format!("instnoth-fmt-{}.instnoth", std::process::id()));
+    let file = file.to_str().unwrap();
+    let messy = "package:\"fmt\"\nphase \"A\" {\n        message   \"x\"   # comment\n}\n";
+    std::fs::write(file, messy).unwrap();
+    // --check только сообщает и ничего не меняет
+    assert!(!instnoth(&["fmt", "--check", file]).status.success());
+    assert_eq!(std::fs::read_to_string(file).unwrap(), messy);
+    assert!(instnoth(&["fmt", file]).status.success());
+    assert_eq!(std::fs::read_to_string(file).unwrap(), "package: \"fmt\"\n\nphase \"A\" {\n  message \"x\" # comment\n}\n");
+    assert!(instnoth(&["fmt", "--check", file]).status.success());
+    // Файл с ошибкой разбора не трогается
+    std::fs::write(file, "package: \"fmt\"\nphase \"A\" {\n").unwrap();
+    assert!(!instnoth(&["fmt", file]).status.success());
+    assert_eq!(std::fs::read_to_string(file).unwrap(), "package: \"fmt\"\nphase \"A\" {\n");
+    std::fs::remove_file(file).unwrap();
+}
+
+#[test]
+fn text_package_depends_on_toml_package() {
+    let dir = std::env::temp_dir().join(format!("instnoth-toml-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("base.instnoth.toml"), "[package]\nname = \"base\"\nversion = \"2.1\"\n\n[[phase]]\nname = \"Основа\"\ncommands = [{ cmd = \"message\", text = \"база из TOML\" }]\n").unwrap();
+    std::fs::write(dir.join("app.instnoth"), "package: \"app\"\ndepends: \"base.instnoth.toml >= 2\"\n\nphase \"Приложение\" {\n  message \"приложение\"\n}\n").unwrap();
+    let app = dir.join("app.instnoth");
+    let out = stdout(&["run", app.to_str().unwrap(), "--quick", "--no-fun"]);
+    let (base, app) = (out.find("база из TOML").expect(&out), out.find("приложение").expect(&out));
+    assert!(base < app, "{out}");
+    // fmt не трогает TOML, даже найденный в каталоге
+    assert!(instnoth(&["fmt", "--check", dir.to_str().unwrap()]).status.success());
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn missing_dependencies_abort_before_install() {
+    let dir = std::env::temp_dir().join(format!("instnoth-missing-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("lib.instnoth"), "package: \"lib\"\ndepends: \"gone.instnoth\"\n\nphase \"Библиотека\" {\n  message \"lib\"\n}\n").unwrap();
+    let app = dir.join("app.instnoth");
+    std::fs::write(&app, "package: \"app\"\ndepends: \"lib.instnoth\" \"nope.instnoth\"\n\nphase \"Приложение\" {\n  message \"приложение\"\n}\n").unwrap();
+    let app = app.to_str().unwrap();
+
+    let output = instnoth(&["run", app, "--quick", "--no-fun"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("приложение"));
+    // Перечислены все ненайденные файлы с пакетом, которому они нужны, и абсолютным путём
+    let absolute = std::path::absolute(&dir).unwrap();
+    assert!(stderr.contains(&format!("lib требует gone.instnoth: нет файла {}", absolute.join("gone.instnoth").display())), "{stderr}");
+    assert!(stderr.contains(&format!("app требует nope.instnoth: нет файла {}", absolute.join("nope.instnoth").display())), "{stderr}");
+
+    let out = stdout(&["run", app, "--quick", "--no-fun", "--ignore-missing-deps"]);
+    assert!(out.contains("lib") && out.contains("приложение"), "{out}");
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn deps_tree_marks_cycles_apart_from_shared_dependencies() {
+    let dir = std::env::temp_dir().join(format!("instnoth-deps-cycle-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    for (name, depends) in [("app", "\"left.instnoth\" \"right.instnoth\""), ("left", "\"base.instnoth\""), ("right", "\"base.instnoth\""), ("base", "\"app.instnoth\"")] {
+        std::fs::write(dir.join(format!("{name}.instnoth")), format!("package: \"{name}\"\ndepends: {depends}\n\nphase \"A\" {{\n  message \"x\"\n}}\n")).unwrap();
+    }
+    let out = stdout(&["deps", dir.join("app.instnoth").to_str().unwrap()]);
+    let file = |name: &str| dir.join(format!("{name}.instnoth")).display().to_string();
+    // base из right — уже показанный пакет, а app из base — цикл
+    assert!(out.contains("(уже показан)"), "{out}");
+    assert!(out.contains(&format!("(цикл: {} → {} → {} → {})", file("app"), file("left"), file("base"), file("app"))), "{out}");
+    let dot = dir.join("deps.dot");
+    stdout(&["deps", &file("app"), "--format", "dot", "-o", dot.to_str().unwrap()]);
+    assert!(std::fs::read_to_string(&dot).unwrap().starts_with("digraph deps {"));
+    // С --skip-deps файлы зависимостей не читаются — видны только их пути
+    let named = stdout(&["deps", &file("app"), "--skip-deps"]);
+    assert!(named.contains("… не загружены: left.instnoth, right.instnoth (глубина зависимостей 0)") && !named.contains("left (v"), "{named}");
+    // Под left скрыты base и замыкающий цикл app
+    let capped = stdout(&["deps", &file("app"), "--max-depth", "1"]);
+    assert!(capped.contains("└─ … 2 уровня скрыто") && !capped.contains("(цикл"), "{capped}");
+    // --plan на цикле останавливается той же ошибкой, что и установка
+    let plan = instnoth(&["--plan", &file("app")]);
+    assert!(!plan.status.success());
+    assert!(String::from_utf8_lossy(&plan.stderr).contains("Обнаружена циклическая зависимость"));
+    let plan = stdout(&["--plan", "builtin:all"]);
+    assert!(plan.contains("1. Можно устанавливать параллельно: Python, Node.js, Docker Engine"), "{plan}");
+    assert!(plan.contains("2. All-in-One Dev Bundle"), "{plan}");
+    assert!(plan.contains("Глубина зависимостей: без ограничения"), "{plan}");
+    let plan = stdout(&["--plan", "builtin:all", "--deps-depth", "0"]);
+    assert!(plan.contains("Глубина зависимостей: 0 (только указанные пакеты)") && !plan.contains("Python"), "{plan}");
+    let plan = stdout(&["--plan", "builtin:all", "--lang", "en"]);
+    assert!(plan.contains("Installation by dependency level:\nDependency depth: unlimited\n"), "{plan}");
+    assert!(plan.contains("1. Can be installed in parallel: Python, Node.js, Docker Engine"), "{plan}");
+    assert!(plan.contains("2 levels, 4 packages; --parallel N installs packages of one level at once"), "{plan}");
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn dependencies_resolve_against_their_own_file() {
+    let dir = std::env::temp_dir().join(format!("instnoth-two-dirs-{}", std::process::id()));
+    let package = |path: &str, name: &str, depends: &str| {
+        let path = dir.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let depends = if depends.is_empty() { String::new() } else { format!("depends: \"{depends}\"\n") };
+        std::fs::write(path, format!("package: \"{name}\"\n{depends}\nphase \"Фаза {name}\" {{\n  message \"{name}\"\n}}\n")).unwrap();
+    };
+    // У одноимённых lib.instnoth в a/ и b/ разные пакеты; вложенная зависимость a/sub/ — от своего каталога
+    package("a/one.instnoth", "one", "lib.instnoth");
+    package("a/lib.instnoth", "lib-a", "sub/inner.instnoth");
+    package("a/sub/inner.instnoth", "inner", "deep.instnoth");
+    package("a/sub/deep.instnoth", "deep", "");
+    package("b/two.instnoth", "two", "lib.instnoth");
+    package("b/lib.instnoth", "lib-b", "");
+
+    let (one, two) = (dir.join("a/one.instnoth"), dir.join("b/two.instnoth"));
+    let out = stdout(&["run", one.to_str().unwrap(), two.to_str().unwrap(), "--quick", "--no-fun"]);
+    let order: Vec<usize> = ["Фаза deep", "Фаза inner", "Фаза lib-a", "Фаза one", "Фаза lib-b", "Фаза two"].iter()
+        .map(|phase| out.find(phase).unwrap_or_else(|| panic!("нет {phase}:\n{out}")))
+        .collect();
+    assert!(order.windows(2).all(|w| w[0] < w[1]), "{out}");
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn installed_packages_are_skipped_until_newer_or_reinstalled() {
+    let dir = std::env::temp_dir().join(format!("instnoth-installed-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("demo.instnoth");
+    let write = |version: &str| std::fs::write(&file, format!("package: \"demo\"\nversion: \"{version}\"\n\nphase \"Сборка\" {{\n  message \"x\"\n}}\n")).unwrap();
+    let state = dir.join("state");
+    let run = |extra: &[&str]| {
+        let args = [&["--quick", "--no-fun", "--state-dir", state.to_str().unwrap(), file.to_str().unwrap()], extra].concat();
+        stdout(&args)
+    };
+
+    write("1.0");
+    assert!(run(&[]).contains("Сборка"));
+    let again = run(&[]);
+    assert!(again.contains("demo уже установлен (v1.0)") && !again.contains("Сборка"), "{again}");
+    assert!(run(&["--reinstall"]).contains("Сборка"));
+    write("1.1");
+    assert!(run(&[]).contains("Сборка"));
+
+    let list = stdout(&["list", "--installed", "--state-dir", state.to_str().unwrap()]);
+    assert!(list.contains("demo v1.1") && list.contains(&file.display().to_string()), "{list}");
+    // Даты в одной колонке, сколько бы знаков ни было в версиях
+    stdout(&["--quick", "--no-fun", "--state-dir", state.to_str().unwrap(), "builtin:python"]);
+    let list = stdout(&["list", "--installed", "--state-dir", state.to_str().unwrap()]);
+    let date_column = |name: &str| list.lines().find(|l| l.contains(name)).and_then(|l| l.find(" 20")).unwrap();
+    assert_eq!(date_column("demo"), date_column("Python"), "{list}");
+    stdout(&["remove", "Python", "--state-dir", state.to_str().unwrap()]);
+    let unknown = instnoth(&["remove", "demo", "nope", "--state-dir", state.to_str().unwrap()]);
+    assert!(!unknown.status.success());
+    assert!(String::from_utf8_lossy(&unknown.stderr).contains("Не установлены: nope"));
+    // С --uninstall запись пропадает после показанного удаления
+    let removed = stdout(&["remove", "DEMO", "--uninstall", "--quick", "--state-dir", state.to_str().unwrap()]);
+    assert!(removed.contains("demo удалён успешно"), "{removed}");
+    assert!(stdout(&["list", "--installed", "--state-dir", state.to_str().unwrap()]).contains("(пока ничего)"));
+    assert!(run(&[]).contains("Сборка"));
+    assert!(stdout(&["remove", "demo", "--state-dir", state.to_str().unwrap()]).contains("demo (v1.1) убран из списка установленных"));
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn conflicting_packages_refuse_to_install_unless_forced() {
+    let dir = std::env::temp_dir().join(format!("instnoth-conflicts-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let package = |name: &str, header: &str| {
+        let path = dir.join(format!("{name}.instnoth"));
+        std::fs::write(&path, format!("package: \"{name}\"\nversion: \"1.0\"\n{header}\nphase \"A\" {{\n  message \"x\"\n}}\n")).unwrap();
+        path.to_str().unwrap().to_string()
+    };
+    let nginx = package("nginx", "provides: \"webserver\"\n");
+    let apache = package("apache", "conflicts: \"webserver\"\n");
+    let site = package("site", "depends: \"webserver\"\n");
+    let state = dir.join("state");
+    let run = |files: &[&str], extra: &[&str]| {
+        instnoth(&[&["--quick", "--no-fun", "--state-dir", state.to_str().unwrap()], files, extra].concat())
+    };
+
+    // Зависимость по provides берётся из пакетов того же запуска
+    let installed = run(&[&nginx, &site], &[]);
+    assert!(installed.status.success(), "{}", String::from_utf8_lossy(&installed.stderr));
+
+    let refused = run(&[&nginx, &apache], &["--reinstall"]);
+    assert!(!refused.status.success());
+    let stderr = String::from_utf8_lossy(&refused.stderr);
+    assert!(stderr.contains(&format!("{apache} конфликтует с {nginx} (conflicts: \"webserver\", provides у nginx)")), "{stderr}");
+    // Установленный nginx помнит свой provides из прошлого запуска
+    let refused = run(&[&apache], &[]);
+    assert!(!refused.status.success());
+    let stderr = String::from_utf8_lossy(&refused.stderr);
+    assert!(stderr.contains(&format!("{apache} конфликтует с установленным nginx v1.0 ({nginx})")), "{stderr}");
+
+    let forced = run(&[&apache], &["--force-conflicts"]);
+    assert!(forced.status.success());
+    assert!(String::from_utf8_lossy(&forced.stderr).contains("--force-conflicts: СТАВЯТСЯ КОНФЛИКТУЮЩИЕ ПАКЕТЫ"));
+    assert!(String::from_utf8_lossy(&forced.stdout).contains("apache"));
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn locked_run_refuses_drifted_dependencies() {
+    let dir = std::env::temp_dir().join(format!("instnoth-lock-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("lib")).unwrap();
+    let package = |path: &str, name: &str, version: &str, depends: &str| {
+        let depends = if depends.is_empty() { String::new() } else { format!("depends: \"{depends}\"\n") };
+        std::fs::write(dir.join(path), format!("package: \"{name}\"\nversion: \"{version}\"\n{depends}\nphase \"A\" {{\n  message \"x\"\n}}\n")).unwrap();
+    };
+    package("app.instnoth", "app", "1.0", "lib/base.instnoth");
+    package("lib/base.instnoth", "base", "2.0", "");
+    let app = dir.join("app.instnoth");
+    let app = app.to_str().unwrap();
+
+    assert!(stdout(&["lock", app]).contains("(2 файла)"));
+    let lock = std::fs::read_to_string(dir.join("app.instnoth.lock")).unwrap();
+    assert!(lock.contains("\"path\":\"lib/base.instnoth\",\"name\":\"base\",\"version\":\"2.0\""), "{lock}");
+    assert!(stdout(&["run", app, "--locked", "--quick", "--no-fun"]).contains("app установлен"));
+
+    package("lib/base.instnoth", "base", "2.1", "");
+    let drifted = instnoth(&["run", app, "--locked", "--quick"]);
+    assert!(!drifted.status.success());
+    let stderr = String::from_utf8_lossy(&drifted.stderr);
+    assert!(stderr.contains("~ lib/base.instnoth: base 2.0 → base 2.1 (sha256 "), "{stderr}");
+    assert!(String::from_utf8_lossy(&drifted.stdout).is_empty());
+    // Без lockfile --locked запускать не с чем сверять
+    std::fs::remove_file(dir.join("app.instnoth.lock")).unwrap();
+    assert!(!instnoth(&["run", app, "--locked", "--quick"]).status.success());
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,41 @@
+//! Запуск instnoth из интеграционных тестов. Каждый файл тестов берёт свою часть
+#![allow(dead_code)]
+
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Каталог списка установленных пакетов для одного запуска: у каждого свой, иначе пакет,
+/// установленный одним тестом, пропускался бы в другом
+pub fn state_dir() -> std::path::PathBuf {
+    static RUNS: AtomicUsize = AtomicUsize::new(0);
+    std::env::temp_dir().join(format!("instnoth-data-{}-{}", std::process::id(), RUNS.fetch_add(1, Ordering::SeqCst)))
+}
+
+/// instnoth с данными на stdin и своим XDG_DATA_HOME, который после запуска удаляется
+pub fn instnoth_with_stdin(args: &[&str], stdin: &[u8]) -> Output {
+    let data = state_dir();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_instnoth"))
+        .args(args)
+        .env("XDG_DATA_HOME", &data)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("не удалось запустить instnoth");
+    child.stdin.take().unwrap().write_all(stdin).unwrap();
+    let output = child.wait_with_output().unwrap();
+    let _ = std::fs::remove_dir_all(&data);
+    output
+}
+
+pub fn instnoth(args: &[&str]) -> Output {
+    instnoth_with_stdin(args, b"")
+}
+
+/// stdout успешного запуска; при ошибке тест падает с её текстом
+pub fn stdout(args: &[&str]) -> String {
+    let output = instnoth(args);
+    assert!(output.status.success(), "{args:?}: {}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
@@ -0,0 +1,30 @@
+mod common;
+
+use common::stdout;
+use std::process::Command;
+
+#[test]
+fn complete_phases_lists_phase_names_of_file() {
+    let phases = stdout(&["__complete-phases", "examples/python.instnoth"]);
+    let phases: Vec<&str> = phases.lines().collect();
+    assert_eq!(phases.first(), Some(&"Анализ системы"));
+    assert!(phases.contains(&"Компиляция Python"), "{phases:?}");
+    // Встроенные файлы дополняются так же, как файлы на диске
+    assert_eq!(stdout(&["__complete-phases", "builtin:python"]).lines().collect::<Vec<_>>(), phases);
+}
+
+#[test]
+fn completion_scripts_complete_flags_and_phases() {
+    for shell in ["bash", "zsh", "fish"] {
+        let script = stdout(&["completions", shell]);
+        for flag in ["only-phase", "skip-phase", "file", "output"] {
+            assert!(script.contains(flag), "{shell}: нет --{flag}");
+        }
+        assert!(script.contains("instnoth __complete-phases"), "{shell}: фазы не дополняются");
+    }
+    // Скрипт для bash должен хотя бы разбираться, если bash есть
+    let script = stdout(&["completions", "bash"]);
+    if let Ok(status) = Command::new("bash").args(["-n", "-c", &script]).status() {
+        assert!(status.success(), "bash не разобрал скрипт");
+    }
+}
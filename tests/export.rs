@@ -0,0 +1,53 @@
+mod common;
+
+use std::process::Command;
+
+fn bash_available() -> bool {
+    Command::new("bash").arg("--version").output().is_ok_and(|o| o.status.success())
+}
+
+fn export(file: &str) -> String {
+    export_in(file, "ru")
+}
+
+fn export_in(file: &str, lang: &str) -> String {
+    common::stdout(&["export", file, "--format", "sh", "--lang", lang])
+}
+
+#[test]
+fn exported_scripts_pass_bash_syntax_check() {
+    if !bash_available() {
+        eprintln!("bash не найден, проверка экспорта пропущена");
+        return;
+    }
+    for entry in std::fs::read_dir("examples").unwrap() {
+        let path = entry.unwrap().path();
+        let script = export(path.to_str().unwrap());
+        let status = Command::new("bash").args(["-n", "-c", &script]).status().unwrap();
+        assert!(status.success(), "{}: bash -n не прошёл", path.display());
+    }
+}
+
+#[test]
+fn exported_script_runs_without_instnoth() {
+    if !bash_available() {
+        eprintln!("bash не найден, проверка экспорта пропущена");
+        return;
+    }
+    // Зависимости попадают в скрипт, как при установке
+    let script = export("examples/all.instnoth");
+    let output = Command::new("bash").args(["-c", &script, "script", "--quick"]).output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let order: Vec<usize> = ["✓ Python установлен", "✓ Node.js установлен", "✓ All-in-One Dev Bundle установлен"].iter()
+        .map(|line| stdout.find(line).unwrap_or_else(|| panic!("нет {line:?}:\n{stdout}")))
+        .collect();
+    assert!(order.windows(2).all(|w| w[0] < w[1]), "{stdout}");
+}
+
+#[test]
+fn exported_script_follows_lang() {
+    let script = export_in("examples/python.instnoth", "en");
+    assert!(script.lines().nth(1) == Some("# Installing Python 3.12.1"), "{script}");
+    assert!(script.contains("'✓ Python installed successfully!'") && !script.contains("Python установлен успешно"), "{script}");
+}
@@ -0,0 +1,98 @@
+use instnoth::deps::{builtin, DependencyManager};
+use instnoth::output::OutputSink;
+use instnoth::parser::InstnothParser;
+use instnoth::sim::{Simulator, SimulatorOptions, Verbosity};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Копит текстовый вывод симулятора, кроме заглушённого в --quiet
+#[derive(Clone, Default)]
+struct Collect(Arc<Mutex<String>>, Arc<AtomicBool>);
+
+impl OutputSink for Collect {
+    fn line(&self, text: &str) {
+        if !self.1.load(Ordering::SeqCst) {
+            let mut buf = self.0.lock().unwrap();
+            buf.push_str(text);
+            buf.push('\n');
+        }
+    }
+
+    fn inline(&self, text: &str) {
+        if !self.1.load(Ordering::SeqCst) {
+            self.0.lock().unwrap().push_str(text);
+        }
+    }
+
+    fn mute(&self, muted: bool) {
+        self.1.store(muted, Ordering::SeqCst);
+    }
+}
+
+fn install(source: &str, seed: u64) -> String {
+    colored::control::set_override(false);
+    let package = InstnothParser::new(source.to_string()).parse().unwrap();
+    let sink = Collect::default();
+    let mut simulator = Simulator::with_options(SimulatorOptions {
+        quick: true,
+        seed: Some(seed),
+        out: Box::new(sink.clone()),
+        non_interactive: true,
+        ..Default::default()
+    });
+    simulator.run(&package).unwrap();
+    let text = sink.0.lock().unwrap().clone();
+    text
+}
+
+#[test]
+fn builtin_package_resolves_dependencies_and_installs() {
+    let path = builtin::path("all");
+    let mut manager = DependencyManager::new(path.parent().unwrap().to_path_buf());
+    let all = manager.load_package(&path).unwrap();
+    let order = manager.get_install_order(&[all]).unwrap();
+    let names: Vec<&str> = order.iter().map(|p| p.name.as_str()).collect();
+    assert_eq!(names.last(), Some(&"All-in-One Dev Bundle"));
+    assert!(names.len() > 1, "{names:?}");
+    // Зависимости берутся из соседних встроенных файлов, а не с диска
+    assert!(order.iter().all(|p| p.file_path.as_ref().unwrap().starts_with("builtin:")), "{names:?}");
+
+    let python = builtin::get("python").unwrap();
+    let output = install(python, 3);
+    let package = InstnothParser::new(python.to_string()).parse().unwrap();
+    for phase in &package.phases {
+        assert!(output.contains(&phase.name), "нет фазы {}:\n{output}", phase.name);
+    }
+}
+
+#[test]
+fn same_seed_gives_same_installation() {
+    let source = builtin::get("docker").unwrap();
+    assert_eq!(install(source, 11), install(source, 11));
+
+    // Тихий режим из настроек: по строке на фазу вместо вывода команд
+    let package = InstnothParser::new(source.to_string()).parse().unwrap();
+    let sink = Collect::default();
+    let mut simulator = Simulator::with_options(SimulatorOptions {
+        quick: true,
+        verbosity: Verbosity::Quiet,
+        seed: Some(11),
+        out: Box::new(sink.clone()),
+        non_interactive: true,
+        ..Default::default()
+    });
+    simulator.run(&package).unwrap();
+    assert!(sink.0.lock().unwrap().len() < install(source, 11).len());
+}
+
+#[test]
+fn serialized_package_parses_back_to_same_phases() {
+    for (name, source) in builtin::all() {
+        let package = InstnothParser::new(source.to_string()).parse().unwrap();
+        let reparsed = InstnothParser::new(package.to_instnoth()).parse().unwrap();
+        assert_eq!(reparsed.name, package.name, "{name}");
+        assert_eq!(reparsed.depends.len(), package.depends.len(), "{name}");
+        let phases = |p: &instnoth::parser::Package| p.phases.iter().map(|ph| (ph.name.clone(), ph.commands.clone())).collect::<Vec<_>>();
+        assert_eq!(phases(&reparsed), phases(&package), "{name}");
+    }
+}
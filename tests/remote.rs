@@ -0,0 +1,62 @@
+mod common;
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::process::Command;
+use std::thread;
+
+/// HTTP-сервер на свободном порту: отдаёт файлы examples/ по имени, на остальное — 404
+fn serve_examples() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut request = String::new();
+            BufReader::new(&stream).read_line(&mut request).unwrap();
+            let name = request.split_whitespace().nth(1).unwrap_or("/").trim_start_matches('/').to_string();
+            let response = match std::fs::read(format!("examples/{name}")) {
+                Ok(body) => [format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", body.len()).into_bytes(), body].concat(),
+                Err(_) => b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_vec(),
+            };
+            let _ = stream.write_all(&response);
+        }
+    });
+    base
+}
+
+fn curl_available() -> bool {
+    Command::new("curl").arg("--version").output().is_ok_and(|o| o.status.success())
+}
+
+fn instnoth(args: &[&str]) -> std::process::Output {
+    common::instnoth(&[&["--quick", "--no-summary", "--no-fun", "--seed", "7"][..], args].concat())
+}
+
+#[test]
+fn remote_package_depends_on_sibling_urls() {
+    if !curl_available() {
+        eprintln!("curl не найден, проверка загрузки по адресу пропущена");
+        return;
+    }
+    let base = serve_examples();
+    let output = instnoth(&["--file", &format!("{base}/all.instnoth")]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    // План показывает, откуда пакет: адрес вместо пути, остальное совпадает
+    let remote = String::from_utf8(output.stdout).unwrap().replace(&format!("{base}/"), "examples/");
+    let local = String::from_utf8(instnoth(&["--file", "examples/all.instnoth"]).stdout).unwrap();
+    assert_eq!(remote, local);
+}
+
+#[test]
+fn failed_download_names_the_url() {
+    if !curl_available() {
+        eprintln!("curl не найден, проверка загрузки по адресу пропущена");
+        return;
+    }
+    let url = format!("{}/missing.instnoth", serve_examples());
+    let output = instnoth(&["--file", &url]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains(&format!("Не удалось загрузить {url}: ")), "{stderr}");
+}
@@ -0,0 +1,24 @@
+mod common;
+
+fn run(seed: u64, file: &str) -> Vec<u8> {
+    let output = common::instnoth(&["--quick", "--no-summary", "--seed", &seed.to_string(), "--file", file]);
+    assert!(output.status.success(), "instnoth завершился с ошибкой");
+    output.stdout
+}
+
+#[test]
+fn same_seed_gives_identical_output() {
+    for file in ["examples/linux.instnoth", "examples/devstack.instnoth"] {
+        assert_eq!(
+            run(42, file),
+            run(42, file),
+            "{file}: вывод отличается при одном seed"
+        );
+    }
+}
+
+#[test]
+fn different_seeds_give_different_output() {
+    let file = "examples/linux.instnoth";
+    assert_ne!(run(1, file), run(2, file));
+}
@@ -0,0 +1,22 @@
+mod common;
+
+fn run(stdin: &[u8], files: &[&str]) -> Vec<u8> {
+    let args = [&["--quick", "--no-summary", "--no-fun", "--seed", "7", "--file"][..], files].concat();
+    let output = common::instnoth_with_stdin(&args, stdin);
+    assert!(output.status.success(), "instnoth завершился с ошибкой");
+    output.stdout
+}
+
+#[test]
+fn dash_reads_package_from_stdin() {
+    let source = std::fs::read("examples/python.instnoth").unwrap();
+    assert_eq!(run(&source, &["-"]), run(b"", &["examples/python.instnoth"]));
+}
+
+#[test]
+fn stdin_package_mixes_with_files() {
+    let source = b"package: \"gen\"\nversion: \"0.1\"\nphase \"Setup\" {\n ask \"Continue?\" default=yes\n message \"generated\"\n}\n";
+    let output = String::from_utf8(run(source, &["-", "examples/nodejs.instnoth"])).unwrap();
+    assert!(output.contains("generated"), "{output}");
+    assert!(output.contains("Node.js"), "{output}");
+}